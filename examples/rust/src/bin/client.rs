@@ -18,7 +18,9 @@ use {
     },
     tokio::{fs, sync::Mutex},
     tonic::transport::{channel::ClientTlsConfig, Certificate},
-    yellowstone_grpc_client::{GeyserGrpcClient, GeyserGrpcClientError, Interceptor},
+    yellowstone_grpc_client::{
+        GeyserGrpcClient, GeyserGrpcClientError, Interceptor, LatencyHistogram,
+    },
     yellowstone_grpc_proto::{
         convert_from,
         plugin::filter::message::FilteredUpdate,
@@ -26,12 +28,15 @@ use {
             subscribe_request_filter_accounts_filter::Filter as AccountsFilterOneof,
             subscribe_request_filter_accounts_filter_lamports::Cmp as AccountsFilterLamports,
             subscribe_request_filter_accounts_filter_memcmp::Data as AccountsFilterMemcmpOneof,
-            subscribe_update::UpdateOneof, CommitmentLevel, SubscribeRequest,
-            SubscribeRequestAccountsDataSlice, SubscribeRequestFilterAccounts,
-            SubscribeRequestFilterAccountsFilter, SubscribeRequestFilterAccountsFilterLamports,
-            SubscribeRequestFilterAccountsFilterMemcmp, SubscribeRequestFilterBlocks,
-            SubscribeRequestFilterBlocksMeta, SubscribeRequestFilterEntry,
-            SubscribeRequestFilterSlots, SubscribeRequestFilterTransactions, SubscribeRequestPing,
+            subscribe_update::UpdateOneof, AccountOrigin, CommitmentLevel, OnLag,
+            SlotStatus as SlotStatusProto, SubscribeRequest, SubscribeRequestAccountsDataSlice,
+            SubscribeRequestFilterAccounts, SubscribeRequestFilterAccountsFilter,
+            SubscribeRequestFilterAccountsFilterLamports,
+            SubscribeRequestFilterAccountsFilterLamportsRange,
+            SubscribeRequestFilterAccountsFilterMemcmp, SubscribeRequestFilterAccountsPda,
+            SubscribeRequestFilterBlocks, SubscribeRequestFilterBlocksMeta,
+            SubscribeRequestFilterEntry, SubscribeRequestFilterSlots, SubscribeRequestFilterStats,
+            SubscribeRequestFilterTransactions, SubscribeRequestFilterVotes, SubscribeRequestPing,
             SubscribeUpdateAccountInfo, SubscribeUpdateEntry, SubscribeUpdateTransactionInfo,
         },
         prost::Message,
@@ -45,6 +50,8 @@ type TransactionsStatusFilterMap = HashMap<String, SubscribeRequestFilterTransac
 type EntryFilterMap = HashMap<String, SubscribeRequestFilterEntry>;
 type BlocksFilterMap = HashMap<String, SubscribeRequestFilterBlocks>;
 type BlocksMetaFilterMap = HashMap<String, SubscribeRequestFilterBlocksMeta>;
+type VotesFilterMap = HashMap<String, SubscribeRequestFilterVotes>;
+type StatsFilterMap = HashMap<String, SubscribeRequestFilterStats>;
 
 #[derive(Debug, Clone, Parser)]
 #[clap(author, version, about)]
@@ -205,6 +212,23 @@ enum Action {
         blockhash: String,
     },
     GetVersion,
+    GetSlotStatus,
+    GetAccountsSnapshot {
+        /// Tracked accounts
+        #[clap(long)]
+        pubkey: Vec<String>,
+    },
+    GetTransactionStatus {
+        #[clap(long, short)]
+        signature: String,
+    },
+    GetSlotAncestry {
+        #[clap(long, short)]
+        slot: u64,
+        /// Max number of returned slots, all retained ancestors if zero
+        #[clap(long, short, default_value_t = 0)]
+        limit: u32,
+    },
 }
 
 #[derive(Debug, Clone, clap::Args)]
@@ -228,6 +252,30 @@ struct ActionSubscribe {
     #[clap(long)]
     accounts_owner: Vec<String>,
 
+    /// Exclude accounts by Pubkey
+    #[clap(long)]
+    accounts_account_exclude: Vec<String>,
+
+    /// Exclude accounts by Owner Pubkey
+    #[clap(long)]
+    accounts_owner_exclude: Vec<String>,
+
+    /// Filter by signature of the transaction which wrote the account
+    #[clap(long)]
+    accounts_txn_signature: Vec<String>,
+
+    /// Filter by prefix of base58 encoded account Pubkey
+    #[clap(long)]
+    accounts_account_prefix: Vec<String>,
+
+    /// Filter by prefix of base58 encoded owner Pubkey
+    #[clap(long)]
+    accounts_owner_prefix: Vec<String>,
+
+    /// Filter by origin of the write (transaction, startup, unknown)
+    #[clap(long)]
+    accounts_origins: Vec<String>,
+
     /// Filter by Offset and Data, format: `offset,data in base58`
     #[clap(long)]
     accounts_memcmp: Vec<String>,
@@ -244,6 +292,31 @@ struct ActionSubscribe {
     #[clap(long)]
     accounts_lamports: Vec<String>,
 
+    /// Filter by inclusive range of lamports, format: `min..max` (`min..` / `..max`)
+    #[clap(long)]
+    accounts_lamports_range: Option<String>,
+
+    /// Filter accounts with less lamports than rent-exempt minimum (best-effort)
+    #[clap(long)]
+    accounts_below_rent_exempt: bool,
+
+    /// Filter executable or not executable accounts
+    #[clap(long)]
+    accounts_executable: Option<bool>,
+
+    /// Filter by program derived addresses, format: `program_id,data prefix in base58`
+    #[clap(long)]
+    accounts_pda: Vec<String>,
+
+    /// Skip account updates if newer write in the same slot is known
+    #[clap(long)]
+    accounts_coalesce_per_slot: Option<bool>,
+
+    /// Only the last write per account of finalized slots, with rollback notices
+    /// (requires `--commitment finalized`)
+    #[clap(long)]
+    accounts_finalized_with_proof: Option<bool>,
+
     /// Receive only part of updated data account, format: `offset,size`
     #[clap(long)]
     accounts_data_slice: Vec<String>,
@@ -260,6 +333,10 @@ struct ActionSubscribe {
     #[clap(long)]
     slots_interslot_updates: bool,
 
+    /// Receive only selected slot statuses (processed, confirmed, finalized, first_shred_received, completed, created_bank, dead)
+    #[clap(long)]
+    slots_statuses: Vec<String>,
+
     /// Subscribe on transactions updates
     #[clap(long)]
     transactions: bool,
@@ -276,6 +353,10 @@ struct ActionSubscribe {
     #[clap(long)]
     transactions_signature: Option<String>,
 
+    /// Track transactions with these signatures, every signature is matched once
+    #[clap(long)]
+    transactions_signatures: Vec<String>,
+
     /// Filter included account in transactions
     #[clap(long)]
     transactions_account_include: Vec<String>,
@@ -288,6 +369,18 @@ struct ActionSubscribe {
     #[clap(long)]
     transactions_account_required: Vec<String>,
 
+    /// Send transactions sorted by index once the slot is done
+    #[clap(long)]
+    transactions_ordered_per_slot: bool,
+
+    /// Filter system program transfers with at least this amount of lamports
+    #[clap(long)]
+    transactions_transfer_lamports_min: Option<u64>,
+
+    /// Check transfers of inner instructions
+    #[clap(long)]
+    transactions_transfer_include_inner: bool,
+
     /// Subscribe on transactions_status updates
     #[clap(long)]
     transactions_status: bool,
@@ -316,6 +409,10 @@ struct ActionSubscribe {
     #[clap(long)]
     transactions_status_account_required: Vec<String>,
 
+    /// Send transactions sorted by index once the slot is done for transactions_status
+    #[clap(long)]
+    transactions_status_ordered_per_slot: bool,
+
     #[clap(long)]
     entries: bool,
 
@@ -339,14 +436,62 @@ struct ActionSubscribe {
     #[clap(long)]
     blocks_include_entries: Option<bool>,
 
+    /// Receive diagnostics of failed block reconstruction
+    #[clap(long)]
+    blocks_include_diagnostics: Option<bool>,
+
     /// Subscribe on block meta updates (without transactions)
     #[clap(long)]
     blocks_meta: bool,
 
+    /// Subscribe on votes aggregated per vote account (requires `votes` in the plugin config)
+    #[clap(long)]
+    votes: bool,
+
+    /// Filter vote accounts, all vote accounts if empty
+    #[clap(long)]
+    votes_vote_pubkey: Vec<String>,
+
+    /// Subscribe on account writes statistics per slot (requires `slot_stats` in the plugin config)
+    #[clap(long)]
+    slot_stats: bool,
+
     /// Re-send message from slot
     #[clap(long)]
     from_slot: Option<u64>,
 
+    /// Receive startup accounts (requires `snapshot_plugin_channel_capacity` in the plugin config)
+    #[clap(long)]
+    startup_accounts: Option<bool>,
+
+    /// Include `created_at` to updates
+    #[clap(long)]
+    include_created_at: Option<bool>,
+
+    /// Skip messages on lag instead of disconnect
+    #[clap(long)]
+    on_lag_skip: bool,
+
+    /// Include `confirmed_at_slot` / `finalized_at_slot` to account updates
+    #[clap(long)]
+    include_commitment_slot: Option<bool>,
+
+    /// Include resolved account keys (static and loaded) to transaction updates
+    #[clap(long)]
+    include_resolved_accounts: Option<bool>,
+
+    /// Include stream counters to periodic pings
+    #[clap(long)]
+    include_ping_stats: Option<bool>,
+
+    /// Create accounts and transactions filters from the server-side preset
+    #[clap(long)]
+    preset: Option<String>,
+
+    /// Value of the preset variable, `name=value` (pubkeys of the list are comma-separated)
+    #[clap(long)]
+    preset_param: Vec<String>,
+
     /// Send ping in subscribe request
     #[clap(long)]
     ping: Option<i32>,
@@ -437,6 +582,62 @@ impl Action {
                             _ => anyhow::bail!("invalid lamports"),
                         }
                     }
+                    if args.accounts_lamports_range.is_some() || args.accounts_below_rent_exempt {
+                        let (min, max) = match args.accounts_lamports_range.as_deref() {
+                            Some(range) => {
+                                let Some((min, max)) = range.split_once("..") else {
+                                    anyhow::bail!("invalid lamports range: {range}");
+                                };
+                                let parse = |value: &str| {
+                                    (!value.is_empty())
+                                        .then(|| value.parse::<u64>())
+                                        .transpose()
+                                        .map_err(|_| anyhow::anyhow!("invalid lamports: {value}"))
+                                };
+                                (parse(min)?, parse(max)?)
+                            }
+                            None => (None, None),
+                        };
+                        filters.push(SubscribeRequestFilterAccountsFilter {
+                            filter: Some(AccountsFilterOneof::LamportsRange(
+                                SubscribeRequestFilterAccountsFilterLamportsRange {
+                                    min,
+                                    max,
+                                    below_rent_exempt: Some(args.accounts_below_rent_exempt),
+                                },
+                            )),
+                        });
+                    }
+
+                    let origins = args
+                        .accounts_origins
+                        .iter()
+                        .map(|origin| {
+                            AccountOrigin::from_str_name(&format!(
+                                "ACCOUNT_ORIGIN_{}",
+                                origin.to_uppercase()
+                            ))
+                            .map(|origin| origin as i32)
+                            .ok_or_else(|| anyhow::anyhow!("invalid account origin: {origin}"))
+                        })
+                        .collect::<anyhow::Result<_>>()?;
+
+                    let pda = args
+                        .accounts_pda
+                        .iter()
+                        .map(|pda| {
+                            let (program_id, data_prefix) = pda
+                                .split_once(',')
+                                .ok_or_else(|| anyhow::anyhow!("invalid pda: {pda}"))?;
+                            Ok(SubscribeRequestFilterAccountsPda {
+                                program_id: program_id.to_owned(),
+                                data_prefix: bs58::decode(data_prefix.trim())
+                                    .into_vec()
+                                    .map_err(|_| anyhow::anyhow!("invalid pda data prefix"))?,
+                                ..Default::default()
+                            })
+                        })
+                        .collect::<anyhow::Result<_>>()?;
 
                     accounts.insert(
                         "client".to_owned(),
@@ -445,6 +646,16 @@ impl Action {
                             account: accounts_account,
                             owner: args.accounts_owner.clone(),
                             filters,
+                            coalesce_accounts_per_slot: args.accounts_coalesce_per_slot,
+                            account_exclude: args.accounts_account_exclude.clone(),
+                            owner_exclude: args.accounts_owner_exclude.clone(),
+                            txn_signature: args.accounts_txn_signature.clone(),
+                            account_prefix: args.accounts_account_prefix.clone(),
+                            owner_prefix: args.accounts_owner_prefix.clone(),
+                            origins,
+                            executable: args.accounts_executable,
+                            pda,
+                            finalized_with_proof: args.accounts_finalized_with_proof,
                         },
                     );
                 }
@@ -456,6 +667,18 @@ impl Action {
                         SubscribeRequestFilterSlots {
                             filter_by_commitment: Some(args.slots_filter_by_commitment),
                             interslot_updates: Some(args.slots_interslot_updates),
+                            statuses: args
+                                .slots_statuses
+                                .iter()
+                                .map(|status| {
+                                    SlotStatusProto::from_str_name(&format!(
+                                        "SLOT_{}",
+                                        status.to_uppercase()
+                                    ))
+                                    .map(|status| status as i32)
+                                    .ok_or_else(|| anyhow::anyhow!("invalid slot status: {status}"))
+                                })
+                                .collect::<anyhow::Result<_>>()?,
                         },
                     );
                 }
@@ -471,6 +694,19 @@ impl Action {
                             account_include: args.transactions_account_include.clone(),
                             account_exclude: args.transactions_account_exclude.clone(),
                             account_required: args.transactions_account_required.clone(),
+                            ordered_per_slot: args.transactions_ordered_per_slot.then_some(true),
+                            include_logs: None,
+                            include_token_balances: None,
+                            include_inner_instructions: None,
+                            include_return_data: None,
+                            include_rewards: None,
+                            include_loaded_addresses: None,
+                            transfer_lamports_min: args.transactions_transfer_lamports_min,
+                            transfer_include_inner: args
+                                .transactions_transfer_include_inner
+                                .then_some(true),
+                            transfer_balance_fallback: None,
+                            signatures: args.transactions_signatures.clone(),
                         },
                     );
                 }
@@ -486,6 +722,19 @@ impl Action {
                             account_include: args.transactions_status_account_include.clone(),
                             account_exclude: args.transactions_status_account_exclude.clone(),
                             account_required: args.transactions_status_account_required.clone(),
+                            ordered_per_slot: args
+                                .transactions_status_ordered_per_slot
+                                .then_some(true),
+                            include_logs: None,
+                            include_token_balances: None,
+                            include_inner_instructions: None,
+                            include_return_data: None,
+                            include_rewards: None,
+                            include_loaded_addresses: None,
+                            transfer_lamports_min: None,
+                            transfer_include_inner: None,
+                            transfer_balance_fallback: None,
+                            signatures: vec![],
                         },
                     );
                 }
@@ -504,6 +753,7 @@ impl Action {
                             include_transactions: args.blocks_include_transactions,
                             include_accounts: args.blocks_include_accounts,
                             include_entries: args.blocks_include_entries,
+                            include_diagnostics: args.blocks_include_diagnostics,
                         },
                     );
                 }
@@ -513,6 +763,21 @@ impl Action {
                     blocks_meta.insert("client".to_owned(), SubscribeRequestFilterBlocksMeta {});
                 }
 
+                let mut votes: VotesFilterMap = HashMap::new();
+                if args.votes {
+                    votes.insert(
+                        "client".to_owned(),
+                        SubscribeRequestFilterVotes {
+                            vote_pubkey: args.votes_vote_pubkey.clone(),
+                        },
+                    );
+                }
+
+                let mut stats: StatsFilterMap = HashMap::new();
+                if args.slot_stats {
+                    stats.insert("client".to_owned(), SubscribeRequestFilterStats {});
+                }
+
                 let mut accounts_data_slice = Vec::new();
                 for data_slice in args.accounts_data_slice.iter() {
                     match data_slice.split_once(',') {
@@ -529,6 +794,14 @@ impl Action {
 
                 let ping = args.ping.map(|id| SubscribeRequestPing { id });
 
+                let mut preset_params = HashMap::new();
+                for param in args.preset_param.iter() {
+                    let Some((name, value)) = param.split_once('=') else {
+                        anyhow::bail!("invalid preset_param");
+                    };
+                    preset_params.insert(name.to_owned(), value.to_owned());
+                }
+
                 Some((
                     SubscribeRequest {
                         slots,
@@ -542,6 +815,17 @@ impl Action {
                         accounts_data_slice,
                         ping,
                         from_slot: args.from_slot,
+                        include_created_at: args.include_created_at,
+                        startup_accounts: args.startup_accounts,
+                        on_lag: args.on_lag_skip.then_some(OnLag::Skip as i32),
+                        include_commitment_slot: args.include_commitment_slot,
+                        include_resolved_accounts: args.include_resolved_accounts,
+                        include_ping_stats: args.include_ping_stats,
+                        preset: args.preset.clone(),
+                        preset_params,
+                        seen_signatures_bloom: None,
+                        votes,
+                        stats,
                     },
                     args.resub.unwrap_or(0),
                     args.stats,
@@ -633,6 +917,26 @@ async fn main() -> anyhow::Result<()> {
                     .await
                     .map_err(anyhow::Error::new)
                     .map(|response| info!("response: {response:?}")),
+                Action::GetSlotStatus => client
+                    .get_slot_status()
+                    .await
+                    .map_err(anyhow::Error::new)
+                    .map(|response| info!("response: {response:?}")),
+                Action::GetAccountsSnapshot { pubkey } => client
+                    .get_accounts_snapshot(pubkey.clone(), commitment)
+                    .await
+                    .map_err(anyhow::Error::new)
+                    .map(|response| info!("response: {response:?}")),
+                Action::GetTransactionStatus { signature } => client
+                    .get_transaction_status(signature.clone())
+                    .await
+                    .map_err(anyhow::Error::new)
+                    .map(|response| info!("response: {response:?}")),
+                Action::GetSlotAncestry { slot, limit } => client
+                    .get_slot_ancestry(*slot, *limit)
+                    .await
+                    .map_err(anyhow::Error::new)
+                    .map(|response| info!("response: {response:?}")),
             }
             .map_err(backoff::Error::transient)?;
 
@@ -682,6 +986,8 @@ async fn geyser_subscribe(
     let pb_total = crate_progress_bar(&pb_multi, ProgressBarTpl::Total)?;
     let mut pb_verify_c = verify_encoding.then_some((0, 0));
     let pb_verify = crate_progress_bar(&pb_multi, ProgressBarTpl::Verify)?;
+    let mut pb_latency_h = LatencyHistogram::default();
+    let pb_latency = crate_progress_bar(&pb_multi, ProgressBarTpl::Latency)?;
 
     let (mut subscribe_tx, mut stream) = client.subscribe_with_request(Some(request)).await?;
 
@@ -700,8 +1006,19 @@ async fn geyser_subscribe(
                         Some(UpdateOneof::Entry(_)) => (&mut pb_entries_c, &pb_entries),
                         Some(UpdateOneof::BlockMeta(_)) => (&mut pb_blocks_mt_c, &pb_blocks_mt),
                         Some(UpdateOneof::Block(_)) => (&mut pb_blocks_c, &pb_blocks),
+                        Some(UpdateOneof::BlockDiagnostic(_)) => (&mut pb_blocks_c, &pb_blocks),
+                        Some(UpdateOneof::Votes(_)) => (&mut pb_txs_c, &pb_txs),
+                        Some(UpdateOneof::SlotStats(_)) => (&mut pb_slots_c, &pb_slots),
                         Some(UpdateOneof::Ping(_)) => (&mut pb_pp_c, &pb_pp),
                         Some(UpdateOneof::Pong(_)) => (&mut pb_pp_c, &pb_pp),
+                        Some(UpdateOneof::EndOfStartup(_)) => (&mut pb_accounts_c, &pb_accounts),
+                        Some(UpdateOneof::Lost(_)) => (&mut pb_pp_c, &pb_pp),
+                        Some(UpdateOneof::ReplayDone(_)) => (&mut pb_pp_c, &pb_pp),
+                        Some(UpdateOneof::QuotaWarning(_)) => (&mut pb_pp_c, &pb_pp),
+                        Some(UpdateOneof::StreamInfo(_)) => (&mut pb_pp_c, &pb_pp),
+                        Some(UpdateOneof::Maintenance(_)) => (&mut pb_pp_c, &pb_pp),
+                        Some(UpdateOneof::Rollback(_)) => (&mut pb_accounts_c, &pb_accounts),
+                        Some(UpdateOneof::TransactionSlotDone(_)) => (&mut pb_txs_c, &pb_txs),
                         None => {
                             pb_multi.println("update not found in the message")?;
                             break;
@@ -713,6 +1030,11 @@ async fn geyser_subscribe(
                     pb_total_c += 1;
                     pb_total.set_message(format_thousands(pb_total_c));
                     pb_total.inc(encoded_len);
+                    if pb_latency_h.observe_update(&msg).is_some() {
+                        if let Some(mean) = pb_latency_h.mean() {
+                            pb_latency.set_message(format!("{mean:.2?}"));
+                        }
+                    }
 
                     if let Some((prost_c, ref_c)) = &mut pb_verify_c {
                         let encoded_len_prost0 = msg.encoded_len();
@@ -761,11 +1083,12 @@ async fn geyser_subscribe(
                 }
 
                 let filters = msg.filters;
-                let created_at: SystemTime = msg
+                let created_at = msg
                     .created_at
-                    .ok_or(anyhow::anyhow!("no created_at in the message"))?
-                    .try_into()
-                    .context("failed to parse created_at")?;
+                    .map(SystemTime::try_from)
+                    .transpose()
+                    .context("failed to parse created_at")?
+                    .unwrap_or_else(SystemTime::now);
                 match msg.update_oneof {
                     Some(UpdateOneof::Account(msg)) => {
                         let account = msg
@@ -774,11 +1097,17 @@ async fn geyser_subscribe(
                         let mut value = create_pretty_account(account)?;
                         value["isStartup"] = json!(msg.is_startup);
                         value["slot"] = json!(msg.slot);
+                        if let Some(slot) = msg.confirmed_at_slot {
+                            value["confirmedAtSlot"] = json!(slot);
+                        }
+                        if let Some(slot) = msg.finalized_at_slot {
+                            value["finalizedAtSlot"] = json!(slot);
+                        }
                         print_update("account", created_at, &filters, value);
                     }
                     Some(UpdateOneof::Slot(msg)) => {
-                        let status = CommitmentLevel::try_from(msg.status)
-                            .context("failed to decode commitment")?;
+                        let status = SlotStatusProto::try_from(msg.status)
+                            .context("failed to decode slot status")?;
                         print_update(
                             "slot",
                             created_at,
@@ -863,10 +1192,31 @@ async fn geyser_subscribe(
                                 "accounts": msg.accounts.into_iter().map(create_pretty_account).collect::<Result<Value, _>>()?,
                                 "entriesCount": msg.entries_count,
                                 "entries": msg.entries.into_iter().map(create_pretty_entry).collect::<Result<Value, _>>()?,
+                                "isPartial": msg.is_partial,
+                                "missingTransactionCount": msg.missing_transaction_count,
                             }),
                         );
                     }
-                    Some(UpdateOneof::Ping(_)) => {
+                    Some(UpdateOneof::Ping(msg)) => {
+                        if let Some(stats) = msg.stats {
+                            print_update(
+                                "ping_stats",
+                                created_at,
+                                &filters,
+                                json!({
+                                    "seq": msg.seq,
+                                    "updatesSent": stats.updates_sent,
+                                    "bytesSent": stats.bytes_sent,
+                                    "messagesFiltered": stats.messages_filtered,
+                                    "queueDepth": stats.queue_depth,
+                                    "queueHighWatermark": stats.queue_high_watermark,
+                                    "processedSlot": stats.processed_slot,
+                                    "confirmedSlot": stats.confirmed_slot,
+                                    "finalizedSlot": stats.finalized_slot,
+                                }),
+                            );
+                        }
+
                         // This is necessary to keep load balancers that expect client pings alive. If your load balancer doesn't
                         // require periodic client pings then this is unnecessary
                         subscribe_tx
@@ -877,6 +1227,166 @@ async fn geyser_subscribe(
                             .await?;
                     }
                     Some(UpdateOneof::Pong(_)) => {}
+                    Some(UpdateOneof::EndOfStartup(_)) => {
+                        print_update("end_of_startup", created_at, &filters, json!({}));
+                    }
+                    Some(UpdateOneof::Lost(msg)) => {
+                        print_update(
+                            "lost",
+                            created_at,
+                            &filters,
+                            json!({
+                                "skippedBatches": msg.skipped_batches,
+                                "skippedUpdates": msg.skipped_updates,
+                                "fromSlot": msg.from_slot,
+                                "toSlot": msg.to_slot,
+                            }),
+                        );
+                    }
+                    Some(UpdateOneof::QuotaWarning(msg)) => {
+                        print_update(
+                            "quota_warning",
+                            created_at,
+                            &filters,
+                            json!({
+                                "usedBytes": msg.used_bytes,
+                                "limitBytes": msg.limit_bytes,
+                                "usedMessages": msg.used_messages,
+                                "limitMessages": msg.limit_messages,
+                                "resetsAt": msg.resets_at.map(|resets_at| resets_at.seconds),
+                            }),
+                        );
+                    }
+                    Some(UpdateOneof::Maintenance(msg)) => {
+                        print_update(
+                            "maintenance",
+                            created_at,
+                            &filters,
+                            json!({
+                                "reason": msg.reason,
+                                "expectedAtSlot": msg.expected_at_slot,
+                                "drainDeadlineSecs": msg.drain_deadline_secs,
+                                "announcedAt": msg.announced_at,
+                                "cancelled": msg.cancelled,
+                            }),
+                        );
+                    }
+                    Some(UpdateOneof::Rollback(msg)) => {
+                        print_update(
+                            "rollback",
+                            created_at,
+                            &filters,
+                            json!({
+                                "slot": msg.slot,
+                                "finalizedSlot": msg.finalized_slot,
+                                "pubkeys": msg
+                                    .pubkeys
+                                    .iter()
+                                    .map(|pubkey| bs58::encode(pubkey).into_string())
+                                    .collect::<Vec<_>>(),
+                            }),
+                        );
+                    }
+                    Some(UpdateOneof::StreamInfo(msg)) => {
+                        print_update(
+                            "stream_info",
+                            created_at,
+                            &filters,
+                            json!({
+                                "processed": msg.processed.map(|slot| slot.slot),
+                                "confirmed": msg.confirmed.map(|slot| slot.slot),
+                                "finalized": msg.finalized.map(|slot| slot.slot),
+                                "version": msg.version,
+                                "commitment": msg.commitment,
+                                "accounts": msg.accounts,
+                                "slots": msg.slots,
+                                "transactions": msg.transactions,
+                                "transactionsStatus": msg.transactions_status,
+                                "entries": msg.entries,
+                                "blocks": msg.blocks,
+                                "blocksMeta": msg.blocks_meta,
+                                "votes": msg.votes,
+                                "stats": msg.stats,
+                            }),
+                        );
+                    }
+                    Some(UpdateOneof::BlockDiagnostic(msg)) => {
+                        print_update(
+                            "block_diagnostic",
+                            created_at,
+                            &filters,
+                            json!({
+                                "slot": msg.slot,
+                                "isPartial": msg.is_partial,
+                                "blockMetaMissing": msg.block_meta_missing,
+                                "executedTransactionCount": msg.executed_transaction_count,
+                                "receivedTransactionCount": msg.received_transaction_count,
+                                "entriesCount": msg.entries_count,
+                                "receivedEntriesCount": msg.received_entries_count,
+                                "missingTransactionIndexes": msg.missing_transaction_indexes,
+                            }),
+                        );
+                    }
+                    Some(UpdateOneof::Votes(msg)) => {
+                        print_update(
+                            "votes",
+                            created_at,
+                            &filters,
+                            json!({
+                                "slot": msg.slot,
+                                "fromSlot": msg.from_slot,
+                                "toSlot": msg.to_slot,
+                                "entries": msg.entries.iter().map(|entry| json!({
+                                    "votePubkey": bs58::encode(&entry.vote_pubkey).into_string(),
+                                    "votedOnSlot": entry.voted_on_slot,
+                                    "latencySlots": entry.latency_slots,
+                                    "count": entry.count,
+                                })).collect::<Vec<_>>(),
+                            }),
+                        );
+                    }
+                    Some(UpdateOneof::SlotStats(msg)) => {
+                        print_update(
+                            "slot_stats",
+                            created_at,
+                            &filters,
+                            json!({
+                                "slot": msg.slot,
+                                "accountUpdates": msg.account_updates,
+                                "uniqueAccounts": msg.unique_accounts,
+                                "dataBytes": msg.data_bytes,
+                                "topOwners": msg.top_owners.iter().map(|owner| json!({
+                                    "owner": bs58::encode(&owner.owner).into_string(),
+                                    "accountUpdates": owner.account_updates,
+                                    "dataBytes": owner.data_bytes,
+                                })).collect::<Vec<_>>(),
+                            }),
+                        );
+                    }
+                    Some(UpdateOneof::ReplayDone(msg)) => {
+                        print_update(
+                            "replay_done",
+                            created_at,
+                            &filters,
+                            json!({
+                                "fromSlot": msg.from_slot,
+                                "toSlot": msg.to_slot,
+                                "replayedUpdates": msg.replayed_updates,
+                            }),
+                        );
+                    }
+                    Some(UpdateOneof::TransactionSlotDone(msg)) => {
+                        print_update(
+                            "transaction_slot_done",
+                            created_at,
+                            &filters,
+                            json!({
+                                "slot": msg.slot,
+                                "sentCount": msg.sent_count,
+                                "bestEffort": msg.best_effort,
+                            }),
+                        );
+                    }
                     None => {
                         error!("update not found in the message");
                         break;
@@ -908,6 +1418,17 @@ async fn geyser_subscribe(
                     accounts_data_slice: Vec::default(),
                     ping: None,
                     from_slot: None,
+                    include_created_at: None,
+                    startup_accounts: None,
+                    on_lag: None,
+                    include_commitment_slot: None,
+                    include_resolved_accounts: None,
+                    include_ping_stats: None,
+                    preset: None,
+                    preset_params: HashMap::default(),
+                    seen_signatures_bloom: None,
+                    votes: HashMap::default(),
+                    stats: HashMap::default(),
                 })
                 .await
                 .map_err(GeyserGrpcClientError::SubscribeSendError)?;
@@ -922,6 +1443,7 @@ enum ProgressBarTpl {
     Msg(&'static str),
     Total,
     Verify,
+    Latency,
 }
 
 fn crate_progress_bar(
@@ -939,6 +1461,7 @@ fn crate_progress_bar(
         ProgressBarTpl::Verify => {
             "{spinner} verify: {msg} (elapsed time, compare to prost)".to_owned()
         }
+        ProgressBarTpl::Latency => "{spinner} latency: {msg} (mean, since created_at)".to_owned(),
     };
     pb.set_style(ProgressStyle::with_template(&tpl)?);
     Ok(pb)
@@ -966,13 +1489,26 @@ fn create_pretty_account(account: SubscribeUpdateAccountInfo) -> anyhow::Result<
         "data": hex::encode(account.data),
         "writeVersion": account.write_version,
         "txnSignature": account.txn_signature.map(|sig| bs58::encode(sig).into_string()),
+        "origin": account.origin.and_then(|origin| AccountOrigin::try_from(origin).ok()).map(|origin| origin.as_str_name()),
     }))
 }
 
 fn create_pretty_transaction(tx: SubscribeUpdateTransactionInfo) -> anyhow::Result<Value> {
+    let resolved_accounts = tx
+        .resolved_accounts
+        .iter()
+        .map(|account| {
+            Ok(json!({
+                "pubkey": Pubkey::try_from(account.pubkey.as_slice()).map_err(|_| anyhow::anyhow!("invalid resolved account pubkey"))?.to_string(),
+                "isSigner": account.is_signer,
+                "isWritable": account.is_writable,
+            }))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
     Ok(json!({
         "signature": Signature::try_from(tx.signature.as_slice()).context("invalid signature")?.to_string(),
         "isVote": tx.is_vote,
+        "resolvedAccounts": resolved_accounts,
         "tx": convert_from::create_tx_with_meta(tx)
             .map_err(|error| anyhow::anyhow!(error))
             .context("invalid tx with meta")?
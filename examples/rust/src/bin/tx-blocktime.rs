@@ -104,6 +104,17 @@ async fn main() -> anyhow::Result<()> {
                 account_include: args.account_include,
                 account_exclude: args.account_exclude,
                 account_required: args.account_required,
+                ordered_per_slot: None,
+                include_logs: None,
+                include_token_balances: None,
+                include_inner_instructions: None,
+                include_return_data: None,
+                include_rewards: None,
+                include_loaded_addresses: None,
+                transfer_lamports_min: None,
+                transfer_include_inner: None,
+                transfer_balance_fallback: None,
+                signatures: vec![],
             } },
             entry: HashMap::new(),
             blocks: HashMap::new(),
@@ -112,6 +123,17 @@ async fn main() -> anyhow::Result<()> {
             accounts_data_slice: vec![],
             ping: None,
             from_slot: None,
+            include_created_at: None,
+            startup_accounts: None,
+            on_lag: None,
+            include_commitment_slot: None,
+            include_resolved_accounts: None,
+            include_ping_stats: None,
+            preset: None,
+            preset_params: HashMap::new(),
+            seen_signatures_bloom: None,
+            votes: HashMap::new(),
+            stats: HashMap::new(),
         })
         .await?;
 
@@ -0,0 +1,254 @@
+//! Blocking client for synchronous applications, requests are executed on the runtime owned by
+//! the client.
+
+use {
+    crate::{
+        GeyserGrpcBuilder, GeyserGrpcBuilderError, GeyserGrpcClient, GeyserGrpcClientError,
+        GeyserGrpcClientResult, Interceptor,
+    },
+    futures::stream::StreamExt,
+    std::{io, sync::Arc},
+    tokio::{
+        runtime::{Builder, Runtime},
+        sync::mpsc,
+        task::JoinHandle,
+    },
+    yellowstone_grpc_proto::prelude::{
+        CommitmentLevel, GetLatestBlockhashResponse, GetSlotResponse, GetVersionResponse,
+        IsBlockhashValidResponse, SubscribeRequest, SubscribeUpdate,
+    },
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum BlockingGeyserClientError {
+    #[error("Failed to create runtime: {0}")]
+    Runtime(#[from] io::Error),
+    #[error(transparent)]
+    Builder(#[from] GeyserGrpcBuilderError),
+}
+
+pub type BlockingGeyserClientResult<T> = Result<T, BlockingGeyserClientError>;
+
+/// Client for synchronous code
+///
+/// # Panics
+///
+/// Methods block the current thread and panic if called within an asynchronous execution
+/// context, use [`GeyserGrpcClient`] there.
+pub struct BlockingGeyserClient<F> {
+    runtime: Arc<Runtime>,
+    client: GeyserGrpcClient<F>,
+    buffer_size: usize,
+}
+
+impl BlockingGeyserClient<()> {
+    pub const BUFFER_SIZE_DEFAULT: usize = 1_024;
+
+    /// Create runtime with one worker thread and connect to the endpoint
+    pub fn connect(
+        builder: GeyserGrpcBuilder,
+    ) -> BlockingGeyserClientResult<BlockingGeyserClient<impl Interceptor>> {
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("geyserGrpcBlocking")
+            .enable_all()
+            .build()?;
+        let client = runtime.block_on(builder.connect())?;
+        Ok(BlockingGeyserClient {
+            runtime: Arc::new(runtime),
+            client,
+            buffer_size: Self::BUFFER_SIZE_DEFAULT,
+        })
+    }
+}
+
+impl<F: Interceptor + 'static> BlockingGeyserClient<F> {
+    /// Max number of received updates not consumed by the iterator
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size.max(1);
+        self
+    }
+
+    pub fn subscribe_iter(
+        &mut self,
+        request: SubscribeRequest,
+    ) -> GeyserGrpcClientResult<SubscribeIter> {
+        let (subscribe_tx, mut stream) = self
+            .runtime
+            .block_on(self.client.subscribe_with_request(Some(request)))?;
+
+        let (updates_tx, updates_rx) = mpsc::channel(self.buffer_size);
+        let task = self.runtime.spawn(async move {
+            // request stream is closed on drop
+            let _subscribe_tx = subscribe_tx;
+            while let Some(update) = stream.next().await {
+                let is_err = update.is_err();
+                if updates_tx.send(update.map_err(Into::into)).await.is_err() || is_err {
+                    break;
+                }
+            }
+        });
+
+        Ok(SubscribeIter {
+            updates_rx,
+            task,
+            _runtime: Arc::clone(&self.runtime),
+        })
+    }
+
+    pub fn get_latest_blockhash(
+        &mut self,
+        commitment: Option<CommitmentLevel>,
+    ) -> GeyserGrpcClientResult<GetLatestBlockhashResponse> {
+        self.runtime
+            .block_on(self.client.get_latest_blockhash(commitment))
+    }
+
+    pub fn get_slot(
+        &mut self,
+        commitment: Option<CommitmentLevel>,
+    ) -> GeyserGrpcClientResult<GetSlotResponse> {
+        self.runtime.block_on(self.client.get_slot(commitment))
+    }
+
+    pub fn is_blockhash_valid(
+        &mut self,
+        blockhash: String,
+        commitment: Option<CommitmentLevel>,
+    ) -> GeyserGrpcClientResult<IsBlockhashValidResponse> {
+        self.runtime
+            .block_on(self.client.is_blockhash_valid(blockhash, commitment))
+    }
+
+    pub fn get_version(&mut self) -> GeyserGrpcClientResult<GetVersionResponse> {
+        self.runtime.block_on(self.client.get_version())
+    }
+}
+
+/// Updates of the subscription, the stream is cancelled on drop
+pub struct SubscribeIter {
+    updates_rx: mpsc::Receiver<Result<SubscribeUpdate, GeyserGrpcClientError>>,
+    task: JoinHandle<()>,
+    // keep runtime alive while the stream is active
+    _runtime: Arc<Runtime>,
+}
+
+impl Iterator for SubscribeIter {
+    type Item = Result<SubscribeUpdate, GeyserGrpcClientError>;
+
+    /// Block until the next update is received
+    ///
+    /// # Panics
+    ///
+    /// Panics if called within an asynchronous execution context.
+    fn next(&mut self) -> Option<Self::Item> {
+        self.updates_rx.blocking_recv()
+    }
+}
+
+impl Drop for SubscribeIter {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::BlockingGeyserClient,
+        crate::{
+            mock::{MockGeyser, MOCK_SLOT},
+            GeyserGrpcClient,
+        },
+        std::{net::SocketAddr, sync::mpsc as std_mpsc, time::Duration},
+        tokio::runtime::Runtime,
+        yellowstone_grpc_proto::prelude::{
+            subscribe_update::UpdateOneof, SubscribeRequest, SubscribeUpdate, SubscribeUpdateSlot,
+        },
+    };
+
+    const MOCK_UPDATES: u64 = 3;
+
+    /// Mock server sends `MOCK_UPDATES` slot updates and keeps the stream open
+    fn spawn_mock_server() -> (Runtime, SocketAddr, std_mpsc::Receiver<()>) {
+        let runtime = Runtime::new().expect("runtime");
+        let (cancelled_tx, cancelled_rx) = std_mpsc::channel();
+        let mock = MockGeyser {
+            updates: (0..MOCK_UPDATES)
+                .map(|slot| {
+                    let update = SubscribeUpdate {
+                        update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                            slot,
+                            ..Default::default()
+                        })),
+                        ..Default::default()
+                    };
+                    (Duration::ZERO, update)
+                })
+                .collect(),
+            keep_open: true,
+            cancelled_tx: Some(cancelled_tx),
+            ..Default::default()
+        };
+        let addr = runtime.block_on(mock.spawn());
+        (runtime, addr, cancelled_rx)
+    }
+
+    fn connect(addr: SocketAddr) -> BlockingGeyserClient<impl crate::Interceptor> {
+        let builder =
+            GeyserGrpcClient::build_from_shared(format!("http://{addr}")).expect("valid endpoint");
+        BlockingGeyserClient::connect(builder).expect("connected")
+    }
+
+    #[test]
+    fn test_blocking_unary() {
+        let (_runtime, addr, _cancelled_rx) = spawn_mock_server();
+        let mut client = connect(addr);
+
+        assert_eq!(client.get_slot(None).expect("get_slot").slot, MOCK_SLOT);
+        assert_eq!(client.get_version().expect("get_version").version, "mock");
+        let blockhash = client
+            .get_latest_blockhash(None)
+            .expect("get_latest_blockhash")
+            .blockhash;
+        assert!(
+            client
+                .is_blockhash_valid(blockhash, None)
+                .expect("is_blockhash_valid")
+                .valid
+        );
+    }
+
+    #[test]
+    fn test_blocking_subscribe_iter() {
+        let (_runtime, addr, cancelled_rx) = spawn_mock_server();
+        let mut client = connect(addr).buffer_size(1);
+
+        let mut updates = client
+            .subscribe_iter(SubscribeRequest::default())
+            .expect("subscribed");
+        for expected in 0..MOCK_UPDATES {
+            let update = updates.next().expect("stream is open").expect("update");
+            let Some(UpdateOneof::Slot(msg)) = update.update_oneof else {
+                panic!("expected slot update");
+            };
+            assert_eq!(msg.slot, expected);
+        }
+
+        // stream is cancelled on drop even if the client is still alive
+        drop(updates);
+        cancelled_rx
+            .recv_timeout(Duration::from_secs(5))
+            .expect("stream cancelled");
+
+        // runtime is released with the last iterator
+        let updates = client
+            .subscribe_iter(SubscribeRequest::default())
+            .expect("subscribed");
+        drop(client);
+        drop(updates);
+        cancelled_rx
+            .recv_timeout(Duration::from_secs(5))
+            .expect("stream cancelled");
+    }
+}
@@ -0,0 +1,791 @@
+//! Subscription buffered on the client side, the gRPC stream is drained into a bounded memory
+//! queue and updates over the queue capacity are spilled to a file, so a stalled consumer does
+//! not backpressure the connection and the server does not disconnect it as a lagged client.
+
+use {
+    futures::stream::{Stream, StreamExt},
+    std::{
+        collections::VecDeque,
+        fmt,
+        fs::{self, File, OpenOptions},
+        io::{self, Read, Seek, SeekFrom, Write},
+        path::{Path, PathBuf},
+        pin::Pin,
+        process,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc as std_mpsc, Arc, Mutex, MutexGuard,
+        },
+        task::{Context, Poll, Waker},
+        thread,
+    },
+    tokio::task::JoinHandle,
+    tonic::Status,
+    yellowstone_grpc_proto::{prelude::SubscribeUpdate, prost::Message},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum BufferedSubscriptionError {
+    #[error("gRPC status: {0}")]
+    TonicStatus(#[from] Status),
+    #[error("buffer overflow, {dropped} updates dropped")]
+    Overflow { dropped: u64 },
+    #[error("spill file error: {0}")]
+    Io(#[from] io::Error),
+}
+
+pub type BufferedSubscriptionResult<T> = Result<T, BufferedSubscriptionError>;
+
+/// What to do once both memory queue and spill file are full
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BufferedOverflowPolicy {
+    /// Stop draining and drop the gRPC stream, buffered updates are delivered before
+    /// [`BufferedSubscriptionError::Overflow`]
+    #[default]
+    Disconnect,
+    /// Drop the oldest buffered updates, number of dropped updates is reported with
+    /// [`BufferedSubscriptionError::Overflow`] before the next update, the stream continues
+    DropOldest,
+}
+
+pub type OnBufferedStatsCallback = Arc<dyn Fn(&BufferedSubscriptionStats) + Send + Sync>;
+
+#[derive(Clone)]
+pub struct BufferedSubscriptionConfig {
+    /// Updates kept in memory before spilling to the file
+    pub memory_capacity: usize,
+    /// Max size of encoded updates in the spill file, `0` disables spilling
+    pub disk_capacity: u64,
+    /// Directory of the spill file, system temporary directory by default
+    pub disk_dir: Option<PathBuf>,
+    pub overflow_policy: BufferedOverflowPolicy,
+    /// Called on every change of buffered updates, e.g. to export queue depth and spilled bytes
+    pub on_stats: Option<OnBufferedStatsCallback>,
+}
+
+impl fmt::Debug for BufferedSubscriptionConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferedSubscriptionConfig")
+            .field("memory_capacity", &self.memory_capacity)
+            .field("disk_capacity", &self.disk_capacity)
+            .field("disk_dir", &self.disk_dir)
+            .field("overflow_policy", &self.overflow_policy)
+            .field("on_stats", &self.on_stats.is_some())
+            .finish()
+    }
+}
+
+impl Default for BufferedSubscriptionConfig {
+    fn default() -> Self {
+        Self {
+            memory_capacity: 16_384,
+            disk_capacity: 1024 * 1024 * 1024,
+            disk_dir: None,
+            overflow_policy: BufferedOverflowPolicy::default(),
+            on_stats: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BufferedSubscriptionStats {
+    /// Updates in the memory queue
+    pub memory_len: usize,
+    /// Updates in the spill file
+    pub spilled_len: usize,
+    /// Size of encoded updates in the spill file
+    pub spilled_bytes: u64,
+    /// Total number of dropped updates
+    pub dropped: u64,
+}
+
+/// Ring of length prefixed records in a file of fixed max size, removed on drop
+#[derive(Debug)]
+struct SpillRing {
+    file: File,
+    path: PathBuf,
+    capacity: u64,
+    head: u64,
+    len: u64,
+    records: usize,
+}
+
+impl Drop for SpillRing {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl SpillRing {
+    const RECORD_HEADER_SIZE: u64 = 4;
+
+    fn create(dir: &Path, capacity: u64) -> io::Result<Self> {
+        static ID: AtomicUsize = AtomicUsize::new(0);
+        let path = dir.join(format!(
+            "yellowstone-grpc-buffer-{}-{}",
+            process::id(),
+            ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(Self {
+            file,
+            path,
+            capacity,
+            head: 0,
+            len: 0,
+            records: 0,
+        })
+    }
+
+    const fn is_empty(&self) -> bool {
+        self.records == 0
+    }
+
+    /// Returns `false` if there is not enough space for the record
+    fn push(&mut self, data: &[u8]) -> io::Result<bool> {
+        let size = Self::RECORD_HEADER_SIZE + data.len() as u64;
+        if size > self.capacity - self.len {
+            return Ok(false);
+        }
+
+        let tail = (self.head + self.len) % self.capacity;
+        self.write_at(tail, &(data.len() as u32).to_le_bytes())?;
+        self.write_at((tail + Self::RECORD_HEADER_SIZE) % self.capacity, data)?;
+        self.len += size;
+        self.records += 1;
+        Ok(true)
+    }
+
+    fn pop(&mut self) -> io::Result<Option<Vec<u8>>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+
+        let mut header = [0; Self::RECORD_HEADER_SIZE as usize];
+        self.read_at(self.head, &mut header)?;
+        let mut data = vec![0; u32::from_le_bytes(header) as usize];
+        self.read_at(
+            (self.head + Self::RECORD_HEADER_SIZE) % self.capacity,
+            &mut data,
+        )?;
+
+        let size = Self::RECORD_HEADER_SIZE + data.len() as u64;
+        self.head = (self.head + size) % self.capacity;
+        self.len -= size;
+        self.records -= 1;
+        if self.is_empty() {
+            self.head = 0;
+        }
+        Ok(Some(data))
+    }
+
+    // data is split at the end of the ring
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> io::Result<()> {
+        let (first, second) = data.split_at(data.len().min((self.capacity - offset) as usize));
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(first)?;
+        if !second.is_empty() {
+            self.file.seek(SeekFrom::Start(0))?;
+            self.file.write_all(second)?;
+        }
+        Ok(())
+    }
+
+    fn read_at(&mut self, offset: u64, data: &mut [u8]) -> io::Result<()> {
+        let len = data.len().min((self.capacity - offset) as usize);
+        let (first, second) = data.split_at_mut(len);
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(first)?;
+        if !second.is_empty() {
+            self.file.seek(SeekFrom::Start(0))?;
+            self.file.read_exact(second)?;
+        }
+        Ok(())
+    }
+}
+
+enum SpillCommand {
+    /// Encoded update, the record is already counted in `spilled` of [`BufferState`]
+    Push(Vec<u8>),
+    /// Records were dropped or reserved room in the memory queue
+    Refill,
+}
+
+/// Buffered updates, every update in memory is older than any update in the spill file.
+///
+/// Spill file is accessed only by the spill thread, the state keeps sizes of spilled records, so
+/// the drain task and the consumer never do file IO or wait for it under the lock.
+#[derive(Debug)]
+struct BufferState {
+    memory: VecDeque<SubscribeUpdate>,
+    memory_capacity: usize,
+    // `None` if spilling is disabled or the spill file is broken
+    spill_tx: Option<std_mpsc::Sender<SpillCommand>>,
+    disk_capacity: u64,
+    // sizes of spilled records in order, including records not written by the spill thread yet
+    spilled: VecDeque<u64>,
+    spilled_bytes: u64,
+    // first spilled records with reserved room in the memory queue, moved by the spill thread
+    in_transit: usize,
+    in_transit_bytes: u64,
+    // oldest records dropped from `spilled`, still in the spill file
+    disk_dropped: usize,
+    overflow_policy: BufferedOverflowPolicy,
+    dropped: u64,
+    // dropped updates not reported to the consumer yet
+    dropped_pending: u64,
+    // set once the gRPC stream is finished, `None` inside if it's finished without error
+    end: Option<Option<BufferedSubscriptionError>>,
+    waker: Option<Waker>,
+}
+
+impl BufferState {
+    fn stats(&self) -> BufferedSubscriptionStats {
+        BufferedSubscriptionStats {
+            memory_len: self.memory.len(),
+            spilled_len: self.spilled.len(),
+            spilled_bytes: self.spilled_bytes,
+            dropped: self.dropped,
+        }
+    }
+
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn push(&mut self, update: SubscribeUpdate) -> BufferedSubscriptionResult<()> {
+        let mut encoded = None;
+        loop {
+            if self.spilled.is_empty() && self.memory.len() < self.memory_capacity {
+                self.memory.push_back(update);
+                return Ok(());
+            }
+
+            if let Some(spill_tx) = self.spill_tx.as_ref() {
+                let data = encoded.get_or_insert_with(|| update.encode_to_vec());
+                let size = SpillRing::RECORD_HEADER_SIZE + data.len() as u64;
+                if size <= self.disk_capacity - (self.spilled_bytes - self.in_transit_bytes) {
+                    // sent under the lock, so records are written in the order of `spilled`
+                    let _ = spill_tx.send(SpillCommand::Push(std::mem::take(data)));
+                    self.spilled.push_back(size);
+                    self.spilled_bytes += size;
+                    return Ok(());
+                }
+            }
+
+            match self.overflow_policy {
+                BufferedOverflowPolicy::Disconnect => {
+                    self.dropped += 1;
+                    return Err(BufferedSubscriptionError::Overflow { dropped: 1 });
+                }
+                BufferedOverflowPolicy::DropOldest => {
+                    self.dropped += 1;
+                    self.dropped_pending += 1;
+                    if !self.drop_oldest() {
+                        // update is larger than the spill file and memory queue is disabled
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns `false` if nothing is buffered
+    fn drop_oldest(&mut self) -> bool {
+        if self.memory.pop_front().is_none() {
+            if !self.pop_spilled() {
+                return false;
+            }
+            self.disk_dropped += 1;
+        }
+        self.refill();
+        true
+    }
+
+    fn pop_spilled(&mut self) -> bool {
+        let Some(size) = self.spilled.pop_front() else {
+            return false;
+        };
+        self.spilled_bytes -= size;
+        if self.in_transit > 0 {
+            self.in_transit -= 1;
+            self.in_transit_bytes -= size;
+        }
+        true
+    }
+
+    /// Reserve room in the memory queue for spilled updates, moved by the spill thread
+    fn refill(&mut self) {
+        let in_transit = self.in_transit;
+        // with disabled memory queue spilled updates are moved one by one
+        while self.memory.len() + self.in_transit < self.memory_capacity.max(1) {
+            let Some(size) = self.spilled.get(self.in_transit) else {
+                break;
+            };
+            self.in_transit_bytes += size;
+            self.in_transit += 1;
+        }
+        if self.in_transit > in_transit {
+            if let Some(spill_tx) = self.spill_tx.as_ref() {
+                let _ = spill_tx.send(SpillCommand::Refill);
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Option<BufferedSubscriptionResult<SubscribeUpdate>> {
+        if self.dropped_pending > 0 {
+            let dropped = std::mem::take(&mut self.dropped_pending);
+            return Some(Err(BufferedSubscriptionError::Overflow { dropped }));
+        }
+
+        let update = self.memory.pop_front();
+        self.refill();
+        update.map(Ok)
+    }
+
+    /// Spill file is broken, nothing can be delivered after the error
+    fn fail(&mut self, error: BufferedSubscriptionError) {
+        self.memory.clear();
+        self.spill_tx = None;
+        self.spilled.clear();
+        self.spilled_bytes = 0;
+        self.in_transit = 0;
+        self.in_transit_bytes = 0;
+        self.disk_dropped = 0;
+        if !matches!(self.end, Some(Some(_))) {
+            self.end = Some(Some(error));
+        }
+    }
+}
+
+struct BufferShared {
+    state: Mutex<BufferState>,
+    on_stats: Option<OnBufferedStatsCallback>,
+}
+
+impl fmt::Debug for BufferShared {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferShared")
+            .field("state", &self.state)
+            .field("on_stats", &self.on_stats.is_some())
+            .finish()
+    }
+}
+
+impl BufferShared {
+    fn lock(&self) -> MutexGuard<'_, BufferState> {
+        self.state.lock().expect("unpoisoned mutex")
+    }
+
+    fn report(&self, state: &BufferState) {
+        if let Some(on_stats) = &self.on_stats {
+            on_stats(&state.stats());
+        }
+    }
+
+    /// Loop of the spill thread, finished once the sender is dropped
+    fn spill_loop(&self, mut ring: SpillRing, spill_rx: std_mpsc::Receiver<SpillCommand>) {
+        while let Ok(command) = spill_rx.recv() {
+            // dropped and moved records free space in the file for the pushed one
+            let result = self.spill_refill(&mut ring).and_then(|()| match command {
+                SpillCommand::Push(data) if ring.push(&data)? => Ok(()),
+                // space is reserved before the record is sent
+                SpillCommand::Push(_data) => Err(io::Error::other("spill file overflow").into()),
+                SpillCommand::Refill => Ok(()),
+            });
+            if let Err(error) = result {
+                let mut state = self.lock();
+                state.fail(error);
+                self.report(&state);
+                state.wake();
+                break;
+            }
+        }
+    }
+
+    /// Move updates with reserved room to the memory queue and skip dropped records
+    fn spill_refill(&self, ring: &mut SpillRing) -> BufferedSubscriptionResult<()> {
+        loop {
+            {
+                let state = self.lock();
+                if state.disk_dropped == 0 && state.in_transit == 0 {
+                    return Ok(());
+                }
+            }
+
+            // record can be still in the queue of commands
+            let Some(data) = ring.pop()? else {
+                return Ok(());
+            };
+            let update = SubscribeUpdate::decode(data.as_slice())
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+            let mut state = self.lock();
+            // record is dropped by the drain task while it was read
+            if state.disk_dropped > 0 {
+                state.disk_dropped -= 1;
+                continue;
+            }
+            state.pop_spilled();
+            state.memory.push_back(update);
+            self.report(&state);
+            state.wake();
+        }
+    }
+}
+
+/// Updates of the subscription, drained from the gRPC stream by a background task.
+///
+/// Updates are delivered in the order of the gRPC stream. The gRPC stream is dropped once
+/// [`BufferedSubscription`] is dropped. Spill file is written and read by a dedicated thread.
+#[derive(Debug)]
+pub struct BufferedSubscription {
+    shared: Arc<BufferShared>,
+    task: JoinHandle<()>,
+}
+
+impl Drop for BufferedSubscription {
+    fn drop(&mut self) {
+        self.task.abort();
+        // spill thread is finished and the spill file is removed
+        self.shared.lock().spill_tx = None;
+    }
+}
+
+impl BufferedSubscription {
+    /// Spawn a task draining the stream, should be called within Tokio runtime
+    pub fn new<S>(stream: S, config: BufferedSubscriptionConfig) -> BufferedSubscriptionResult<Self>
+    where
+        S: Stream<Item = Result<SubscribeUpdate, Status>> + Send + Unpin + 'static,
+    {
+        let disk = if config.disk_capacity > 0 {
+            let dir = config.disk_dir.unwrap_or_else(std::env::temp_dir);
+            Some(SpillRing::create(&dir, config.disk_capacity)?)
+        } else {
+            None
+        };
+        let (spill_tx, spill_rx) = std_mpsc::channel();
+        let shared = Arc::new(BufferShared {
+            state: Mutex::new(BufferState {
+                memory: VecDeque::with_capacity(config.memory_capacity.min(1_024)),
+                memory_capacity: config.memory_capacity,
+                spill_tx: disk.is_some().then_some(spill_tx),
+                disk_capacity: config.disk_capacity,
+                spilled: VecDeque::new(),
+                spilled_bytes: 0,
+                in_transit: 0,
+                in_transit_bytes: 0,
+                disk_dropped: 0,
+                overflow_policy: config.overflow_policy,
+                dropped: 0,
+                dropped_pending: 0,
+                end: None,
+                waker: None,
+            }),
+            on_stats: config.on_stats,
+        });
+        if let Some(ring) = disk {
+            let shared = Arc::clone(&shared);
+            thread::Builder::new()
+                .name("geyserGrpcSpill".to_owned())
+                .spawn(move || shared.spill_loop(ring, spill_rx))?;
+        }
+        let task = tokio::spawn(Self::drain(stream, Arc::clone(&shared)));
+        Ok(Self { shared, task })
+    }
+
+    async fn drain<S>(mut stream: S, shared: Arc<BufferShared>)
+    where
+        S: Stream<Item = Result<SubscribeUpdate, Status>> + Unpin,
+    {
+        loop {
+            let item = stream.next().await;
+            let mut state = shared.lock();
+            if state.end.is_some() {
+                break;
+            }
+            let result = match item {
+                Some(Ok(update)) => state.push(update).map(|()| true),
+                Some(Err(status)) => Err(status.into()),
+                None => Ok(false),
+            };
+            let is_finished = match result {
+                Ok(true) => false,
+                Ok(false) => {
+                    state.end = Some(None);
+                    true
+                }
+                Err(error) => {
+                    state.end = Some(Some(error));
+                    true
+                }
+            };
+            shared.report(&state);
+            state.wake();
+            if is_finished {
+                break;
+            }
+        }
+    }
+
+    pub fn stats(&self) -> BufferedSubscriptionStats {
+        self.shared.lock().stats()
+    }
+}
+
+impl Stream for BufferedSubscription {
+    type Item = BufferedSubscriptionResult<SubscribeUpdate>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut state = self.shared.lock();
+        if let Some(item) = state.pop() {
+            self.shared.report(&state);
+            return Poll::Ready(Some(item));
+        }
+        // spilled updates are moved to the memory queue by the spill thread
+        if !state.spilled.is_empty() {
+            state.waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        match state.end.as_mut() {
+            Some(end) => Poll::Ready(end.take().map(Err)),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{
+            BufferedOverflowPolicy, BufferedSubscription, BufferedSubscriptionConfig,
+            BufferedSubscriptionError, BufferedSubscriptionStats, SpillRing,
+        },
+        futures::{channel::mpsc, stream::StreamExt},
+        std::{
+            sync::{Arc, Mutex},
+            time::Duration,
+        },
+        tonic::Status,
+        yellowstone_grpc_proto::{
+            prelude::{subscribe_update::UpdateOneof, SubscribeUpdate, SubscribeUpdatePing},
+            prost::Message,
+        },
+    };
+
+    fn create_update(seq: u64) -> SubscribeUpdate {
+        SubscribeUpdate {
+            filters: vec!["client".to_owned()],
+            update_oneof: Some(UpdateOneof::Ping(SubscribeUpdatePing {
+                seq,
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn get_seq(item: Option<Result<SubscribeUpdate, BufferedSubscriptionError>>) -> u64 {
+        match item
+            .expect("stream is alive")
+            .expect("valid update")
+            .update_oneof
+        {
+            Some(UpdateOneof::Ping(msg)) => msg.seq,
+            update => panic!("unexpected update: {update:?}"),
+        }
+    }
+
+    fn create_config(
+        memory_capacity: usize,
+        disk_capacity: u64,
+        overflow_policy: BufferedOverflowPolicy,
+    ) -> BufferedSubscriptionConfig {
+        BufferedSubscriptionConfig {
+            memory_capacity,
+            disk_capacity,
+            disk_dir: None,
+            overflow_policy,
+            on_stats: None,
+        }
+    }
+
+    async fn wait_stats(
+        buffered: &BufferedSubscription,
+        f: impl Fn(&BufferedSubscriptionStats) -> bool,
+    ) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !f(&buffered.stats()) {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        })
+        .await
+        .expect("timeout");
+    }
+
+    #[test]
+    fn test_spill_ring_wraps() {
+        let mut ring = SpillRing::create(&std::env::temp_dir(), 32).expect("file created");
+        let path = ring.path.clone();
+        assert!(ring.push(&[0; 10]).expect("written"));
+        for round in 1..16u8 {
+            // records and headers are split at the end of the file
+            assert!(ring.push(&[round; 10]).expect("written"));
+            assert!(!ring.push(&[0; 10]).expect("not written"));
+            assert_eq!(ring.pop().expect("read"), Some(vec![round - 1; 10]));
+        }
+        assert_eq!(ring.pop().expect("read"), Some(vec![15; 10]));
+        assert_eq!(ring.pop().expect("read"), None);
+        drop(ring);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_order_memory_disk_memory() {
+        let stats = Arc::new(Mutex::new(vec![]));
+        let (tx, rx) = mpsc::unbounded();
+        let mut config = create_config(8, 64 * 1024, BufferedOverflowPolicy::Disconnect);
+        config.on_stats = Some(Arc::new({
+            let stats = Arc::clone(&stats);
+            move |value: &BufferedSubscriptionStats| stats.lock().unwrap().push(*value)
+        }));
+        let mut buffered = BufferedSubscription::new(rx, config).expect("buffered subscription");
+
+        // consumer is stalled, updates over the memory capacity are spilled
+        for seq in 0..100 {
+            tx.unbounded_send(Ok(create_update(seq))).expect("alive");
+        }
+        wait_stats(&buffered, |stats| stats.spilled_len == 92).await;
+        assert_eq!(buffered.stats().memory_len, 8);
+
+        // slow consumer, spilled updates are moved back to memory in order
+        for seq in 0..50 {
+            assert_eq!(get_seq(buffered.next().await), seq);
+            tokio::time::sleep(Duration::from_micros(100)).await;
+        }
+        for seq in 100..150 {
+            tx.unbounded_send(Ok(create_update(seq))).expect("alive");
+        }
+        for seq in 50..150 {
+            assert_eq!(get_seq(buffered.next().await), seq);
+        }
+        let last = *stats.lock().unwrap().last().expect("stats reported");
+        assert_eq!(last, BufferedSubscriptionStats::default());
+        assert!(stats
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|stats| stats.spilled_bytes > 0));
+
+        // memory queue is used again once the spill file is empty
+        tx.unbounded_send(Ok(create_update(150))).expect("alive");
+        wait_stats(&buffered, |stats| stats.memory_len == 1).await;
+        assert_eq!(buffered.stats().spilled_len, 0);
+        assert_eq!(get_seq(buffered.next().await), 150);
+
+        // error of the gRPC stream ends the stream after buffered updates
+        tx.unbounded_send(Ok(create_update(151))).expect("alive");
+        tx.unbounded_send(Err(Status::internal("lagged")))
+            .expect("alive");
+        assert_eq!(get_seq(buffered.next().await), 151);
+        assert!(matches!(
+            buffered.next().await,
+            Some(Err(BufferedSubscriptionError::TonicStatus(status))) if status.message() == "lagged"
+        ));
+        assert!(buffered.next().await.is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_overflow_disconnect() {
+        let (tx, rx) = mpsc::unbounded();
+        let config = create_config(4, 0, BufferedOverflowPolicy::Disconnect);
+        let mut buffered = BufferedSubscription::new(rx, config).expect("buffered subscription");
+
+        for seq in 0..5 {
+            tx.unbounded_send(Ok(create_update(seq))).expect("alive");
+        }
+        // drained stream is dropped
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !tx.is_closed() {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        })
+        .await
+        .expect("timeout");
+
+        for seq in 0..4 {
+            assert_eq!(get_seq(buffered.next().await), seq);
+        }
+        assert!(matches!(
+            buffered.next().await,
+            Some(Err(BufferedSubscriptionError::Overflow { dropped: 1 }))
+        ));
+        assert!(buffered.next().await.is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_overflow_drop_oldest() {
+        let (tx, rx) = mpsc::unbounded();
+        let size = create_update(1).encoded_len() as u64 + 4;
+        let config = create_config(2, size * 2, BufferedOverflowPolicy::DropOldest);
+        let mut buffered = BufferedSubscription::new(rx, config).expect("buffered subscription");
+
+        for seq in 0..10 {
+            tx.unbounded_send(Ok(create_update(seq))).expect("alive");
+        }
+        wait_stats(&buffered, |stats| stats.dropped == 6).await;
+
+        // the newest updates are kept in order
+        assert!(matches!(
+            buffered.next().await,
+            Some(Err(BufferedSubscriptionError::Overflow { dropped: 6 }))
+        ));
+        for seq in 6..10 {
+            assert_eq!(get_seq(buffered.next().await), seq);
+        }
+
+        drop(tx);
+        assert!(buffered.next().await.is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_spill_file_removed() {
+        let dir = std::env::temp_dir().join(format!(
+            "yellowstone-grpc-buffer-test-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).expect("dir created");
+        let (tx, rx) = mpsc::unbounded();
+        let mut config = create_config(1, 64 * 1024, BufferedOverflowPolicy::Disconnect);
+        config.disk_dir = Some(dir.clone());
+        let buffered = BufferedSubscription::new(rx, config).expect("buffered subscription");
+
+        for seq in 0..10 {
+            tx.unbounded_send(Ok(create_update(seq))).expect("alive");
+        }
+        wait_stats(&buffered, |stats| stats.spilled_len == 9).await;
+
+        // spill thread is finished once the subscription is dropped
+        drop(buffered);
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while std::fs::read_dir(&dir)
+                .expect("dir exists")
+                .next()
+                .is_some()
+            {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        })
+        .await
+        .expect("timeout");
+        std::fs::remove_dir(&dir).expect("dir removed");
+    }
+}
@@ -0,0 +1,835 @@
+//! Local cache of accounts mirroring a subscription, see [`AccountCache`].
+
+use {
+    futures::stream::{Stream, StreamExt},
+    std::{
+        collections::{BTreeMap, HashMap, HashSet},
+        fmt,
+    },
+    tonic::Status,
+    yellowstone_grpc_proto::prelude::{
+        subscribe_update::UpdateOneof, CommitmentLevel, SlotStatus, SubscribeUpdate,
+        SubscribeUpdateAccount, SubscribeUpdateAccountInfo, SubscribeUpdateSlot,
+    },
+};
+
+const COMMITMENTS: [CommitmentLevel; 3] = [
+    CommitmentLevel::Processed,
+    CommitmentLevel::Confirmed,
+    CommitmentLevel::Finalized,
+];
+
+/// Latest write of the account
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountState {
+    pub slot: u64,
+    pub account: SubscribeUpdateAccountInfo,
+}
+
+impl AccountState {
+    /// Writes are ordered by slot, then by `write_version` inside the slot
+    pub const fn version(&self) -> (u64, u64) {
+        (self.slot, self.account.write_version)
+    }
+}
+
+/// Change of the account in the view of the commitment, `account` is `None` once the account
+/// is removed from the view, e.g. the only write was in the abandoned fork
+#[derive(Debug)]
+pub struct AccountChange<'a> {
+    pub commitment: CommitmentLevel,
+    pub pubkey: &'a [u8],
+    pub account: Option<&'a AccountState>,
+}
+
+type AccountChangeCallback = Box<dyn FnMut(&AccountChange<'_>) + Send>;
+
+#[derive(Debug, Default)]
+struct SlotInfo {
+    parent: Option<u64>,
+    confirmed: bool,
+    pubkeys: HashSet<Vec<u8>>,
+}
+
+#[derive(Debug, Default)]
+struct AccountCacheState {
+    finalized: HashMap<Vec<u8>, AccountState>,
+    // writes of not finalized slots, the latest write of the slot per account
+    pending: HashMap<Vec<u8>, BTreeMap<u64, AccountState>>,
+    slots: BTreeMap<u64, SlotInfo>,
+    finalized_slot: Option<u64>,
+    // owners of all stored writes, views are filtered on iteration
+    owners: HashMap<Vec<u8>, HashSet<Vec<u8>>>,
+}
+
+impl AccountCacheState {
+    fn get(&self, pubkey: &[u8], commitment: CommitmentLevel) -> Option<&AccountState> {
+        let mut pending = self
+            .pending
+            .get(pubkey)
+            .into_iter()
+            .flat_map(|writes| writes.iter().rev());
+        let state = match commitment {
+            CommitmentLevel::Processed => pending.next(),
+            CommitmentLevel::Confirmed => pending.find(|(slot, _state)| {
+                self.slots
+                    .get(*slot)
+                    .is_some_and(|slot_info| slot_info.confirmed)
+            }),
+            CommitmentLevel::Finalized => None,
+        };
+        state
+            .map(|(_slot, state)| state)
+            .or_else(|| self.finalized.get(pubkey))
+    }
+
+    fn versions(&self, pubkey: &[u8]) -> [Option<(u64, u64)>; 3] {
+        COMMITMENTS.map(|commitment| self.get(pubkey, commitment).map(AccountState::version))
+    }
+
+    fn owners_of(&self, pubkey: &[u8]) -> HashSet<Vec<u8>> {
+        self.finalized
+            .get(pubkey)
+            .into_iter()
+            .chain(
+                self.pending
+                    .get(pubkey)
+                    .into_iter()
+                    .flat_map(|writes| writes.values()),
+            )
+            .map(|state| state.account.owner.clone())
+            .collect()
+    }
+
+    fn reindex(&mut self, pubkey: &[u8], owners_before: HashSet<Vec<u8>>) {
+        let owners = self.owners_of(pubkey);
+        for owner in owners_before.difference(&owners) {
+            if let Some(pubkeys) = self.owners.get_mut(owner) {
+                pubkeys.remove(pubkey);
+                if pubkeys.is_empty() {
+                    self.owners.remove(owner);
+                }
+            }
+        }
+        for owner in owners {
+            self.owners
+                .entry(owner)
+                .or_default()
+                .insert(pubkey.to_vec());
+        }
+    }
+
+    fn set_finalized(&mut self, pubkey: Vec<u8>, state: AccountState) {
+        match self.finalized.get(&pubkey) {
+            Some(current) if current.version() >= state.version() => {}
+            _ => {
+                self.finalized.insert(pubkey, state);
+            }
+        }
+    }
+
+    fn write(&mut self, pubkey: Vec<u8>, state: AccountState) {
+        self.slots
+            .entry(state.slot)
+            .or_default()
+            .pubkeys
+            .insert(pubkey.clone());
+        let writes = self.pending.entry(pubkey).or_default();
+        match writes.get(&state.slot) {
+            // write versions inside the slot can be received out of order
+            Some(current) if current.version() >= state.version() => {}
+            _ => {
+                writes.insert(state.slot, state);
+            }
+        }
+    }
+
+    /// Slot with known not finalized ancestors, and parent of the lowest slot in the chain
+    fn chain(&self, slot: u64) -> (Vec<u64>, Option<u64>) {
+        let mut chain = vec![slot];
+        loop {
+            let parent = self
+                .slots
+                .get(chain.last().expect("not empty"))
+                .and_then(|slot_info| slot_info.parent);
+            match parent {
+                Some(parent) if self.slots.contains_key(&parent) => chain.push(parent),
+                parent => return (chain, parent),
+            }
+        }
+    }
+
+    /// Not confirmed slots between the chain of the confirmed slot and the slot, these slots
+    /// are in the abandoned forks
+    fn abandoned(&self, slot: u64) -> (Vec<u64>, Vec<u64>) {
+        let (chain, parent) = self.chain(slot);
+        let lowest = *chain.last().expect("not empty");
+        let from = parent.map_or(lowest, |parent| parent + 1);
+        let abandoned = self
+            .slots
+            .range(from..slot)
+            .filter(|(slot, slot_info)| !slot_info.confirmed && !chain.contains(*slot))
+            .map(|(slot, _slot_info)| *slot)
+            .collect();
+        (chain, abandoned)
+    }
+
+    fn remove_slot(&mut self, slot: u64) -> Vec<(Vec<u8>, AccountState)> {
+        let Some(slot_info) = self.slots.remove(&slot) else {
+            return vec![];
+        };
+        let mut states = Vec::with_capacity(slot_info.pubkeys.len());
+        for pubkey in slot_info.pubkeys {
+            if let Some(writes) = self.pending.get_mut(&pubkey) {
+                if let Some(state) = writes.remove(&slot) {
+                    states.push((pubkey.clone(), state));
+                }
+                if writes.is_empty() {
+                    self.pending.remove(&pubkey);
+                }
+            }
+        }
+        states
+    }
+
+    fn slot_pubkeys<'a>(&self, slots: impl IntoIterator<Item = &'a u64>) -> HashSet<Vec<u8>> {
+        slots
+            .into_iter()
+            .filter_map(|slot| self.slots.get(slot))
+            .flat_map(|slot_info| slot_info.pubkeys.iter().cloned())
+            .collect()
+    }
+}
+
+/// Latest state of accounts received with a subscription, per commitment.
+///
+/// Subscription should include accounts with `processed` commitment and slots with
+/// `filter_by_commitment: false`: account writes are kept per slot and moved to the state of
+/// the commitment by slot statuses, so one stream is enough for every commitment:
+///
+/// - writes inside the slot are resolved by `write_version`, writes of different slots by slot;
+/// - confirmed slot confirms its ancestors known from `parent` of slot updates, not confirmed
+///   slots between the confirmed slot and its ancestors are in abandoned forks, their writes are
+///   removed from the processed state;
+/// - finalized slot moves writes of the slot and its ancestors to the finalized state, other not
+///   finalized slots up to the finalized slot are removed, writes of removed slots received later
+///   are ignored;
+/// - writes of dead slots are removed;
+/// - startup accounts (`is_startup`) are finalized state of the snapshot,
+///   [`AccountCache::is_startup_done`] is set once `EndOfStartup` is received.
+///
+/// Callbacks added with [`AccountCache::on_change`] are called for every change of the
+/// account in every commitment, including removes of processed writes in abandoned forks.
+#[derive(Default)]
+pub struct AccountCache {
+    state: AccountCacheState,
+    callbacks: Vec<AccountChangeCallback>,
+    startup_done: bool,
+}
+
+impl fmt::Debug for AccountCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccountCache")
+            .field("finalized_accounts", &self.state.finalized.len())
+            .field("pending_accounts", &self.state.pending.len())
+            .field("pending_slots", &self.state.slots.len())
+            .field("finalized_slot", &self.state.finalized_slot)
+            .field("callbacks", &self.callbacks.len())
+            .field("startup_done", &self.startup_done)
+            .finish()
+    }
+}
+
+impl AccountCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add callback called on every change of the account state
+    pub fn on_change(&mut self, callback: impl FnMut(&AccountChange<'_>) + Send + 'static) {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    pub fn get(&self, pubkey: &[u8], commitment: CommitmentLevel) -> Option<&AccountState> {
+        self.state.get(pubkey, commitment)
+    }
+
+    /// Accounts owned by `owner` in the state of the commitment, order is not defined
+    pub fn iter_owner<'a>(
+        &'a self,
+        owner: &'a [u8],
+        commitment: CommitmentLevel,
+    ) -> impl Iterator<Item = (&'a [u8], &'a AccountState)> + 'a {
+        self.state
+            .owners
+            .get(owner)
+            .into_iter()
+            .flatten()
+            .filter_map(move |pubkey| {
+                self.state
+                    .get(pubkey, commitment)
+                    .filter(|state| state.account.owner == owner)
+                    .map(|state| (pubkey.as_slice(), state))
+            })
+    }
+
+    /// Last received finalized slot
+    pub const fn finalized_slot(&self) -> Option<u64> {
+        self.state.finalized_slot
+    }
+
+    /// `EndOfStartup` is received, startup accounts are loaded
+    pub const fn is_startup_done(&self) -> bool {
+        self.startup_done
+    }
+
+    /// Apply the update, updates other than accounts, slots and `EndOfStartup` are ignored
+    pub fn update(&mut self, update: &SubscribeUpdate) {
+        match update.update_oneof.as_ref() {
+            Some(UpdateOneof::Account(msg)) => self.update_account(msg),
+            Some(UpdateOneof::Slot(msg)) => self.update_slot(msg),
+            Some(UpdateOneof::EndOfStartup(_)) => self.startup_done = true,
+            _ => {}
+        }
+    }
+
+    /// Apply updates of the stream until it is finished, stream error is returned
+    pub async fn consume<S>(&mut self, stream: S) -> Result<(), Status>
+    where
+        S: Stream<Item = Result<SubscribeUpdate, Status>>,
+    {
+        futures::pin_mut!(stream);
+        while let Some(update) = stream.next().await {
+            self.update(&update?);
+        }
+        Ok(())
+    }
+
+    fn update_account(&mut self, msg: &SubscribeUpdateAccount) {
+        let Some(account) = msg.account.as_ref() else {
+            return;
+        };
+        let pubkey = account.pubkey.clone();
+        let state = AccountState {
+            slot: msg.slot,
+            account: account.clone(),
+        };
+        if msg.is_startup {
+            self.apply(vec![pubkey.clone()], |cache| {
+                cache.set_finalized(pubkey, state)
+            });
+        } else if self
+            .state
+            .finalized_slot
+            .is_none_or(|finalized_slot| msg.slot > finalized_slot)
+        {
+            self.apply(vec![pubkey.clone()], |cache| cache.write(pubkey, state));
+        }
+    }
+
+    fn update_slot(&mut self, msg: &SubscribeUpdateSlot) {
+        let slot = msg.slot;
+        if self
+            .state
+            .finalized_slot
+            .is_some_and(|finalized_slot| slot <= finalized_slot)
+        {
+            return;
+        }
+        let slot_info = self.state.slots.entry(slot).or_default();
+        if msg.parent.is_some() {
+            slot_info.parent = msg.parent;
+        }
+
+        match SlotStatus::try_from(msg.status) {
+            Ok(SlotStatus::SlotConfirmed) => {
+                let (chain, abandoned) = self.state.abandoned(slot);
+                let pubkeys = self
+                    .state
+                    .slot_pubkeys(chain.iter().chain(abandoned.iter()));
+                self.apply(pubkeys.into_iter().collect(), |cache| {
+                    for chain_slot in chain {
+                        if let Some(slot_info) = cache.slots.get_mut(&chain_slot) {
+                            slot_info.confirmed = true;
+                        }
+                    }
+                    for abandoned_slot in abandoned {
+                        cache.remove_slot(abandoned_slot);
+                    }
+                });
+            }
+            Ok(SlotStatus::SlotFinalized) => {
+                let (mut chain, _parent) = self.state.chain(slot);
+                chain.reverse();
+                let removed = self
+                    .state
+                    .slots
+                    .range(..=slot)
+                    .map(|(slot, _slot_info)| *slot)
+                    .collect::<Vec<_>>();
+                let pubkeys = self.state.slot_pubkeys(removed.iter());
+                self.apply(pubkeys.into_iter().collect(), |cache| {
+                    for chain_slot in chain {
+                        for (pubkey, state) in cache.remove_slot(chain_slot) {
+                            cache.set_finalized(pubkey, state);
+                        }
+                    }
+                    for removed_slot in removed {
+                        cache.remove_slot(removed_slot);
+                    }
+                    cache.finalized_slot = Some(slot);
+                });
+            }
+            Ok(SlotStatus::SlotDead) => {
+                let pubkeys = self.state.slot_pubkeys([&slot]);
+                self.apply(pubkeys.into_iter().collect(), |cache| {
+                    cache.remove_slot(slot);
+                });
+            }
+            _ => {}
+        }
+    }
+
+    /// Apply `f` and call callbacks for changed states of `pubkeys`
+    fn apply(&mut self, pubkeys: Vec<Vec<u8>>, f: impl FnOnce(&mut AccountCacheState)) {
+        let before = pubkeys
+            .iter()
+            .map(|pubkey| (self.state.versions(pubkey), self.state.owners_of(pubkey)))
+            .collect::<Vec<_>>();
+        f(&mut self.state);
+
+        for (pubkey, (versions, owners)) in pubkeys.iter().zip(before) {
+            self.state.reindex(pubkey, owners);
+            let versions_new = self.state.versions(pubkey);
+            for ((commitment, version), version_new) in
+                COMMITMENTS.iter().zip(versions).zip(versions_new)
+            {
+                if version == version_new {
+                    continue;
+                }
+                let change = AccountChange {
+                    commitment: *commitment,
+                    pubkey,
+                    account: self.state.get(pubkey, *commitment),
+                };
+                for callback in self.callbacks.iter_mut() {
+                    callback(&change);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{AccountCache, AccountState},
+        std::{
+            collections::{HashMap, HashSet},
+            sync::{Arc, Mutex},
+        },
+        yellowstone_grpc_proto::prelude::{
+            subscribe_update::UpdateOneof, CommitmentLevel, SlotStatus, SubscribeUpdate,
+            SubscribeUpdateAccount, SubscribeUpdateAccountInfo, SubscribeUpdateEndOfStartup,
+            SubscribeUpdateSlot,
+        },
+    };
+
+    const PUBKEYS: u8 = 8;
+    const OWNERS: u8 = 2;
+
+    fn account_update(
+        slot: u64,
+        pubkey: u8,
+        owner: u8,
+        write_version: u64,
+        is_startup: bool,
+    ) -> SubscribeUpdate {
+        SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                account: Some(SubscribeUpdateAccountInfo {
+                    pubkey: vec![pubkey],
+                    owner: vec![owner],
+                    lamports: write_version,
+                    write_version,
+                    ..Default::default()
+                }),
+                slot,
+                is_startup,
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn slot_update(slot: u64, parent: Option<u64>, status: SlotStatus) -> SubscribeUpdate {
+        SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot,
+                parent,
+                status: status as i32,
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn version(cache: &AccountCache, pubkey: u8, commitment: CommitmentLevel) -> Option<u64> {
+        cache
+            .get(&[pubkey], commitment)
+            .map(|state| state.account.write_version)
+    }
+
+    /// xorshift64*, tests are deterministic for the seed
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+
+        fn chance(&mut self, percent: u64) -> bool {
+            self.below(100) < percent
+        }
+    }
+
+    /// Chain with forks: every fork slot is a child of the previous chain slot and is not
+    /// continued, so the next chain slot abandons it
+    struct Chain {
+        slots: HashMap<u64, u64>, // slot -> parent
+    }
+
+    impl Chain {
+        fn is_ancestor(&self, ancestor: u64, mut slot: u64) -> bool {
+            loop {
+                if slot == ancestor {
+                    return true;
+                }
+                match self.slots.get(&slot) {
+                    Some(parent) if *parent >= ancestor => slot = *parent,
+                    _ => return false,
+                }
+            }
+        }
+    }
+
+    /// Writes and statuses received so far, views are computed from scratch
+    #[derive(Default)]
+    struct Reference {
+        writes: Vec<(u64, u8, u8, u64)>, // (slot, pubkey, owner, write_version)
+        confirmed: Vec<u64>,
+        finalized: Vec<u64>,
+        dead: HashSet<u64>,
+    }
+
+    impl Reference {
+        fn update(&mut self, update: &SubscribeUpdate) {
+            match update.update_oneof.as_ref() {
+                Some(UpdateOneof::Account(msg)) => {
+                    let account = msg.account.as_ref().expect("account");
+                    let slot = if msg.is_startup { 0 } else { msg.slot };
+                    self.writes.push((
+                        slot,
+                        account.pubkey[0],
+                        account.owner[0],
+                        account.write_version,
+                    ));
+                }
+                Some(UpdateOneof::Slot(msg)) => match SlotStatus::try_from(msg.status) {
+                    Ok(SlotStatus::SlotConfirmed) => self.confirmed.push(msg.slot),
+                    Ok(SlotStatus::SlotFinalized) => self.finalized.push(msg.slot),
+                    Ok(SlotStatus::SlotDead) => {
+                        self.dead.insert(msg.slot);
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+
+        fn visible(&self, chain: &Chain, slot: u64, commitment: CommitmentLevel) -> bool {
+            let committed = |slots: &[u64]| {
+                slot == 0
+                    || slots
+                        .iter()
+                        .any(|committed| chain.is_ancestor(slot, *committed))
+            };
+            match commitment {
+                CommitmentLevel::Processed => {
+                    !self.dead.contains(&slot)
+                        && !self
+                            .confirmed
+                            .iter()
+                            .chain(self.finalized.iter())
+                            .any(|confirmed| {
+                                *confirmed > slot && !chain.is_ancestor(slot, *confirmed)
+                            })
+                }
+                CommitmentLevel::Confirmed => {
+                    committed(&self.confirmed) || committed(&self.finalized)
+                }
+                CommitmentLevel::Finalized => committed(&self.finalized),
+            }
+        }
+
+        fn get(
+            &self,
+            chain: &Chain,
+            pubkey: u8,
+            commitment: CommitmentLevel,
+        ) -> Option<(u64, u8, u64)> {
+            self.writes
+                .iter()
+                .filter(|(slot, write_pubkey, _owner, _write_version)| {
+                    *write_pubkey == pubkey && self.visible(chain, *slot, commitment)
+                })
+                .map(|(slot, _pubkey, owner, write_version)| (*slot, *owner, *write_version))
+                .max_by_key(|(slot, _owner, write_version)| (*slot, *write_version))
+        }
+    }
+
+    /// Updates of the chain as sent by the server: statuses of the slot after its writes,
+    /// writes of the next slot can be received before the status of the previous slot
+    fn generate(rng: &mut Rng, slots: u64) -> (Chain, Vec<SubscribeUpdate>) {
+        let mut chain = Chain {
+            slots: HashMap::new(),
+        };
+        let mut write_version = 0;
+        let mut updates = vec![];
+        for pubkey in 0..PUBKEYS {
+            if rng.chance(50) {
+                write_version += 1;
+                let owner = rng.below(u64::from(OWNERS)) as u8;
+                updates.push(account_update(0, pubkey, owner, write_version, true));
+            }
+        }
+        updates.push(SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::EndOfStartup(SubscribeUpdateEndOfStartup {})),
+            ..Default::default()
+        });
+
+        let mut parent = 0;
+        let mut canonical = vec![];
+        let mut pending: Vec<SubscribeUpdate> = vec![];
+        for slot in 1..=slots {
+            let is_canonical = rng.chance(70);
+            chain.slots.insert(slot, parent);
+            let mut slot_updates = vec![slot_update(slot, Some(parent), SlotStatus::SlotProcessed)];
+            let mut writes = vec![];
+            for _ in 0..rng.below(6) {
+                write_version += 1;
+                let pubkey = rng.below(u64::from(PUBKEYS)) as u8;
+                let owner = rng.below(u64::from(OWNERS)) as u8;
+                writes.push(account_update(slot, pubkey, owner, write_version, false));
+            }
+            // write versions are not ordered inside the slot
+            for index in (1..writes.len()).rev() {
+                writes.swap(index, rng.below(index as u64 + 1) as usize);
+            }
+            slot_updates.extend(writes);
+            if !is_canonical && rng.chance(30) {
+                slot_updates.push(slot_update(slot, Some(parent), SlotStatus::SlotDead));
+            }
+
+            // updates of the previous slots are interleaved with updates of the slot
+            let mut merged = vec![];
+            let (mut previous, mut current) = (
+                pending.into_iter().peekable(),
+                slot_updates.into_iter().peekable(),
+            );
+            while previous.peek().is_some() || current.peek().is_some() {
+                let next =
+                    if previous.peek().is_none() || (current.peek().is_some() && rng.chance(50)) {
+                        current.next()
+                    } else {
+                        previous.next()
+                    };
+                merged.extend(next);
+            }
+            pending = merged;
+
+            if is_canonical {
+                // all updates of the fork are received before the next canonical slot is confirmed
+                updates.append(&mut pending);
+                canonical.push(slot);
+                parent = slot;
+                if rng.chance(80) {
+                    pending.push(slot_update(
+                        slot,
+                        Some(chain.slots[&slot]),
+                        SlotStatus::SlotConfirmed,
+                    ));
+                }
+                if canonical.len() > 4 && rng.chance(80) {
+                    let finalized = canonical[canonical.len() - 5];
+                    updates.push(slot_update(
+                        finalized,
+                        Some(chain.slots[&finalized]),
+                        SlotStatus::SlotFinalized,
+                    ));
+                }
+            }
+        }
+        updates.append(&mut pending);
+        if let Some(last) = canonical.last() {
+            updates.push(slot_update(
+                *last,
+                Some(chain.slots[last]),
+                SlotStatus::SlotConfirmed,
+            ));
+            updates.push(slot_update(
+                *last,
+                Some(chain.slots[last]),
+                SlotStatus::SlotFinalized,
+            ));
+        }
+        (chain, updates)
+    }
+
+    #[test]
+    fn test_account_cache_write_versions() {
+        let mut cache = AccountCache::new();
+        for update in [
+            slot_update(10, Some(9), SlotStatus::SlotProcessed),
+            account_update(10, 1, 0, 5, false),
+            account_update(10, 1, 0, 3, false), // older write of the same slot
+            slot_update(11, Some(10), SlotStatus::SlotProcessed),
+            account_update(11, 1, 0, 4, false), // next slot wins even with lower write version
+        ] {
+            cache.update(&update);
+        }
+        assert_eq!(version(&cache, 1, CommitmentLevel::Processed), Some(4));
+        assert_eq!(version(&cache, 1, CommitmentLevel::Confirmed), None);
+
+        cache.update(&slot_update(10, Some(9), SlotStatus::SlotConfirmed));
+        assert_eq!(version(&cache, 1, CommitmentLevel::Confirmed), Some(5));
+        cache.update(&slot_update(10, Some(9), SlotStatus::SlotFinalized));
+        assert_eq!(version(&cache, 1, CommitmentLevel::Finalized), Some(5));
+        assert_eq!(version(&cache, 1, CommitmentLevel::Processed), Some(4));
+
+        // write of the finalized slot is ignored
+        cache.update(&account_update(10, 1, 0, 6, false));
+        assert_eq!(version(&cache, 1, CommitmentLevel::Finalized), Some(5));
+    }
+
+    #[test]
+    fn test_account_cache_abandoned_fork() {
+        let changes = Arc::new(Mutex::new(vec![]));
+        let mut cache = AccountCache::new();
+        cache.on_change({
+            let changes = Arc::clone(&changes);
+            move |change| {
+                changes.lock().unwrap().push((
+                    change.commitment,
+                    change.account.map(|state| state.account.write_version),
+                ))
+            }
+        });
+        for update in [
+            account_update(0, 1, 0, 1, true),
+            slot_update(10, Some(9), SlotStatus::SlotProcessed),
+            slot_update(11, Some(10), SlotStatus::SlotProcessed),
+            account_update(11, 1, 0, 2, false), // fork
+            slot_update(12, Some(10), SlotStatus::SlotProcessed),
+            slot_update(12, Some(10), SlotStatus::SlotConfirmed),
+        ] {
+            cache.update(&update);
+        }
+        assert_eq!(version(&cache, 1, CommitmentLevel::Processed), Some(1));
+        assert_eq!(
+            changes.lock().unwrap().as_slice(),
+            [
+                (CommitmentLevel::Processed, Some(1)),
+                (CommitmentLevel::Confirmed, Some(1)),
+                (CommitmentLevel::Finalized, Some(1)),
+                (CommitmentLevel::Processed, Some(2)),
+                (CommitmentLevel::Processed, Some(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_account_cache_randomized() {
+        for seed in 1..=200 {
+            let mut rng = Rng(seed);
+            let (chain, updates) = generate(&mut rng, 40);
+
+            let mirror = Arc::new(Mutex::new(HashMap::new()));
+            let mut cache = AccountCache::new();
+            cache.on_change({
+                let mirror = Arc::clone(&mirror);
+                move |change| {
+                    let key = (change.commitment, change.pubkey.to_vec());
+                    let mut mirror = mirror.lock().unwrap();
+                    match change.account {
+                        Some(state) => mirror.insert(key, state.clone()),
+                        None => mirror.remove(&key),
+                    };
+                }
+            });
+            let mut reference = Reference::default();
+            for update in updates.iter() {
+                cache.update(update);
+                reference.update(update);
+
+                for commitment in [CommitmentLevel::Confirmed, CommitmentLevel::Finalized] {
+                    for pubkey in 0..PUBKEYS {
+                        assert_eq!(
+                            cache.get(&[pubkey], commitment).map(AccountState::version),
+                            reference
+                                .get(&chain, pubkey, commitment)
+                                .map(|(slot, _owner, write_version)| (slot, write_version)),
+                            "seed {seed}, pubkey {pubkey}, {commitment:?}"
+                        );
+                    }
+                }
+            }
+            assert!(cache.is_startup_done());
+
+            for commitment in [
+                CommitmentLevel::Processed,
+                CommitmentLevel::Confirmed,
+                CommitmentLevel::Finalized,
+            ] {
+                for pubkey in 0..PUBKEYS {
+                    let expected = reference.get(&chain, pubkey, commitment);
+                    assert_eq!(
+                        cache.get(&[pubkey], commitment).map(AccountState::version),
+                        expected.map(|(slot, _owner, write_version)| (slot, write_version)),
+                        "seed {seed}, pubkey {pubkey}, {commitment:?}"
+                    );
+                    assert_eq!(
+                        mirror.lock().unwrap().get(&(commitment, vec![pubkey])),
+                        cache.get(&[pubkey], commitment),
+                        "seed {seed}, pubkey {pubkey}, {commitment:?}"
+                    );
+                }
+                for owner in 0..OWNERS {
+                    let mut owned = cache
+                        .iter_owner(&[owner], commitment)
+                        .map(|(pubkey, _state)| pubkey[0])
+                        .collect::<Vec<_>>();
+                    owned.sort_unstable();
+                    let expected = (0..PUBKEYS)
+                        .filter(|pubkey| {
+                            reference.get(&chain, *pubkey, commitment).is_some_and(
+                                |(_slot, write_owner, _write_version)| write_owner == owner,
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    assert_eq!(
+                        owned, expected,
+                        "seed {seed}, owner {owner}, {commitment:?}"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -1,26 +1,48 @@
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "buffered")]
+pub mod buffered;
+pub mod cache;
+#[cfg(test)]
+mod mock;
+pub mod multi;
+
 pub use tonic::{service::Interceptor, transport::ClientTlsConfig};
 use {
     bytes::Bytes,
     futures::{
         channel::mpsc,
         sink::{Sink, SinkExt},
-        stream::Stream,
+        stream::{Stream, StreamExt},
+    },
+    std::{
+        fmt,
+        sync::Arc,
+        time::{Duration, SystemTime},
     },
-    std::time::Duration,
     tonic::{
         codec::{CompressionEncoding, Streaming},
-        metadata::{errors::InvalidMetadataValue, AsciiMetadataValue, MetadataValue},
+        metadata::{
+            errors::{InvalidMetadataKey, InvalidMetadataValue},
+            AsciiMetadataKey, AsciiMetadataValue, MetadataValue,
+        },
         service::interceptor::InterceptedService,
         transport::channel::{Channel, Endpoint},
         Request, Response, Status,
     },
     tonic_health::pb::{health_client::HealthClient, HealthCheckRequest, HealthCheckResponse},
-    yellowstone_grpc_proto::prelude::{
-        geyser_client::GeyserClient, CommitmentLevel, GetBlockHeightRequest,
-        GetBlockHeightResponse, GetLatestBlockhashRequest, GetLatestBlockhashResponse,
-        GetSlotRequest, GetSlotResponse, GetVersionRequest, GetVersionResponse,
-        IsBlockhashValidRequest, IsBlockhashValidResponse, PingRequest, PongResponse,
-        SubscribeRequest, SubscribeUpdate,
+    yellowstone_grpc_proto::{
+        bloom::SignaturesBloom,
+        prelude::{
+            geyser_client::GeyserClient, CommitmentLevel, GetAccountsSnapshotRequest,
+            GetAccountsSnapshotResponse, GetBlockHeightRequest, GetBlockHeightResponse,
+            GetLatestBlockhashRequest, GetLatestBlockhashResponse, GetSlotAncestryRequest,
+            GetSlotAncestryResponse, GetSlotRequest, GetSlotResponse, GetSlotStatusRequest,
+            GetSlotStatusResponse, GetTransactionStatusRequest, GetTransactionStatusResponse,
+            GetVersionRequest, GetVersionResponse, IsBlockhashValidRequest,
+            IsBlockhashValidResponse, PingRequest, PongResponse, SubscribeRequest,
+            SubscribeRequestSeenSignaturesBloom, SubscribeUpdate,
+        },
     },
 };
 
@@ -44,6 +66,94 @@ impl Interceptor for InterceptorXToken {
     }
 }
 
+/// Interceptor added with [`GeyserGrpcBuilder::with_interceptor`], shared by all clients
+#[derive(Clone)]
+pub struct SharedInterceptor(Arc<InterceptorFn>);
+
+type InterceptorFn = dyn Fn(Request<()>) -> Result<Request<()>, Status> + Send + Sync;
+
+pub type OnUpdateCallback = Arc<dyn Fn(&SubscribeUpdate) + Send + Sync>;
+
+pub type OnErrorCallback = Arc<dyn Fn(&Status) + Send + Sync>;
+
+impl fmt::Debug for SharedInterceptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedInterceptor")
+    }
+}
+
+impl Interceptor for SharedInterceptor {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        (self.0)(request)
+    }
+}
+
+/// `x-token`, custom metadata and interceptors from the builder, applied in this order
+#[derive(Debug, Clone)]
+struct InterceptorChain {
+    x_token: InterceptorXToken,
+    metadata: Arc<Vec<(AsciiMetadataKey, AsciiMetadataValue)>>,
+    interceptors: Vec<SharedInterceptor>,
+}
+
+impl Interceptor for InterceptorChain {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        let mut request = self.x_token.call(request)?;
+        for (key, value) in self.metadata.iter() {
+            request.metadata_mut().insert(key.clone(), value.clone());
+        }
+        for interceptor in self.interceptors.iter_mut() {
+            request = interceptor.call(request)?;
+        }
+        Ok(request)
+    }
+}
+
+/// Callbacks for observability, called for every received update and every error status
+#[derive(Clone, Default)]
+pub struct GeyserGrpcHooks {
+    pub on_update: Option<OnUpdateCallback>,
+    pub on_error: Option<OnErrorCallback>,
+}
+
+impl fmt::Debug for GeyserGrpcHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GeyserGrpcHooks")
+            .field("on_update", &self.on_update.is_some())
+            .field("on_error", &self.on_error.is_some())
+            .finish()
+    }
+}
+
+impl GeyserGrpcHooks {
+    fn response<T>(&self, result: Result<Response<T>, Status>) -> GeyserGrpcClientResult<T> {
+        match result {
+            Ok(response) => Ok(response.into_inner()),
+            Err(status) => {
+                self.error(&status);
+                Err(status.into())
+            }
+        }
+    }
+
+    fn update(&self, result: &Result<SubscribeUpdate, Status>) {
+        match result {
+            Ok(update) => {
+                if let Some(on_update) = &self.on_update {
+                    on_update(update);
+                }
+            }
+            Err(status) => self.error(status),
+        }
+    }
+
+    fn error(&self, status: &Status) {
+        if let Some(on_error) = &self.on_error {
+            on_error(status);
+        }
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum GeyserGrpcClientError {
     #[error("gRPC status: {0}")]
@@ -57,6 +167,7 @@ pub type GeyserGrpcClientResult<T> = Result<T, GeyserGrpcClientError>;
 pub struct GeyserGrpcClient<F> {
     pub health: HealthClient<InterceptedService<Channel, F>>,
     pub geyser: GeyserClient<InterceptedService<Channel, F>>,
+    pub hooks: GeyserGrpcHooks,
 }
 
 impl GeyserGrpcClient<()> {
@@ -76,7 +187,18 @@ impl<F: Interceptor> GeyserGrpcClient<F> {
         health: HealthClient<InterceptedService<Channel, F>>,
         geyser: GeyserClient<InterceptedService<Channel, F>>,
     ) -> Self {
-        Self { health, geyser }
+        Self {
+            health,
+            geyser,
+            hooks: GeyserGrpcHooks {
+                on_update: None,
+                on_error: None,
+            },
+        }
+    }
+
+    pub fn with_hooks(self, hooks: GeyserGrpcHooks) -> Self {
+        Self { hooks, ..self }
     }
 
     // Health
@@ -84,8 +206,7 @@ impl<F: Interceptor> GeyserGrpcClient<F> {
         let request = HealthCheckRequest {
             service: "geyser.Geyser".to_owned(),
         };
-        let response = self.health.check(request).await?;
-        Ok(response.into_inner())
+        self.hooks.response(self.health.check(request).await)
     }
 
     pub async fn health_watch(
@@ -94,8 +215,7 @@ impl<F: Interceptor> GeyserGrpcClient<F> {
         let request = HealthCheckRequest {
             service: "geyser.Geyser".to_owned(),
         };
-        let response = self.health.watch(request).await?;
-        Ok(response.into_inner())
+        self.hooks.response(self.health.watch(request).await)
     }
 
     // Subscribe
@@ -122,9 +242,14 @@ impl<F: Interceptor> GeyserGrpcClient<F> {
                 .await
                 .map_err(GeyserGrpcClientError::SubscribeSendError)?;
         }
-        let response: Response<Streaming<SubscribeUpdate>> =
-            self.geyser.subscribe(subscribe_rx).await?;
-        Ok((subscribe_tx, response.into_inner()))
+        let stream: Streaming<SubscribeUpdate> = self
+            .hooks
+            .response(self.geyser.subscribe(subscribe_rx).await)?;
+        let hooks = self.hooks.clone();
+        Ok((
+            subscribe_tx,
+            stream.inspect(move |result| hooks.update(result)),
+        ))
     }
 
     pub async fn subscribe_once(
@@ -140,8 +265,7 @@ impl<F: Interceptor> GeyserGrpcClient<F> {
     pub async fn ping(&mut self, count: i32) -> GeyserGrpcClientResult<PongResponse> {
         let message = PingRequest { count };
         let request = tonic::Request::new(message);
-        let response = self.geyser.ping(request).await?;
-        Ok(response.into_inner())
+        self.hooks.response(self.geyser.ping(request).await)
     }
 
     pub async fn get_latest_blockhash(
@@ -151,8 +275,8 @@ impl<F: Interceptor> GeyserGrpcClient<F> {
         let request = tonic::Request::new(GetLatestBlockhashRequest {
             commitment: commitment.map(|value| value as i32),
         });
-        let response = self.geyser.get_latest_blockhash(request).await?;
-        Ok(response.into_inner())
+        self.hooks
+            .response(self.geyser.get_latest_blockhash(request).await)
     }
 
     pub async fn get_block_height(
@@ -162,8 +286,8 @@ impl<F: Interceptor> GeyserGrpcClient<F> {
         let request = tonic::Request::new(GetBlockHeightRequest {
             commitment: commitment.map(|value| value as i32),
         });
-        let response = self.geyser.get_block_height(request).await?;
-        Ok(response.into_inner())
+        self.hooks
+            .response(self.geyser.get_block_height(request).await)
     }
 
     pub async fn get_slot(
@@ -173,8 +297,7 @@ impl<F: Interceptor> GeyserGrpcClient<F> {
         let request = tonic::Request::new(GetSlotRequest {
             commitment: commitment.map(|value| value as i32),
         });
-        let response = self.geyser.get_slot(request).await?;
-        Ok(response.into_inner())
+        self.hooks.response(self.geyser.get_slot(request).await)
     }
 
     pub async fn is_blockhash_valid(
@@ -186,21 +309,60 @@ impl<F: Interceptor> GeyserGrpcClient<F> {
             blockhash,
             commitment: commitment.map(|value| value as i32),
         });
-        let response = self.geyser.is_blockhash_valid(request).await?;
-        Ok(response.into_inner())
+        self.hooks
+            .response(self.geyser.is_blockhash_valid(request).await)
     }
 
     pub async fn get_version(&mut self) -> GeyserGrpcClientResult<GetVersionResponse> {
         let request = tonic::Request::new(GetVersionRequest {});
-        let response = self.geyser.get_version(request).await?;
-        Ok(response.into_inner())
+        self.hooks.response(self.geyser.get_version(request).await)
+    }
+
+    pub async fn get_slot_status(&mut self) -> GeyserGrpcClientResult<GetSlotStatusResponse> {
+        let request = tonic::Request::new(GetSlotStatusRequest {});
+        self.hooks
+            .response(self.geyser.get_slot_status(request).await)
+    }
+
+    pub async fn get_accounts_snapshot(
+        &mut self,
+        pubkeys: Vec<String>,
+        commitment: Option<CommitmentLevel>,
+    ) -> GeyserGrpcClientResult<GetAccountsSnapshotResponse> {
+        let request = tonic::Request::new(GetAccountsSnapshotRequest {
+            pubkeys,
+            commitment: commitment.map(|value| value as i32),
+        });
+        self.hooks
+            .response(self.geyser.get_accounts_snapshot(request).await)
+    }
+
+    pub async fn get_transaction_status(
+        &mut self,
+        signature: String,
+    ) -> GeyserGrpcClientResult<GetTransactionStatusResponse> {
+        let request = tonic::Request::new(GetTransactionStatusRequest { signature });
+        self.hooks
+            .response(self.geyser.get_transaction_status(request).await)
+    }
+
+    pub async fn get_slot_ancestry(
+        &mut self,
+        slot: u64,
+        limit: u32,
+    ) -> GeyserGrpcClientResult<GetSlotAncestryResponse> {
+        let request = tonic::Request::new(GetSlotAncestryRequest { slot, limit });
+        self.hooks
+            .response(self.geyser.get_slot_ancestry(request).await)
     }
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum GeyserGrpcBuilderError {
-    #[error("Failed to parse x-token: {0}")]
+    #[error("Failed to parse metadata value: {0}")]
     MetadataValueError(#[from] InvalidMetadataValue),
+    #[error("Failed to parse metadata key: {0}")]
+    MetadataKeyError(#[from] InvalidMetadataKey),
     #[error("gRPC transport error: {0}")]
     TonicError(#[from] tonic::transport::Error),
 }
@@ -216,6 +378,9 @@ pub struct GeyserGrpcBuilder {
     pub accept_compressed: Option<CompressionEncoding>,
     pub max_decoding_message_size: Option<usize>,
     pub max_encoding_message_size: Option<usize>,
+    pub metadata: Vec<(AsciiMetadataKey, AsciiMetadataValue)>,
+    pub interceptors: Vec<SharedInterceptor>,
+    pub hooks: GeyserGrpcHooks,
 }
 
 impl GeyserGrpcBuilder {
@@ -229,6 +394,12 @@ impl GeyserGrpcBuilder {
             accept_compressed: None,
             max_decoding_message_size: None,
             max_encoding_message_size: None,
+            metadata: Vec::new(),
+            interceptors: Vec::new(),
+            hooks: GeyserGrpcHooks {
+                on_update: None,
+                on_error: None,
+            },
         }
     }
 
@@ -245,9 +416,13 @@ impl GeyserGrpcBuilder {
         self,
         channel: Channel,
     ) -> GeyserGrpcBuilderResult<GeyserGrpcClient<impl Interceptor>> {
-        let interceptor = InterceptorXToken {
-            x_token: self.x_token,
-            x_request_snapshot: self.x_request_snapshot,
+        let interceptor = InterceptorChain {
+            x_token: InterceptorXToken {
+                x_token: self.x_token,
+                x_request_snapshot: self.x_request_snapshot,
+            },
+            metadata: Arc::new(self.metadata),
+            interceptors: self.interceptors,
         };
 
         let mut geyser = GeyserClient::with_interceptor(channel.clone(), interceptor.clone());
@@ -264,10 +439,10 @@ impl GeyserGrpcBuilder {
             geyser = geyser.max_encoding_message_size(limit);
         }
 
-        Ok(GeyserGrpcClient::new(
-            HealthClient::with_interceptor(channel, interceptor),
-            geyser,
-        ))
+        Ok(
+            GeyserGrpcClient::new(HealthClient::with_interceptor(channel, interceptor), geyser)
+                .with_hooks(self.hooks),
+        )
     }
 
     pub async fn connect(self) -> GeyserGrpcBuilderResult<GeyserGrpcClient<impl Interceptor>> {
@@ -299,6 +474,42 @@ impl GeyserGrpcBuilder {
         }
     }
 
+    // Add metadata to every request
+    pub fn with_metadata<V>(mut self, key: &str, value: V) -> GeyserGrpcBuilderResult<Self>
+    where
+        V: TryInto<AsciiMetadataValue, Error = InvalidMetadataValue>,
+    {
+        self.metadata.push((key.parse()?, value.try_into()?));
+        Ok(self)
+    }
+
+    // Call interceptor for every request, after `x-token` and metadata are added
+    pub fn with_interceptor<F>(mut self, interceptor: F) -> Self
+    where
+        F: Fn(Request<()>) -> Result<Request<()>, Status> + Send + Sync + 'static,
+    {
+        self.interceptors
+            .push(SharedInterceptor(Arc::new(interceptor)));
+        self
+    }
+
+    // Observability callbacks
+    pub fn on_update<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&SubscribeUpdate) + Send + Sync + 'static,
+    {
+        self.hooks.on_update = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn on_error<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Status) + Send + Sync + 'static,
+    {
+        self.hooks.on_error = Some(Arc::new(callback));
+        self
+    }
+
     // Endpoint options
     pub fn connect_timeout(self, dur: Duration) -> Self {
         Self {
@@ -414,9 +625,247 @@ impl GeyserGrpcBuilder {
     }
 }
 
+/// Returns time elapsed since the plugin received the update from the validator,
+/// `None` if `created_at` is not included (see `include_created_at` in `SubscribeRequest`).
+/// Measured against the local wall clock, so clocks should be synchronized.
+pub fn get_update_latency(update: &SubscribeUpdate) -> Option<Duration> {
+    let created_at = SystemTime::try_from(update.created_at?).ok()?;
+    Some(
+        SystemTime::now()
+            .duration_since(created_at)
+            .unwrap_or_default(),
+    )
+}
+
+/// Simple latency histogram with fixed bucket upper bounds
+#[derive(Debug, Clone)]
+pub struct LatencyHistogram {
+    bounds: Vec<Duration>,
+    counts: Vec<u64>,
+    count: u64,
+    sum: Duration,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new(
+            [1, 2, 5, 10, 25, 50, 100, 250, 500, 1_000, 2_500, 5_000]
+                .into_iter()
+                .map(Duration::from_millis)
+                .collect(),
+        )
+    }
+}
+
+impl LatencyHistogram {
+    pub fn new(mut bounds: Vec<Duration>) -> Self {
+        bounds.sort_unstable();
+        bounds.dedup();
+        Self {
+            counts: vec![0; bounds.len() + 1],
+            bounds,
+            count: 0,
+            sum: Duration::ZERO,
+        }
+    }
+
+    pub fn observe(&mut self, latency: Duration) {
+        let index = self.bounds.partition_point(|bound| *bound < latency);
+        self.counts[index] += 1;
+        self.count += 1;
+        self.sum += latency;
+    }
+
+    /// Observe latency of the update, returns `None` if update has no `created_at`
+    pub fn observe_update(&mut self, update: &SubscribeUpdate) -> Option<Duration> {
+        let latency = get_update_latency(update)?;
+        self.observe(latency);
+        Some(latency)
+    }
+
+    pub const fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub const fn sum(&self) -> Duration {
+        self.sum
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        (self.count > 0)
+            .then(|| Duration::from_nanos((self.sum.as_nanos() / self.count as u128) as u64))
+    }
+
+    /// Iterate over buckets as `(upper bound, count)`, last bucket has no upper bound
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<Duration>, u64)> + '_ {
+        self.bounds
+            .iter()
+            .copied()
+            .map(Some)
+            .chain(std::iter::once(None))
+            .zip(self.counts.iter().copied())
+    }
+}
+
+/// Event detected by [`SequenceGapDetector`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceEvent {
+    /// Updates skipped by the server on purpose (e.g. with `on_lag: skip`)
+    Dropped { count: u64 },
+    /// Broadcast batches skipped by the server, matched updates in them are unknown
+    DroppedBatches { count: u64 },
+    /// Updates sent by the server but not received
+    Gap { expected: u64, received: u64 },
+    /// Update with the sequence lower than expected, e.g. duplicated update
+    Reordered { expected: u64, received: u64 },
+}
+
+/// Validates `sequence`, `dropped_since_last` and `dropped_batches_since_last` of received updates. Sequence is per
+/// connection and starts from 1 on every subscribe, so [`SequenceGapDetector::reset`] should
+/// be called on reconnect. Updates without `sequence` (old servers) are not checked.
+#[derive(Debug, Default, Clone)]
+pub struct SequenceGapDetector {
+    last: u64,
+    received: u64,
+    missed: u64,
+    dropped: u64,
+    dropped_batches: u64,
+}
+
+impl SequenceGapDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check the update, events are returned in order: skipped by the server, then gap
+    pub fn observe(&mut self, update: &SubscribeUpdate) -> Vec<SequenceEvent> {
+        let mut events = Vec::new();
+        if update.dropped_since_last > 0 {
+            self.dropped += update.dropped_since_last;
+            events.push(SequenceEvent::Dropped {
+                count: update.dropped_since_last,
+            });
+        }
+        if update.dropped_batches_since_last > 0 {
+            self.dropped_batches += update.dropped_batches_since_last;
+            events.push(SequenceEvent::DroppedBatches {
+                count: update.dropped_batches_since_last,
+            });
+        }
+
+        if update.sequence == 0 {
+            return events;
+        }
+        self.received += 1;
+        let expected = self.last + 1;
+        if update.sequence > expected {
+            self.missed += update.sequence - expected;
+            events.push(SequenceEvent::Gap {
+                expected,
+                received: update.sequence,
+            });
+        } else if update.sequence < expected {
+            events.push(SequenceEvent::Reordered {
+                expected,
+                received: update.sequence,
+            });
+        }
+        self.last = self.last.max(update.sequence);
+        events
+    }
+
+    /// Start a new stream, counters are kept
+    pub fn reset(&mut self) {
+        self.last = 0;
+    }
+
+    /// Last received sequence of the current stream
+    pub const fn last(&self) -> u64 {
+        self.last
+    }
+
+    /// Received updates with sequence
+    pub const fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// Updates sent by the server but not received
+    pub const fn missed(&self) -> u64 {
+        self.missed
+    }
+
+    /// Updates skipped by the server on purpose
+    pub const fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Broadcast batches skipped by the server
+    pub const fn dropped_batches(&self) -> u64 {
+        self.dropped_batches
+    }
+}
+
+/// Bloom of received transaction signatures for `SubscribeRequest.seen_signatures_bloom`,
+/// transactions already processed by the client are not sent again after reconnect
+#[derive(Debug, Clone)]
+pub struct SeenSignaturesBloomBuilder {
+    bloom: SignaturesBloom,
+}
+
+impl SeenSignaturesBloomBuilder {
+    /// Bloom sized for `expected_signatures` with the given false positive rate, a not seen
+    /// transaction is suppressed by the server with this probability
+    pub fn new(expected_signatures: usize, false_positive_rate: f64) -> Self {
+        Self {
+            bloom: SignaturesBloom::with_false_positive_rate(
+                expected_signatures,
+                false_positive_rate,
+            ),
+        }
+    }
+
+    pub fn insert(&mut self, signature: impl AsRef<[u8]>) -> &mut Self {
+        self.bloom.insert(signature.as_ref());
+        self
+    }
+
+    pub fn contains(&self, signature: impl AsRef<[u8]>) -> bool {
+        self.bloom.contains(signature.as_ref())
+    }
+
+    pub fn num_bytes(&self) -> usize {
+        self.bloom.num_bytes()
+    }
+
+    pub fn build(self) -> SubscribeRequestSeenSignaturesBloom {
+        self.bloom.into()
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::GeyserGrpcClient;
+    use {
+        super::{
+            get_update_latency, GeyserGrpcBuilderError, GeyserGrpcClient, LatencyHistogram,
+            SeenSignaturesBloomBuilder, SequenceEvent, SequenceGapDetector,
+        },
+        crate::mock::{MockGeyser, MOCK_SLOT},
+        futures::stream::StreamExt,
+        std::{
+            sync::{
+                atomic::{AtomicUsize, Ordering},
+                mpsc, Arc,
+            },
+            time::{Duration, SystemTime},
+        },
+        tonic::{metadata::MetadataValue, Code, Status},
+        yellowstone_grpc_proto::{
+            bloom::SignaturesBloom,
+            prelude::{SubscribeRequest, SubscribeUpdate},
+            prost::Message,
+            prost_types::Timestamp,
+        },
+    };
 
     #[tokio::test]
     async fn test_channel_https_success() {
@@ -485,4 +934,211 @@ mod tests {
                 .to_owned()
         );
     }
+
+    #[tokio::test]
+    async fn test_interceptor_and_hooks() {
+        let (metadata_tx, metadata_rx) = mpsc::channel();
+        let addr = MockGeyser {
+            updates: vec![(Duration::ZERO, SubscribeUpdate::default()); 3],
+            metadata_tx: Some(metadata_tx),
+            ..Default::default()
+        }
+        .spawn()
+        .await;
+
+        let updates = Arc::new(AtomicUsize::new(0));
+        let errors = Arc::new(AtomicUsize::new(0));
+        let mut client = GeyserGrpcClient::build_from_shared(format!("http://{addr}"))
+            .expect("valid endpoint")
+            .x_token(Some("token"))
+            .expect("valid token")
+            .with_metadata("x-trace-id", "trace")
+            .expect("valid metadata")
+            .with_interceptor(|mut request| {
+                request
+                    .metadata_mut()
+                    .insert("x-intercepted", MetadataValue::from_static("true"));
+                Ok(request)
+            })
+            .on_update({
+                let updates = Arc::clone(&updates);
+                move |_update| {
+                    updates.fetch_add(1, Ordering::Relaxed);
+                }
+            })
+            .on_error({
+                let errors = Arc::clone(&errors);
+                move |_status| {
+                    errors.fetch_add(1, Ordering::Relaxed);
+                }
+            })
+            .connect()
+            .await
+            .expect("connected");
+
+        // unary and streaming calls carry the same metadata
+        let slot = client.get_slot(None).await.expect("slot");
+        assert_eq!(slot.slot, MOCK_SLOT);
+        let stream = client
+            .subscribe_once(SubscribeRequest::default())
+            .await
+            .expect("subscribed");
+        assert_eq!(stream.count().await, 3);
+        for _ in 0..2 {
+            let metadata = metadata_rx
+                .recv_timeout(Duration::from_secs(5))
+                .expect("request metadata");
+            assert_eq!(metadata.get("x-token").expect("x-token"), "token");
+            assert_eq!(metadata.get("x-trace-id").expect("x-trace-id"), "trace");
+            assert_eq!(
+                metadata.get("x-intercepted").expect("x-intercepted"),
+                "true"
+            );
+        }
+        assert_eq!(updates.load(Ordering::Relaxed), 3);
+        assert_eq!(errors.load(Ordering::Relaxed), 0);
+
+        // interceptor error fails the call without sending the request
+        let mut client = GeyserGrpcClient::build_from_shared(format!("http://{addr}"))
+            .expect("valid endpoint")
+            .with_metadata("x-reject", "true")
+            .expect("valid metadata")
+            .with_interceptor(|request| {
+                if request.metadata().contains_key("x-reject") {
+                    Err(Status::permission_denied("rejected"))
+                } else {
+                    Ok(request)
+                }
+            })
+            .on_error({
+                let errors = Arc::clone(&errors);
+                move |status| {
+                    assert_eq!(status.code(), Code::PermissionDenied);
+                    errors.fetch_add(1, Ordering::Relaxed);
+                }
+            })
+            .connect()
+            .await
+            .expect("connected");
+        assert!(client.get_slot(None).await.is_err());
+        assert_eq!(errors.load(Ordering::Relaxed), 1);
+        assert!(metadata_rx.try_recv().is_err());
+
+        let error = GeyserGrpcClient::build_from_shared(format!("http://{addr}"))
+            .expect("valid endpoint")
+            .with_metadata("invalid key", "value")
+            .expect_err("invalid key");
+        assert!(matches!(error, GeyserGrpcBuilderError::MetadataKeyError(_)));
+    }
+
+    #[test]
+    fn test_sequence_gap_detector() {
+        let update = |sequence, dropped_since_last| SubscribeUpdate {
+            sequence,
+            dropped_since_last,
+            ..Default::default()
+        };
+
+        let mut detector = SequenceGapDetector::new();
+        assert!(detector.observe(&update(1, 0)).is_empty());
+        assert_eq!(
+            detector.observe(&update(2, 3)),
+            [SequenceEvent::Dropped { count: 3 }]
+        );
+        assert_eq!(
+            detector.observe(&update(5, 1)),
+            [
+                SequenceEvent::Dropped { count: 1 },
+                SequenceEvent::Gap {
+                    expected: 3,
+                    received: 5
+                }
+            ]
+        );
+        assert_eq!(
+            detector.observe(&update(4, 0)),
+            [SequenceEvent::Reordered {
+                expected: 6,
+                received: 4
+            }]
+        );
+        assert!(detector.observe(&update(6, 0)).is_empty());
+        let mut lost = update(7, 0);
+        lost.dropped_batches_since_last = 2;
+        assert_eq!(
+            detector.observe(&lost),
+            [SequenceEvent::DroppedBatches { count: 2 }]
+        );
+        // updates without sequence are not checked
+        assert!(detector.observe(&update(0, 0)).is_empty());
+        assert_eq!(detector.last(), 7);
+
+        // sequence of the new stream starts from 1
+        detector.reset();
+        assert!(detector.observe(&update(1, 0)).is_empty());
+        assert_eq!(
+            (
+                detector.received(),
+                detector.missed(),
+                detector.dropped(),
+                detector.dropped_batches()
+            ),
+            (7, 2, 4, 2)
+        );
+    }
+
+    #[test]
+    fn test_latency_histogram() {
+        let mut update = SubscribeUpdate::default();
+        assert_eq!(get_update_latency(&update), None);
+
+        update.created_at = Some(Timestamp::from(
+            SystemTime::now() - Duration::from_millis(30),
+        ));
+        let latency = get_update_latency(&update).expect("created_at is set");
+        assert!(latency >= Duration::from_millis(30));
+
+        let mut histogram =
+            LatencyHistogram::new(vec![Duration::from_millis(10), Duration::from_millis(1)]);
+        histogram.observe(Duration::from_millis(1));
+        histogram.observe(Duration::from_millis(5));
+        histogram.observe(Duration::from_millis(20));
+        assert_eq!(
+            histogram.buckets().collect::<Vec<_>>(),
+            vec![
+                (Some(Duration::from_millis(1)), 1),
+                (Some(Duration::from_millis(10)), 1),
+                (None, 1),
+            ]
+        );
+        assert_eq!(histogram.count(), 3);
+        assert_eq!(histogram.sum(), Duration::from_millis(26));
+        assert!(histogram.observe_update(&update).is_some());
+        assert_eq!(histogram.count(), 4);
+    }
+
+    #[test]
+    fn test_seen_signatures_bloom_round_trip() {
+        let signature = |seed: u8| -> Vec<u8> {
+            (0..64u8)
+                .map(|index| seed.wrapping_mul(31).wrapping_add(index.wrapping_mul(7)))
+                .collect()
+        };
+
+        let mut builder = SeenSignaturesBloomBuilder::new(100, 0.01);
+        for seed in 0..100 {
+            builder.insert(signature(seed));
+        }
+        assert!(builder.num_bytes() > 0);
+
+        let request = SubscribeRequest {
+            seen_signatures_bloom: Some(builder.build()),
+            ..Default::default()
+        };
+        let request =
+            SubscribeRequest::decode(request.encode_to_vec().as_slice()).expect("valid request");
+        let bloom = SignaturesBloom::try_from(request.seen_signatures_bloom.expect("bloom is set"))
+            .expect("valid bloom");
+        assert!((0..100).all(|seed| bloom.contains(&signature(seed))));
+    }
 }
@@ -0,0 +1,168 @@
+//! In-process Geyser server for tests.
+
+use {
+    std::{net::SocketAddr, pin::Pin, sync::mpsc as std_mpsc, time::Duration},
+    tokio::{net::TcpListener, sync::mpsc, time::sleep},
+    tokio_stream::{
+        wrappers::{ReceiverStream, TcpListenerStream},
+        Stream,
+    },
+    tonic::{metadata::MetadataMap, transport::Server, Request, Response, Status, Streaming},
+    yellowstone_grpc_proto::prelude::{
+        geyser_server::{Geyser, GeyserServer},
+        GetAccountsSnapshotRequest, GetAccountsSnapshotResponse, GetBlockHeightRequest,
+        GetBlockHeightResponse, GetLatestBlockhashRequest, GetLatestBlockhashResponse,
+        GetSlotAncestryRequest, GetSlotAncestryResponse, GetSlotRequest, GetSlotResponse,
+        GetSlotStatusRequest, GetSlotStatusResponse, GetTransactionStatusRequest,
+        GetTransactionStatusResponse, GetVersionRequest, GetVersionResponse,
+        IsBlockhashValidRequest, IsBlockhashValidResponse, PingRequest, PongResponse,
+        SubscribeRequest, SubscribeUpdate,
+    },
+};
+
+pub const MOCK_SLOT: u64 = 42;
+pub const MOCK_BLOCKHASH: &str = "blockhash";
+
+/// Sends `updates` to every subscription, each after its delay
+#[derive(Debug, Default, Clone)]
+pub struct MockGeyser {
+    pub updates: Vec<(Duration, SubscribeUpdate)>,
+    /// Keep the stream open after all updates are sent
+    pub keep_open: bool,
+    /// Notified once the kept open stream is cancelled by the client
+    pub cancelled_tx: Option<std_mpsc::Sender<()>>,
+    /// Receives metadata of every request
+    pub metadata_tx: Option<std_mpsc::Sender<MetadataMap>>,
+}
+
+impl MockGeyser {
+    /// Bind random port and serve on the current runtime
+    pub async fn spawn(self) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().expect("local address");
+        let metadata_tx = self.metadata_tx.clone();
+        tokio::spawn(
+            Server::builder()
+                .add_service(GeyserServer::with_interceptor(
+                    self,
+                    move |request: Request<()>| {
+                        if let Some(metadata_tx) = &metadata_tx {
+                            let _ = metadata_tx.send(request.metadata().clone());
+                        }
+                        Ok(request)
+                    },
+                ))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        addr
+    }
+}
+
+#[tonic::async_trait]
+impl Geyser for MockGeyser {
+    type SubscribeStream =
+        Pin<Box<dyn Stream<Item = Result<SubscribeUpdate, Status>> + Send + 'static>>;
+
+    async fn subscribe(
+        &self,
+        _request: Request<Streaming<SubscribeRequest>>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let (tx, rx) = mpsc::channel(16);
+        let mock = self.clone();
+        tokio::spawn(async move {
+            for (delay, update) in mock.updates {
+                sleep(delay).await;
+                if tx.send(Ok(update)).await.is_err() {
+                    break;
+                }
+            }
+            if mock.keep_open {
+                tx.closed().await;
+                if let Some(cancelled_tx) = mock.cancelled_tx {
+                    let _ = cancelled_tx.send(());
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+
+    async fn ping(&self, request: Request<PingRequest>) -> Result<Response<PongResponse>, Status> {
+        Ok(Response::new(PongResponse {
+            count: request.into_inner().count,
+            ..Default::default()
+        }))
+    }
+
+    async fn get_latest_blockhash(
+        &self,
+        _request: Request<GetLatestBlockhashRequest>,
+    ) -> Result<Response<GetLatestBlockhashResponse>, Status> {
+        Ok(Response::new(GetLatestBlockhashResponse {
+            slot: MOCK_SLOT,
+            blockhash: MOCK_BLOCKHASH.to_owned(),
+            last_valid_block_height: MOCK_SLOT + 150,
+        }))
+    }
+
+    async fn get_block_height(
+        &self,
+        _request: Request<GetBlockHeightRequest>,
+    ) -> Result<Response<GetBlockHeightResponse>, Status> {
+        Err(Status::unimplemented("mock"))
+    }
+
+    async fn get_slot(
+        &self,
+        _request: Request<GetSlotRequest>,
+    ) -> Result<Response<GetSlotResponse>, Status> {
+        Ok(Response::new(GetSlotResponse { slot: MOCK_SLOT }))
+    }
+
+    async fn is_blockhash_valid(
+        &self,
+        request: Request<IsBlockhashValidRequest>,
+    ) -> Result<Response<IsBlockhashValidResponse>, Status> {
+        Ok(Response::new(IsBlockhashValidResponse {
+            slot: MOCK_SLOT,
+            valid: request.into_inner().blockhash == MOCK_BLOCKHASH,
+            known: true,
+        }))
+    }
+
+    async fn get_version(
+        &self,
+        _request: Request<GetVersionRequest>,
+    ) -> Result<Response<GetVersionResponse>, Status> {
+        Ok(Response::new(GetVersionResponse {
+            version: "mock".to_owned(),
+        }))
+    }
+
+    async fn get_slot_status(
+        &self,
+        _request: Request<GetSlotStatusRequest>,
+    ) -> Result<Response<GetSlotStatusResponse>, Status> {
+        Err(Status::unimplemented("mock"))
+    }
+
+    async fn get_accounts_snapshot(
+        &self,
+        _request: Request<GetAccountsSnapshotRequest>,
+    ) -> Result<Response<GetAccountsSnapshotResponse>, Status> {
+        Err(Status::unimplemented("mock"))
+    }
+
+    async fn get_transaction_status(
+        &self,
+        _request: Request<GetTransactionStatusRequest>,
+    ) -> Result<Response<GetTransactionStatusResponse>, Status> {
+        Err(Status::unimplemented("mock"))
+    }
+
+    async fn get_slot_ancestry(
+        &self,
+        _request: Request<GetSlotAncestryRequest>,
+    ) -> Result<Response<GetSlotAncestryResponse>, Status> {
+        Err(Status::unimplemented("mock"))
+    }
+}
@@ -0,0 +1,790 @@
+//! Subscription to multiple endpoints with the same request, updates are deduplicated.
+
+use {
+    crate::GeyserGrpcBuilder,
+    futures::{
+        channel::mpsc,
+        future::join_all,
+        sink::{Sink, SinkExt},
+        stream::{self, BoxStream, SelectAll, Stream, StreamExt},
+    },
+    std::{
+        collections::{BTreeMap, HashMap, VecDeque},
+        pin::Pin,
+        task::{Context, Poll},
+        time::{Duration, Instant},
+    },
+    tonic::Status,
+    yellowstone_grpc_proto::prelude::{
+        subscribe_update::UpdateOneof, SlotStatus, SubscribeRequest, SubscribeRequestFilterSlots,
+        SubscribeUpdate,
+    },
+};
+
+/// Name of the slots filter added to the request to track finalized slots,
+/// updates matched only by this filter are not delivered
+pub const FINALIZED_SLOTS_FILTER: &str = "__multi_geyser_finalized";
+
+#[derive(Debug, thiserror::Error)]
+pub enum MultiGeyserClientError {
+    #[error("no endpoints")]
+    NoEndpoints,
+    #[error("all endpoints failed: {0}")]
+    AllEndpointsFailed(String),
+}
+
+pub type MultiGeyserClientResult<T> = Result<T, MultiGeyserClientError>;
+
+/// When the update is delivered
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MultiGeyserPolicy {
+    /// Deliver the first received copy, later copies are dropped
+    #[default]
+    FastestWins,
+    /// Deliver once the update is received from the number of endpoints, the number is capped by
+    /// connected endpoints
+    Quorum(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MultiGeyserConfig {
+    /// Updates are deduplicated for slots up to this distance behind the last finalized slot,
+    /// older updates are dropped
+    pub dedup_window_slots: u64,
+    /// Updates without slot (pongs, end of startup) are deduplicated within this time since the
+    /// first copy, a pong for a ping with the same id sent later is delivered again
+    pub dedup_window_unslotted: Duration,
+    pub policy: MultiGeyserPolicy,
+}
+
+impl Default for MultiGeyserConfig {
+    fn default() -> Self {
+        Self {
+            dedup_window_slots: 32,
+            dedup_window_unslotted: Duration::from_secs(10),
+            policy: MultiGeyserPolicy::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EndpointStatus {
+    Connected,
+    Disconnected(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct EndpointHealth {
+    pub endpoint: String,
+    pub status: EndpointStatus,
+    /// Updates received from the endpoint, including duplicates
+    pub received: u64,
+    /// Delivered updates first received from the endpoint
+    pub delivered: u64,
+    /// Highest slot received from the endpoint
+    pub slot: u64,
+    /// Distance between the highest slot of all endpoints and the endpoint slot
+    pub slot_lag: u64,
+    /// Delay behind the first copy, measured on the last duplicated update
+    pub lag: Option<Duration>,
+}
+
+type EndpointSink = Pin<Box<dyn Sink<SubscribeRequest, Error = mpsc::SendError> + Send>>;
+
+// `None` is sent once the endpoint stream is finished
+type EndpointStream = BoxStream<'static, (usize, Option<Result<SubscribeUpdate, Status>>)>;
+
+struct Endpoint {
+    health: EndpointHealth,
+    sink: Option<EndpointSink>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum DedupKey {
+    Account { pubkey: Vec<u8>, write_version: u64 },
+    Slot(i32),
+    Transaction(Vec<u8>),
+    TransactionStatus(Vec<u8>),
+    Block,
+    BlockMeta,
+    BlockDiagnostic,
+    Votes,
+    SlotStats,
+    Rollback,
+    Entry(u64),
+    TransactionSlotDone,
+    EndOfStartup,
+    Pong(i32),
+}
+
+impl DedupKey {
+    /// Returns slot and key, `None` if the update is not deduplicated
+    fn from_update(update: &SubscribeUpdate) -> Option<(Option<u64>, Self)> {
+        Some(match update.update_oneof.as_ref()? {
+            UpdateOneof::Account(msg) => {
+                let account = msg.account.as_ref()?;
+                (
+                    Some(msg.slot),
+                    Self::Account {
+                        pubkey: account.pubkey.clone(),
+                        write_version: account.write_version,
+                    },
+                )
+            }
+            UpdateOneof::Slot(msg) => (Some(msg.slot), Self::Slot(msg.status)),
+            UpdateOneof::Transaction(msg) => (
+                Some(msg.slot),
+                Self::Transaction(msg.transaction.as_ref()?.signature.clone()),
+            ),
+            UpdateOneof::TransactionStatus(msg) => (
+                Some(msg.slot),
+                Self::TransactionStatus(msg.signature.clone()),
+            ),
+            UpdateOneof::Block(msg) => (Some(msg.slot), Self::Block),
+            UpdateOneof::BlockMeta(msg) => (Some(msg.slot), Self::BlockMeta),
+            UpdateOneof::BlockDiagnostic(msg) => (Some(msg.slot), Self::BlockDiagnostic),
+            UpdateOneof::Votes(msg) => (Some(msg.slot), Self::Votes),
+            UpdateOneof::SlotStats(msg) => (Some(msg.slot), Self::SlotStats),
+            UpdateOneof::Rollback(msg) => (Some(msg.slot), Self::Rollback),
+            UpdateOneof::Entry(msg) => (Some(msg.slot), Self::Entry(msg.index)),
+            UpdateOneof::TransactionSlotDone(msg) => (Some(msg.slot), Self::TransactionSlotDone),
+            UpdateOneof::EndOfStartup(_) => (None, Self::EndOfStartup),
+            UpdateOneof::Pong(msg) => (None, Self::Pong(msg.id)),
+            UpdateOneof::Ping(_)
+            | UpdateOneof::Lost(_)
+            | UpdateOneof::ReplayDone(_)
+            | UpdateOneof::QuotaWarning(_)
+            | UpdateOneof::StreamInfo(_)
+            | UpdateOneof::Maintenance(_) => return None,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct DedupEntry {
+    first_seen: Instant,
+    seen_by: Vec<usize>,
+    delivered: bool,
+    // first copy, kept until the quorum is reached
+    pending: Option<SubscribeUpdate>,
+}
+
+#[derive(Debug, Default)]
+struct DedupWindow {
+    min_slot: u64,
+    slots: BTreeMap<u64, HashMap<DedupKey, DedupEntry>>,
+    unslotted: HashMap<DedupKey, DedupEntry>,
+}
+
+impl DedupWindow {
+    fn on_finalized(&mut self, slot: u64, window: u64) {
+        let min_slot = slot.saturating_sub(window);
+        if min_slot > self.min_slot {
+            self.min_slot = min_slot;
+            self.slots = self.slots.split_off(&min_slot);
+        }
+    }
+
+    fn on_unslotted(&mut self, now: Instant, window: Duration) {
+        self.unslotted
+            .retain(|_key, entry| now.duration_since(entry.first_seen) < window);
+    }
+
+    fn entries_mut(&mut self) -> impl Iterator<Item = &mut DedupEntry> {
+        self.slots
+            .values_mut()
+            .flat_map(|entries| entries.values_mut())
+            .chain(self.unslotted.values_mut())
+    }
+}
+
+/// Client subscribed to multiple endpoints with the same request.
+///
+/// Stream yields the union of updates from all endpoints, every update is delivered once
+/// according to [`MultiGeyserPolicy`]. Disconnected endpoints are not reconnected, the stream
+/// ends once all endpoints are disconnected.
+pub struct MultiGeyserClient {
+    config: MultiGeyserConfig,
+    endpoints: Vec<Endpoint>,
+    streams: SelectAll<EndpointStream>,
+    window: DedupWindow,
+    ready: VecDeque<SubscribeUpdate>,
+    max_slot: u64,
+}
+
+impl std::fmt::Debug for MultiGeyserClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MultiGeyserClient")
+            .field("config", &self.config)
+            .field("health", &self.health())
+            .finish()
+    }
+}
+
+impl MultiGeyserClient {
+    /// Connect and subscribe to all endpoints, fails only if no endpoint is subscribed
+    pub async fn subscribe(
+        builders: Vec<GeyserGrpcBuilder>,
+        request: SubscribeRequest,
+        config: MultiGeyserConfig,
+    ) -> MultiGeyserClientResult<Self> {
+        if builders.is_empty() {
+            return Err(MultiGeyserClientError::NoEndpoints);
+        }
+
+        let request = Self::with_finalized_filter(request);
+        let results = join_all(builders.into_iter().map(|builder| {
+            let request = request.clone();
+            async move {
+                let endpoint = builder.endpoint.uri().to_string();
+                let result = match builder.connect().await {
+                    Ok(mut client) => client
+                        .subscribe_with_request(Some(request))
+                        .await
+                        .map(|(sink, stream)| {
+                            let sink: EndpointSink = Box::pin(sink);
+                            (sink, stream.boxed())
+                        })
+                        .map_err(|error| error.to_string()),
+                    Err(error) => Err(error.to_string()),
+                };
+                (endpoint, result)
+            }
+        }))
+        .await;
+
+        let mut endpoints = Vec::with_capacity(results.len());
+        let mut streams = SelectAll::new();
+        let mut errors = vec![];
+        for (idx, (endpoint, result)) in results.into_iter().enumerate() {
+            let (status, sink) = match result {
+                Ok((sink, stream)) => {
+                    streams.push(
+                        stream
+                            .map(move |item| (idx, Some(item)))
+                            .chain(stream::once(async move { (idx, None) }))
+                            .boxed(),
+                    );
+                    (EndpointStatus::Connected, Some(sink))
+                }
+                Err(error) => {
+                    errors.push(format!("{endpoint}: {error}"));
+                    (EndpointStatus::Disconnected(error), None)
+                }
+            };
+            endpoints.push(Endpoint {
+                health: EndpointHealth {
+                    endpoint,
+                    status,
+                    received: 0,
+                    delivered: 0,
+                    slot: 0,
+                    slot_lag: 0,
+                    lag: None,
+                },
+                sink,
+            });
+        }
+        if streams.is_empty() {
+            return Err(MultiGeyserClientError::AllEndpointsFailed(
+                errors.join(", "),
+            ));
+        }
+
+        Ok(Self {
+            config,
+            endpoints,
+            streams,
+            window: DedupWindow::default(),
+            ready: VecDeque::new(),
+            max_slot: 0,
+        })
+    }
+
+    /// Send new request to all connected endpoints, fails only if no endpoint accepted it
+    pub async fn update_subscription(
+        &mut self,
+        request: SubscribeRequest,
+    ) -> MultiGeyserClientResult<()> {
+        let request = Self::with_finalized_filter(request);
+        let mut sent = false;
+        let mut errors = vec![];
+        for endpoint in self.endpoints.iter_mut() {
+            let Some(sink) = endpoint.sink.as_mut() else {
+                continue;
+            };
+            match sink.send(request.clone()).await {
+                Ok(()) => sent = true,
+                Err(error) => {
+                    errors.push(format!("{}: {error}", endpoint.health.endpoint));
+                    endpoint.sink = None;
+                    endpoint.health.status = EndpointStatus::Disconnected(error.to_string());
+                }
+            }
+        }
+        if sent {
+            Ok(())
+        } else {
+            Err(MultiGeyserClientError::AllEndpointsFailed(
+                errors.join(", "),
+            ))
+        }
+    }
+
+    /// Health of endpoints in the order of builders passed to [`MultiGeyserClient::subscribe`]
+    pub fn health(&self) -> Vec<EndpointHealth> {
+        self.endpoints
+            .iter()
+            .map(|endpoint| EndpointHealth {
+                slot_lag: self.max_slot.saturating_sub(endpoint.health.slot),
+                ..endpoint.health.clone()
+            })
+            .collect()
+    }
+
+    fn with_finalized_filter(mut request: SubscribeRequest) -> SubscribeRequest {
+        request.slots.insert(
+            FINALIZED_SLOTS_FILTER.to_owned(),
+            SubscribeRequestFilterSlots {
+                statuses: vec![SlotStatus::SlotFinalized as i32],
+                ..Default::default()
+            },
+        );
+        request
+    }
+
+    fn quorum(&self) -> usize {
+        match self.config.policy {
+            MultiGeyserPolicy::FastestWins => 1,
+            MultiGeyserPolicy::Quorum(quorum) => {
+                let connected = self
+                    .endpoints
+                    .iter()
+                    .filter(|endpoint| endpoint.health.status == EndpointStatus::Connected)
+                    .count();
+                quorum.min(connected).max(1)
+            }
+        }
+    }
+
+    fn handle_update(&mut self, idx: usize, mut update: SubscribeUpdate) {
+        self.endpoints[idx].health.received += 1;
+
+        if let Some(pos) = update
+            .filters
+            .iter()
+            .position(|name| name == FINALIZED_SLOTS_FILTER)
+        {
+            update.filters.remove(pos);
+            if let Some(UpdateOneof::Slot(msg)) = &update.update_oneof {
+                if msg.status == SlotStatus::SlotFinalized as i32 {
+                    self.window
+                        .on_finalized(msg.slot, self.config.dedup_window_slots);
+                }
+            }
+            if update.filters.is_empty() {
+                return;
+            }
+        }
+
+        if matches!(update.update_oneof, Some(UpdateOneof::Ping(_))) {
+            return;
+        }
+        let Some((slot, key)) = DedupKey::from_update(&update) else {
+            self.ready.push_back(update);
+            return;
+        };
+
+        let quorum = self.quorum();
+        let now = Instant::now();
+        let entries = match slot {
+            Some(slot) => {
+                let health = &mut self.endpoints[idx].health;
+                health.slot = health.slot.max(slot);
+                self.max_slot = self.max_slot.max(slot);
+                if slot < self.window.min_slot {
+                    return;
+                }
+                self.window.slots.entry(slot).or_default()
+            }
+            None => {
+                self.window
+                    .on_unslotted(now, self.config.dedup_window_unslotted);
+                &mut self.window.unslotted
+            }
+        };
+
+        let entry = entries.entry(key).or_insert_with(|| DedupEntry {
+            first_seen: now,
+            seen_by: vec![],
+            delivered: false,
+            pending: None,
+        });
+        if entry.seen_by.contains(&idx) {
+            return;
+        }
+        if !entry.seen_by.is_empty() {
+            self.endpoints[idx].health.lag = Some(now.duration_since(entry.first_seen));
+        }
+        entry.seen_by.push(idx);
+        if entry.delivered {
+            return;
+        }
+
+        if entry.seen_by.len() >= quorum {
+            entry.delivered = true;
+            self.endpoints[entry.seen_by[0]].health.delivered += 1;
+            self.ready.push_back(entry.pending.take().unwrap_or(update));
+        } else if entry.pending.is_none() {
+            entry.pending = Some(update);
+        }
+    }
+
+    fn handle_disconnect(&mut self, idx: usize, reason: String) {
+        let endpoint = &mut self.endpoints[idx];
+        if endpoint.health.status != EndpointStatus::Connected {
+            return;
+        }
+        endpoint.health.status = EndpointStatus::Disconnected(reason);
+        endpoint.sink = None;
+
+        // quorum can be reached with less connected endpoints
+        let quorum = self.quorum();
+        for entry in self.window.entries_mut() {
+            if !entry.delivered && entry.seen_by.len() >= quorum {
+                if let Some(update) = entry.pending.take() {
+                    entry.delivered = true;
+                    self.endpoints[entry.seen_by[0]].health.delivered += 1;
+                    self.ready.push_back(update);
+                }
+            }
+        }
+    }
+}
+
+impl Stream for MultiGeyserClient {
+    type Item = SubscribeUpdate;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(update) = self.ready.pop_front() {
+                return Poll::Ready(Some(update));
+            }
+
+            match self.streams.poll_next_unpin(cx) {
+                Poll::Ready(Some((idx, Some(Ok(update))))) => self.handle_update(idx, update),
+                Poll::Ready(Some((idx, Some(Err(status))))) => {
+                    self.handle_disconnect(idx, status.to_string())
+                }
+                Poll::Ready(Some((idx, None))) => {
+                    self.handle_disconnect(idx, "stream finished".to_owned())
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{
+            EndpointStatus, MultiGeyserClient, MultiGeyserConfig, MultiGeyserPolicy,
+            FINALIZED_SLOTS_FILTER,
+        },
+        crate::{mock::MockGeyser, GeyserGrpcClient},
+        futures::stream::StreamExt,
+        std::time::Duration,
+        tokio::time::timeout,
+        yellowstone_grpc_proto::prelude::{
+            subscribe_update::UpdateOneof, SlotStatus, SubscribeRequest, SubscribeUpdate,
+            SubscribeUpdateAccount, SubscribeUpdateAccountInfo, SubscribeUpdatePong,
+            SubscribeUpdateSlot, SubscribeUpdateTransaction, SubscribeUpdateTransactionInfo,
+        },
+    };
+
+    fn account(slot: u64, pubkey: u8, write_version: u64) -> SubscribeUpdate {
+        SubscribeUpdate {
+            filters: vec!["client".to_owned()],
+            update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                account: Some(SubscribeUpdateAccountInfo {
+                    pubkey: vec![pubkey; 32],
+                    write_version,
+                    ..Default::default()
+                }),
+                slot,
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn transaction(slot: u64, signature: u8) -> SubscribeUpdate {
+        SubscribeUpdate {
+            filters: vec!["client".to_owned()],
+            update_oneof: Some(UpdateOneof::Transaction(SubscribeUpdateTransaction {
+                transaction: Some(SubscribeUpdateTransactionInfo {
+                    signature: vec![signature; 64],
+                    ..Default::default()
+                }),
+                slot,
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn slot(slot: u64, status: SlotStatus, filters: &[&str]) -> SubscribeUpdate {
+        SubscribeUpdate {
+            filters: filters.iter().map(|name| name.to_string()).collect(),
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot,
+                status: status as i32,
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn pong(id: i32) -> SubscribeUpdate {
+        SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Pong(SubscribeUpdatePong { id })),
+            ..Default::default()
+        }
+    }
+
+    fn delayed(delay_ms: u64, updates: Vec<SubscribeUpdate>) -> Vec<(Duration, SubscribeUpdate)> {
+        updates
+            .into_iter()
+            .map(|update| (Duration::from_millis(delay_ms), update))
+            .collect()
+    }
+
+    async fn subscribe(mocks: Vec<MockGeyser>, policy: MultiGeyserPolicy) -> MultiGeyserClient {
+        let mut builders = vec![];
+        for mock in mocks {
+            let addr = mock.spawn().await;
+            builders.push(
+                GeyserGrpcClient::build_from_shared(format!("http://{addr}"))
+                    .expect("valid endpoint"),
+            );
+        }
+        let config = MultiGeyserConfig {
+            dedup_window_slots: 2,
+            dedup_window_unslotted: Duration::from_millis(200),
+            policy,
+        };
+        MultiGeyserClient::subscribe(builders, SubscribeRequest::default(), config)
+            .await
+            .expect("subscribed")
+    }
+
+    async fn collect(client: &mut MultiGeyserClient) -> Vec<SubscribeUpdate> {
+        timeout(Duration::from_secs(10), client.collect::<Vec<_>>())
+            .await
+            .expect("all streams finished")
+    }
+
+    #[tokio::test]
+    async fn test_fastest_wins_exactly_once() {
+        let updates = vec![
+            account(1, 1, 1),
+            account(1, 1, 2),
+            transaction(1, 1),
+            slot(1, SlotStatus::SlotProcessed, &["client"]),
+            slot(1, SlotStatus::SlotConfirmed, &["client"]),
+        ];
+        let mut updates_b = updates.clone();
+        updates_b.push(account(1, 2, 3));
+        let mut client = subscribe(
+            vec![
+                MockGeyser {
+                    updates: delayed(1, updates.clone()),
+                    ..Default::default()
+                },
+                MockGeyser {
+                    updates: delayed(15, updates_b.clone()),
+                    ..Default::default()
+                },
+            ],
+            MultiGeyserPolicy::FastestWins,
+        )
+        .await;
+
+        let received = collect(&mut client).await;
+        assert_eq!(received, updates_b);
+
+        let health = client.health();
+        assert_eq!(health[0].received, 5);
+        assert_eq!(health[0].delivered, 5);
+        assert_eq!(health[1].received, 6);
+        assert_eq!(health[1].delivered, 1);
+        assert!(health[1].lag.is_some());
+        for endpoint in health {
+            assert_eq!(
+                endpoint.status,
+                EndpointStatus::Disconnected("stream finished".to_owned())
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failover() {
+        let mut client = subscribe(
+            vec![
+                MockGeyser {
+                    updates: delayed(1, vec![account(1, 1, 1)]),
+                    ..Default::default()
+                },
+                MockGeyser {
+                    updates: delayed(20, vec![account(1, 1, 1), account(2, 1, 2)]),
+                    ..Default::default()
+                },
+            ],
+            MultiGeyserPolicy::FastestWins,
+        )
+        .await;
+
+        let first = client.next().await.expect("first update");
+        assert_eq!(first, account(1, 1, 1));
+        assert_eq!(
+            client.health()[1].slot_lag,
+            1,
+            "slow endpoint is behind the fast one"
+        );
+
+        let rest = collect(&mut client).await;
+        assert_eq!(rest, vec![account(2, 1, 2)]);
+        assert_eq!(client.health()[0].slot_lag, 1);
+    }
+
+    #[tokio::test]
+    async fn test_quorum() {
+        let mut client = subscribe(
+            vec![
+                MockGeyser {
+                    updates: delayed(1, vec![account(1, 1, 1), account(1, 2, 1)]),
+                    keep_open: true,
+                    ..Default::default()
+                },
+                MockGeyser {
+                    updates: delayed(20, vec![account(1, 1, 1)]),
+                    keep_open: true,
+                    ..Default::default()
+                },
+            ],
+            MultiGeyserPolicy::Quorum(2),
+        )
+        .await;
+
+        // second account is not confirmed by the second endpoint
+        let first = timeout(Duration::from_secs(10), client.next())
+            .await
+            .expect("quorum is reached");
+        assert_eq!(first, Some(account(1, 1, 1)));
+        assert!(timeout(Duration::from_millis(100), client.next())
+            .await
+            .is_err());
+        assert_eq!(client.health()[0].delivered, 1);
+    }
+
+    #[tokio::test]
+    async fn test_quorum_flush_on_disconnect() {
+        let mut client = subscribe(
+            vec![
+                MockGeyser {
+                    updates: delayed(1, vec![account(1, 1, 1)]),
+                    keep_open: true,
+                    ..Default::default()
+                },
+                MockGeyser {
+                    updates: delayed(50, vec![]),
+                    ..Default::default()
+                },
+            ],
+            MultiGeyserPolicy::Quorum(2),
+        )
+        .await;
+
+        // second endpoint disconnects, quorum is reduced to one connected endpoint
+        let first = timeout(Duration::from_secs(10), client.next())
+            .await
+            .expect("pending update is flushed");
+        assert_eq!(first, Some(account(1, 1, 1)));
+        assert_eq!(
+            client.health()[1].status,
+            EndpointStatus::Disconnected("stream finished".to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_finalized_window() {
+        let updates_a = vec![
+            account(1, 1, 1),
+            slot(5, SlotStatus::SlotFinalized, &[FINALIZED_SLOTS_FILTER]),
+            account(5, 1, 2),
+        ];
+        let updates_b = vec![
+            account(1, 1, 1),
+            slot(
+                5,
+                SlotStatus::SlotFinalized,
+                &["client", FINALIZED_SLOTS_FILTER],
+            ),
+            account(5, 1, 2),
+        ];
+        let mut client = subscribe(
+            vec![
+                MockGeyser {
+                    updates: delayed(1, updates_a),
+                    ..Default::default()
+                },
+                MockGeyser {
+                    updates: delayed(30, updates_b),
+                    ..Default::default()
+                },
+            ],
+            MultiGeyserPolicy::FastestWins,
+        )
+        .await;
+
+        // finalized slot 5 with window of 2 slots drops the late copy of slot 1,
+        // hidden filter is removed from the delivered slot update
+        let received = collect(&mut client).await;
+        assert_eq!(
+            received,
+            vec![
+                account(1, 1, 1),
+                account(5, 1, 2),
+                slot(5, SlotStatus::SlotFinalized, &["client"]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unslotted_window() {
+        let mut client = subscribe(
+            vec![
+                MockGeyser {
+                    updates: vec![
+                        (Duration::from_millis(1), pong(1)),
+                        (Duration::from_millis(600), pong(1)),
+                    ],
+                    ..Default::default()
+                },
+                MockGeyser {
+                    updates: delayed(20, vec![pong(1)]),
+                    ..Default::default()
+                },
+            ],
+            MultiGeyserPolicy::FastestWins,
+        )
+        .await;
+
+        // copy from the second endpoint is deduplicated, the pong of a later ping with the same id
+        // is out of the window and delivered
+        let received = collect(&mut client).await;
+        assert_eq!(received, vec![pong(1), pong(1)]);
+        assert_eq!(client.window.unslotted.len(), 1);
+    }
+}
@@ -0,0 +1,120 @@
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use {
+    common::{TestChain, TestServer, TestSubscription},
+    criterion::{criterion_group, criterion_main, BenchmarkId, Criterion},
+    futures::stream::StreamExt,
+    std::{
+        collections::HashMap,
+        time::{Duration, Instant},
+    },
+    tokio::{runtime::Runtime, sync::oneshot},
+    yellowstone_grpc_proto::{
+        plugin::message::SlotStatus,
+        prelude::{
+            subscribe_update::UpdateOneof, SubscribeRequest, SubscribeRequestFilterAccounts,
+            SubscribeRequestFilterBlocks,
+        },
+    },
+};
+
+const TRANSACTIONS_PER_BLOCK: usize = 256;
+const FINALIZED_LAG: usize = 32;
+
+/// Subscription with blocks filter, updates are drained in own task so the client never lags
+async fn spawn_blocks_subscriber(server: &TestServer) {
+    let mut client = server.connect().await;
+    let (ready_tx, ready_rx) = oneshot::channel();
+    tokio::spawn(async move {
+        let request = SubscribeRequest {
+            blocks: HashMap::from([(
+                "blocks".to_owned(),
+                SubscribeRequestFilterBlocks {
+                    include_transactions: Some(true),
+                    include_accounts: Some(true),
+                    include_entries: Some(true),
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+        let (_requests, mut updates) = client
+            .subscribe_with_request(Some(request))
+            .await
+            .expect("subscribed");
+        let mut ready_tx = Some(ready_tx);
+        while let Some(Ok(update)) = updates.next().await {
+            if matches!(update.update_oneof, Some(UpdateOneof::StreamInfo(_))) {
+                if let Some(ready_tx) = ready_tx.take() {
+                    let _ = ready_tx.send(());
+                }
+            }
+        }
+    });
+    ready_rx.await.expect("blocks subscription is accepted");
+}
+
+/// Time between the send of the block and the processed update of the last account of the block
+async fn processed_latency(iters: u64, blocks_subscriber: bool) -> Duration {
+    let server = TestServer::start().await;
+    if blocks_subscriber {
+        spawn_blocks_subscriber(&server).await;
+    }
+    let mut client = server.connect().await;
+    let mut stream = TestSubscription::new(
+        &mut client,
+        SubscribeRequest {
+            accounts: HashMap::from([(
+                "accounts".to_owned(),
+                SubscribeRequestFilterAccounts::default(),
+            )]),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    let blocks = TestChain::new(100).next_blocks(iters as usize, TRANSACTIONS_PER_BLOCK);
+    let mut elapsed = Duration::ZERO;
+    for (index, block) in blocks.iter().enumerate() {
+        let pubkey = block.accounts.last().expect("account").pubkey.to_bytes();
+        let started = Instant::now();
+        server.send_all(block.messages());
+        if let Some(finalized) = index.checked_sub(FINALIZED_LAG) {
+            server.send(blocks[finalized].status(SlotStatus::Finalized));
+        }
+        stream
+            .next_matching(|update| match update {
+                UpdateOneof::Account(msg)
+                    if msg
+                        .account
+                        .as_ref()
+                        .is_some_and(|account| account.pubkey == pubkey) =>
+                {
+                    Some(())
+                }
+                _ => None,
+            })
+            .await;
+        elapsed += started.elapsed();
+    }
+    elapsed
+}
+
+fn bench_processed_latency(c: &mut Criterion) {
+    let runtime = Runtime::new().expect("failed to create tokio runtime");
+    let mut group = c.benchmark_group("processed_latency");
+    for blocks_subscriber in [false, true] {
+        group.bench_with_input(
+            BenchmarkId::new("blocks_subscriber", blocks_subscriber),
+            &blocks_subscriber,
+            |b, &blocks_subscriber| {
+                b.iter_custom(|iters| runtime.block_on(processed_latency(iters, blocks_subscriber)))
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_processed_latency);
+criterion_main!(benches);
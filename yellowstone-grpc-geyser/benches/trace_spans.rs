@@ -0,0 +1,32 @@
+use {
+    criterion::{black_box, criterion_group, criterion_main, Criterion},
+    tracing_subscriber::{layer::SubscriberExt, EnvFilter},
+    yellowstone_grpc_geyser::trace,
+    yellowstone_grpc_proto::plugin::message::{CommitmentLevel, Message, MessageSlot, SlotStatus},
+};
+
+/// Spans of every geyser message and broadcast batch, without `log.otlp` in the config no
+/// subscriber is installed and spans should cost about the same as no spans
+fn bench_trace_spans(c: &mut Criterion) {
+    let message = Message::Slot(MessageSlot::new(42, Some(41), SlotStatus::Processed));
+    let mut group = c.benchmark_group("trace_spans");
+    group.bench_function("without_spans", |b| {
+        b.iter(|| black_box(&message).get_slot())
+    });
+    let with_spans = || {
+        trace::message_span(black_box(&message)).in_scope(|| {
+            trace::batch_span(CommitmentLevel::Processed, 1, 64)
+                .in_scope(|| black_box(&message).get_slot())
+        })
+    };
+    group.bench_function("without_subscriber", |b| b.iter(with_spans));
+    // OTLP export with spans of the plugin disabled by the filter
+    let subscriber = tracing_subscriber::registry().with(EnvFilter::new("info"));
+    tracing::subscriber::with_default(subscriber, || {
+        group.bench_function("filtered", |b| b.iter(with_spans));
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_trace_spans);
+criterion_main!(benches);
@@ -18,6 +18,10 @@ fn main() -> anyhow::Result<()> {
         "cargo:rustc-env=SOLANA_SDK_VERSION={}",
         get_pkg_version(&lockfile, "solana-sdk")
     );
+    println!(
+        "cargo:rustc-env=GEYSER_INTERFACE_VERSION={}",
+        get_pkg_version(&lockfile, "agave-geyser-plugin-interface")
+    );
     println!(
         "cargo:rustc-env=YELLOWSTONE_GRPC_PROTO_VERSION={}",
         get_pkg_version(&lockfile, "yellowstone-grpc-proto")
@@ -0,0 +1,274 @@
+//! Run gRPC service with a synthetic message source instead of the validator.
+//!
+//! Every slot contains one transfer transaction with the updated recipient account, one entry
+//! and block meta. Slots are confirmed right after the block meta and finalized with a lag.
+
+use {
+    clap::Parser,
+    serde_json::json,
+    solana_sdk::{
+        account::Account, clock::Slot, hash::Hash, pubkey::Pubkey, signature::Keypair,
+        system_program, system_transaction, transaction::VersionedTransaction,
+    },
+    solana_transaction_status::TransactionStatusMeta,
+    std::{
+        net::SocketAddr,
+        sync::Arc,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+    tokio::{signal, sync::Notify, time::interval},
+    tracing::info,
+    yellowstone_grpc_geyser::{
+        config::{ConfigGrpc, ConfigTokio},
+        grpc::GrpcService,
+        source::MessagesSender,
+    },
+    yellowstone_grpc_proto::plugin::message::{
+        Message, MessageAccount, MessageAccountInfo, MessageBlockMeta, MessageEntry, MessageSlot,
+        MessageTransaction, MessageTransactionInfo, SlotStatus,
+    },
+};
+
+#[derive(Debug, Parser)]
+#[clap(
+    author,
+    version,
+    about = "Run gRPC service with a synthetic message source"
+)]
+struct Args {
+    #[clap(long, default_value = "127.0.0.1:10000")]
+    /// gRPC service address
+    address: SocketAddr,
+
+    #[clap(long, default_value_t = 1)]
+    /// First produced slot
+    first_slot: Slot,
+
+    #[clap(long, default_value_t = 400)]
+    /// Slot time in milliseconds
+    slot_time: u64,
+}
+
+/// Returns the bound address, so the port `0` is resolved
+async fn spawn_service(
+    address: SocketAddr,
+) -> anyhow::Result<(MessagesSender, Arc<Notify>, SocketAddr)> {
+    let config: ConfigGrpc = serde_json::from_value(json!({ "address": address }))?;
+    let (_snapshot_tx, messages_tx, shutdown, addresses) =
+        GrpcService::create(ConfigTokio::default(), config, None, false).await?;
+    Ok((messages_tx, shutdown, addresses.grpc))
+}
+
+#[derive(Debug)]
+struct SyntheticSource {
+    slot: Slot,
+    blockhash: Hash,
+    payer: Keypair,
+}
+
+impl SyntheticSource {
+    // distance between confirmed and finalized slots
+    const FINALIZED_LAG: u64 = 32;
+
+    fn new(first_slot: Slot) -> Self {
+        Self {
+            slot: first_slot.max(1) - 1,
+            blockhash: Hash::new_unique(),
+            payer: Keypair::new(),
+        }
+    }
+
+    /// Messages of the next slot in the order expected by the service
+    fn next_slot(&mut self) -> Vec<Message> {
+        let parent = self.slot;
+        let parent_blockhash = self.blockhash;
+        self.slot += 1;
+        self.blockhash = Hash::new_unique();
+        let slot = self.slot;
+
+        let recipient = Pubkey::new_unique();
+        let transaction = VersionedTransaction::from(system_transaction::transfer(
+            &self.payer,
+            &recipient,
+            1,
+            parent_blockhash,
+        ));
+        let meta = TransactionStatusMeta {
+            status: Ok(()),
+            fee: 5_000,
+            pre_balances: vec![1_000_000, 0, 1],
+            post_balances: vec![994_999, 1, 1],
+            ..Default::default()
+        };
+        let block_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs() as i64)
+            .unwrap_or_default();
+
+        let mut messages = vec![
+            Message::Slot(MessageSlot::new(slot, Some(parent), SlotStatus::Processed)),
+            Message::Account(MessageAccount::new(
+                MessageAccountInfo::from_account(
+                    recipient,
+                    &Account::new(1, 0, &system_program::id()),
+                    slot,
+                    Some(transaction.signatures[0]),
+                ),
+                slot,
+            )),
+            Message::Transaction(MessageTransaction::new(
+                MessageTransactionInfo::from_transaction(&transaction, &meta, false, 0),
+                slot,
+            )),
+            Message::Entry(Arc::new(MessageEntry::new(
+                slot,
+                0,
+                1,
+                self.blockhash,
+                1,
+                0,
+            ))),
+            Message::BlockMeta(Arc::new(
+                MessageBlockMeta::new(slot, parent, self.blockhash, parent_blockhash, 1, 1)
+                    .with_block_time(block_time)
+                    .with_block_height(slot),
+            )),
+            Message::Slot(MessageSlot::new(slot, Some(parent), SlotStatus::Confirmed)),
+        ];
+        if let Some(finalized) = slot
+            .checked_sub(Self::FINALIZED_LAG)
+            .filter(|slot| *slot > 0)
+        {
+            messages.push(Message::Slot(MessageSlot::new(
+                finalized,
+                Some(finalized - 1),
+                SlotStatus::Finalized,
+            )));
+        }
+        messages
+    }
+
+    fn send_next_slot(&mut self, messages_tx: &MessagesSender) -> anyhow::Result<()> {
+        for message in self.next_slot() {
+            messages_tx.send(message)?;
+        }
+        Ok(())
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    solana_logger::setup_with_default("info");
+
+    let (messages_tx, shutdown, address) = spawn_service(args.address).await?;
+    info!("gRPC service is running on {address}");
+
+    let mut source = SyntheticSource::new(args.first_slot);
+    let mut slot_interval = interval(Duration::from_millis(args.slot_time));
+    loop {
+        tokio::select! {
+            _ = slot_interval.tick() => source.send_next_slot(&messages_tx)?,
+            result = signal::ctrl_c() => {
+                result?;
+                break;
+            }
+        }
+    }
+
+    shutdown.notify_one();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{spawn_service, SyntheticSource},
+        futures::stream::{self, StreamExt},
+        std::{collections::HashMap, time::Duration},
+        tokio::time::{sleep, timeout},
+        yellowstone_grpc_proto::prelude::{
+            geyser_client::GeyserClient, subscribe_update::UpdateOneof, CommitmentLevel,
+            SlotStatus, SubscribeRequest, SubscribeRequestFilterAccounts,
+            SubscribeRequestFilterBlocksMeta, SubscribeRequestFilterSlots,
+            SubscribeRequestFilterTransactions,
+        },
+    };
+
+    #[tokio::test]
+    async fn test_synthetic_source() {
+        let (messages_tx, shutdown, address) = spawn_service(([127, 0, 0, 1], 0).into())
+            .await
+            .expect("service is created");
+
+        let mut client = loop {
+            match GeyserClient::connect(format!("http://{address}")).await {
+                Ok(client) => break client,
+                Err(_error) => sleep(Duration::from_millis(10)).await,
+            }
+        };
+        let request = SubscribeRequest {
+            accounts: HashMap::from([(
+                "all".to_owned(),
+                SubscribeRequestFilterAccounts::default(),
+            )]),
+            slots: HashMap::from([("all".to_owned(), SubscribeRequestFilterSlots::default())]),
+            transactions: HashMap::from([(
+                "all".to_owned(),
+                SubscribeRequestFilterTransactions::default(),
+            )]),
+            blocks_meta: HashMap::from([(
+                "all".to_owned(),
+                SubscribeRequestFilterBlocksMeta::default(),
+            )]),
+            commitment: Some(CommitmentLevel::Confirmed as i32),
+            ..Default::default()
+        };
+        let mut updates = client
+            .subscribe(stream::iter([request]).chain(stream::pending()))
+            .await
+            .expect("subscribed")
+            .into_inner();
+
+        // produce slots until the client is subscribed
+        let source = tokio::spawn(async move {
+            let mut source = SyntheticSource::new(1);
+            loop {
+                source
+                    .send_next_slot(&messages_tx)
+                    .expect("service is alive");
+                sleep(Duration::from_millis(10)).await;
+            }
+        });
+
+        // all messages of the slot are received before the confirmed status
+        let mut received = HashMap::<u64, Vec<&str>>::new();
+        let slot = timeout(Duration::from_secs(10), async {
+            while let Some(update) = updates.next().await {
+                let (slot, kind) = match update.expect("valid update").update_oneof {
+                    Some(UpdateOneof::Slot(msg))
+                        if msg.status == SlotStatus::SlotConfirmed as i32 =>
+                    {
+                        return msg.slot;
+                    }
+                    Some(UpdateOneof::Account(msg)) => (msg.slot, "account"),
+                    Some(UpdateOneof::Transaction(msg)) => (msg.slot, "transaction"),
+                    Some(UpdateOneof::BlockMeta(msg)) => (msg.slot, "block_meta"),
+                    _ => continue,
+                };
+                received.entry(slot).or_default().push(kind);
+            }
+            panic!("stream is closed");
+        })
+        .await
+        .expect("confirmed slot is received");
+        source.abort();
+        shutdown.notify_one();
+
+        let mut kinds = received
+            .remove(&slot)
+            .expect("messages of the confirmed slot");
+        kinds.sort_unstable();
+        assert_eq!(kinds, ["account", "block_meta", "transaction"]);
+    }
+}
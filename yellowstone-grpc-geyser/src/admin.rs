@@ -0,0 +1,374 @@
+//! Admin gRPC service: list connected clients, disconnect them, change the log filter and
+//! announce planned maintenance.
+
+use {
+    crate::{
+        config::ConfigGrpcAdmin,
+        grpc::{StreamTx, StreamUpdate},
+        health::PipelineHealth,
+        metrics,
+    },
+    solana_sdk::clock::Slot,
+    std::{
+        collections::HashMap,
+        future::pending,
+        net::SocketAddr,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex, MutexGuard,
+        },
+        time::{SystemTime, UNIX_EPOCH},
+    },
+    tokio::{
+        net::TcpListener,
+        sync::{mpsc, oneshot, watch, Notify},
+    },
+    tonic::{
+        service::interceptor::interceptor,
+        transport::server::{Server, TcpIncoming},
+        Request, Response, Result as TonicResult, Status,
+    },
+    tracing::info,
+    yellowstone_grpc_proto::{
+        plugin::{
+            filter::{message::EncodedBytesCounter, Filter},
+            message::CommitmentLevel,
+        },
+        prelude::{
+            geyser_admin_server::{GeyserAdmin, GeyserAdminServer},
+            AnnounceMaintenanceRequest, AnnounceMaintenanceResponse, ClientInfo,
+            CommitmentLevel as CommitmentLevelProto, DisconnectClientRequest,
+            DisconnectClientResponse, ListClientsRequest, ListClientsResponse, SetLogFilterRequest,
+            SetLogFilterResponse, SubscribeUpdateMaintenance,
+        },
+    },
+};
+
+/// The latest maintenance announced with `AnnounceMaintenance`, sent to all clients
+pub type MaintenanceRx = watch::Receiver<Option<Arc<SubscribeUpdateMaintenance>>>;
+pub type MaintenanceTx = watch::Sender<Option<Arc<SubscribeUpdateMaintenance>>>;
+
+#[derive(Debug)]
+struct ClientEntry {
+    peer: Option<SocketAddr>,
+    endpoint: String,
+    connected_at: SystemTime,
+    filters: HashMap<String, u64>,
+    commitment: CommitmentLevel,
+    stream_tx: mpsc::WeakSender<StreamUpdate>,
+    bytes_sent: EncodedBytesCounter,
+    pda_derivations: Arc<AtomicU64>,
+    processed_slot: Arc<AtomicU64>,
+    // kept only for `/debug_clients`
+    filter: Option<Box<Filter>>,
+    disconnect_tx: Option<oneshot::Sender<Status>>,
+}
+
+/// Connected clients, entries are added and updated by `client_loop`. Shared by the admin
+/// service and `/debug_clients` of the prometheus server.
+#[derive(Debug, Default)]
+pub struct ClientRegistry {
+    clients: Mutex<HashMap<usize, ClientEntry>>,
+    debug_filters: bool,
+}
+
+impl ClientRegistry {
+    /// Filters of clients are cloned on every update only with `debug_filters`
+    pub fn new(debug_filters: bool) -> Self {
+        Self {
+            clients: Mutex::default(),
+            debug_filters,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<usize, ClientEntry>> {
+        self.clients.lock().expect("unpoisoned mutex")
+    }
+
+    pub fn register(
+        self: &Arc<Self>,
+        id: usize,
+        peer: Option<SocketAddr>,
+        endpoint: &str,
+        stream_tx: &StreamTx,
+        bytes_sent: EncodedBytesCounter,
+    ) -> RegisteredClient {
+        let (disconnect_tx, disconnect_rx) = oneshot::channel();
+        let pda_derivations = Arc::new(AtomicU64::new(0));
+        let processed_slot = Arc::new(AtomicU64::new(0));
+        self.lock().insert(
+            id,
+            ClientEntry {
+                peer,
+                endpoint: endpoint.to_owned(),
+                connected_at: SystemTime::now(),
+                filters: HashMap::new(),
+                commitment: CommitmentLevel::Processed,
+                stream_tx: stream_tx.downgrade(),
+                bytes_sent,
+                pda_derivations: Arc::clone(&pda_derivations),
+                processed_slot: Arc::clone(&processed_slot),
+                filter: None,
+                disconnect_tx: Some(disconnect_tx),
+            },
+        );
+        RegisteredClient {
+            registry: Arc::clone(self),
+            id,
+            disconnect_rx,
+            pda_derivations,
+            processed_slot,
+        }
+    }
+
+    pub fn list(&self) -> Vec<ClientInfo> {
+        let mut clients = self
+            .lock()
+            .iter()
+            .map(|(id, entry)| ClientInfo {
+                id: *id as u64,
+                peer: entry.peer.map(|peer| peer.to_string()).unwrap_or_default(),
+                endpoint: entry.endpoint.clone(),
+                connected_at: entry
+                    .connected_at
+                    .duration_since(UNIX_EPOCH)
+                    .map(|duration| duration.as_millis() as u64)
+                    .unwrap_or_default(),
+                filters: entry.filters.clone(),
+                commitment: CommitmentLevelProto::from(entry.commitment) as i32,
+                queue_depth: entry
+                    .stream_tx
+                    .upgrade()
+                    .map(|tx| (tx.max_capacity() - tx.capacity()) as u64)
+                    .unwrap_or_default(),
+                bytes_sent: entry.bytes_sent.get(),
+                pda_derivations: entry.pda_derivations.load(Ordering::Relaxed),
+            })
+            .collect::<Vec<_>>();
+        clients.sort_by_key(|client| client.id);
+        clients
+    }
+
+    pub fn count(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Processed slot and filter of every client, one client per line
+    pub fn debug_statuses(&self) -> String {
+        let mut statuses = self
+            .lock()
+            .iter()
+            .map(|(id, entry)| {
+                let slot = entry.processed_slot.load(Ordering::Relaxed);
+                let status = match &entry.filter {
+                    Some(filter) => format!("client#{id:06}, {slot}, {filter:?}"),
+                    None => format!("client#{id:06}, {slot}"),
+                };
+                (*id, status)
+            })
+            .collect::<Vec<_>>();
+        statuses.sort();
+        statuses
+            .into_iter()
+            .map(|(_id, status)| status + "\n")
+            .collect()
+    }
+
+    /// Returns `false` if the client is not found or already disconnected
+    pub fn disconnect(&self, id: usize, reason: &str) -> bool {
+        self.lock()
+            .get_mut(&id)
+            .and_then(|entry| entry.disconnect_tx.take())
+            .is_some_and(|tx| tx.send(Status::aborted(reason)).is_ok())
+    }
+}
+
+/// Entry of the client in the registry, removed on drop
+#[derive(Debug)]
+pub struct RegisteredClient {
+    registry: Arc<ClientRegistry>,
+    id: usize,
+    disconnect_rx: oneshot::Receiver<Status>,
+    pda_derivations: Arc<AtomicU64>,
+    processed_slot: Arc<AtomicU64>,
+}
+
+impl Drop for RegisteredClient {
+    fn drop(&mut self) {
+        self.registry.lock().remove(&self.id);
+    }
+}
+
+impl RegisteredClient {
+    pub fn update_filter(&self, filter: &Filter) {
+        if let Some(entry) = self.registry.lock().get_mut(&self.id) {
+            entry.filters = filter
+                .get_metrics()
+                .into_iter()
+                .filter(|(_name, value)| *value > 0)
+                .map(|(name, value)| (name.to_owned(), value as u64))
+                .collect();
+            entry.commitment = filter.get_commitment_level();
+            if self.registry.debug_filters {
+                entry.filter = Some(Box::new(filter.clone()));
+            }
+        }
+    }
+
+    pub fn update_processed_slot(&self, slot: Slot) {
+        self.processed_slot.store(slot, Ordering::Relaxed);
+    }
+
+    pub fn add_pda_derivations(&self, count: u64) {
+        self.pda_derivations.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Resolves with the status for the client once `DisconnectClient` is called
+    pub async fn disconnected(&mut self) -> Status {
+        match (&mut self.disconnect_rx).await {
+            Ok(status) => status,
+            Err(_error) => pending().await,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AdminService {
+    clients: Arc<ClientRegistry>,
+    maintenance_tx: MaintenanceTx,
+    health: Arc<PipelineHealth>,
+}
+
+impl AdminService {
+    pub async fn bind(config: &ConfigGrpcAdmin) -> std::io::Result<TcpListener> {
+        TcpListener::bind(config.address).await
+    }
+
+    /// Serve admin requests until `shutdown` is notified
+    pub fn spawn(
+        config: ConfigGrpcAdmin,
+        listener: TcpListener,
+        clients: Arc<ClientRegistry>,
+        maintenance_tx: MaintenanceTx,
+        health: Arc<PipelineHealth>,
+        shutdown: Arc<Notify>,
+    ) -> anyhow::Result<()> {
+        let incoming = TcpIncoming::from_listener(listener, true, None)
+            .map_err(|error| anyhow::anyhow!(error))?;
+        let x_token = config.x_token;
+        tokio::spawn(async move {
+            Server::builder()
+                .layer(interceptor(move |request: Request<()>| {
+                    match request.metadata().get("x-token") {
+                        Some(token)
+                            if metrics::constant_time_eq(token.as_bytes(), x_token.as_bytes()) =>
+                        {
+                            Ok(request)
+                        }
+                        _ => Err(Status::unauthenticated("No valid auth token")),
+                    }
+                }))
+                .add_service(GeyserAdminServer::new(Self {
+                    clients,
+                    maintenance_tx,
+                    health,
+                }))
+                .serve_with_incoming_shutdown(incoming, shutdown.notified())
+                .await
+        });
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl GeyserAdmin for AdminService {
+    async fn list_clients(
+        &self,
+        _request: Request<ListClientsRequest>,
+    ) -> TonicResult<Response<ListClientsResponse>> {
+        Ok(Response::new(ListClientsResponse {
+            clients: self.clients.list(),
+        }))
+    }
+
+    async fn disconnect_client(
+        &self,
+        request: Request<DisconnectClientRequest>,
+    ) -> TonicResult<Response<DisconnectClientResponse>> {
+        let DisconnectClientRequest { id, reason } = request.into_inner();
+        let reason = if reason.is_empty() {
+            "disconnected by admin".to_owned()
+        } else {
+            reason
+        };
+        if self.clients.disconnect(id as usize, &reason) {
+            info!("admin: client #{id} disconnected: {reason}");
+            Ok(Response::new(DisconnectClientResponse {}))
+        } else {
+            Err(Status::not_found(format!("client #{id} not found")))
+        }
+    }
+
+    async fn set_log_filter(
+        &self,
+        request: Request<SetLogFilterRequest>,
+    ) -> TonicResult<Response<SetLogFilterResponse>> {
+        let directive = request.into_inner().directive;
+        if directive.trim().is_empty() {
+            return Err(Status::invalid_argument("directive should not be empty"));
+        }
+        solana_logger::setup_with(&directive);
+        info!("admin: log filter changed to {directive:?}");
+        Ok(Response::new(SetLogFilterResponse {}))
+    }
+
+    async fn announce_maintenance(
+        &self,
+        request: Request<AnnounceMaintenanceRequest>,
+    ) -> TonicResult<Response<AnnounceMaintenanceResponse>> {
+        let AnnounceMaintenanceRequest {
+            reason,
+            expected_at_slot,
+            drain_deadline_secs,
+            not_serving,
+            clear,
+        } = request.into_inner();
+        let clients = self.clients.count();
+        if clear {
+            info!("admin: maintenance cleared for {clients} clients");
+            // clients which received the announcement are notified with `cancelled`
+            self.maintenance_tx.send_replace(None);
+            self.health.clear_maintenance();
+            return Ok(Response::new(AnnounceMaintenanceResponse {
+                clients: clients as u64,
+            }));
+        }
+
+        let reason = if reason.is_empty() {
+            "planned maintenance".to_owned()
+        } else {
+            reason
+        };
+        info!(
+            "admin: maintenance announced to {clients} clients: {reason}, expected at slot {expected_at_slot:?}, drain deadline {drain_deadline_secs}s, not serving: {not_serving}"
+        );
+        // replaces the previous announcement, clients receive only the latest one
+        self.maintenance_tx
+            .send_replace(Some(Arc::new(SubscribeUpdateMaintenance {
+                reason,
+                expected_at_slot,
+                drain_deadline_secs,
+                announced_at: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|duration| duration.as_millis() as u64)
+                    .unwrap_or_default(),
+                cancelled: false,
+            })));
+        if not_serving {
+            self.health.set_maintenance();
+        }
+        Ok(Response::new(AnnounceMaintenanceResponse {
+            clients: clients as u64,
+        }))
+    }
+}
@@ -1,4 +1,4 @@
-use {clap::Parser, yellowstone_grpc_geyser::config::Config};
+use {clap::Parser, std::process::ExitCode, yellowstone_grpc_geyser::check::ConfigCheckReport};
 
 #[derive(Debug, Parser)]
 #[clap(author, version, about)]
@@ -6,11 +6,38 @@ struct Args {
     #[clap(short, long, default_value_t = String::from("config.json"))]
     /// Path to config
     config: String,
+
+    #[clap(long)]
+    /// Print report as JSON
+    json: bool,
+
+    #[clap(long)]
+    /// Exit with error code on warnings
+    deny_warnings: bool,
 }
 
-fn main() -> anyhow::Result<()> {
+#[tokio::main]
+async fn main() -> anyhow::Result<ExitCode> {
     let args = Args::parse();
-    let _config = Config::load_from_file(args.config)?;
-    println!("Config is OK!");
-    Ok(())
+    let report = ConfigCheckReport::check_file(&args.config).await;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for error in report.errors.iter() {
+            println!("error: {error}");
+        }
+        for warning in report.warnings.iter() {
+            println!("warning: {warning}");
+        }
+        if report.errors.is_empty() {
+            println!("Config is OK!");
+        }
+    }
+
+    Ok(if report.is_ok(args.deny_warnings) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
 }
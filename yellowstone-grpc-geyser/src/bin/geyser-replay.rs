@@ -0,0 +1,83 @@
+use {
+    anyhow::Context,
+    clap::Parser,
+    std::{sync::Arc, time::Duration},
+    tokio::{signal, time::sleep},
+    tracing::info,
+    yellowstone_grpc_geyser::{
+        admin::ClientRegistry, config::Config, grpc::GrpcService, metrics::PrometheusService,
+        record::MessageLogReader,
+    },
+};
+
+#[derive(Debug, Parser)]
+#[clap(
+    author,
+    version,
+    about = "Replay recorded messages through gRPC service"
+)]
+struct Args {
+    #[clap(short, long, default_value_t = String::from("config.json"))]
+    /// Path to config
+    config: String,
+
+    #[clap(short, long)]
+    /// Path to messages recorded with `record_messages_path`
+    messages: String,
+
+    #[clap(long, default_value_t = 1.0)]
+    /// Replay speed relative to the original pace, `0` to send messages without delays
+    speed: f64,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    anyhow::ensure!(args.speed >= 0.0, "speed should be non-negative");
+    let config = Config::load_from_file(&args.config)?;
+    solana_logger::setup_with_default(&config.log.level);
+
+    let debug_clients = config
+        .debug_clients_http
+        .then(|| Arc::new(ClientRegistry::new(true)));
+    let (snapshot_tx, messages_tx, shutdown, _addresses) =
+        GrpcService::create(config.tokio, config.grpc, debug_clients.clone(), false).await?;
+    // recorded log does not contain startup accounts
+    drop(snapshot_tx);
+    let prometheus = PrometheusService::new(config.prometheus, debug_clients).await?;
+
+    let mut reader = MessageLogReader::open(&args.messages)
+        .await
+        .with_context(|| format!("failed to open {}", args.messages))?;
+    let mut prev_created_at = None;
+    let mut count = 0;
+    while let Some(message) = reader.read().await? {
+        let created_at = message.get_created_at();
+        if let Some(prev) = prev_created_at.replace(created_at) {
+            if args.speed > 0.0 {
+                let delay = Duration::try_from(prost_types::Duration {
+                    seconds: created_at.seconds - prev.seconds,
+                    nanos: created_at.nanos - prev.nanos,
+                });
+                if let Ok(delay) = delay {
+                    sleep(delay.div_f64(args.speed)).await;
+                }
+            }
+        }
+
+        if messages_tx.send(message).is_err() {
+            break;
+        }
+        count += 1;
+    }
+    info!(
+        "replayed {count} messages from {}, press Ctrl-C to exit",
+        args.messages
+    );
+
+    signal::ctrl_c().await?;
+    shutdown.notify_one();
+    drop(messages_tx);
+    prometheus.shutdown();
+    Ok(())
+}
@@ -0,0 +1,281 @@
+//! Dry-run check of the plugin config, used by `config-check` binary.
+
+use {
+    crate::{admin::AdminService, config::Config, grpc::GrpcService, metrics::PrometheusService},
+    serde::Serialize,
+    std::{net::SocketAddr, path::Path},
+};
+
+#[derive(Debug, Default, Serialize)]
+pub struct ConfigCheckReport {
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl ConfigCheckReport {
+    pub async fn check_file(path: impl AsRef<Path>) -> Self {
+        match Config::load_from_file(path) {
+            Ok(config) => Self::check(&config).await,
+            Err(error) => Self::from_load_error(error),
+        }
+    }
+
+    pub async fn check_str(config: &str) -> Self {
+        match Config::load_from_str(config) {
+            Ok(config) => Self::check(&config).await,
+            Err(error) => Self::from_load_error(error),
+        }
+    }
+
+    fn from_load_error(error: impl std::fmt::Display) -> Self {
+        Self {
+            errors: vec![format!("config: {error}")],
+            warnings: vec![],
+        }
+    }
+
+    /// Run the same checks as the plugin on load, bound addresses are released after the check
+    pub async fn check(config: &Config) -> Self {
+        let mut report = Self::default();
+
+        // gRPC
+        let grpc_listener = match GrpcService::bind_listener(&config.grpc) {
+            Ok(listener) => Some(listener),
+            Err(error) => {
+                report.errors.push(format!("grpc: {error:#}"));
+                None
+            }
+        };
+        if let Err(error) = GrpcService::create_server_builder(&config.grpc).await {
+            report.errors.push(format!("grpc: {error:#}"));
+        }
+        if config.grpc.x_token.is_none() && !is_loopback(config.grpc.address) {
+            report.warnings.push(format!(
+                "grpc: x_token is not set, {} is available without authentication",
+                config.grpc.address
+            ));
+        }
+
+        let admin_listener = match &config.grpc.admin {
+            Some(admin) => match AdminService::bind(admin).await {
+                Ok(listener) => Some(listener),
+                Err(error) => {
+                    report.errors.push(format!(
+                        "grpc: failed to bind admin address {}: {error}",
+                        admin.address
+                    ));
+                    None
+                }
+            },
+            None => None,
+        };
+
+        // Prometheus
+        if let Some(prometheus) = &config.prometheus {
+            if let Err(error) = PrometheusService::bind(prometheus).await {
+                report.errors.push(format!(
+                    "prometheus: failed to bind {}: {error}",
+                    prometheus.address
+                ));
+            }
+            if prometheus.auth.is_none()
+                && prometheus.allowed_cidrs.is_empty()
+                && !is_loopback(prometheus.address)
+            {
+                report.warnings.push(format!(
+                    "prometheus: neither auth nor allowed_cidrs are set, {} is available for everyone",
+                    prometheus.address
+                ));
+            }
+        }
+        drop(grpc_listener);
+        drop(admin_listener);
+
+        // Record messages
+        if let Some(path) = &config.record_messages_path {
+            match path
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+            {
+                Some(parent) if !parent.is_dir() => report.errors.push(format!(
+                    "record_messages_path: directory {parent:?} does not exist"
+                )),
+                _ if path.is_dir() => report
+                    .errors
+                    .push(format!("record_messages_path: {path:?} is a directory")),
+                _ if path.exists() => report.warnings.push(format!(
+                    "record_messages_path: existing file {path:?} will be overwritten"
+                )),
+                _ => {}
+            }
+        }
+
+        report
+    }
+
+    pub fn is_ok(&self, deny_warnings: bool) -> bool {
+        self.errors.is_empty() && (!deny_warnings || self.warnings.is_empty())
+    }
+}
+
+const fn is_loopback(address: SocketAddr) -> bool {
+    address.ip().is_loopback()
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::ConfigCheckReport, serde_json::json, tokio::net::TcpListener};
+
+    async fn check(config: serde_json::Value) -> ConfigCheckReport {
+        ConfigCheckReport::check_str(&config.to_string()).await
+    }
+
+    fn assert_error(report: &ConfigCheckReport, needle: &str) {
+        assert!(
+            report.errors.iter().any(|error| error.contains(needle)),
+            "{needle:?} not found in {:?}",
+            report.errors
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_ok() {
+        let report = check(json!({
+            "libpath": "",
+            "grpc": {"address": "127.0.0.1:0"},
+            "prometheus": {"address": "127.0.0.1:0"},
+        }))
+        .await;
+        assert!(report.is_ok(true), "{report:?}");
+    }
+
+    #[tokio::test]
+    async fn test_check_invalid_config() {
+        let report = check(json!({"libpath": "", "grpc": {"address": "127.0.0.1"}})).await;
+        assert_error(&report, "config:");
+
+        let report = check(json!({
+            "libpath": "",
+            "grpc": {"address": "127.0.0.1:0", "tracked_accounts": {}},
+        }))
+        .await;
+        assert_error(&report, "tracked_accounts");
+
+        for (key, value, needle) in [
+            (
+                "server_initial_stream_window_size",
+                json!(u32::MAX),
+                "`server_initial_stream_window_size` should be less or equal",
+            ),
+            (
+                "server_max_frame_size",
+                json!(1024),
+                "`server_max_frame_size` should be in the range",
+            ),
+            (
+                "server_max_concurrent_streams",
+                json!(0),
+                "`server_max_concurrent_streams` should be greater than 0",
+            ),
+            (
+                "admin",
+                json!({"address": "127.0.0.1:0", "x_token": ""}),
+                "`admin.x_token` should not be empty",
+            ),
+        ] {
+            let report = check(json!({
+                "libpath": "",
+                "grpc": {"address": "127.0.0.1:0", key: value},
+            }))
+            .await;
+            assert_error(&report, needle);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_bind() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let address = listener.local_addr().expect("local address");
+
+        let report = check(json!({"libpath": "", "grpc": {"address": address}})).await;
+        assert_error(&report, "grpc: failed to bind");
+
+        let report = check(json!({
+            "libpath": "",
+            "grpc": {"address": "127.0.0.1:0"},
+            "prometheus": {"address": address},
+        }))
+        .await;
+        assert_error(&report, "prometheus: failed to bind");
+
+        let report = check(json!({
+            "libpath": "",
+            "grpc": {
+                "address": "127.0.0.1:0",
+                "admin": {"address": address, "x_token": "admin"},
+            },
+        }))
+        .await;
+        assert_error(&report, "grpc: failed to bind admin address");
+
+        let report = check(json!({
+            "libpath": "",
+            "grpc": {"address": "127.0.0.1:0", "bind_ipv6_dual_stack": true},
+        }))
+        .await;
+        assert_error(&report, "bind_ipv6_dual_stack");
+    }
+
+    #[tokio::test]
+    async fn test_check_tls() {
+        let report = check(json!({
+            "libpath": "",
+            "grpc": {
+                "address": "127.0.0.1:0",
+                "tls_config": {"cert_path": "/nonexistent/cert.pem", "key_path": "/nonexistent/key.pem"},
+            },
+        }))
+        .await;
+        assert_error(&report, "grpc: failed to load tls_config files");
+    }
+
+    #[tokio::test]
+    async fn test_check_record_messages_path() {
+        let report = check(json!({
+            "libpath": "",
+            "grpc": {"address": "127.0.0.1:0"},
+            "record_messages_path": "/nonexistent/messages.bin",
+        }))
+        .await;
+        assert_error(&report, "record_messages_path");
+
+        let path = std::env::temp_dir().join(format!("check-record-{}.bin", std::process::id()));
+        let config = json!({
+            "libpath": "",
+            "grpc": {"address": "127.0.0.1:0"},
+            "record_messages_path": path,
+        });
+        let report = check(config.clone()).await;
+        assert!(report.is_ok(true), "{report:?}");
+
+        std::fs::write(&path, b"").expect("write file");
+        let report = check(config).await;
+        std::fs::remove_file(&path).expect("remove file");
+        assert!(report.is_ok(false), "{report:?}");
+        assert_eq!(report.warnings.len(), 1, "{report:?}");
+    }
+
+    #[tokio::test]
+    async fn test_check_warnings() {
+        let report = check(json!({
+            "libpath": "",
+            "grpc": {"address": "0.0.0.0:0"},
+            "prometheus": {"address": "0.0.0.0:0"},
+        }))
+        .await;
+        assert!(report.errors.is_empty(), "{report:?}");
+        assert_eq!(report.warnings.len(), 2, "{report:?}");
+        assert!(report.is_ok(false));
+        assert!(!report.is_ok(true));
+    }
+}
@@ -2,14 +2,27 @@ use {
     agave_geyser_plugin_interface::geyser_plugin_interface::{
         GeyserPluginError, Result as PluginResult,
     },
+    base64::{engine::general_purpose::STANDARD as base64_engine, Engine},
     serde::{de, Deserialize, Deserializer},
+    solana_sdk::{
+        clock::MAX_RECENT_BLOCKHASHES, pubkey::Pubkey,
+        system_instruction::MAX_PERMITTED_DATA_LENGTH,
+    },
     std::{
-        collections::HashSet, fmt, fs::read_to_string, net::SocketAddr, path::Path, str::FromStr,
+        collections::{HashMap, HashSet},
+        fmt,
+        fs::read_to_string,
+        net::SocketAddr,
+        path::{Path, PathBuf},
+        str::FromStr,
         time::Duration,
     },
     tokio::sync::Semaphore,
     tonic::codec::CompressionEncoding,
-    yellowstone_grpc_proto::plugin::filter::limits::FilterLimits,
+    yellowstone_grpc_proto::plugin::{
+        filter::limits::{FilterLimits, FilterLimitsAccessCidr},
+        message::CommitmentLevel,
+    },
 };
 
 #[derive(Debug, Clone, Deserialize)]
@@ -26,13 +39,37 @@ pub struct Config {
     /// Collect client filters, processed slot and make it available on prometheus port `/debug_clients`
     #[serde(default)]
     pub debug_clients_http: bool,
+    /// Record messages from the validator to the file, can be replayed with `geyser-replay`
+    #[serde(default)]
+    pub record_messages_path: Option<PathBuf>,
+    /// Stop recording after this number of bytes
+    #[serde(
+        default = "Config::default_record_messages_max_size",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub record_messages_max_size: usize,
 }
 
 impl Config {
-    fn load_from_str(config: &str) -> PluginResult<Self> {
-        serde_json::from_str(config).map_err(|error| GeyserPluginError::ConfigFileReadError {
-            msg: error.to_string(),
-        })
+    const fn default_record_messages_max_size() -> usize {
+        1024 * 1024 * 1024
+    }
+
+    pub fn load_from_str(config: &str) -> PluginResult<Self> {
+        let config: Self = serde_json::from_str(config).map_err(|error| {
+            GeyserPluginError::ConfigFileReadError {
+                msg: error.to_string(),
+            }
+        })?;
+        config
+            .grpc
+            .validate()
+            .map_err(|msg| GeyserPluginError::ConfigFileReadError { msg })?;
+        if let Some(otlp) = &config.log.otlp {
+            otlp.validate()
+                .map_err(|msg| GeyserPluginError::ConfigFileReadError { msg })?;
+        }
+        Ok(config)
     }
 
     pub fn load_from_file<P: AsRef<Path>>(file: P) -> PluginResult<Self> {
@@ -47,12 +84,16 @@ pub struct ConfigLog {
     /// Log level.
     #[serde(default = "ConfigLog::default_level")]
     pub level: String,
+    /// Export tracing spans to OpenTelemetry collector, spans are disabled without it
+    #[serde(default)]
+    pub otlp: Option<ConfigLogOtlp>,
 }
 
 impl Default for ConfigLog {
     fn default() -> Self {
         Self {
             level: Self::default_level(),
+            otlp: None,
         }
     }
 }
@@ -63,17 +104,73 @@ impl ConfigLog {
     }
 }
 
-#[derive(Debug, Clone, Default, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigLogOtlp {
+    /// gRPC endpoint of the collector
+    pub endpoint: String,
+    /// Ratio of sampled traces, from `0.0` to `1.0`
+    #[serde(default = "ConfigLogOtlp::default_sampling_ratio")]
+    pub sampling_ratio: f64,
+    /// Exported spans and events, `EnvFilter` directives
+    #[serde(default = "ConfigLogOtlp::default_filter")]
+    pub filter: String,
+    #[serde(default = "ConfigLogOtlp::default_service_name")]
+    pub service_name: String,
+}
+
+impl ConfigLogOtlp {
+    const fn default_sampling_ratio() -> f64 {
+        1.0
+    }
+
+    fn default_filter() -> String {
+        "info,yellowstone_grpc_geyser=debug".to_owned()
+    }
+
+    fn default_service_name() -> String {
+        "yellowstone-grpc-geyser".to_owned()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.sampling_ratio) {
+            return Err(format!(
+                "log.otlp.sampling_ratio should be from 0 to 1, got {}",
+                self.sampling_ratio
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct ConfigTokio {
     /// Number of worker threads in Tokio runtime
     pub worker_threads: Option<usize>,
     /// Threads affinity
-    #[serde(deserialize_with = "ConfigTokio::deserialize_affinity")]
+    #[serde(default, deserialize_with = "ConfigTokio::deserialize_affinity")]
     pub affinity: Option<Vec<usize>>,
+    /// Names of runtime threads, followed by the thread number
+    #[serde(default = "ConfigTokio::default_thread_name_prefix")]
+    pub thread_name_prefix: String,
+}
+
+impl Default for ConfigTokio {
+    fn default() -> Self {
+        Self {
+            worker_threads: None,
+            affinity: None,
+            thread_name_prefix: Self::default_thread_name_prefix(),
+        }
+    }
 }
 
 impl ConfigTokio {
+    fn default_thread_name_prefix() -> String {
+        "solGeyserGrpc".to_owned()
+    }
+
     fn deserialize_affinity<'de, D>(deserializer: D) -> Result<Option<Vec<usize>>, D::Error>
     where
         D: Deserializer<'de>,
@@ -136,6 +233,18 @@ fn parse_taskset(taskset: &str) -> Result<Vec<usize>, String> {
 pub struct ConfigGrpc {
     /// Address of Grpc service.
     pub address: SocketAddr,
+    /// Accept IPv4 connections on IPv6 `address` (`IPV6_V6ONLY=false`)
+    #[serde(default)]
+    pub bind_ipv6_dual_stack: bool,
+    /// Set `SO_REUSEPORT`, new process can bind the same address before the old one exits
+    #[serde(default)]
+    pub so_reuseport: bool,
+    /// Size of the queue of pending connections
+    #[serde(
+        default = "ConfigGrpc::default_tcp_backlog",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub tcp_backlog: u32,
     /// TLS config
     pub tls_config: Option<ConfigGrpcServerTls>,
     /// Possible compression options
@@ -166,6 +275,24 @@ pub struct ConfigGrpc {
         deserialize_with = "deserialize_int_str"
     )]
     pub channel_capacity: usize,
+    /// Capacity of the broadcast channel of processed messages, `channel_capacity` if not set
+    #[serde(default, deserialize_with = "deserialize_int_str_maybe")]
+    pub channel_capacity_processed: Option<usize>,
+    /// Capacity of the broadcast channel of confirmed messages, `channel_capacity` if not set
+    #[serde(default, deserialize_with = "deserialize_int_str_maybe")]
+    pub channel_capacity_confirmed: Option<usize>,
+    /// Capacity of the broadcast channel of finalized messages, `channel_capacity` if not set
+    #[serde(default, deserialize_with = "deserialize_int_str_maybe")]
+    pub channel_capacity_finalized: Option<usize>,
+    /// Number of broadcast shards by account owner, `0` disables sharding
+    #[serde(default, deserialize_with = "deserialize_int_str")]
+    pub broadcast_shards: usize,
+    /// Max estimated encoded bytes of the broadcasted batch, larger batches are split, `0` disables the limit
+    #[serde(
+        default = "ConfigGrpc::default_broadcast_batch_max_bytes",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub broadcast_batch_max_bytes: usize,
     /// Concurrency limit for unary requests
     #[serde(
         default = "ConfigGrpc::unary_concurrency_limit_default",
@@ -192,12 +319,72 @@ pub struct ConfigGrpc {
         with = "humantime_serde"
     )]
     pub filter_names_cleanup_interval: Duration,
+    /// Max number of filter updates per second per connection, `0` disables the limit
+    #[serde(
+        default = "ConfigGrpc::default_filter_updates_rate_limit",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub filter_updates_rate_limit: u32,
+    /// Interval of `Ping` updates sent to every subscription
+    #[serde(
+        default = "ConfigGrpc::default_ping_interval",
+        with = "humantime_serde"
+    )]
+    pub ping_interval: Duration,
+    /// Max time transactions of filters with `ordered_per_slot` are buffered waiting for block
+    /// meta of the slot, flushed as best-effort after it
+    #[serde(
+        default = "ConfigGrpc::default_transactions_ordered_timeout",
+        with = "humantime_serde"
+    )]
+    pub transactions_ordered_timeout: Duration,
     /// Number of slots stored for re-broadcast (replay)
     #[serde(
         default = "ConfigGrpc::default_replay_stored_slots",
         deserialize_with = "deserialize_int_str"
     )]
     pub replay_stored_slots: u64,
+    /// Max estimated encoded bytes of messages stored for replay, the oldest finalized slots
+    /// are evicted first, `0` disables the limit
+    #[serde(
+        default = "ConfigGrpc::default_replay_stored_slots_max_bytes",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub replay_stored_slots_max_bytes: usize,
+    /// Max number of messages in the batch for processed commitment
+    #[serde(
+        default = "ConfigGrpc::default_processed_batch_max_size",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub processed_batch_max_size: usize,
+    /// Max delay in milliseconds before sending processed batch, `0` means send every message immediately
+    #[serde(
+        default = "ConfigGrpc::default_processed_batch_max_delay_ms",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub processed_batch_max_delay_ms: u64,
+    /// Send processed batch on every slot message
+    #[serde(default = "ConfigGrpc::default_processed_batch_flush_on_slot")]
+    pub processed_batch_flush_on_slot: bool,
+    /// Send partial block if it's not reconstructed during this time after the slot is confirmed
+    #[serde(default, with = "humantime_serde")]
+    pub block_assembly_timeout: Option<Duration>,
+    /// Send `BlockDiagnostic` of not reconstructed blocks to blocks filters with `include_diagnostics`
+    #[serde(default)]
+    pub block_diagnostics: bool,
+    /// Report `NOT_SERVING` in the gRPC health service if no message was processed during this time
+    #[serde(
+        default = "ConfigGrpc::default_health_stale_timeout",
+        with = "humantime_serde"
+    )]
+    pub health_stale_timeout: Duration,
+    /// Minimum number of distinct block metas received before `IsBlockhashValid` answers,
+    /// bounded by the chain length for short chains (test validator)
+    #[serde(default = "ConfigGrpc::default_blockhash_warmup_block_metas")]
+    pub blockhash_warmup_block_metas: usize,
+    /// Enable gRPC server reflection service
+    #[serde(default = "ConfigGrpc::default_enable_reflection")]
+    pub enable_reflection: bool,
     #[serde(default)]
     pub server_http2_adaptive_window: Option<bool>,
     #[serde(default, with = "humantime_serde")]
@@ -208,9 +395,133 @@ pub struct ConfigGrpc {
     pub server_initial_connection_window_size: Option<u32>,
     #[serde(default)]
     pub server_initial_stream_window_size: Option<u32>,
+    /// Max number of concurrent HTTP/2 streams per connection, unlimited by default
+    #[serde(default, deserialize_with = "deserialize_int_str_maybe")]
+    pub server_max_concurrent_streams: Option<u32>,
+    /// Max HTTP/2 frame size, between 16_384 and 16_777_215 bytes
+    #[serde(default, deserialize_with = "deserialize_int_str_maybe")]
+    pub server_max_frame_size: Option<u32>,
+    /// Set `TCP_NODELAY` on accepted connections
+    #[serde(default = "ConfigGrpc::default_server_tcp_nodelay")]
+    pub server_tcp_nodelay: bool,
+    /// Accounts with latest values available with `GetAccountsSnapshot`
+    #[serde(default)]
+    pub tracked_accounts: Option<ConfigGrpcTrackedAccounts>,
+    /// Index of recent transactions available with `GetTransactionStatus`
+    #[serde(default)]
+    pub recent_transactions: Option<ConfigGrpcRecentTransactions>,
+    /// Vote transactions aggregated per vote account for votes filters, disabled by default
+    #[serde(default)]
+    pub votes: Option<ConfigGrpcVotes>,
+    /// Account writes statistics per slot for stats filters, disabled by default
+    #[serde(default)]
+    pub slot_stats: Option<ConfigGrpcSlotStats>,
+    /// Admin service to inspect and disconnect clients, disabled by default
+    #[serde(default)]
+    pub admin: Option<ConfigGrpcAdmin>,
+    /// Soft and hard limits of bytes and messages sent to a subscription, disabled by default
+    #[serde(default)]
+    pub quota: Option<ConfigGrpcQuota>,
+    /// Accounts and transactions filters selected with `preset` of the subscribe request,
+    /// variables are substituted with `preset_params`
+    #[serde(default)]
+    pub filter_presets: HashMap<String, ConfigGrpcFilterPreset>,
 }
 
 impl ConfigGrpc {
+    // HTTP/2 limits, RFC 9113 section 6.5.2
+    const HTTP2_MAX_WINDOW_SIZE: u32 = (1 << 31) - 1;
+    const HTTP2_MIN_FRAME_SIZE: u32 = 1 << 14;
+    const HTTP2_MAX_FRAME_SIZE: u32 = (1 << 24) - 1;
+
+    const fn default_tcp_backlog() -> u32 {
+        1024
+    }
+
+    const fn default_server_tcp_nodelay() -> bool {
+        true
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (name, value) in [
+            (
+                "server_initial_connection_window_size",
+                self.server_initial_connection_window_size,
+            ),
+            (
+                "server_initial_stream_window_size",
+                self.server_initial_stream_window_size,
+            ),
+        ] {
+            if value.is_some_and(|value| value > Self::HTTP2_MAX_WINDOW_SIZE) {
+                return Err(format!(
+                    "grpc: `{name}` should be less or equal to {}",
+                    Self::HTTP2_MAX_WINDOW_SIZE
+                ));
+            }
+        }
+        if let Some(value) = self.server_max_frame_size {
+            if !(Self::HTTP2_MIN_FRAME_SIZE..=Self::HTTP2_MAX_FRAME_SIZE).contains(&value) {
+                return Err(format!(
+                    "grpc: `server_max_frame_size` should be in the range [{}, {}]",
+                    Self::HTTP2_MIN_FRAME_SIZE,
+                    Self::HTTP2_MAX_FRAME_SIZE
+                ));
+            }
+        }
+        if self.server_max_concurrent_streams == Some(0) {
+            return Err("grpc: `server_max_concurrent_streams` should be greater than 0".into());
+        }
+        for (name, value) in [
+            (
+                "channel_capacity_processed",
+                self.channel_capacity_processed,
+            ),
+            (
+                "channel_capacity_confirmed",
+                self.channel_capacity_confirmed,
+            ),
+            (
+                "channel_capacity_finalized",
+                self.channel_capacity_finalized,
+            ),
+        ] {
+            if value == Some(0) {
+                return Err(format!("grpc: `{name}` should be greater than 0"));
+            }
+        }
+        if let Some(tracked_accounts) = &self.tracked_accounts {
+            tracked_accounts.validate()?;
+        }
+        if let Some(votes) = &self.votes {
+            votes.validate()?;
+        }
+        if let Some(admin) = &self.admin {
+            if admin.x_token.is_empty() {
+                return Err("grpc: `admin.x_token` should not be empty".into());
+            }
+            if admin.address.port() != 0 && admin.address == self.address {
+                return Err("grpc: `admin.address` should be different from `address`".into());
+            }
+        }
+        if let Some(quota) = &self.quota {
+            quota.validate()?;
+        }
+        for (name, preset) in self.filter_presets.iter() {
+            preset.validate(name)?;
+        }
+        if !self
+            .filter_limits
+            .is_commitment_allowed(self.filter_limits.default_commitment)
+        {
+            return Err(
+                "grpc: `filter_limits.default_commitment` should be in `filter_limits.allowed_commitments`"
+                    .into(),
+            );
+        }
+        Ok(())
+    }
+
     const fn max_decoding_message_size_default() -> usize {
         4 * 1024 * 1024
     }
@@ -227,6 +538,16 @@ impl ConfigGrpc {
         250_000
     }
 
+    /// Capacity of the broadcast channel of messages with the commitment
+    pub fn get_channel_capacity(&self, commitment: CommitmentLevel) -> usize {
+        match commitment {
+            CommitmentLevel::Processed => self.channel_capacity_processed,
+            CommitmentLevel::Confirmed => self.channel_capacity_confirmed,
+            CommitmentLevel::Finalized => self.channel_capacity_finalized,
+        }
+        .unwrap_or(self.channel_capacity)
+    }
+
     const fn unary_concurrency_limit_default() -> usize {
         Semaphore::MAX_PERMITS
     }
@@ -243,9 +564,386 @@ impl ConfigGrpc {
         Duration::from_secs(1)
     }
 
+    const fn default_filter_updates_rate_limit() -> u32 {
+        5
+    }
+
+    const fn default_ping_interval() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    const fn default_transactions_ordered_timeout() -> Duration {
+        Duration::from_secs(10)
+    }
+
     const fn default_replay_stored_slots() -> u64 {
         0
     }
+
+    const fn default_replay_stored_slots_max_bytes() -> usize {
+        0
+    }
+
+    const fn default_broadcast_batch_max_bytes() -> usize {
+        8 * 1024 * 1024
+    }
+
+    const fn default_processed_batch_max_size() -> usize {
+        31
+    }
+
+    const fn default_processed_batch_max_delay_ms() -> u64 {
+        10
+    }
+
+    const fn default_processed_batch_flush_on_slot() -> bool {
+        true
+    }
+
+    const fn default_health_stale_timeout() -> Duration {
+        Duration::from_secs(30)
+    }
+
+    const fn default_blockhash_warmup_block_metas() -> usize {
+        MAX_RECENT_BLOCKHASHES + 32
+    }
+
+    const fn default_enable_reflection() -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcTrackedAccounts {
+    /// Tracked accounts
+    #[serde(default, deserialize_with = "deserialize_pubkey_set")]
+    pub pubkeys: HashSet<Pubkey>,
+    /// Accounts owned by these programs are tracked too (up to `max_accounts`)
+    #[serde(default, deserialize_with = "deserialize_pubkey_set")]
+    pub owners: HashSet<Pubkey>,
+    /// Max number of tracked accounts per commitment level
+    #[serde(
+        default = "ConfigGrpcTrackedAccounts::default_max_accounts",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub max_accounts: usize,
+    /// Max data size of tracked account, accounts with bigger data are not stored
+    #[serde(
+        default = "ConfigGrpcTrackedAccounts::default_max_data_size",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub max_data_size: usize,
+    /// Max number of pubkeys per `GetAccountsSnapshot` request
+    #[serde(
+        default = "ConfigGrpcTrackedAccounts::default_max_request_pubkeys",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub max_request_pubkeys: usize,
+}
+
+impl ConfigGrpcTrackedAccounts {
+    const fn default_max_accounts() -> usize {
+        10_000
+    }
+
+    const fn default_max_data_size() -> usize {
+        10 * 1024
+    }
+
+    const fn default_max_request_pubkeys() -> usize {
+        1_000
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.pubkeys.is_empty() && self.owners.is_empty() {
+            return Err("tracked_accounts: at least one of `pubkeys` or `owners` required".into());
+        }
+        if self.pubkeys.len() > self.max_accounts {
+            return Err(format!(
+                "tracked_accounts: {} pubkeys exceeds `max_accounts` ({})",
+                self.pubkeys.len(),
+                self.max_accounts
+            ));
+        }
+        if self.max_data_size > MAX_PERMITTED_DATA_LENGTH as usize {
+            return Err(format!(
+                "tracked_accounts: `max_data_size` should be less or equal to {MAX_PERMITTED_DATA_LENGTH}"
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcRecentTransactions {
+    /// Number of slots behind the highest processed slot kept in the index
+    #[serde(
+        default = "ConfigGrpcRecentTransactions::default_max_slots",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub max_slots: u64,
+    /// Max number of transactions in the index, the oldest slots are removed first
+    #[serde(
+        default = "ConfigGrpcRecentTransactions::default_max_transactions",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub max_transactions: usize,
+    /// Index vote transactions too
+    #[serde(default)]
+    pub include_votes: bool,
+}
+
+impl ConfigGrpcRecentTransactions {
+    const fn default_max_slots() -> u64 {
+        150
+    }
+
+    const fn default_max_transactions() -> usize {
+        1_000_000
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcVotes {
+    /// Number of slots aggregated in one update, windows are aligned to multiples of the value
+    #[serde(
+        default = "ConfigGrpcVotes::default_window_slots",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub window_slots: u64,
+    /// Max number of vote accounts per slot, votes of other accounts are dropped
+    #[serde(
+        default = "ConfigGrpcVotes::default_max_vote_accounts",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub max_vote_accounts: usize,
+}
+
+impl ConfigGrpcVotes {
+    const fn default_window_slots() -> u64 {
+        1
+    }
+
+    const fn default_max_vote_accounts() -> usize {
+        10_000
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.window_slots == 0 {
+            return Err("votes: `window_slots` should be greater than 0".into());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcSlotStats {
+    /// Number of owners with the most account updates included in every update
+    #[serde(
+        default = "ConfigGrpcSlotStats::default_top_owners",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub top_owners: usize,
+    /// Max number of owners counted per slot, owners with the least account updates are
+    /// replaced once it is reached
+    #[serde(
+        default = "ConfigGrpcSlotStats::default_max_owners",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub max_owners: usize,
+}
+
+impl ConfigGrpcSlotStats {
+    const fn default_top_owners() -> usize {
+        10
+    }
+
+    const fn default_max_owners() -> usize {
+        1_000
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcAdmin {
+    /// Address of admin service, TLS and HTTP/2 options of the main service are not applied
+    pub address: SocketAddr,
+    /// Token required in `x-token` header of every admin request
+    pub x_token: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcQuota {
+    /// Usage is accounted during this window
+    #[serde(default = "ConfigGrpcQuota::default_window", with = "humantime_serde")]
+    pub window: Duration,
+    /// `fixed` resets usage once the window is passed, `rolling` accounts the last `window`
+    #[serde(default)]
+    pub window_kind: ConfigGrpcQuotaWindow,
+    /// Limits of clients without a token from `tokens`
+    #[serde(default)]
+    pub limits: ConfigGrpcQuotaLimits,
+    /// Limits by `x-token` header, replace `limits`
+    #[serde(default)]
+    pub tokens: HashMap<String, ConfigGrpcQuotaLimits>,
+}
+
+impl ConfigGrpcQuota {
+    const fn default_window() -> Duration {
+        Duration::from_secs(24 * 60 * 60)
+    }
+
+    /// Limits of the client with `x-token` header
+    pub fn get_limits(&self, x_token: Option<&str>) -> ConfigGrpcQuotaLimits {
+        x_token
+            .and_then(|x_token| self.tokens.get(x_token))
+            .copied()
+            .unwrap_or(self.limits)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.window.is_zero() {
+            return Err("quota: `window` should be greater than 0".into());
+        }
+        for limits in std::iter::once(&self.limits).chain(self.tokens.values()) {
+            for (name, soft, hard) in [
+                ("bytes", limits.soft_bytes, limits.hard_bytes),
+                ("messages", limits.soft_messages, limits.hard_messages),
+            ] {
+                if let (Some(soft), Some(hard)) = (soft, hard) {
+                    if soft > hard {
+                        return Err(format!(
+                            "quota: `soft_{name}` should be less or equal to `hard_{name}`"
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigGrpcQuotaWindow {
+    #[default]
+    Fixed,
+    Rolling,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcQuotaLimits {
+    /// `QuotaWarning` update is sent once the usage is reached
+    #[serde(default, deserialize_with = "deserialize_int_str_maybe")]
+    pub soft_bytes: Option<u64>,
+    /// Stream is closed with `RESOURCE_EXHAUSTED` instead of exceeding the limit
+    #[serde(default, deserialize_with = "deserialize_int_str_maybe")]
+    pub hard_bytes: Option<u64>,
+    #[serde(default, deserialize_with = "deserialize_int_str_maybe")]
+    pub soft_messages: Option<u64>,
+    #[serde(default, deserialize_with = "deserialize_int_str_maybe")]
+    pub hard_messages: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcFilterPreset {
+    /// Variables referenced as `${name}` in lists of pubkeys of filters
+    #[serde(default)]
+    pub variables: HashMap<String, ConfigGrpcFilterPresetVariable>,
+    #[serde(default)]
+    pub accounts: HashMap<String, ConfigGrpcFilterPresetAccounts>,
+    #[serde(default)]
+    pub transactions: HashMap<String, ConfigGrpcFilterPresetTransactions>,
+}
+
+impl ConfigGrpcFilterPreset {
+    /// Name of the variable if the value is `${name}`
+    pub fn get_variable(value: &str) -> Option<&str> {
+        value.strip_prefix("${")?.strip_suffix('}')
+    }
+
+    fn validate(&self, name: &str) -> Result<(), String> {
+        let lists = self
+            .accounts
+            .values()
+            .flat_map(|filter| [&filter.account, &filter.owner])
+            .chain(self.transactions.values().flat_map(|filter| {
+                [
+                    &filter.account_include,
+                    &filter.account_exclude,
+                    &filter.account_required,
+                ]
+            }));
+        for value in lists.flatten() {
+            match Self::get_variable(value) {
+                Some(variable) if !self.variables.contains_key(variable) => {
+                    return Err(format!(
+                        "grpc: `filter_presets.{name}`: variable `{variable}` is not defined"
+                    ));
+                }
+                Some(_) => {}
+                None => {
+                    value.parse::<Pubkey>().map_err(|error| {
+                        format!("grpc: `filter_presets.{name}`: invalid pubkey {value} ({error:?})")
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ConfigGrpcFilterPresetVariable {
+    Pubkey,
+    /// Comma-separated list, expanded in place of the variable
+    Pubkeys {
+        #[serde(
+            default = "ConfigGrpcFilterPresetVariable::default_max_pubkeys",
+            deserialize_with = "deserialize_int_str"
+        )]
+        max: usize,
+    },
+}
+
+impl ConfigGrpcFilterPresetVariable {
+    const fn default_max_pubkeys() -> usize {
+        100
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcFilterPresetAccounts {
+    #[serde(default)]
+    pub account: Vec<String>,
+    #[serde(default)]
+    pub owner: Vec<String>,
+    #[serde(default)]
+    pub nonempty_txn_signature: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcFilterPresetTransactions {
+    #[serde(default)]
+    pub vote: Option<bool>,
+    #[serde(default)]
+    pub failed: Option<bool>,
+    #[serde(default)]
+    pub account_include: Vec<String>,
+    #[serde(default)]
+    pub account_exclude: Vec<String>,
+    #[serde(default)]
+    pub account_required: Vec<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -303,11 +1001,40 @@ impl ConfigGrpcCompression {
     }
 }
 
-#[derive(Debug, Clone, Copy, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct ConfigPrometheus {
     /// Address of Prometheus service.
     pub address: SocketAddr,
+    /// Required `Authorization` for all endpoints
+    #[serde(default)]
+    pub auth: Option<ConfigPrometheusAuth>,
+    /// Allowed remote addresses, everything is allowed if empty
+    #[serde(default)]
+    pub allowed_cidrs: Vec<FilterLimitsAccessCidr>,
+}
+
+/// Unknown fields are rejected, so a mix of both variants is not silently read as one of them
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum ConfigPrometheusAuth {
+    Basic { username: String, password: String },
+    Bearer { bearer_token: String },
+}
+
+impl ConfigPrometheusAuth {
+    /// Expected value of `Authorization` header
+    pub fn get_header_value(&self) -> String {
+        match self {
+            Self::Basic { username, password } => {
+                format!(
+                    "Basic {}",
+                    base64_engine.encode(format!("{username}:{password}"))
+                )
+            }
+            Self::Bearer { bearer_token } => format!("Bearer {bearer_token}"),
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -348,3 +1075,17 @@ where
         None => Ok(None),
     }
 }
+
+fn deserialize_pubkey_set<'de, D>(deserializer: D) -> Result<HashSet<Pubkey>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<&str>::deserialize(deserializer)?
+        .into_iter()
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|error| de::Error::custom(format!("Invalid pubkey: {value} ({error:?})")))
+        })
+        .collect::<Result<_, _>>()
+}
@@ -1,32 +1,51 @@
 use {
     crate::{
-        config::{ConfigGrpc, ConfigTokio},
-        metrics::{self, DebugClientMessage},
-        version::GrpcVersionInfo,
+        admin::{AdminService, ClientRegistry, MaintenanceRx, RegisteredClient},
+        config::{
+            ConfigGrpc, ConfigGrpcQuota, ConfigGrpcRecentTransactions, ConfigGrpcSlotStats,
+            ConfigGrpcTrackedAccounts, ConfigGrpcVotes, ConfigTokio,
+        },
+        health::{is_blockhashes_warmed_up, PipelineHealth},
+        metrics,
+        preset::FilterPresets,
+        quota::{ClientQuota, QuotaCheck},
+        source::{MessagesOrderChecker, MessagesSender},
+        trace,
+        version::{GrpcVersionInfo, VERSION},
     },
     anyhow::Context,
-    log::{error, info},
     prost_types::Timestamp,
+    socket2::{Domain, Protocol, Socket, Type},
     solana_sdk::{
-        clock::{Slot, MAX_RECENT_BLOCKHASHES},
+        clock::{Slot, MAX_PROCESSING_AGE, MAX_RECENT_BLOCKHASHES},
         pubkey::Pubkey,
+        signature::Signature,
     },
     std::{
-        collections::{BTreeMap, HashMap},
+        cmp::Reverse,
+        collections::{
+            hash_map::Entry as HashMapEntry, BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet,
+        },
+        future::{pending, Future},
+        hash::{DefaultHasher, Hash as _, Hasher},
+        net::SocketAddr,
+        pin::Pin,
+        str::FromStr,
         sync::{
-            atomic::{AtomicUsize, Ordering},
+            atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
             Arc,
         },
-        time::SystemTime,
+        task::{ready, Context as TaskContext, Poll},
+        time::{SystemTime, UNIX_EPOCH},
     },
     tokio::{
         fs,
-        runtime::Builder,
-        sync::{broadcast, mpsc, oneshot, Mutex, Notify, RwLock, Semaphore},
+        net::TcpListener,
+        sync::{broadcast, mpsc, oneshot, watch, Mutex, Notify, RwLock, Semaphore},
         task::spawn_blocking,
-        time::{sleep, Duration, Instant},
+        time::{sleep, Duration, Instant, Sleep},
     },
-    tokio_stream::wrappers::ReceiverStream,
+    tokio_stream::{wrappers::ReceiverStream, Stream},
     tonic::{
         service::interceptor::interceptor,
         transport::{
@@ -35,166 +54,401 @@ use {
         },
         Request, Response, Result as TonicResult, Status, Streaming,
     },
-    tonic_health::server::health_reporter,
+    tonic_health::{
+        pb::FILE_DESCRIPTOR_SET as HEALTH_FILE_DESCRIPTOR_SET, server::health_reporter,
+    },
+    tonic_reflection::server::Builder as ReflectionBuilder,
+    tracing::{error, info, warn, Instrument},
     yellowstone_grpc_proto::{
+        convert_from::convert_account_data,
+        geyser::FILE_DESCRIPTOR_SET as GEYSER_FILE_DESCRIPTOR_SET,
         plugin::{
             filter::{
-                limits::FilterLimits,
-                message::{FilteredUpdate, FilteredUpdateOneof},
+                limits::{FilterCaller, FilterLimits},
+                message::{
+                    EncodedBytesCounter, FilteredUpdate, FilteredUpdateOneof, FilteredUpdates,
+                },
                 name::FilterNames,
-                Filter,
+                Filter, FilterBuilder, FilterSeenSignatures,
             },
             message::{
-                CommitmentLevel, Message, MessageBlock, MessageBlockMeta, MessageEntry,
-                MessageSlot, MessageTransactionInfo, SlotStatus,
+                CommitmentLevel, Message, MessageAccountInfo, MessageBlock, MessageBlockDiagnostic,
+                MessageBlockMeta, MessageEntry, MessageRollback, MessageSlot, MessageSlotStats,
+                MessageTransaction, MessageTransactionInfo, MessageVotes, SlotStatus,
             },
             proto::geyser_server::{Geyser, GeyserServer},
         },
         prelude::{
-            CommitmentLevel as CommitmentLevelProto, GetBlockHeightRequest, GetBlockHeightResponse,
-            GetLatestBlockhashRequest, GetLatestBlockhashResponse, GetSlotRequest, GetSlotResponse,
-            GetVersionRequest, GetVersionResponse, IsBlockhashValidRequest,
-            IsBlockhashValidResponse, PingRequest, PongResponse, SubscribeRequest,
+            AccountOrigin, AccountSnapshot, AccountSnapshotStatus,
+            CommitmentLevel as CommitmentLevelProto, CommitmentSlot as CommitmentSlotProto,
+            GetAccountsSnapshotRequest, GetAccountsSnapshotResponse, GetBlockHeightRequest,
+            GetBlockHeightResponse, GetLatestBlockhashRequest, GetLatestBlockhashResponse,
+            GetSlotAncestryRequest, GetSlotAncestryResponse, GetSlotRequest, GetSlotResponse,
+            GetSlotStatusRequest, GetSlotStatusResponse, GetTransactionStatusRequest,
+            GetTransactionStatusResponse, GetVersionRequest, GetVersionResponse,
+            IsBlockhashValidRequest, IsBlockhashValidResponse, PingRequest, PongResponse,
+            SlotAncestor, SlotStatus as SlotStatusProto, SubscribeRequest,
+            SubscribeUpdateAccountInfo, SubscribeUpdateBlockDiagnostic, SubscribeUpdateLost,
+            SubscribeUpdateMaintenance, SubscribeUpdatePing, SubscribeUpdatePingStats,
+            SubscribeUpdateReplayDone, SubscribeUpdateSlotStats, SubscribeUpdateSlotStatsOwner,
+            SubscribeUpdateStreamInfo, SubscribeUpdateTransactionSlotDone, SubscribeUpdateVotes,
+            SubscribeUpdateVotesEntry, TransactionError,
         },
+        prost::Message as _,
     },
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 struct BlockhashStatus {
-    slot: u64,
-    processed: bool,
-    confirmed: bool,
-    finalized: bool,
+    slot: Slot,
+    block_height: u64,
 }
 
-impl BlockhashStatus {
-    const fn new(slot: u64) -> Self {
-        Self {
-            slot,
-            processed: false,
-            confirmed: false,
-            finalized: false,
-        }
-    }
+#[derive(Debug, Clone, Copy)]
+struct SlotAncestryEntry {
+    parent: Option<Slot>,
+    status: SlotStatus, // highest received status
 }
 
 #[derive(Debug, Default)]
 struct BlockMetaStorageInner {
     blocks: HashMap<u64, Arc<MessageBlockMeta>>,
     blockhashes: HashMap<String, BlockhashStatus>,
+    // parents of recent slots, kept for the same window as blockhashes
+    slots: BTreeMap<Slot, SlotAncestryEntry>,
+    // distinct block metas received, used for the warm-up only
+    block_metas: usize,
     processed: Option<u64>,
     confirmed: Option<u64>,
     finalized: Option<u64>,
+    // block heights of commitment slots, the age of blockhashes is counted from them
+    processed_height: Option<u64>,
+    confirmed_height: Option<u64>,
+    finalized_height: Option<u64>,
+    // finalized slot of the last cleanup
+    cleanup_slot: Option<u64>,
+}
+
+impl BlockMetaStorageInner {
+    /// Apply messages received with one write lock, cleanup of outdated blocks is done once
+    /// for the batch and only if `cleanup` is set
+    fn update_batch(&mut self, messages: impl IntoIterator<Item = Message>, cleanup: bool) {
+        for message in messages {
+            self.update(message);
+        }
+        if cleanup {
+            self.cleanup();
+        }
+    }
+
+    fn update(&mut self, message: Message) {
+        match message {
+            Message::Slot(msg) => {
+                let entry = self.slots.entry(msg.slot).or_insert(SlotAncestryEntry {
+                    parent: msg.parent,
+                    status: msg.status,
+                });
+                if entry.parent.is_none() {
+                    entry.parent = msg.parent;
+                }
+                // statuses before `processed` and `dead` never replace commitment statuses
+                if SlotStatuses::rank(msg.status) >= SlotStatuses::rank(entry.status) {
+                    entry.status = msg.status;
+                }
+
+                match msg.status {
+                    SlotStatus::Processed => {
+                        Self::update_slot(&mut self.processed, msg.slot);
+                    }
+                    SlotStatus::Confirmed => {
+                        Self::update_slot(&mut self.confirmed, msg.slot);
+                    }
+                    SlotStatus::Finalized => {
+                        Self::update_slot(&mut self.finalized, msg.slot);
+                    }
+                    _ => {}
+                }
+                self.update_heights(msg.slot);
+            }
+            Message::BlockMeta(msg) => {
+                let slot = msg.slot;
+                // blockhashes without block height (old ledgers) are never valid
+                if let Some(block_height) = msg.block_height {
+                    self.blockhashes.insert(
+                        msg.blockhash.clone(),
+                        BlockhashStatus {
+                            slot,
+                            block_height: block_height.block_height,
+                        },
+                    );
+                }
+                if self.blocks.insert(slot, msg).is_none() {
+                    self.block_metas = self.block_metas.saturating_add(1);
+                }
+                self.update_heights(slot);
+            }
+            msg => {
+                error!("invalid message in BlockMetaStorage: {msg:?}");
+            }
+        }
+    }
+
+    /// Block meta and statuses of the slot are received in any order, the height is updated
+    /// by whichever comes last
+    fn update_heights(&mut self, slot: Slot) {
+        let Some(block_height) = self
+            .blocks
+            .get(&slot)
+            .and_then(|block| block.block_height)
+            .map(|block_height| block_height.block_height)
+        else {
+            return;
+        };
+        if self.processed == Some(slot) {
+            Self::update_slot(&mut self.processed_height, block_height);
+        }
+        if self.confirmed == Some(slot) {
+            Self::update_slot(&mut self.confirmed_height, block_height);
+        }
+        if self.finalized == Some(slot) {
+            Self::update_slot(&mut self.finalized_height, block_height);
+        }
+    }
+
+    /// Remove blocks and slots behind the finalized slot, blockhashes expired at the
+    /// finalized block height are expired at every commitment
+    fn cleanup(&mut self) {
+        const KEEP_SLOTS: u64 = 3;
+
+        let Some(finalized) = self.finalized else {
+            return;
+        };
+        if self.cleanup_slot == Some(finalized) {
+            return;
+        }
+        self.cleanup_slot = Some(finalized);
+
+        if let Some(keep_slot) = finalized.checked_sub(KEEP_SLOTS) {
+            self.blocks.retain(|slot, _block| *slot >= keep_slot);
+        }
+        if let Some(finalized_height) = self.finalized_height {
+            self.blockhashes.retain(|_blockhash, status| {
+                finalized_height.saturating_sub(status.block_height) < MAX_PROCESSING_AGE as u64
+            });
+        }
+        if let Some(keep_slot) = finalized.checked_sub(MAX_RECENT_BLOCKHASHES as u64 + 32) {
+            self.slots = self.slots.split_off(&keep_slot);
+        }
+    }
+
+    // late status of an older slot should not move the pointer (or the height) back
+    fn update_slot(current: &mut Option<u64>, slot: u64) {
+        if current.is_none_or(|current| current < slot) {
+            *current = Some(slot);
+        }
+    }
+
+    /// Same as `lastValidBlockHeight` of `getLatestBlockhash` in RPC
+    const fn last_valid_block_height(block_height: u64) -> u64 {
+        block_height + MAX_PROCESSING_AGE as u64
+    }
+
+    fn is_blockhash_valid(
+        &self,
+        blockhash: &str,
+        commitment: CommitmentLevel,
+        warmup_block_metas: usize,
+    ) -> Result<IsBlockhashValidResponse, Status> {
+        if !is_blockhashes_warmed_up(self.block_metas, warmup_block_metas, self.finalized) {
+            return Err(Status::unavailable("blockhashes are warming up"));
+        }
+
+        let (Some(slot), Some(height)) = (match commitment {
+            CommitmentLevel::Processed => (self.processed, self.processed_height),
+            CommitmentLevel::Confirmed => (self.confirmed, self.confirmed_height),
+            CommitmentLevel::Finalized => (self.finalized, self.finalized_height),
+        }) else {
+            return Err(Status::unavailable("blockhashes are warming up"));
+        };
+
+        // same as `is_hash_valid_for_age` of the bank at the commitment: the block should be
+        // on the fork of the bank and not older than `MAX_PROCESSING_AGE` blocks
+        let (valid, known) = match self.blockhashes.get(blockhash) {
+            Some(status) => {
+                let valid = height.saturating_sub(status.block_height) < MAX_PROCESSING_AGE as u64
+                    && self.is_on_fork(status.slot, slot, commitment);
+                (valid, true)
+            }
+            None => (false, false),
+        };
+
+        Ok(IsBlockhashValidResponse { slot, valid, known })
+    }
+
+    /// Whether `ancestor` is the slot or one of its ancestors, if parents are not known the
+    /// status of `ancestor` should reach the commitment
+    fn is_on_fork(&self, ancestor: Slot, mut slot: Slot, commitment: CommitmentLevel) -> bool {
+        while slot > ancestor {
+            match self.slots.get(&slot).and_then(|entry| entry.parent) {
+                Some(parent) if parent < slot => slot = parent,
+                _ => {
+                    let required = match commitment {
+                        CommitmentLevel::Processed => SlotStatus::Processed,
+                        CommitmentLevel::Confirmed => SlotStatus::Confirmed,
+                        CommitmentLevel::Finalized => SlotStatus::Finalized,
+                    };
+                    return self.slots.get(&ancestor).is_some_and(|entry| {
+                        SlotStatuses::rank(entry.status) >= SlotStatuses::rank(required)
+                    });
+                }
+            }
+        }
+        slot == ancestor
+    }
+
+    /// The slot and its ancestors while parents are known, `confirmed` / `finalized` status
+    /// of a descendant is reported as implied status of ancestors with lower received status
+    /// (statuses of skipped parents are not always sent by Geyser)
+    fn get_slot_ancestry(&self, slot: Slot, limit: u32) -> Result<GetSlotAncestryResponse, Status> {
+        let limit = if limit == 0 {
+            usize::MAX
+        } else {
+            limit as usize
+        };
+
+        let mut slots = vec![];
+        let mut next = Some(slot);
+        let mut descendant_status = None;
+        while let Some((slot, entry)) =
+            next.and_then(|slot| self.slots.get(&slot).map(|entry| (slot, entry)))
+        {
+            if slots.len() >= limit {
+                break;
+            }
+
+            let status = match descendant_status {
+                Some(status) if SlotStatuses::rank(status) > SlotStatuses::rank(entry.status) => {
+                    status
+                }
+                _ => entry.status,
+            };
+            if matches!(status, SlotStatus::Confirmed | SlotStatus::Finalized) {
+                descendant_status = Some(status);
+            }
+            slots.push(SlotAncestor {
+                slot,
+                parent: entry.parent,
+                status: SlotStatusProto::from(entry.status) as i32,
+                implied_status: SlotStatusProto::from(status) as i32,
+            });
+            next = entry.parent.filter(|parent| *parent < slot);
+        }
+
+        if slots.is_empty() {
+            Err(Status::not_found("slot is not in the retention window"))
+        } else {
+            Ok(GetSlotAncestryResponse { slots })
+        }
+    }
 }
 
 #[derive(Debug)]
 struct BlockMetaStorage {
     read_sem: Semaphore,
+    warmup_block_metas: usize,
     inner: Arc<RwLock<BlockMetaStorageInner>>,
 }
 
 impl BlockMetaStorage {
-    fn new(unary_concurrency_limit: usize) -> (Self, mpsc::UnboundedSender<Message>) {
+    fn new(
+        unary_concurrency_limit: usize,
+        warmup_block_metas: usize,
+    ) -> (Self, mpsc::UnboundedSender<Message>) {
         let inner = Arc::new(RwLock::new(BlockMetaStorageInner::default()));
         let (tx, mut rx) = mpsc::unbounded_channel();
 
         let storage = Arc::clone(&inner);
         tokio::spawn(async move {
-            const KEEP_SLOTS: u64 = 3;
-
-            while let Some(message) = rx.recv().await {
-                let mut storage = storage.write().await;
-                match message {
-                    Message::Slot(msg) => {
-                        match msg.status {
-                            SlotStatus::Processed => {
-                                storage.processed.replace(msg.slot);
-                            }
-                            SlotStatus::Confirmed => {
-                                storage.confirmed.replace(msg.slot);
-                            }
-                            SlotStatus::Finalized => {
-                                storage.finalized.replace(msg.slot);
-                            }
-                            _ => {}
-                        }
-
-                        if let Some(blockhash) = storage
-                            .blocks
-                            .get(&msg.slot)
-                            .map(|block| block.blockhash.clone())
-                        {
-                            let entry = storage
-                                .blockhashes
-                                .entry(blockhash)
-                                .or_insert_with(|| BlockhashStatus::new(msg.slot));
-
-                            match msg.status {
-                                SlotStatus::Processed => {
-                                    entry.processed = true;
-                                }
-                                SlotStatus::Confirmed => {
-                                    entry.confirmed = true;
-                                }
-                                SlotStatus::Finalized => {
-                                    entry.finalized = true;
-                                }
-                                _ => {}
-                            }
-                        }
-
-                        if msg.status == SlotStatus::Finalized {
-                            if let Some(keep_slot) = msg.slot.checked_sub(KEEP_SLOTS) {
-                                storage.blocks.retain(|slot, _block| *slot >= keep_slot);
-                            }
-
-                            if let Some(keep_slot) =
-                                msg.slot.checked_sub(MAX_RECENT_BLOCKHASHES as u64 + 32)
-                            {
-                                storage
-                                    .blockhashes
-                                    .retain(|_blockhash, status| status.slot >= keep_slot);
-                            }
-                        }
-                    }
-                    Message::BlockMeta(msg) => {
-                        storage.blocks.insert(msg.slot, msg);
-                    }
-                    msg => {
-                        error!("invalid message in BlockMetaStorage: {msg:?}");
-                    }
-                }
+            let mut messages = Vec::with_capacity(BLOCK_META_STORAGE_BATCH_MAX);
+            let mut next = None;
+            // cleanup is deferred while there is a backlog, the next batch has newer
+            // finalized slot anyway
+            while let Some(drained) = Self::recv_batch(&mut rx, &mut messages, &mut next).await {
+                storage
+                    .write()
+                    .await
+                    .update_batch(messages.drain(..), drained);
             }
         });
 
         (
             Self {
                 read_sem: Semaphore::new(unary_concurrency_limit),
+                warmup_block_metas,
                 inner,
             },
             tx,
         )
     }
 
-    fn parse_commitment(commitment: Option<i32>) -> Result<CommitmentLevel, Status> {
-        let commitment = commitment.unwrap_or(CommitmentLevelProto::Processed as i32);
-        CommitmentLevelProto::try_from(commitment)
-            .map(Into::into)
-            .map_err(|_error| {
-                let msg = format!("failed to create CommitmentLevel from {commitment:?}");
-                Status::unknown(msg)
+    /// Receive messages applied with one write lock, at most `BLOCK_META_STORAGE_BATCH_MAX`
+    /// so readers never wait for more than one batch. Returns `true` if there is no backlog
+    /// after the batch, `None` once the channel is closed
+    async fn recv_batch(
+        rx: &mut mpsc::UnboundedReceiver<Message>,
+        messages: &mut Vec<Message>,
+        next: &mut Option<Message>,
+    ) -> Option<bool> {
+        let message = match next.take() {
+            Some(message) => message,
+            None => rx.recv().await?,
+        };
+        messages.push(message);
+        loop {
+            match rx.try_recv() {
+                Ok(message) if messages.len() < BLOCK_META_STORAGE_BATCH_MAX => {
+                    messages.push(message)
+                }
+                Ok(message) => {
+                    *next = Some(message);
+                    return Some(false);
+                }
+                Err(_error) => return Some(true),
+            }
+        }
+    }
+
+    /// Commitment of the request or `default_commitment` of filter limits, commitments not in
+    /// `allowed_commitments` are rejected
+    fn parse_commitment(
+        commitment: Option<i32>,
+        limits: &FilterLimits,
+    ) -> Result<CommitmentLevel, Status> {
+        let commitment = commitment
+            .map(|commitment| {
+                CommitmentLevelProto::try_from(commitment)
+                    .map(Into::into)
+                    .map_err(|_error| {
+                        let msg = format!("failed to create CommitmentLevel from {commitment:?}");
+                        Status::unknown(msg)
+                    })
             })
+            .transpose()?;
+        limits
+            .check_commitment(commitment)
+            .map_err(|error| Status::invalid_argument(error.to_string()))
     }
 
     async fn get_block<F, T>(
         &self,
         handler: F,
         commitment: Option<i32>,
+        limits: &FilterLimits,
     ) -> Result<Response<T>, Status>
     where
         F: FnOnce(&MessageBlockMeta) -> Option<T>,
     {
-        let commitment = Self::parse_commitment(commitment)?;
+        let commitment = Self::parse_commitment(commitment, limits)?;
         let _permit = self.read_sem.acquire().await;
         let storage = self.inner.read().await;
 
@@ -217,1082 +471,9535 @@ impl BlockMetaStorage {
         &self,
         blockhash: &str,
         commitment: Option<i32>,
+        limits: &FilterLimits,
     ) -> Result<Response<IsBlockhashValidResponse>, Status> {
-        let commitment = Self::parse_commitment(commitment)?;
+        let commitment = Self::parse_commitment(commitment, limits)?;
+        let _permit = self.read_sem.acquire().await;
+        let storage = self.inner.read().await;
+        storage
+            .is_blockhash_valid(blockhash, commitment, self.warmup_block_metas)
+            .map(Response::new)
+    }
+
+    async fn get_slot_ancestry(
+        &self,
+        slot: Slot,
+        limit: u32,
+    ) -> Result<Response<GetSlotAncestryResponse>, Status> {
         let _permit = self.read_sem.acquire().await;
         let storage = self.inner.read().await;
+        storage.get_slot_ancestry(slot, limit).map(Response::new)
+    }
+}
+
+fn unix_timestamp_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Default)]
+struct CommitmentSlot {
+    slot: AtomicU64,
+    updated_at: AtomicU64, // unix timestamp in nanoseconds, zero if slot never received
+}
 
-        if storage.blockhashes.len() < MAX_RECENT_BLOCKHASHES + 32 {
-            return Err(Status::internal("startup"));
+impl CommitmentSlot {
+    fn update(&self, slot: Slot) -> bool {
+        if self.slot.fetch_max(slot, Ordering::Relaxed) < slot {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_nanos() as u64)
+                .unwrap_or_default();
+            self.updated_at.store(now, Ordering::Relaxed);
+            true
+        } else {
+            false
         }
+    }
 
-        let slot = match commitment {
-            CommitmentLevel::Processed => storage.processed,
-            CommitmentLevel::Confirmed => storage.confirmed,
-            CommitmentLevel::Finalized => storage.finalized,
+    fn to_proto(&self) -> Option<CommitmentSlotProto> {
+        let updated_at = self.updated_at.load(Ordering::Relaxed);
+        (updated_at != 0).then(|| CommitmentSlotProto {
+            slot: self.slot.load(Ordering::Relaxed),
+            updated_at: Some(Timestamp {
+                seconds: (updated_at / 1_000_000_000) as i64,
+                nanos: (updated_at % 1_000_000_000) as i32,
+            }),
+        })
+    }
+}
+
+/// Highest slots received from Geyser, shared between `geyser_loop` and unary methods
+#[derive(Debug, Default)]
+struct CommitmentSlots {
+    processed: CommitmentSlot,
+    confirmed: CommitmentSlot,
+    finalized: CommitmentSlot,
+}
+
+impl CommitmentSlots {
+    fn update(&self, status: SlotStatus, slot: Slot) {
+        let (commitment, entry) = match status {
+            SlotStatus::Processed => (CommitmentLevel::Processed, &self.processed),
+            SlotStatus::Confirmed => (CommitmentLevel::Confirmed, &self.confirmed),
+            SlotStatus::Finalized => (CommitmentLevel::Finalized, &self.finalized),
+            _ => return,
+        };
+        if entry.update(slot) {
+            metrics::update_geyser_slot(commitment, slot);
         }
-        .ok_or_else(|| Status::internal("startup"))?;
+    }
 
-        let valid = storage
-            .blockhashes
-            .get(blockhash)
-            .map(|status| match commitment {
-                CommitmentLevel::Processed => status.processed,
-                CommitmentLevel::Confirmed => status.confirmed,
-                CommitmentLevel::Finalized => status.finalized,
-            })
-            .unwrap_or(false);
+    fn to_proto(&self) -> GetSlotStatusResponse {
+        GetSlotStatusResponse {
+            processed: self.processed.to_proto(),
+            confirmed: self.confirmed.to_proto(),
+            finalized: self.finalized.to_proto(),
+        }
+    }
 
-        Ok(Response::new(IsBlockhashValidResponse { valid, slot }))
+    fn get_stream_info(&self, filter: &Filter) -> FilteredUpdate {
+        let mut update = self.fill_stream_info(filter.get_stream_info());
+        update.warnings = filter.get_warnings().to_vec();
+        update
+    }
+
+    fn fill_stream_info(&self, mut msg: SubscribeUpdateStreamInfo) -> FilteredUpdate {
+        msg.processed = self.processed.to_proto();
+        msg.confirmed = self.confirmed.to_proto();
+        msg.finalized = self.finalized.to_proto();
+        msg.version = VERSION.version.to_owned();
+        FilteredUpdate::new_empty(FilteredUpdateOneof::stream_info(msg))
+    }
+
+    fn fill_ping_stats(&self, stats: &mut SubscribeUpdatePingStats) {
+        stats.processed_slot = self.processed.slot.load(Ordering::Relaxed);
+        stats.confirmed_slot = self.confirmed.slot.load(Ordering::Relaxed);
+        stats.finalized_slot = self.finalized.slot.load(Ordering::Relaxed);
     }
 }
 
+#[derive(Debug, Clone)]
+struct TrackedAccount {
+    slot: Slot,
+    account: Arc<MessageAccountInfo>,
+    origin: AccountOrigin,
+}
+
+/// Tracked accounts of one commitment level
 #[derive(Debug, Default)]
-struct MessageId {
-    id: u64,
+struct TrackedAccountsMap {
+    accounts: HashMap<Pubkey, TrackedAccount>,
+    owned: usize, // accounts tracked only by owner
 }
 
-impl MessageId {
-    fn next(&mut self) -> u64 {
-        self.id = self.id.checked_add(1).expect("message id overflow");
-        self.id
+impl TrackedAccountsMap {
+    fn insert(&mut self, pubkey: Pubkey, account: TrackedAccount, explicit: bool) {
+        if self.accounts.insert(pubkey, account).is_none() && !explicit {
+            self.owned += 1;
+        }
+    }
+
+    fn remove(&mut self, pubkey: &Pubkey, explicit: bool) {
+        if self.accounts.remove(pubkey).is_some() && !explicit {
+            self.owned -= 1;
+        }
     }
 }
 
-#[derive(Debug, Default)]
-struct SlotMessages {
-    messages: Vec<Option<(u64, Message)>>, // Option is used for accounts with low write_version
-    messages_slots: Vec<(u64, Message)>,
-    block_meta: Option<Arc<MessageBlockMeta>>,
-    transactions: Vec<Arc<MessageTransactionInfo>>,
-    accounts_dedup: HashMap<Pubkey, (u64, usize)>, // (write_version, message_index)
-    entries: Vec<Arc<MessageEntry>>,
-    sealed: bool,
-    entries_count: usize,
-    confirmed_at: Option<usize>,
-    finalized_at: Option<usize>,
-    parent_slot: Option<Slot>,
-    confirmed: bool,
-    finalized: bool,
+/// Latest values of tracked accounts by commitment, updated by `geyser_loop`
+#[derive(Debug)]
+struct TrackedAccounts {
+    pubkeys: HashSet<Pubkey>,
+    owners: HashSet<Pubkey>,
+    max_accounts: usize,
+    max_data_size: usize,
+    max_request_pubkeys: usize,
+    read_sem: Semaphore,
+    processed: RwLock<TrackedAccountsMap>,
+    confirmed: RwLock<TrackedAccountsMap>,
+    finalized: RwLock<TrackedAccountsMap>,
 }
 
-impl SlotMessages {
-    pub fn try_seal(&mut self, msgid_gen: &mut MessageId) -> Option<(u64, Message)> {
-        if !self.sealed {
-            if let Some(block_meta) = &self.block_meta {
-                let executed_transaction_count = block_meta.executed_transaction_count as usize;
-                let entries_count = block_meta.entries_count as usize;
+impl TrackedAccounts {
+    fn new(config: ConfigGrpcTrackedAccounts, unary_concurrency_limit: usize) -> Self {
+        Self {
+            pubkeys: config.pubkeys,
+            owners: config.owners,
+            max_accounts: config.max_accounts,
+            max_data_size: config.max_data_size,
+            max_request_pubkeys: config.max_request_pubkeys,
+            read_sem: Semaphore::new(unary_concurrency_limit),
+            processed: RwLock::default(),
+            confirmed: RwLock::default(),
+            finalized: RwLock::default(),
+        }
+    }
 
-                // Additional check `entries_count == 0` due to bug of zero entries on block produced by validator
-                // See GitHub issue: https://github.com/solana-labs/solana/issues/33823
-                if self.transactions.len() == executed_transaction_count
-                    && (entries_count == 0 || self.entries.len() == entries_count)
-                {
-                    let transactions = std::mem::take(&mut self.transactions);
-                    let mut entries = std::mem::take(&mut self.entries);
-                    if entries_count == 0 {
-                        entries.clear();
-                    }
+    const fn get_accounts(&self, commitment: CommitmentLevel) -> &RwLock<TrackedAccountsMap> {
+        match commitment {
+            CommitmentLevel::Processed => &self.processed,
+            CommitmentLevel::Confirmed => &self.confirmed,
+            CommitmentLevel::Finalized => &self.finalized,
+        }
+    }
 
-                    let mut accounts = Vec::with_capacity(self.messages.len());
-                    for item in self.messages.iter().flatten() {
-                        if let (_msgid, Message::Account(account)) = item {
-                            accounts.push(Arc::clone(&account.account));
-                        }
-                    }
+    fn is_tracked(&self, account: &MessageAccountInfo) -> bool {
+        self.pubkeys.contains(&account.pubkey) || self.owners.contains(&account.owner)
+    }
+
+    async fn update(&self, commitment: CommitmentLevel, messages: &[(u64, Message)]) {
+        let mut updates = messages
+            .iter()
+            .filter_map(|(_msgid, message)| match message {
+                Message::Account(msg) => Some(msg),
+                _ => None,
+            })
+            .peekable();
+        if updates.peek().is_none() {
+            return;
+        }
+
+        // tracked sets are immutable, so the write lock is taken only if something changes:
+        // a tracked account is updated or an account with changed owner is removed
+        if !updates.clone().any(|msg| self.is_tracked(&msg.account)) {
+            if self.owners.is_empty() {
+                return;
+            }
+            let accounts = self.get_accounts(commitment).read().await;
+            if !updates.any(|msg| accounts.accounts.contains_key(&msg.account.pubkey)) {
+                return;
+            }
+        }
+
+        let mut accounts = self.get_accounts(commitment).write().await;
+        for (_msgid, message) in messages {
+            let Message::Account(msg) = message else {
+                continue;
+            };
+            let pubkey = msg.account.pubkey;
+            let explicit = self.pubkeys.contains(&pubkey);
+            if !self.is_tracked(&msg.account) {
+                // owner changed
+                if !self.owners.is_empty() {
+                    accounts.remove(&pubkey, explicit);
+                }
+                continue;
+            }
 
-                    let message_block = Message::Block(Arc::new(MessageBlock::new(
-                        Arc::clone(block_meta),
-                        transactions,
-                        accounts,
-                        entries,
-                    )));
-                    let message = (msgid_gen.next(), message_block);
-                    self.messages.push(Some(message.clone()));
+            if msg.account.data.len() > self.max_data_size {
+                accounts.remove(&pubkey, explicit);
+                continue;
+            }
 
-                    self.sealed = true;
-                    self.entries_count = entries_count;
-                    return Some(message);
+            // explicit pubkeys always have a place, owned accounts use the rest of `max_accounts`
+            match accounts.accounts.get(&pubkey) {
+                Some(entry)
+                    if (entry.slot, entry.account.write_version)
+                        > (msg.slot, msg.account.write_version) =>
+                {
+                    continue
+                }
+                None if !explicit
+                    && accounts.owned >= self.max_accounts.saturating_sub(self.pubkeys.len()) =>
+                {
+                    continue
                 }
+                _ => {}
             }
+            accounts.insert(
+                pubkey,
+                TrackedAccount {
+                    slot: msg.slot,
+                    account: Arc::clone(&msg.account),
+                    origin: msg.get_origin(),
+                },
+                explicit,
+            );
         }
+    }
 
-        None
+    async fn get_snapshot(
+        &self,
+        request: &GetAccountsSnapshotRequest,
+        limits: &FilterLimits,
+    ) -> Result<GetAccountsSnapshotResponse, Status> {
+        let commitment = BlockMetaStorage::parse_commitment(request.commitment, limits)?;
+        if request.pubkeys.len() > self.max_request_pubkeys {
+            return Err(Status::invalid_argument(format!(
+                "max amount of pubkeys reached, only {} allowed",
+                self.max_request_pubkeys
+            )));
+        }
+        let _permit = self.read_sem.acquire().await;
+        let accounts = self.get_accounts(commitment).read().await;
+        let processed = if commitment == CommitmentLevel::Processed || self.owners.is_empty() {
+            None
+        } else {
+            Some(self.processed.read().await)
+        };
+
+        let accounts = request
+            .pubkeys
+            .iter()
+            .map(|value| {
+                let mut snapshot = AccountSnapshot {
+                    pubkey: value.clone(),
+                    ..Default::default()
+                };
+                let status = match Pubkey::from_str(value) {
+                    Ok(pubkey) => match accounts.accounts.get(&pubkey) {
+                        Some(entry) => {
+                            snapshot.account = Some(SubscribeUpdateAccountInfo {
+                                pubkey: entry.account.pubkey.as_ref().into(),
+                                lamports: entry.account.lamports,
+                                owner: entry.account.owner.as_ref().into(),
+                                executable: entry.account.executable,
+                                rent_epoch: entry.account.rent_epoch,
+                                data: convert_account_data(entry.account.data.clone()),
+                                write_version: entry.account.write_version,
+                                txn_signature: entry
+                                    .account
+                                    .txn_signature
+                                    .map(|signature| signature.as_ref().into()),
+                                origin: Some(entry.origin as i32),
+                            });
+                            snapshot.slot = entry.slot;
+                            AccountSnapshotStatus::AccountSnapshotFound
+                        }
+                        None if self.pubkeys.contains(&pubkey)
+                            || processed.as_ref().is_some_and(|processed| {
+                                processed.accounts.contains_key(&pubkey)
+                            }) =>
+                        {
+                            AccountSnapshotStatus::AccountSnapshotNotAvailable
+                        }
+                        None => AccountSnapshotStatus::AccountSnapshotNotTracked,
+                    },
+                    Err(_error) => AccountSnapshotStatus::AccountSnapshotInvalidPubkey,
+                };
+                snapshot.status = status as i32;
+                snapshot
+            })
+            .collect();
+
+        Ok(GetAccountsSnapshotResponse { accounts })
     }
 }
 
-type BroadcastedMessage = (CommitmentLevel, Arc<Vec<(u64, Message)>>);
-
-enum ReplayedResponse {
-    Messages(Vec<(u64, Message)>),
-    Lagged(Slot),
+#[derive(Debug)]
+struct RecentTransaction {
+    slot: Slot,
+    err: Option<TransactionError>,
 }
 
-type ReplayStoredSlotsRequest = (CommitmentLevel, Slot, oneshot::Sender<ReplayedResponse>);
+impl RecentTransaction {
+    fn get_err_size(err: &Option<TransactionError>) -> usize {
+        err.as_ref().map_or(0, |err| err.err.len())
+    }
+}
 
 #[derive(Debug)]
-pub struct GrpcService {
-    config_snapshot_client_channel_capacity: usize,
-    config_channel_capacity: usize,
-    config_filter_limits: Arc<FilterLimits>,
-    blocks_meta: Option<BlockMetaStorage>,
-    subscribe_id: AtomicUsize,
-    snapshot_rx: Mutex<Option<crossbeam_channel::Receiver<Box<Message>>>>,
-    broadcast_tx: broadcast::Sender<BroadcastedMessage>,
-    replay_stored_slots_tx: Option<mpsc::Sender<ReplayStoredSlotsRequest>>,
-    debug_clients_tx: Option<mpsc::UnboundedSender<DebugClientMessage>>,
-    filter_names: Arc<Mutex<FilterNames>>,
+struct RecentTransactionsSlot {
+    commitment: CommitmentLevel,
+    parent: Option<Slot>,
+    signatures: Vec<Signature>,
 }
 
-impl GrpcService {
-    #[allow(clippy::type_complexity)]
-    pub async fn create(
-        config_tokio: ConfigTokio,
-        config: ConfigGrpc,
-        debug_clients_tx: Option<mpsc::UnboundedSender<DebugClientMessage>>,
-        is_reload: bool,
-    ) -> anyhow::Result<(
-        Option<crossbeam_channel::Sender<Box<Message>>>,
-        mpsc::UnboundedSender<Message>,
-        Arc<Notify>,
-    )> {
-        // Bind service address
-        let incoming = TcpIncoming::new(
-            config.address,
-            true,                          // tcp_nodelay
-            Some(Duration::from_secs(20)), // tcp_keepalive
-        )
-        .map_err(|error| anyhow::anyhow!(error))?;
+#[derive(Debug, Default)]
+struct RecentTransactionsInner {
+    transactions: HashMap<Signature, RecentTransaction>,
+    slots: BTreeMap<Slot, RecentTransactionsSlot>,
+    errors_bytes: usize,     // bytes of errors of stored transactions
+    signatures_count: usize, // signatures of all slots, including replaced transactions
+}
 
-        // Snapshot channel
-        let (snapshot_tx, snapshot_rx) = match config.snapshot_plugin_channel_capacity {
-            Some(cap) if !is_reload => {
-                let (tx, rx) = crossbeam_channel::bounded(cap);
-                (Some(tx), Some(rx))
+impl RecentTransactionsInner {
+    fn get_slot(&mut self, slot: Slot) -> &mut RecentTransactionsSlot {
+        self.slots
+            .entry(slot)
+            .or_insert_with(|| RecentTransactionsSlot {
+                commitment: CommitmentLevel::Processed,
+                parent: None,
+                signatures: vec![],
+            })
+    }
+
+    /// Commitment of the slot is applied to its ancestors too, so statuses skipped on lag are
+    /// restored by the next status of the same fork
+    fn set_commitment(&mut self, slot: Slot, commitment: CommitmentLevel) {
+        let entry = self.get_slot(slot);
+        entry.commitment = entry.commitment.max(commitment);
+        let mut parent = entry.parent;
+        while let Some(entry) = parent.and_then(|slot| self.slots.get_mut(&slot)) {
+            if entry.commitment >= commitment {
+                break;
             }
-            _ => (None, None),
-        };
+            entry.commitment = commitment;
+            parent = entry.parent;
+        }
+    }
 
-        // Blocks meta storage
-        let (blocks_meta, blocks_meta_tx) = if config.unary_disabled {
-            (None, None)
-        } else {
-            let (blocks_meta, blocks_meta_tx) =
-                BlockMetaStorage::new(config.unary_concurrency_limit);
-            (Some(blocks_meta), Some(blocks_meta_tx))
-        };
+    /// Statuses skipped on lag are restored from the highest confirmed and finalized slots,
+    /// slots of other forks stay at the received commitment
+    fn resync(&mut self, commitment_slots: &CommitmentSlots) {
+        for (commitment, slot) in [
+            (CommitmentLevel::Finalized, &commitment_slots.finalized),
+            (CommitmentLevel::Confirmed, &commitment_slots.confirmed),
+        ] {
+            let slot = slot.slot.load(Ordering::Relaxed);
+            if self.slots.contains_key(&slot) {
+                self.set_commitment(slot, commitment);
+            }
+        }
+    }
 
-        // Messages to clients combined by commitment
-        let (broadcast_tx, _) = broadcast::channel(config.channel_capacity);
-        // attempt to prevent spam of geyser loop with capacity eq 1
-        let (replay_stored_slots_tx, replay_stored_slots_rx) = if config.replay_stored_slots == 0 {
-            (None, None)
-        } else {
-            let (tx, rx) = mpsc::channel(1);
-            (Some(tx), Some(rx))
-        };
+    fn update(&mut self, messages: &[(u64, Message)], include_votes: bool) {
+        for (_msgid, message) in messages {
+            match message {
+                Message::Transaction(msg) if include_votes || !msg.transaction.is_vote => {
+                    let signature = msg.transaction.signature;
+                    let err = msg.transaction.meta.err.clone();
+                    self.errors_bytes += RecentTransaction::get_err_size(&err);
+                    if let Some(tx) = self.transactions.insert(
+                        signature,
+                        RecentTransaction {
+                            slot: msg.slot,
+                            err,
+                        },
+                    ) {
+                        self.errors_bytes -= RecentTransaction::get_err_size(&tx.err);
+                    }
+                    self.get_slot(msg.slot).signatures.push(signature);
+                    self.signatures_count += 1;
+                }
+                Message::Slot(msg) => {
+                    let commitment = match msg.status {
+                        SlotStatus::Confirmed => CommitmentLevel::Confirmed,
+                        SlotStatus::Finalized => CommitmentLevel::Finalized,
+                        _ => CommitmentLevel::Processed,
+                    };
+                    if msg.parent.is_some() {
+                        self.get_slot(msg.slot).parent = msg.parent;
+                    }
+                    self.set_commitment(msg.slot, commitment);
+                }
+                _ => {}
+            }
+        }
+    }
 
-        // gRPC server builder with optional TLS
-        let mut server_builder = Server::builder();
-        if let Some(tls_config) = &config.tls_config {
-            let (cert, key) = tokio::try_join!(
-                fs::read(&tls_config.cert_path),
-                fs::read(&tls_config.key_path)
-            )
-            .context("failed to load tls_config files")?;
-            server_builder = server_builder
-                .tls_config(ServerTlsConfig::new().identity(Identity::from_pem(cert, key)))
-                .context("failed to apply tls_config")?;
+    fn prune(&mut self, max_slots: u64, max_transactions: usize) {
+        let Some(last_slot) = self.slots.keys().next_back().copied() else {
+            return;
+        };
+        let first_slot = last_slot.saturating_sub(max_slots);
+        while let Some(entry) = self.slots.first_entry() {
+            if *entry.key() >= first_slot && self.transactions.len() <= max_transactions {
+                break;
+            }
+            let (slot, entry) = entry.remove_entry();
+            self.signatures_count -= entry.signatures.len();
+            for signature in entry.signatures {
+                // the same transaction can be in the slot on another fork
+                if let HashMapEntry::Occupied(entry) = self.transactions.entry(signature) {
+                    if entry.get().slot == slot {
+                        self.errors_bytes -= RecentTransaction::get_err_size(&entry.remove().err);
+                    }
+                }
+            }
         }
-        if let Some(enabled) = config.server_http2_adaptive_window {
-            server_builder = server_builder.http2_adaptive_window(Some(enabled));
+    }
+
+    /// Approximate memory usage of the index, counted without iteration over the index
+    fn get_size(&self) -> usize {
+        self.transactions.capacity() * (std::mem::size_of::<(Signature, RecentTransaction)>() + 1)
+            + self.errors_bytes
+            + self.slots.len() * std::mem::size_of::<(Slot, RecentTransactionsSlot)>()
+            + self.signatures_count * std::mem::size_of::<Signature>()
+    }
+}
+
+/// Transactions of the last slots with errors and commitment of the slot, updated from
+/// the broadcast of processed messages, so lookups never block `geyser_loop`
+#[derive(Debug)]
+struct RecentTransactions {
+    read_sem: Semaphore,
+    inner: Arc<RwLock<RecentTransactionsInner>>,
+}
+
+impl RecentTransactions {
+    fn new(
+        config: ConfigGrpcRecentTransactions,
+        unary_concurrency_limit: usize,
+        mut messages_rx: broadcast::Receiver<BroadcastedMessage>,
+        commitment_slots: Arc<CommitmentSlots>,
+    ) -> Self {
+        let inner = Arc::new(RwLock::new(RecentTransactionsInner::default()));
+
+        let storage = Arc::clone(&inner);
+        tokio::spawn(async move {
+            loop {
+                let messages = match messages_rx.recv().await {
+                    Ok((CommitmentLevel::Processed, messages)) => messages,
+                    Ok(_) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                    Err(broadcast::error::RecvError::Lagged(count)) => {
+                        error!("recent transactions: lagged to receive {count} broadcasts");
+                        storage.write().await.resync(&commitment_slots);
+                        continue;
+                    }
+                };
+
+                let mut storage = storage.write().await;
+                storage.update(&messages, config.include_votes);
+                storage.prune(config.max_slots, config.max_transactions);
+                metrics::update_recent_transactions(storage.transactions.len(), storage.get_size());
+            }
+        });
+
+        Self {
+            read_sem: Semaphore::new(unary_concurrency_limit),
+            inner,
         }
-        if let Some(http2_keepalive_interval) = config.server_http2_keepalive_interval {
-            server_builder =
-                server_builder.http2_keepalive_interval(Some(http2_keepalive_interval));
+    }
+
+    async fn get_status(
+        &self,
+        request: &GetTransactionStatusRequest,
+    ) -> Result<GetTransactionStatusResponse, Status> {
+        let signature = Signature::from_str(&request.signature)
+            .map_err(|_error| Status::invalid_argument("failed to parse signature"))?;
+
+        let _permit = self.read_sem.acquire().await;
+        let inner = self.inner.read().await;
+        let Some(tx) = inner.transactions.get(&signature) else {
+            return Err(Status::not_found("transaction not found"));
+        };
+        let commitment = inner
+            .slots
+            .get(&tx.slot)
+            .map_or(CommitmentLevel::Processed, |entry| entry.commitment);
+        Ok(GetTransactionStatusResponse {
+            slot: tx.slot,
+            err: tx.err.clone(),
+            commitment: CommitmentLevelProto::from(commitment) as i32,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct MessageId {
+    id: u64,
+}
+
+impl MessageId {
+    fn next(&mut self) -> u64 {
+        self.id = self.id.checked_add(1).expect("message id overflow");
+        self.id
+    }
+}
+
+/// Highest commitment status processed per slot, used to ignore duplicated statuses and
+/// regressions (e.g. `Confirmed` re-sent after `Finalized` on plugin reload)
+#[derive(Debug, Default)]
+struct SlotStatuses {
+    statuses: BTreeMap<Slot, SlotStatus>,
+    // statuses of older slots are pruned and always ignored
+    min_slot: Slot,
+}
+
+impl SlotStatuses {
+    const fn rank(status: SlotStatus) -> Option<u8> {
+        match status {
+            SlotStatus::Processed => Some(0),
+            SlotStatus::Confirmed => Some(1),
+            SlotStatus::Finalized => Some(2),
+            _ => None,
         }
-        if let Some(http2_keepalive_timeout) = config.server_http2_keepalive_timeout {
-            server_builder = server_builder.http2_keepalive_timeout(Some(http2_keepalive_timeout));
+    }
+
+    /// Returns `false` if the same or higher status of the slot was already processed
+    fn update(&mut self, slot: Slot, status: SlotStatus) -> bool {
+        let Some(rank) = Self::rank(status) else {
+            return true;
+        };
+        if slot < self.min_slot {
+            return false;
         }
-        if let Some(sz) = config.server_initial_connection_window_size {
-            server_builder = server_builder.initial_connection_window_size(sz);
+        match self.statuses.get(&slot).copied().and_then(Self::rank) {
+            Some(current) if current >= rank => false,
+            _ => {
+                self.statuses.insert(slot, status);
+                true
+            }
         }
-        if let Some(sz) = config.server_initial_stream_window_size {
-            server_builder = server_builder.initial_stream_window_size(sz);
+    }
+
+    fn prune(&mut self, min_slot: Slot) {
+        if min_slot > self.min_slot {
+            self.statuses = self.statuses.split_off(&min_slot);
+            self.min_slot = min_slot;
         }
+    }
+}
 
-        let filter_names = Arc::new(Mutex::new(FilterNames::new(
-            config.filter_name_size_limit,
-            config.filter_names_size_limit,
-            config.filter_names_cleanup_interval,
-        )));
+/// Votes of one vote account, latency of the highest voted slot is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct VotesEntry {
+    voted_on_slot: Slot,
+    latency_slots: u64,
+    count: u64,
+}
 
-        // Create Server
-        let max_decoding_message_size = config.max_decoding_message_size;
-        let mut service = GeyserServer::new(Self {
-            config_snapshot_client_channel_capacity: config.snapshot_client_channel_capacity,
-            config_channel_capacity: config.channel_capacity,
-            config_filter_limits: Arc::new(config.filter_limits),
-            blocks_meta,
-            subscribe_id: AtomicUsize::new(0),
-            snapshot_rx: Mutex::new(snapshot_rx),
-            broadcast_tx: broadcast_tx.clone(),
-            replay_stored_slots_tx,
-            debug_clients_tx,
-            filter_names,
-        })
-        .max_decoding_message_size(max_decoding_message_size);
-        for encoding in config.compression.accept {
-            service = service.accept_compressed(encoding);
+impl VotesEntry {
+    fn merge(&mut self, other: Self) {
+        self.count += other.count;
+        if other.voted_on_slot > self.voted_on_slot {
+            self.voted_on_slot = other.voted_on_slot;
+            self.latency_slots = other.latency_slots;
+        } else if other.voted_on_slot == self.voted_on_slot {
+            self.latency_slots = self.latency_slots.min(other.latency_slots);
         }
-        for encoding in config.compression.send {
-            service = service.send_compressed(encoding);
+    }
+}
+
+#[derive(Debug)]
+struct VotesWindow {
+    from_slot: Slot,
+    votes: HashMap<Pubkey, VotesEntry>,
+}
+
+/// Vote transactions aggregated per vote account over windows of `window_slots` slots.
+/// Votes of the slot are added to the window on the processed status of the slot, the
+/// window is closed by the status of its last slot or of a slot from another window.
+#[derive(Debug)]
+struct VotesAggregator {
+    config: ConfigGrpcVotes,
+    // votes of slots without processed status, dead slots are removed on finalized status
+    slots: BTreeMap<Slot, HashMap<Pubkey, VotesEntry>>,
+    window: Option<VotesWindow>,
+}
+
+impl VotesAggregator {
+    const fn new(config: ConfigGrpcVotes) -> Self {
+        Self {
+            config,
+            slots: BTreeMap::new(),
+            window: None,
         }
+    }
 
-        // Run geyser message loop
-        let (messages_tx, messages_rx) = mpsc::unbounded_channel();
-        spawn_blocking(move || {
-            let mut builder = Builder::new_multi_thread();
-            if let Some(worker_threads) = config_tokio.worker_threads {
-                builder.worker_threads(worker_threads);
+    /// Returns votes of closed windows, sent with the slot of the status message
+    fn update(&mut self, message: &Message) -> Vec<SubscribeUpdateVotes> {
+        match message {
+            Message::Transaction(msg) => {
+                if let Some((vote_pubkey, voted_on_slot)) = msg.transaction.get_vote() {
+                    let entry = VotesEntry {
+                        voted_on_slot,
+                        latency_slots: msg.slot.saturating_sub(voted_on_slot),
+                        count: 1,
+                    };
+                    let votes = self.slots.entry(msg.slot).or_default();
+                    if !Self::insert(votes, vote_pubkey, entry, self.config.max_vote_accounts) {
+                        metrics::votes_dropped_inc();
+                    }
+                }
+                vec![]
             }
-            if let Some(tokio_cpus) = config_tokio.affinity.clone() {
-                builder.on_thread_start(move || {
-                    affinity::set_thread_affinity(&tokio_cpus).expect("failed to set affinity")
-                });
+            Message::Slot(msg) => match msg.status {
+                SlotStatus::Processed => self.close_slot(msg.slot),
+                SlotStatus::Finalized => {
+                    self.slots = self.slots.split_off(&(msg.slot + 1));
+                    vec![]
+                }
+                SlotStatus::Dead => {
+                    self.slots.remove(&msg.slot);
+                    vec![]
+                }
+                _ => vec![],
+            },
+            _ => vec![],
+        }
+    }
+
+    /// Returns `false` if the vote account is new and `max_vote_accounts` is reached
+    fn insert(
+        votes: &mut HashMap<Pubkey, VotesEntry>,
+        vote_pubkey: Pubkey,
+        entry: VotesEntry,
+        max_vote_accounts: usize,
+    ) -> bool {
+        let is_full = votes.len() >= max_vote_accounts;
+        match votes.entry(vote_pubkey) {
+            HashMapEntry::Occupied(mut current) => current.get_mut().merge(entry),
+            HashMapEntry::Vacant(_) if is_full => return false,
+            HashMapEntry::Vacant(vacant) => {
+                vacant.insert(entry);
             }
-            builder
-                .thread_name_fn(crate::get_thread_name)
-                .enable_all()
-                .build()
-                .expect("Failed to create a new runtime for geyser loop")
-                .block_on(Self::geyser_loop(
-                    messages_rx,
-                    blocks_meta_tx,
-                    broadcast_tx,
-                    replay_stored_slots_rx,
-                    config.replay_stored_slots,
-                ));
-        });
+        }
+        true
+    }
 
-        // Run Server
-        let shutdown = Arc::new(Notify::new());
-        let shutdown_grpc = Arc::clone(&shutdown);
-        tokio::spawn(async move {
-            // gRPC Health check service
-            let (mut health_reporter, health_service) = health_reporter();
-            health_reporter.set_serving::<GeyserServer<Self>>().await;
+    fn close_slot(&mut self, slot: Slot) -> Vec<SubscribeUpdateVotes> {
+        let mut updates = vec![];
+        let from_slot = slot - slot % self.config.window_slots;
+        if matches!(&self.window, Some(window) if window.from_slot != from_slot) {
+            updates.extend(self.take_window(slot));
+        }
 
-            server_builder
-                .layer(interceptor(move |request: Request<()>| {
-                    if let Some(x_token) = &config.x_token {
-                        match request.metadata().get("x-token") {
-                            Some(token) if x_token == token => Ok(request),
-                            _ => Err(Status::unauthenticated("No valid auth token")),
-                        }
-                    } else {
-                        Ok(request)
-                    }
-                }))
-                .add_service(health_service)
-                .add_service(service)
-                .serve_with_incoming_shutdown(incoming, shutdown_grpc.notified())
-                .await
+        let window = self.window.get_or_insert_with(|| VotesWindow {
+            from_slot,
+            votes: HashMap::new(),
         });
+        for (vote_pubkey, entry) in self.slots.remove(&slot).unwrap_or_default() {
+            if !Self::insert(
+                &mut window.votes,
+                vote_pubkey,
+                entry,
+                self.config.max_vote_accounts,
+            ) {
+                metrics::votes_dropped_inc();
+            }
+        }
 
-        Ok((snapshot_tx, messages_tx, shutdown))
+        if slot >= from_slot + self.config.window_slots - 1 {
+            updates.extend(self.take_window(slot));
+        }
+        updates
     }
 
-    async fn geyser_loop(
-        mut messages_rx: mpsc::UnboundedReceiver<Message>,
-        blocks_meta_tx: Option<mpsc::UnboundedSender<Message>>,
-        broadcast_tx: broadcast::Sender<BroadcastedMessage>,
-        replay_stored_slots_rx: Option<mpsc::Receiver<ReplayStoredSlotsRequest>>,
-        replay_stored_slots: u64,
-    ) {
-        const PROCESSED_MESSAGES_MAX: usize = 31;
-        const PROCESSED_MESSAGES_SLEEP: Duration = Duration::from_millis(10);
+    fn take_window(&mut self, slot: Slot) -> Option<SubscribeUpdateVotes> {
+        let window = self.window.take()?;
+        if window.votes.is_empty() {
+            return None;
+        }
 
-        let mut msgid_gen = MessageId::default();
-        let mut messages: BTreeMap<u64, SlotMessages> = Default::default();
-        let mut processed_messages = Vec::with_capacity(PROCESSED_MESSAGES_MAX);
-        let mut processed_first_slot = None;
-        let processed_sleep = sleep(PROCESSED_MESSAGES_SLEEP);
-        tokio::pin!(processed_sleep);
-        let (_tx, rx) = mpsc::channel(1);
-        let mut replay_stored_slots_rx = replay_stored_slots_rx.unwrap_or(rx);
+        let mut entries = window
+            .votes
+            .into_iter()
+            .map(|(vote_pubkey, entry)| SubscribeUpdateVotesEntry {
+                vote_pubkey: vote_pubkey.to_bytes().to_vec(),
+                voted_on_slot: entry.voted_on_slot,
+                latency_slots: entry.latency_slots,
+                count: entry.count,
+            })
+            .collect::<Vec<_>>();
+        entries.sort_unstable_by(|a, b| a.vote_pubkey.cmp(&b.vote_pubkey));
+        Some(SubscribeUpdateVotes {
+            slot,
+            from_slot: window.from_slot,
+            to_slot: window.from_slot + self.config.window_slots - 1,
+            entries,
+        })
+    }
+}
 
-        loop {
-            tokio::select! {
-                Some(message) = messages_rx.recv() => {
-                    metrics::message_queue_size_dec();
-                    let msgid = msgid_gen.next();
+#[derive(Debug, Default)]
+struct SlotStatsState {
+    account_updates: u64,
+    accounts: HashSet<Pubkey>,
+    data_bytes: u64,
+    // account updates and data bytes per owner, up to `max_owners`
+    owners: HashMap<Pubkey, (u64, u64)>,
+    // min-heap of owners by account updates, one entry per owner, updates of the entry can
+    // be behind the map
+    owners_heap: BinaryHeap<Reverse<(u64, Pubkey)>>,
+}
 
-                    // Update metrics
-                    if let Message::Slot(slot_message) = &message {
-                        metrics::update_slot_plugin_status(slot_message.status, slot_message.slot);
-                    }
+impl SlotStatsState {
+    /// Once `max_owners` owners are counted, the owner with the least account updates is
+    /// replaced by the new one and the new owner starts from its account updates
+    /// (Space-Saving), so owners with many updates are not lost
+    fn update_owner(&mut self, owner: Pubkey, data_bytes: u64, max_owners: usize) {
+        if let Some(entry) = self.owners.get_mut(&owner) {
+            entry.0 += 1;
+            entry.1 += data_bytes;
+            return;
+        }
 
-                    // Update blocks info
-                    if let Some(blocks_meta_tx) = &blocks_meta_tx {
-                        if matches!(&message, Message::Slot(_) | Message::BlockMeta(_)) {
-                            let _ = blocks_meta_tx.send(message.clone());
-                        }
+        let mut account_updates = 1;
+        if self.owners.len() >= max_owners {
+            while let Some(Reverse((updates, min_owner))) = self.owners_heap.pop() {
+                match self.owners.get(&min_owner) {
+                    Some((current, _)) if *current != updates => {
+                        self.owners_heap.push(Reverse((*current, min_owner)));
+                    }
+                    Some(_) => {
+                        self.owners.remove(&min_owner);
+                        account_updates += updates;
+                        break;
                     }
+                    None => {}
+                }
+            }
+        }
+        self.owners.insert(owner, (account_updates, data_bytes));
+        self.owners_heap.push(Reverse((account_updates, owner)));
+    }
+}
 
-                    // Remove outdated block reconstruction info
-                    match &message {
-                        // On startup we can receive multiple Confirmed/Finalized slots without BlockMeta message
-                        // With saved first Processed slot we can ignore errors caused by startup process
-                        Message::Slot(msg) if processed_first_slot.is_none() && msg.status == SlotStatus::Processed => {
-                            processed_first_slot = Some(msg.slot);
-                        }
-                        Message::Slot(msg) if msg.status == SlotStatus::Finalized => {
-                            // keep extra 10 slots + slots for replay
-                            if let Some(msg_slot) = msg.slot.checked_sub(10 + replay_stored_slots) {
-                                loop {
-                                    match messages.keys().next().cloned() {
-                                        Some(slot) if slot < msg_slot => {
-                                            if let Some(slot_messages) = messages.remove(&slot) {
-                                                match processed_first_slot {
-                                                    Some(processed_first) if slot <= processed_first => continue,
-                                                    None => continue,
-                                                    _ => {}
-                                                }
+/// Account writes counted per slot, statistics of the slot are created on the processed status
+/// of the slot. Startup accounts are not counted.
+#[derive(Debug)]
+struct SlotStatsAggregator {
+    config: ConfigGrpcSlotStats,
+    // slots without processed status, dead slots are removed on finalized status
+    slots: BTreeMap<Slot, SlotStatsState>,
+}
 
-                                                if !slot_messages.sealed && slot_messages.finalized_at.is_some() {
-                                                    let mut reasons = vec![];
-                                                    if let Some(block_meta) = slot_messages.block_meta {
-                                                        let block_txn_count = block_meta.executed_transaction_count as usize;
-                                                        let msg_txn_count = slot_messages.transactions.len();
-                                                        if block_txn_count != msg_txn_count {
-                                                            reasons.push("InvalidTxnCount");
-                                                            error!("failed to reconstruct #{slot} -- tx count: {block_txn_count} vs {msg_txn_count}");
-                                                        }
-                                                        let block_entries_count = block_meta.entries_count as usize;
-                                                        let msg_entries_count = slot_messages.entries.len();
-                                                        if block_entries_count != msg_entries_count {
-                                                            reasons.push("InvalidEntriesCount");
-                                                            error!("failed to reconstruct #{slot} -- entries count: {block_entries_count} vs {msg_entries_count}");
-                                                        }
-                                                    } else {
-                                                        reasons.push("NoBlockMeta");
-                                                    }
-                                                    let reason = reasons.join(",");
-
-                                                    metrics::update_invalid_blocks(format!("failed reconstruct {reason}"));
-                                                }
-                                            }
-                                        }
-                                        _ => break,
-                                    }
-                                }
-                            }
-                        }
-                        _ => {}
-                    }
+impl SlotStatsAggregator {
+    const fn new(config: ConfigGrpcSlotStats) -> Self {
+        Self {
+            config,
+            slots: BTreeMap::new(),
+        }
+    }
 
-                    // Update block reconstruction info
-                    let slot_messages = messages.entry(message.get_slot()).or_default();
-                    if let Message::Slot(msg) = &message {
-                        match msg.status {
-                            SlotStatus::Processed => {
-                                slot_messages.parent_slot = msg.parent;
-                            },
-                            SlotStatus::Confirmed => {
-                                slot_messages.confirmed = true;
-                            },
-                            SlotStatus::Finalized => {
-                                slot_messages.finalized = true;
-                            },
-                            _ => {}
-                        }
-                    }
-                    if matches!(&message, Message::Slot(_)) {
-                        slot_messages.messages_slots.push((msgid, message.clone()));
-                    } else {
-                        slot_messages.messages.push(Some((msgid, message.clone())));
-
-                        // If we already build Block message, new message will be a problem
-                        if slot_messages.sealed && !(matches!(&message, Message::Entry(_)) && slot_messages.entries_count == 0) {
-                            let kind = match &message {
-                                Message::Slot(_) => "Slot",
-                                Message::Account(_) => "Account",
-                                Message::Transaction(_) => "Transaction",
-                                Message::Entry(_) => "Entry",
-                                Message::BlockMeta(_) => "BlockMeta",
-                                Message::Block(_) => "Block",
-                            };
-                            metrics::update_invalid_blocks(format!("unexpected message {kind}"));
-                        }
-                    }
-                    let mut sealed_block_msg = None;
-                    match &message {
-                        Message::BlockMeta(msg) => {
-                            if slot_messages.block_meta.is_some() {
-                                metrics::update_invalid_blocks("unexpected message: BlockMeta (duplicate)");
-                            }
-                            slot_messages.block_meta = Some(Arc::clone(msg));
-                            sealed_block_msg = slot_messages.try_seal(&mut msgid_gen);
-                        }
-                        Message::Transaction(msg) => {
-                            slot_messages.transactions.push(Arc::clone(&msg.transaction));
-                            sealed_block_msg = slot_messages.try_seal(&mut msgid_gen);
-                        }
-                        // Dedup accounts by max write_version
-                        Message::Account(msg) => {
-                            let write_version = msg.account.write_version;
-                            let msg_index = slot_messages.messages.len() - 1;
-                            if let Some(entry) = slot_messages.accounts_dedup.get_mut(&msg.account.pubkey) {
-                                if entry.0 < write_version {
-                                    // We can replace the message, but in this case we will lose the order
-                                    slot_messages.messages[entry.1] = None;
-                                    *entry = (write_version, msg_index);
-                                }
-                            } else {
-                                slot_messages.accounts_dedup.insert(msg.account.pubkey, (write_version, msg_index));
+    fn update(&mut self, message: &Message) -> Option<SubscribeUpdateSlotStats> {
+        match message {
+            Message::Account(msg) if !msg.is_startup => {
+                let data_bytes = msg.account.data.len() as u64;
+                let state = self.slots.entry(msg.slot).or_default();
+                state.account_updates += 1;
+                state.accounts.insert(msg.account.pubkey);
+                state.data_bytes += data_bytes;
+                state.update_owner(
+                    msg.account.owner,
+                    data_bytes,
+                    self.config.max_owners.max(self.config.top_owners),
+                );
+                None
+            }
+            Message::Slot(msg) => match msg.status {
+                SlotStatus::Processed => Some(self.take_slot(msg.slot)),
+                SlotStatus::Finalized => {
+                    self.slots = self.slots.split_off(&(msg.slot + 1));
+                    None
+                }
+                SlotStatus::Dead => {
+                    self.slots.remove(&msg.slot);
+                    None
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn take_slot(&mut self, slot: Slot) -> SubscribeUpdateSlotStats {
+        let state = self.slots.remove(&slot).unwrap_or_default();
+
+        let mut owners = state.owners.into_iter().collect::<Vec<_>>();
+        // most updated owners first, ties are ordered by owner for stable output
+        let cmp = |(a_owner, (a_updates, _)): &(Pubkey, (u64, u64)),
+                   (b_owner, (b_updates, _)): &(Pubkey, (u64, u64))| {
+            b_updates.cmp(a_updates).then_with(|| a_owner.cmp(b_owner))
+        };
+        if owners.len() > self.config.top_owners {
+            if self.config.top_owners > 0 {
+                owners.select_nth_unstable_by(self.config.top_owners - 1, cmp);
+            }
+            owners.truncate(self.config.top_owners);
+        }
+        owners.sort_unstable_by(cmp);
+
+        SubscribeUpdateSlotStats {
+            slot,
+            account_updates: state.account_updates,
+            unique_accounts: state.accounts.len() as u64,
+            data_bytes: state.data_bytes,
+            top_owners: owners
+                .into_iter()
+                .map(
+                    |(owner, (account_updates, data_bytes))| SubscribeUpdateSlotStatsOwner {
+                        owner: owner.to_bytes().to_vec(),
+                        account_updates,
+                        data_bytes,
+                    },
+                )
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct SlotMessages {
+    messages: Vec<Option<(u64, Message)>>, // Option is used for accounts with low write_version
+    messages_slots: Vec<(u64, Message)>,
+    accounts_dedup: HashMap<Pubkey, (u64, usize)>, // (write_version, message_index)
+    confirmed_at: Option<usize>,
+    finalized_at: Option<usize>,
+    parent_slot: Option<Slot>,
+    confirmed: bool,
+    finalized: bool,
+    bytes: usize, // estimated encoded bytes of stored messages, slot statuses are not counted
+    assemble: bool, // messages are handed off to the block assembly
+    rolled_back: bool, // finalized but not an ancestor of a later finalized slot
+    encoded_cleared: bool, // shared encodings of transactions are dropped, see `geyser_loop`
+}
+
+/// Running max of bytes stored per slot, stored slots are scanned only once the largest
+/// slot is shrunk or evicted
+#[derive(Debug, Default)]
+struct SlotBytesMax {
+    value: usize,
+    stale: bool,
+}
+
+impl SlotBytesMax {
+    fn grown(&mut self, slot_bytes: usize) {
+        self.value = self.value.max(slot_bytes);
+    }
+
+    fn shrunk(&mut self, slot_bytes_before: usize) {
+        if slot_bytes_before >= self.value {
+            self.stale = true;
+        }
+    }
+
+    fn get(&mut self, messages: &BTreeMap<u64, SlotMessages>) -> usize {
+        if self.stale {
+            self.value = messages
+                .values()
+                .map(|slot_messages| slot_messages.bytes)
+                .max()
+                .unwrap_or_default();
+            self.stale = false;
+        }
+        self.value
+    }
+}
+
+/// Message handed off by `geyser_loop` to the block assembly
+#[derive(Debug)]
+struct BlockAssemblyMessage {
+    message: Message,
+    // slot statuses are handed off for every slot to remove outdated slots, other messages
+    // only if the block of the slot is assembled
+    assemble: bool,
+    received_at: Instant,
+    // set for confirmed and finalized statuses of assembled slots, notified once blocks
+    // completed by the previous messages are sent back to `geyser_loop`
+    synced: Option<oneshot::Sender<()>>,
+}
+
+/// Block or diagnostic of not reconstructed block returned to `geyser_loop`
+#[derive(Debug)]
+struct AssembledBlock {
+    slot: Slot,
+    finalized: bool, // finalized status of the slot was received by the block assembly
+    messages: Vec<Message>,
+    received_at: Instant, // hand-off of the message which completed the assembly
+}
+
+#[derive(Debug, Default)]
+struct SlotBlock {
+    block_meta: Option<Arc<MessageBlockMeta>>,
+    transactions: Vec<Arc<MessageTransactionInfo>>,
+    accounts: Vec<Option<Arc<MessageAccountInfo>>>, // Option is used for accounts with low write_version
+    accounts_dedup: HashMap<Pubkey, (u64, usize)>,  // (write_version, account_index)
+    entries: Vec<Arc<MessageEntry>>,
+    sealed: bool,
+    entries_count: usize,
+    confirmed: bool,
+    finalized: bool,
+}
+
+impl SlotBlock {
+    fn try_seal(&mut self) -> Option<Message> {
+        if !self.sealed {
+            // block meta of old interface versions without transactions count can be sent
+            // only as partial block
+            if let Some(block_meta) = self
+                .block_meta
+                .as_ref()
+                .filter(|block_meta| !block_meta.executed_transaction_count_unknown)
+            {
+                let executed_transaction_count = block_meta.executed_transaction_count as usize;
+                let entries_count = block_meta.entries_count as usize;
+
+                // Additional check `entries_count == 0` due to bug of zero entries on block produced by validator
+                // See GitHub issue: https://github.com/solana-labs/solana/issues/33823
+                if self.transactions.len() == executed_transaction_count
+                    && (entries_count == 0 || self.entries.len() == entries_count)
+                {
+                    return Some(self.seal(false));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Seal block with received transactions and entries, used once assembly timeout is reached
+    fn try_seal_partial(&mut self) -> Option<Message> {
+        if !self.sealed && self.block_meta.is_some() {
+            Some(self.seal(true))
+        } else {
+            None
+        }
+    }
+
+    fn seal(&mut self, is_partial: bool) -> Message {
+        let block_meta = Arc::clone(self.block_meta.as_ref().expect("checked block meta"));
+        let entries_count = block_meta.entries_count as usize;
+
+        let transactions = std::mem::take(&mut self.transactions);
+        let mut entries = std::mem::take(&mut self.entries);
+        if entries_count == 0 {
+            entries.clear();
+        }
+        let accounts = std::mem::take(&mut self.accounts)
+            .into_iter()
+            .flatten()
+            .collect();
+
+        let missing_transaction_count = block_meta
+            .executed_transaction_count
+            .saturating_sub(transactions.len() as u64);
+        let mut block = MessageBlock::new(block_meta, transactions, accounts, entries);
+        block.is_partial = is_partial;
+        block.missing_transaction_count = missing_transaction_count;
+
+        self.sealed = true;
+        self.entries_count = entries_count;
+        Message::Block(Arc::new(block))
+    }
+
+    /// Messages received after the seal are not included to the block
+    fn check_sealed(&self, kind: &str) {
+        if self.sealed {
+            metrics::update_invalid_blocks(format!("unexpected message {kind}"));
+        }
+    }
+
+    /// Expected and received counts of not reconstructed block, should be called before seal
+    fn get_block_diagnostic(&self, slot: Slot, is_partial: bool) -> SubscribeUpdateBlockDiagnostic {
+        let mut diagnostic = SubscribeUpdateBlockDiagnostic {
+            slot,
+            is_partial,
+            block_meta_missing: self.block_meta.is_none(),
+            received_transaction_count: self.transactions.len() as u64,
+            received_entries_count: self.entries.len() as u64,
+            ..Default::default()
+        };
+        if let Some(block_meta) = &self.block_meta {
+            diagnostic.executed_transaction_count = block_meta.executed_transaction_count;
+            diagnostic.entries_count = block_meta.entries_count;
+            let received = self
+                .transactions
+                .iter()
+                .map(|tx| tx.index as u64)
+                .collect::<HashSet<_>>();
+            diagnostic.missing_transaction_indexes = (0..diagnostic.executed_transaction_count)
+                .filter(|index| !received.contains(index))
+                .take(BLOCK_DIAGNOSTIC_MISSING_TRANSACTIONS_MAX)
+                .collect();
+        }
+        diagnostic
+    }
+
+    /// Reasons of failed block reconstruction, used for `invalid_full_blocks_total` metric
+    fn get_invalid_block_reasons(diagnostic: &SubscribeUpdateBlockDiagnostic) -> String {
+        let slot = diagnostic.slot;
+        let mut reasons = vec![];
+        if diagnostic.block_meta_missing {
+            reasons.push("NoBlockMeta");
+            error!(
+                "failed to reconstruct #{slot} -- no block meta, received txs: {}, entries: {}",
+                diagnostic.received_transaction_count, diagnostic.received_entries_count
+            );
+        } else {
+            if diagnostic.executed_transaction_count != diagnostic.received_transaction_count {
+                reasons.push("InvalidTxnCount");
+                error!(
+                    "failed to reconstruct #{slot} -- tx count: {} vs {}, missing indexes: {:?}",
+                    diagnostic.executed_transaction_count,
+                    diagnostic.received_transaction_count,
+                    diagnostic.missing_transaction_indexes
+                );
+            }
+            if diagnostic.entries_count != diagnostic.received_entries_count {
+                reasons.push("InvalidEntriesCount");
+                error!(
+                    "failed to reconstruct #{slot} -- entries count: {} vs {}",
+                    diagnostic.entries_count, diagnostic.received_entries_count
+                );
+            }
+        }
+        for reason in reasons.iter() {
+            metrics::invalid_blocks_mode_inc(reason);
+        }
+        reasons.join(",")
+    }
+}
+
+/// Full blocks assembled from messages handed off by `geyser_loop`, runs in own task so
+/// processed messages are broadcasted without waiting for the assembly
+#[derive(Debug)]
+struct BlockAssembly {
+    slots: BTreeMap<Slot, SlotBlock>,
+    // On startup we can receive multiple Confirmed/Finalized slots without BlockMeta message
+    // With saved first Processed slot we can ignore errors caused by startup process
+    processed_first_slot: Option<Slot>,
+    // deadlines to send partial blocks of confirmed slots
+    deadlines: BTreeSet<(Instant, Slot)>,
+    timeout: Option<Duration>,
+    diagnostics: bool,
+}
+
+impl BlockAssembly {
+    const fn new(timeout: Option<Duration>, diagnostics: bool) -> Self {
+        Self {
+            slots: BTreeMap::new(),
+            processed_first_slot: None,
+            deadlines: BTreeSet::new(),
+            timeout,
+            diagnostics,
+        }
+    }
+
+    fn update(&mut self, message: BlockAssemblyMessage) -> Vec<AssembledBlock> {
+        let BlockAssemblyMessage {
+            message,
+            assemble,
+            received_at,
+            ..
+        } = message;
+        let slot = message.get_slot();
+
+        let mut blocks = vec![];
+        if let Message::Slot(msg) = &message {
+            match msg.status {
+                SlotStatus::Processed if self.processed_first_slot.is_none() => {
+                    self.processed_first_slot = Some(slot);
+                }
+                SlotStatus::Finalized => blocks.extend(self.remove_outdated(slot, received_at)),
+                _ => {}
+            }
+        }
+        if !assemble {
+            return blocks;
+        }
+
+        let entry = self.slots.entry(slot).or_default();
+        let block = match &message {
+            Message::Slot(msg) => {
+                match msg.status {
+                    SlotStatus::Confirmed => {
+                        entry.confirmed = true;
+                        if !entry.sealed {
+                            if let Some(timeout) = self.timeout {
+                                self.deadlines.insert((received_at + timeout, slot));
                             }
                         }
-                        Message::Entry(msg) => {
-                            slot_messages.entries.push(Arc::clone(msg));
-                            sealed_block_msg = slot_messages.try_seal(&mut msgid_gen);
+                    }
+                    SlotStatus::Finalized => entry.finalized = true,
+                    _ => {}
+                }
+                None
+            }
+            Message::BlockMeta(msg) => {
+                entry.check_sealed("BlockMeta");
+                if entry.block_meta.is_some() {
+                    metrics::update_invalid_blocks("unexpected message: BlockMeta (duplicate)");
+                }
+                entry.block_meta = Some(Arc::clone(msg));
+                entry.try_seal()
+            }
+            Message::Transaction(msg) => {
+                entry.check_sealed("Transaction");
+                entry.transactions.push(Arc::clone(&msg.transaction));
+                entry.try_seal()
+            }
+            // Dedup accounts by max write_version
+            Message::Account(msg) => {
+                entry.check_sealed("Account");
+                let write_version = msg.account.write_version;
+                match entry.accounts_dedup.entry(msg.account.pubkey) {
+                    HashMapEntry::Occupied(mut dedup) => {
+                        if dedup.get().0 < write_version {
+                            // We can replace the account, but in this case we will lose the order
+                            entry.accounts[dedup.get().1] = None;
+                            *dedup.get_mut() = (write_version, entry.accounts.len());
+                            entry.accounts.push(Some(Arc::clone(&msg.account)));
                         }
-                        _ => {}
                     }
+                    HashMapEntry::Vacant(dedup) => {
+                        dedup.insert((write_version, entry.accounts.len()));
+                        entry.accounts.push(Some(Arc::clone(&msg.account)));
+                    }
+                }
+                None
+            }
+            Message::Entry(msg) => {
+                if entry.entries_count > 0 {
+                    entry.check_sealed("Entry");
+                }
+                entry.entries.push(Arc::clone(msg));
+                entry.try_seal()
+            }
+            _ => None,
+        };
+        if let Some(block) = block {
+            blocks.push(AssembledBlock {
+                slot,
+                finalized: entry.finalized,
+                messages: vec![block],
+                received_at,
+            });
+        }
+        blocks
+    }
+
+    /// Remove slots behind the finalized slot, keep extra `FINALIZED_SLOTS_KEPT` slots for late
+    /// messages
+    fn remove_outdated(&mut self, finalized: Slot, received_at: Instant) -> Vec<AssembledBlock> {
+        let mut blocks = vec![];
+        let Some(min_slot) = finalized.checked_sub(FINALIZED_SLOTS_KEPT) else {
+            return blocks;
+        };
+
+        let slots = self.slots.split_off(&min_slot);
+        for (slot, entry) in std::mem::replace(&mut self.slots, slots) {
+            match self.processed_first_slot {
+                Some(processed_first) if slot <= processed_first => continue,
+                None => continue,
+                _ => {}
+            }
+
+            if !entry.sealed && entry.finalized {
+                let diagnostic = entry.get_block_diagnostic(slot, false);
+                let reason = SlotBlock::get_invalid_block_reasons(&diagnostic);
+                metrics::update_invalid_blocks(format!("failed reconstruct {reason}"));
+                if self.diagnostics {
+                    let message = MessageBlockDiagnostic::new(diagnostic);
+                    blocks.push(AssembledBlock {
+                        slot,
+                        finalized: true,
+                        messages: vec![Message::BlockDiagnostic(Arc::new(message))],
+                        received_at,
+                    });
+                }
+            }
+        }
+        blocks
+    }
+
+    /// Partial blocks of confirmed slots with expired deadlines
+    fn seal_expired(&mut self, now: Instant) -> Vec<AssembledBlock> {
+        let mut blocks = vec![];
+        while let Some((deadline, slot)) = self.deadlines.first().copied() {
+            if deadline > now {
+                break;
+            }
+            self.deadlines.pop_first();
+
+            let Some(entry) = self.slots.get_mut(&slot) else {
+                continue;
+            };
+            let diagnostic = entry.get_block_diagnostic(slot, true);
+            let Some(message) = entry.try_seal_partial() else {
+                continue;
+            };
+            let reason = SlotBlock::get_invalid_block_reasons(&diagnostic);
+            metrics::update_invalid_blocks(format!("partial block {reason}"));
+            let mut messages = vec![message];
+            if self.diagnostics {
+                let message = MessageBlockDiagnostic::new(diagnostic);
+                messages.push(Message::BlockDiagnostic(Arc::new(message)));
+            }
+            blocks.push(AssembledBlock {
+                slot,
+                finalized: entry.finalized,
+                messages,
+                received_at: deadline,
+            });
+        }
+        blocks
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.deadlines.first().map(|(deadline, _slot)| *deadline)
+    }
+}
+
+/// Slots behind the finalized slot kept for late messages and statuses, by the block assembly
+/// and by `geyser_loop`
+const FINALIZED_SLOTS_KEPT: u64 = 10;
+
+/// Messages handed off to the block assembly, `geyser_loop` waits for the assembly once the
+/// channel is full
+const BLOCK_ASSEMBLY_CHANNEL_CAPACITY: usize = 100_000;
+
+/// Messages applied to the block meta storage with one write lock, unary reads wait for one
+/// batch at most
+const BLOCK_META_STORAGE_BATCH_MAX: usize = 1_024;
+
+/// Max number of missing transaction indexes in the block diagnostic
+const BLOCK_DIAGNOSTIC_MISSING_TRANSACTIONS_MAX: usize = 16;
+
+/// Pending filter updates per connection, new updates are rejected on overflow
+const FILTER_UPDATES_CHANNEL_CAPACITY: usize = 4;
+
+/// Queued slot updates and pings per connection, delivered ahead of queued updates of other slots
+const STREAM_PRIORITY_CHANNEL_CAPACITY: usize = 1_024;
+
+type BroadcastedMessage = (CommitmentLevel, Arc<Vec<(u64, Message)>>);
+
+/// Broadcast channel of one commitment with optional shards by account owner
+#[derive(Debug)]
+struct BroadcastChannel {
+    tx: broadcast::Sender<BroadcastedMessage>,
+    shards: Vec<broadcast::Sender<BroadcastedMessage>>,
+    // batches are partitioned into shards by own task, so `geyser_loop` is not delayed
+    shards_tx: Option<mpsc::UnboundedSender<BroadcastedMessage>>,
+}
+
+impl BroadcastChannel {
+    fn new(capacity: usize, shards: usize) -> Self {
+        let shards = (0..shards)
+            .map(|_| broadcast::channel(capacity).0)
+            .collect::<Vec<_>>();
+        let shards_tx = (!shards.is_empty()).then(|| {
+            let (tx, rx) = mpsc::unbounded_channel();
+            tokio::spawn(Self::shards_loop(rx, shards.clone()));
+            tx
+        });
+        Self {
+            tx: broadcast::channel(capacity).0,
+            shards,
+            shards_tx,
+        }
+    }
+
+    async fn shards_loop(
+        mut rx: mpsc::UnboundedReceiver<BroadcastedMessage>,
+        shards_tx: Vec<broadcast::Sender<BroadcastedMessage>>,
+    ) {
+        while let Some((commitment, messages)) = rx.recv().await {
+            let mut shards = vec![vec![]; shards_tx.len()];
+            for (msgid, message) in messages.iter() {
+                if let Message::Account(msg) = message {
+                    shards[Self::get_owner_shard(&msg.account.owner, shards_tx.len())]
+                        .push((*msgid, message.clone()));
+                } else {
+                    for shard in shards.iter_mut() {
+                        shard.push((*msgid, message.clone()));
+                    }
+                }
+            }
+            for (tx, messages) in shards_tx.iter().zip(shards) {
+                if !messages.is_empty() {
+                    let _ = tx.send((commitment, messages.into()));
+                }
+            }
+        }
+    }
+
+    fn get_owner_shard(owner: &Pubkey, shards: usize) -> usize {
+        let mut hasher = DefaultHasher::new();
+        owner.hash(&mut hasher);
+        (hasher.finish() % shards as u64) as usize
+    }
+}
+
+/// Broadcast of messages to clients, one channel per commitment with optional shards by
+/// account owner.
+///
+/// Client receives batches only from the channel of the filter commitment, so clients of
+/// one commitment never wake up for batches of other commitments. Every shard receives all
+/// non-account messages and account messages of owners mapped to the shard, in the same
+/// order and the same batches as the full stream. Client with owner-scoped accounts filters
+/// mapped to one shard receives only messages of that shard, filters with owners of several
+/// shards are not rejected and receive the full stream.
+#[derive(Debug, Clone)]
+struct Broadcast {
+    channels: Arc<[BroadcastChannel; 3]>, // indexed by commitment
+    enabled: [bool; 3],                   // indexed by commitment
+    shards: usize,
+    batch_max_bytes: usize, // zero disables splitting
+    blocks_subscribers: Arc<AtomicUsize>,
+}
+
+impl Broadcast {
+    /// Capacities of processed, confirmed and finalized channels
+    fn new(capacities: [usize; 3], shards: usize, batch_max_bytes: usize) -> Self {
+        Self {
+            channels: Arc::new(capacities.map(|capacity| BroadcastChannel::new(capacity, shards))),
+            enabled: [true; 3],
+            shards,
+            batch_max_bytes,
+            blocks_subscribers: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Blocks are assembled only while there are subscriptions with blocks filters
+    fn has_blocks_subscribers(&self) -> bool {
+        self.blocks_subscribers.load(Ordering::Relaxed) > 0
+    }
+
+    fn blocks_subscriber(&self) -> BlocksSubscriber {
+        BlocksSubscriber {
+            subscribers: Arc::clone(&self.blocks_subscribers),
+            active: false,
+        }
+    }
+
+    /// Batches of the commitment are not sent, no receiver can subscribe to it
+    fn disable(&mut self, commitment: CommitmentLevel) {
+        self.enabled[commitment as usize] = false;
+    }
+
+    fn is_enabled(&self, commitment: CommitmentLevel) -> bool {
+        self.enabled[commitment as usize]
+    }
+
+    fn get_channel(&self, commitment: CommitmentLevel) -> &BroadcastChannel {
+        &self.channels[commitment as usize]
+    }
+
+    /// Split batch by estimated encoded bytes preserving the order, so small messages are not
+    /// blocked behind large ones. Message larger than the limit is sent in own batch.
+    fn split(&self, messages: Vec<(u64, Message)>) -> Vec<(Vec<(u64, Message)>, usize)> {
+        let mut batches = vec![];
+        let mut batch = vec![];
+        let mut batch_bytes = 0;
+        for (msgid, message) in messages {
+            let bytes = message.get_size_estimate();
+            if self.batch_max_bytes > 0
+                && !batch.is_empty()
+                && batch_bytes + bytes > self.batch_max_bytes
+            {
+                batches.push((std::mem::take(&mut batch), batch_bytes));
+                batch_bytes = 0;
+            }
+            batch.push((msgid, message));
+            batch_bytes += bytes;
+        }
+        if !batch.is_empty() || batches.is_empty() {
+            batches.push((batch, batch_bytes));
+        }
+        batches
+    }
+
+    fn get_owner_shard(&self, owner: &Pubkey) -> usize {
+        BroadcastChannel::get_owner_shard(owner, self.shards)
+    }
+
+    /// Returns shard with all messages required by the filter, `None` for the full stream
+    fn get_shard(&self, filter: &Filter) -> Option<usize> {
+        if self.shards == 0 {
+            return None;
+        }
+
+        let owners = filter.get_accounts_owners()?;
+        let mut shards = owners.iter().map(|owner| self.get_owner_shard(owner));
+        let shard = shards.next().unwrap_or(0);
+        shards.all(|value| value == shard).then_some(shard)
+    }
+
+    fn subscribe(
+        &self,
+        commitment: CommitmentLevel,
+        shard: Option<usize>,
+    ) -> broadcast::Receiver<BroadcastedMessage> {
+        let channel = self.get_channel(commitment);
+        match shard {
+            Some(shard) => channel.shards[shard].subscribe(),
+            None => channel.tx.subscribe(),
+        }
+    }
+
+    fn send(&self, commitment: CommitmentLevel, messages: Vec<(u64, Message)>) {
+        if !self.is_enabled(commitment) {
+            return;
+        }
+        for (messages, bytes) in self.split(messages) {
+            let _span = trace::batch_span(commitment, messages.len(), bytes).entered();
+            metrics::broadcast_batch_bytes_observe(bytes);
+            self.send_batch(commitment, messages);
+        }
+    }
+
+    fn send_batch(&self, commitment: CommitmentLevel, messages: Vec<(u64, Message)>) {
+        let channel = self.get_channel(commitment);
+        let messages = Arc::new(messages);
+        if let Some(shards_tx) = &channel.shards_tx {
+            let _ = shards_tx.send((commitment, Arc::clone(&messages)));
+        }
+        let _ = channel.tx.send((commitment, messages));
+    }
+}
+
+/// Subscription counted by [`Broadcast::has_blocks_subscribers`] while its filter has blocks
+/// filters, removed on drop
+#[derive(Debug)]
+struct BlocksSubscriber {
+    subscribers: Arc<AtomicUsize>,
+    active: bool,
+}
+
+impl Drop for BlocksSubscriber {
+    fn drop(&mut self) {
+        if self.active {
+            self.subscribers.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl BlocksSubscriber {
+    fn update(&mut self, filter: &Filter) {
+        let active = filter.has_blocks();
+        if active != self.active {
+            if active {
+                self.subscribers.fetch_add(1, Ordering::Relaxed);
+            } else {
+                self.subscribers.fetch_sub(1, Ordering::Relaxed);
+            }
+            self.active = active;
+        }
+    }
+}
+
+/// Latest sent write versions of accounts, used by clients with `coalesce_accounts_per_slot`
+#[derive(Debug, Default)]
+struct AccountsCoalesce {
+    slots: BTreeMap<Slot, HashMap<Pubkey, u64>>,
+}
+
+impl AccountsCoalesce {
+    const KEEP_SLOTS: u64 = 2;
+
+    /// Returns indexes of account messages with newer write in the same batch or already sent
+    fn get_superseded(&mut self, messages: &[(u64, Message)]) -> HashSet<usize> {
+        let mut latest = HashMap::<(Slot, Pubkey), u64>::new();
+        let mut superseded = HashSet::new();
+        for (index, (_msgid, message)) in messages.iter().enumerate().rev() {
+            if let Message::Account(msg) = message {
+                let key = (msg.slot, msg.account.pubkey);
+                let write_version = msg.account.write_version;
+                let sent = self
+                    .slots
+                    .get(&msg.slot)
+                    .and_then(|accounts| accounts.get(&msg.account.pubkey))
+                    .copied();
+                match latest.get(&key).copied().max(sent) {
+                    Some(latest_write_version) if latest_write_version > write_version => {
+                        superseded.insert(index);
+                    }
+                    _ => {
+                        latest.insert(key, write_version);
+                    }
+                }
+            }
+        }
+
+        for ((slot, pubkey), write_version) in latest {
+            let entry = self
+                .slots
+                .entry(slot)
+                .or_default()
+                .entry(pubkey)
+                .or_default();
+            *entry = write_version.max(*entry);
+        }
+        if let Some(keep_slot) = self
+            .slots
+            .keys()
+            .next_back()
+            .and_then(|slot| slot.checked_sub(Self::KEEP_SLOTS))
+        {
+            self.slots = self.slots.split_off(&keep_slot);
+        }
+
+        superseded
+    }
+}
+
+/// Transactions from `seen_signatures_bloom` of the latest filter with the bloom are suppressed
+/// until `slots` slots are passed since the first transaction, then the bloom is dropped
+#[derive(Debug, Default)]
+struct SeenSignatures {
+    filter: Option<FilterSeenSignatures>,
+    until_slot: Option<Slot>,
+}
+
+impl SeenSignatures {
+    fn update(&mut self, filter: &Filter) {
+        if let Some(seen) = filter.get_seen_signatures() {
+            self.filter = Some(seen.clone());
+            self.until_slot = None;
+        }
+    }
+
+    fn is_seen(&mut self, message: &MessageTransaction) -> bool {
+        let Some(filter) = &self.filter else {
+            return false;
+        };
+        let until_slot = *self
+            .until_slot
+            .get_or_insert(message.slot.saturating_add(filter.slots()));
+        if message.slot >= until_slot {
+            self.filter = None;
+            return false;
+        }
+        filter.contains(&message.transaction.signature)
+    }
+}
+
+/// Latest slots of account updates matched by `txn_signature` of accounts filters, matched
+/// signatures are removed from the filter once the slot is finalized
+#[derive(Debug, Default)]
+struct AccountsTxnSignatures {
+    slots: HashMap<Signature, Slot>,
+}
+
+impl AccountsTxnSignatures {
+    fn update(&mut self, filter: &mut Filter, message: &Message) {
+        match message {
+            Message::Account(msg) => {
+                if let Some(signature) = &msg.account.txn_signature {
+                    if filter.is_accounts_txn_signature(signature) {
+                        let slot = self.slots.entry(*signature).or_default();
+                        *slot = msg.slot.max(*slot);
+                    }
+                }
+            }
+            Message::Slot(msg) if msg.status == SlotStatus::Finalized => {
+                self.slots.retain(|signature, slot| {
+                    let expired = *slot <= msg.slot;
+                    if expired {
+                        filter.remove_accounts_txn_signature(signature);
+                    }
+                    !expired
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// `signatures` of transactions filters, a signature is removed from the filter once a
+/// transaction is sent for a filter with the signature, all signatures are removed after `signatures_expire_slots` slots since
+/// the first slot after the filter is accepted
+#[derive(Debug, Default)]
+struct TransactionsSignatures {
+    until_slot: Option<Slot>,
+}
+
+impl TransactionsSignatures {
+    fn update_transaction(
+        &mut self,
+        filter: &mut Filter,
+        message: &MessageTransaction,
+        updates: &FilteredUpdates,
+    ) {
+        let signature = &message.transaction.signature;
+        if updates
+            .iter()
+            .any(|update| filter.is_transactions_signature(signature, &update.filters))
+        {
+            filter.remove_transactions_signature(signature);
+        }
+    }
+
+    fn update_slot(&mut self, filter: &mut Filter, slot: Slot) {
+        let Some(slots) = filter.get_transactions_signatures_expire_slots() else {
+            return;
+        };
+        let until_slot = *self.until_slot.get_or_insert(slot.saturating_add(slots));
+        if slot >= until_slot {
+            filter.clear_transactions_signatures();
+        }
+    }
+}
+
+/// Transaction updates of filters with `ordered_per_slot`, buffered until block meta of the slot
+#[derive(Debug)]
+struct TransactionsOrdered {
+    timeout: Duration,
+    slots: BTreeMap<Slot, (Instant, Vec<FilteredUpdate>)>,
+    done: BTreeSet<Slot>,
+}
+
+impl TransactionsOrdered {
+    const KEEP_DONE_SLOTS: usize = 64;
+
+    const fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            slots: BTreeMap::new(),
+            done: BTreeSet::new(),
+        }
+    }
+
+    /// Buffer updates for ordered filters, returns updates which should be sent immediately
+    fn push(&mut self, filter: &Filter, slot: Slot, updates: FilteredUpdates) -> FilteredUpdates {
+        // late transactions of already flushed slot are sent as is
+        if !filter.is_transactions_ordered() || self.done.contains(&slot) {
+            return updates;
+        }
+
+        let mut unordered = FilteredUpdates::new();
+        for mut update in updates {
+            let (filters, ordered) = filter.split_transactions_ordered(&update.filters);
+            if ordered.is_empty() {
+                unordered.push(update);
+                continue;
+            }
+            if !filters.is_empty() {
+                let mut update = update.clone();
+                update.filters = filters;
+                unordered.push(update);
+            }
+            update.filters = ordered;
+            self.slots
+                .entry(slot)
+                .or_insert_with(|| (Instant::now(), vec![]))
+                .1
+                .push(update);
+        }
+        unordered
+    }
+
+    /// Buffered updates of the slot sorted by transaction index and `TransactionSlotDone` marker
+    fn flush_slot(
+        &mut self,
+        filter: &Filter,
+        slot: Slot,
+        best_effort: bool,
+    ) -> Vec<FilteredUpdate> {
+        let mut updates = self
+            .slots
+            .remove(&slot)
+            .map(|(_created_at, updates)| updates)
+            .unwrap_or_default();
+        if updates.is_empty() && !filter.is_transactions_ordered() {
+            return updates;
+        }
+
+        self.done.insert(slot);
+        while self.done.len() > Self::KEEP_DONE_SLOTS {
+            self.done.pop_first();
+        }
+
+        let mut filters = filter.get_transactions_ordered();
+        for update in updates.iter() {
+            for name in update.filters.iter() {
+                if !filters.contains(name) {
+                    filters.push(name.clone());
+                }
+            }
+        }
+        updates.sort_by_key(|update| match &update.message {
+            FilteredUpdateOneof::Transaction(msg) => msg.transaction.index,
+            FilteredUpdateOneof::TransactionStatus(msg) => msg.transaction.index,
+            _ => usize::MAX,
+        });
+
+        let mut done = FilteredUpdate::new_empty(FilteredUpdateOneof::transaction_slot_done(
+            SubscribeUpdateTransactionSlotDone {
+                slot,
+                sent_count: updates.len() as u64,
+                best_effort,
+            },
+        ));
+        done.filters = filters;
+        updates.push(done);
+        updates
+    }
+
+    /// Flush slots without block meta after timeout
+    fn flush_expired(&mut self, filter: &Filter) -> Vec<(Slot, Vec<FilteredUpdate>)> {
+        let expired = self
+            .slots
+            .iter()
+            .filter(|(_slot, (created_at, _updates))| created_at.elapsed() >= self.timeout)
+            .map(|(slot, _)| *slot)
+            .collect::<Vec<_>>();
+        expired
+            .into_iter()
+            .map(|slot| (slot, self.flush_slot(filter, slot, true)))
+            .collect()
+    }
+
+    /// Time when the oldest buffered slot expires
+    fn next_deadline(&self) -> Option<Instant> {
+        self.slots
+            .values()
+            .map(|(created_at, _updates)| *created_at + self.timeout)
+            .min()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ProcessedBatchConfig {
+    max_size: usize,
+    max_delay: Duration, // zero means send every message immediately
+    flush_on_slot: bool,
+}
+
+impl ProcessedBatchConfig {
+    const fn new(config: &ConfigGrpc) -> Self {
+        Self {
+            max_size: config.processed_batch_max_size,
+            max_delay: Duration::from_millis(config.processed_batch_max_delay_ms),
+            flush_on_slot: config.processed_batch_flush_on_slot,
+        }
+    }
+}
+
+/// Fixed window limit of filter updates per connection
+#[derive(Debug)]
+struct FilterUpdatesRateLimit {
+    max: u32,
+    window_start: Instant,
+    count: u32,
+}
+
+impl FilterUpdatesRateLimit {
+    const WINDOW: Duration = Duration::from_secs(1);
+
+    fn new(max: u32) -> Self {
+        Self {
+            max,
+            window_start: Instant::now(),
+            count: 0,
+        }
+    }
+
+    fn try_acquire(&mut self) -> bool {
+        if self.max == 0 {
+            return true;
+        }
+
+        let now = Instant::now();
+        if now.duration_since(self.window_start) >= Self::WINDOW {
+            self.window_start = now;
+            self.count = 0;
+        }
+        if self.count < self.max {
+            self.count += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Signatures in the request re-arm expired signature matches, so such a request is applied
+/// even if it is identical to the applied one
+fn request_has_signatures(request: &SubscribeRequest) -> bool {
+    request.seen_signatures_bloom.is_some()
+        || request
+            .accounts
+            .values()
+            .any(|filter| !filter.txn_signature.is_empty())
+        || request
+            .transactions
+            .values()
+            .chain(request.transactions_status.values())
+            .any(|filter| filter.signature.is_some() || !filter.signatures.is_empty())
+}
+
+type ClientFilterUpdate = Option<(Option<u64>, Filter)>;
+
+enum ReplayedResponse {
+    /// Stored messages and receiver subscribed right after them, so live updates continue
+    /// without gaps or duplicates
+    Messages(Vec<(u64, Message)>, broadcast::Receiver<BroadcastedMessage>),
+    /// Oldest available slot
+    Lagged(Slot),
+}
+
+type ReplayStoredSlotsRequest = (
+    CommitmentLevel,
+    Option<usize>,
+    Slot,
+    oneshot::Sender<ReplayedResponse>,
+);
+
+/// Startup account or `None` for the end of startup
+type StartupMessage = Option<Arc<Message>>;
+
+/// Accounts from the snapshot channel of the plugin, broadcasted to every client with
+/// `startup_accounts` (or `x-request-snapshot` header) subscribed at the time
+#[derive(Debug, Clone)]
+struct StartupBroadcast {
+    tx: broadcast::Sender<StartupMessage>,
+    ended: Arc<AtomicBool>,
+    /// Wakes the forwarding thread on a new subscriber or a received message
+    wake_tx: crossbeam_channel::Sender<()>,
+}
+
+impl StartupBroadcast {
+    /// Messages are forwarded only while there is a subscriber and the slowest subscriber has
+    /// less than `capacity` messages queued, so the validator startup is paced by the slowest
+    /// client and nothing is lost
+    fn new(
+        snapshot_rx: crossbeam_channel::Receiver<Box<Message>>,
+        capacity: usize,
+    ) -> anyhow::Result<Self> {
+        let (tx, _rx) = broadcast::channel(capacity);
+        let ended = Arc::new(AtomicBool::new(false));
+        let (wake_tx, wake_rx) = crossbeam_channel::bounded(1);
+
+        let startup = Self {
+            tx: tx.clone(),
+            ended: Arc::clone(&ended),
+            wake_tx,
+        };
+        std::thread::Builder::new()
+            .name("solGeyserStartup".to_owned())
+            .spawn(move || {
+                while let Ok(message) = snapshot_rx.recv() {
+                    let mut message = Some(Arc::from(message));
+                    loop {
+                        while tx.receiver_count() == 0 || tx.len() >= capacity {
+                            if wake_rx.recv().is_err() {
+                                // the service and all subscribers are gone
+                                return;
+                            }
+                        }
+                        match tx.send(message) {
+                            Ok(_receivers) => break,
+                            // last subscriber is gone right before the send
+                            Err(broadcast::error::SendError(value)) => message = value,
+                        }
+                    }
+                    metrics::startup_queue_size_set(snapshot_rx.len() + tx.len());
+                }
+                // clients subscribed after this point receive the end of startup right away
+                ended.store(true, Ordering::SeqCst);
+                let _ = tx.send(None);
+                metrics::startup_queue_size_set(0);
+            })
+            .context("failed to spawn startup broadcast thread")?;
+
+        Ok(startup)
+    }
+
+    fn subscribe(&self) -> StartupReceiver {
+        let rx = self.tx.subscribe();
+        let _ = self.wake_tx.try_send(());
+        StartupReceiver {
+            rx: (!self.ended.load(Ordering::SeqCst)).then_some(rx),
+            wake_tx: self.wake_tx.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct StartupReceiver {
+    rx: Option<broadcast::Receiver<StartupMessage>>,
+    wake_tx: crossbeam_channel::Sender<()>,
+}
+
+impl Drop for StartupReceiver {
+    fn drop(&mut self) {
+        // the slowest subscriber may be gone
+        self.rx = None;
+        let _ = self.wake_tx.try_send(());
+    }
+}
+
+impl StartupReceiver {
+    /// `Ok(None)` once the startup is finished, `Err` with the number of skipped messages if
+    /// the receiver lagged
+    async fn recv(&mut self) -> Result<Option<Arc<Message>>, u64> {
+        let Some(rx) = self.rx.as_mut() else {
+            return Ok(None);
+        };
+        let result = match rx.recv().await {
+            Ok(Some(message)) => Ok(Some(message)),
+            Ok(None) | Err(broadcast::error::RecvError::Closed) => {
+                self.rx = None;
+                Ok(None)
+            }
+            Err(broadcast::error::RecvError::Lagged(count)) => Err(count),
+        };
+        // the queue of the slowest subscriber may be shorter now
+        let _ = self.wake_tx.try_send(());
+        result
+    }
+}
+
+/// Local addresses of the started services, so a port `0` in the config can be resolved
+#[derive(Debug, Clone, Copy)]
+pub struct BoundAddresses {
+    pub grpc: SocketAddr,
+    pub admin: Option<SocketAddr>,
+}
+
+#[derive(Debug)]
+pub struct GrpcService {
+    config_snapshot_client_channel_capacity: usize,
+    config_channel_capacity: usize,
+    config_filter_limits: Arc<FilterLimits>,
+    config_filter_updates_rate_limit: u32,
+    config_ping_interval: Duration,
+    config_transactions_ordered_timeout: Duration,
+    config_quota: Option<ConfigGrpcQuota>,
+    config_filter_presets: Arc<FilterPresets>,
+    blocks_meta: Option<BlockMetaStorage>,
+    commitment_slots: Arc<CommitmentSlots>,
+    tracked_accounts: Option<Arc<TrackedAccounts>>,
+    recent_transactions: Option<RecentTransactions>,
+    subscribe_id: AtomicUsize,
+    startup: Option<StartupBroadcast>,
+    broadcast: Broadcast,
+    replay_stored_slots_tx: Option<mpsc::Sender<ReplayStoredSlotsRequest>>,
+    clients: Option<Arc<ClientRegistry>>,
+    maintenance_rx: MaintenanceRx,
+    filter_names: Arc<Mutex<FilterNames>>,
+}
+
+impl GrpcService {
+    #[allow(clippy::type_complexity)]
+    pub async fn create(
+        config_tokio: ConfigTokio,
+        config: ConfigGrpc,
+        debug_clients: Option<Arc<ClientRegistry>>,
+        is_reload: bool,
+    ) -> anyhow::Result<(
+        Option<crossbeam_channel::Sender<Box<Message>>>,
+        MessagesSender,
+        Arc<Notify>,
+        BoundAddresses,
+    )> {
+        // Bind service address
+        let listener = Self::bind_listener(&config)?;
+        let mut addresses = BoundAddresses {
+            grpc: listener
+                .local_addr()
+                .context("failed to get local address of gRPC listener")?,
+            admin: None,
+        };
+        let incoming = TcpIncoming::from_listener(
+            listener,
+            config.server_tcp_nodelay,
+            Some(Duration::from_secs(20)), // tcp_keepalive
+        )
+        .map_err(|error| anyhow::anyhow!(error))?;
+
+        // Admin service address, bound before the start to fail early
+        let admin = match config.admin.clone() {
+            Some(admin) => {
+                let listener = AdminService::bind(&admin)
+                    .await
+                    .with_context(|| format!("failed to bind admin address {}", admin.address))?;
+                addresses.admin = Some(
+                    listener
+                        .local_addr()
+                        .context("failed to get local address of admin listener")?,
+                );
+                Some((admin, listener))
+            }
+            None => None,
+        };
+        // the same registry is used by `/debug_clients`
+        let clients = debug_clients.or_else(|| {
+            admin
+                .is_some()
+                .then(|| Arc::new(ClientRegistry::new(false)))
+        });
+        // announced only with the admin service
+        let (maintenance_tx, maintenance_rx) = watch::channel(None);
+
+        // Snapshot channel
+        let (snapshot_tx, startup) = match config.snapshot_plugin_channel_capacity {
+            Some(cap) if !is_reload => {
+                let (tx, rx) = crossbeam_channel::bounded(cap);
+                (Some(tx), Some(StartupBroadcast::new(rx, cap)?))
+            }
+            _ => (None, None),
+        };
+
+        // Blocks meta storage
+        let (blocks_meta, blocks_meta_tx) = if config.unary_disabled {
+            (None, None)
+        } else {
+            let (blocks_meta, blocks_meta_tx) = BlockMetaStorage::new(
+                config.unary_concurrency_limit,
+                config.blockhash_warmup_block_metas,
+            );
+            (Some(blocks_meta), Some(blocks_meta_tx))
+        };
+
+        // Highest slots by commitment
+        let commitment_slots = Arc::new(CommitmentSlots::default());
+
+        // Pipeline liveness, blockhashes are required only by unary methods
+        let health = Arc::new(PipelineHealth::new(
+            config.health_stale_timeout,
+            if config.unary_disabled {
+                0
+            } else {
+                config.blockhash_warmup_block_metas
+            },
+        ));
+
+        // Latest values of tracked accounts
+        let tracked_accounts = match config.tracked_accounts.clone() {
+            Some(tracked_accounts) if !config.unary_disabled => Some(Arc::new(
+                TrackedAccounts::new(tracked_accounts, config.unary_concurrency_limit),
+            )),
+            _ => None,
+        };
+
+        // Messages to clients combined by commitment
+        let processed_batch = ProcessedBatchConfig::new(&config);
+        let mut broadcast = Broadcast::new(
+            [
+                CommitmentLevel::Processed,
+                CommitmentLevel::Confirmed,
+                CommitmentLevel::Finalized,
+            ]
+            .map(|commitment| config.get_channel_capacity(commitment)),
+            config.broadcast_shards,
+            config.broadcast_batch_max_bytes,
+        );
+        // clients can't subscribe to commitments not in `allowed_commitments`, but processed
+        // batches are still received by the index of recent transactions
+        for commitment in [
+            CommitmentLevel::Processed,
+            CommitmentLevel::Confirmed,
+            CommitmentLevel::Finalized,
+        ] {
+            let recent_transactions = commitment == CommitmentLevel::Processed
+                && config.recent_transactions.is_some()
+                && !config.unary_disabled;
+            if !config.filter_limits.is_commitment_allowed(commitment) && !recent_transactions {
+                broadcast.disable(commitment);
+            }
+        }
+
+        // Index of recent transactions, updated from the broadcast
+        let recent_transactions = match config.recent_transactions {
+            Some(recent_transactions) if !config.unary_disabled => Some(RecentTransactions::new(
+                recent_transactions,
+                config.unary_concurrency_limit,
+                broadcast.subscribe(CommitmentLevel::Processed, None),
+                Arc::clone(&commitment_slots),
+            )),
+            _ => None,
+        };
+        // attempt to prevent spam of geyser loop with capacity eq 1
+        let (replay_stored_slots_tx, replay_stored_slots_rx) = if config.replay_stored_slots == 0 {
+            (None, None)
+        } else {
+            let (tx, rx) = mpsc::channel(1);
+            (Some(tx), Some(rx))
+        };
+
+        // gRPC server builder with optional TLS
+        let server_builder = Self::create_server_builder(&config).await?;
+
+        let filter_names = Arc::new(Mutex::new(FilterNames::new(
+            config.filter_name_size_limit,
+            config.filter_names_size_limit,
+            config.filter_names_cleanup_interval,
+        )));
+
+        // Create Server
+        let max_decoding_message_size = config.max_decoding_message_size;
+        let mut service = GeyserServer::new(Self {
+            config_snapshot_client_channel_capacity: config.snapshot_client_channel_capacity,
+            config_channel_capacity: config.channel_capacity,
+            config_filter_limits: Arc::new(config.filter_limits),
+            config_filter_updates_rate_limit: config.filter_updates_rate_limit,
+            config_ping_interval: config.ping_interval,
+            config_transactions_ordered_timeout: config.transactions_ordered_timeout,
+            config_quota: config.quota.clone(),
+            config_filter_presets: Arc::new(FilterPresets::new(config.filter_presets.clone())),
+            blocks_meta,
+            commitment_slots: Arc::clone(&commitment_slots),
+            tracked_accounts: tracked_accounts.clone(),
+            recent_transactions,
+            subscribe_id: AtomicUsize::new(0),
+            startup,
+            broadcast: broadcast.clone(),
+            replay_stored_slots_tx,
+            clients: clients.clone(),
+            maintenance_rx,
+            filter_names,
+        })
+        .max_decoding_message_size(max_decoding_message_size);
+        for encoding in config.compression.accept {
+            service = service.accept_compressed(encoding);
+        }
+        for encoding in config.compression.send {
+            service = service.send_compressed(encoding);
+        }
+
+        // Run geyser message loop
+        let (messages_tx, messages_rx) = mpsc::unbounded_channel();
+        let health_loop = Arc::clone(&health);
+        spawn_blocking(move || {
+            crate::create_runtime(&config_tokio, "geyser_loop")
+                .expect("Failed to create a new runtime for geyser loop")
+                .block_on(Self::geyser_loop(
+                    messages_rx,
+                    blocks_meta_tx,
+                    commitment_slots,
+                    Some(health_loop),
+                    tracked_accounts,
+                    broadcast,
+                    replay_stored_slots_rx,
+                    config.replay_stored_slots,
+                    config.replay_stored_slots_max_bytes,
+                    processed_batch,
+                    config.block_assembly_timeout,
+                    config.block_diagnostics,
+                    config.votes.map(VotesAggregator::new),
+                    config.slot_stats.map(SlotStatsAggregator::new),
+                ));
+        });
+
+        // gRPC reflection service
+        let reflection_services = if config.enable_reflection {
+            let builder = || {
+                ReflectionBuilder::configure()
+                    .register_encoded_file_descriptor_set(GEYSER_FILE_DESCRIPTOR_SET)
+                    .register_encoded_file_descriptor_set(HEALTH_FILE_DESCRIPTOR_SET)
+            };
+            let v1 = builder()
+                .build_v1()
+                .context("failed to build reflection service")?;
+            let v1alpha = builder()
+                .build_v1alpha()
+                .context("failed to build reflection service")?;
+            (Some(v1), Some(v1alpha))
+        } else {
+            (None, None)
+        };
+
+        // Run admin service, stopped with the main server
+        let admin_shutdown = Arc::new(Notify::new());
+        if let (Some((admin, listener)), Some(clients)) = (admin, clients) {
+            AdminService::spawn(
+                admin,
+                listener,
+                clients,
+                maintenance_tx,
+                Arc::clone(&health),
+                Arc::clone(&admin_shutdown),
+            )?;
+        }
+
+        // Run Server
+        let shutdown = Arc::new(Notify::new());
+        let shutdown_grpc = Arc::clone(&shutdown);
+        tokio::spawn(async move {
+            // gRPC Health check service
+            let (health_reporter, health_service) = health_reporter();
+            let health_task =
+                tokio::spawn(
+                    async move { health.report::<GeyserServer<Self>>(health_reporter).await },
+                );
+
+            let result = server_builder
+                .layer(interceptor(move |request: Request<()>| {
+                    if let Some(x_token) = &config.x_token {
+                        match request.metadata().get("x-token") {
+                            Some(token)
+                                if metrics::constant_time_eq(
+                                    token.as_bytes(),
+                                    x_token.as_bytes(),
+                                ) =>
+                            {
+                                Ok(request)
+                            }
+                            _ => Err(Status::unauthenticated("No valid auth token")),
+                        }
+                    } else {
+                        Ok(request)
+                    }
+                }))
+                .add_service(health_service)
+                .add_optional_service(reflection_services.0)
+                .add_optional_service(reflection_services.1)
+                .add_service(service)
+                .serve_with_incoming_shutdown(incoming, shutdown_grpc.notified())
+                .await;
+            admin_shutdown.notify_one();
+            health_task.abort();
+            metrics::set_grpc_serving(false);
+            result
+        });
+
+        Ok((
+            snapshot_tx,
+            MessagesSender::new(messages_tx),
+            shutdown,
+            addresses,
+        ))
+    }
+
+    /// gRPC server builder with optional TLS and HTTP/2 options from the config
+    pub async fn create_server_builder(config: &ConfigGrpc) -> anyhow::Result<Server> {
+        let mut server_builder = Server::builder();
+        if let Some(tls_config) = &config.tls_config {
+            let (cert, key) = tokio::try_join!(
+                fs::read(&tls_config.cert_path),
+                fs::read(&tls_config.key_path)
+            )
+            .context("failed to load tls_config files")?;
+            server_builder = server_builder
+                .tls_config(ServerTlsConfig::new().identity(Identity::from_pem(cert, key)))
+                .context("failed to apply tls_config")?;
+        }
+        if let Some(enabled) = config.server_http2_adaptive_window {
+            server_builder = server_builder.http2_adaptive_window(Some(enabled));
+        }
+        if let Some(http2_keepalive_interval) = config.server_http2_keepalive_interval {
+            server_builder =
+                server_builder.http2_keepalive_interval(Some(http2_keepalive_interval));
+        }
+        if let Some(http2_keepalive_timeout) = config.server_http2_keepalive_timeout {
+            server_builder = server_builder.http2_keepalive_timeout(Some(http2_keepalive_timeout));
+        }
+        if let Some(sz) = config.server_initial_connection_window_size {
+            server_builder = server_builder.initial_connection_window_size(sz);
+        }
+        if let Some(sz) = config.server_initial_stream_window_size {
+            server_builder = server_builder.initial_stream_window_size(sz);
+        }
+        if let Some(max) = config.server_max_concurrent_streams {
+            server_builder = server_builder.max_concurrent_streams(max);
+        }
+        if let Some(sz) = config.server_max_frame_size {
+            server_builder = server_builder.max_frame_size(sz);
+        }
+        Ok(server_builder)
+    }
+
+    /// Bind gRPC address with socket options from the config
+    pub fn bind_listener(config: &ConfigGrpc) -> anyhow::Result<TcpListener> {
+        let address = config.address;
+        let socket = Socket::new(
+            Domain::for_address(address),
+            Type::STREAM,
+            Some(Protocol::TCP),
+        )
+        .with_context(|| format!("failed to create socket for {address}"))?;
+        if config.bind_ipv6_dual_stack {
+            anyhow::ensure!(
+                address.is_ipv6(),
+                "bind_ipv6_dual_stack requires IPv6 address, got {address}"
+            );
+            socket
+                .set_only_v6(false)
+                .context("bind_ipv6_dual_stack: failed to set IPV6_V6ONLY=false")?;
+        }
+        // same as in `std::net::TcpListener::bind`
+        #[cfg(unix)]
+        socket
+            .set_reuse_address(true)
+            .context("failed to set SO_REUSEADDR")?;
+        if config.so_reuseport {
+            #[cfg(unix)]
+            socket
+                .set_reuse_port(true)
+                .context("so_reuseport: failed to set SO_REUSEPORT")?;
+            #[cfg(not(unix))]
+            anyhow::bail!("so_reuseport: SO_REUSEPORT is not supported on this platform");
+        }
+        socket
+            .bind(&address.into())
+            .with_context(|| format!("failed to bind {address}"))?;
+        socket
+            .listen(config.tcp_backlog.try_into().unwrap_or(i32::MAX))
+            .with_context(|| {
+                format!(
+                    "tcp_backlog: failed to listen with backlog {}",
+                    config.tcp_backlog
+                )
+            })?;
+        socket
+            .set_nonblocking(true)
+            .context("failed to set non-blocking mode")?;
+        TcpListener::from_std(socket.into()).context("failed to register listener in tokio runtime")
+    }
+
+    /// Set the highest processed slot at the time the account message was flushed with the
+    /// commitment, so the delay of the commitment can be measured in slots
+    fn set_commitment_slot(
+        (msgid, mut message): (u64, Message),
+        commitment: CommitmentLevel,
+        slot: Slot,
+    ) -> (u64, Message) {
+        if let Message::Account(msg) = &mut message {
+            match commitment {
+                CommitmentLevel::Processed => {}
+                CommitmentLevel::Confirmed => msg.confirmed_at_slot = Some(slot),
+                CommitmentLevel::Finalized => msg.finalized_at_slot = Some(slot),
+            }
+        }
+        (msgid, message)
+    }
+
+    /// Rollbacks of slots which were sent as finalized but are not ancestors of the finalized
+    /// `slot`, only slots above the oldest known ancestor are checked. Account messages of the
+    /// slot are already deduped, so every rolled back account is listed once.
+    fn get_rollbacks(messages: &mut BTreeMap<u64, SlotMessages>, slot: Slot) -> Vec<Message> {
+        let mut ancestors = HashSet::new();
+        let mut lowest = slot;
+        while let Some(parent) = messages
+            .get(&lowest)
+            .and_then(|slot_messages| slot_messages.parent_slot)
+            .filter(|parent| *parent < lowest && messages.contains_key(parent))
+        {
+            ancestors.insert(parent);
+            lowest = parent;
+        }
+
+        let mut rollbacks = vec![];
+        for (rolled_back_slot, slot_messages) in messages.range_mut(lowest..slot) {
+            let Some(finalized_at) = slot_messages.finalized_at else {
+                continue;
+            };
+            if slot_messages.rolled_back || ancestors.contains(rolled_back_slot) {
+                continue;
+            }
+
+            warn!(
+                "slot {rolled_back_slot} was finalized, but it's not an ancestor of finalized slot {slot}"
+            );
+            slot_messages.rolled_back = true;
+            let accounts = slot_messages.messages[..finalized_at]
+                .iter()
+                .flatten()
+                .filter_map(|(_msgid, message)| match message {
+                    Message::Account(msg) => Some(msg.clone()),
+                    _ => None,
+                })
+                .collect();
+            rollbacks.push(Message::Rollback(Arc::new(MessageRollback::new(
+                *rolled_back_slot,
+                slot,
+                accounts,
+            ))));
+        }
+        rollbacks
+    }
+
+    /// Receive messages from Geyser and broadcast messages to clients, full blocks are built by
+    /// [`BlockAssembly`] in own task.
+    ///
+    /// Ordering invariant for `Confirmed` and `Finalized` commitments: all buffered messages
+    /// of the slot are sent in the same batch as the slot status message and before it (batch
+    /// can be split by `broadcast_batch_max_bytes`, the order is kept), so once a client
+    /// received the status message of the slot it will not receive any message of that slot
+    /// anymore. The only exception is the `Block` message if the block
+    /// was assembled after the slot status was received and `BlockDiagnostic` of not
+    /// reconstructed block. Other late messages are counted by `late_messages_total`.
+    /// Blocks are assembled only for slots first seen while there were subscriptions with
+    /// blocks filters, see [`Broadcast::has_blocks_subscribers`].
+    /// Expected order of received messages is described in [`MessagesSender`].
+    #[allow(clippy::too_many_arguments)]
+    async fn geyser_loop(
+        mut messages_rx: mpsc::UnboundedReceiver<Message>,
+        blocks_meta_tx: Option<mpsc::UnboundedSender<Message>>,
+        commitment_slots: Arc<CommitmentSlots>,
+        health: Option<Arc<PipelineHealth>>,
+        tracked_accounts: Option<Arc<TrackedAccounts>>,
+        broadcast: Broadcast,
+        replay_stored_slots_rx: Option<mpsc::Receiver<ReplayStoredSlotsRequest>>,
+        replay_stored_slots: u64,
+        replay_stored_slots_max_bytes: usize,
+        processed_batch: ProcessedBatchConfig,
+        block_assembly_timeout: Option<Duration>,
+        block_diagnostics: bool,
+        mut votes: Option<VotesAggregator>,
+        mut slot_stats: Option<SlotStatsAggregator>,
+    ) {
+        let mut msgid_gen = MessageId::default();
+        let mut order_checker = MessagesOrderChecker::default();
+        let mut messages: BTreeMap<u64, SlotMessages> = Default::default();
+        let mut messages_bytes = 0;
+        let mut slot_bytes_max = SlotBytesMax::default();
+        let mut slot_statuses = SlotStatuses::default();
+        let mut processed_tip = 0; // stamped on accounts flushed with confirmed and finalized
+        let mut processed_messages = Vec::with_capacity(processed_batch.max_size);
+        let processed_sleep = sleep(processed_batch.max_delay);
+        tokio::pin!(processed_sleep);
+        let (_tx, rx) = mpsc::channel(1);
+        let mut replay_stored_slots_rx = replay_stored_slots_rx.unwrap_or(rx);
+        let (block_assembly_tx, block_assembly_rx) = mpsc::channel(BLOCK_ASSEMBLY_CHANNEL_CAPACITY);
+        let (blocks_tx, mut blocks_rx) = mpsc::unbounded_channel();
+        tokio::spawn(Self::block_assembly_loop(
+            block_assembly_rx,
+            blocks_tx,
+            BlockAssembly::new(block_assembly_timeout, block_diagnostics),
+        ));
+        // messages of the slot are not copied for commitments without receivers
+        let confirmed_enabled =
+            broadcast.is_enabled(CommitmentLevel::Confirmed) || tracked_accounts.is_some();
+        let finalized_enabled =
+            broadcast.is_enabled(CommitmentLevel::Finalized) || tracked_accounts.is_some();
+
+        loop {
+            tokio::select! {
+                message = messages_rx.recv() => {
+                    // plugin is unloaded, other branches would keep the loop and its runtime alive
+                    let Some(message) = message else {
+                        break;
+                    };
+                    metrics::message_queue_size_dec();
+                    order_checker.check(&message);
+                    if let Some(health) = &health {
+                        health.on_message(&message);
+                    }
+
+                    // Ignore duplicated statuses and regressions, messages were already sent
+                    if let Message::Slot(msg) = &message {
+                        if !slot_statuses.update(msg.slot, msg.status) {
+                            metrics::slot_status_duplicates_inc(msg.status);
+                            continue;
+                        }
+                    }
+
+                    let span = trace::message_span(&message);
+                    async {
+                        // Votes of closed windows and slot stats are sent before the status of the slot
+                        let mut aggregated_messages = match &mut votes {
+                            Some(votes) => votes
+                                .update(&message)
+                                .into_iter()
+                                .map(|update| (msgid_gen.next(), Message::Votes(Arc::new(MessageVotes::new(update)))))
+                                .collect::<Vec<_>>(),
+                            None => vec![],
+                        };
+                        if let Some(update) = slot_stats.as_mut().and_then(|slot_stats| slot_stats.update(&message)) {
+                            aggregated_messages.push((msgid_gen.next(), Message::SlotStats(Arc::new(MessageSlotStats::new(update)))));
+                        }
+                        let msgid = msgid_gen.next();
+
+                        // Update metrics
+                        if let Message::Slot(slot_message) = &message {
+                            metrics::update_slot_plugin_status(slot_message.status, slot_message.slot);
+                            commitment_slots.update(slot_message.status, slot_message.slot);
+                            if slot_message.status == SlotStatus::Processed {
+                                processed_tip = processed_tip.max(slot_message.slot);
+                            }
+                        }
+
+                        // Update blocks info
+                        if let Some(blocks_meta_tx) = &blocks_meta_tx {
+                            if matches!(&message, Message::Slot(_) | Message::BlockMeta(_)) {
+                                let _ = blocks_meta_tx.send(message.clone());
+                            }
+                        }
+
+                        // Remove outdated messages
+                        if let Message::Slot(msg) = &message {
+                            if msg.status == SlotStatus::Finalized {
+                                // keep extra slots for late messages + slots for replay
+                                let kept_slots = FINALIZED_SLOTS_KEPT + replay_stored_slots;
+                                if let Some(msg_slot) = msg.slot.checked_sub(kept_slots) {
+                                    slot_statuses.prune(msg_slot);
+                                    loop {
+                                        match messages.keys().next().cloned() {
+                                            Some(slot) if slot < msg_slot => {
+                                                if let Some(slot_messages) = messages.remove(&slot) {
+                                                    messages_bytes -= slot_messages.bytes;
+                                                    slot_bytes_max.shrunk(slot_messages.bytes);
+                                                    metrics::replay_stored_slots_evicted_inc("slots");
+                                                }
+                                            }
+                                            _ => break,
+                                        }
+                                    }
+                                }
+
+                                // evict the oldest finalized slots while stored messages are over the budget,
+                                // the last `FINALIZED_SLOTS_KEPT` slots are kept like above for late statuses
+                                if replay_stored_slots_max_bytes > 0 {
+                                    while messages_bytes > replay_stored_slots_max_bytes {
+                                        match messages.first_entry() {
+                                            Some(entry) if entry.get().finalized_at.is_some() && *entry.key() < msg.slot.saturating_sub(FINALIZED_SLOTS_KEPT) => {
+                                                let bytes = entry.remove().bytes;
+                                                messages_bytes -= bytes;
+                                                slot_bytes_max.shrunk(bytes);
+                                                metrics::replay_stored_slots_evicted_inc("bytes");
+                                            }
+                                            _ => {
+                                                metrics::replay_stored_slots_over_budget_inc();
+                                                break;
+                                            }
+                                        }
+                                    }
+                                }
+                                // slots behind the kept slots are sent to all commitments, shared encodings
+                                // are dropped while transactions are kept for replay
+                                for slot_messages in messages
+                                    .range_mut(..msg.slot.saturating_sub(FINALIZED_SLOTS_KEPT))
+                                    .map(|(_slot, slot_messages)| slot_messages)
+                                    .filter(|slot_messages| !slot_messages.encoded_cleared)
+                                {
+                                    for (_msgid, message) in slot_messages.messages.iter().flatten() {
+                                        message.clear_encoded();
+                                    }
+                                    slot_messages.encoded_cleared = true;
+                                }
+                                metrics::update_replay_stored_slots(
+                                    messages.len(),
+                                    messages_bytes,
+                                    slot_bytes_max.get(&messages),
+                                );
+                            }
+                        }
+
+                        // Store messages for confirmed/finalized commitments and replay
+                        let slot_messages = messages.entry(message.get_slot()).or_insert_with(|| SlotMessages {
+                            assemble: broadcast.has_blocks_subscribers(),
+                            ..Default::default()
+                        });
+                        slot_messages.messages.extend(aggregated_messages.iter().cloned().map(Some));
+                        let aggregated_bytes = aggregated_messages.iter().map(|(_msgid, message)| message.get_size_estimate()).sum::<usize>();
+                        slot_messages.bytes += aggregated_bytes;
+                        messages_bytes += aggregated_bytes;
+                        slot_bytes_max.grown(slot_messages.bytes);
+                        if let Message::Slot(msg) = &message {
+                            match msg.status {
+                                SlotStatus::Processed => {
+                                    slot_messages.parent_slot = msg.parent;
+                                },
+                                SlotStatus::Confirmed => {
+                                    slot_messages.confirmed = true;
+                                },
+                                SlotStatus::Finalized => {
+                                    slot_messages.finalized = true;
+                                },
+                                _ => {}
+                            }
+                            metrics::update_replay_stored_slot_bytes(msg.status, slot_messages.bytes);
+                        }
+                        if matches!(&message, Message::Slot(_)) {
+                            slot_messages.messages_slots.push((msgid, message.clone()));
+                        } else {
+                            // the status is already sent, message is kept only for processed and replay
+                            if slot_messages.confirmed_at.is_some() {
+                                metrics::late_messages_inc(CommitmentLevel::Confirmed);
+                            }
+                            if slot_messages.finalized_at.is_some() {
+                                metrics::late_messages_inc(CommitmentLevel::Finalized);
+                            }
+                            slot_messages.messages.push(Some((msgid, message.clone())));
+                            let bytes = message.get_size_estimate();
+                            slot_messages.bytes += bytes;
+                            messages_bytes += bytes;
+                            slot_bytes_max.grown(slot_messages.bytes);
+                        }
+                        // Dedup accounts by max write_version
+                        if let Message::Account(msg) = &message {
+                            let write_version = msg.account.write_version;
+                            let msg_index = slot_messages.messages.len() - 1;
+                            if let Some(entry) = slot_messages.accounts_dedup.get_mut(&msg.account.pubkey) {
+                                if entry.0 < write_version {
+                                    // We can replace the message, but in this case we will lose the order
+                                    if let Some((_msgid, message)) = slot_messages.messages[entry.1].take() {
+                                        let bytes = message.get_size_estimate();
+                                        slot_bytes_max.shrunk(slot_messages.bytes);
+                                        slot_messages.bytes -= bytes;
+                                        messages_bytes -= bytes;
+                                    }
+                                    *entry = (write_version, msg_index);
+                                }
+                            } else {
+                                slot_messages.accounts_dedup.insert(msg.account.pubkey, (write_version, msg_index));
+                            }
+                        }
+
+                        // Hand off messages to the block assembly, statuses are sent for every slot
+                        let assemble = slot_messages.assemble;
+                        let mut assembly_synced = None;
+                        if assemble || matches!(&message, Message::Slot(_)) {
+                            assembly_synced = Self::block_assembly_send(&block_assembly_tx, &message, assemble);
+                        }
+
+                        // Send messages to filter (and to clients)
+                        let mut messages_vec = Vec::with_capacity(4);
+                        let slot_status = if let Message::Slot(msg) = &message {
+                            Some((msg.slot, msg.status))
+                        } else {
+                            None
+                        };
+                        messages_vec.push((msgid, message));
+                        messages_vec.extend(aggregated_messages.into_iter().rev());
+
+                        // sometimes we do not receive all statuses
+                        if let Some((slot, status)) = slot_status {
+                            let mut slots = vec![slot];
+                            while let Some((parent, Some(entry))) = slots
+                                .pop()
+                                .and_then(|slot| messages.get(&slot))
+                                .and_then(|entry| entry.parent_slot)
+                                .map(|parent| (parent, messages.get_mut(&parent)))
+                            {
+                                if (status == SlotStatus::Confirmed && !entry.confirmed) ||
+                                    (status == SlotStatus::Finalized && !entry.finalized)
+                                {
+                                    if status == SlotStatus::Confirmed {
+                                        entry.confirmed = true;
+                                    } else if status == SlotStatus::Finalized {
+                                        entry.finalized = true;
+                                    }
+
+                                    slots.push(parent);
+                                    slot_statuses.update(parent, status);
+                                    let message_slot = Message::Slot(MessageSlot {
+                                        slot: parent,
+                                        parent: entry.parent_slot,
+                                        status,
+                                        dead_error: None,
+                                        created_at: Timestamp::from(SystemTime::now())
+                                    });
+                                    if let Some(synced) = Self::block_assembly_send(&block_assembly_tx, &message_slot, entry.assemble) {
+                                        assembly_synced = Some(synced);
+                                    }
+                                    messages_vec.push((msgid_gen.next(), message_slot));
+                                    metrics::missed_status_message_inc(status);
+                                }
+                            }
+                        }
+
+                        // blocks assembled before the status are sent with it (see ordering invariant),
+                        // messages are handled by the assembly in order, so waiting for the last status is enough
+                        if let Some(synced) = assembly_synced {
+                            let _ = synced.await;
+                            while let Ok(block) = blocks_rx.try_recv() {
+                                Self::geyser_loop_block(
+                                    block,
+                                    &mut msgid_gen,
+                                    &mut messages,
+                                    &mut processed_messages,
+                                    &broadcast,
+                                    processed_batch,
+                                    processed_sleep.as_mut(),
+                                );
+                            }
+                        }
+
+                        for message in messages_vec.into_iter().rev() {
+                            if let Message::Slot(slot) = &message.1 {
+                                let (mut confirmed_messages, mut finalized_messages) = match slot.status {
+                                    SlotStatus::Processed | SlotStatus::FirstShredReceived | SlotStatus::Completed | SlotStatus::CreatedBank | SlotStatus::Dead => {
+                                        (Vec::with_capacity(1), Vec::with_capacity(1))
+                                    }
+                                    SlotStatus::Confirmed => {
+                                        if let Some(slot_messages) = messages.get_mut(&slot.slot) {
+                                            slot_messages.confirmed_at = Some(slot_messages.messages.len());
+                                        }
+
+                                        let vec = messages
+                                            .get(&slot.slot)
+                                            .filter(|_slot_messages| confirmed_enabled)
+                                            .map(|slot_messages| slot_messages.messages.iter().flatten().cloned().map(|message| Self::set_commitment_slot(message, CommitmentLevel::Confirmed, processed_tip.max(slot.slot))).collect())
+                                            .unwrap_or_default();
+                                        (vec, Vec::with_capacity(1))
+                                    }
+                                    SlotStatus::Finalized => {
+                                        if let Some(slot_messages) = messages.get_mut(&slot.slot) {
+                                            slot_messages.finalized_at = Some(slot_messages.messages.len());
+                                        }
+
+                                        // rollbacks of previously finalized slots are sent first
+                                        let mut vec = Self::get_rollbacks(&mut messages, slot.slot)
+                                            .into_iter()
+                                            .filter(|_message| finalized_enabled)
+                                            .map(|message| (msgid_gen.next(), message))
+                                            .collect::<Vec<_>>();
+                                        vec.extend(messages
+                                            .get(&slot.slot)
+                                            .filter(|_slot_messages| finalized_enabled)
+                                            .into_iter()
+                                            .flat_map(|slot_messages| slot_messages.messages.iter().flatten().cloned().map(|message| Self::set_commitment_slot(message, CommitmentLevel::Finalized, processed_tip.max(slot.slot)))));
+                                        (Vec::with_capacity(1), vec)
+                                    }
+                                };
+
+                                if let Some(tracked_accounts) = &tracked_accounts {
+                                    for (commitment, messages) in [
+                                        (CommitmentLevel::Confirmed, &confirmed_messages),
+                                        (CommitmentLevel::Finalized, &finalized_messages),
+                                    ] {
+                                        tracked_accounts.update(commitment, messages).await;
+                                    }
+                                }
+
+                                // processed
+                                processed_messages.push(message.clone());
+                                if processed_batch.flush_on_slot
+                                    || processed_messages.len() >= processed_batch.max_size
+                                    || processed_batch.max_delay.is_zero()
+                                {
+                                    broadcast.send(CommitmentLevel::Processed, processed_messages);
+                                    processed_messages = Vec::with_capacity(processed_batch.max_size);
+                                    processed_sleep
+                                        .as_mut()
+                                        .reset(Instant::now() + processed_batch.max_delay);
+                                }
+
+                                // confirmed
+                                confirmed_messages.push(message.clone());
+                                broadcast.send(CommitmentLevel::Confirmed, confirmed_messages);
+
+                                // finalized
+                                finalized_messages.push(message);
+                                broadcast.send(CommitmentLevel::Finalized, finalized_messages);
+                            } else {
+                                if let Some(tracked_accounts) = &tracked_accounts {
+                                    tracked_accounts.update(CommitmentLevel::Processed, std::slice::from_ref(&message)).await;
+                                }
+
+                                processed_messages.push(message);
+                                if processed_messages.len() >= processed_batch.max_size
+                                    || processed_batch.max_delay.is_zero()
+                                {
+                                    broadcast.send(CommitmentLevel::Processed, processed_messages);
+                                    processed_messages = Vec::with_capacity(processed_batch.max_size);
+                                    processed_sleep
+                                        .as_mut()
+                                        .reset(Instant::now() + processed_batch.max_delay);
+                                }
+                            }
+                        }
+                    }
+                    .instrument(span)
+                    .await;
+                }
+                Some(block) = blocks_rx.recv() => {
+                    Self::geyser_loop_block(
+                        block,
+                        &mut msgid_gen,
+                        &mut messages,
+                        &mut processed_messages,
+                        &broadcast,
+                        processed_batch,
+                        processed_sleep.as_mut(),
+                    );
+                }
+                () = &mut processed_sleep, if !processed_batch.max_delay.is_zero() => {
+                    if !processed_messages.is_empty() {
+                        broadcast.send(CommitmentLevel::Processed, processed_messages);
+                        processed_messages = Vec::with_capacity(processed_batch.max_size);
+                    }
+                    processed_sleep.as_mut().reset(Instant::now() + processed_batch.max_delay);
+                }
+                Some((commitment, shard, replay_slot, tx)) = replay_stored_slots_rx.recv() => {
+                    if let Some((slot, _)) = messages.first_key_value() {
+                        if replay_slot < *slot {
+                            let _ = tx.send(ReplayedResponse::Lagged(*slot));
+                            continue;
+                        }
+                    }
+
+                    // pending batch is already stored, flush it so the new receiver starts right after the replay
+                    if !processed_messages.is_empty() {
+                        broadcast.send(CommitmentLevel::Processed, processed_messages);
+                        processed_messages = Vec::with_capacity(processed_batch.max_size);
+                        processed_sleep.as_mut().reset(Instant::now() + processed_batch.max_delay);
+                    }
+
+                    let mut replayed_messages = Vec::with_capacity(32_768);
+                    for (slot, messages) in messages.iter() {
+                        if *slot >= replay_slot {
+                            if commitment == CommitmentLevel::Processed {
+                                replayed_messages.extend_from_slice(&messages.messages_slots);
+                                replayed_messages.extend(messages.messages.iter().filter_map(|v| v.clone()));
+                            } else {
+                                // keep the same ordering as in broadcast: slot messages after the data
+                                if (commitment == CommitmentLevel::Finalized && messages.finalized)
+                                    || (commitment == CommitmentLevel::Confirmed && messages.confirmed)
+                                {
+                                    replayed_messages.extend(messages.messages.iter().filter_map(|v| v.clone()));
+                                }
+                                replayed_messages.extend_from_slice(&messages.messages_slots);
+                            }
+                        }
+                    }
+                    if commitment == CommitmentLevel::Processed {
+                        replayed_messages.sort_by_key(|msg| msg.0);
+                    }
+                    let messages_rx = broadcast.subscribe(commitment, shard);
+                    let _ = tx.send(ReplayedResponse::Messages(replayed_messages, messages_rx));
+                }
+                else => break,
+            }
+        }
+    }
+
+    /// Broadcast block or diagnostic returned by the block assembly
+    fn geyser_loop_block(
+        block: AssembledBlock,
+        msgid_gen: &mut MessageId,
+        messages: &mut BTreeMap<u64, SlotMessages>,
+        processed_messages: &mut Vec<(u64, Message)>,
+        broadcast: &Broadcast,
+        processed_batch: ProcessedBatchConfig,
+        processed_sleep: Pin<&mut Sleep>,
+    ) {
+        metrics::block_assembly_lag_observe(block.received_at.elapsed());
+        let block_messages = block
+            .messages
+            .into_iter()
+            .map(|message| (msgid_gen.next(), message))
+            .collect::<Vec<_>>();
+
+        // Block is stored and sent with the slot status if the status is not sent yet,
+        // otherwise deliver as late Block (see ordering invariant)
+        let (confirmed, finalized) = match messages.get_mut(&block.slot) {
+            Some(slot_messages) => {
+                slot_messages.messages.extend(
+                    block_messages
+                        .iter()
+                        .filter(|(_msgid, message)| matches!(message, Message::Block(_)))
+                        .cloned()
+                        .map(Some),
+                );
+                (
+                    slot_messages.confirmed_at.is_some(),
+                    slot_messages.finalized_at.is_some(),
+                )
+            }
+            // slot is already removed, so the diagnostic is sent to every commitment
+            None => (block.finalized, block.finalized),
+        };
+
+        processed_messages.extend(block_messages.iter().cloned());
+        if processed_messages.len() >= processed_batch.max_size
+            || processed_batch.max_delay.is_zero()
+            || confirmed
+            || finalized
+        {
+            broadcast.send(
+                CommitmentLevel::Processed,
+                std::mem::replace(
+                    processed_messages,
+                    Vec::with_capacity(processed_batch.max_size),
+                ),
+            );
+            processed_sleep.reset(Instant::now() + processed_batch.max_delay);
+        }
+        if confirmed {
+            broadcast.send(CommitmentLevel::Confirmed, block_messages.clone());
+        }
+        if finalized {
+            broadcast.send(CommitmentLevel::Finalized, block_messages);
+        }
+    }
+
+    /// Hand off the message to the block assembly, the message is dropped and counted by
+    /// `block_assembly_overflow_total` if the assembly is behind by
+    /// `BLOCK_ASSEMBLY_CHANNEL_CAPACITY` messages. Returns receiver notified once the assembly
+    /// handled confirmed or finalized status of the assembled slot
+    fn block_assembly_send(
+        block_assembly_tx: &mpsc::Sender<BlockAssemblyMessage>,
+        message: &Message,
+        assemble: bool,
+    ) -> Option<oneshot::Receiver<()>> {
+        let (synced, synced_rx) = match message {
+            Message::Slot(msg)
+                if assemble
+                    && matches!(msg.status, SlotStatus::Confirmed | SlotStatus::Finalized) =>
+            {
+                let (tx, rx) = oneshot::channel();
+                (Some(tx), Some(rx))
+            }
+            _ => (None, None),
+        };
+        let message = BlockAssemblyMessage {
+            message: message.clone(),
+            assemble,
+            received_at: Instant::now(),
+            synced,
+        };
+        match block_assembly_tx.try_send(message) {
+            Ok(()) => synced_rx,
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                metrics::block_assembly_overflow_inc();
+                None
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                error!("block assembly loop is closed");
+                None
+            }
+        }
+    }
+
+    /// Build full blocks from messages handed off by `geyser_loop`, blocks are sent back to
+    /// `geyser_loop` for the broadcast
+    async fn block_assembly_loop(
+        mut messages_rx: mpsc::Receiver<BlockAssemblyMessage>,
+        blocks_tx: mpsc::UnboundedSender<AssembledBlock>,
+        mut assembly: BlockAssembly,
+    ) {
+        let deadline_sleep = sleep(Duration::ZERO);
+        tokio::pin!(deadline_sleep);
+
+        loop {
+            let (blocks, synced) = tokio::select! {
+                message = messages_rx.recv() => {
+                    let Some(mut message) = message else {
+                        break;
+                    };
+                    let synced = message.synced.take();
+                    (assembly.update(message), synced)
+                }
+                () = &mut deadline_sleep, if assembly.next_deadline().is_some() => {
+                    (assembly.seal_expired(Instant::now()), None)
+                }
+            };
+            if let Some(deadline) = assembly.next_deadline() {
+                deadline_sleep.as_mut().reset(deadline);
+            }
+
+            for block in blocks {
+                if blocks_tx.send(block).is_err() {
+                    return;
+                }
+            }
+            if let Some(synced) = synced {
+                let _ = synced.send(());
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn client_loop(
+        id: usize,
+        endpoint: String,
+        stream_tx: StreamTx,
+        mut stream_priority_tx: StreamPriorityTx,
+        mut client_rx: mpsc::Receiver<ClientFilterUpdate>,
+        mut startup_rx: Option<StartupReceiver>,
+        startup: Option<StartupBroadcast>,
+        broadcast: Broadcast,
+        commitment_slots: Arc<CommitmentSlots>,
+        replay_stored_slots_tx: Option<mpsc::Sender<ReplayStoredSlotsRequest>>,
+        mut registered: Option<RegisteredClient>,
+        mut quota: Option<ClientQuota>,
+        mut maintenance_rx: MaintenanceRx,
+        ping_interval: Duration,
+        transactions_ordered_timeout: Duration,
+        drop_client: impl FnOnce(),
+    ) {
+        let mut filter = Filter::default();
+        let mut accounts_coalesce = AccountsCoalesce::default();
+        let mut accounts_txn_signatures = AccountsTxnSignatures::default();
+        let mut transactions_signatures = TransactionsSignatures::default();
+        let mut transactions_ordered = TransactionsOrdered::new(transactions_ordered_timeout);
+        let mut seen_signatures = SeenSignatures::default();
+        let mut messages_commitment = filter.get_commitment_level();
+        let mut messages_shard = broadcast.get_shard(&filter);
+        let mut messages_rx = broadcast.subscribe(messages_commitment, messages_shard);
+        let mut blocks_subscriber = broadcast.blocks_subscriber();
+        let mut messages_last_slot = 0;
+        let mut lost: Option<SubscribeUpdateLost> = None;
+        let mut filter_received = false;
+        let mut messages_filtered = 0;
+        let mut ping_seq = 0;
+        let ping_sleep = sleep(ping_interval);
+        tokio::pin!(ping_sleep);
+        let mut maintenance_closed = false;
+        metrics::update_subscriptions(&endpoint, None, Some(&filter));
+
+        metrics::connections_total_inc();
+        info!("client #{id}: new");
+
+        // announced before the subscription
+        let mut maintenance_announced = None;
+        let maintenance = Self::client_loop_maintenance_update(
+            maintenance_rx.borrow_and_update().clone(),
+            &mut maintenance_announced,
+        );
+        let mut is_alive = match maintenance {
+            Some(msg) => Self::client_loop_send_maintenance(id, &stream_priority_tx, msg).await,
+            None => true,
+        };
+        let mut startup_done = startup_rx.is_some();
+        if let Some(startup_rx) = startup_rx.take().filter(|_| is_alive) {
+            Self::client_loop_snapshot(
+                id,
+                &endpoint,
+                &stream_tx,
+                &mut stream_priority_tx,
+                &mut client_rx,
+                startup_rx,
+                &mut is_alive,
+                &mut filter,
+                &commitment_slots,
+            )
+            .await;
+            filter_received = true;
+            blocks_subscriber.update(&filter);
+            if let Some(registered) = &registered {
+                registered.update_filter(&filter);
+            }
+        }
+
+        // ordered transactions are flushed on timeout even if no messages are received
+        let ordered_sleep = sleep(Duration::ZERO);
+        tokio::pin!(ordered_sleep);
+
+        if is_alive {
+            'outer: loop {
+                if let Some(deadline) = transactions_ordered.next_deadline() {
+                    ordered_sleep.as_mut().reset(deadline);
+                }
+                tokio::select! {
+                    () = &mut ordered_sleep, if transactions_ordered.next_deadline().is_some() => {
+                        for (slot, updates) in transactions_ordered.flush_expired(&filter) {
+                            info!("client #{id}: block meta for slot {slot} is not received in time");
+                            for update in updates {
+                                if !Self::client_loop_send_update(id, &endpoint, &stream_tx, &mut stream_priority_tx, &filter, &mut lost, &mut quota, slot, update) {
+                                    break 'outer;
+                                }
+                            }
+                        }
+                    }
+                    status = async {
+                        match registered.as_mut() {
+                            Some(registered) => registered.disconnected().await,
+                            None => pending().await,
+                        }
+                    } => {
+                        info!("client #{id}: disconnected by admin");
+                        stream_priority_tx.close(status);
+                        break 'outer;
+                    }
+                    _ = &mut ping_sleep => {
+                        ping_sleep.as_mut().reset(Instant::now() + ping_interval);
+                        // increased even if ping is dropped, so the client can detect the loss
+                        ping_seq += 1;
+                        if !Self::client_loop_send_ping(id, &stream_tx, &mut stream_priority_tx, &filter, &commitment_slots, ping_seq, &mut messages_filtered) {
+                            break 'outer;
+                        }
+                    }
+                    result = maintenance_rx.changed(), if !maintenance_closed => {
+                        match result {
+                            Ok(()) => {
+                                let maintenance = Self::client_loop_maintenance_update(
+                                    maintenance_rx.borrow_and_update().clone(),
+                                    &mut maintenance_announced,
+                                );
+                                if let Some(msg) = maintenance {
+                                    if !Self::client_loop_send_maintenance(id, &stream_priority_tx, msg).await {
+                                        break 'outer;
+                                    }
+                                }
+                            }
+                            Err(_error) => maintenance_closed = true,
+                        }
+                    }
+                    mut message = client_rx.recv() => {
+                        // forward to latest filter
+                        loop {
+                            match client_rx.try_recv() {
+                                Ok(message_new) => {
+                                    message = Some(message_new);
+                                }
+                                Err(mpsc::error::TryRecvError::Empty) => break,
+                                Err(mpsc::error::TryRecvError::Disconnected) => {
+                                    message = None;
+                                    break;
+                                }
+                            }
+                        }
+
+                        match message {
+                            Some(Some((from_slot, filter_new))) => {
+                                metrics::update_subscriptions(&endpoint, Some(&filter), Some(&filter_new));
+                                filter = filter_new;
+                                blocks_subscriber.update(&filter);
+                                accounts_txn_signatures = AccountsTxnSignatures::default();
+                                transactions_signatures = TransactionsSignatures::default();
+                                transactions_ordered = TransactionsOrdered::new(transactions_ordered_timeout);
+                                seen_signatures.update(&filter);
+                                if let Some(registered) = &registered {
+                                    registered.update_filter(&filter);
+                                }
+                                info!("client #{id}: filter updated");
+
+                                let mut stream_info = commitment_slots.get_stream_info(&filter);
+                                stream_priority_tx.stamp_dropped(&mut stream_info);
+                                if stream_tx.send(Ok(stream_info)).await.is_err() {
+                                    error!("client #{id}: stream closed");
+                                    break 'outer;
+                                }
+                                stream_priority_tx.reset_dropped();
+
+                                // messages queued for the previous commitment or shard are skipped,
+                                // the gap is reported with the lost marker before the next batch
+                                let commitment = filter.get_commitment_level();
+                                let shard = broadcast.get_shard(&filter);
+                                if commitment != messages_commitment || shard != messages_shard {
+                                    if filter_received {
+                                        let msg = lost.get_or_insert_with(|| SubscribeUpdateLost {
+                                            from_slot: messages_last_slot,
+                                            ..Default::default()
+                                        });
+                                        msg.skipped_batches += messages_rx.len() as u64;
+                                        msg.to_slot = 0; // updated on the next batch
+                                        stream_priority_tx.dropped_batches += messages_rx.len() as u64;
+                                    }
+                                    messages_commitment = commitment;
+                                    messages_shard = shard;
+                                    messages_rx = broadcast.subscribe(commitment, shard);
+                                    info!("client #{id}: broadcast changed to {commitment:?} commitment and {shard:?} shard");
+                                }
+                                filter_received = true;
+
+                                if filter.is_startup_accounts() && !startup_done {
+                                    startup_done = true;
+                                    let Some(startup_rx) = startup.as_ref().map(StartupBroadcast::subscribe) else {
+                                        info!("client #{id}: startup accounts are not available");
+                                        stream_priority_tx.close(Status::failed_precondition("startup accounts are not available"));
+                                        break 'outer;
+                                    };
+                                    Self::client_loop_snapshot_stream(id, &stream_tx, &mut stream_priority_tx, startup_rx, &mut is_alive, &filter).await;
+                                    if !is_alive {
+                                        break 'outer;
+                                    }
+                                }
+
+                                if let Some(from_slot) = from_slot {
+                                    let Some(replay_stored_slots_tx) = &replay_stored_slots_tx else {
+                                        info!("client #{id}: from_slot is not supported");
+                                        stream_priority_tx.close(Status::internal("from_slot is not supported"));
+                                        break 'outer;
+                                    };
+
+                                    let (tx, rx) = oneshot::channel();
+                                    let commitment = filter.get_commitment_level();
+                                    if let Err(_error) = replay_stored_slots_tx.send((commitment, messages_shard, from_slot, tx)).await {
+                                        error!("client #{id}: failed to send from_slot request");
+                                        stream_priority_tx.close(Status::internal("failed to send from_slot request"));
+                                        break 'outer;
+                                    }
+
+                                    let messages = match rx.await {
+                                        Ok(ReplayedResponse::Messages(messages, rx)) => {
+                                            messages_rx = rx;
+                                            messages
+                                        }
+                                        Ok(ReplayedResponse::Lagged(slot)) => {
+                                            info!("client #{id}: broadcast from {from_slot} is not available");
+                                            let message = format!(
+                                                "broadcast from {from_slot} is not available, last available: {slot}"
+                                            );
+                                            let mut status = Status::out_of_range(message);
+                                            status.metadata_mut().insert("x-first-available-slot", slot.into());
+                                            stream_priority_tx.close(status);
+                                            break 'outer;
+                                        },
+                                        Err(_error) => {
+                                            error!("client #{id}: failed to get replay response");
+                                            stream_priority_tx.close(Status::internal("failed to get replay response"));
+                                            break 'outer;
+                                        }
+                                    };
+
+                                    let mut replayed_updates = 0;
+                                    for (_msgid, message) in messages.iter() {
+                                        let updates = filter.get_updates(message, Some(commitment));
+                                        if let Message::Transaction(msg) = message {
+                                            if seen_signatures.is_seen(msg) {
+                                                metrics::seen_signatures_suppressed_inc(&endpoint, updates.len());
+                                                continue;
+                                            }
+                                        }
+                                        let slot = message.get_slot();
+                                        for mut message in updates {
+                                            stream_priority_tx.stamp_dropped(&mut message);
+                                            match stream_tx.send(Ok(message)).await {
+                                                Ok(position) => {
+                                                    stream_priority_tx.queued(slot, position);
+                                                    stream_priority_tx.reset_dropped();
+                                                    replayed_updates += 1;
+                                                }
+                                                Err(mpsc::error::SendError(_)) => {
+                                                    error!("client #{id}: stream closed");
+                                                    break 'outer;
+                                                }
+                                            }
+                                        }
+                                    }
+
+                                    Self::client_loop_pda_derivations(&endpoint, &filter, registered.as_ref());
+
+                                    let to_slot = messages.iter().map(|(_msgid, message)| message.get_slot()).max().unwrap_or(from_slot);
+                                    messages_last_slot = messages_last_slot.max(to_slot);
+                                    let mut message = FilteredUpdate::new_empty(FilteredUpdateOneof::replay_done(SubscribeUpdateReplayDone {
+                                        from_slot,
+                                        to_slot,
+                                        replayed_updates,
+                                    }));
+                                    stream_priority_tx.stamp_dropped(&mut message);
+                                    if stream_tx.send(Ok(message)).await.is_err() {
+                                        error!("client #{id}: stream closed");
+                                        break 'outer;
+                                    }
+                                    stream_priority_tx.reset_dropped();
+                                }
+                            }
+                            Some(None) => {
+                                break 'outer;
+                            },
+                            None => {
+                                break 'outer;
+                            }
+                        }
+                    }
+                    message = messages_rx.recv() => {
+                        let (commitment, messages) = match message {
+                            Ok((commitment, messages)) => (commitment, messages),
+                            Err(broadcast::error::RecvError::Closed) => {
+                                break 'outer;
+                            },
+                            Err(broadcast::error::RecvError::Lagged(count)) if filter.is_lag_skip() => {
+                                // continue from the newest batch
+                                let skipped = count + messages_rx.len() as u64;
+                                messages_rx = messages_rx.resubscribe();
+                                info!("client #{id}: lagged to receive geyser messages, skipped {skipped} batches");
+                                metrics::lag_skipped_inc(&endpoint, "batches", skipped);
+                                let msg = lost.get_or_insert_with(|| SubscribeUpdateLost {
+                                    from_slot: messages_last_slot,
+                                    ..Default::default()
+                                });
+                                msg.skipped_batches += skipped;
+                                msg.to_slot = 0; // updated on the next batch
+                                stream_priority_tx.dropped_batches += skipped;
+                                continue;
+                            }
+                            Err(broadcast::error::RecvError::Lagged(_)) => {
+                                info!("client #{id}: lagged to receive geyser messages");
+                                stream_priority_tx.close(Status::internal("lagged to receive geyser messages"));
+                                break 'outer;
+                            }
+                        };
+
+                        if let Some(msg) = lost.as_mut() {
+                            if msg.to_slot == 0 {
+                                msg.to_slot = messages.first().map(|(_msgid, message)| message.get_slot()).unwrap_or(messages_last_slot);
+                            }
+                            let mut update = FilteredUpdate::new_empty(FilteredUpdateOneof::lost(*msg));
+                            stream_priority_tx.stamp_dropped(&mut update);
+                            match stream_tx.try_send(Ok(update)) {
+                                Ok(_position) => {
+                                    lost = None;
+                                    stream_priority_tx.reset_dropped();
+                                }
+                                Err(mpsc::error::TrySendError::Full(_)) => {}
+                                Err(mpsc::error::TrySendError::Closed(_)) => {
+                                    error!("client #{id}: stream closed");
+                                    break 'outer;
+                                }
+                            }
+                        }
+                        if let Some((_msgid, message)) = messages.last() {
+                            messages_last_slot = message.get_slot();
+                        }
+
+                        if commitment == filter.get_commitment_level() {
+                            let superseded = if filter.is_accounts_coalesce() {
+                                accounts_coalesce.get_superseded(&messages)
+                            } else {
+                                HashSet::new()
+                            };
+                            for (index, (_msgid, message)) in messages.iter().enumerate() {
+                                let mut updates = match message {
+                                    Message::Account(message) if superseded.contains(&index) => {
+                                        filter.get_updates_superseded(message)
+                                    }
+                                    message => filter.get_updates(message, Some(commitment)),
+                                };
+                                if updates.is_empty() {
+                                    messages_filtered += 1;
+                                }
+                                match message {
+                                    Message::Transaction(msg) => {
+                                        if seen_signatures.is_seen(msg) {
+                                            metrics::seen_signatures_suppressed_inc(&endpoint, updates.len());
+                                            updates.clear();
+                                        }
+                                        transactions_signatures.update_transaction(&mut filter, msg, &updates);
+                                        updates = transactions_ordered.push(&filter, msg.slot, updates);
+                                    }
+                                    Message::BlockMeta(msg) => {
+                                        updates.extend(transactions_ordered.flush_slot(&filter, msg.slot, false));
+                                    }
+                                    Message::Slot(msg) => {
+                                        transactions_signatures.update_slot(&mut filter, msg.slot);
+                                    }
+                                    _ => {}
+                                }
+                                for update in updates {
+                                    if !Self::client_loop_send_update(id, &endpoint, &stream_tx, &mut stream_priority_tx, &filter, &mut lost, &mut quota, message.get_slot(), update) {
+                                        break 'outer;
+                                    }
+                                }
+                                accounts_txn_signatures.update(&mut filter, message);
+                            }
+                            Self::client_loop_pda_derivations(&endpoint, &filter, registered.as_ref());
+                        }
+
+                        // slot messages are sent with every commitment
+                        if let Some(registered) = &registered {
+                            for message in messages.iter() {
+                                if let Message::Slot(slot_message) = &message.1 {
+                                    registered.update_processed_slot(slot_message.slot);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        metrics::connections_total_dec();
+        metrics::update_subscriptions(&endpoint, Some(&filter), None);
+        if quota.is_some() {
+            metrics::quota_used_remove(&endpoint, id);
+        }
+        info!("client #{id}: removed");
+        drop_client();
+    }
+
+    /// Send ping with increasing `seq`, dropped if the priority queue is full. Counters of sent
+    /// updates are filled by the stream, other counters are reset once the ping is queued
+    fn client_loop_send_ping(
+        id: usize,
+        stream_tx: &StreamTx,
+        stream_priority_tx: &mut StreamPriorityTx,
+        filter: &Filter,
+        commitment_slots: &CommitmentSlots,
+        seq: u64,
+        messages_filtered: &mut u64,
+    ) -> bool {
+        let stats = filter.is_ping_stats().then(|| {
+            let queue_depth = stream_tx.queue_depth() as u64;
+            let mut stats = SubscribeUpdatePingStats {
+                messages_filtered: *messages_filtered,
+                queue_depth,
+                queue_high_watermark: stream_tx.take_queue_high_watermark().max(queue_depth),
+                ..Default::default()
+            };
+            commitment_slots.fill_ping_stats(&mut stats);
+            stats
+        });
+        let queue_high_watermark = stats.as_ref().map(|stats| stats.queue_high_watermark);
+        // ping with stats is delivered after updates counted in stats, others ahead of them
+        let barrier = if stats.is_some() {
+            stream_tx.sequence()
+        } else {
+            0
+        };
+        let mut msg = FilteredUpdate::new_empty(FilteredUpdateOneof::Ping(SubscribeUpdatePing {
+            seq,
+            server_timestamp: unix_timestamp_ms(),
+            stats,
+        }));
+        stream_priority_tx.stamp_dropped(&mut msg);
+        match stream_priority_tx.tx.try_send((barrier, Ok(msg))) {
+            Ok(()) => {
+                *messages_filtered = 0;
+                stream_priority_tx.reset_dropped();
+                true
+            }
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                if let Some(queue_high_watermark) = queue_high_watermark {
+                    stream_tx.update_queue_high_watermark(queue_high_watermark);
+                }
+                true
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                error!("client #{id}: stream closed");
+                false
+            }
+        }
+    }
+
+    /// Maintenance update for the latest announcement, cleared announcement is sent with
+    /// `cancelled` only to the client which received it
+    fn client_loop_maintenance_update(
+        maintenance: Option<Arc<SubscribeUpdateMaintenance>>,
+        announced: &mut Option<Arc<SubscribeUpdateMaintenance>>,
+    ) -> Option<Arc<SubscribeUpdateMaintenance>> {
+        match (maintenance, announced.take()) {
+            (Some(msg), _) => {
+                *announced = Some(Arc::clone(&msg));
+                Some(msg)
+            }
+            (None, Some(msg)) => Some(Arc::new(SubscribeUpdateMaintenance {
+                cancelled: true,
+                ..(*msg).clone()
+            })),
+            (None, None) => None,
+        }
+    }
+
+    /// Announced maintenance is never dropped and delivered ahead of all queued updates
+    async fn client_loop_send_maintenance(
+        id: usize,
+        stream_priority_tx: &StreamPriorityTx,
+        msg: Arc<SubscribeUpdateMaintenance>,
+    ) -> bool {
+        let msg = FilteredUpdate::new_empty(FilteredUpdateOneof::maintenance(msg));
+        if stream_priority_tx.tx.send((0, Ok(msg))).await.is_err() {
+            error!("client #{id}: stream closed");
+            return false;
+        }
+        true
+    }
+
+    /// Add `create_program_address` calls of `pda` templates to metrics of the client
+    fn client_loop_pda_derivations(
+        endpoint: &str,
+        filter: &Filter,
+        registered: Option<&RegisteredClient>,
+    ) {
+        let count = filter.take_pda_derivations();
+        if count > 0 {
+            metrics::pda_derivations_inc(endpoint, count);
+            if let Some(registered) = registered {
+                registered.add_pda_derivations(count);
+            }
+        }
+    }
+
+    /// Send update to the client, returns `false` if the client should be removed
+    #[allow(clippy::too_many_arguments)]
+    fn client_loop_send_update(
+        id: usize,
+        endpoint: &str,
+        stream_tx: &StreamTx,
+        stream_priority_tx: &mut StreamPriorityTx,
+        filter: &Filter,
+        lost: &mut Option<SubscribeUpdateLost>,
+        quota: &mut Option<ClientQuota>,
+        slot: Slot,
+        mut update: FilteredUpdate,
+    ) -> bool {
+        let bytes = if let Some(quota) = quota.as_mut() {
+            let now = Instant::now();
+            let bytes = update.get_size_estimate() as u64;
+            if !quota.check(bytes, now) {
+                let warning = quota.get_warning(now);
+                info!("client #{id}: quota exceeded");
+                metrics::quota_exceeded_inc(endpoint, "hard");
+                let message = format!(
+                    "quota exceeded: {} bytes and {} messages sent in the window",
+                    warning.used_bytes, warning.used_messages
+                );
+                stream_priority_tx.close(Status::resource_exhausted(message));
+                return false;
+            }
+            bytes
+        } else {
+            0
+        };
+
+        stream_priority_tx.stamp_dropped(&mut update);
+
+        // slot updates are delivered ahead of queued updates of other slots
+        let is_slot = matches!(update.message, FilteredUpdateOneof::Slot(_));
+        let result = if is_slot {
+            stream_priority_tx.try_send(slot, update)
+        } else {
+            let mut result = stream_tx.try_send(Ok(update));
+            // the oldest queued data update is evicted for the newest one
+            if filter.is_lag_skip() {
+                if let Err(mpsc::error::TrySendError::Full(update)) = result {
+                    result = match stream_tx.evict_head(&stream_priority_tx.received) {
+                        Some(evicted_slot) => {
+                            Self::client_loop_update_dropped(
+                                endpoint,
+                                stream_priority_tx,
+                                lost,
+                                evicted_slot,
+                            );
+                            stream_tx.try_send(update)
+                        }
+                        None => Err(mpsc::error::TrySendError::Full(update)),
+                    };
+                }
+            }
+            result.map(|position| stream_priority_tx.queued(slot, position))
+        };
+        match result {
+            Ok(()) => {}
+            // slot updates are dropped only if the priority queue is full too, the loop is never
+            // blocked by a stalled client
+            Err(mpsc::error::TrySendError::Full(_)) if filter.is_lag_skip() => {
+                Self::client_loop_update_dropped(endpoint, stream_priority_tx, lost, slot);
+                return true;
+            }
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                error!("client #{id}: lagged to send an update");
+                stream_priority_tx.close(Status::internal("lagged to send an update"));
+                return false;
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                error!("client #{id}: stream closed");
+                return false;
+            }
+        }
+        stream_priority_tx.reset_dropped();
+        tracing::trace!(
+            client = id,
+            slot,
+            queue_depth = stream_tx.queue_depth(),
+            "update queued"
+        );
+
+        if let Some(quota) = quota.as_mut() {
+            if let QuotaCheck::Warning(warning) = quota.add(bytes, Instant::now()) {
+                info!("client #{id}: soft quota reached");
+                metrics::quota_exceeded_inc(endpoint, "soft");
+                // delivered after queued updates of the slot, dropped if the client is too far behind
+                let update = FilteredUpdate::new_empty(FilteredUpdateOneof::quota_warning(warning));
+                match stream_priority_tx.try_send(slot, update) {
+                    Ok(()) => {}
+                    Err(mpsc::error::TrySendError::Full(_)) => {
+                        warn!("client #{id}: quota warning dropped, priority queue is full");
+                    }
+                    Err(mpsc::error::TrySendError::Closed(_)) => {
+                        error!("client #{id}: stream closed");
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    /// Count the update dropped on lag, reported with the next lost marker
+    fn client_loop_update_dropped(
+        endpoint: &str,
+        stream_priority_tx: &mut StreamPriorityTx,
+        lost: &mut Option<SubscribeUpdateLost>,
+        slot: Slot,
+    ) {
+        metrics::lag_skipped_inc(endpoint, "updates", 1);
+        let msg = lost.get_or_insert_with(|| SubscribeUpdateLost {
+            from_slot: slot,
+            ..Default::default()
+        });
+        msg.from_slot = msg.from_slot.min(slot);
+        msg.skipped_updates += 1;
+        msg.to_slot = msg.to_slot.max(slot);
+        stream_priority_tx.dropped_updates += 1;
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn client_loop_snapshot(
+        id: usize,
+        endpoint: &str,
+        stream_tx: &StreamTx,
+        stream_priority_tx: &mut StreamPriorityTx,
+        client_rx: &mut mpsc::Receiver<ClientFilterUpdate>,
+        startup_rx: StartupReceiver,
+        is_alive: &mut bool,
+        filter: &mut Filter,
+        commitment_slots: &CommitmentSlots,
+    ) {
+        info!("client #{id}: going to receive snapshot data");
+
+        // we start with default filter, for snapshot we need wait actual filter first
+        while *is_alive {
+            match client_rx.recv().await {
+                Some(Some((_from_slot, filter_new))) => {
+                    if let Some(msg) = filter_new.get_pong_msg() {
+                        if stream_tx.send(Ok(msg)).await.is_err() {
+                            error!("client #{id}: stream closed");
+                            *is_alive = false;
+                        }
+                        continue;
+                    }
+
+                    metrics::update_subscriptions(endpoint, Some(filter), Some(&filter_new));
+                    *filter = filter_new;
+                    info!("client #{id}: filter updated");
+                    if stream_tx
+                        .send(Ok(commitment_slots.get_stream_info(filter)))
+                        .await
+                        .is_err()
+                    {
+                        error!("client #{id}: stream closed");
+                        *is_alive = false;
+                    }
+                    break;
+                }
+                Some(None) => {
+                    *is_alive = false;
+                }
+                None => {
+                    *is_alive = false;
+                }
+            };
+        }
+
+        Self::client_loop_snapshot_stream(
+            id,
+            stream_tx,
+            stream_priority_tx,
+            startup_rx,
+            is_alive,
+            filter,
+        )
+        .await;
+    }
+
+    async fn client_loop_snapshot_stream(
+        id: usize,
+        stream_tx: &StreamTx,
+        stream_priority_tx: &mut StreamPriorityTx,
+        mut startup_rx: StartupReceiver,
+        is_alive: &mut bool,
+        filter: &Filter,
+    ) {
+        while *is_alive {
+            let message = match startup_rx.recv().await {
+                Ok(Some(message)) => message,
+                // not expected, the broadcast is paced by the slowest client
+                Err(count) => {
+                    error!("client #{id}: lagged to receive startup accounts, skipped {count}");
+                    let status = Status::data_loss(format!("startup accounts skipped: {count}"));
+                    stream_priority_tx.close(status);
+                    *is_alive = false;
+                    break;
+                }
+                Ok(None) => {
+                    info!("client #{id}: end of startup");
+                    if filter.is_startup_accounts() {
+                        let msg = FilteredUpdate::new_empty(FilteredUpdateOneof::end_of_startup());
+                        if stream_tx.send(Ok(msg)).await.is_err() {
+                            error!("client #{id}: stream closed");
+                            *is_alive = false;
+                        }
+                    }
+                    break;
+                }
+            };
+
+            let slot = message.get_slot();
+            for message in filter.get_updates(&message, None) {
+                match stream_tx.send(Ok(message)).await {
+                    Ok(position) => stream_priority_tx.queued(slot, position),
+                    Err(mpsc::error::SendError(_)) => {
+                        error!("client #{id}: stream closed");
+                        *is_alive = false;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Update of the main queue with its position, assigned in the order of queueing
+pub type StreamUpdate = (u64, TonicResult<FilteredUpdate>);
+
+/// Sender of the main queue, shared by `client_loop` and the task of incoming requests
+#[derive(Debug, Clone)]
+pub struct StreamTx {
+    tx: mpsc::Sender<StreamUpdate>,
+    /// Position of the last queued update, locked until the update is queued
+    sequence: Arc<std::sync::Mutex<u64>>,
+    /// Max length of the queue since the last ping, sampled on every queued update
+    queued_max: Arc<AtomicU64>,
+    /// Receiver of the queue, the oldest updates are evicted on lag
+    rx: std::sync::Weak<std::sync::Mutex<StreamRx>>,
+}
+
+impl StreamTx {
+    fn push(
+        &self,
+        permit: mpsc::Permit<'_, StreamUpdate>,
+        update: TonicResult<FilteredUpdate>,
+    ) -> u64 {
+        let mut sequence = self.sequence.lock().expect("unpoisoned mutex");
+        *sequence += 1;
+        permit.send((*sequence, update));
+        self.update_queue_high_watermark(self.queue_depth() as u64);
+        *sequence
+    }
+
+    /// Returns position of the queued update
+    pub async fn send(
+        &self,
+        update: TonicResult<FilteredUpdate>,
+    ) -> Result<u64, mpsc::error::SendError<TonicResult<FilteredUpdate>>> {
+        match self.tx.reserve().await {
+            Ok(permit) => Ok(self.push(permit, update)),
+            Err(mpsc::error::SendError(())) => Err(mpsc::error::SendError(update)),
+        }
+    }
+
+    /// Returns position of the queued update
+    pub fn try_send(
+        &self,
+        update: TonicResult<FilteredUpdate>,
+    ) -> Result<u64, mpsc::error::TrySendError<TonicResult<FilteredUpdate>>> {
+        match self.tx.try_reserve() {
+            Ok(permit) => Ok(self.push(permit, update)),
+            Err(mpsc::error::TrySendError::Full(())) => {
+                Err(mpsc::error::TrySendError::Full(update))
+            }
+            Err(mpsc::error::TrySendError::Closed(())) => {
+                Err(mpsc::error::TrySendError::Closed(update))
+            }
+        }
+    }
+
+    /// Position of the last queued update
+    fn sequence(&self) -> u64 {
+        *self.sequence.lock().expect("unpoisoned mutex")
+    }
+
+    pub fn queue_depth(&self) -> usize {
+        self.tx.max_capacity() - self.tx.capacity()
+    }
+
+    fn update_queue_high_watermark(&self, queued: u64) {
+        self.queued_max.fetch_max(queued, Ordering::Relaxed);
+    }
+
+    /// Max length of the queue since the previous call
+    fn take_queue_high_watermark(&self) -> u64 {
+        self.queued_max.swap(0, Ordering::Relaxed)
+    }
+
+    pub fn downgrade(&self) -> mpsc::WeakSender<StreamUpdate> {
+        self.tx.downgrade()
+    }
+
+    /// Remove the oldest queued update if it's a data update, so the newest one can be queued
+    /// instead. Update at the head is kept if it controls the stream. Returns the slot of the
+    /// evicted update.
+    fn evict_head(&self, received: &AtomicU64) -> Option<Slot> {
+        let rx = self.rx.upgrade()?;
+        let mut rx = rx.lock().expect("unpoisoned mutex");
+        let head = match rx.head.take() {
+            Some(head) => Some(head),
+            None => rx.rx.try_recv().ok(),
+        };
+        let slot = match &head {
+            Some((position, Ok(update))) => update.message.get_data_slot().inspect(|_slot| {
+                received.fetch_max(*position, Ordering::SeqCst);
+            }),
+            _ => None,
+        };
+        if slot.is_none() {
+            rx.head = head;
+        }
+        slot
+    }
+}
+
+/// Receiver of the main queue, shared with the sender to evict the oldest updates
+#[derive(Debug)]
+struct StreamRx {
+    rx: mpsc::Receiver<StreamUpdate>,
+    /// Update received by the sender but not evicted, returned first
+    head: Option<StreamUpdate>,
+}
+
+impl StreamRx {
+    fn poll_recv(&mut self, cx: &mut TaskContext<'_>) -> Poll<Option<StreamUpdate>> {
+        match self.head.take() {
+            Some(update) => Poll::Ready(Some(update)),
+            None => self.rx.poll_recv(cx),
+        }
+    }
+}
+
+/// Priority update with the position of the last update to be received from the main queue
+/// before it
+type StreamPriorityUpdate = (u64, TonicResult<FilteredUpdate>);
+
+/// Sender of slot updates and pings, delivered ahead of queued updates of other slots
+#[derive(Debug)]
+struct StreamPriorityTx {
+    tx: mpsc::Sender<StreamPriorityUpdate>,
+    /// Position of the last update received from the main queue by the stream
+    received: Arc<AtomicU64>,
+    /// Position in the main queue of the last queued update of the slot
+    slots: HashMap<Slot, u64>,
+    /// Updates dropped since the last queued update, sent with the next one
+    dropped_updates: u64,
+    /// Broadcast batches skipped since the last queued update, sent with the next one
+    dropped_batches: u64,
+    /// Status delivered ahead of all queued updates, ends the stream
+    terminal_tx: Option<oneshot::Sender<Status>>,
+}
+
+impl StreamPriorityTx {
+    /// Save position of the update of `slot` just queued to the main queue
+    fn queued(&mut self, slot: Slot, position: u64) {
+        self.slots.insert(slot, position);
+    }
+
+    /// Counters of dropped updates and skipped batches are carried by the update, reset with
+    /// [`Self::reset_dropped`] once the update is queued, so they sum up to `Lost` counters
+    fn stamp_dropped(&self, update: &mut FilteredUpdate) {
+        update.dropped_since_last = self.dropped_updates;
+        update.dropped_batches_since_last = self.dropped_batches;
+    }
+
+    fn reset_dropped(&mut self) {
+        self.dropped_updates = 0;
+        self.dropped_batches = 0;
+    }
+
+    /// End the stream with `status`, updates in the queues are never sent
+    fn close(&mut self, status: Status) {
+        if let Some(terminal_tx) = self.terminal_tx.take() {
+            let _ = terminal_tx.send(status);
+        }
+    }
+
+    fn get_barrier(&mut self, slot: Slot) -> u64 {
+        let received = self.received.load(Ordering::SeqCst);
+        self.slots.retain(|_slot, position| *position > received);
+        self.slots.get(&slot).copied().unwrap_or_default()
+    }
+
+    fn try_send(
+        &mut self,
+        slot: Slot,
+        update: FilteredUpdate,
+    ) -> Result<(), mpsc::error::TrySendError<TonicResult<FilteredUpdate>>> {
+        let barrier = self.get_barrier(slot);
+        self.tx
+            .try_send((barrier, Ok(update)))
+            .map_err(|error| match error {
+                mpsc::error::TrySendError::Full((_barrier, update)) => {
+                    mpsc::error::TrySendError::Full(update)
+                }
+                mpsc::error::TrySendError::Closed((_barrier, update)) => {
+                    mpsc::error::TrySendError::Closed(update)
+                }
+            })
+    }
+
+    async fn send(
+        &mut self,
+        slot: Slot,
+        update: FilteredUpdate,
+    ) -> Result<(), mpsc::error::SendError<StreamPriorityUpdate>> {
+        let barrier = self.get_barrier(slot);
+        self.tx.send((barrier, Ok(update))).await
+    }
+}
+
+/// Updates to the client, sent bytes are counted on encoding for the admin service
+///
+/// Slot updates and pings are delivered ahead of queued updates, but never before updates
+/// of the same slot queued earlier. Terminal status is delivered ahead of everything. Every
+/// update is stamped with `sequence` in the order of sending. Ping with stats gets the
+/// number and size of updates sent since the previous one.
+#[derive(Debug)]
+pub struct SubscribeStream {
+    rx: Arc<std::sync::Mutex<StreamRx>>,
+    priority_rx: ReceiverStream<StreamPriorityUpdate>,
+    priority_pending: Option<StreamPriorityUpdate>,
+    terminal_rx: Option<oneshot::Receiver<Status>>,
+    terminated: bool,
+    received: Arc<AtomicU64>,
+    sequence: u64,
+    bytes: EncodedBytesCounter,
+    ping_updates: u64,
+    ping_bytes_start: u64,
+}
+
+impl SubscribeStream {
+    fn new(capacity: usize) -> (StreamTx, StreamPriorityTx, Self) {
+        let (tx, rx) = mpsc::channel(capacity);
+        let rx = Arc::new(std::sync::Mutex::new(StreamRx { rx, head: None }));
+        let tx = StreamTx {
+            tx,
+            sequence: Arc::new(std::sync::Mutex::new(0)),
+            queued_max: Arc::new(AtomicU64::new(0)),
+            rx: Arc::downgrade(&rx),
+        };
+        let (priority_tx, priority_rx) = mpsc::channel(STREAM_PRIORITY_CHANNEL_CAPACITY);
+        let (terminal_tx, terminal_rx) = oneshot::channel();
+        let received = Arc::new(AtomicU64::new(0));
+        let priority_tx = StreamPriorityTx {
+            tx: priority_tx,
+            received: Arc::clone(&received),
+            slots: HashMap::new(),
+            dropped_updates: 0,
+            dropped_batches: 0,
+            terminal_tx: Some(terminal_tx),
+        };
+        let stream = Self {
+            rx,
+            priority_rx: ReceiverStream::new(priority_rx),
+            priority_pending: None,
+            terminal_rx: Some(terminal_rx),
+            terminated: false,
+            received,
+            sequence: 0,
+            bytes: EncodedBytesCounter::default(),
+            ping_updates: 0,
+            ping_bytes_start: 0,
+        };
+        (tx, priority_tx, stream)
+    }
+}
+
+impl Stream for SubscribeStream {
+    type Item = TonicResult<FilteredUpdate>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if this.terminated {
+            return Poll::Ready(None);
+        }
+        if let Some(terminal_rx) = this.terminal_rx.as_mut() {
+            if let Poll::Ready(result) = Pin::new(terminal_rx).poll(cx) {
+                this.terminal_rx = None;
+                // sender is dropped without status once `client_loop` is finished
+                if let Ok(status) = result {
+                    this.terminated = true;
+                    return Poll::Ready(Some(Err(status)));
+                }
+            }
+        }
+        if this.priority_pending.is_none() {
+            if let Poll::Ready(Some(update)) = Pin::new(&mut this.priority_rx).poll_next(cx) {
+                this.priority_pending = Some(update);
+            }
+        }
+        let mut item = match this.priority_pending.take() {
+            Some((barrier, item)) if barrier <= this.received.load(Ordering::SeqCst) => Some(item),
+            pending => {
+                this.priority_pending = pending;
+                let update = this.rx.lock().expect("unpoisoned mutex").poll_recv(cx);
+                ready!(update).map(|(position, item)| {
+                    // the sender can evict the next updates meanwhile
+                    this.received.fetch_max(position, Ordering::SeqCst);
+                    item
+                })
+            }
+        };
+        if let Some(Ok(update)) = &mut item {
+            this.sequence += 1;
+            update.sequence = this.sequence;
+            update.encoded_bytes = Some(this.bytes.clone());
+            if let FilteredUpdateOneof::Ping(SubscribeUpdatePing {
+                stats: Some(stats), ..
+            }) = &mut update.message
+            {
+                // previous updates are already encoded, the ping itself is not counted
+                let bytes = this.bytes.get();
+                stats.updates_sent = std::mem::take(&mut this.ping_updates);
+                stats.bytes_sent = bytes - this.ping_bytes_start;
+                this.ping_bytes_start = bytes + update.encoded_len() as u64;
+            } else {
+                this.ping_updates += 1;
+            }
+        }
+        Poll::Ready(item)
+    }
+}
+
+#[tonic::async_trait]
+impl Geyser for GrpcService {
+    type SubscribeStream = SubscribeStream;
+
+    async fn subscribe(
+        &self,
+        mut request: Request<Streaming<SubscribeRequest>>,
+    ) -> TonicResult<Response<Self::SubscribeStream>> {
+        let id = self.subscribe_id.fetch_add(1, Ordering::Relaxed);
+
+        let x_request_snapshot = request.metadata().contains_key("x-request-snapshot");
+        let startup_rx = if x_request_snapshot {
+            self.startup.as_ref().map(StartupBroadcast::subscribe)
+        } else {
+            None
+        };
+        let (stream_tx, stream_priority_tx, stream) =
+            SubscribeStream::new(if startup_rx.is_some() {
+                self.config_snapshot_client_channel_capacity
+            } else {
+                self.config_channel_capacity
+            });
+        let (client_tx, client_rx) = mpsc::channel(FILTER_UPDATES_CHANNEL_CAPACITY);
+        let notify_exit = Arc::new(Notify::new());
+
+        let endpoint = request
+            .metadata()
+            .get("x-endpoint")
+            .and_then(|h| h.to_str().ok().map(|s| s.to_string()))
+            .unwrap_or_else(|| "".to_owned());
+
+        let registered = self.clients.as_ref().map(|clients| {
+            clients.register(
+                id,
+                request.remote_addr(),
+                &endpoint,
+                &stream_tx,
+                stream.bytes.clone(),
+            )
+        });
+
+        let caller = FilterCaller {
+            x_token: request
+                .metadata()
+                .get("x-token")
+                .and_then(|value| value.to_str().ok().map(|value| value.to_owned())),
+            remote_ip: request.remote_addr().map(|addr| addr.ip()),
+        };
+
+        let quota = self.config_quota.as_ref().map(|config| {
+            ClientQuota::new(config, caller.x_token.as_deref()).with_metrics(&endpoint, id)
+        });
+
+        let config_filter_limits = Arc::clone(&self.config_filter_limits);
+        let config_filter_presets = Arc::clone(&self.config_filter_presets);
+        let mut filter_updates_rate_limit =
+            FilterUpdatesRateLimit::new(self.config_filter_updates_rate_limit);
+        let filter_names = Arc::clone(&self.filter_names);
+        let incoming_stream_tx = stream_tx.clone();
+        let incoming_commitment_slots = Arc::clone(&self.commitment_slots);
+        let incoming_client_tx = client_tx;
+        let incoming_exit = Arc::clone(&notify_exit);
+        tokio::spawn(async move {
+            let exit = incoming_exit.notified();
+            tokio::pin!(exit);
+            // request and stream info of the latest filter sent to `client_loop`
+            let mut last_request: Option<(SubscribeRequest, SubscribeUpdateStreamInfo)> = None;
+
+            loop {
+                tokio::select! {
+                    _ = &mut exit => {
+                        break;
+                    }
+                    message = request.get_mut().message() => match message {
+                        Ok(Some(mut request)) => {
+                            if let Err(error) = config_filter_presets.apply(&mut request) {
+                                let status = Status::invalid_argument(format!("failed to apply preset: {error}"));
+                                if incoming_stream_tx.send(Err(status)).await.is_err() {
+                                    let _ = incoming_client_tx.send(None).await;
+                                }
+                                continue;
+                            }
+
+                            // the same filter is re-sent as keepalive by some clients, nothing to rebuild,
+                            // keepalives are acked with the stream info and not counted by the rate limit
+                            if let Some((_, info)) = last_request.as_ref().filter(|(last, _)| {
+                                request.from_slot.is_none() && !request_has_signatures(&request) && *last == request
+                            }) {
+                                metrics::filter_updates_identical_inc();
+                                let msg = incoming_commitment_slots.fill_stream_info(info.clone());
+                                if incoming_stream_tx.send(Ok(msg)).await.is_err() {
+                                    error!("client #{id}: stream closed");
+                                    let _ = incoming_client_tx.send(None).await;
+                                    break;
+                                }
+                                continue;
+                            }
+
+                            // filter update rejected, but previous filter is still used
+                            if request.ping.is_none() && !filter_updates_rate_limit.try_acquire() {
+                                metrics::filter_updates_rejected_inc("rate_limit");
+                                let msg = FilteredUpdate::new_warnings(vec![format!(
+                                    "RESOURCE_EXHAUSTED: filter update rejected, max {} updates per second allowed",
+                                    filter_updates_rate_limit.max
+                                )]);
+                                if incoming_stream_tx.send(Ok(msg)).await.is_err() {
+                                    error!("client #{id}: stream closed");
+                                    let _ = incoming_client_tx.send(None).await;
+                                    break;
+                                }
+                                continue;
+                            }
+
+                            // large filters are built on the blocking pool, so the runtime worker is not stalled
+                            let started_at = Instant::now();
+                            let mut names = Arc::clone(&filter_names).lock_owned().await;
+                            let limits = Arc::clone(&config_filter_limits);
+                            let caller = caller.clone();
+                            let Ok((request, filter)) = tokio::task::spawn_blocking(move || {
+                                names.try_clean();
+                                let filter = FilterBuilder::new(&limits)
+                                    .with_caller(&caller)
+                                    .build(&request, &mut names);
+                                let (hits, misses) = names.take_pubkeys_stats();
+                                metrics::filter_pubkeys_cache_inc(hits, misses);
+                                (request, filter)
+                            }).await else {
+                                error!("client #{id}: failed to build filter");
+                                let _ = incoming_client_tx.send(None).await;
+                                break;
+                            };
+                            metrics::filter_build_duration_observe(started_at.elapsed());
+
+                            if let Err(status) = match filter {
+                                Ok(filter) => {
+                                    if let Some(msg) = filter.get_pong_msg() {
+                                        if incoming_stream_tx.send(Ok(msg)).await.is_err() {
+                                            error!("client #{id}: stream closed");
+                                            let _ = incoming_client_tx.send(None).await;
+                                            break;
+                                        }
+                                        continue;
+                                    }
+
+                                    match incoming_client_tx.try_reserve() {
+                                        Ok(permit) => {
+                                            let info = filter.get_stream_info();
+                                            permit.send(Some((request.from_slot, filter)));
+                                            last_request = Some((request, info));
+                                            Ok(())
+                                        },
+                                        Err(mpsc::error::TrySendError::Full(())) => {
+                                            metrics::filter_updates_rejected_inc("queue_full");
+                                            let msg = FilteredUpdate::new_warnings(vec![
+                                                "RESOURCE_EXHAUSTED: filter update rejected, too many pending updates".to_owned(),
+                                            ]);
+                                            if incoming_stream_tx.send(Ok(msg)).await.is_err() {
+                                                error!("client #{id}: stream closed");
+                                                let _ = incoming_client_tx.send(None).await;
+                                                break;
+                                            }
+                                            Ok(())
+                                        }
+                                        Err(error) => Err(Status::invalid_argument(format!(
+                                            "failed to create filter: {error}"
+                                        ))),
+                                    }
+                                },
+                                Err(error) if error.is_permission_denied() => Err(Status::permission_denied(format!(
+                                    "failed to create filter: {error}"
+                                ))),
+                                Err(error) => {
+                                    let mut status = Status::invalid_argument(format!(
+                                        "failed to create filter: {error}"
+                                    ));
+                                    if let Some((max, connections)) = error.shard_hint() {
+                                        status.metadata_mut().insert("x-shard-max-pubkeys", max.into());
+                                        status.metadata_mut().insert("x-shard-connections", connections.into());
+                                    }
+                                    Err(status)
+                                },
+                            } {
+                                if incoming_stream_tx.send(Err(status)).await.is_err() {
+                                    let _ = incoming_client_tx.send(None).await;
+                                }
+                            }
+                        }
+                        Ok(None) => {
+                            break;
+                        }
+                        Err(_error) => {
+                            let _ = incoming_client_tx.send(None).await;
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        tokio::spawn(Self::client_loop(
+            id,
+            endpoint,
+            stream_tx,
+            stream_priority_tx,
+            client_rx,
+            startup_rx,
+            self.startup.clone(),
+            self.broadcast.clone(),
+            Arc::clone(&self.commitment_slots),
+            self.replay_stored_slots_tx.clone(),
+            registered,
+            quota,
+            self.maintenance_rx.clone(),
+            self.config_ping_interval,
+            self.config_transactions_ordered_timeout,
+            move || notify_exit.notify_one(),
+        ));
+
+        Ok(Response::new(stream))
+    }
+
+    async fn ping(&self, request: Request<PingRequest>) -> Result<Response<PongResponse>, Status> {
+        let count = request.get_ref().count;
+        let response = PongResponse {
+            count,
+            server_timestamp: unix_timestamp_ms(),
+        };
+        Ok(Response::new(response))
+    }
+
+    async fn get_latest_blockhash(
+        &self,
+        request: Request<GetLatestBlockhashRequest>,
+    ) -> Result<Response<GetLatestBlockhashResponse>, Status> {
+        if let Some(blocks_meta) = &self.blocks_meta {
+            blocks_meta
+                .get_block(
+                    |block| {
+                        block.block_height.map(|value| GetLatestBlockhashResponse {
+                            slot: block.slot,
+                            blockhash: block.blockhash.clone(),
+                            last_valid_block_height: BlockMetaStorageInner::last_valid_block_height(
+                                value.block_height,
+                            ),
+                        })
+                    },
+                    request.get_ref().commitment,
+                    &self.config_filter_limits,
+                )
+                .await
+        } else {
+            Err(Status::unimplemented("method disabled"))
+        }
+    }
+
+    async fn get_block_height(
+        &self,
+        request: Request<GetBlockHeightRequest>,
+    ) -> Result<Response<GetBlockHeightResponse>, Status> {
+        if let Some(blocks_meta) = &self.blocks_meta {
+            blocks_meta
+                .get_block(
+                    |block| {
+                        block.block_height.map(|value| GetBlockHeightResponse {
+                            block_height: value.block_height,
+                        })
+                    },
+                    request.get_ref().commitment,
+                    &self.config_filter_limits,
+                )
+                .await
+        } else {
+            Err(Status::unimplemented("method disabled"))
+        }
+    }
+
+    async fn get_slot(
+        &self,
+        request: Request<GetSlotRequest>,
+    ) -> Result<Response<GetSlotResponse>, Status> {
+        if let Some(blocks_meta) = &self.blocks_meta {
+            blocks_meta
+                .get_block(
+                    |block| Some(GetSlotResponse { slot: block.slot }),
+                    request.get_ref().commitment,
+                    &self.config_filter_limits,
+                )
+                .await
+        } else {
+            Err(Status::unimplemented("method disabled"))
+        }
+    }
+
+    async fn is_blockhash_valid(
+        &self,
+        request: Request<IsBlockhashValidRequest>,
+    ) -> Result<Response<IsBlockhashValidResponse>, Status> {
+        if let Some(blocks_meta) = &self.blocks_meta {
+            let req = request.get_ref();
+            blocks_meta
+                .is_blockhash_valid(&req.blockhash, req.commitment, &self.config_filter_limits)
+                .await
+        } else {
+            Err(Status::unimplemented("method disabled"))
+        }
+    }
+
+    async fn get_version(
+        &self,
+        _request: Request<GetVersionRequest>,
+    ) -> Result<Response<GetVersionResponse>, Status> {
+        Ok(Response::new(GetVersionResponse {
+            version: serde_json::to_string(&GrpcVersionInfo::default()).unwrap(),
+        }))
+    }
+
+    async fn get_slot_status(
+        &self,
+        _request: Request<GetSlotStatusRequest>,
+    ) -> Result<Response<GetSlotStatusResponse>, Status> {
+        Ok(Response::new(self.commitment_slots.to_proto()))
+    }
+
+    async fn get_accounts_snapshot(
+        &self,
+        request: Request<GetAccountsSnapshotRequest>,
+    ) -> Result<Response<GetAccountsSnapshotResponse>, Status> {
+        if let Some(tracked_accounts) = &self.tracked_accounts {
+            tracked_accounts
+                .get_snapshot(request.get_ref(), &self.config_filter_limits)
+                .await
+                .map(Response::new)
+        } else {
+            Err(Status::unimplemented("method disabled"))
+        }
+    }
+
+    async fn get_transaction_status(
+        &self,
+        request: Request<GetTransactionStatusRequest>,
+    ) -> Result<Response<GetTransactionStatusResponse>, Status> {
+        if let Some(recent_transactions) = &self.recent_transactions {
+            recent_transactions
+                .get_status(request.get_ref())
+                .await
+                .map(Response::new)
+        } else {
+            Err(Status::unimplemented("method disabled"))
+        }
+    }
+
+    async fn get_slot_ancestry(
+        &self,
+        request: Request<GetSlotAncestryRequest>,
+    ) -> Result<Response<GetSlotAncestryResponse>, Status> {
+        if let Some(blocks_meta) = &self.blocks_meta {
+            let req = request.get_ref();
+            blocks_meta.get_slot_ancestry(req.slot, req.limit).await
+        } else {
+            Err(Status::unimplemented("method disabled"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        futures::future::FutureExt,
+        prost_types::FileDescriptorProto,
+        solana_sdk::{
+            hash::Hash,
+            transaction::{Transaction, VersionedTransaction},
+            vote::{instruction as vote_instruction, state::Vote},
+        },
+        tokio_stream::StreamExt,
+        tonic::transport::Channel,
+        tonic_health::pb::{
+            health_check_response::ServingStatus, health_client::HealthClient, HealthCheckRequest,
+        },
+        tonic_reflection::pb::v1::{
+            server_reflection_client::ServerReflectionClient,
+            server_reflection_request::MessageRequest, server_reflection_response::MessageResponse,
+            ServerReflectionRequest,
+        },
+        tracing::{
+            field::{Field, Visit},
+            span::{Attributes, Id},
+            Event, Subscriber,
+        },
+        tracing_subscriber::{
+            layer::{Context, SubscriberExt},
+            registry::LookupSpan,
+            Layer,
+        },
+        yellowstone_grpc_proto::{
+            bloom::SignaturesBloom,
+            plugin::{
+                filter::{
+                    limits::FilterLimitsOverLimitPolicy,
+                    message::FilteredUpdateFilters,
+                    name::{FilterName, FilterNames},
+                },
+                message::{MessageAccount, MessageAccountInfo, MessageTransaction},
+            },
+            prelude::{
+                geyser_admin_client::GeyserAdminClient, geyser_client::GeyserClient,
+                subscribe_update::UpdateOneof, AnnounceMaintenanceRequest, BlockHeight,
+                DisconnectClientRequest, ListClientsRequest, OnLag, SubscribeRequestFilterAccounts,
+                SubscribeRequestFilterBlocks, SubscribeRequestFilterSlots,
+                SubscribeRequestFilterTransactions, SubscribeRequestPing, SubscribeUpdate,
+                SubscribeUpdateBlockMeta, SubscribeUpdatePong, SubscribeUpdateStreamInfo,
+                TransactionStatusMeta,
+            },
+        },
+    };
+
+    const PING_INTERVAL: Duration = Duration::from_secs(10);
+    const TRANSACTIONS_ORDERED_TIMEOUT: Duration = Duration::from_secs(10);
+
+    struct GeyserLoop {
+        messages_tx: mpsc::UnboundedSender<Message>,
+        broadcast_rx: [broadcast::Receiver<BroadcastedMessage>; 3],
+        commitment_slots: Arc<CommitmentSlots>,
+    }
+
+    impl GeyserLoop {
+        fn spawn() -> Self {
+            Self::spawn_with_processed_batch(ProcessedBatchConfig {
+                max_size: 31,
+                max_delay: Duration::from_millis(10),
+                flush_on_slot: true,
+            })
+        }
+
+        fn spawn_with_processed_batch(processed_batch: ProcessedBatchConfig) -> Self {
+            Self::spawn_inner(
+                processed_batch,
+                None,
+                Broadcast::new([1024; 3], 0, 0),
+                None,
+                false,
+            )
+        }
+
+        fn spawn_with_block_assembly_timeout(block_assembly_timeout: Duration) -> Self {
+            Self::spawn_inner(
+                ProcessedBatchConfig {
+                    max_size: 31,
+                    max_delay: Duration::from_millis(10),
+                    flush_on_slot: true,
+                },
+                None,
+                Broadcast::new([1024; 3], 0, 0),
+                Some(block_assembly_timeout),
+                false,
+            )
+        }
+
+        fn spawn_with_block_diagnostics(block_assembly_timeout: Duration) -> Self {
+            Self::spawn_inner(
+                ProcessedBatchConfig {
+                    max_size: 31,
+                    max_delay: Duration::from_millis(10),
+                    flush_on_slot: true,
+                },
+                None,
+                Broadcast::new([1024; 3], 0, 0),
+                Some(block_assembly_timeout),
+                true,
+            )
+        }
+
+        fn spawn_with_broadcast(broadcast: Broadcast) -> Self {
+            Self::spawn_inner(
+                ProcessedBatchConfig {
+                    max_size: 31,
+                    max_delay: Duration::from_secs(3600),
+                    flush_on_slot: true,
+                },
+                None,
+                broadcast,
+                None,
+                false,
+            )
+        }
+
+        fn spawn_with_tracked_accounts(tracked_accounts: Arc<TrackedAccounts>) -> Self {
+            Self::spawn_inner(
+                ProcessedBatchConfig {
+                    max_size: 1,
+                    max_delay: Duration::ZERO,
+                    flush_on_slot: true,
+                },
+                Some(tracked_accounts),
+                Broadcast::new([1024; 3], 0, 0),
+                None,
+                false,
+            )
+        }
+
+        fn spawn_inner(
+            processed_batch: ProcessedBatchConfig,
+            tracked_accounts: Option<Arc<TrackedAccounts>>,
+            broadcast: Broadcast,
+            block_assembly_timeout: Option<Duration>,
+            block_diagnostics: bool,
+        ) -> Self {
+            let (messages_tx, messages_rx) = mpsc::unbounded_channel();
+            let broadcast_rx = [
+                CommitmentLevel::Processed,
+                CommitmentLevel::Confirmed,
+                CommitmentLevel::Finalized,
+            ]
+            .map(|commitment| broadcast.subscribe(commitment, None));
+            let commitment_slots = Arc::new(CommitmentSlots::default());
+            // blocks are assembled as for subscription with blocks filters
+            broadcast.blocks_subscribers.fetch_add(1, Ordering::Relaxed);
+            tokio::spawn(GrpcService::geyser_loop(
+                messages_rx,
+                None,
+                Arc::clone(&commitment_slots),
+                None,
+                tracked_accounts,
+                broadcast,
+                None,
+                0,
+                0,
+                processed_batch,
+                block_assembly_timeout,
+                block_diagnostics,
+                None,
+                None,
+            ));
+            Self {
+                messages_tx,
+                broadcast_rx,
+                commitment_slots,
+            }
+        }
+
+        fn send(&self, message: Message) {
+            metrics::message_queue_size_inc();
+            self.messages_tx
+                .send(message)
+                .expect("geyser loop is alive");
+        }
+
+        /// Next batch of any commitment, order between commitments is not defined
+        async fn recv(&mut self) -> BroadcastedMessage {
+            let [processed, confirmed, finalized] = &mut self.broadcast_rx;
+            let recv = async {
+                tokio::select! {
+                    biased;
+                    message = processed.recv() => message,
+                    message = confirmed.recv() => message,
+                    message = finalized.recv() => message,
+                }
+            };
+            tokio::time::timeout(Duration::from_secs(5), recv)
+                .await
+                .expect("timeout")
+                .expect("broadcast is alive")
+        }
+
+        async fn recv_commitment(&mut self, commitment: CommitmentLevel) -> BroadcastedMessage {
+            tokio::time::timeout(
+                Duration::from_secs(5),
+                self.broadcast_rx[commitment as usize].recv(),
+            )
+            .await
+            .expect("timeout")
+            .expect("broadcast is alive")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_geyser_loop_exit_on_close() {
+        let (messages_tx, messages_rx) = mpsc::unbounded_channel();
+        let geyser_loop = tokio::spawn(GrpcService::geyser_loop(
+            messages_rx,
+            None,
+            Arc::new(CommitmentSlots::default()),
+            None,
+            None,
+            Broadcast::new([16; 3], 0, 0),
+            None,
+            0,
+            0,
+            ProcessedBatchConfig {
+                max_size: 31,
+                max_delay: Duration::from_millis(10),
+                flush_on_slot: true,
+            },
+            None,
+            false,
+            None,
+            None,
+        ));
+        drop(messages_tx);
+        tokio::time::timeout(Duration::from_secs(5), geyser_loop)
+            .await
+            .expect("loop is finished")
+            .expect("loop is not failed");
+    }
+
+    fn create_message_slot(slot: Slot, parent: Option<Slot>, status: SlotStatus) -> Message {
+        Message::Slot(MessageSlot {
+            slot,
+            parent,
+            status,
+            dead_error: None,
+            created_at: Timestamp::from(SystemTime::now()),
+        })
+    }
+
+    fn create_message_account(slot: Slot, pubkey: Pubkey, write_version: u64) -> Message {
+        Message::Account(MessageAccount {
+            account: Arc::new(MessageAccountInfo {
+                pubkey,
+                lamports: 42,
+                owner: Pubkey::new_unique(),
+                executable: false,
+                rent_epoch: 0,
+                data: Default::default(),
+                write_version,
+                txn_signature: None,
+                base58: Default::default(),
+            }),
+            slot,
+            is_startup: false,
+            created_at: Timestamp::from(SystemTime::now()),
+            confirmed_at_slot: None,
+            finalized_at_slot: None,
+        })
+    }
+
+    fn create_message_account_signed(slot: Slot, txn_signature: Option<Signature>) -> Message {
+        let Message::Account(mut msg) = create_message_account(slot, Pubkey::new_unique(), 0)
+        else {
+            unreachable!()
+        };
+        Arc::make_mut(&mut msg.account).txn_signature = txn_signature;
+        Message::Account(msg)
+    }
+
+    fn create_message_transaction(
+        slot: Slot,
+        signature: Signature,
+        err: Option<TransactionError>,
+        is_vote: bool,
+    ) -> Message {
+        Message::Transaction(MessageTransaction {
+            transaction: Arc::new(MessageTransactionInfo {
+                signature,
+                is_vote,
+                transaction: Default::default(),
+                meta: TransactionStatusMeta {
+                    err,
+                    ..Default::default()
+                },
+                token_balances_none: false,
+                rewards_none: false,
+                index: 0,
+                account_keys: HashSet::new(),
+                encoded: Default::default(),
+            }),
+            slot,
+            created_at: Timestamp::from(SystemTime::now()),
+        })
+    }
+
+    fn create_message_vote(slot: Slot, vote_pubkey: Pubkey, voted_on_slot: Slot) -> Message {
+        let authority = Pubkey::new_unique();
+        let instruction = vote_instruction::vote(
+            &vote_pubkey,
+            &authority,
+            Vote::new(vec![voted_on_slot], Hash::new_unique()),
+        );
+        let transaction = VersionedTransaction::from(Transaction::new_with_payer(
+            &[instruction],
+            Some(&authority),
+        ));
+        Message::Transaction(MessageTransaction {
+            transaction: Arc::new(MessageTransactionInfo::from_transaction(
+                &transaction,
+                &solana_transaction_status::TransactionStatusMeta::default(),
+                true,
+                0,
+            )),
+            slot,
+            created_at: Timestamp::from(SystemTime::now()),
+        })
+    }
+
+    fn create_message_block_meta(
+        slot: Slot,
+        executed_transaction_count: u64,
+        entries_count: u64,
+    ) -> Message {
+        Message::BlockMeta(Arc::new(MessageBlockMeta {
+            block_meta: SubscribeUpdateBlockMeta {
+                slot,
+                blockhash: Hash::new_unique().to_string(),
+                parent_slot: slot - 1,
+                block_height: Some(BlockHeight { block_height: slot }),
+                executed_transaction_count,
+                entries_count,
+                ..Default::default()
+            },
+            created_at: Timestamp::from(SystemTime::now()),
+        }))
+    }
+
+    fn create_message_entry(slot: Slot, index: usize) -> Message {
+        Message::Entry(Arc::new(MessageEntry {
+            slot,
+            index,
+            num_hashes: 0,
+            hash: Hash::new_unique(),
+            executed_transaction_count: 0,
+            starting_transaction_index: 0,
+            created_at: Timestamp::from(SystemTime::now()),
+        }))
+    }
+
+    fn create_service(commitment_slots: Arc<CommitmentSlots>) -> GrpcService {
+        GrpcService {
+            config_snapshot_client_channel_capacity: 1,
+            config_channel_capacity: 1,
+            config_filter_limits: Arc::new(FilterLimits::default()),
+            config_filter_updates_rate_limit: 0,
+            config_ping_interval: PING_INTERVAL,
+            config_transactions_ordered_timeout: TRANSACTIONS_ORDERED_TIMEOUT,
+            config_quota: None,
+            config_filter_presets: Arc::new(FilterPresets::default()),
+            blocks_meta: None,
+            commitment_slots,
+            tracked_accounts: None,
+            recent_transactions: None,
+            subscribe_id: AtomicUsize::new(0),
+            startup: None,
+            broadcast: Broadcast::new([1; 3], 0, 0),
+            replay_stored_slots_tx: None,
+            clients: None,
+            maintenance_rx: watch::channel(None).1,
+            filter_names: Arc::new(Mutex::new(FilterNames::new(
+                128,
+                4_096,
+                Duration::from_secs(1),
+            ))),
+        }
+    }
+
+    fn create_config_grpc(config: serde_json::Value) -> ConfigGrpc {
+        serde_json::from_str(&config.to_string()).expect("valid config")
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_bind_reuseport() {
+        let mut config = create_config_grpc(serde_json::json!({
+            "address": "127.0.0.1:0",
+            "so_reuseport": true,
+        }));
+        let first = GrpcService::bind_listener(&config).expect("first listener");
+        config.address = first.local_addr().expect("local address");
+        let second = GrpcService::bind_listener(&config).expect("second listener");
+        assert_eq!(second.local_addr().expect("local address"), config.address);
+
+        config.so_reuseport = false;
+        let error = GrpcService::bind_listener(&config).expect_err("address in use");
+        assert!(format!("{error:#}").contains("failed to bind"), "{error:#}");
+    }
+
+    #[tokio::test]
+    async fn test_bind_ipv6_dual_stack() {
+        let config = create_config_grpc(serde_json::json!({
+            "address": "127.0.0.1:0",
+            "bind_ipv6_dual_stack": true,
+        }));
+        let error = GrpcService::bind_listener(&config).expect_err("IPv4 address");
+        assert!(
+            format!("{error:#}").contains("bind_ipv6_dual_stack"),
+            "{error:#}"
+        );
+
+        let config = create_config_grpc(serde_json::json!({
+            "address": "[::]:0",
+            "bind_ipv6_dual_stack": true,
+        }));
+        let Ok(listener) = GrpcService::bind_listener(&config) else {
+            // IPv6 is not available
+            return;
+        };
+        let port = listener.local_addr().expect("local address").port();
+        let (stream, accepted) = tokio::join!(
+            tokio::net::TcpStream::connect(("127.0.0.1", port)),
+            listener.accept()
+        );
+        stream.expect("connected over IPv4");
+        accepted.expect("accepted IPv4 connection");
+    }
+
+    #[tokio::test]
+    async fn test_slot_status_gauges_and_rpc() {
+        let mut geyser = GeyserLoop::spawn();
+        for (slot, status) in [
+            (1_000_001, SlotStatus::Processed),
+            (1_000_002, SlotStatus::Processed),
+            (1_000_001, SlotStatus::Confirmed),
+            (1_000_000, SlotStatus::Processed), // lower slot should be ignored
+            (1_000_000, SlotStatus::Finalized),
+        ] {
+            geyser.send(create_message_slot(slot, slot.checked_sub(1), status));
+            // every slot message flushed to all commitments
+            for _ in 0..3 {
+                geyser.recv().await;
+            }
+        }
+
+        let service = create_service(Arc::clone(&geyser.commitment_slots));
+        let response = service
+            .get_slot_status(Request::new(GetSlotStatusRequest {}))
+            .await
+            .expect("valid response")
+            .into_inner();
+
+        for (expected, value) in [
+            (1_000_002, response.processed),
+            (1_000_001, response.confirmed),
+            (1_000_000, response.finalized),
+        ] {
+            let value = value.expect("slot received");
+            assert_eq!(value.slot, expected);
+            assert!(value.updated_at.is_some());
+        }
+    }
+
+    #[test]
+    fn test_commitment_slot_update() {
+        // `geyser_slot` gauge is set only if the slot is increased
+        let slot = CommitmentSlot::default();
+        assert_eq!(slot.to_proto(), None);
+        assert!(slot.update(10));
+        assert!(!slot.update(9));
+        assert!(!slot.update(10));
+        assert!(slot.update(11));
+        assert_eq!(slot.to_proto().map(|slot| slot.slot), Some(11));
+    }
+
+    #[tokio::test]
+    async fn test_slot_status_after_slot_messages() {
+        let mut geyser = GeyserLoop::spawn();
+        let slot = 100;
+        for message in [
+            create_message_slot(slot, Some(slot - 1), SlotStatus::Processed),
+            create_message_account(slot, Pubkey::new_unique(), 1),
+            create_message_account(slot, Pubkey::new_unique(), 1),
+            create_message_block_meta(slot, 0, 1),
+            create_message_slot(slot, Some(slot - 1), SlotStatus::Confirmed),
+            create_message_account(slot, Pubkey::new_unique(), 1),
+            create_message_entry(slot, 0), // block is sealed after the confirmed status
+            create_message_slot(slot, Some(slot - 1), SlotStatus::Finalized),
+        ] {
+            geyser.send(message);
+        }
+
+        let mut confirmed = vec![];
+        let mut finalized = vec![];
+        while finalized.is_empty()
+            || !matches!(finalized.last(), Some(Message::Slot(msg)) if msg.status == SlotStatus::Finalized)
+        {
+            let (commitment, messages) = geyser.recv().await;
+            let vec = match commitment {
+                CommitmentLevel::Processed => continue,
+                CommitmentLevel::Confirmed => &mut confirmed,
+                CommitmentLevel::Finalized => &mut finalized,
+            };
+            vec.extend(messages.iter().map(|(_msgid, message)| message.clone()));
+        }
+
+        for (messages, status, accounts) in [
+            (confirmed, SlotStatus::Confirmed, 2),
+            (finalized, SlotStatus::Finalized, 3),
+        ] {
+            let status_index = messages
+                .iter()
+                .position(|message| matches!(message, Message::Slot(msg) if msg.slot == slot && msg.status == status))
+                .expect("slot status should be received");
+            assert_eq!(
+                messages[..status_index]
+                    .iter()
+                    .filter(|message| matches!(message, Message::Account(_)))
+                    .count(),
+                accounts
+            );
+            assert!(messages[status_index + 1..]
+                .iter()
+                .all(|message| message.get_slot() != slot
+                    || matches!(message, Message::Slot(_) | Message::Block(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_finalized_rollback_on_fork() {
+        let mut geyser = GeyserLoop::spawn();
+        let (account1, account2) = (Pubkey::new_unique(), Pubkey::new_unique());
+        // slot 101 is finalized, but later finalized slot 103 is built on top of 100
+        for message in [
+            create_message_slot(100, Some(99), SlotStatus::Processed),
+            create_message_slot(101, Some(100), SlotStatus::Processed),
+            create_message_account(101, account1, 1),
+            create_message_account(101, account2, 1),
+            create_message_account(101, account1, 2),
+            create_message_slot(101, Some(100), SlotStatus::Finalized),
+            create_message_slot(102, Some(100), SlotStatus::Processed),
+            create_message_account(102, account1, 3),
+            create_message_slot(103, Some(102), SlotStatus::Processed),
+            create_message_slot(103, Some(102), SlotStatus::Finalized),
+            create_message_slot(104, Some(103), SlotStatus::Processed),
+            create_message_slot(104, Some(103), SlotStatus::Finalized),
+        ] {
+            geyser.send(message);
+        }
+
+        let mut finalized = vec![];
+        while !finalized.iter().any(|message| {
+            matches!(message, Message::Slot(msg) if msg.slot == 104 && msg.status == SlotStatus::Finalized)
+        }) {
+            let (commitment, messages) = geyser.recv().await;
+            if commitment == CommitmentLevel::Finalized {
+                finalized.extend(messages.iter().map(|(_msgid, message)| message.clone()));
+            }
+        }
+
+        // only the last write of the account in the finalized slot
+        let accounts = finalized
+            .iter()
+            .filter_map(|message| match message {
+                Message::Account(msg) => Some((
+                    msg.slot,
+                    msg.account.pubkey,
+                    msg.account.write_version,
+                    msg.finalized_at_slot,
+                )),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            accounts,
+            [
+                (101, account2, 1, Some(101)),
+                (101, account1, 2, Some(101)),
+                (102, account1, 3, Some(103)),
+            ]
+        );
+
+        // rollback of 101 is sent once, before messages of the finalized fork
+        let rollbacks = finalized
+            .iter()
+            .enumerate()
+            .filter_map(|(index, message)| match message {
+                Message::Rollback(msg) => Some((index, msg)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(rollbacks.len(), 1);
+        let (rollback_index, rollback) = rollbacks[0];
+        assert_eq!((rollback.slot, rollback.finalized_slot), (101, 102));
+        let mut pubkeys = rollback
+            .accounts
+            .iter()
+            .map(|msg| (msg.account.pubkey, msg.account.write_version))
+            .collect::<Vec<_>>();
+        pubkeys.sort();
+        let mut expected = vec![(account1, 2), (account2, 1)];
+        expected.sort();
+        assert_eq!(pubkeys, expected);
+        let account_index = finalized
+            .iter()
+            .position(|message| matches!(message, Message::Account(msg) if msg.slot == 102))
+            .expect("account of slot 102 should be finalized");
+        assert!(rollback_index < account_index);
+    }
+
+    #[tokio::test]
+    async fn test_processed_batch_without_delay() {
+        for (max_delay, received) in [(Duration::ZERO, true), (Duration::from_secs(3_600), false)] {
+            let mut geyser = GeyserLoop::spawn_with_processed_batch(ProcessedBatchConfig {
+                max_size: 31,
+                max_delay,
+                flush_on_slot: true,
+            });
+            geyser.send(create_message_account(42, Pubkey::new_unique(), 1));
+
+            let message =
+                tokio::time::timeout(Duration::from_millis(100), geyser.broadcast_rx[0].recv())
+                    .await;
+            match message {
+                Ok(Ok((commitment, messages))) => {
+                    assert!(received);
+                    assert_eq!(commitment, CommitmentLevel::Processed);
+                    assert!(matches!(messages.as_slice(), [(_, Message::Account(_))]));
+                }
+                Ok(Err(error)) => panic!("failed to receive message: {error:?}"),
+                Err(_elapsed) => assert!(!received),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_processed_batch_without_flush_on_slot() {
+        let mut geyser = GeyserLoop::spawn_with_processed_batch(ProcessedBatchConfig {
+            max_size: 2,
+            max_delay: Duration::from_secs(3_600),
+            flush_on_slot: false,
+        });
+        geyser.send(create_message_slot(42, Some(41), SlotStatus::Processed));
+        geyser.send(create_message_account(42, Pubkey::new_unique(), 1));
+
+        let (commitment, messages) = geyser.recv_commitment(CommitmentLevel::Confirmed).await;
+        assert_eq!(commitment, CommitmentLevel::Confirmed);
+        assert!(matches!(messages.as_slice(), [(_, Message::Slot(_))]));
+        let (commitment, _messages) = geyser.recv_commitment(CommitmentLevel::Finalized).await;
+        assert_eq!(commitment, CommitmentLevel::Finalized);
+        let (commitment, messages) = geyser.recv_commitment(CommitmentLevel::Processed).await;
+        assert_eq!(commitment, CommitmentLevel::Processed);
+        assert!(matches!(
+            messages.as_slice(),
+            [(_, Message::Slot(_)), (_, Message::Account(_))]
+        ));
+    }
+
+    /// Name and fields of spans and events, `parent` field is the name of the parent span
+    #[derive(Debug, Default, Clone)]
+    struct CapturedTraces(Arc<std::sync::Mutex<Vec<(String, HashMap<String, String>)>>>);
+
+    impl CapturedTraces {
+        fn get(&self, name: &str) -> Vec<HashMap<String, String>> {
+            self.0
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(trace_name, _fields)| trace_name == name)
+                .map(|(_name, fields)| fields.clone())
+                .collect()
+        }
+    }
+
+    #[derive(Default)]
+    struct CapturedFields(HashMap<String, String>);
+
+    impl Visit for CapturedFields {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_owned(), value.to_owned());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.insert(field.name().to_owned(), format!("{value:?}"));
+        }
+    }
+
+    impl<S> Layer<S> for CapturedTraces
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            let mut fields = CapturedFields::default();
+            attrs.record(&mut fields);
+            if let Some(parent) = ctx.span(id).and_then(|span| span.parent()) {
+                fields
+                    .0
+                    .insert("parent".to_owned(), parent.name().to_owned());
+            }
+            let name = attrs.metadata().name().to_owned();
+            self.0.lock().unwrap().push((name, fields.0));
+        }
+
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            let mut fields = CapturedFields::default();
+            event.record(&mut fields);
+            let name = fields.0.get("message").cloned().unwrap_or_default();
+            self.0.lock().unwrap().push((name, fields.0));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_trace_spans() {
+        let traces = CapturedTraces::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(traces.clone()));
+
+        let mut geyser = GeyserLoop::spawn();
+        geyser.send(create_message_slot(42, Some(41), SlotStatus::Processed));
+        geyser.recv_commitment(CommitmentLevel::Processed).await;
+
+        let spans = traces.get("geyser_message");
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0]["slot"], "42");
+        assert_eq!(spans[0]["kind"], "slot");
+        let spans = traces.get("broadcast_batch");
+        assert_eq!(spans.len(), 3);
+        for (fields, commitment) in spans.iter().zip(["Processed", "Confirmed", "Finalized"]) {
+            assert_eq!(fields["commitment"], commitment);
+            assert_eq!(fields["messages"], "1");
+            assert_eq!(fields["parent"], "geyser_message");
+        }
+
+        let (stream_tx, mut stream_priority_tx, _stream_rx) = SubscribeStream::new(16);
+        let update =
+            FilteredUpdate::new_empty(FilteredUpdateOneof::lost(SubscribeUpdateLost::default()));
+        assert!(GrpcService::client_loop_send_update(
+            7,
+            "",
+            &stream_tx,
+            &mut stream_priority_tx,
+            &Filter::default(),
+            &mut None,
+            &mut None,
+            42,
+            update,
+        ));
+        let events = traces.get("update queued");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["client"], "7");
+        assert_eq!(events[0]["slot"], "42");
+        assert_eq!(events[0]["queue_depth"], "1");
+    }
+
+    #[test]
+    fn test_accounts_coalesce() {
+        let pubkey = Pubkey::new_unique();
+        let mut coalesce = AccountsCoalesce::default();
+
+        // newer write in the same batch
+        let messages = [
+            create_message_account(10, pubkey, 2),
+            create_message_account(10, Pubkey::new_unique(), 1),
+            create_message_account(10, pubkey, 3),
+            create_message_account(11, pubkey, 1),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(msgid, message)| (msgid as u64, message))
+        .collect::<Vec<_>>();
+        assert_eq!(coalesce.get_superseded(&messages), HashSet::from([0]));
+
+        // newer write was sent in the previous batch
+        let messages = [
+            (4, create_message_account(10, pubkey, 1)),
+            (5, create_message_account(10, pubkey, 4)),
+            (6, create_message_account(11, pubkey, 2)),
+        ];
+        assert_eq!(coalesce.get_superseded(&messages), HashSet::from([0]));
+
+        // old slots removed on slot advance
+        let messages = [(7, create_message_account(20, pubkey, 1))];
+        assert!(coalesce.get_superseded(&messages).is_empty());
+        assert_eq!(coalesce.slots.keys().copied().collect::<Vec<_>>(), vec![20]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_reflection() {
+        let config: ConfigGrpc = serde_json::from_value(serde_json::json!({
+            "address": "127.0.0.1:0",
+        }))
+        .expect("valid config");
+        let (_snapshot_tx, _messages_tx, shutdown, BoundAddresses { grpc: address, .. }) =
+            GrpcService::create(ConfigTokio::default(), config, None, false)
+                .await
+                .expect("service created");
+
+        let channel = tonic::transport::Endpoint::from_shared(format!("http://{address}"))
+            .expect("valid endpoint")
+            .connect()
+            .await
+            .expect("connected");
+        let mut client = ServerReflectionClient::new(channel);
+        let request = ServerReflectionRequest {
+            host: String::new(),
+            message_request: Some(MessageRequest::FileContainingSymbol(
+                "geyser.Geyser".to_owned(),
+            )),
+        };
+        let response = client
+            .server_reflection_info(tokio_stream::once(request))
+            .await
+            .expect("reflection stream")
+            .into_inner()
+            .message()
+            .await
+            .expect("valid message")
+            .expect("message exists");
+        let Some(MessageResponse::FileDescriptorResponse(response)) = response.message_response
+        else {
+            panic!("unexpected response: {response:?}");
+        };
+
+        let methods = response
+            .file_descriptor_proto
+            .iter()
+            .map(|bytes| FileDescriptorProto::decode(bytes.as_slice()).expect("valid descriptor"))
+            .filter(|file| file.package() == "geyser")
+            .flat_map(|file| file.service)
+            .filter(|service| service.name() == "Geyser")
+            .flat_map(|service| service.method)
+            .map(|method| method.name().to_owned())
+            .collect::<Vec<_>>();
+        assert!(methods.iter().any(|name| name == "Subscribe"));
+
+        shutdown.notify_one();
+    }
+
+    async fn recv_update(stream_rx: &mut SubscribeStream) -> TonicResult<FilteredUpdate> {
+        let update = tokio::time::timeout(Duration::from_secs(5), stream_rx.next())
+            .await
+            .expect("timeout")
+            .expect("stream is alive");
+        // encoded like by tonic, so sent bytes are counted
+        if let Ok(update) = &update {
+            update.encode_to_vec();
+        }
+        update
+    }
+
+    async fn recv_stream_info(stream_rx: &mut SubscribeStream) -> SubscribeUpdateStreamInfo {
+        let update = recv_update(stream_rx).await.expect("valid update");
+        let FilteredUpdateOneof::StreamInfo(msg) = update.message else {
+            panic!("stream info expected, got: {:?}", update.message);
+        };
+        *msg
+    }
+
+    /// Arguments of `GrpcService::client_loop` in tests, defaults are of a plain client
+    struct TestClientLoop {
+        stream_capacity: usize,
+        from_slot: Option<Slot>,
+        startup_rx: Option<StartupReceiver>,
+        startup: Option<StartupBroadcast>,
+        commitment_slots: Arc<CommitmentSlots>,
+        replay_stored_slots_tx: Option<mpsc::Sender<ReplayStoredSlotsRequest>>,
+        registered: Option<RegisteredClient>,
+        quota: Option<ClientQuota>,
+        maintenance_rx: MaintenanceRx,
+        ping_interval: Duration,
+        transactions_ordered_timeout: Duration,
+    }
+
+    impl Default for TestClientLoop {
+        fn default() -> Self {
+            Self {
+                stream_capacity: 16,
+                from_slot: None,
+                startup_rx: None,
+                startup: None,
+                commitment_slots: Arc::new(CommitmentSlots::default()),
+                replay_stored_slots_tx: None,
+                registered: None,
+                quota: None,
+                maintenance_rx: watch::channel(None).1,
+                ping_interval: PING_INTERVAL,
+                transactions_ordered_timeout: TRANSACTIONS_ORDERED_TIMEOUT,
+            }
+        }
+    }
+
+    impl TestClientLoop {
+        /// Spawns the loop with a new stream, `filter` is applied before any other update
+        fn spawn(
+            self,
+            filter: Option<Filter>,
+            broadcast: &Broadcast,
+        ) -> (mpsc::Sender<ClientFilterUpdate>, SubscribeStream) {
+            let (stream_tx, stream_priority_tx, stream_rx) =
+                SubscribeStream::new(self.stream_capacity);
+            let client_tx =
+                self.spawn_with_stream(stream_tx, stream_priority_tx, filter, broadcast);
+            (client_tx, stream_rx)
+        }
+
+        /// Spawns the loop writing to the given stream, filter updates channel is kept open by
+        /// the loop itself
+        fn spawn_with_stream(
+            self,
+            stream_tx: StreamTx,
+            stream_priority_tx: StreamPriorityTx,
+            filter: Option<Filter>,
+            broadcast: &Broadcast,
+        ) -> mpsc::Sender<ClientFilterUpdate> {
+            let (client_tx, client_rx) = mpsc::channel(FILTER_UPDATES_CHANNEL_CAPACITY);
+            if let Some(filter) = filter {
+                client_tx
+                    .try_send(Some((self.from_slot, filter)))
+                    .expect("client loop is alive");
+            }
+            tokio::spawn(GrpcService::client_loop(
+                0,
+                String::new(),
+                stream_tx,
+                stream_priority_tx,
+                client_rx,
+                self.startup_rx,
+                self.startup,
+                broadcast.clone(),
+                self.commitment_slots,
+                self.replay_stored_slots_tx,
+                self.registered,
+                self.quota,
+                self.maintenance_rx,
+                self.ping_interval,
+                self.transactions_ordered_timeout,
+                {
+                    let client_tx = client_tx.clone();
+                    move || drop(client_tx)
+                },
+            ));
+            client_tx
+        }
+    }
+
+    fn spawn_test_client_loop(
+        filter: Filter,
+        broadcast: &Broadcast,
+    ) -> (mpsc::Sender<ClientFilterUpdate>, SubscribeStream) {
+        TestClientLoop::default().spawn(Some(filter), broadcast)
+    }
+
+    #[tokio::test]
+    async fn test_startup_accounts() {
+        let (snapshot_tx, snapshot_rx) = crossbeam_channel::bounded(16);
+        let startup = StartupBroadcast::new(snapshot_rx, 2).expect("thread spawned");
+        let create_filter = || {
+            let request = SubscribeRequest {
+                accounts: [(
+                    "startup".to_owned(),
+                    SubscribeRequestFilterAccounts::default(),
+                )]
+                .into_iter()
+                .collect(),
+                startup_accounts: Some(true),
+                ..Default::default()
+            };
+            Filter::new(
+                &request,
+                &FilterLimits::default(),
+                &mut FilterNames::new(128, 4_096, Duration::from_secs(1)),
+            )
+            .expect("valid filter")
+        };
+        let broadcast = Broadcast::new([16; 3], 0, 0);
+        let spawn_client = |startup_rx| {
+            TestClientLoop {
+                startup_rx,
+                startup: Some(startup.clone()),
+                ..Default::default()
+            }
+            .spawn(Some(create_filter()), &broadcast)
+        };
+
+        // every subscriber receives all startup accounts, more than the broadcast capacity
+        let mut clients = (0..2)
+            .map(|_| spawn_client(Some(startup.subscribe())))
+            .collect::<Vec<_>>();
+        for write_version in 0..5 {
+            let Message::Account(mut message) =
+                create_message_account(0, Pubkey::new_unique(), write_version)
+            else {
+                unreachable!();
+            };
+            message.is_startup = true;
+            metrics::startup_queue_size_inc();
+            snapshot_tx
+                .send(Box::new(Message::Account(message)))
+                .expect("snapshot channel is alive");
+        }
+        drop(snapshot_tx); // notify_end_of_startup
+
+        for (_client_tx, stream_rx) in clients.iter_mut() {
+            recv_stream_info(stream_rx).await;
+            for _ in 0..5 {
+                let update = recv_update(stream_rx).await.expect("valid update");
+                assert!(
+                    matches!(&update.message, FilteredUpdateOneof::Account(msg) if msg.is_startup)
+                );
+            }
+            assert_eq!(
+                recv_update(stream_rx).await.expect("valid update").message,
+                FilteredUpdateOneof::EndOfStartup
+            );
+        }
+
+        // subscribed after the end of startup
+        let (_client_tx, mut stream_rx) = spawn_client(None);
+        recv_stream_info(&mut stream_rx).await;
+        assert_eq!(
+            recv_update(&mut stream_rx)
+                .await
+                .expect("valid update")
+                .message,
+            FilteredUpdateOneof::EndOfStartup
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_info() {
+        let create_filter = |request: &SubscribeRequest| {
+            Filter::new(
+                request,
+                &FilterLimits::default(),
+                &mut FilterNames::new(128, 4_096, Duration::from_secs(1)),
+            )
+            .expect("valid filter")
+        };
+        let commitment_slots = Arc::new(CommitmentSlots::default());
+        commitment_slots.update(SlotStatus::Processed, 42);
+        commitment_slots.update(SlotStatus::Confirmed, 41);
+
+        let broadcast = Broadcast::new([16; 3], 0, 0);
+        let (client_tx, mut stream_rx) = TestClientLoop {
+            commitment_slots: Arc::clone(&commitment_slots),
+            ..Default::default()
+        }
+        .spawn(
+            Some(create_filter(&SubscribeRequest {
+                slots: [("slots".to_owned(), SubscribeRequestFilterSlots::default())]
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            })),
+            &broadcast,
+        );
+
+        let msg = recv_stream_info(&mut stream_rx).await;
+        assert_eq!(msg.processed.map(|slot| slot.slot), Some(42));
+        assert_eq!(msg.confirmed.map(|slot| slot.slot), Some(41));
+        assert_eq!(msg.finalized, None);
+        assert_eq!(msg.version, VERSION.version);
+        assert_eq!(msg.commitment, CommitmentLevelProto::Processed as i32);
+        assert_eq!(msg.slots, vec!["slots".to_owned()]);
+        assert!(msg.accounts.is_empty());
+
+        // re-sent after filter update, before any other update
+        commitment_slots.update(SlotStatus::Processed, 43);
+        client_tx
+            .try_send(Some((
+                None,
+                create_filter(&SubscribeRequest {
+                    accounts: [(
+                        "accounts".to_owned(),
+                        SubscribeRequestFilterAccounts::default(),
+                    )]
+                    .into_iter()
+                    .collect(),
+                    commitment: Some(CommitmentLevelProto::Confirmed as i32),
+                    ..Default::default()
+                }),
+            )))
+            .expect("client loop is alive");
+        let msg = recv_stream_info(&mut stream_rx).await;
+        assert_eq!(msg.processed.map(|slot| slot.slot), Some(43));
+        assert_eq!(msg.commitment, CommitmentLevelProto::Confirmed as i32);
+        assert_eq!(msg.accounts, vec!["accounts".to_owned()]);
+        assert!(msg.slots.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stream_info_warnings() {
+        let mut limits = FilterLimits::default();
+        limits.accounts.account_max = 1;
+        limits.accounts.over_limit_policy = FilterLimitsOverLimitPolicy::Clamp;
+        let request = SubscribeRequest {
+            accounts: [(
+                "accounts".to_owned(),
+                SubscribeRequestFilterAccounts {
+                    account: (0..3).map(|_| Pubkey::new_unique().to_string()).collect(),
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &request,
+            &limits,
+            &mut FilterNames::new(128, 4_096, Duration::from_secs(1)),
+        )
+        .expect("clamped filter");
+
+        // clamped Pubkeys are reported with the stream info, not with a separate update
+        let (_client_tx, mut stream_rx) =
+            spawn_test_client_loop(filter, &Broadcast::new([16; 3], 0, 0));
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert!(matches!(update.message, FilteredUpdateOneof::StreamInfo(_)));
+        assert_eq!(
+            update.warnings,
+            vec!["filter `accounts`: `account` clamped to 1 Pubkeys, 2 dropped".to_owned()]
+        );
+    }
+
+    fn spawn_client_loop_on_lag_skip(
+        broadcast: &Broadcast,
+        stream_capacity: usize,
+    ) -> SubscribeStream {
+        let request = SubscribeRequest {
+            accounts: [("all".to_owned(), SubscribeRequestFilterAccounts::default())]
+                .into_iter()
+                .collect(),
+            slots: [("all".to_owned(), SubscribeRequestFilterSlots::default())]
+                .into_iter()
+                .collect(),
+            on_lag: Some(OnLag::Skip as i32),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &request,
+            &FilterLimits::default(),
+            &mut FilterNames::new(128, 4_096, Duration::from_secs(1)),
+        )
+        .expect("valid filter");
+
+        let (_client_tx, stream_rx) = TestClientLoop {
+            stream_capacity,
+            ..Default::default()
+        }
+        .spawn(Some(filter), broadcast);
+        stream_rx
+    }
+
+    #[tokio::test]
+    async fn test_on_lag_skip_broadcast() {
+        let broadcast = Broadcast::new([2; 3], 0, 0);
+        let mut stream_rx = spawn_client_loop_on_lag_skip(&broadcast, 16);
+        recv_stream_info(&mut stream_rx).await; // filter is applied
+
+        let send_account = |slot| {
+            broadcast.send(
+                CommitmentLevel::Processed,
+                vec![(slot, create_message_account(slot, Pubkey::new_unique(), 0))],
+            )
+        };
+        send_account(1);
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert!(matches!(update.message, FilteredUpdateOneof::Account(msg) if msg.slot == 1));
+
+        for slot in 2..12 {
+            send_account(slot);
+        }
+        tokio::task::yield_now().await; // lagged
+        send_account(20);
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert_eq!(
+            update.message,
+            FilteredUpdateOneof::lost(SubscribeUpdateLost {
+                skipped_batches: 10,
+                skipped_updates: 0,
+                from_slot: 1,
+                to_slot: 20,
+            })
+        );
+        // skipped batches are carried by the lost marker, not counted as dropped updates
+        assert_eq!(
+            (update.dropped_since_last, update.dropped_batches_since_last),
+            (0, 10)
+        );
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert_eq!(
+            (update.dropped_since_last, update.dropped_batches_since_last),
+            (0, 0)
+        );
+        assert!(matches!(update.message, FilteredUpdateOneof::Account(msg) if msg.slot == 20));
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_changed_lost() {
+        let create_filter = |commitment: CommitmentLevelProto| {
+            let request = SubscribeRequest {
+                accounts: [("all".to_owned(), SubscribeRequestFilterAccounts::default())]
+                    .into_iter()
+                    .collect(),
+                commitment: Some(commitment as i32),
+                ..Default::default()
+            };
+            Filter::new(
+                &request,
+                &FilterLimits::default(),
+                &mut FilterNames::new(128, 4_096, Duration::from_secs(1)),
+            )
+            .expect("valid filter")
+        };
+        let broadcast = Broadcast::new([16; 3], 0, 0);
+        let (client_tx, mut stream_rx) =
+            spawn_test_client_loop(create_filter(CommitmentLevelProto::Confirmed), &broadcast);
+        let send_account = |commitment, slot| {
+            broadcast.send(
+                commitment,
+                vec![(slot, create_message_account(slot, Pubkey::new_unique(), 0))],
+            )
+        };
+
+        // the first filter is not a change
+        recv_stream_info(&mut stream_rx).await;
+        send_account(CommitmentLevel::Confirmed, 1);
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert!(matches!(update.message, FilteredUpdateOneof::Account(msg) if msg.slot == 1));
+
+        client_tx
+            .try_send(Some((None, create_filter(CommitmentLevelProto::Finalized))))
+            .expect("client loop is alive");
+        recv_stream_info(&mut stream_rx).await;
+        send_account(CommitmentLevel::Finalized, 3);
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert_eq!(
+            update.message,
+            FilteredUpdateOneof::lost(SubscribeUpdateLost {
+                skipped_batches: 0,
+                skipped_updates: 0,
+                from_slot: 1,
+                to_slot: 3,
+            })
+        );
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert!(matches!(update.message, FilteredUpdateOneof::Account(msg) if msg.slot == 3));
+    }
+
+    #[tokio::test]
+    async fn test_stream_evict_head() {
+        let (stream_tx, stream_priority_tx, mut stream_rx) = SubscribeStream::new(2);
+        let account = |write_version| {
+            let Message::Account(msg) =
+                create_message_account(1, Pubkey::new_unique(), write_version)
+            else {
+                unreachable!();
+            };
+            Ok(FilteredUpdate::new_empty(FilteredUpdateOneof::account(
+                &msg,
+                Default::default(),
+            )))
+        };
+        let lost = FilteredUpdate::new_empty(FilteredUpdateOneof::lost(Default::default()));
+        stream_tx.try_send(Ok(lost)).expect("not full");
+        stream_tx.try_send(account(1)).expect("not full");
+
+        // lost marker at the head is kept
+        assert_eq!(stream_tx.evict_head(&stream_priority_tx.received), None);
+        stream_tx.try_send(account(2)).expect("not full");
+        assert_eq!(stream_tx.evict_head(&stream_priority_tx.received), None);
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert!(matches!(update.message, FilteredUpdateOneof::Lost(_)));
+
+        // the oldest account is evicted and counted as received
+        assert_eq!(stream_tx.evict_head(&stream_priority_tx.received), Some(1));
+        assert_eq!(stream_priority_tx.received.load(Ordering::SeqCst), 2);
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert!(
+            matches!(update.message, FilteredUpdateOneof::Account(msg) if msg.account.write_version == 2)
+        );
+        assert_eq!(stream_tx.queue_depth(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_on_lag_skip_stream() {
+        let broadcast = Broadcast::new([16; 3], 0, 0);
+        let mut stream_rx = spawn_client_loop_on_lag_skip(&broadcast, 2);
+        recv_stream_info(&mut stream_rx).await; // filter is applied
+
+        let slot = 100;
+        broadcast.send(
+            CommitmentLevel::Processed,
+            (0..3)
+                .map(|write_version| {
+                    create_message_account(slot, Pubkey::new_unique(), write_version)
+                })
+                .chain([create_message_slot(
+                    slot,
+                    Some(slot - 1),
+                    SlotStatus::Processed,
+                )])
+                .enumerate()
+                .map(|(msgid, message)| (msgid as u64, message))
+                .collect(),
+        );
+
+        // two accounts fit into the queue, the oldest one is evicted, slot is never dropped
+        for expected_write_version in 1..3 {
+            let update = recv_update(&mut stream_rx).await.expect("valid update");
+            let FilteredUpdateOneof::Account(msg) = update.message else {
+                panic!("account expected, got: {:?}", update.message);
+            };
+            assert_eq!(msg.account.write_version, expected_write_version);
+        }
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert!(matches!(update.message, FilteredUpdateOneof::Slot(_)));
+        assert_eq!(update.dropped_since_last, 1);
+
+        broadcast.send(
+            CommitmentLevel::Processed,
+            vec![(4, create_message_account(slot + 1, Pubkey::new_unique(), 0))],
+        );
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert_eq!(
+            update.message,
+            FilteredUpdateOneof::lost(SubscribeUpdateLost {
+                skipped_batches: 0,
+                skipped_updates: 1,
+                from_slot: slot,
+                to_slot: slot,
+            })
+        );
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert!(
+            matches!(update.message, FilteredUpdateOneof::Account(msg) if msg.slot == slot + 1)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_on_lag_skip_slot_priority_full() {
+        let request = SubscribeRequest {
+            slots: [("all".to_owned(), SubscribeRequestFilterSlots::default())]
+                .into_iter()
+                .collect(),
+            on_lag: Some(OnLag::Skip as i32),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &request,
+            &FilterLimits::default(),
+            &mut FilterNames::new(128, 4_096, Duration::from_secs(1)),
+        )
+        .expect("valid filter");
+        let slot_update = |slot| {
+            let Message::Slot(msg) =
+                create_message_slot(slot, Some(slot - 1), SlotStatus::Processed)
+            else {
+                unreachable!();
+            };
+            FilteredUpdate::new_empty(FilteredUpdateOneof::slot(msg))
+        };
+
+        let (stream_tx, mut stream_priority_tx, _stream_rx) = SubscribeStream::new(1);
+        for _ in 0..STREAM_PRIORITY_CHANNEL_CAPACITY {
+            stream_priority_tx
+                .try_send(1, slot_update(1))
+                .expect("not full");
+        }
+
+        // slot update is dropped without waiting and reported with the next lost marker
+        let mut lost = None;
+        assert!(GrpcService::client_loop_send_update(
+            0,
+            "",
+            &stream_tx,
+            &mut stream_priority_tx,
+            &filter,
+            &mut lost,
+            &mut None,
+            2,
+            slot_update(2),
+        ));
+        assert_eq!(
+            lost,
+            Some(SubscribeUpdateLost {
+                skipped_batches: 0,
+                skipped_updates: 1,
+                from_slot: 2,
+                to_slot: 2,
+            })
+        );
+        assert_eq!(stream_priority_tx.dropped_updates, 1);
+    }
+
+    #[tokio::test]
+    async fn test_sequence_dropped_since_last() {
+        let broadcast = Broadcast::new([16; 3], 0, 0);
+        let mut stream_rx = spawn_client_loop_on_lag_skip(&broadcast, 2);
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert!(matches!(update.message, FilteredUpdateOneof::StreamInfo(_)));
+        assert_eq!((update.sequence, update.dropped_since_last), (1, 0));
+
+        let slot = 100;
+        broadcast.send(
+            CommitmentLevel::Processed,
+            (0..5)
+                .map(|write_version| {
+                    create_message_account(slot, Pubkey::new_unique(), write_version)
+                })
+                .chain([create_message_slot(
+                    slot,
+                    Some(slot - 1),
+                    SlotStatus::Processed,
+                )])
+                .enumerate()
+                .map(|(msgid, message)| (msgid as u64, message))
+                .collect(),
+        );
+        let mut updates = vec![];
+        for _ in 0..3 {
+            updates.push(recv_update(&mut stream_rx).await.expect("valid update"));
+        }
+        broadcast.send(
+            CommitmentLevel::Processed,
+            vec![(6, create_message_account(slot + 1, Pubkey::new_unique(), 0))],
+        );
+        for _ in 0..2 {
+            updates.push(recv_update(&mut stream_rx).await.expect("valid update"));
+        }
+
+        // sequence has no gaps, dropped updates are reported with the next queued update
+        assert_eq!(
+            updates
+                .iter()
+                .map(|update| update.sequence)
+                .collect::<Vec<_>>(),
+            [2, 3, 4, 5, 6]
+        );
+        assert!(matches!(updates[2].message, FilteredUpdateOneof::Slot(_)));
+        assert_eq!(updates[2].dropped_since_last, 3);
+        let FilteredUpdateOneof::Lost(lost) = &updates[3].message else {
+            panic!("lost expected, got: {:?}", updates[3].message);
+        };
+        assert_eq!(
+            updates
+                .iter()
+                .map(|update| update.dropped_since_last)
+                .sum::<u64>(),
+            lost.skipped_updates
+        );
+    }
+
+    #[tokio::test]
+    async fn test_slot_updates_priority() {
+        let broadcast = Broadcast::new([16; 3], 0, 0);
+        let mut stream_rx = spawn_client_loop_on_lag_skip(&broadcast, 16);
+        recv_stream_info(&mut stream_rx).await; // filter is applied
+
+        // whole batch is queued before the stream is polled
+        broadcast.send(
+            CommitmentLevel::Processed,
+            [create_message_account(10, Pubkey::new_unique(), 0)]
+                .into_iter()
+                .chain([create_message_slot(10, Some(9), SlotStatus::Processed)])
+                .chain((1..4).map(|write_version| {
+                    create_message_account(11, Pubkey::new_unique(), write_version)
+                }))
+                .chain([
+                    create_message_slot(10, Some(9), SlotStatus::Confirmed),
+                    create_message_slot(11, Some(10), SlotStatus::Processed),
+                ])
+                .enumerate()
+                .map(|(msgid, message)| (msgid as u64, message))
+                .collect(),
+        );
+
+        // slot updates are ahead of queued accounts of other slots, but never of the same slot
+        let mut updates = vec![];
+        for _ in 0..7 {
+            updates.push(
+                match recv_update(&mut stream_rx)
+                    .await
+                    .expect("valid update")
+                    .message
+                {
+                    FilteredUpdateOneof::Account(msg) => (msg.slot, None),
+                    FilteredUpdateOneof::Slot(msg) => (msg.slot, Some(msg.status)),
+                    message => panic!("unexpected update: {message:?}"),
+                },
+            );
+        }
+        assert_eq!(
+            updates,
+            [
+                (10, None),
+                (10, Some(SlotStatus::Processed)),
+                (10, Some(SlotStatus::Confirmed)),
+                (11, None),
+                (11, None),
+                (11, None),
+                (11, Some(SlotStatus::Processed)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_priority_positions() {
+        async fn recv_ping_seq(stream_rx: &mut SubscribeStream) -> u64 {
+            match recv_update(stream_rx).await.expect("valid update").message {
+                FilteredUpdateOneof::Ping(msg) => msg.seq,
+                message => panic!("unexpected update: {message:?}"),
+            }
+        }
+
+        let (stream_tx, mut stream_priority_tx, mut stream_rx) = SubscribeStream::new(16);
+        let ping = |seq| FilteredUpdate::new_empty(FilteredUpdateOneof::ping(seq, 0));
+        let mut queue = |slot, seq| {
+            let position = stream_tx.try_send(Ok(ping(seq))).expect("not full");
+            stream_priority_tx.queued(slot, position);
+        };
+
+        // queued by another sender, position is assigned anyway
+        assert_eq!(stream_tx.clone().try_send(Ok(ping(1))).ok(), Some(1));
+        queue(10, 2);
+        assert_eq!(recv_ping_seq(&mut stream_rx).await, 1);
+        queue(10, 3);
+        queue(11, 4);
+        for (slot, seq) in [(10, 100), (11, 101)] {
+            stream_priority_tx
+                .try_send(slot, ping(seq))
+                .expect("not full");
+        }
+
+        let mut received = vec![];
+        for _ in 0..5 {
+            received.push(recv_ping_seq(&mut stream_rx).await);
+        }
+        assert_eq!(received, [2, 3, 100, 4, 101]);
+    }
+
+    #[tokio::test]
+    async fn test_accounts_txn_signature_expiry() {
+        // slots of account updates received before the update of `slot`, updates of other
+        // slots can be delivered ahead of queued accounts
+        async fn recv_slot_accounts(stream_rx: &mut SubscribeStream, slot: Slot) -> Vec<Slot> {
+            let mut accounts = vec![];
+            loop {
+                match recv_update(stream_rx).await.expect("valid update").message {
+                    FilteredUpdateOneof::Account(msg) => accounts.push(msg.slot),
+                    FilteredUpdateOneof::Slot(msg) if msg.slot == slot => break accounts,
+                    FilteredUpdateOneof::Slot(_) => {}
+                    message => panic!("unexpected update: {message:?}"),
+                }
+            }
+        }
+
+        let signature = Signature::new_unique();
+        let request = SubscribeRequest {
+            accounts: [(
+                "signature".to_owned(),
+                SubscribeRequestFilterAccounts {
+                    txn_signature: vec![signature.to_string()],
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            slots: [("slots".to_owned(), SubscribeRequestFilterSlots::default())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &request,
+            &FilterLimits::default(),
+            &mut FilterNames::new(128, 4_096, Duration::from_secs(1)),
+        )
+        .expect("valid filter");
+
+        let broadcast = Broadcast::new([16; 3], 0, 0);
+        let (_client_tx, mut stream_rx) = spawn_test_client_loop(filter, &broadcast);
+        recv_stream_info(&mut stream_rx).await; // filter is applied
+
+        let send = |messages: Vec<Message>| {
+            broadcast.send(
+                CommitmentLevel::Processed,
+                messages
+                    .into_iter()
+                    .enumerate()
+                    .map(|(msgid, message)| (msgid as u64, message))
+                    .collect(),
+            )
+        };
+
+        // every account written by the transaction, writes without signature never match
+        send(vec![
+            create_message_account_signed(10, Some(signature)),
+            create_message_account_signed(10, Some(signature)),
+            create_message_account_signed(10, Some(Signature::new_unique())),
+            create_message_account_signed(10, None),
+            create_message_slot(10, Some(9), SlotStatus::Processed),
+        ]);
+        assert_eq!(recv_slot_accounts(&mut stream_rx, 10).await, [10, 10]);
+
+        // still matched until the slot is finalized
+        send(vec![
+            create_message_account_signed(11, Some(signature)),
+            create_message_slot(10, Some(9), SlotStatus::Finalized),
+            create_message_slot(11, Some(10), SlotStatus::Processed),
+        ]);
+        assert_eq!(recv_slot_accounts(&mut stream_rx, 11).await, [11]);
+        send(vec![
+            create_message_account_signed(12, Some(signature)),
+            create_message_slot(11, Some(10), SlotStatus::Finalized),
+            create_message_slot(12, Some(11), SlotStatus::Processed),
+        ]);
+        assert_eq!(recv_slot_accounts(&mut stream_rx, 12).await, [12]);
+
+        // signature expired with the finalized slot of the latest match
+        send(vec![
+            create_message_slot(12, Some(11), SlotStatus::Finalized),
+            create_message_account_signed(13, Some(signature)),
+            create_message_slot(13, Some(12), SlotStatus::Processed),
+        ]);
+        assert!(recv_slot_accounts(&mut stream_rx, 13).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_quota_warning_and_cutoff() {
+        let request = SubscribeRequest {
+            accounts: [("accounts".to_owned(), Default::default())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &request,
+            &FilterLimits::default(),
+            &mut FilterNames::new(128, 4_096, Duration::from_secs(1)),
+        )
+        .expect("valid filter");
+        let quota: ConfigGrpcQuota = serde_json::from_value(serde_json::json!({
+            "limits": {
+                "soft_messages": 2,
+                "hard_messages": 3,
+            },
+        }))
+        .expect("valid config");
+
+        let broadcast = Broadcast::new([16; 3], 0, 0);
+        let (_client_tx, mut stream_rx) = TestClientLoop {
+            quota: Some(ClientQuota::new(&quota, None)),
+            ..Default::default()
+        }
+        .spawn(Some(filter), &broadcast);
+        recv_stream_info(&mut stream_rx).await; // filter is applied
+
+        let send_accounts = |slot, write_versions: std::ops::Range<u64>| {
+            broadcast.send(
+                CommitmentLevel::Processed,
+                write_versions
+                    .map(|write_version| {
+                        (
+                            write_version,
+                            create_message_account(slot, Pubkey::new_unique(), write_version),
+                        )
+                    })
+                    .collect(),
+            );
+        };
+
+        send_accounts(10, 0..2);
+        for _ in 0..2 {
+            let update = recv_update(&mut stream_rx).await.expect("valid update");
+            assert!(matches!(update.message, FilteredUpdateOneof::Account(_)));
+        }
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        let FilteredUpdateOneof::QuotaWarning(warning) = update.message else {
+            panic!("quota warning expected: {update:?}");
+        };
+        assert_eq!(warning.used_messages, 2);
+        assert_eq!(warning.limit_messages, 3);
+        assert_eq!(warning.limit_bytes, 0);
+        assert!(warning.resets_at.is_some());
+
+        // status is delivered ahead of queued updates, at most one is received before
+        send_accounts(11, 2..5);
+        let mut accounts = 0;
+        let status = loop {
+            match recv_update(&mut stream_rx).await {
+                Ok(update) => {
+                    assert!(matches!(update.message, FilteredUpdateOneof::Account(_)));
+                    accounts += 1;
+                }
+                Err(status) => break status,
+            }
+        };
+        assert!(accounts <= 1);
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert!(stream_rx.next().await.is_none());
+    }
+
+    fn create_message_transaction_index(slot: Slot, index: usize) -> Message {
+        let Message::Transaction(mut message) =
+            create_message_transaction(slot, Signature::new_unique(), None, false)
+        else {
+            unreachable!();
+        };
+        Arc::get_mut(&mut message.transaction)
+            .expect("unique transaction")
+            .index = index;
+        Message::Transaction(message)
+    }
+
+    fn get_transaction_index(update: &FilteredUpdate) -> usize {
+        match &update.message {
+            FilteredUpdateOneof::Transaction(msg) => msg.transaction.index,
+            message => panic!("unexpected update: {message:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transactions_ordered_per_slot() {
+        let request = SubscribeRequest {
+            transactions: [
+                (
+                    "ordered".to_owned(),
+                    SubscribeRequestFilterTransactions {
+                        ordered_per_slot: Some(true),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "unordered".to_owned(),
+                    SubscribeRequestFilterTransactions {
+                        vote: Some(true),
+                        ..Default::default()
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let create_filter = || {
+            Filter::new(
+                &request,
+                &FilterLimits::default(),
+                &mut FilterNames::new(128, 4_096, Duration::from_secs(1)),
+            )
+            .expect("valid filter")
+        };
+
+        let broadcast = Broadcast::new([16; 3], 0, 0);
+        let (client_tx, mut stream_rx) = spawn_test_client_loop(create_filter(), &broadcast);
+        recv_stream_info(&mut stream_rx).await; // filter is applied
+
+        // transactions of two slots arrive interleaved and out of order
+        let slot = 100;
+        broadcast.send(
+            CommitmentLevel::Processed,
+            [
+                create_message_transaction_index(slot, 2),
+                create_message_transaction_index(slot + 1, 1),
+                create_message_transaction_index(slot, 0),
+                create_message_transaction_index(slot + 1, 0),
+                create_message_transaction_index(slot, 1),
+                create_message_block_meta(slot, 3, 1),
+            ]
+            .into_iter()
+            .enumerate()
+            .map(|(msgid, message)| (msgid as u64, message))
+            .collect(),
+        );
+        for index in 0..3 {
+            let update = recv_update(&mut stream_rx).await.expect("valid update");
+            assert_eq!(get_transaction_index(&update), index);
+            assert_eq!(
+                update.filters,
+                FilteredUpdateFilters::from_vec(vec![FilterName::new("ordered")])
+            );
+        }
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert_eq!(
+            update.message,
+            FilteredUpdateOneof::transaction_slot_done(SubscribeUpdateTransactionSlotDone {
+                slot,
+                sent_count: 3,
+                best_effort: false,
+            })
+        );
+
+        // transaction matched by both filters: sent immediately for `unordered`
+        let Message::Transaction(mut vote) = create_message_transaction_index(slot + 1, 2) else {
+            unreachable!();
+        };
+        Arc::get_mut(&mut vote.transaction)
+            .expect("unique transaction")
+            .is_vote = true;
+        broadcast.send(
+            CommitmentLevel::Processed,
+            vec![(6, Message::Transaction(vote))],
+        );
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert_eq!(get_transaction_index(&update), 2);
+        assert_eq!(
+            update.filters,
+            FilteredUpdateFilters::from_vec(vec![FilterName::new("unordered")])
+        );
+
+        broadcast.send(
+            CommitmentLevel::Processed,
+            vec![(7, create_message_block_meta(slot + 1, 3, 1))],
+        );
+        for index in 0..3 {
+            let update = recv_update(&mut stream_rx).await.expect("valid update");
+            assert_eq!(get_transaction_index(&update), index);
+        }
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert!(matches!(
+            update.message,
+            FilteredUpdateOneof::TransactionSlotDone(msg) if msg.slot == slot + 1 && msg.sent_count == 3
+        ));
+
+        // buffered transactions of the previous filter are dropped on filter update
+        let Message::Transaction(mut vote) = create_message_transaction_index(slot + 2, 0) else {
+            unreachable!();
+        };
+        Arc::get_mut(&mut vote.transaction)
+            .expect("unique transaction")
+            .is_vote = true;
+        broadcast.send(
+            CommitmentLevel::Processed,
+            vec![(8, Message::Transaction(vote))],
+        );
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert_eq!(
+            update.filters,
+            FilteredUpdateFilters::from_vec(vec![FilterName::new("unordered")])
+        );
+        client_tx
+            .try_send(Some((None, create_filter())))
+            .expect("client loop is alive");
+        recv_stream_info(&mut stream_rx).await;
+        broadcast.send(
+            CommitmentLevel::Processed,
+            vec![(9, create_message_block_meta(slot + 2, 1, 1))],
+        );
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert!(matches!(
+            update.message,
+            FilteredUpdateOneof::TransactionSlotDone(msg) if msg.slot == slot + 2 && msg.sent_count == 0
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_seen_signatures_bloom() {
+        let seen = Signature::new_unique();
+        let unseen = Signature::new_unique();
+        let mut bloom = SignaturesBloom::with_false_positive_rate(16, 0.0001);
+        bloom.insert(seen.as_ref());
+        let request = SubscribeRequest {
+            transactions: [(
+                "tx".to_owned(),
+                SubscribeRequestFilterTransactions::default(),
+            )]
+            .into_iter()
+            .collect(),
+            seen_signatures_bloom: Some(bloom.into()),
+            ..Default::default()
+        };
+        let mut limits = FilterLimits::default();
+        limits.seen_signatures.slots = 2;
+        let filter = Filter::new(
+            &request,
+            &limits,
+            &mut FilterNames::new(128, 4_096, Duration::from_secs(1)),
+        )
+        .expect("valid filter");
+
+        let broadcast = Broadcast::new([16; 3], 0, 0);
+        let (_client_tx, mut stream_rx) = spawn_test_client_loop(filter, &broadcast);
+        recv_stream_info(&mut stream_rx).await; // filter is applied
+
+        // seen signature is suppressed only during the first 2 slots
+        let slot = 100;
+        broadcast.send(
+            CommitmentLevel::Processed,
+            [
+                create_message_transaction(slot, seen, None, false),
+                create_message_transaction(slot, unseen, None, false),
+                create_message_transaction(slot + 1, seen, None, false),
+                create_message_transaction(slot + 2, seen, None, false),
+            ]
+            .into_iter()
+            .enumerate()
+            .map(|(msgid, message)| (msgid as u64, message))
+            .collect(),
+        );
+        for (expected_slot, expected_signature) in [(slot, unseen), (slot + 2, seen)] {
+            let update = recv_update(&mut stream_rx).await.expect("valid update");
+            assert!(matches!(
+                update.message,
+                FilteredUpdateOneof::Transaction(msg)
+                    if msg.slot == expected_slot && msg.transaction.signature == expected_signature
+            ));
+        }
+        assert!(stream_rx.next().now_or_never().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_transactions_signatures_expire() {
+        let [sent, expired] = [Signature::new_unique(), Signature::new_unique()];
+        let request = SubscribeRequest {
+            transactions: [(
+                "tx".to_owned(),
+                SubscribeRequestFilterTransactions {
+                    signatures: vec![sent.to_string(), expired.to_string()],
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let mut limits = FilterLimits::default();
+        limits.transactions.signatures_expire_slots = 2;
+        let mut names = FilterNames::new(128, 4_096, Duration::from_secs(1));
+        let create_filter =
+            |names: &mut FilterNames| Filter::new(&request, &limits, names).expect("valid filter");
+
+        let broadcast = Broadcast::new([16; 3], 0, 0);
+        let (client_tx, mut stream_rx) =
+            spawn_test_client_loop(create_filter(&mut names), &broadcast);
+        recv_stream_info(&mut stream_rx).await; // filter is applied
+
+        let send = |messages: Vec<Message>| {
+            broadcast.send(
+                CommitmentLevel::Processed,
+                messages
+                    .into_iter()
+                    .enumerate()
+                    .map(|(msgid, message)| (msgid as u64, message))
+                    .collect(),
+            );
+        };
+        let recv_signature = |update: FilteredUpdate| match update.message {
+            FilteredUpdateOneof::Transaction(msg) => (msg.slot, msg.transaction.signature),
+            message => panic!("unexpected update: {message:?}"),
+        };
+
+        // sent signature is matched once, all signatures expire in 2 slots
+        let slot = 100;
+        send(vec![
+            create_message_slot(slot, Some(slot - 1), SlotStatus::Processed),
+            create_message_transaction(slot, sent, None, false),
+            create_message_transaction(slot + 1, sent, None, false),
+            create_message_slot(slot + 2, Some(slot + 1), SlotStatus::Processed),
+            create_message_transaction(slot + 2, expired, None, false),
+        ]);
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert_eq!(recv_signature(update), (slot, sent));
+
+        // filter update replaces the set and restarts the expiration
+        client_tx
+            .try_send(Some((None, create_filter(&mut names))))
+            .expect("client loop is alive");
+        recv_stream_info(&mut stream_rx).await;
+        send(vec![
+            create_message_slot(slot + 3, Some(slot + 2), SlotStatus::Processed),
+            create_message_transaction(slot + 3, expired, None, false),
+        ]);
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert_eq!(recv_signature(update), (slot + 3, expired));
+        assert!(stream_rx.next().now_or_never().is_none());
+    }
+
+    #[test]
+    fn test_transactions_signatures_update() {
+        let signature = Signature::new_unique();
+        let request = SubscribeRequest {
+            transactions: [
+                (
+                    "tx".to_owned(),
+                    SubscribeRequestFilterTransactions {
+                        vote: Some(false),
+                        signatures: vec![signature.to_string()],
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "votes".to_owned(),
+                    SubscribeRequestFilterTransactions {
+                        vote: Some(true),
+                        ..Default::default()
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let mut filter = Filter::new(
+            &request,
+            &FilterLimits::default(),
+            &mut FilterNames::new(128, 4_096, Duration::from_secs(1)),
+        )
+        .expect("valid filter");
+        let mut signatures = TransactionsSignatures::default();
+        let mut update = |filter: &mut Filter, is_vote: bool| {
+            let message = create_message_transaction(100, signature, None, is_vote);
+            let updates = filter.get_updates(&message, None);
+            let Message::Transaction(msg) = &message else {
+                unreachable!();
+            };
+            signatures.update_transaction(filter, msg, &updates);
+            updates
+                .iter()
+                .flat_map(|update| update.filters.iter().map(|name| name.as_ref().to_owned()))
+                .collect::<Vec<_>>()
+        };
+
+        // sent by the filter without the signature, the signature is kept
+        assert_eq!(update(&mut filter, true), ["votes"]);
+        assert_eq!(update(&mut filter, false), ["tx"]);
+        assert!(update(&mut filter, false).is_empty());
+    }
+
+    #[test]
+    fn test_transactions_ordered_timeout() {
+        let request = SubscribeRequest {
+            transactions: [(
+                "ordered".to_owned(),
+                SubscribeRequestFilterTransactions {
+                    ordered_per_slot: Some(true),
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &request,
+            &FilterLimits::default(),
+            &mut FilterNames::new(128, 4_096, Duration::from_secs(1)),
+        )
+        .expect("valid filter");
+
+        let slot = 100;
+        let mut ordered = TransactionsOrdered::new(Duration::ZERO);
+        for index in [1, 0] {
+            let message = create_message_transaction_index(slot, index);
+            let updates = ordered.push(&filter, slot, filter.get_updates(&message, None));
+            assert!(updates.is_empty());
+        }
+        assert!(ordered.next_deadline().is_some());
+
+        // block meta is not received, slot is flushed as best-effort
+        let expired = ordered.flush_expired(&filter);
+        assert_eq!(expired.len(), 1);
+        let (expired_slot, updates) = &expired[0];
+        assert_eq!(*expired_slot, slot);
+        assert_eq!(
+            updates
+                .iter()
+                .take(2)
+                .map(get_transaction_index)
+                .collect::<Vec<_>>(),
+            [0, 1]
+        );
+        assert_eq!(
+            updates[2].message,
+            FilteredUpdateOneof::transaction_slot_done(SubscribeUpdateTransactionSlotDone {
+                slot,
+                sent_count: 2,
+                best_effort: true,
+            })
+        );
+        assert!(ordered.flush_expired(&filter).is_empty());
+        assert!(ordered.next_deadline().is_none());
+
+        // late transaction of flushed slot is sent immediately
+        let message = create_message_transaction_index(slot, 2);
+        let updates = ordered.push(&filter, slot, filter.get_updates(&message, None));
+        assert_eq!(updates.len(), 1);
+    }
+
+    /// Replay recorded fixture and collect `(slot, write_version)` of confirmed accounts
+    /// until the confirmed status of `last_slot`
+    async fn replay_fixture_confirmed_accounts(path: &str, last_slot: Slot) -> Vec<(Slot, u64)> {
+        let mut geyser = GeyserLoop::spawn();
+        for message in crate::record::tests::read_log(path).await {
+            geyser.send(message);
+        }
+
+        let mut accounts = vec![];
+        loop {
+            let (commitment, messages) = geyser.recv().await;
+            if commitment != CommitmentLevel::Confirmed {
+                continue;
+            }
+            for (_msgid, message) in messages.iter() {
+                match message {
+                    Message::Account(msg) => accounts.push((msg.slot, msg.account.write_version)),
+                    Message::Slot(msg)
+                        if msg.slot == last_slot && msg.status == SlotStatus::Confirmed =>
+                    {
+                        return accounts
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_accounts_dedup() {
+        let accounts =
+            replay_fixture_confirmed_accounts(crate::record::tests::FIXTURE_ACCOUNTS_DEDUP, 100)
+                .await;
+        assert_eq!(accounts, vec![(100, 11), (100, 13)]);
+    }
+
+    #[tokio::test]
+    async fn test_replay_fork() {
+        let accounts =
+            replay_fixture_confirmed_accounts(crate::record::tests::FIXTURE_FORK, 102).await;
+        // slot 100 confirmed as parent of 102, slot 101 is on the dead fork
+        assert_eq!(accounts, vec![(100, 10), (102, 12)]);
+    }
+
+    #[test]
+    fn test_slot_block_without_counts() {
+        let Message::BlockMeta(block_meta) = create_message_block_meta(100, 0, 0) else {
+            unreachable!("block meta message");
+        };
+        let mut block = SlotBlock {
+            block_meta: Some(Arc::clone(&block_meta)),
+            ..Default::default()
+        };
+        assert!(block.try_seal().is_some());
+
+        // block meta of old interface versions doesn't have counts, block is only sent partial
+        let mut block_meta = block_meta.as_ref().clone();
+        block_meta.executed_transaction_count_unknown = true;
+        block_meta.entries_count_unknown = true;
+        let mut block = SlotBlock {
+            block_meta: Some(Arc::new(block_meta)),
+            ..Default::default()
+        };
+        assert!(block.try_seal().is_none());
+        match block.try_seal_partial() {
+            Some(Message::Block(msg)) => {
+                assert!(msg.is_partial);
+                assert!(msg.meta.executed_transaction_count_unknown);
+            }
+            message => panic!("unexpected message: {message:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_block_assembly_timeout() {
+        let timeout = Duration::from_millis(500);
+        let mut geyser = GeyserLoop::spawn_with_block_assembly_timeout(timeout);
+        let slot = 100;
+        let started = Instant::now();
+        for message in [
+            create_message_slot(slot, Some(slot - 1), SlotStatus::Processed),
+            create_message_transaction(slot, Signature::new_unique(), None, false),
+            create_message_block_meta(slot, 2, 0),
+            create_message_slot(slot, Some(slot - 1), SlotStatus::Confirmed),
+        ] {
+            geyser.send(message);
+        }
+
+        let mut blocks = vec![];
+        while blocks.len() < 2 {
+            let (commitment, messages) = geyser.recv().await;
+            for (_msgid, message) in messages.iter() {
+                if let Message::Block(block) = message {
+                    blocks.push((commitment, Arc::clone(block)));
+                }
+            }
+        }
+        assert!(
+            started.elapsed() >= timeout,
+            "block sent before the timeout"
+        );
+        blocks.sort_by_key(|(commitment, _block)| *commitment);
+
+        for (commitment, (received_commitment, block)) in
+            [CommitmentLevel::Processed, CommitmentLevel::Confirmed]
+                .into_iter()
+                .zip(blocks)
+        {
+            assert_eq!(received_commitment, commitment);
+            assert_eq!(block.meta.slot, slot);
+            assert!(block.is_partial);
+            assert_eq!(block.missing_transaction_count, 1);
+            assert_eq!(block.transactions.len(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_block_diagnostic_missing_transactions() {
+        let mut geyser = GeyserLoop::spawn_with_block_diagnostics(Duration::from_millis(100));
+        let slot = 100;
+        for message in [
+            create_message_slot(slot, Some(slot - 1), SlotStatus::Processed),
+            create_message_transaction_index(slot, 1),
+            create_message_block_meta(slot, 3, 0),
+            create_message_slot(slot, Some(slot - 1), SlotStatus::Confirmed),
+        ] {
+            geyser.send(message);
+        }
+
+        let mut diagnostics = vec![];
+        while diagnostics.len() < 2 {
+            let (commitment, messages) = geyser.recv().await;
+            for (_msgid, message) in messages.iter() {
+                if let Message::BlockDiagnostic(diagnostic) = message {
+                    diagnostics.push((commitment, Arc::clone(diagnostic)));
+                }
+            }
+        }
+        diagnostics.sort_by_key(|(commitment, _diagnostic)| *commitment);
+        assert_eq!(diagnostics[0].0, CommitmentLevel::Processed);
+        assert_eq!(diagnostics[1].0, CommitmentLevel::Confirmed);
+        assert_eq!(
+            diagnostics[0].1.diagnostic,
+            SubscribeUpdateBlockDiagnostic {
+                slot,
+                is_partial: true,
+                block_meta_missing: false,
+                executed_transaction_count: 3,
+                received_transaction_count: 1,
+                entries_count: 0,
+                received_entries_count: 0,
+                missing_transaction_indexes: vec![0, 2],
+            }
+        );
+
+        // only blocks filters with `include_diagnostics` receive the diagnostic
+        let request = SubscribeRequest {
+            blocks: [
+                (
+                    "diagnostics".to_owned(),
+                    SubscribeRequestFilterBlocks {
+                        include_diagnostics: Some(true),
+                        ..Default::default()
+                    },
+                ),
+                ("blocks".to_owned(), SubscribeRequestFilterBlocks::default()),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &request,
+            &FilterLimits::default(),
+            &mut FilterNames::new(128, 4_096, Duration::from_secs(1)),
+        )
+        .expect("valid filter");
+        let updates = filter.get_updates(
+            &Message::BlockDiagnostic(Arc::clone(&diagnostics[0].1)),
+            None,
+        );
+        assert_eq!(updates.len(), 1);
+        assert_eq!(
+            updates[0].filters.as_slice(),
+            [FilterName::new("diagnostics")]
+        );
+        assert!(matches!(
+            &updates[0].message,
+            FilteredUpdateOneof::BlockDiagnostic(msg) if msg.slot == slot
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_block_diagnostic_no_block_meta() {
+        let mut geyser = GeyserLoop::spawn_with_block_diagnostics(Duration::from_secs(3600));
+        let slot = 100;
+        for message in [
+            // messages of the first processed slot are ignored on startup
+            create_message_slot(slot - 1, Some(slot - 2), SlotStatus::Processed),
+            create_message_slot(slot, Some(slot - 1), SlotStatus::Processed),
+            create_message_transaction_index(slot, 0),
+            create_message_slot(slot, Some(slot - 1), SlotStatus::Confirmed),
+            create_message_slot(slot, Some(slot - 1), SlotStatus::Finalized),
+            // slot is removed with the status `FINALIZED_SLOTS_KEPT` slots ahead
+            create_message_slot(
+                slot + FINALIZED_SLOTS_KEPT + 1,
+                Some(slot),
+                SlotStatus::Finalized,
+            ),
+        ] {
+            geyser.send(message);
+        }
+
+        let diagnostic = loop {
+            let (_commitment, messages) = geyser.recv_commitment(CommitmentLevel::Finalized).await;
+            if let Some(diagnostic) = messages.iter().find_map(|(_msgid, message)| match message {
+                Message::BlockDiagnostic(diagnostic) => Some(Arc::clone(diagnostic)),
+                _ => None,
+            }) {
+                break diagnostic;
+            }
+        };
+        assert_eq!(
+            diagnostic.diagnostic,
+            SubscribeUpdateBlockDiagnostic {
+                slot,
+                is_partial: false,
+                block_meta_missing: true,
+                received_transaction_count: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_block_confirmed_commitment() {
+        // (meta before confirmation, last transaction after confirmation)
+        for last_transaction_confirmed in [false, true] {
+            let mut geyser = GeyserLoop::spawn();
+            let slot = 100;
+            let mut messages = vec![
+                create_message_slot(slot, Some(slot - 1), SlotStatus::Processed),
+                create_message_transaction(slot, Signature::new_unique(), None, false),
+                create_message_block_meta(slot, 2, 0),
+                create_message_slot(slot, Some(slot - 1), SlotStatus::Confirmed),
+            ];
+            let last_transaction =
+                create_message_transaction(slot, Signature::new_unique(), None, false);
+            if last_transaction_confirmed {
+                messages.push(last_transaction);
+            } else {
+                messages.insert(2, last_transaction);
+            }
+            for message in messages {
+                geyser.send(message);
+            }
+
+            // block is delivered without waiting for the finalized status
+            let mut confirmed = vec![];
+            while !confirmed
+                .iter()
+                .any(|message| matches!(message, Message::Block(_)))
+            {
+                let (_commitment, messages) =
+                    geyser.recv_commitment(CommitmentLevel::Confirmed).await;
+                confirmed.extend(messages.iter().map(|(_msgid, message)| message.clone()));
+            }
+            let status_index = confirmed
+                .iter()
+                .position(|message| matches!(message, Message::Slot(msg) if msg.status == SlotStatus::Confirmed))
+                .expect("confirmed status");
+            let block_index = confirmed
+                .iter()
+                .position(|message| matches!(message, Message::Block(_)))
+                .expect("block");
+            assert_eq!(block_index > status_index, last_transaction_confirmed);
+            let Message::Block(block) = &confirmed[block_index] else {
+                unreachable!();
+            };
+            assert!(!block.is_partial);
+            assert_eq!(block.transactions.len(), 2);
+
+            // and once for the finalized commitment
+            geyser.send(create_message_slot(
+                slot,
+                Some(slot - 1),
+                SlotStatus::Finalized,
+            ));
+            let mut finalized = vec![];
+            while !matches!(finalized.last(), Some(Message::Slot(msg)) if msg.status == SlotStatus::Finalized)
+            {
+                let (_commitment, messages) =
+                    geyser.recv_commitment(CommitmentLevel::Finalized).await;
+                finalized.extend(messages.iter().map(|(_msgid, message)| message.clone()));
+            }
+            assert_eq!(
+                finalized
+                    .iter()
+                    .filter(|message| matches!(message, Message::Block(_)))
+                    .count(),
+                1
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_block_assembly_blocks_subscribers() {
+        let broadcast = Broadcast::new([1024; 3], 0, 0);
+        let mut geyser = GeyserLoop::spawn_with_broadcast(broadcast.clone());
+        broadcast.blocks_subscribers.fetch_sub(1, Ordering::Relaxed);
+        assert!(!broadcast.has_blocks_subscribers());
+
+        // slot first seen without blocks subscribers is not assembled
+        geyser.send(create_message_slot(100, Some(99), SlotStatus::Processed));
+        loop {
+            let (_commitment, messages) = geyser.recv_commitment(CommitmentLevel::Processed).await;
+            if messages
+                .iter()
+                .any(|(_msgid, message)| matches!(message, Message::Slot(msg) if msg.slot == 100))
+            {
+                break;
+            }
+        }
+
+        let filter_with_blocks = |blocks: bool| {
+            let mut request = SubscribeRequest::default();
+            if blocks {
+                request
+                    .blocks
+                    .insert("blocks".to_owned(), SubscribeRequestFilterBlocks::default());
+            }
+            Filter::new(
+                &request,
+                &FilterLimits::default(),
+                &mut FilterNames::new(128, 4_096, Duration::from_secs(1)),
+            )
+            .expect("valid filter")
+        };
+        let mut subscriber = broadcast.blocks_subscriber();
+        subscriber.update(&filter_with_blocks(true));
+        subscriber.update(&filter_with_blocks(true));
+        assert_eq!(broadcast.blocks_subscribers.load(Ordering::Relaxed), 1);
+
+        for message in [
+            create_message_block_meta(100, 0, 0),
+            create_message_slot(101, Some(100), SlotStatus::Processed),
+            create_message_block_meta(101, 0, 0),
+            create_message_slot(100, Some(99), SlotStatus::Confirmed),
+            create_message_slot(101, Some(100), SlotStatus::Confirmed),
+        ] {
+            geyser.send(message);
+        }
+
+        let mut blocks = vec![];
+        let mut confirmed = false;
+        while !confirmed || blocks.is_empty() {
+            let (_commitment, messages) = geyser.recv_commitment(CommitmentLevel::Confirmed).await;
+            for (_msgid, message) in messages.iter() {
+                match message {
+                    Message::Block(block) => blocks.push(block.meta.slot),
+                    Message::Slot(msg) if msg.slot == 101 => confirmed = true,
+                    _ => {}
+                }
+            }
+        }
+        assert_eq!(blocks, vec![101]);
+
+        subscriber.update(&filter_with_blocks(false));
+        assert!(!broadcast.has_blocks_subscribers());
+        subscriber.update(&filter_with_blocks(true));
+        drop(subscriber);
+        assert!(!broadcast.has_blocks_subscribers());
+    }
+
+    #[tokio::test]
+    async fn test_slot_status_duplicates() {
+        let duplicates = || {
+            metrics::SLOT_STATUS_DUPLICATES
+                .with_label_values(&[SlotStatus::Confirmed.as_str()])
+                .get()
+        };
+        let duplicates_before = duplicates();
+
+        let mut geyser = GeyserLoop::spawn();
+        for (slot, status) in [
+            (100, SlotStatus::Processed),
+            (100, SlotStatus::Confirmed),
+            (100, SlotStatus::Confirmed), // duplicate
+            (100, SlotStatus::Finalized),
+            (100, SlotStatus::Confirmed), // regression, e.g. after plugin reload
+            (100, SlotStatus::Processed),
+            (101, SlotStatus::Processed),
+            (102, SlotStatus::Processed),
+            (102, SlotStatus::Confirmed), // status of 101 is sent by geyser_loop
+            (101, SlotStatus::Confirmed), // late status
+            (103, SlotStatus::Processed),
+        ] {
+            geyser.send(create_message_slot(slot, slot.checked_sub(1), status));
+        }
+
+        let mut received = HashMap::<CommitmentLevel, Vec<(Slot, SlotStatus)>>::new();
+        while !received
+            .get(&CommitmentLevel::Finalized)
+            .is_some_and(|statuses| statuses.contains(&(103, SlotStatus::Processed)))
+        {
+            let (commitment, messages) = geyser.recv().await;
+            for (_msgid, message) in messages.iter() {
+                if let Message::Slot(msg) = message {
+                    received
+                        .entry(commitment)
+                        .or_default()
+                        .push((msg.slot, msg.status));
+                }
+            }
+        }
+
+        for commitment in [
+            CommitmentLevel::Processed,
+            CommitmentLevel::Confirmed,
+            CommitmentLevel::Finalized,
+        ] {
+            assert_eq!(
+                received.get(&commitment),
+                Some(&vec![
+                    (100, SlotStatus::Processed),
+                    (100, SlotStatus::Confirmed),
+                    (100, SlotStatus::Finalized),
+                    (101, SlotStatus::Processed),
+                    (102, SlotStatus::Processed),
+                    (101, SlotStatus::Confirmed),
+                    (102, SlotStatus::Confirmed),
+                    (103, SlotStatus::Processed),
+                ]),
+                "{commitment:?}"
+            );
+        }
+        assert!(duplicates() - duplicates_before >= 3);
+    }
+
+    #[test]
+    fn test_block_meta_storage_late_status() {
+        let mut storage = BlockMetaStorageInner::default();
+        for (slot, status) in [
+            (100, SlotStatus::Processed),
+            (101, SlotStatus::Processed),
+            (100, SlotStatus::Processed),
+            (101, SlotStatus::Confirmed),
+            (100, SlotStatus::Confirmed),
+            (100, SlotStatus::Finalized),
+            (99, SlotStatus::Finalized),
+        ] {
+            storage.update(create_message_slot(slot, slot.checked_sub(1), status));
+        }
+        assert_eq!(storage.processed, Some(101));
+        assert_eq!(storage.confirmed, Some(101));
+        assert_eq!(storage.finalized, Some(100));
+    }
+
+    #[test]
+    fn test_block_meta_storage_slot_ancestry() {
+        use SlotStatusProto::{SlotConfirmed, SlotFinalized, SlotProcessed};
+
+        let mut storage = BlockMetaStorageInner::default();
+        // fork: 100 -> 101 -> 102 and 100 -> 103 -> 104
+        for (slot, parent, status) in [
+            (100, Some(99), SlotStatus::Processed),
+            (101, Some(100), SlotStatus::Processed),
+            (102, Some(101), SlotStatus::Processed),
+            (103, Some(100), SlotStatus::Processed),
+            (104, Some(103), SlotStatus::Processed),
+            (104, None, SlotStatus::Confirmed),
+        ] {
+            storage.update(create_message_slot(slot, parent, status));
+        }
+        let ancestry = |storage: &BlockMetaStorageInner, slot, limit| {
+            storage.get_slot_ancestry(slot, limit).map(|response| {
+                response
+                    .slots
+                    .into_iter()
+                    .map(|ancestor| {
+                        let status = SlotStatusProto::try_from(ancestor.status).expect("valid");
+                        let implied_status =
+                            SlotStatusProto::try_from(ancestor.implied_status).expect("valid");
+                        (ancestor.slot, ancestor.parent, status, implied_status)
+                    })
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        assert_eq!(
+            ancestry(&storage, 102, 0).expect("known slot"),
+            vec![
+                (102, Some(101), SlotProcessed, SlotProcessed),
+                (101, Some(100), SlotProcessed, SlotProcessed),
+                (100, Some(99), SlotProcessed, SlotProcessed),
+            ]
+        );
+        // parent from the processed status is kept, confirmed is implied for ancestors
+        assert_eq!(
+            ancestry(&storage, 104, 0).expect("known slot"),
+            vec![
+                (104, Some(103), SlotConfirmed, SlotConfirmed),
+                (103, Some(100), SlotProcessed, SlotConfirmed),
+                (100, Some(99), SlotProcessed, SlotConfirmed),
+            ]
+        );
+        assert_eq!(
+            ancestry(&storage, 104, 2).expect("known slot"),
+            vec![
+                (104, Some(103), SlotConfirmed, SlotConfirmed),
+                (103, Some(100), SlotProcessed, SlotConfirmed),
+            ]
+        );
+        assert_eq!(
+            ancestry(&storage, 105, 0).map_err(|status| status.code()),
+            Err(tonic::Code::NotFound)
+        );
+
+        // statuses are never downgraded, slots before the retention window are removed
+        storage.update(create_message_slot(104, Some(103), SlotStatus::Processed));
+        assert_eq!(storage.slots[&104].status, SlotStatus::Confirmed);
+        let finalized = 100 + MAX_RECENT_BLOCKHASHES as u64 + 33;
+        storage.update_batch(
+            [create_message_slot(
+                finalized,
+                Some(104),
+                SlotStatus::Finalized,
+            )],
+            true,
+        );
+        assert_eq!(
+            ancestry(&storage, finalized, 0).expect("known slot"),
+            vec![
+                (finalized, Some(104), SlotFinalized, SlotFinalized),
+                (104, Some(103), SlotConfirmed, SlotFinalized),
+                (103, Some(100), SlotProcessed, SlotFinalized),
+            ]
+        );
+        assert!(ancestry(&storage, 100, 0).is_err());
+    }
+
+    #[test]
+    fn test_block_meta_storage_batch_cleanup() {
+        let mut storage = BlockMetaStorageInner::default();
+        let messages = (1..=10).flat_map(|slot| {
+            [
+                create_message_block_meta(slot, 0, 0),
+                create_message_slot(slot, Some(slot - 1), SlotStatus::Finalized),
+            ]
+        });
+        storage.update_batch(messages, false);
+        assert_eq!(storage.blocks.len(), 10);
+
+        // cleanup for the newest finalized slot only
+        storage.update_batch([], true);
+        assert_eq!(storage.cleanup_slot, Some(10));
+        assert_eq!(
+            storage.blocks.keys().copied().collect::<BTreeSet<_>>(),
+            (7..=10).collect()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_block_meta_storage_commitment_limits() {
+        let (storage, _tx) = BlockMetaStorage::new(100, 0);
+        storage.inner.write().await.update_batch(
+            [
+                create_message_block_meta(1, 0, 0),
+                create_message_block_meta(2, 0, 0),
+                create_message_slot(1, Some(0), SlotStatus::Processed),
+                create_message_slot(2, Some(1), SlotStatus::Processed),
+                create_message_slot(1, Some(0), SlotStatus::Confirmed),
+            ],
+            true,
+        );
+        let storage = &storage;
+        let get_slot = |commitment: Option<CommitmentLevelProto>, limits: FilterLimits| async move {
+            storage
+                .get_block(
+                    |block| Some(block.slot),
+                    commitment.map(|commitment| commitment as i32),
+                    &limits,
+                )
+                .await
+                .map(|response| response.into_inner())
+        };
+
+        // processed by default
+        assert_eq!(get_slot(None, FilterLimits::default()).await.ok(), Some(2));
+
+        let mut limits = FilterLimits::default();
+        limits.default_commitment = CommitmentLevel::Confirmed;
+        limits.allowed_commitments = Some(
+            [CommitmentLevel::Confirmed, CommitmentLevel::Finalized]
+                .into_iter()
+                .collect(),
+        );
+        assert_eq!(get_slot(None, limits.clone()).await.ok(), Some(1));
+        let status = get_slot(Some(CommitmentLevelProto::Processed), limits)
+            .await
+            .expect_err("processed is not allowed");
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert!(
+            status.message().contains("supported: confirmed, finalized"),
+            "{status:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_block_meta_storage_backlog_readers() {
+        let storage = RwLock::new(BlockMetaStorageInner::default());
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        // backlog of messages after restart
+        let last_slot = 10_000;
+        let mut total = 0;
+        for slot in 1..=last_slot {
+            tx.send(create_message_block_meta(slot, 0, 0))
+                .expect("storage is alive");
+            tx.send(create_message_slot(
+                slot,
+                Some(slot - 1),
+                SlotStatus::Processed,
+            ))
+            .expect("storage is alive");
+            total += 2;
+            if let Some(finalized) = slot.checked_sub(32) {
+                tx.send(create_message_slot(
+                    finalized,
+                    finalized.checked_sub(1),
+                    SlotStatus::Finalized,
+                ))
+                .expect("storage is alive");
+                total += 1;
+            }
+        }
+        drop(tx);
+
+        // every write lock hold applies at most one batch, so readers wait for one batch at most
+        let mut messages = Vec::new();
+        let mut next = None;
+        let mut holds = 0;
+        while let Some(drained) =
+            BlockMetaStorage::recv_batch(&mut rx, &mut messages, &mut next).await
+        {
+            assert!(messages.len() <= BLOCK_META_STORAGE_BATCH_MAX);
+            storage
+                .write()
+                .await
+                .update_batch(messages.drain(..), drained);
+            holds += 1;
+
+            let inner = storage
+                .try_read()
+                .expect("lock is released after the batch");
+            if drained {
+                // outdated blocks are removed once the backlog is drained
+                assert_eq!(inner.cleanup_slot, Some(last_slot - 32));
+                assert!(inner.blocks.len() <= 32 + 4);
+            } else {
+                assert_eq!(inner.cleanup_slot, None, "cleanup during the backlog");
+            }
+        }
+        assert_eq!(holds, total.div_ceil(BLOCK_META_STORAGE_BATCH_MAX));
+
+        let inner = storage.read().await;
+        assert_eq!(inner.finalized, Some(last_slot - 32));
+        assert!(inner.blocks.contains_key(&(last_slot - 32)));
+    }
+
+    #[test]
+    fn test_block_meta_storage_blockhash_warmup() {
+        let mut storage = BlockMetaStorageInner::default();
+        let is_unavailable = |storage: &BlockMetaStorageInner| {
+            storage
+                .is_blockhash_valid("unknown", CommitmentLevel::Processed, 3)
+                .is_err_and(|status| status.code() == tonic::Code::Unavailable)
+        };
+        assert!(is_unavailable(&storage));
+
+        for slot in 1..=3 {
+            storage.update(create_message_block_meta(slot, 0, 0));
+            storage.update(create_message_slot(
+                slot,
+                Some(slot - 1),
+                SlotStatus::Processed,
+            ));
+        }
+        // no finalized slot yet
+        assert!(is_unavailable(&storage));
+
+        storage.update(create_message_slot(1, Some(0), SlotStatus::Finalized));
+        assert!(!is_unavailable(&storage));
+    }
+
+    #[test]
+    fn test_block_meta_storage_blockhash_short_chain() {
+        // test validator, chain is shorter than the warm-up requirement
+        let mut storage = BlockMetaStorageInner::default();
+        for slot in 1..=2 {
+            storage.update(create_message_block_meta(slot, 0, 0));
+            storage.update(create_message_slot(
+                slot,
+                Some(slot - 1),
+                SlotStatus::Processed,
+            ));
+        }
+        storage.update(create_message_slot(2, Some(1), SlotStatus::Finalized));
+        assert!(storage
+            .is_blockhash_valid("unknown", CommitmentLevel::Processed, 332)
+            .is_err());
+
+        storage.update(create_message_block_meta(3, 0, 0));
+        storage.update(create_message_slot(3, Some(2), SlotStatus::Processed));
+        let response = storage
+            .is_blockhash_valid("unknown", CommitmentLevel::Processed, 332)
+            .expect("warmed up");
+        assert_eq!(response.slot, 3);
+    }
+
+    #[test]
+    fn test_block_meta_storage_blockhash_states() {
+        let mut storage = BlockMetaStorageInner::default();
+        let mut blockhashes = vec![];
+        for slot in 1..=3 {
+            let message = create_message_block_meta(slot, 0, 0);
+            if let Message::BlockMeta(block_meta) = &message {
+                blockhashes.push(block_meta.blockhash.clone());
+            }
+            storage.update(message);
+            storage.update(create_message_slot(
+                slot,
+                Some(slot - 1),
+                SlotStatus::Processed,
+            ));
+        }
+        storage.update(create_message_slot(1, Some(0), SlotStatus::Confirmed));
+        storage.update(create_message_slot(1, Some(0), SlotStatus::Finalized));
+
+        // valid
+        let response = storage
+            .is_blockhash_valid(&blockhashes[1], CommitmentLevel::Processed, 3)
+            .expect("warmed up");
+        assert_eq!(
+            (response.slot, response.valid, response.known),
+            (3, true, true)
+        );
+
+        // seen, but not at the requested commitment
+        let response = storage
+            .is_blockhash_valid(&blockhashes[1], CommitmentLevel::Finalized, 3)
+            .expect("warmed up");
+        assert_eq!(
+            (response.slot, response.valid, response.known),
+            (1, false, true)
+        );
+
+        // never seen
+        let response = storage
+            .is_blockhash_valid(
+                &Hash::new_unique().to_string(),
+                CommitmentLevel::Processed,
+                3,
+            )
+            .expect("warmed up");
+        assert_eq!((response.valid, response.known), (false, false));
+    }
+
+    fn add_block_meta(
+        storage: &mut BlockMetaStorageInner,
+        slot: Slot,
+        parent: Slot,
+        block_height: u64,
+    ) -> String {
+        let blockhash = Hash::new_unique().to_string();
+        storage.update(Message::BlockMeta(Arc::new(MessageBlockMeta {
+            block_meta: SubscribeUpdateBlockMeta {
+                slot,
+                blockhash: blockhash.clone(),
+                parent_slot: parent,
+                block_height: Some(BlockHeight { block_height }),
+                ..Default::default()
+            },
+            created_at: Timestamp::from(SystemTime::now()),
+        })));
+        storage.update(create_message_slot(
+            slot,
+            Some(parent),
+            SlotStatus::Processed,
+        ));
+        blockhash
+    }
+
+    #[test]
+    fn test_block_meta_storage_blockhash_age() {
+        let mut storage = BlockMetaStorageInner::default();
+        let blockhashes = (1..=200)
+            .map(|slot| add_block_meta(&mut storage, slot, slot - 1, slot))
+            .collect::<Vec<_>>();
+        storage.update(create_message_slot(190, Some(189), SlotStatus::Confirmed));
+        storage.update(create_message_slot(1, Some(0), SlotStatus::Finalized));
+        let is_valid = |storage: &BlockMetaStorageInner, height: u64, commitment| {
+            let response = storage
+                .is_blockhash_valid(&blockhashes[height as usize - 1], commitment, 0)
+                .expect("warmed up");
+            (response.valid, response.known)
+        };
+
+        // age is counted in blocks from the block height of the commitment slot
+        assert_eq!(
+            is_valid(&storage, 200 - 149, CommitmentLevel::Processed),
+            (true, true)
+        );
+        assert_eq!(
+            is_valid(&storage, 200 - 150, CommitmentLevel::Processed),
+            (false, true)
+        );
+        assert_eq!(
+            is_valid(&storage, 190 - 149, CommitmentLevel::Confirmed),
+            (true, true)
+        );
+        assert_eq!(
+            is_valid(&storage, 190 - 150, CommitmentLevel::Confirmed),
+            (false, true)
+        );
+        assert_eq!(
+            is_valid(&storage, 190 - 149, CommitmentLevel::Processed),
+            (false, true)
+        );
+        // blocks after the commitment slot
+        assert_eq!(
+            is_valid(&storage, 200, CommitmentLevel::Confirmed),
+            (false, true)
+        );
+
+        // blockhashes expired at the finalized height are removed
+        storage.update_batch(
+            [create_message_slot(200, Some(199), SlotStatus::Finalized)],
+            true,
+        );
+        assert_eq!(
+            is_valid(&storage, 200 - 149, CommitmentLevel::Finalized),
+            (true, true)
+        );
+        assert_eq!(
+            is_valid(&storage, 200 - 150, CommitmentLevel::Finalized),
+            (false, false)
+        );
+    }
+
+    #[test]
+    fn test_block_meta_storage_last_valid_block_height() {
+        let mut storage = BlockMetaStorageInner::default();
+        let blockhash = add_block_meta(&mut storage, 1, 0, 1);
+        storage.update(create_message_slot(1, Some(0), SlotStatus::Finalized));
+        // returned by `GetLatestBlockhash` for the block, same as RPC
+        let last_valid_block_height = BlockMetaStorageInner::last_valid_block_height(1);
+        assert_eq!(last_valid_block_height, 1 + MAX_PROCESSING_AGE as u64);
+        let is_valid = |storage: &BlockMetaStorageInner| {
+            storage
+                .is_blockhash_valid(&blockhash, CommitmentLevel::Processed, 0)
+                .expect("warmed up")
+                .valid
+        };
+
+        // by the age check of the runtime the blockhash expires one block before the height
+        // returned by RPC
+        for slot in 2..last_valid_block_height {
+            add_block_meta(&mut storage, slot, slot - 1, slot);
+        }
+        assert!(is_valid(&storage));
+        let slot = last_valid_block_height;
+        add_block_meta(&mut storage, slot, slot - 1, slot);
+        assert!(!is_valid(&storage));
+    }
+
+    #[test]
+    fn test_block_meta_storage_blockhash_skipped_slot() {
+        let mut storage = BlockMetaStorageInner::default();
+        let blockhash_10 = add_block_meta(&mut storage, 10, 9, 10);
+        storage.update(create_message_slot(10, Some(9), SlotStatus::Finalized));
+        // slot 11 is processed on the fork abandoned for slot 12, both have height 11
+        let blockhash_11 = add_block_meta(&mut storage, 11, 10, 11);
+        let blockhash_12 = add_block_meta(&mut storage, 12, 10, 11);
+        storage.update(create_message_slot(12, Some(10), SlotStatus::Confirmed));
+
+        for commitment in [CommitmentLevel::Processed, CommitmentLevel::Confirmed] {
+            for (blockhash, valid) in [
+                (&blockhash_10, true),
+                (&blockhash_11, false),
+                (&blockhash_12, true),
+            ] {
+                let response = storage
+                    .is_blockhash_valid(blockhash, commitment, 0)
+                    .expect("warmed up");
+                assert_eq!(
+                    (response.valid, response.known),
+                    (valid, true),
+                    "{commitment:?}"
+                );
+            }
+        }
+
+        // chain is not known, the status of the slot is used
+        storage.slots.remove(&12);
+        let response = storage
+            .is_blockhash_valid(&blockhash_11, CommitmentLevel::Confirmed, 0)
+            .expect("warmed up");
+        assert!(!response.valid);
+        let response = storage
+            .is_blockhash_valid(&blockhash_10, CommitmentLevel::Confirmed, 0)
+            .expect("warmed up");
+        assert!(response.valid);
+    }
+
+    #[tokio::test]
+    async fn test_slot_statuses_on_every_commitment() {
+        let mut geyser = GeyserLoop::spawn();
+        let slot = 100;
+        for status in [
+            SlotStatus::CreatedBank,
+            SlotStatus::Processed,
+            SlotStatus::Confirmed,
+            SlotStatus::Finalized,
+        ] {
+            geyser.send(create_message_slot(slot, Some(slot - 1), status));
+        }
+
+        let mut received = HashMap::<CommitmentLevel, Vec<SlotStatus>>::new();
+        while received
+            .values()
+            .filter(|statuses| statuses.contains(&SlotStatus::Finalized))
+            .count()
+            < 3
+        {
+            let (commitment, messages) = geyser.recv().await;
+            let request = SubscribeRequest {
+                slots: [(
+                    "slots".to_owned(),
+                    SubscribeRequestFilterSlots {
+                        statuses: vec![
+                            SlotStatusProto::SlotConfirmed as i32,
+                            SlotStatusProto::SlotFinalized as i32,
+                        ],
+                        ..Default::default()
+                    },
+                )]
+                .into_iter()
+                .collect(),
+                commitment: Some(CommitmentLevelProto::from(commitment) as i32),
+                ..Default::default()
+            };
+            let filter = Filter::new(
+                &request,
+                &FilterLimits::default(),
+                &mut FilterNames::new(128, 4_096, Duration::from_secs(1)),
+            )
+            .expect("valid filter");
+            for (_msgid, message) in messages.iter() {
+                for update in filter.get_updates(message, Some(commitment)) {
+                    if let FilteredUpdateOneof::Slot(msg) = update.message {
+                        received.entry(commitment).or_default().push(msg.status);
+                    }
+                }
+            }
+        }
+
+        for commitment in [
+            CommitmentLevel::Processed,
+            CommitmentLevel::Confirmed,
+            CommitmentLevel::Finalized,
+        ] {
+            assert_eq!(
+                received.get(&commitment),
+                Some(&vec![SlotStatus::Confirmed, SlotStatus::Finalized])
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_accounts_snapshot() {
+        let tracked = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let owned = Pubkey::new_unique();
+        let tracked_accounts = Arc::new(TrackedAccounts::new(
+            ConfigGrpcTrackedAccounts {
+                pubkeys: [tracked].into_iter().collect(),
+                owners: [owner].into_iter().collect(),
+                max_accounts: 2,
+                max_data_size: 16,
+                max_request_pubkeys: 4,
+            },
+            1,
+        ));
+        let mut geyser = GeyserLoop::spawn_with_tracked_accounts(Arc::clone(&tracked_accounts));
+
+        let create_account = |slot, pubkey, owner, write_version, data_size| {
+            Message::Account(MessageAccount {
+                account: Arc::new(MessageAccountInfo {
+                    pubkey,
+                    lamports: 42,
+                    owner,
+                    executable: false,
+                    rent_epoch: 0,
+                    data: vec![0; data_size].into(),
+                    write_version,
+                    txn_signature: None,
+                    base58: Default::default(),
+                }),
+                slot,
+                is_startup: false,
+                created_at: Timestamp::from(SystemTime::now()),
+                confirmed_at_slot: None,
+                finalized_at_slot: None,
+            })
+        };
+        let slot = 100;
+        geyser.send(create_message_slot(
+            slot,
+            Some(slot - 1),
+            SlotStatus::Processed,
+        ));
+        for _ in 0..3 {
+            geyser.recv().await;
+        }
+        for message in [
+            create_account(slot, owned, owner, 3, 8),
+            create_account(slot, Pubkey::new_unique(), owner, 4, 8), // max_accounts reached
+            create_account(slot, tracked, Pubkey::new_unique(), 2, 8), // place is reserved
+            create_account(slot, tracked, Pubkey::new_unique(), 1, 8), // older write
+            create_account(slot, Pubkey::new_unique(), Pubkey::new_unique(), 5, 8),
+        ] {
+            geyser.send(message);
+            geyser.recv().await;
+        }
+        geyser.send(create_message_slot(
+            slot,
+            Some(slot - 1),
+            SlotStatus::Confirmed,
+        ));
+        for _ in 0..3 {
+            geyser.recv().await;
+        }
+        geyser.send(create_account(slot + 1, owned, owner, 6, 32)); // data is too large
+        geyser.recv().await;
+
+        let mut service = create_service(Arc::clone(&geyser.commitment_slots));
+        service.tracked_accounts = Some(tracked_accounts);
+        let untracked = Pubkey::new_unique();
+        let get_snapshot = |commitment: CommitmentLevelProto| GetAccountsSnapshotRequest {
+            pubkeys: vec![
+                tracked.to_string(),
+                owned.to_string(),
+                untracked.to_string(),
+                "invalid".to_owned(),
+            ],
+            commitment: Some(commitment as i32),
+        };
+        let summary = |response: GetAccountsSnapshotResponse| {
+            response
+                .accounts
+                .into_iter()
+                .map(|snapshot| {
+                    (
+                        snapshot.status(),
+                        snapshot.account.map(|account| account.write_version),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let response = service
+            .get_accounts_snapshot(Request::new(get_snapshot(CommitmentLevelProto::Processed)))
+            .await
+            .expect("valid response")
+            .into_inner();
+        assert_eq!(response.accounts[0].slot, slot);
+        assert_eq!(
+            summary(response),
+            vec![
+                (AccountSnapshotStatus::AccountSnapshotFound, Some(2)),
+                (AccountSnapshotStatus::AccountSnapshotNotTracked, None),
+                (AccountSnapshotStatus::AccountSnapshotNotTracked, None),
+                (AccountSnapshotStatus::AccountSnapshotInvalidPubkey, None),
+            ]
+        );
+
+        let response = service
+            .get_accounts_snapshot(Request::new(get_snapshot(CommitmentLevelProto::Confirmed)))
+            .await
+            .expect("valid response")
+            .into_inner();
+        assert_eq!(
+            summary(response),
+            vec![
+                (AccountSnapshotStatus::AccountSnapshotFound, Some(2)),
+                (AccountSnapshotStatus::AccountSnapshotFound, Some(3)),
+                (AccountSnapshotStatus::AccountSnapshotNotTracked, None),
+                (AccountSnapshotStatus::AccountSnapshotInvalidPubkey, None),
+            ]
+        );
+
+        let response = service
+            .get_accounts_snapshot(Request::new(get_snapshot(CommitmentLevelProto::Finalized)))
+            .await
+            .expect("valid response")
+            .into_inner();
+        assert_eq!(
+            summary(response)[..2],
+            [
+                (AccountSnapshotStatus::AccountSnapshotNotAvailable, None),
+                (AccountSnapshotStatus::AccountSnapshotNotTracked, None),
+            ]
+        );
+
+        let mut request = get_snapshot(CommitmentLevelProto::Processed);
+        request.pubkeys.push(tracked.to_string());
+        let status = service
+            .get_accounts_snapshot(Request::new(request))
+            .await
+            .expect_err("too many pubkeys");
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_filter_updates_flood() {
+        let config: ConfigGrpc = serde_json::from_value(serde_json::json!({
+            "address": "127.0.0.1:0",
+            "filter_updates_rate_limit": 5,
+        }))
+        .expect("valid config");
+        let (_snapshot_tx, messages_tx, shutdown, BoundAddresses { grpc: address, .. }) =
+            GrpcService::create(ConfigTokio::default(), config, None, false)
+                .await
+                .expect("service created");
+
+        let mut client = GeyserClient::connect(format!("http://{address}"))
+            .await
+            .expect("connected");
+        let request = SubscribeRequest {
+            slots: [("slots".to_owned(), SubscribeRequestFilterSlots::default())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let (request_tx, request_rx) = mpsc::channel(256);
+        request_tx.send(request.clone()).await.expect("alive");
+        let mut stream = client
+            .subscribe(ReceiverStream::new(request_rx))
+            .await
+            .expect("subscribed")
+            .into_inner();
+        // identical updates are not counted by the rate limit, so every update is different
+        let started_at = Instant::now();
+        for i in 0..100 {
+            let mut request = request.clone();
+            request.slots = [(format!("slots{i}"), SubscribeRequestFilterSlots::default())]
+                .into_iter()
+                .collect();
+            request_tx.send(request).await.expect("alive");
+        }
+
+        // every request is answered once read: accepted with the stream info, rejected with
+        // the warning, so neither requests nor filters pile up on the server
+        let (mut accepted, mut rejected) = (0, 0);
+        while accepted + rejected < 101 {
+            let update = tokio::time::timeout(Duration::from_secs(5), stream.message())
+                .await
+                .expect("timeout")
+                .expect("stream is not failed")
+                .expect("stream is alive");
+            if matches!(update.update_oneof, Some(UpdateOneof::StreamInfo(_))) {
+                accepted += 1;
+            }
+            rejected += update
+                .warnings
+                .iter()
+                .filter(|warning| warning.starts_with("RESOURCE_EXHAUSTED"))
+                .count();
+        }
+        // no more filters are built than the rate limit allows
+        let windows = started_at.elapsed().as_secs() as usize + 1;
+        assert!(accepted <= 5 * windows, "accepted: {accepted}");
+        assert!(rejected >= 101 - 5 * windows, "rejected: {rejected}");
+
+        // the stream and the last accepted filter are still active
+        let mut slot = 1_000;
+        loop {
+            metrics::message_queue_size_inc();
+            messages_tx
+                .send(create_message_slot(
+                    slot,
+                    Some(slot - 1),
+                    SlotStatus::Processed,
+                ))
+                .expect("geyser loop is alive");
+            slot += 1;
+
+            let update = tokio::time::timeout(Duration::from_secs(5), stream.message())
+                .await
+                .expect("timeout")
+                .expect("stream is not failed")
+                .expect("stream is alive");
+            if matches!(update.update_oneof, Some(UpdateOneof::Slot(_))) {
+                break;
+            }
+        }
+
+        shutdown.notify_one();
+    }
+
+    #[tokio::test]
+    async fn test_filter_updates_identical() {
+        let identical_before = metrics::FILTER_UPDATES_IDENTICAL.get();
+        let config: ConfigGrpc = serde_json::from_value(serde_json::json!({
+            "address": "127.0.0.1:0",
+        }))
+        .expect("valid config");
+        let (_snapshot_tx, _messages_tx, shutdown, BoundAddresses { grpc: address, .. }) =
+            GrpcService::create(ConfigTokio::default(), config, None, false)
+                .await
+                .expect("service created");
+
+        let mut client = GeyserClient::connect(format!("http://{address}"))
+            .await
+            .expect("connected");
+        let create_request = |name: &str| SubscribeRequest {
+            slots: [(name.to_owned(), SubscribeRequestFilterSlots::default())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let (request_tx, request_rx) = mpsc::channel(16);
+        request_tx.send(create_request("a")).await.expect("alive");
+        let mut stream = client
+            .subscribe(ReceiverStream::new(request_rx))
+            .await
+            .expect("subscribed")
+            .into_inner();
+        // keepalive re-sends of the same filter are acked with the stream info, the filter is not re-applied
+        for _ in 0..3 {
+            request_tx.send(create_request("a")).await.expect("alive");
+        }
+        request_tx.send(create_request("b")).await.expect("alive");
+
+        let mut applied = vec![];
+        loop {
+            let update = tokio::time::timeout(Duration::from_secs(5), stream.message())
+                .await
+                .expect("timeout")
+                .expect("stream is not failed")
+                .expect("stream is alive");
+            if let Some(UpdateOneof::StreamInfo(msg)) = update.update_oneof {
+                if msg.slots == vec!["b".to_owned()] {
+                    break;
+                }
+                applied.push(msg.slots);
+            }
+        }
+        assert_eq!(applied, vec![vec!["a".to_owned()]; 4]);
+        // acked without building the filter again
+        assert!(metrics::FILTER_UPDATES_IDENTICAL.get() - identical_before >= 3);
+
+        shutdown.notify_one();
+    }
+
+    #[test]
+    fn test_request_has_signatures() {
+        assert!(!request_has_signatures(&SubscribeRequest::default()));
+
+        let signature = Signature::new_unique().to_string();
+        for filter in [
+            SubscribeRequestFilterTransactions {
+                signature: Some(signature.clone()),
+                ..Default::default()
+            },
+            SubscribeRequestFilterTransactions {
+                signatures: vec![signature.clone()],
+                ..Default::default()
+            },
+        ] {
+            let transactions = [("txs".to_owned(), filter)]
+                .into_iter()
+                .collect::<HashMap<_, _>>();
+            assert!(request_has_signatures(&SubscribeRequest {
+                transactions: transactions.clone(),
+                ..Default::default()
+            }));
+            assert!(request_has_signatures(&SubscribeRequest {
+                transactions_status: transactions,
+                ..Default::default()
+            }));
+        }
+        assert!(request_has_signatures(&SubscribeRequest {
+            accounts: [(
+                "accounts".to_owned(),
+                SubscribeRequestFilterAccounts {
+                    txn_signature: vec![signature],
+                    ..Default::default()
+                }
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_filter_preset_subscribe() {
+        let config: ConfigGrpc = serde_json::from_value(serde_json::json!({
+            "address": "127.0.0.1:0",
+            "filter_presets": {
+                "vaults": {
+                    "variables": {
+                        "accounts": { "type": "pubkeys", "max": 4 },
+                    },
+                    "accounts": {
+                        "vaults": { "account": ["${accounts}"] },
+                    },
+                },
+            },
+        }))
+        .expect("valid config");
+        let (_snapshot_tx, messages_tx, shutdown, BoundAddresses { grpc: address, .. }) =
+            GrpcService::create(ConfigTokio::default(), config, None, false)
+                .await
+                .expect("service created");
+        let mut client = GeyserClient::connect(format!("http://{address}"))
+            .await
+            .expect("connected");
+
+        // substitution failure names the variable
+        let request = SubscribeRequest {
+            preset: Some("vaults".to_owned()),
+            preset_params: [("accounts".to_owned(), "0x123".to_owned())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let mut stream = client
+            .subscribe(tokio_stream::iter([request]))
+            .await
+            .expect("subscribed")
+            .into_inner();
+        let status = tokio::time::timeout(Duration::from_secs(5), stream.message())
+            .await
+            .expect("timeout")
+            .expect_err("invalid params");
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert!(status.message().contains("`${accounts}`"), "{status:?}");
+
+        let vaults = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let request = SubscribeRequest {
+            preset: Some("vaults".to_owned()),
+            preset_params: [(
+                "accounts".to_owned(),
+                format!("{},{}", vaults[0], vaults[1]),
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let (request_tx, request_rx) = mpsc::channel(1);
+        request_tx.send(request).await.expect("alive");
+        let mut stream = client
+            .subscribe(ReceiverStream::new(request_rx))
+            .await
+            .expect("subscribed")
+            .into_inner();
+
+        let mut slot = 1_000;
+        loop {
+            for pubkey in [Pubkey::new_unique(), vaults[1]] {
+                metrics::message_queue_size_inc();
+                messages_tx
+                    .send(create_message_account(slot, pubkey, 0))
+                    .expect("geyser loop is alive");
+            }
+            slot += 1;
+
+            let update = tokio::time::timeout(Duration::from_secs(5), stream.message())
+                .await
+                .expect("timeout")
+                .expect("stream is not failed")
+                .expect("stream is alive");
+            if let Some(UpdateOneof::Account(msg)) = update.update_oneof {
+                assert_eq!(update.filters, vec!["vaults".to_owned()]);
+                assert_eq!(
+                    msg.account.map(|account| account.pubkey),
+                    Some(vaults[1].to_bytes().to_vec())
+                );
+                break;
+            }
+        }
 
-                    // Send messages to filter (and to clients)
-                    let mut messages_vec = Vec::with_capacity(4);
-                    if let Some(sealed_block_msg) = sealed_block_msg {
-                        messages_vec.push(sealed_block_msg);
+        shutdown.notify_one();
+    }
+
+    #[tokio::test]
+    async fn test_health_pipeline_liveness() {
+        async fn wait_status(client: &mut HealthClient<Channel>, expected: ServingStatus) {
+            let request = HealthCheckRequest {
+                service: "geyser.Geyser".to_owned(),
+            };
+            tokio::time::timeout(Duration::from_secs(5), async {
+                loop {
+                    let status = client
+                        .check(request.clone())
+                        .await
+                        .expect("health check")
+                        .into_inner()
+                        .status();
+                    if status == expected {
+                        break;
                     }
-                    let slot_status = if let Message::Slot(msg) = &message {
-                        Some((msg.slot, msg.status))
+                    sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .unwrap_or_else(|_| panic!("health status is not {expected:?}"));
+        }
+
+        let config: ConfigGrpc = serde_json::from_value(serde_json::json!({
+            "address": "127.0.0.1:0",
+            "unary_disabled": true,
+            "health_stale_timeout": "200ms",
+        }))
+        .expect("valid config");
+        let (_snapshot_tx, messages_tx, shutdown, BoundAddresses { grpc: address, .. }) =
+            GrpcService::create(ConfigTokio::default(), config, None, false)
+                .await
+                .expect("service created");
+        let send = |message| {
+            metrics::message_queue_size_inc();
+            messages_tx.send(message).expect("geyser loop is alive");
+        };
+
+        let channel = Channel::from_shared(format!("http://{address}"))
+            .expect("valid uri")
+            .connect()
+            .await
+            .expect("connected");
+        let mut client = HealthClient::new(channel);
+        wait_status(&mut client, ServingStatus::NotServing).await;
+
+        // processed slots only, not warmed up
+        send(create_message_slot(100, Some(99), SlotStatus::Processed));
+        sleep(Duration::from_millis(50)).await;
+        wait_status(&mut client, ServingStatus::NotServing).await;
+
+        send(create_message_slot(100, Some(99), SlotStatus::Finalized));
+        wait_status(&mut client, ServingStatus::Serving).await;
+
+        // no messages during the stale timeout
+        wait_status(&mut client, ServingStatus::NotServing).await;
+
+        send(create_message_slot(101, Some(100), SlotStatus::Processed));
+        wait_status(&mut client, ServingStatus::Serving).await;
+
+        shutdown.notify_one();
+    }
+
+    #[tokio::test]
+    async fn test_ping_seq() {
+        let (client_tx, mut stream_rx) = TestClientLoop {
+            ping_interval: Duration::from_millis(10),
+            ..Default::default()
+        }
+        .spawn(None, &Broadcast::new([16; 3], 0, 0));
+
+        let mut prev = SubscribeUpdatePing::default();
+        for _ in 0..3 {
+            let update = recv_update(&mut stream_rx).await.expect("valid update");
+            let FilteredUpdateOneof::Ping(ping) = update.message else {
+                panic!("expected ping, received: {:?}", update.message);
+            };
+            assert_eq!(ping.seq, prev.seq + 1);
+            assert!(ping.server_timestamp >= prev.server_timestamp);
+            assert_eq!(ping.stats, None);
+            prev = ping;
+        }
+        drop(client_tx);
+    }
+
+    #[tokio::test]
+    async fn test_ping_stats() {
+        let pubkey = Pubkey::new_unique();
+        let filter = Filter::new(
+            &SubscribeRequest {
+                accounts: [(
+                    "accounts".to_owned(),
+                    SubscribeRequestFilterAccounts {
+                        account: vec![pubkey.to_string()],
+                        ..Default::default()
+                    },
+                )]
+                .into_iter()
+                .collect(),
+                include_ping_stats: Some(true),
+                ..Default::default()
+            },
+            &FilterLimits::default(),
+            &mut FilterNames::new(128, 4_096, Duration::from_secs(1)),
+        )
+        .expect("valid filter");
+        let commitment_slots = Arc::new(CommitmentSlots::default());
+        commitment_slots.update(SlotStatus::Processed, 42);
+
+        let broadcast = Broadcast::new([16; 3], 0, 0);
+        let (_client_tx, mut stream_rx) = TestClientLoop {
+            commitment_slots: Arc::clone(&commitment_slots),
+            ping_interval: Duration::from_millis(50),
+            ..Default::default()
+        }
+        .spawn(Some(filter), &broadcast);
+        // filter is applied
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert!(matches!(update.message, FilteredUpdateOneof::StreamInfo(_)));
+        let (mut updates, mut bytes) = (1, update.encoded_len() as u64);
+
+        // 2 of 5 accounts are matched by the filter
+        broadcast.send(
+            CommitmentLevel::Processed,
+            (0..5)
+                .map(|write_version| {
+                    let pubkey = if write_version % 2 == 0 {
+                        Pubkey::new_unique()
                     } else {
-                        None
+                        pubkey
                     };
-                    messages_vec.push((msgid, message));
-
-                    // sometimes we do not receive all statuses
-                    if let Some((slot, status)) = slot_status {
-                        let mut slots = vec![slot];
-                        while let Some((parent, Some(entry))) = slots
-                            .pop()
-                            .and_then(|slot| messages.get(&slot))
-                            .and_then(|entry| entry.parent_slot)
-                            .map(|parent| (parent, messages.get_mut(&parent)))
-                        {
-                            if (status == SlotStatus::Confirmed && !entry.confirmed) ||
-                                (status == SlotStatus::Finalized && !entry.finalized)
-                            {
-                                if status == SlotStatus::Confirmed {
-                                    entry.confirmed = true;
-                                } else if status == SlotStatus::Finalized {
-                                    entry.finalized = true;
-                                }
+                    (
+                        write_version,
+                        create_message_account(10, pubkey, write_version),
+                    )
+                })
+                .collect(),
+        );
 
-                                slots.push(parent);
-                                let message_slot = Message::Slot(MessageSlot {
-                                    slot: parent,
-                                    parent: entry.parent_slot,
-                                    status,
-                                    dead_error: None,
-                                    created_at: Timestamp::from(SystemTime::now())
-                                });
-                                messages_vec.push((msgid_gen.next(), message_slot));
-                                metrics::missed_status_message_inc(status);
-                            }
-                        }
+        // filtered messages can be counted by a later ping
+        let (mut accounts, mut filtered) = (0, 0);
+        while accounts < 2 || updates > 0 || filtered < 3 {
+            let update = recv_update(&mut stream_rx).await.expect("valid update");
+            match &update.message {
+                FilteredUpdateOneof::Ping(SubscribeUpdatePing {
+                    stats: Some(stats), ..
+                }) => {
+                    assert_eq!(stats.updates_sent, updates);
+                    assert_eq!(stats.bytes_sent, bytes);
+                    assert!(stats.queue_high_watermark >= stats.queue_depth);
+                    // sampled on every queued update
+                    if updates > 0 {
+                        assert!(stats.queue_high_watermark > 0);
+                    }
+                    assert_eq!(stats.processed_slot, 42);
+                    assert_eq!(stats.finalized_slot, 0);
+                    filtered += stats.messages_filtered;
+                    (updates, bytes) = (0, 0);
+                }
+                message => {
+                    if matches!(message, FilteredUpdateOneof::Account(_)) {
+                        accounts += 1;
                     }
+                    updates += 1;
+                    bytes += update.encoded_len() as u64;
+                }
+            }
+        }
+        assert_eq!(filtered, 3);
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_stalled_client() {
+        let filter = Filter::new(
+            &SubscribeRequest {
+                slots: [("slots".to_owned(), SubscribeRequestFilterSlots::default())]
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            },
+            &FilterLimits::default(),
+            &mut FilterNames::new(128, 4_096, Duration::from_secs(1)),
+        )
+        .expect("valid filter");
+
+        let registry = Arc::new(ClientRegistry::new(false));
+        let (stream_tx, stream_priority_tx, mut stream_rx) = SubscribeStream::new(1);
+        let registered = registry.register(0, None, "", &stream_tx, stream_rx.bytes.clone());
+        let queue = stream_tx.clone();
+        let _client_tx = TestClientLoop {
+            registered: Some(registered),
+            ping_interval: Duration::from_secs(60),
+            ..Default::default()
+        }
+        .spawn_with_stream(
+            stream_tx,
+            stream_priority_tx,
+            Some(filter),
+            &Broadcast::new([16; 3], 0, 0),
+        );
+
+        // stream info fills the queue, the client never reads it
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while queue.queue_depth() == 0 {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("queue is full");
+
+        assert!(registry.disconnect(0, "stalled"));
+        let status = recv_update(&mut stream_rx)
+            .await
+            .expect_err("terminal status");
+        assert_eq!(status.code(), tonic::Code::Aborted);
+        assert_eq!(status.message(), "stalled");
+        assert!(
+            stream_rx.next().await.is_none(),
+            "queued updates are not sent"
+        );
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while registry.count() > 0 {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("client removed");
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_ahead_of_backlog() {
+        let (stream_tx, stream_priority_tx, mut stream_rx) = SubscribeStream::new(16);
+        let announced = Arc::new(SubscribeUpdateMaintenance {
+            reason: "upgrade".to_owned(),
+            expected_at_slot: Some(100),
+            drain_deadline_secs: 30,
+            announced_at: unix_timestamp_ms(),
+            cancelled: false,
+        });
+        let (maintenance_tx, maintenance_rx) = watch::channel(Some(Arc::clone(&announced)));
+        let client_tx = TestClientLoop {
+            maintenance_rx,
+            ..Default::default()
+        }
+        .spawn_with_stream(
+            stream_tx.clone(),
+            stream_priority_tx,
+            None,
+            &Broadcast::new([16; 3], 0, 0),
+        );
+
+        // announced before the subscription
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert_eq!(update.message, FilteredUpdateOneof::maintenance(announced));
+
+        // backlog in the main queue
+        for seq in 1..=3 {
+            let msg = FilteredUpdate::new_empty(FilteredUpdateOneof::ping(seq, 0));
+            stream_tx.send(Ok(msg)).await.expect("alive");
+        }
+
+        // announced while the client is connected
+        let announced = Arc::new(SubscribeUpdateMaintenance {
+            reason: "restart".to_owned(),
+            ..Default::default()
+        });
+        maintenance_tx.send_replace(Some(Arc::clone(&announced)));
+        sleep(Duration::from_millis(50)).await;
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert_eq!(update.message, FilteredUpdateOneof::maintenance(announced));
+
+        for seq in 1..=3 {
+            let update = recv_update(&mut stream_rx).await.expect("valid update");
+            let FilteredUpdateOneof::Ping(ping) = update.message else {
+                panic!("expected ping, received: {:?}", update.message);
+            };
+            assert_eq!(ping.seq, seq);
+        }
+        drop(client_tx);
+    }
+
+    #[tokio::test]
+    async fn test_ping_pong() {
+        let config: ConfigGrpc = serde_json::from_value(serde_json::json!({
+            "address": "127.0.0.1:0",
+        }))
+        .expect("valid config");
+        let (_snapshot_tx, _messages_tx, shutdown, BoundAddresses { grpc: address, .. }) =
+            GrpcService::create(ConfigTokio::default(), config, None, false)
+                .await
+                .expect("service created");
+
+        let mut client = GeyserClient::connect(format!("http://{address}"))
+            .await
+            .expect("connected");
+        let before = unix_timestamp_ms();
+        let pong = client
+            .ping(PingRequest { count: 7 })
+            .await
+            .expect("pong")
+            .into_inner();
+        assert_eq!(pong.count, 7);
+        assert!(pong.server_timestamp >= before && pong.server_timestamp <= unix_timestamp_ms());
+
+        let (request_tx, request_rx) = mpsc::channel(4);
+        let mut stream = client
+            .subscribe(ReceiverStream::new(request_rx))
+            .await
+            .expect("subscribed")
+            .into_inner();
+        for id in [1, 42] {
+            request_tx
+                .send(SubscribeRequest {
+                    ping: Some(SubscribeRequestPing { id }),
+                    ..Default::default()
+                })
+                .await
+                .expect("alive");
+            let update = tokio::time::timeout(Duration::from_secs(5), stream.message())
+                .await
+                .expect("timeout")
+                .expect("stream is not failed")
+                .expect("stream is alive");
+            assert_eq!(
+                update.update_oneof,
+                Some(UpdateOneof::Pong(SubscribeUpdatePong { id }))
+            );
+        }
+
+        shutdown.notify_one();
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_commitment_channels() {
+        let broadcast = Broadcast::new([1, 1, 16], 0, 0);
+        let request = SubscribeRequest {
+            accounts: [("all".to_owned(), SubscribeRequestFilterAccounts::default())]
+                .into_iter()
+                .collect(),
+            commitment: Some(CommitmentLevelProto::Finalized as i32),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &request,
+            &FilterLimits::default(),
+            &mut FilterNames::new(128, 4_096, Duration::from_secs(1)),
+        )
+        .expect("valid filter");
 
-                    for message in messages_vec.into_iter().rev() {
-                        if let Message::Slot(slot) = &message.1 {
-                            let (mut confirmed_messages, mut finalized_messages) = match slot.status {
-                                SlotStatus::Processed | SlotStatus::FirstShredReceived | SlotStatus::Completed | SlotStatus::CreatedBank | SlotStatus::Dead => {
-                                    (Vec::with_capacity(1), Vec::with_capacity(1))
-                                }
-                                SlotStatus::Confirmed => {
-                                    if let Some(slot_messages) = messages.get_mut(&slot.slot) {
-                                        if !slot_messages.sealed {
-                                            slot_messages.confirmed_at = Some(slot_messages.messages.len());
-                                        }
-                                    }
+        let (_client_tx, mut stream_rx) = spawn_test_client_loop(filter, &broadcast);
+        recv_stream_info(&mut stream_rx).await; // filter is applied
 
-                                    let vec = messages
-                                        .get(&slot.slot)
-                                        .map(|slot_messages| slot_messages.messages.iter().flatten().cloned().collect())
-                                        .unwrap_or_default();
-                                    (vec, Vec::with_capacity(1))
-                                }
-                                SlotStatus::Finalized => {
-                                    if let Some(slot_messages) = messages.get_mut(&slot.slot) {
-                                        if !slot_messages.sealed {
-                                            slot_messages.finalized_at = Some(slot_messages.messages.len());
-                                        }
-                                    }
+        // finalized client is subscribed only to the finalized channel
+        for (commitment, receivers) in [
+            (CommitmentLevel::Processed, 0),
+            (CommitmentLevel::Confirmed, 0),
+            (CommitmentLevel::Finalized, 1),
+        ] {
+            assert_eq!(
+                broadcast.get_channel(commitment).tx.receiver_count(),
+                receivers
+            );
+        }
 
-                                    let vec = messages
-                                        .get_mut(&slot.slot)
-                                        .map(|slot_messages| slot_messages.messages.iter().flatten().cloned().collect())
-                                        .unwrap_or_default();
-                                    (Vec::with_capacity(1), vec)
-                                }
-                            };
+        // processed batches over the capacity do not lag finalized client
+        for slot in 0..8 {
+            broadcast.send(
+                CommitmentLevel::Processed,
+                vec![(slot, create_message_account(slot, Pubkey::new_unique(), 0))],
+            );
+        }
+        broadcast.send(
+            CommitmentLevel::Finalized,
+            vec![(42, create_message_account(42, Pubkey::new_unique(), 0))],
+        );
+        let update = recv_update(&mut stream_rx).await.expect("valid update");
+        assert!(matches!(update.message, FilteredUpdateOneof::Account(msg) if msg.slot == 42));
+    }
 
-                            // processed
-                            processed_messages.push(message.clone());
-                            let _ =
-                                broadcast_tx.send((CommitmentLevel::Processed, processed_messages.into()));
-                            processed_messages = Vec::with_capacity(PROCESSED_MESSAGES_MAX);
-                            processed_sleep
-                                .as_mut()
-                                .reset(Instant::now() + PROCESSED_MESSAGES_SLEEP);
-
-                            // confirmed
-                            confirmed_messages.push(message.clone());
-                            let _ =
-                                broadcast_tx.send((CommitmentLevel::Confirmed, confirmed_messages.into()));
-
-                            // finalized
-                            finalized_messages.push(message);
-                            let _ =
-                                broadcast_tx.send((CommitmentLevel::Finalized, finalized_messages.into()));
-                        } else {
-                            let mut confirmed_messages = vec![];
-                            let mut finalized_messages = vec![];
-                            if matches!(&message.1, Message::Block(_)) {
-                                if let Some(slot_messages) = messages.get(&message.1.get_slot()) {
-                                    if let Some(confirmed_at) = slot_messages.confirmed_at {
-                                        confirmed_messages.extend(
-                                            slot_messages.messages.as_slice()[confirmed_at..].iter().filter_map(|x| x.clone())
-                                        );
-                                    }
-                                    if let Some(finalized_at) = slot_messages.finalized_at {
-                                        finalized_messages.extend(
-                                            slot_messages.messages.as_slice()[finalized_at..].iter().filter_map(|x| x.clone())
-                                        );
-                                    }
-                                }
-                            }
+    /// Geyser loop storing slots for replay, returns messages sender and the replay requests sender
+    fn spawn_replay_geyser_loop(
+        broadcast: Broadcast,
+        max_bytes: usize,
+    ) -> (
+        mpsc::UnboundedSender<Message>,
+        mpsc::Sender<ReplayStoredSlotsRequest>,
+    ) {
+        let (messages_tx, messages_rx) = mpsc::unbounded_channel();
+        let (replay_tx, replay_rx) = mpsc::channel(1);
+        tokio::spawn(GrpcService::geyser_loop(
+            messages_rx,
+            None,
+            Arc::new(CommitmentSlots::default()),
+            None,
+            None,
+            broadcast,
+            Some(replay_rx),
+            100,
+            max_bytes,
+            ProcessedBatchConfig {
+                max_size: 8,
+                max_delay: Duration::from_secs(3600),
+                flush_on_slot: false,
+            },
+            None,
+            false,
+            None,
+            None,
+        ));
+        (messages_tx, replay_tx)
+    }
 
-                            processed_messages.push(message);
-                            if processed_messages.len() >= PROCESSED_MESSAGES_MAX
-                                || !confirmed_messages.is_empty()
-                                || !finalized_messages.is_empty()
-                            {
-                                let _ = broadcast_tx
-                                    .send((CommitmentLevel::Processed, processed_messages.into()));
-                                processed_messages = Vec::with_capacity(PROCESSED_MESSAGES_MAX);
-                                processed_sleep
-                                    .as_mut()
-                                    .reset(Instant::now() + PROCESSED_MESSAGES_SLEEP);
-                            }
+    /// Client subscribed to all processed accounts starting from `from_slot`
+    fn spawn_replay_client(
+        broadcast: Broadcast,
+        replay_tx: mpsc::Sender<ReplayStoredSlotsRequest>,
+        from_slot: Slot,
+    ) -> SubscribeStream {
+        let request = SubscribeRequest {
+            accounts: [("all".to_owned(), SubscribeRequestFilterAccounts::default())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &request,
+            &FilterLimits::default(),
+            &mut FilterNames::new(128, 4_096, Duration::from_secs(1)),
+        )
+        .expect("valid filter");
 
-                            if !confirmed_messages.is_empty() {
-                                let _ =
-                                    broadcast_tx.send((CommitmentLevel::Confirmed, confirmed_messages.into()));
-                            }
+        let (_client_tx, stream_rx) = TestClientLoop {
+            stream_capacity: 64,
+            from_slot: Some(from_slot),
+            replay_stored_slots_tx: Some(replay_tx),
+            ..Default::default()
+        }
+        .spawn(Some(filter), &broadcast);
+        stream_rx
+    }
 
-                            if !finalized_messages.is_empty() {
-                                let _ =
-                                    broadcast_tx.send((CommitmentLevel::Finalized, finalized_messages.into()));
-                            }
-                        }
-                    }
-                }
-                () = &mut processed_sleep => {
-                    if !processed_messages.is_empty() {
-                        let _ = broadcast_tx.send((CommitmentLevel::Processed, processed_messages.into()));
-                        processed_messages = Vec::with_capacity(PROCESSED_MESSAGES_MAX);
-                    }
-                    processed_sleep.as_mut().reset(Instant::now() + PROCESSED_MESSAGES_SLEEP);
-                }
-                Some((commitment, replay_slot, tx)) = replay_stored_slots_rx.recv() => {
-                    if let Some((slot, _)) = messages.first_key_value() {
-                        if replay_slot < *slot {
-                            let _ = tx.send(ReplayedResponse::Lagged(*slot));
-                            continue;
-                        }
-                    }
+    #[tokio::test]
+    async fn test_replay_then_live() {
+        let broadcast = Broadcast::new([1024; 3], 0, 0);
+        let mut confirmed_rx = broadcast.subscribe(CommitmentLevel::Confirmed, None);
+        let (messages_tx, replay_tx) = spawn_replay_geyser_loop(broadcast.clone(), 0);
 
-                    let mut replayed_messages = Vec::with_capacity(32_768);
-                    for (slot, messages) in messages.iter() {
-                        if *slot >= replay_slot {
-                            replayed_messages.extend_from_slice(&messages.messages_slots);
-                            if commitment == CommitmentLevel::Processed
-                                || (commitment == CommitmentLevel::Finalized && messages.finalized)
-                                || (commitment == CommitmentLevel::Confirmed && messages.confirmed)
-                            {
-                                replayed_messages.extend(messages.messages.iter().filter_map(|v| v.clone()));
-                            }
+        // stored messages are still in the pending processed batch
+        messages_tx
+            .send(create_message_slot(10, None, SlotStatus::Processed))
+            .expect("geyser loop is alive");
+        for write_version in 0..5 {
+            messages_tx
+                .send(create_message_account(
+                    10,
+                    Pubkey::new_unique(),
+                    write_version,
+                ))
+                .expect("geyser loop is alive");
+        }
+        messages_tx
+            .send(create_message_slot(10, None, SlotStatus::Confirmed))
+            .expect("geyser loop is alive");
+        confirmed_rx.recv().await.expect("broadcast is alive");
+
+        let mut stream_rx = spawn_replay_client(broadcast, replay_tx, 10);
+        recv_stream_info(&mut stream_rx).await;
+        let mut write_versions = vec![];
+        loop {
+            let update = recv_update(&mut stream_rx).await.expect("valid update");
+            match update.message {
+                FilteredUpdateOneof::Account(msg) => write_versions.push(msg.account.write_version),
+                FilteredUpdateOneof::ReplayDone(msg) => {
+                    assert_eq!(
+                        msg,
+                        SubscribeUpdateReplayDone {
+                            from_slot: 10,
+                            to_slot: 10,
+                            replayed_updates: 5,
                         }
-                    }
-                    let _ = tx.send(ReplayedResponse::Messages(replayed_messages));
+                    );
+                    break;
                 }
-                else => break,
+                message => panic!("unexpected message: {message:?}"),
             }
         }
+        assert_eq!(write_versions, (0..5).collect::<Vec<_>>());
+
+        // live messages follow the replay without duplicates of the flushed batch
+        for write_version in 5..21 {
+            messages_tx
+                .send(create_message_account(
+                    11,
+                    Pubkey::new_unique(),
+                    write_version,
+                ))
+                .expect("geyser loop is alive");
+        }
+        for write_version in 5..21 {
+            let update = recv_update(&mut stream_rx).await.expect("valid update");
+            assert!(
+                matches!(update.message, FilteredUpdateOneof::Account(msg) if msg.account.write_version == write_version)
+            );
+        }
     }
 
-    #[allow(clippy::too_many_arguments)]
-    async fn client_loop(
-        id: usize,
-        endpoint: String,
-        stream_tx: mpsc::Sender<TonicResult<FilteredUpdate>>,
-        mut client_rx: mpsc::UnboundedReceiver<Option<(Option<u64>, Filter)>>,
-        mut snapshot_rx: Option<crossbeam_channel::Receiver<Box<Message>>>,
-        mut messages_rx: broadcast::Receiver<BroadcastedMessage>,
-        replay_stored_slots_tx: Option<mpsc::Sender<ReplayStoredSlotsRequest>>,
-        debug_client_tx: Option<mpsc::UnboundedSender<DebugClientMessage>>,
-        drop_client: impl FnOnce(),
-    ) {
-        let mut filter = Filter::default();
-        metrics::update_subscriptions(&endpoint, None, Some(&filter));
+    #[tokio::test]
+    async fn test_replay_out_of_range() {
+        let broadcast = Broadcast::new([1024; 3], 0, 0);
+        let mut finalized_rx = broadcast.subscribe(CommitmentLevel::Finalized, None);
+        let (messages_tx, replay_tx) = spawn_replay_geyser_loop(broadcast.clone(), 600);
 
-        metrics::connections_total_inc();
-        DebugClientMessage::maybe_send(&debug_client_tx, || DebugClientMessage::UpdateFilter {
-            id,
-            filter: Box::new(filter.clone()),
-        });
-        info!("client #{id}: new");
+        // slot 10 is evicted once the stored data is over the budget, only finalized slots
+        // older than the last `FINALIZED_SLOTS_KEPT` slots can be evicted
+        for slot in 10..13 {
+            messages_tx
+                .send(create_message_account_with_data(slot, 100))
+                .expect("geyser loop is alive");
+        }
+        for slot in [10, 11, 22] {
+            messages_tx
+                .send(create_message_slot(slot, None, SlotStatus::Finalized))
+                .expect("geyser loop is alive");
+        }
+        loop {
+            let (_commitment, messages) = finalized_rx.recv().await.expect("broadcast is alive");
+            if messages
+                .iter()
+                .any(|(_msgid, message)| matches!(message, Message::Slot(msg) if msg.slot == 22))
+            {
+                break;
+            }
+        }
 
-        let mut is_alive = true;
-        if let Some(snapshot_rx) = snapshot_rx.take() {
-            Self::client_loop_snapshot(
-                id,
-                &endpoint,
-                &stream_tx,
-                &mut client_rx,
-                snapshot_rx,
-                &mut is_alive,
-                &mut filter,
-            )
-            .await;
+        let mut stream_rx = spawn_replay_client(broadcast, replay_tx, 10);
+        recv_stream_info(&mut stream_rx).await;
+        let status = recv_update(&mut stream_rx).await.expect_err("out of range");
+        assert_eq!(status.code(), tonic::Code::OutOfRange);
+        assert_eq!(
+            status
+                .metadata()
+                .get("x-first-available-slot")
+                .and_then(|value| value.to_str().ok()),
+            Some("11")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_shards() {
+        let broadcast = Broadcast::new([1024; 3], 2, 0);
+        let mut owners = vec![];
+        while owners.len() < 2 {
+            let owner = Pubkey::new_unique();
+            if broadcast.get_owner_shard(&owner) == owners.len() {
+                owners.push(owner);
+            }
         }
 
-        if is_alive {
-            'outer: loop {
-                tokio::select! {
-                    mut message = client_rx.recv() => {
-                        // forward to latest filter
-                        loop {
-                            match client_rx.try_recv() {
-                                Ok(message_new) => {
-                                    message = Some(message_new);
-                                }
-                                Err(mpsc::error::TryRecvError::Empty) => break,
-                                Err(mpsc::error::TryRecvError::Disconnected) => {
-                                    message = None;
-                                    break;
-                                }
-                            }
-                        }
+        let create_filter = |owners: &[Pubkey]| {
+            let request = SubscribeRequest {
+                accounts: [(
+                    "owners".to_owned(),
+                    SubscribeRequestFilterAccounts {
+                        owner: owners.iter().map(|owner| owner.to_string()).collect(),
+                        ..Default::default()
+                    },
+                )]
+                .into_iter()
+                .collect(),
+                ..Default::default()
+            };
+            Filter::new(
+                &request,
+                &FilterLimits::default(),
+                &mut FilterNames::new(128, 4_096, Duration::from_secs(1)),
+            )
+            .expect("valid filter")
+        };
+        assert_eq!(broadcast.get_shard(&Filter::default()), Some(0));
+        assert_eq!(broadcast.get_shard(&create_filter(&owners[1..])), Some(1));
+        assert_eq!(broadcast.get_shard(&create_filter(&owners)), None);
+        assert_eq!(broadcast.get_shard(&create_filter(&[])), None);
+        assert_eq!(
+            Broadcast::new([1; 3], 0, 0).get_shard(&create_filter(&owners[..1])),
+            None
+        );
 
-                        match message {
-                            Some(Some((from_slot, filter_new))) => {
-                                metrics::update_subscriptions(&endpoint, Some(&filter), Some(&filter_new));
-                                filter = filter_new;
-                                DebugClientMessage::maybe_send(&debug_client_tx, || DebugClientMessage::UpdateFilter { id, filter: Box::new(filter.clone()) });
-                                info!("client #{id}: filter updated");
+        let mut shards_rx = [
+            CommitmentLevel::Processed,
+            CommitmentLevel::Confirmed,
+            CommitmentLevel::Finalized,
+        ]
+        .map(|commitment| {
+            [
+                broadcast.subscribe(commitment, Some(0)),
+                broadcast.subscribe(commitment, Some(1)),
+            ]
+        });
+        let mut geyser = GeyserLoop::spawn_with_broadcast(broadcast);
+        let slot = 100;
+        for write_version in 0..4 {
+            geyser.send(Message::Account(MessageAccount {
+                account: Arc::new(MessageAccountInfo {
+                    pubkey: Pubkey::new_unique(),
+                    lamports: 42,
+                    owner: owners[write_version as usize % 2],
+                    executable: false,
+                    rent_epoch: 0,
+                    data: Default::default(),
+                    write_version,
+                    txn_signature: None,
+                    base58: Default::default(),
+                }),
+                slot,
+                is_startup: false,
+                created_at: Timestamp::from(SystemTime::now()),
+                confirmed_at_slot: None,
+                finalized_at_slot: None,
+            }));
+        }
+        geyser.send(create_message_slot(
+            slot,
+            Some(slot - 1),
+            SlotStatus::Processed,
+        ));
+        geyser.send(create_message_slot(
+            slot,
+            Some(slot - 1),
+            SlotStatus::Confirmed,
+        ));
 
-                                if let Some(from_slot) = from_slot {
-                                    let Some(replay_stored_slots_tx) = &replay_stored_slots_tx else {
-                                        info!("client #{id}: from_slot is not supported");
-                                        tokio::spawn(async move {
-                                            let _ = stream_tx.send(Err(Status::internal("from_slot is not supported"))).await;
-                                        });
-                                        break 'outer;
-                                    };
+        // every shard has the same batches as the full stream, only with own accounts
+        for _ in 0..6 {
+            let (commitment, messages) = geyser.recv().await;
+            for (shard, shard_rx) in shards_rx[commitment as usize].iter_mut().enumerate() {
+                let (shard_commitment, shard_messages) = shard_rx.try_recv().expect("shard batch");
+                assert_eq!(shard_commitment, commitment);
+                let expected = messages
+                    .iter()
+                    .filter(|(_msgid, message)| match message {
+                        Message::Account(msg) => msg.account.owner == owners[shard],
+                        _ => true,
+                    })
+                    .map(|(msgid, _message)| *msgid)
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    shard_messages
+                        .iter()
+                        .map(|(msgid, _message)| *msgid)
+                        .collect::<Vec<_>>(),
+                    expected
+                );
+            }
+        }
+        for shard_rx in shards_rx.iter_mut().flatten() {
+            assert!(shard_rx.try_recv().is_err());
+        }
+    }
 
-                                    let (tx, rx) = oneshot::channel();
-                                    let commitment = filter.get_commitment_level();
-                                    if let Err(_error) = replay_stored_slots_tx.send((commitment, from_slot, tx)).await {
-                                        error!("client #{id}: failed to send from_slot request");
-                                        tokio::spawn(async move {
-                                            let _ = stream_tx.send(Err(Status::internal("failed to send from_slot request"))).await;
-                                        });
-                                        break 'outer;
-                                    }
+    fn create_message_account_with_data(slot: Slot, data_len: usize) -> Message {
+        Message::Account(MessageAccount {
+            account: Arc::new(MessageAccountInfo {
+                pubkey: Pubkey::new_unique(),
+                lamports: 42,
+                owner: Pubkey::new_unique(),
+                executable: false,
+                rent_epoch: 0,
+                data: vec![0; data_len].into(),
+                write_version: 0,
+                txn_signature: None,
+                base58: Default::default(),
+            }),
+            slot,
+            is_startup: false,
+            created_at: Timestamp::from(SystemTime::now()),
+            confirmed_at_slot: None,
+            finalized_at_slot: None,
+        })
+    }
 
-                                    let mut messages = match rx.await {
-                                        Ok(ReplayedResponse::Messages(messages)) => messages,
-                                        Ok(ReplayedResponse::Lagged(slot)) => {
-                                            info!("client #{id}: broadcast from {from_slot} is not available");
-                                            tokio::spawn(async move {
-                                                let message = format!(
-                                                    "broadcast from {from_slot} is not available, last available: {slot}"
-                                                );
-                                                let _ = stream_tx.send(Err(Status::internal(message))).await;
-                                            });
-                                            break 'outer;
-                                        },
-                                        Err(_error) => {
-                                            error!("client #{id}: failed to get replay response");
-                                            tokio::spawn(async move {
-                                                let _ = stream_tx.send(Err(Status::internal("failed to get replay response"))).await;
-                                            });
-                                            break 'outer;
-                                        }
-                                    };
+    #[test]
+    fn test_slot_bytes_max() {
+        let mut messages = BTreeMap::new();
+        let mut slot_bytes_max = SlotBytesMax::default();
+        for (slot, bytes) in [(10, 300), (11, 500), (12, 200)] {
+            messages.insert(
+                slot,
+                SlotMessages {
+                    bytes,
+                    ..Default::default()
+                },
+            );
+            slot_bytes_max.grown(bytes);
+        }
+        assert_eq!(slot_bytes_max.get(&messages), 500);
 
-                                    messages.sort_by_key(|msg| msg.0);
-                                    for (_msgid, message) in messages.iter() {
-                                        for message in filter.get_updates(message, Some(commitment)) {
-                                            match stream_tx.send(Ok(message)).await {
-                                                Ok(()) => {}
-                                                Err(mpsc::error::SendError(_)) => {
-                                                    error!("client #{id}: stream closed");
-                                                    break 'outer;
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                            Some(None) => {
-                                break 'outer;
-                            },
-                            None => {
-                                break 'outer;
-                            }
-                        }
-                    }
-                    message = messages_rx.recv() => {
-                        let (commitment, messages) = match message {
-                            Ok((commitment, messages)) => (commitment, messages),
-                            Err(broadcast::error::RecvError::Closed) => {
-                                break 'outer;
-                            },
-                            Err(broadcast::error::RecvError::Lagged(_)) => {
-                                info!("client #{id}: lagged to receive geyser messages");
-                                tokio::spawn(async move {
-                                    let _ = stream_tx.send(Err(Status::internal("lagged to receive geyser messages"))).await;
-                                });
-                                break 'outer;
-                            }
-                        };
+        // eviction of a smaller slot keeps the max without a scan
+        let evicted = messages.remove(&10).expect("stored slot");
+        slot_bytes_max.shrunk(evicted.bytes);
+        assert!(!slot_bytes_max.stale);
+        assert_eq!(slot_bytes_max.get(&messages), 500);
 
-                        if commitment == filter.get_commitment_level() {
-                            for (_msgid, message) in messages.iter() {
-                                for message in filter.get_updates(message, Some(commitment)) {
-                                    match stream_tx.try_send(Ok(message)) {
-                                        Ok(()) => {}
-                                        Err(mpsc::error::TrySendError::Full(_)) => {
-                                            error!("client #{id}: lagged to send an update");
-                                            tokio::spawn(async move {
-                                                let _ = stream_tx.send(Err(Status::internal("lagged to send an update"))).await;
-                                            });
-                                            break 'outer;
-                                        }
-                                        Err(mpsc::error::TrySendError::Closed(_)) => {
-                                            error!("client #{id}: stream closed");
-                                            break 'outer;
-                                        }
-                                    }
-                                }
-                            }
-                        }
+        // the largest slot is shrunk and then evicted
+        let slot_messages = messages.get_mut(&11).expect("stored slot");
+        slot_bytes_max.shrunk(slot_messages.bytes);
+        slot_messages.bytes = 100;
+        assert_eq!(slot_bytes_max.get(&messages), 200);
+        let evicted = messages.remove(&12).expect("stored slot");
+        slot_bytes_max.shrunk(evicted.bytes);
+        assert_eq!(slot_bytes_max.get(&messages), 100);
+        messages.clear();
+        slot_bytes_max.shrunk(100);
+        assert_eq!(slot_bytes_max.get(&messages), 0);
+    }
 
-                        if commitment == CommitmentLevel::Processed && debug_client_tx.is_some() {
-                            for message in messages.iter() {
-                                if let Message::Slot(slot_message) = &message.1 {
-                                    DebugClientMessage::maybe_send(&debug_client_tx, || DebugClientMessage::UpdateSlot { id, slot: slot_message.slot });
-                                }
-                            }
-                        }
-                    }
-                }
+    #[test]
+    fn test_broadcast_batch_max_bytes() {
+        let messages = [40, 40, 500, 10, 0, 90, 20]
+            .into_iter()
+            .enumerate()
+            .map(|(msgid, data_len)| {
+                let message = if data_len == 0 {
+                    create_message_slot(100, Some(99), SlotStatus::Processed)
+                } else {
+                    create_message_account_with_data(100, data_len)
+                };
+                (msgid as u64, message)
+            })
+            .collect::<Vec<_>>();
+        let get_batches = |broadcast: Broadcast| {
+            let mut rx = broadcast.subscribe(CommitmentLevel::Processed, None);
+            broadcast.send(CommitmentLevel::Processed, messages.clone());
+            let mut batches = vec![];
+            while let Ok((_commitment, messages)) = rx.try_recv() {
+                batches.push(
+                    messages
+                        .iter()
+                        .map(|(msgid, _message)| *msgid)
+                        .collect::<Vec<_>>(),
+                );
             }
-        }
+            batches
+        };
 
-        metrics::connections_total_dec();
-        DebugClientMessage::maybe_send(&debug_client_tx, || DebugClientMessage::Removed { id });
-        metrics::update_subscriptions(&endpoint, Some(&filter), None);
-        info!("client #{id}: removed");
-        drop_client();
+        // oversized message in own batch, order is preserved
+        assert_eq!(
+            get_batches(Broadcast::new([16; 3], 0, 500)),
+            vec![vec![0, 1], vec![2], vec![3, 4, 5], vec![6]]
+        );
+        assert_eq!(
+            get_batches(Broadcast::new([16; 3], 0, 0)),
+            vec![vec![0, 1, 2, 3, 4, 5, 6]]
+        );
     }
 
-    async fn client_loop_snapshot(
-        id: usize,
-        endpoint: &str,
-        stream_tx: &mpsc::Sender<TonicResult<FilteredUpdate>>,
-        client_rx: &mut mpsc::UnboundedReceiver<Option<(Option<u64>, Filter)>>,
-        snapshot_rx: crossbeam_channel::Receiver<Box<Message>>,
-        is_alive: &mut bool,
-        filter: &mut Filter,
-    ) {
-        info!("client #{id}: going to receive snapshot data");
+    #[test]
+    fn test_broadcast_batch_max_bytes_transactions() {
+        let mut transaction = create_message_transaction(100, Signature::new_unique(), None, false);
+        if let Message::Transaction(msg) = &mut transaction {
+            Arc::make_mut(&mut msg.transaction).meta.log_messages = vec!["log".repeat(200)];
+        }
+        let messages = vec![
+            (0, create_message_account_with_data(100, 10)),
+            (1, transaction),
+            (2, create_message_account_with_data(100, 10)),
+            (3, create_message_account_with_data(100, 10)),
+        ];
 
-        // we start with default filter, for snapshot we need wait actual filter first
-        while *is_alive {
-            match client_rx.recv().await {
-                Some(Some((_from_slot, filter_new))) => {
-                    if let Some(msg) = filter_new.get_pong_msg() {
-                        if stream_tx.send(Ok(msg)).await.is_err() {
-                            error!("client #{id}: stream closed");
-                            *is_alive = false;
-                        }
-                        continue;
-                    }
+        // transactions are sized with meta, not only accounts with data
+        let broadcast = Broadcast::new([16; 3], 0, 500);
+        let mut rx = broadcast.subscribe(CommitmentLevel::Processed, None);
+        broadcast.send(CommitmentLevel::Processed, messages);
+        let mut batches = vec![];
+        while let Ok((_commitment, messages)) = rx.try_recv() {
+            batches.push(
+                messages
+                    .iter()
+                    .map(|(msgid, _message)| *msgid)
+                    .collect::<Vec<_>>(),
+            );
+        }
+        assert_eq!(batches, vec![vec![0], vec![1], vec![2, 3]]);
+    }
 
-                    metrics::update_subscriptions(endpoint, Some(filter), Some(&filter_new));
-                    *filter = filter_new;
-                    info!("client #{id}: filter updated");
-                    break;
-                }
-                Some(None) => {
-                    *is_alive = false;
-                }
-                None => {
-                    *is_alive = false;
-                }
-            };
+    #[tokio::test]
+    async fn test_broadcast_batch_max_bytes_commitment() {
+        let mut geyser = GeyserLoop::spawn_with_broadcast(Broadcast::new([1024; 3], 0, 400));
+        let slot = 100;
+        geyser.send(create_message_slot(
+            slot,
+            Some(slot - 1),
+            SlotStatus::Processed,
+        ));
+        for data_len in [10, 500, 10, 10] {
+            geyser.send(create_message_account_with_data(slot, data_len));
+        }
+        geyser.send(create_message_slot(
+            slot,
+            Some(slot - 1),
+            SlotStatus::Confirmed,
+        ));
+
+        // confirmed messages are split, slot status is still the last message
+        let mut confirmed = vec![];
+        loop {
+            let (commitment, messages) = geyser.recv().await;
+            if commitment != CommitmentLevel::Confirmed {
+                continue;
+            }
+            let bytes = messages
+                .iter()
+                .map(|(_msgid, message)| message.get_size_estimate())
+                .collect::<Vec<_>>();
+            assert!(bytes.len() == 1 || bytes.iter().sum::<usize>() <= 400);
+            confirmed.extend(messages.iter().cloned());
+            if matches!(confirmed.last(), Some((_msgid, Message::Slot(msg))) if msg.status == SlotStatus::Confirmed)
+            {
+                break;
+            }
+        }
+        assert!(confirmed.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        let accounts = confirmed
+            .iter()
+            .filter_map(|(_msgid, message)| match message {
+                Message::Account(msg) if msg.slot == slot => Some(msg.account.data.len()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(accounts, [10, 500, 10, 10]);
+        match confirmed.last() {
+            Some((_msgid, Message::Slot(msg))) => {
+                assert_eq!((msg.slot, msg.status), (slot, SlotStatus::Confirmed))
+            }
+            message => panic!("unexpected last message: {message:?}"),
         }
+    }
 
-        while *is_alive {
-            let message = match snapshot_rx.try_recv() {
-                Ok(message) => {
-                    metrics::message_queue_size_dec();
-                    message
-                }
-                Err(crossbeam_channel::TryRecvError::Empty) => {
-                    sleep(Duration::from_millis(1)).await;
-                    continue;
-                }
-                Err(crossbeam_channel::TryRecvError::Disconnected) => {
-                    info!("client #{id}: end of startup");
-                    break;
-                }
-            };
+    #[tokio::test]
+    async fn test_account_commitment_slot() {
+        let mut geyser = GeyserLoop::spawn();
+        let slot = 100;
+        geyser.send(create_message_slot(
+            slot,
+            Some(slot - 1),
+            SlotStatus::Processed,
+        ));
+        geyser.send(create_message_account(slot, Pubkey::new_unique(), 1));
+        geyser.send(create_message_block_meta(slot, 0, 0));
+        geyser.send(create_message_slot(
+            slot + 1,
+            Some(slot),
+            SlotStatus::Processed,
+        ));
+        geyser.send(create_message_slot(
+            slot,
+            Some(slot - 1),
+            SlotStatus::Confirmed,
+        ));
+        geyser.send(create_message_slot(
+            slot + 2,
+            Some(slot + 1),
+            SlotStatus::Processed,
+        ));
+        geyser.send(create_message_slot(
+            slot,
+            Some(slot - 1),
+            SlotStatus::Finalized,
+        ));
 
-            for message in filter.get_updates(&message, None) {
-                if stream_tx.send(Ok(message)).await.is_err() {
-                    error!("client #{id}: stream closed");
-                    *is_alive = false;
-                    break;
+        // account is flushed by the status of the slot for every commitment and stamped with
+        // the processed tip at that time
+        let mut accounts = HashMap::new();
+        while accounts.len() < 3 {
+            let (commitment, messages) = geyser.recv().await;
+            for (_msgid, message) in messages.iter() {
+                if let Message::Account(msg) = message {
+                    accounts.insert(commitment, (msg.confirmed_at_slot, msg.finalized_at_slot));
                 }
             }
         }
+        assert_eq!(accounts[&CommitmentLevel::Processed], (None, None));
+        assert_eq!(
+            accounts[&CommitmentLevel::Confirmed],
+            (Some(slot + 1), None)
+        );
+        assert_eq!(
+            accounts[&CommitmentLevel::Finalized],
+            (None, Some(slot + 2))
+        );
     }
-}
 
-#[tonic::async_trait]
-impl Geyser for GrpcService {
-    type SubscribeStream = ReceiverStream<TonicResult<FilteredUpdate>>;
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_server_max_concurrent_streams() {
+        let config: ConfigGrpc = serde_json::from_value(serde_json::json!({
+            "address": "127.0.0.1:0",
+            "server_max_concurrent_streams": 1,
+        }))
+        .expect("valid config");
+        let (_snapshot_tx, _messages_tx, shutdown, BoundAddresses { grpc: address, .. }) =
+            GrpcService::create(ConfigTokio::default(), config, None, false)
+                .await
+                .expect("service created");
 
-    async fn subscribe(
-        &self,
-        mut request: Request<Streaming<SubscribeRequest>>,
-    ) -> TonicResult<Response<Self::SubscribeStream>> {
-        let id = self.subscribe_id.fetch_add(1, Ordering::Relaxed);
+        // both clients share the same connection
+        let mut client = GeyserClient::connect(format!("http://{address}"))
+            .await
+            .expect("connected");
+        let mut client2 = client.clone();
+        let (request_tx, request_rx) = mpsc::channel::<SubscribeRequest>(1);
+        let stream = client
+            .subscribe(ReceiverStream::new(request_rx))
+            .await
+            .expect("subscribed");
 
-        let x_request_snapshot = request.metadata().contains_key("x-request-snapshot");
-        let snapshot_rx = if x_request_snapshot {
-            self.snapshot_rx.lock().await.take()
-        } else {
-            None
-        };
-        let (stream_tx, stream_rx) = mpsc::channel(if snapshot_rx.is_some() {
-            self.config_snapshot_client_channel_capacity
-        } else {
-            self.config_channel_capacity
-        });
-        let (client_tx, client_rx) = mpsc::unbounded_channel();
-        let notify_exit1 = Arc::new(Notify::new());
-        let notify_exit2 = Arc::new(Notify::new());
+        let (_request_tx2, request_rx2) = mpsc::channel::<SubscribeRequest>(1);
+        let mut second = Box::pin(client2.subscribe(ReceiverStream::new(request_rx2)));
+        assert!(
+            tokio::time::timeout(Duration::from_millis(300), second.as_mut())
+                .await
+                .is_err(),
+            "second stream is not accepted while the first is open"
+        );
 
-        let ping_stream_tx = stream_tx.clone();
-        let ping_client_tx = client_tx.clone();
-        let ping_exit = Arc::clone(&notify_exit1);
-        tokio::spawn(async move {
-            let exit = ping_exit.notified();
-            tokio::pin!(exit);
+        drop(request_tx);
+        drop(stream);
+        tokio::time::timeout(Duration::from_secs(5), second)
+            .await
+            .expect("second stream is accepted after the first is closed")
+            .expect("subscribed");
+
+        shutdown.notify_one();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_admin_disconnect_client() {
+        let config: ConfigGrpc = serde_json::from_value(serde_json::json!({
+            "address": "127.0.0.1:0",
+            "admin": {"address": "127.0.0.1:0", "x_token": "admin"},
+        }))
+        .expect("valid config");
+        let (_snapshot_tx, _messages_tx, shutdown, addresses) =
+            GrpcService::create(ConfigTokio::default(), config, None, false)
+                .await
+                .expect("service created");
+        let (address, admin_address) = (addresses.grpc, addresses.admin.expect("admin service"));
+
+        let mut admin = GeyserAdminClient::connect(format!("http://{admin_address}"))
+            .await
+            .expect("connected");
+        fn with_token<T>(message: T) -> Request<T> {
+            let mut request = Request::new(message);
+            request
+                .metadata_mut()
+                .insert("x-token", "admin".parse().expect("valid header"));
+            request
+        }
+        let status = admin
+            .list_clients(ListClientsRequest {})
+            .await
+            .expect_err("token required");
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+        let mut client = GeyserClient::connect(format!("http://{address}"))
+            .await
+            .expect("connected");
+        let (request_tx, request_rx) = mpsc::channel(1);
+        request_tx
+            .send(SubscribeRequest {
+                slots: [("slots".to_owned(), SubscribeRequestFilterSlots::default())]
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            })
+            .await
+            .expect("alive");
+        let mut stream = client
+            .subscribe(ReceiverStream::new(request_rx))
+            .await
+            .expect("subscribed")
+            .into_inner();
 
+        // filter is applied by the client loop
+        let client_info = tokio::time::timeout(Duration::from_secs(5), async {
             loop {
-                tokio::select! {
-                    _ = &mut exit => {
-                        break;
-                    }
-                    _ = sleep(Duration::from_secs(10)) => {
-                        let msg = FilteredUpdate::new_empty(FilteredUpdateOneof::ping());
-                        match ping_stream_tx.try_send(Ok(msg)) {
-                            Ok(()) => {}
-                            Err(mpsc::error::TrySendError::Full(_)) => {}
-                            Err(mpsc::error::TrySendError::Closed(_)) => {
-                                let _ = ping_client_tx.send(None);
-                                break;
-                            }
-                        }
-                    }
+                let clients = admin
+                    .list_clients(with_token(ListClientsRequest {}))
+                    .await
+                    .expect("listed")
+                    .into_inner()
+                    .clients;
+                assert_eq!(clients.len(), 1);
+                if clients[0].filters.get("slots") == Some(&1) {
+                    break clients[0].clone();
                 }
+                sleep(Duration::from_millis(10)).await;
             }
-        });
+        })
+        .await
+        .expect("filter applied");
+        assert!(!client_info.peer.is_empty());
+        assert!(client_info.connected_at > 0);
 
-        let endpoint = request
-            .metadata()
-            .get("x-endpoint")
-            .and_then(|h| h.to_str().ok().map(|s| s.to_string()))
-            .unwrap_or_else(|| "".to_owned());
+        admin
+            .disconnect_client(with_token(DisconnectClientRequest {
+                id: client_info.id,
+                reason: "misbehaving".to_owned(),
+            }))
+            .await
+            .expect("disconnected");
+        let status = loop {
+            match tokio::time::timeout(Duration::from_secs(5), stream.message())
+                .await
+                .expect("timeout")
+            {
+                Ok(Some(_update)) => continue,
+                Ok(None) => panic!("stream closed without status"),
+                Err(status) => break status,
+            }
+        };
+        assert_eq!(status.code(), tonic::Code::Aborted);
+        assert_eq!(status.message(), "misbehaving");
 
-        let config_filter_limits = Arc::clone(&self.config_filter_limits);
-        let filter_names = Arc::clone(&self.filter_names);
-        let incoming_stream_tx = stream_tx.clone();
-        let incoming_client_tx = client_tx;
-        let incoming_exit = Arc::clone(&notify_exit2);
-        tokio::spawn(async move {
-            let exit = incoming_exit.notified();
-            tokio::pin!(exit);
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !admin
+                .list_clients(with_token(ListClientsRequest {}))
+                .await
+                .expect("listed")
+                .into_inner()
+                .clients
+                .is_empty()
+            {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("client removed");
+        let status = admin
+            .disconnect_client(with_token(DisconnectClientRequest {
+                id: client_info.id,
+                reason: String::new(),
+            }))
+            .await
+            .expect_err("client not found");
+        assert_eq!(status.code(), tonic::Code::NotFound);
 
-            loop {
-                tokio::select! {
-                    _ = &mut exit => {
-                        break;
-                    }
-                    message = request.get_mut().message() => match message {
-                        Ok(Some(request)) => {
-                            let mut filter_names = filter_names.lock().await;
-                            filter_names.try_clean();
+        shutdown.notify_one();
+    }
 
-                            if let Err(error) = match Filter::new(&request, &config_filter_limits, &mut filter_names) {
-                                Ok(filter) => {
-                                    if let Some(msg) = filter.get_pong_msg() {
-                                        if incoming_stream_tx.send(Ok(msg)).await.is_err() {
-                                            error!("client #{id}: stream closed");
-                                            let _ = incoming_client_tx.send(None);
-                                            break;
-                                        }
-                                        continue;
-                                    }
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_admin_announce_maintenance() {
+        let config: ConfigGrpc = serde_json::from_value(serde_json::json!({
+            "address": "127.0.0.1:0",
+            "unary_disabled": true,
+            "admin": {"address": "127.0.0.1:0", "x_token": "admin"},
+        }))
+        .expect("valid config");
+        let (_snapshot_tx, messages_tx, shutdown, addresses) =
+            GrpcService::create(ConfigTokio::default(), config, None, false)
+                .await
+                .expect("service created");
+        let (address, admin_address) = (addresses.grpc, addresses.admin.expect("admin service"));
+        let send = |message| {
+            metrics::message_queue_size_inc();
+            messages_tx.send(message).expect("geyser loop is alive");
+        };
 
-                                    match incoming_client_tx.send(Some((request.from_slot, filter))) {
-                                        Ok(()) => Ok(()),
-                                        Err(error) => Err(error.to_string()),
-                                    }
-                                },
-                                Err(error) => Err(error.to_string()),
-                            } {
-                                let err = Err(Status::invalid_argument(format!(
-                                    "failed to create filter: {error}"
-                                )));
-                                if incoming_stream_tx.send(err).await.is_err() {
-                                    let _ = incoming_client_tx.send(None);
-                                }
-                            }
-                        }
-                        Ok(None) => {
-                            break;
-                        }
-                        Err(_error) => {
-                            let _ = incoming_client_tx.send(None);
-                            break;
-                        }
+        let channel = Channel::from_shared(format!("http://{address}"))
+            .expect("valid uri")
+            .connect()
+            .await
+            .expect("connected");
+        let mut health = HealthClient::new(channel.clone());
+        let mut client = GeyserClient::new(channel);
+        let mut admin = GeyserAdminClient::connect(format!("http://{admin_address}"))
+            .await
+            .expect("connected");
+
+        fn with_token<T>(message: T) -> Request<T> {
+            let mut request = Request::new(message);
+            request
+                .metadata_mut()
+                .insert("x-token", "admin".parse().expect("valid header"));
+            request
+        }
+
+        async fn wait_status(client: &mut HealthClient<Channel>, expected: ServingStatus) {
+            let request = HealthCheckRequest {
+                service: "geyser.Geyser".to_owned(),
+            };
+            tokio::time::timeout(Duration::from_secs(5), async {
+                while client
+                    .check(request.clone())
+                    .await
+                    .expect("health check")
+                    .into_inner()
+                    .status()
+                    != expected
+                {
+                    sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .unwrap_or_else(|_| panic!("health status is not {expected:?}"));
+        }
+
+        async fn subscribe(client: &mut GeyserClient<Channel>) -> Streaming<SubscribeUpdate> {
+            client
+                .subscribe(futures::stream::pending::<SubscribeRequest>())
+                .await
+                .expect("subscribed")
+                .into_inner()
+        }
+
+        async fn recv_maintenance(
+            stream: &mut Streaming<SubscribeUpdate>,
+        ) -> SubscribeUpdateMaintenance {
+            tokio::time::timeout(Duration::from_secs(5), async {
+                loop {
+                    let update = stream
+                        .message()
+                        .await
+                        .expect("stream is not failed")
+                        .expect("stream is alive");
+                    if let Some(UpdateOneof::Maintenance(msg)) = update.update_oneof {
+                        break msg;
                     }
                 }
-            }
-        });
+            })
+            .await
+            .expect("maintenance received")
+        }
 
-        tokio::spawn(Self::client_loop(
-            id,
-            endpoint,
-            stream_tx,
-            client_rx,
-            snapshot_rx,
-            self.broadcast_tx.subscribe(),
-            self.replay_stored_slots_tx.clone(),
-            self.debug_clients_tx.clone(),
-            move || {
-                notify_exit1.notify_one();
-                notify_exit2.notify_one();
-            },
-        ));
+        send(create_message_slot(100, Some(99), SlotStatus::Processed));
+        send(create_message_slot(100, Some(99), SlotStatus::Finalized));
+        wait_status(&mut health, ServingStatus::Serving).await;
+
+        // client is registered before the subscribe call returns
+        let mut existing = subscribe(&mut client).await;
+        let response = admin
+            .announce_maintenance(with_token(AnnounceMaintenanceRequest {
+                reason: "upgrade".to_owned(),
+                expected_at_slot: Some(200),
+                drain_deadline_secs: 30,
+                not_serving: true,
+                clear: false,
+            }))
+            .await
+            .expect("announced")
+            .into_inner();
+        assert_eq!(response.clients, 1);
+
+        let msg = recv_maintenance(&mut existing).await;
+        assert_eq!(msg.reason, "upgrade");
+        assert_eq!(msg.expected_at_slot, Some(200));
+        assert_eq!(msg.drain_deadline_secs, 30);
+        assert!(msg.announced_at > 0);
+        wait_status(&mut health, ServingStatus::NotServing).await;
 
-        Ok(Response::new(ReceiverStream::new(stream_rx)))
+        // new clients receive the announcement right away
+        let mut new = subscribe(&mut client).await;
+        assert_eq!(recv_maintenance(&mut new).await, msg);
+
+        // cleared announcement is cancelled for connected clients only
+        let response = admin
+            .announce_maintenance(with_token(AnnounceMaintenanceRequest {
+                clear: true,
+                ..Default::default()
+            }))
+            .await
+            .expect("cleared")
+            .into_inner();
+        assert_eq!(response.clients, 2);
+        for stream in [&mut existing, &mut new] {
+            let cancelled = recv_maintenance(stream).await;
+            assert!(cancelled.cancelled);
+            assert_eq!(cancelled.reason, msg.reason);
+        }
+        wait_status(&mut health, ServingStatus::Serving).await;
+        let mut after = subscribe(&mut client).await;
+        assert!(
+            tokio::time::timeout(Duration::from_millis(200), recv_maintenance(&mut after))
+                .await
+                .is_err()
+        );
+
+        // admin service is stopped together with the server, once subscriptions are closed
+        drop((existing, new, after, client, health));
+        shutdown.notify_one();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while admin
+                .announce_maintenance(with_token(AnnounceMaintenanceRequest::default()))
+                .await
+                .is_ok()
+            {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("admin service stopped");
     }
 
-    async fn ping(&self, request: Request<PingRequest>) -> Result<Response<PongResponse>, Status> {
-        let count = request.get_ref().count;
-        let response = PongResponse { count };
-        Ok(Response::new(response))
+    async fn wait_transaction_status(
+        recent_transactions: &RecentTransactions,
+        signature: Signature,
+        check: impl Fn(&Result<GetTransactionStatusResponse, Status>) -> bool,
+    ) -> Result<GetTransactionStatusResponse, Status> {
+        let request = GetTransactionStatusRequest {
+            signature: signature.to_string(),
+        };
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let result = recent_transactions.get_status(&request).await;
+                if check(&result) {
+                    break result;
+                }
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("timeout")
     }
 
-    async fn get_latest_blockhash(
-        &self,
-        request: Request<GetLatestBlockhashRequest>,
-    ) -> Result<Response<GetLatestBlockhashResponse>, Status> {
-        if let Some(blocks_meta) = &self.blocks_meta {
-            blocks_meta
-                .get_block(
-                    |block| {
-                        block.block_height.map(|value| GetLatestBlockhashResponse {
-                            slot: block.slot,
-                            blockhash: block.blockhash.clone(),
-                            last_valid_block_height: value.block_height
-                                + MAX_RECENT_BLOCKHASHES as u64,
-                        })
-                    },
-                    request.get_ref().commitment,
-                )
-                .await
-        } else {
-            Err(Status::unimplemented("method disabled"))
+    #[test]
+    fn test_votes_aggregator() {
+        let mut votes = VotesAggregator::new(ConfigGrpcVotes {
+            window_slots: 2,
+            max_vote_accounts: 2,
+        });
+        let vote_pubkeys = (0..3).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+        let entry = |index: usize, voted_on_slot, latency_slots, count| SubscribeUpdateVotesEntry {
+            vote_pubkey: vote_pubkeys[index].to_bytes().to_vec(),
+            voted_on_slot,
+            latency_slots,
+            count,
+        };
+        let update = |slot, from_slot, entries: Vec<SubscribeUpdateVotesEntry>| {
+            let mut entries = entries;
+            entries.sort_unstable_by(|a, b| a.vote_pubkey.cmp(&b.vote_pubkey));
+            SubscribeUpdateVotes {
+                slot,
+                from_slot,
+                to_slot: from_slot + 1,
+                entries,
+            }
+        };
+
+        // window is closed by the processed status of the last slot
+        for message in [
+            create_message_vote(10, vote_pubkeys[0], 8),
+            create_message_vote(10, vote_pubkeys[0], 9),
+            create_message_vote(10, vote_pubkeys[1], 9),
+            create_message_vote(10, vote_pubkeys[2], 9), // over `max_vote_accounts`
+            create_message_transaction(10, Signature::new_unique(), None, false),
+        ] {
+            assert_eq!(votes.update(&message), vec![]);
         }
+        assert_eq!(
+            votes.update(&create_message_slot(10, Some(9), SlotStatus::Processed)),
+            vec![]
+        );
+        assert_eq!(
+            votes.update(&create_message_vote(11, vote_pubkeys[0], 10)),
+            vec![]
+        );
+        assert_eq!(
+            votes.update(&create_message_slot(11, Some(10), SlotStatus::Processed)),
+            vec![update(11, 10, vec![entry(0, 10, 1, 3), entry(1, 9, 1, 1)])]
+        );
+
+        // votes of the dead fork are removed on finalized
+        votes.update(&create_message_vote(12, vote_pubkeys[0], 11));
+        votes.update(&create_message_vote(13, vote_pubkeys[1], 11));
+        assert_eq!(
+            votes.update(&create_message_slot(13, Some(11), SlotStatus::Processed)),
+            vec![update(13, 12, vec![entry(1, 11, 2, 1)])]
+        );
+        assert_eq!(votes.slots.len(), 1);
+        votes.update(&create_message_slot(13, Some(11), SlotStatus::Finalized));
+        assert!(votes.slots.is_empty());
+
+        // window is closed by the slot from the next window if the last slot is skipped
+        votes.update(&create_message_vote(14, vote_pubkeys[2], 13));
+        assert_eq!(
+            votes.update(&create_message_slot(14, Some(13), SlotStatus::Processed)),
+            vec![]
+        );
+        assert_eq!(
+            votes.update(&create_message_slot(16, Some(14), SlotStatus::Processed)),
+            vec![update(16, 14, vec![entry(2, 13, 1, 1)])]
+        );
     }
 
-    async fn get_block_height(
-        &self,
-        request: Request<GetBlockHeightRequest>,
-    ) -> Result<Response<GetBlockHeightResponse>, Status> {
-        if let Some(blocks_meta) = &self.blocks_meta {
-            blocks_meta
-                .get_block(
-                    |block| {
-                        block.block_height.map(|value| GetBlockHeightResponse {
-                            block_height: value.block_height,
-                        })
-                    },
-                    request.get_ref().commitment,
-                )
-                .await
-        } else {
-            Err(Status::unimplemented("method disabled"))
+    #[test]
+    fn test_slot_stats_aggregator() {
+        let mut slot_stats = SlotStatsAggregator::new(ConfigGrpcSlotStats {
+            top_owners: 2,
+            max_owners: 1_000,
+        });
+        let owners = (0..3).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+        let account = |slot, pubkey, owner, data_len: usize, is_startup| {
+            Message::Account(MessageAccount {
+                account: Arc::new(MessageAccountInfo {
+                    pubkey,
+                    lamports: 42,
+                    owner,
+                    executable: false,
+                    rent_epoch: 0,
+                    data: vec![0; data_len].into(),
+                    write_version: 0,
+                    txn_signature: None,
+                    base58: Default::default(),
+                }),
+                slot,
+                is_startup,
+                created_at: Timestamp::from(SystemTime::now()),
+                confirmed_at_slot: None,
+                finalized_at_slot: None,
+            })
+        };
+        let owner = |index: usize, account_updates, data_bytes| SubscribeUpdateSlotStatsOwner {
+            owner: owners[index].to_bytes().to_vec(),
+            account_updates,
+            data_bytes,
+        };
+
+        let pubkey = Pubkey::new_unique();
+        for message in [
+            account(10, pubkey, owners[0], 10, false),
+            account(10, pubkey, owners[0], 20, false),
+            account(10, Pubkey::new_unique(), owners[1], 30, false),
+            account(10, Pubkey::new_unique(), owners[1], 40, false),
+            account(10, Pubkey::new_unique(), owners[1], 0, false),
+            account(10, Pubkey::new_unique(), owners[2], 50, false),
+            account(10, Pubkey::new_unique(), owners[2], 50, true), // startup
+            create_message_slot(10, Some(9), SlotStatus::FirstShredReceived),
+        ] {
+            assert_eq!(slot_stats.update(&message), None);
         }
+        assert_eq!(
+            slot_stats.update(&create_message_slot(10, Some(9), SlotStatus::Processed)),
+            Some(SubscribeUpdateSlotStats {
+                slot: 10,
+                account_updates: 6,
+                unique_accounts: 5,
+                data_bytes: 150,
+                top_owners: vec![owner(1, 3, 70), owner(0, 2, 30)],
+            })
+        );
+        assert!(slot_stats.slots.is_empty());
+
+        // slot without account updates
+        assert_eq!(
+            slot_stats.update(&create_message_slot(11, Some(10), SlotStatus::Processed)),
+            Some(SubscribeUpdateSlotStats {
+                slot: 11,
+                ..Default::default()
+            })
+        );
+
+        // dead slots and forks are removed
+        slot_stats.update(&account(12, Pubkey::new_unique(), owners[0], 1, false));
+        slot_stats.update(&account(13, Pubkey::new_unique(), owners[0], 1, false));
+        slot_stats.update(&account(14, Pubkey::new_unique(), owners[0], 1, false));
+        slot_stats.update(&create_message_slot(12, Some(11), SlotStatus::Dead));
+        assert_eq!(slot_stats.slots.len(), 2);
+        slot_stats.update(&create_message_slot(13, Some(11), SlotStatus::Finalized));
+        assert_eq!(slot_stats.slots.keys().copied().collect::<Vec<_>>(), [14]);
     }
 
-    async fn get_slot(
-        &self,
-        request: Request<GetSlotRequest>,
-    ) -> Result<Response<GetSlotResponse>, Status> {
-        if let Some(blocks_meta) = &self.blocks_meta {
-            blocks_meta
-                .get_block(
-                    |block| Some(GetSlotResponse { slot: block.slot }),
-                    request.get_ref().commitment,
-                )
-                .await
-        } else {
-            Err(Status::unimplemented("method disabled"))
+    #[test]
+    fn test_slot_stats_aggregator_max_owners() {
+        let mut slot_stats = SlotStatsAggregator::new(ConfigGrpcSlotStats {
+            top_owners: 1,
+            max_owners: 2,
+        });
+        let account = |owner| {
+            Message::Account(MessageAccount {
+                account: Arc::new(MessageAccountInfo {
+                    pubkey: Pubkey::new_unique(),
+                    lamports: 42,
+                    owner,
+                    executable: false,
+                    rent_epoch: 0,
+                    data: vec![0; 10].into(),
+                    write_version: 0,
+                    txn_signature: None,
+                    base58: Default::default(),
+                }),
+                slot: 10,
+                is_startup: false,
+                created_at: Timestamp::from(SystemTime::now()),
+                confirmed_at_slot: None,
+                finalized_at_slot: None,
+            })
+        };
+
+        // owner with the most updates comes after the limit is reached by other owners
+        let top = Pubkey::new_unique();
+        let mut owners = (0..10).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+        owners.extend([top; 5]);
+        owners.push(Pubkey::new_unique());
+        owners.push(top);
+        for owner in owners {
+            slot_stats.update(&account(owner));
+            assert!(slot_stats.slots[&10].owners.len() <= 2);
+            assert!(slot_stats.slots[&10].owners_heap.len() <= 2);
         }
+        let update = slot_stats
+            .update(&create_message_slot(10, Some(9), SlotStatus::Processed))
+            .expect("slot stats");
+        assert_eq!(update.account_updates, 17);
+        assert_eq!(update.top_owners.len(), 1);
+        assert_eq!(update.top_owners[0].owner, top.to_bytes().to_vec());
+        assert!(update.top_owners[0].account_updates >= 6);
     }
 
-    async fn is_blockhash_valid(
-        &self,
-        request: Request<IsBlockhashValidRequest>,
-    ) -> Result<Response<IsBlockhashValidResponse>, Status> {
-        if let Some(blocks_meta) = &self.blocks_meta {
-            let req = request.get_ref();
-            blocks_meta
-                .is_blockhash_valid(&req.blockhash, req.commitment)
-                .await
-        } else {
-            Err(Status::unimplemented("method disabled"))
-        }
+    #[tokio::test]
+    async fn test_recent_transactions() {
+        let (broadcast_tx, broadcast_rx) = broadcast::channel(16);
+        let recent_transactions = RecentTransactions::new(
+            ConfigGrpcRecentTransactions {
+                max_slots: 2,
+                max_transactions: 3,
+                include_votes: false,
+            },
+            1,
+            broadcast_rx,
+            Arc::new(CommitmentSlots::default()),
+        );
+        let send = |commitment, messages: Vec<Message>| {
+            let messages = messages.into_iter().map(|message| (0, message)).collect();
+            broadcast_tx
+                .send((commitment, Arc::new(messages)))
+                .expect("index is alive");
+        };
+        let signatures = (0..6).map(|_| Signature::new_unique()).collect::<Vec<_>>();
+        let err = TransactionError { err: vec![1, 2, 3] };
+
+        send(
+            CommitmentLevel::Processed,
+            vec![
+                create_message_transaction(10, signatures[0], None, false),
+                create_message_transaction(10, signatures[1], Some(err.clone()), false),
+                create_message_transaction(10, signatures[2], None, true),
+                create_message_slot(10, Some(9), SlotStatus::Processed),
+            ],
+        );
+        let status = wait_transaction_status(&recent_transactions, signatures[1], Result::is_ok)
+            .await
+            .expect("transaction is indexed");
+        assert_eq!(status.slot, 10);
+        assert_eq!(status.err, Some(err));
+        assert_eq!(status.commitment, CommitmentLevelProto::Processed as i32);
+        let status = wait_transaction_status(&recent_transactions, signatures[0], Result::is_ok)
+            .await
+            .expect("transaction is indexed");
+        assert_eq!(status.err, None);
+        let status = wait_transaction_status(&recent_transactions, signatures[2], |_| true)
+            .await
+            .expect_err("votes are not indexed");
+        assert_eq!(status.code(), tonic::Code::NotFound);
+        let status = recent_transactions
+            .get_status(&GetTransactionStatusRequest {
+                signature: "invalid".to_owned(),
+            })
+            .await
+            .expect_err("invalid signature");
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        // only processed broadcast is used
+        send(
+            CommitmentLevel::Confirmed,
+            vec![create_message_slot(10, Some(9), SlotStatus::Finalized)],
+        );
+        send(
+            CommitmentLevel::Processed,
+            vec![create_message_slot(10, Some(9), SlotStatus::Confirmed)],
+        );
+        wait_transaction_status(&recent_transactions, signatures[0], |result| {
+            result
+                .as_ref()
+                .is_ok_and(|status| status.commitment == CommitmentLevelProto::Confirmed as i32)
+        })
+        .await
+        .expect("transaction is indexed");
+
+        // slots behind `max_slots` are removed
+        send(
+            CommitmentLevel::Processed,
+            vec![create_message_transaction(13, signatures[3], None, false)],
+        );
+        wait_transaction_status(&recent_transactions, signatures[0], Result::is_err)
+            .await
+            .expect_err("slot is removed");
+        wait_transaction_status(&recent_transactions, signatures[3], Result::is_ok)
+            .await
+            .expect("transaction is indexed");
+
+        // the oldest slots are removed once `max_transactions` is reached
+        send(
+            CommitmentLevel::Processed,
+            vec![
+                create_message_transaction(14, signatures[4], None, false),
+                create_message_transaction(14, signatures[5], None, false),
+                create_message_transaction(14, Signature::new_unique(), None, false),
+            ],
+        );
+        wait_transaction_status(&recent_transactions, signatures[3], Result::is_err)
+            .await
+            .expect_err("slot is removed");
+        wait_transaction_status(&recent_transactions, signatures[5], Result::is_ok)
+            .await
+            .expect("transaction is indexed");
     }
 
-    async fn get_version(
-        &self,
-        _request: Request<GetVersionRequest>,
-    ) -> Result<Response<GetVersionResponse>, Status> {
-        Ok(Response::new(GetVersionResponse {
-            version: serde_json::to_string(&GrpcVersionInfo::default()).unwrap(),
-        }))
+    #[test]
+    fn test_recent_transactions_size() {
+        let assert_size = |inner: &RecentTransactionsInner| {
+            let errors_bytes = inner
+                .transactions
+                .values()
+                .map(|tx| RecentTransaction::get_err_size(&tx.err))
+                .sum::<usize>();
+            let signatures_count = inner
+                .slots
+                .values()
+                .map(|entry| entry.signatures.len())
+                .sum::<usize>();
+            assert_eq!(inner.errors_bytes, errors_bytes);
+            assert_eq!(inner.signatures_count, signatures_count);
+        };
+        let mut inner = RecentTransactionsInner::default();
+        let signatures = (0..3).map(|_| Signature::new_unique()).collect::<Vec<_>>();
+        let err = |len| Some(TransactionError { err: vec![0; len] });
+
+        let messages = [
+            create_message_transaction(10, signatures[0], err(3), false),
+            create_message_transaction(10, signatures[1], None, false),
+            // the same transaction on another fork
+            create_message_transaction(11, signatures[0], err(5), false),
+            create_message_transaction(12, signatures[2], err(7), false),
+        ];
+        inner.update(
+            &messages
+                .into_iter()
+                .map(|message| (0, message))
+                .collect::<Vec<_>>(),
+            false,
+        );
+        assert_size(&inner);
+        assert_eq!(inner.errors_bytes, 12);
+        assert!(inner.get_size() > 0);
+
+        inner.prune(1, 10);
+        assert_size(&inner);
+        assert_eq!(inner.errors_bytes, 12);
+        assert_eq!(inner.signatures_count, 2);
+
+        inner.prune(0, 0);
+        assert_size(&inner);
+        assert_eq!((inner.errors_bytes, inner.signatures_count), (0, 0));
+    }
+
+    #[test]
+    fn test_recent_transactions_commitment() {
+        let mut inner = RecentTransactionsInner::default();
+        let update = |inner: &mut RecentTransactionsInner, messages: Vec<Message>| {
+            inner.update(
+                &messages
+                    .into_iter()
+                    .map(|message| (0, message))
+                    .collect::<Vec<_>>(),
+                false,
+            )
+        };
+        // 13 is on another fork
+        update(
+            &mut inner,
+            vec![
+                create_message_slot(10, Some(9), SlotStatus::Processed),
+                create_message_slot(11, Some(10), SlotStatus::Processed),
+                create_message_slot(12, Some(11), SlotStatus::Processed),
+                create_message_slot(13, Some(10), SlotStatus::Processed),
+            ],
+        );
+        let commitments = |inner: &RecentTransactionsInner| {
+            inner
+                .slots
+                .values()
+                .map(|entry| entry.commitment)
+                .collect::<Vec<_>>()
+        };
+
+        // statuses of 10 and 11 are skipped
+        update(
+            &mut inner,
+            vec![create_message_slot(12, Some(11), SlotStatus::Confirmed)],
+        );
+        assert_eq!(
+            commitments(&inner),
+            [
+                CommitmentLevel::Confirmed,
+                CommitmentLevel::Confirmed,
+                CommitmentLevel::Confirmed,
+                CommitmentLevel::Processed
+            ]
+        );
+
+        // lagged, finalized 11 is known only from the highest slots
+        let commitment_slots = CommitmentSlots::default();
+        commitment_slots.finalized.slot.store(11, Ordering::Relaxed);
+        commitment_slots.confirmed.slot.store(14, Ordering::Relaxed); // not received yet
+        inner.resync(&commitment_slots);
+        assert_eq!(
+            commitments(&inner),
+            [
+                CommitmentLevel::Finalized,
+                CommitmentLevel::Finalized,
+                CommitmentLevel::Confirmed,
+                CommitmentLevel::Processed
+            ]
+        );
     }
 }
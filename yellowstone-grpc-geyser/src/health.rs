@@ -0,0 +1,266 @@
+//! Pipeline liveness reported by the gRPC health service and `/healthz`.
+
+use {
+    crate::metrics,
+    solana_sdk::clock::Slot,
+    std::{
+        sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        time::{Duration, Instant},
+    },
+    tokio::time::{interval, MissedTickBehavior},
+    tonic::server::NamedService,
+    tonic_health::server::HealthReporter,
+    tracing::{info, warn},
+    yellowstone_grpc_proto::plugin::message::{Message, SlotStatus},
+};
+
+/// Blockhashes are warmed up once a finalized slot is received and at least
+/// `warmup_block_metas` distinct block metas are seen, on a chain shorter than that all
+/// block metas since genesis are enough
+pub const fn is_blockhashes_warmed_up(
+    block_metas: usize,
+    warmup_block_metas: usize,
+    finalized: Option<Slot>,
+) -> bool {
+    match finalized {
+        Some(slot) => {
+            let chain_len = slot.saturating_add(1);
+            let required = if (warmup_block_metas as u64) < chain_len {
+                warmup_block_metas
+            } else {
+                chain_len as usize
+            };
+            block_metas >= required
+        }
+        None => false,
+    }
+}
+
+/// Serving only once the pipeline is warmed up (first finalized slot received and enough
+/// block metas for the blockhash map) and messages keep flowing through the geyser loop.
+/// Not serving after planned maintenance is announced with `not_serving`, until it is cleared.
+#[derive(Debug)]
+pub struct PipelineHealth {
+    started: Instant,
+    stale_timeout: Duration,
+    warmup_block_metas: usize,
+    finalized: AtomicBool,
+    finalized_slot: AtomicU64,
+    block_metas: AtomicUsize,
+    // milliseconds since `started`, `u64::MAX` if nothing received yet
+    last_message_ms: AtomicU64,
+    maintenance: AtomicBool,
+}
+
+impl PipelineHealth {
+    pub fn new(stale_timeout: Duration, warmup_block_metas: usize) -> Self {
+        Self {
+            started: Instant::now(),
+            stale_timeout,
+            warmup_block_metas,
+            finalized: AtomicBool::new(false),
+            finalized_slot: AtomicU64::new(0),
+            block_metas: AtomicUsize::new(0),
+            last_message_ms: AtomicU64::new(u64::MAX),
+            maintenance: AtomicBool::new(false),
+        }
+    }
+
+    pub fn on_message(&self, message: &Message) {
+        self.on_message_at(message, Instant::now())
+    }
+
+    fn on_message_at(&self, message: &Message, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.started).as_millis() as u64;
+        self.last_message_ms.store(elapsed, Ordering::Relaxed);
+
+        match message {
+            Message::Slot(msg) if msg.status == SlotStatus::Finalized => {
+                self.finalized_slot.fetch_max(msg.slot, Ordering::Relaxed);
+                self.finalized.store(true, Ordering::Relaxed);
+            }
+            Message::BlockMeta(_) => {
+                if self.block_metas.load(Ordering::Relaxed) < self.warmup_block_metas {
+                    self.block_metas.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Stop serving until restart or `clear_maintenance`, so load balancers don't route new
+    /// subscriptions
+    pub fn set_maintenance(&self) {
+        self.maintenance.store(true, Ordering::Relaxed);
+    }
+
+    /// Serve again once the announced maintenance is cancelled
+    pub fn clear_maintenance(&self) {
+        self.maintenance.store(false, Ordering::Relaxed);
+    }
+
+    fn is_maintenance(&self) -> bool {
+        self.maintenance.load(Ordering::Relaxed)
+    }
+
+    pub fn is_serving(&self) -> bool {
+        self.is_serving_at(Instant::now())
+    }
+
+    fn is_serving_at(&self, now: Instant) -> bool {
+        if self.is_maintenance() {
+            return false;
+        }
+
+        let finalized = self
+            .finalized
+            .load(Ordering::Relaxed)
+            .then(|| self.finalized_slot.load(Ordering::Relaxed));
+        if !is_blockhashes_warmed_up(
+            self.block_metas.load(Ordering::Relaxed),
+            self.warmup_block_metas,
+            finalized,
+        ) {
+            return false;
+        }
+
+        let last_message_ms = self.last_message_ms.load(Ordering::Relaxed);
+        let elapsed = now.saturating_duration_since(self.started).as_millis() as u64;
+        elapsed.saturating_sub(last_message_ms) <= self.stale_timeout.as_millis() as u64
+    }
+
+    /// Update the status of service `S` on every change, never returns
+    pub async fn report<S: NamedService>(&self, mut reporter: HealthReporter) {
+        reporter.set_not_serving::<S>().await;
+        metrics::set_grpc_serving(false);
+
+        let period =
+            (self.stale_timeout / 4).clamp(Duration::from_millis(10), Duration::from_secs(1));
+        let mut interval = interval(period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        let mut serving = false;
+        loop {
+            interval.tick().await;
+            let is_serving = self.is_serving();
+            if is_serving == serving {
+                continue;
+            }
+
+            serving = is_serving;
+            if serving {
+                info!("pipeline is healthy, serving");
+                reporter.set_serving::<S>().await;
+            } else if self.is_maintenance() {
+                warn!("maintenance is announced, not serving");
+                reporter.set_not_serving::<S>().await;
+            } else {
+                warn!(
+                    "no messages processed for {:?} or pipeline is not warmed up, not serving",
+                    self.stale_timeout
+                );
+                reporter.set_not_serving::<S>().await;
+            }
+            metrics::set_grpc_serving(serving);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::sync::Arc,
+        yellowstone_grpc_proto::{
+            plugin::message::{MessageBlockMeta, MessageSlot},
+            prelude::SubscribeUpdateBlockMeta,
+        },
+    };
+
+    fn create_message_slot(slot: u64, status: SlotStatus) -> Message {
+        Message::Slot(MessageSlot {
+            slot,
+            parent: None,
+            status,
+            dead_error: None,
+            created_at: Default::default(),
+        })
+    }
+
+    fn create_message_block_meta(slot: u64) -> Message {
+        Message::BlockMeta(Arc::new(MessageBlockMeta {
+            block_meta: SubscribeUpdateBlockMeta {
+                slot,
+                ..Default::default()
+            },
+            created_at: Default::default(),
+        }))
+    }
+
+    #[test]
+    fn test_not_serving_until_finalized() {
+        let health = PipelineHealth::new(Duration::from_secs(30), 0);
+        let now = health.started;
+        assert!(!health.is_serving_at(now));
+
+        health.on_message_at(&create_message_slot(1, SlotStatus::Processed), now);
+        health.on_message_at(&create_message_slot(1, SlotStatus::Confirmed), now);
+        assert!(!health.is_serving_at(now));
+
+        health.on_message_at(&create_message_slot(1, SlotStatus::Finalized), now);
+        assert!(health.is_serving_at(now));
+    }
+
+    #[test]
+    fn test_not_serving_until_blockhashes_warmed_up() {
+        let health = PipelineHealth::new(Duration::from_secs(30), 3);
+        let now = health.started;
+        health.on_message_at(&create_message_slot(100, SlotStatus::Finalized), now);
+        for slot in 0..2 {
+            health.on_message_at(&create_message_block_meta(slot), now);
+            assert!(!health.is_serving_at(now));
+        }
+        health.on_message_at(&create_message_block_meta(2), now);
+        assert!(health.is_serving_at(now));
+    }
+
+    #[test]
+    fn test_warmup_short_chain() {
+        assert!(!is_blockhashes_warmed_up(10, 0, None));
+        assert!(!is_blockhashes_warmed_up(2, 332, Some(1_000)));
+        assert!(is_blockhashes_warmed_up(332, 332, Some(1_000)));
+        // test validator with 5 slots
+        assert!(!is_blockhashes_warmed_up(4, 332, Some(4)));
+        assert!(is_blockhashes_warmed_up(5, 332, Some(4)));
+    }
+
+    #[test]
+    fn test_not_serving_on_maintenance() {
+        let health = PipelineHealth::new(Duration::from_secs(30), 0);
+        let now = health.started;
+        health.on_message_at(&create_message_slot(1, SlotStatus::Finalized), now);
+        assert!(health.is_serving_at(now));
+
+        health.set_maintenance();
+        assert!(!health.is_serving_at(now));
+        health.on_message_at(&create_message_slot(2, SlotStatus::Finalized), now);
+        assert!(!health.is_serving_at(now));
+
+        health.clear_maintenance();
+        assert!(health.is_serving_at(now));
+    }
+
+    #[test]
+    fn test_stale_and_resumed() {
+        let health = PipelineHealth::new(Duration::from_secs(30), 0);
+        let start = health.started;
+        health.on_message_at(&create_message_slot(1, SlotStatus::Finalized), start);
+        assert!(health.is_serving_at(start + Duration::from_secs(30)));
+        assert!(!health.is_serving_at(start + Duration::from_secs(31)));
+
+        let resumed = start + Duration::from_secs(60);
+        health.on_message_at(&create_message_slot(2, SlotStatus::Processed), resumed);
+        assert!(health.is_serving_at(resumed));
+        assert!(!health.is_serving_at(resumed + Duration::from_secs(31)));
+    }
+}
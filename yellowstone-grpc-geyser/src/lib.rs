@@ -1,13 +1,44 @@
+pub mod admin;
+pub mod check;
 pub mod config;
 pub mod grpc;
+pub mod health;
 pub mod metrics;
 pub mod plugin;
+pub mod preset;
+pub mod quota;
+pub mod record;
+pub mod source;
+pub mod trace;
 pub mod version;
 
-pub fn get_thread_name() -> String {
+/// Multi-threaded runtime owned by the plugin, `name` is the label of `tokio_runtime_workers`
+/// metric
+pub fn create_runtime(
+    config: &config::ConfigTokio,
+    name: &'static str,
+) -> std::io::Result<tokio::runtime::Runtime> {
     use std::sync::atomic::{AtomicU64, Ordering};
 
     static ATOMIC_ID: AtomicU64 = AtomicU64::new(0);
-    let id = ATOMIC_ID.fetch_add(1, Ordering::Relaxed);
-    format!("solGeyserGrpc{id:02}")
+
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    if let Some(worker_threads) = config.worker_threads {
+        builder.worker_threads(worker_threads);
+    }
+    if let Some(tokio_cpus) = config.affinity.clone() {
+        builder.on_thread_start(move || {
+            affinity::set_thread_affinity(&tokio_cpus).expect("failed to set affinity")
+        });
+    }
+    let thread_name_prefix = config.thread_name_prefix.clone();
+    let runtime = builder
+        .thread_name_fn(move || {
+            let id = ATOMIC_ID.fetch_add(1, Ordering::Relaxed);
+            format!("{thread_name_prefix}{id:02}")
+        })
+        .enable_all()
+        .build()?;
+    metrics::set_tokio_runtime_workers(name, runtime.metrics().num_workers());
+    Ok(runtime)
 }
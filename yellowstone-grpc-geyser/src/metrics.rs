@@ -1,9 +1,10 @@
 use {
-    crate::{config::ConfigPrometheus, version::VERSION as VERSION_INFO},
+    crate::{admin::ClientRegistry, config::ConfigPrometheus, version::VERSION as VERSION_INFO},
     agave_geyser_plugin_interface::geyser_plugin_interface::SlotStatus as GeyserSlosStatus,
     http_body_util::{combinators::BoxBody, BodyExt, Empty as BodyEmpty, Full as BodyFull},
     hyper::{
         body::{Bytes, Incoming as BodyIncoming},
+        header::{AUTHORIZATION, WWW_AUTHENTICATE},
         service::service_fn,
         Request, Response, StatusCode,
     },
@@ -11,20 +12,23 @@ use {
         rt::tokio::{TokioExecutor, TokioIo},
         server::conn::auto::Builder as ServerBuilder,
     },
-    log::{error, info},
-    prometheus::{IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder},
+    prometheus::{
+        exponential_buckets, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge,
+        IntGaugeVec, Opts, Registry, TextEncoder,
+    },
     solana_sdk::clock::Slot,
     std::{
-        collections::{hash_map::Entry as HashMapEntry, HashMap},
         convert::Infallible,
+        net::IpAddr,
         sync::{Arc, Once},
+        time::Duration,
     },
-    tokio::{
-        net::TcpListener,
-        sync::{mpsc, oneshot, Notify},
-        task::JoinHandle,
+    tokio::{net::TcpListener, sync::Notify},
+    tracing::{error, info},
+    yellowstone_grpc_proto::plugin::{
+        filter::{limits::FilterLimitsAccessCidr, Filter},
+        message::{CommitmentLevel, SlotStatus},
     },
-    yellowstone_grpc_proto::plugin::{filter::Filter, message::SlotStatus},
 };
 
 lazy_static::lazy_static! {
@@ -35,6 +39,11 @@ lazy_static::lazy_static! {
         &["buildts", "git", "package", "proto", "rustc", "solana", "version"]
     ).unwrap();
 
+    static ref PLUGIN_BUILD_INFO: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("plugin_build_info", "Plugin build info, constant 1"),
+        &["buildts", "geyser_interface", "git", "package", "proto", "rustc", "solana", "version"]
+    ).unwrap();
+
     static ref SLOT_STATUS: IntGaugeVec = IntGaugeVec::new(
         Opts::new("slot_status", "Lastest received slot from Geyser"),
         &["status"]
@@ -50,10 +59,20 @@ lazy_static::lazy_static! {
         &["reason"]
     ).unwrap();
 
+    static ref INVALID_BLOCKS_MODE: IntCounterVec = IntCounterVec::new(
+        Opts::new("invalid_blocks_mode_total", "Number of failed block reconstructions by failure mode"),
+        &["mode"]
+    ).unwrap();
+
     static ref MESSAGE_QUEUE_SIZE: IntGauge = IntGauge::new(
         "message_queue_size", "Size of geyser message queue"
     ).unwrap();
 
+    static ref QUEUE_SIZE: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("queue_size", "Size of plugin queues"),
+        &["queue"]
+    ).unwrap();
+
     static ref CONNECTIONS_TOTAL: IntGauge = IntGauge::new(
         "connections_total", "Total number of connections to gRPC service"
     ).unwrap();
@@ -63,124 +82,149 @@ lazy_static::lazy_static! {
         &["endpoint", "subscription"]
     ).unwrap();
 
+    static ref GEYSER_SLOT: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("geyser_slot", "Highest slot received from Geyser by commitment"),
+        &["commitment"]
+    ).unwrap();
+
+    static ref GRPC_SERVING: IntGauge = IntGauge::new(
+        "grpc_serving", "gRPC health status, 1 if serving"
+    ).unwrap();
+
+    static ref TOKIO_RUNTIME_WORKERS: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("tokio_runtime_workers", "Number of worker threads of runtimes owned by the plugin"),
+        &["runtime"]
+    ).unwrap();
+
+    static ref PROMETHEUS_REQUESTS_REJECTED: IntCounterVec = IntCounterVec::new(
+        Opts::new("prometheus_requests_rejected_total", "Number of rejected requests to Prometheus endpoint by reason"),
+        &["reason"]
+    ).unwrap();
+
+    static ref FILTER_UPDATES_REJECTED: IntCounterVec = IntCounterVec::new(
+        Opts::new("filter_updates_rejected_total", "Number of rejected filter updates by reason"),
+        &["reason"]
+    ).unwrap();
+
+    pub(crate) static ref FILTER_UPDATES_IDENTICAL: IntCounter = IntCounter::new(
+        "filter_updates_identical_total", "Number of filter updates skipped because the filter is not changed"
+    ).unwrap();
+
+    static ref FILTER_BUILD_DURATION: Histogram = Histogram::with_opts(
+        HistogramOpts::new("filter_build_duration_seconds", "Time to build the filter of the subscribe request")
+            .buckets(exponential_buckets(0.0001, 4.0, 10).unwrap())
+    ).unwrap();
+
+    static ref FILTER_PUBKEYS_CACHE: IntCounterVec = IntCounterVec::new(
+        Opts::new("filter_pubkeys_cache_total", "Number of Pubkey lists of filters found in the shared cache or created"),
+        &["result"]
+    ).unwrap();
+
+    static ref LAG_SKIPPED: IntCounterVec = IntCounterVec::new(
+        Opts::new("lag_skipped_total", "Number of skipped batches and updates for clients with `on_lag: skip`"),
+        &["endpoint", "kind"]
+    ).unwrap();
+
+    static ref SEEN_SIGNATURES_SUPPRESSED: IntCounterVec = IntCounterVec::new(
+        Opts::new("seen_signatures_suppressed_total", "Number of transaction updates suppressed by `seen_signatures_bloom`"),
+        &["endpoint"]
+    ).unwrap();
+
+    static ref PDA_DERIVATIONS: IntCounterVec = IntCounterVec::new(
+        Opts::new("pda_derivations_total", "Number of `create_program_address` calls by `pda` templates of accounts filters"),
+        &["endpoint"]
+    ).unwrap();
+
+    static ref QUOTA_USED: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("quota_used", "Bytes and messages sent to the client in the current quota window"),
+        &["endpoint", "client", "kind"]
+    ).unwrap();
+
+    static ref QUOTA_EXCEEDED: IntCounterVec = IntCounterVec::new(
+        Opts::new("quota_exceeded_total", "Number of clients reached soft or hard quota"),
+        &["endpoint", "limit"]
+    ).unwrap();
+
+    static ref RECENT_TRANSACTIONS: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("recent_transactions", "Size of recent transactions index"),
+        &["kind"]
+    ).unwrap();
+
+    static ref REPLAY_STORED_SLOTS: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("replay_stored_slots", "Size of messages stored for replay"),
+        &["kind"]
+    ).unwrap();
+
+    static ref REPLAY_STORED_SLOT_BYTES: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("replay_stored_slot_bytes", "Estimated bytes of messages stored for the slot of the last status"),
+        &["status"]
+    ).unwrap();
+
+    static ref REPLAY_STORED_SLOTS_EVICTED: IntCounterVec = IntCounterVec::new(
+        Opts::new("replay_stored_slots_evicted_total", "Number of slots evicted from replay storage by limit"),
+        &["limit"]
+    ).unwrap();
+
+    static ref REPLAY_STORED_SLOTS_OVER_BUDGET: IntCounter = IntCounter::new(
+        "replay_stored_slots_over_budget_total", "Number of times replay storage stayed over the bytes budget without evictable slots"
+    ).unwrap();
+
     static ref MISSED_STATUS_MESSAGE: IntCounterVec = IntCounterVec::new(
         Opts::new("missed_status_message_total", "Number of missed messages by commitment"),
         &["status"]
     ).unwrap();
-}
-
-#[derive(Debug)]
-pub enum DebugClientMessage {
-    UpdateFilter { id: usize, filter: Box<Filter> },
-    UpdateSlot { id: usize, slot: Slot },
-    Removed { id: usize },
-}
 
-impl DebugClientMessage {
-    pub fn maybe_send(tx: &Option<mpsc::UnboundedSender<Self>>, get_msg: impl FnOnce() -> Self) {
-        if let Some(tx) = tx {
-            let _ = tx.send(get_msg());
-        }
-    }
-}
+    static ref LATE_MESSAGES: IntCounterVec = IntCounterVec::new(
+        Opts::new("late_messages_total", "Number of messages received after the slot status was sent, not sent with the commitment"),
+        &["commitment"]
+    ).unwrap();
 
-#[derive(Debug)]
-struct DebugClientStatus {
-    filter: Box<Filter>,
-    processed_slot: Slot,
-}
+    pub(crate) static ref SLOT_STATUS_DUPLICATES: IntCounterVec = IntCounterVec::new(
+        Opts::new("slot_status_duplicates_total", "Number of ignored duplicated or regressed slot statuses"),
+        &["status"]
+    ).unwrap();
 
-#[derive(Debug)]
-struct DebugClientStatuses {
-    requests_tx: mpsc::UnboundedSender<oneshot::Sender<String>>,
-    jh: JoinHandle<()>,
-}
+    static ref MESSAGES_ORDER_VIOLATIONS: IntCounterVec = IntCounterVec::new(
+        Opts::new("messages_order_violations_total", "Number of messages received from the source out of the expected order"),
+        &["violation"]
+    ).unwrap();
 
-impl Drop for DebugClientStatuses {
-    fn drop(&mut self) {
-        self.jh.abort();
-    }
-}
+    static ref VOTES_DROPPED: IntCounter = IntCounter::new(
+        "votes_dropped_total", "Number of vote transactions not aggregated because of `votes.max_vote_accounts`"
+    ).unwrap();
 
-impl DebugClientStatuses {
-    fn new(clients_rx: mpsc::UnboundedReceiver<DebugClientMessage>) -> Arc<Self> {
-        let (requests_tx, requests_rx) = mpsc::unbounded_channel();
-        let jh = tokio::spawn(Self::run(clients_rx, requests_rx));
-        Arc::new(Self { requests_tx, jh })
-    }
+    static ref RECORD_MESSAGES_DROPPED: IntCounter = IntCounter::new(
+        "record_messages_dropped_total", "Number of messages not recorded because the writer is behind"
+    ).unwrap();
 
-    async fn run(
-        mut clients_rx: mpsc::UnboundedReceiver<DebugClientMessage>,
-        mut requests_rx: mpsc::UnboundedReceiver<oneshot::Sender<String>>,
-    ) {
-        let mut clients = HashMap::<usize, DebugClientStatus>::new();
-        loop {
-            tokio::select! {
-                Some(message) = clients_rx.recv() => match message {
-                    DebugClientMessage::UpdateFilter { id, filter } => {
-                        match clients.entry(id) {
-                            HashMapEntry::Occupied(mut entry) => {
-                                entry.get_mut().filter = filter;
-                            }
-                            HashMapEntry::Vacant(entry) => {
-                                entry.insert(DebugClientStatus {
-                                    filter,
-                                    processed_slot: 0,
-                                });
-                            }
-                        }
-                    }
-                    DebugClientMessage::UpdateSlot { id, slot } => {
-                        if let Some(status) = clients.get_mut(&id) {
-                            status.processed_slot = slot;
-                        }
-                    }
-                    DebugClientMessage::Removed { id } => {
-                        clients.remove(&id);
-                    }
-                },
-                Some(tx) = requests_rx.recv() => {
-                    let mut statuses: Vec<(usize, String)> = clients.iter().map(|(id, status)| {
-                        (*id, format!("client#{id:06}, {}, {:?}", status.processed_slot, status.filter))
-                    }).collect();
-                    statuses.sort();
-
-                    let mut status = statuses.into_iter().fold(String::new(), |mut acc: String, (_id, status)| {
-                        if !acc.is_empty() {
-                            acc += "\n";
-                        }
-                        acc + &status
-                    });
-                    if !status.is_empty() {
-                        status += "\n";
-                    }
+    static ref BROADCAST_BATCH_BYTES: Histogram = Histogram::with_opts(
+        HistogramOpts::new("broadcast_batch_bytes", "Estimated encoded bytes of broadcasted batches")
+            .buckets(exponential_buckets(1024.0, 4.0, 10).unwrap())
+    ).unwrap();
 
-                    let _ = tx.send(status);
-                },
-            }
-        }
-    }
+    static ref BLOCK_ASSEMBLY_LAG: Histogram = Histogram::with_opts(
+        HistogramOpts::new("block_assembly_lag_seconds", "Time from the hand-off of the message which completed the block to the broadcast of the block")
+            .buckets(exponential_buckets(0.0001, 4.0, 10).unwrap())
+    ).unwrap();
 
-    async fn get_statuses(&self) -> anyhow::Result<String> {
-        let (tx, rx) = oneshot::channel();
-        self.requests_tx
-            .send(tx)
-            .map_err(|_error| anyhow::anyhow!("failed to send request"))?;
-        rx.await
-            .map_err(|_error| anyhow::anyhow!("failed to wait response"))
-    }
+    static ref BLOCK_ASSEMBLY_OVERFLOW: IntCounter = IntCounter::new(
+        "block_assembly_overflow_total", "Number of messages not handed off to the block assembly because it is behind"
+    ).unwrap();
 }
 
 #[derive(Debug)]
 pub struct PrometheusService {
-    debug_clients_statuses: Option<Arc<DebugClientStatuses>>,
     shutdown: Arc<Notify>,
 }
 
 impl PrometheusService {
+    pub async fn bind(config: &ConfigPrometheus) -> std::io::Result<TcpListener> {
+        TcpListener::bind(&config.address).await
+    }
+
     pub async fn new(
         config: Option<ConfigPrometheus>,
-        debug_clients_rx: Option<mpsc::UnboundedReceiver<DebugClientMessage>>,
+        debug_clients: Option<Arc<ClientRegistry>>,
     ) -> std::io::Result<Self> {
         static REGISTER: Once = Once::new();
         REGISTER.call_once(|| {
@@ -192,13 +236,42 @@ impl PrometheusService {
                 };
             }
             register!(VERSION);
+            register!(PLUGIN_BUILD_INFO);
             register!(SLOT_STATUS);
             register!(SLOT_STATUS_PLUGIN);
             register!(INVALID_FULL_BLOCKS);
+            register!(INVALID_BLOCKS_MODE);
             register!(MESSAGE_QUEUE_SIZE);
+            register!(QUEUE_SIZE);
             register!(CONNECTIONS_TOTAL);
             register!(SUBSCRIPTIONS_TOTAL);
+            register!(FILTER_UPDATES_REJECTED);
+            register!(FILTER_UPDATES_IDENTICAL);
+            register!(FILTER_BUILD_DURATION);
+            register!(FILTER_PUBKEYS_CACHE);
             register!(MISSED_STATUS_MESSAGE);
+            register!(LATE_MESSAGES);
+            register!(SLOT_STATUS_DUPLICATES);
+            register!(MESSAGES_ORDER_VIOLATIONS);
+            register!(BROADCAST_BATCH_BYTES);
+            register!(BLOCK_ASSEMBLY_LAG);
+            register!(BLOCK_ASSEMBLY_OVERFLOW);
+            register!(RECENT_TRANSACTIONS);
+            register!(REPLAY_STORED_SLOTS);
+            register!(REPLAY_STORED_SLOT_BYTES);
+            register!(REPLAY_STORED_SLOTS_EVICTED);
+            register!(REPLAY_STORED_SLOTS_OVER_BUDGET);
+            register!(VOTES_DROPPED);
+            register!(RECORD_MESSAGES_DROPPED);
+            register!(LAG_SKIPPED);
+            register!(SEEN_SIGNATURES_SUPPRESSED);
+            register!(PDA_DERIVATIONS);
+            register!(QUOTA_USED);
+            register!(QUOTA_EXCEEDED);
+            register!(GEYSER_SLOT);
+            register!(GRPC_SERVING);
+            register!(TOKIO_RUNTIME_WORKERS);
+            register!(PROMETHEUS_REQUESTS_REJECTED);
 
             VERSION
                 .with_label_values(&[
@@ -211,26 +284,34 @@ impl PrometheusService {
                     VERSION_INFO.version,
                 ])
                 .inc();
+            PLUGIN_BUILD_INFO
+                .with_label_values(&[
+                    VERSION_INFO.buildts,
+                    VERSION_INFO.geyser_interface,
+                    VERSION_INFO.git,
+                    VERSION_INFO.package,
+                    VERSION_INFO.proto,
+                    VERSION_INFO.rustc,
+                    VERSION_INFO.solana,
+                    VERSION_INFO.version,
+                ])
+                .set(1);
         });
 
         let shutdown = Arc::new(Notify::new());
-        let mut debug_clients_statuses = None;
-        if let Some(ConfigPrometheus { address }) = config {
-            if let Some(debug_clients_rx) = debug_clients_rx {
-                debug_clients_statuses = Some(DebugClientStatuses::new(debug_clients_rx));
-            }
-            let debug_clients_statuses2 = debug_clients_statuses.clone();
+        if let Some(config) = config {
+            let access = Arc::new(PrometheusAccess::new(&config));
 
             let shutdown = Arc::clone(&shutdown);
-            let listener = TcpListener::bind(&address).await?;
-            info!("start prometheus server: {address}");
+            let listener = Self::bind(&config).await?;
+            info!("start prometheus server: {}", config.address);
             tokio::spawn(async move {
                 loop {
-                    let stream = tokio::select! {
+                    let (stream, addr) = tokio::select! {
                         () = shutdown.notified() => break,
                         maybe_conn = listener.accept() => {
                             match maybe_conn {
-                                Ok((stream, _addr)) => stream,
+                                Ok(conn) => conn,
                                 Err(error) => {
                                     error!("failed to accept new connection: {error}");
                                     break;
@@ -238,40 +319,24 @@ impl PrometheusService {
                             }
                         }
                     };
-                    let debug_clients_statuses = debug_clients_statuses2.clone();
+                    let debug_clients = debug_clients.clone();
+                    let access = Arc::clone(&access);
                     tokio::spawn(async move {
                         if let Err(error) = ServerBuilder::new(TokioExecutor::new())
                             .serve_connection(
                                 TokioIo::new(stream),
                                 service_fn(move |req: Request<BodyIncoming>| {
-                                    let debug_clients_statuses = debug_clients_statuses.clone();
+                                    let debug_clients = debug_clients.clone();
+                                    let access = Arc::clone(&access);
                                     async move {
-                                        match req.uri().path() {
-                                            "/metrics" => metrics_handler(),
-                                            "/debug_clients" => {
-                                                if let Some(debug_clients_statuses) =
-                                                    &debug_clients_statuses
-                                                {
-                                                    let (status, body) =
-                                                        match debug_clients_statuses
-                                                            .get_statuses()
-                                                            .await
-                                                        {
-                                                            Ok(body) => (StatusCode::OK, body),
-                                                            Err(error) => (
-                                                                StatusCode::INTERNAL_SERVER_ERROR,
-                                                                error.to_string(),
-                                                            ),
-                                                        };
-                                                    Response::builder().status(status).body(
-                                                        BodyFull::new(Bytes::from(body)).boxed(),
-                                                    )
-                                                } else {
-                                                    not_found_handler()
-                                                }
-                                            }
-                                            _ => not_found_handler(),
-                                        }
+                                        request_handler(
+                                            &req,
+                                            addr.ip(),
+                                            &access,
+                                            debug_clients.as_deref(),
+                                            GRPC_SERVING.get() == 1,
+                                        )
+                                        .await
                                     }
                                 }),
                             )
@@ -284,18 +349,108 @@ impl PrometheusService {
             });
         }
 
-        Ok(PrometheusService {
-            debug_clients_statuses,
-            shutdown,
-        })
+        Ok(PrometheusService { shutdown })
     }
 
     pub fn shutdown(self) {
-        drop(self.debug_clients_statuses);
         self.shutdown.notify_one();
     }
 }
 
+/// Required `Authorization` header and allowed remote addresses, `/healthz` is not restricted
+#[derive(Debug, Default)]
+struct PrometheusAccess {
+    authorization: Option<String>,
+    allowed_cidrs: Vec<FilterLimitsAccessCidr>,
+}
+
+impl PrometheusAccess {
+    fn new(config: &ConfigPrometheus) -> Self {
+        Self {
+            authorization: config.auth.as_ref().map(|auth| auth.get_header_value()),
+            allowed_cidrs: config.allowed_cidrs.clone(),
+        }
+    }
+
+    fn check<B>(&self, req: &Request<B>, remote_ip: IpAddr) -> Result<(), &'static str> {
+        // health checks of load balancers are not restricted
+        if req.uri().path() == "/healthz" {
+            return Ok(());
+        }
+        if !self.allowed_cidrs.is_empty()
+            && !self
+                .allowed_cidrs
+                .iter()
+                .any(|cidr| cidr.contains(remote_ip))
+        {
+            return Err("cidr");
+        }
+        if let Some(authorization) = &self.authorization {
+            if !req
+                .headers()
+                .get(AUTHORIZATION)
+                .is_some_and(|value| constant_time_eq(value.as_bytes(), authorization.as_bytes()))
+            {
+                return Err("auth");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Time of the comparison doesn't depend on the position of the first different byte
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len()
+        && std::hint::black_box(a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b))) == 0
+}
+
+async fn request_handler<B>(
+    req: &Request<B>,
+    remote_ip: IpAddr,
+    access: &PrometheusAccess,
+    debug_clients: Option<&ClientRegistry>,
+    grpc_serving: bool,
+) -> http::Result<Response<BoxBody<Bytes, Infallible>>> {
+    if let Err(reason) = access.check(req, remote_ip) {
+        PROMETHEUS_REQUESTS_REJECTED
+            .with_label_values(&[reason])
+            .inc();
+        return match reason {
+            "cidr" => Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .body(BodyEmpty::new().boxed()),
+            _ => Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .header(WWW_AUTHENTICATE, "Basic realm=\"metrics\"")
+                .body(BodyEmpty::new().boxed()),
+        };
+    }
+
+    match req.uri().path() {
+        "/metrics" => metrics_handler(),
+        "/healthz" => {
+            let (status, body) = if grpc_serving {
+                (StatusCode::OK, "SERVING")
+            } else {
+                (StatusCode::SERVICE_UNAVAILABLE, "NOT_SERVING")
+            };
+            Response::builder()
+                .status(status)
+                .body(BodyFull::new(Bytes::from(body)).boxed())
+        }
+        "/debug_clients" => {
+            if let Some(debug_clients) = debug_clients {
+                Response::builder()
+                    .status(StatusCode::OK)
+                    .body(BodyFull::new(Bytes::from(debug_clients.debug_statuses())).boxed())
+            } else {
+                not_found_handler()
+            }
+        }
+        _ => not_found_handler(),
+    }
+}
+
 fn metrics_handler() -> http::Result<Response<BoxBody<Bytes, Infallible>>> {
     let metrics = TextEncoder::new()
         .encode_to_string(&REGISTRY.gather())
@@ -333,6 +488,10 @@ pub fn update_invalid_blocks(reason: impl AsRef<str>) {
     INVALID_FULL_BLOCKS.with_label_values(&["all"]).inc();
 }
 
+pub fn invalid_blocks_mode_inc(mode: &str) {
+    INVALID_BLOCKS_MODE.with_label_values(&[mode]).inc()
+}
+
 pub fn message_queue_size_inc() {
     MESSAGE_QUEUE_SIZE.inc()
 }
@@ -341,6 +500,14 @@ pub fn message_queue_size_dec() {
     MESSAGE_QUEUE_SIZE.dec()
 }
 
+pub fn startup_queue_size_inc() {
+    QUEUE_SIZE.with_label_values(&["startup"]).inc()
+}
+
+pub fn startup_queue_size_set(size: usize) {
+    QUEUE_SIZE.with_label_values(&["startup"]).set(size as i64)
+}
+
 pub fn connections_total_inc() {
     CONNECTIONS_TOTAL.inc()
 }
@@ -365,8 +532,293 @@ pub fn update_subscriptions(endpoint: &str, old: Option<&Filter>, new: Option<&F
     }
 }
 
+pub fn set_grpc_serving(serving: bool) {
+    GRPC_SERVING.set(serving as i64)
+}
+
+pub fn set_tokio_runtime_workers(runtime: &str, workers: usize) {
+    TOKIO_RUNTIME_WORKERS
+        .with_label_values(&[runtime])
+        .set(workers as i64)
+}
+
+pub fn filter_updates_rejected_inc(reason: &str) {
+    FILTER_UPDATES_REJECTED.with_label_values(&[reason]).inc()
+}
+
+pub fn filter_updates_identical_inc() {
+    FILTER_UPDATES_IDENTICAL.inc()
+}
+
+pub fn filter_build_duration_observe(duration: Duration) {
+    FILTER_BUILD_DURATION.observe(duration.as_secs_f64())
+}
+
+pub fn filter_pubkeys_cache_inc(hits: u64, misses: u64) {
+    FILTER_PUBKEYS_CACHE
+        .with_label_values(&["hit"])
+        .inc_by(hits);
+    FILTER_PUBKEYS_CACHE
+        .with_label_values(&["miss"])
+        .inc_by(misses);
+}
+
 pub fn missed_status_message_inc(status: SlotStatus) {
     MISSED_STATUS_MESSAGE
         .with_label_values(&[status.as_str()])
         .inc()
 }
+
+pub fn late_messages_inc(commitment: CommitmentLevel) {
+    LATE_MESSAGES
+        .with_label_values(&[commitment.as_str()])
+        .inc()
+}
+
+pub fn slot_status_duplicates_inc(status: SlotStatus) {
+    SLOT_STATUS_DUPLICATES
+        .with_label_values(&[status.as_str()])
+        .inc()
+}
+
+pub fn messages_order_violations_inc(violation: &str) {
+    MESSAGES_ORDER_VIOLATIONS
+        .with_label_values(&[violation])
+        .inc()
+}
+
+pub fn broadcast_batch_bytes_observe(bytes: usize) {
+    BROADCAST_BATCH_BYTES.observe(bytes as f64)
+}
+
+pub fn block_assembly_lag_observe(lag: Duration) {
+    BLOCK_ASSEMBLY_LAG.observe(lag.as_secs_f64())
+}
+
+pub fn block_assembly_overflow_inc() {
+    BLOCK_ASSEMBLY_OVERFLOW.inc()
+}
+
+pub fn update_geyser_slot(commitment: CommitmentLevel, slot: Slot) {
+    GEYSER_SLOT
+        .with_label_values(&[commitment.as_str()])
+        .set(slot as i64);
+}
+
+pub fn lag_skipped_inc(endpoint: &str, kind: &str, count: u64) {
+    LAG_SKIPPED
+        .with_label_values(&[endpoint, kind])
+        .inc_by(count)
+}
+
+pub fn seen_signatures_suppressed_inc(endpoint: &str, count: usize) {
+    SEEN_SIGNATURES_SUPPRESSED
+        .with_label_values(&[endpoint])
+        .inc_by(count as u64)
+}
+
+pub fn pda_derivations_inc(endpoint: &str, count: u64) {
+    PDA_DERIVATIONS.with_label_values(&[endpoint]).inc_by(count)
+}
+
+/// Gauges of bytes and messages used by the client
+pub fn quota_used_gauges(endpoint: &str, client: usize) -> (IntGauge, IntGauge) {
+    let client = client.to_string();
+    (
+        QUOTA_USED.with_label_values(&[endpoint, &client, "bytes"]),
+        QUOTA_USED.with_label_values(&[endpoint, &client, "messages"]),
+    )
+}
+
+pub fn quota_used_remove(endpoint: &str, client: usize) {
+    let client = client.to_string();
+    for kind in ["bytes", "messages"] {
+        let _ = QUOTA_USED.remove_label_values(&[endpoint, &client, kind]);
+    }
+}
+
+pub fn quota_exceeded_inc(endpoint: &str, limit: &str) {
+    QUOTA_EXCEEDED.with_label_values(&[endpoint, limit]).inc()
+}
+
+pub fn update_recent_transactions(transactions: usize, bytes: usize) {
+    RECENT_TRANSACTIONS
+        .with_label_values(&["transactions"])
+        .set(transactions as i64);
+    RECENT_TRANSACTIONS
+        .with_label_values(&["bytes"])
+        .set(bytes as i64);
+}
+
+pub fn update_replay_stored_slots(slots: usize, bytes: usize, slot_bytes_max: usize) {
+    REPLAY_STORED_SLOTS
+        .with_label_values(&["slots"])
+        .set(slots as i64);
+    REPLAY_STORED_SLOTS
+        .with_label_values(&["bytes"])
+        .set(bytes as i64);
+    REPLAY_STORED_SLOTS
+        .with_label_values(&["slot_bytes_max"])
+        .set(slot_bytes_max as i64);
+}
+
+pub fn update_replay_stored_slot_bytes(status: SlotStatus, bytes: usize) {
+    REPLAY_STORED_SLOT_BYTES
+        .with_label_values(&[status.as_str()])
+        .set(bytes as i64);
+}
+
+pub fn replay_stored_slots_evicted_inc(limit: &str) {
+    REPLAY_STORED_SLOTS_EVICTED
+        .with_label_values(&[limit])
+        .inc()
+}
+
+pub fn replay_stored_slots_over_budget_inc() {
+    REPLAY_STORED_SLOTS_OVER_BUDGET.inc()
+}
+
+pub fn votes_dropped_inc() {
+    VOTES_DROPPED.inc()
+}
+
+pub fn record_messages_dropped_inc() {
+    RECORD_MESSAGES_DROPPED.inc()
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, serde_json::json};
+
+    fn create_access(config: serde_json::Value) -> PrometheusAccess {
+        let config: ConfigPrometheus =
+            serde_json::from_str(&config.to_string()).expect("valid config");
+        PrometheusAccess::new(&config)
+    }
+
+    fn create_request(path: &str, authorization: Option<&str>) -> Request<()> {
+        let mut builder = Request::builder().uri(path);
+        if let Some(authorization) = authorization {
+            builder = builder.header(AUTHORIZATION, authorization);
+        }
+        builder.body(()).expect("valid request")
+    }
+
+    async fn get_status(
+        access: &PrometheusAccess,
+        req: &Request<()>,
+        remote_ip: &str,
+        grpc_serving: bool,
+    ) -> StatusCode {
+        request_handler(
+            req,
+            remote_ip.parse().expect("valid ip"),
+            access,
+            None,
+            grpc_serving,
+        )
+        .await
+        .expect("valid response")
+        .status()
+    }
+
+    #[tokio::test]
+    async fn test_prometheus_auth() {
+        let access = create_access(json!({
+            "address": "127.0.0.1:8999",
+            "auth": {"username": "user", "password": "pass"},
+        }));
+        // base64("user:pass")
+        let req = create_request("/metrics", Some("Basic dXNlcjpwYXNz"));
+        assert_eq!(
+            get_status(&access, &req, "127.0.0.1", true).await,
+            StatusCode::OK
+        );
+        for authorization in [None, Some("Basic dXNlcjpwYXNzMQ=="), Some("Bearer pass")] {
+            let req = create_request("/metrics", authorization);
+            assert_eq!(
+                get_status(&access, &req, "127.0.0.1", true).await,
+                StatusCode::UNAUTHORIZED
+            );
+        }
+
+        let access = create_access(json!({
+            "address": "127.0.0.1:8999",
+            "auth": {"bearer_token": "secret"},
+            "allowed_cidrs": ["10.0.0.0/8"],
+        }));
+        let req = create_request("/metrics", Some("Bearer secret"));
+        assert_eq!(
+            get_status(&access, &req, "10.1.2.3", true).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            get_status(&access, &req, "192.168.0.1", true).await,
+            StatusCode::FORBIDDEN
+        );
+        let req = create_request("/metrics", Some("Bearer invalid"));
+        assert_eq!(
+            get_status(&access, &req, "10.1.2.3", true).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prometheus_healthz() {
+        let access = create_access(json!({"address": "127.0.0.1:8999"}));
+        let req = create_request("/healthz", None);
+        assert_eq!(
+            get_status(&access, &req, "127.0.0.1", false).await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            get_status(&access, &req, "127.0.0.1", true).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            get_status(&access, &req, "127.0.0.1", false).await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        // auth and allowed addresses are not checked for health checks
+        let access = create_access(json!({
+            "address": "127.0.0.1:8999",
+            "auth": {"bearer_token": "secret"},
+            "allowed_cidrs": ["10.0.0.0/8"],
+        }));
+        assert_eq!(
+            get_status(&access, &req, "192.168.0.1", true).await,
+            StatusCode::OK
+        );
+        let req = create_request("/metrics", None);
+        assert_eq!(
+            get_status(&access, &req, "10.1.2.3", true).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[test]
+    fn test_prometheus_auth_config() {
+        let parse = |auth: serde_json::Value| {
+            serde_json::from_value::<ConfigPrometheus>(json!({
+                "address": "127.0.0.1:8999",
+                "auth": auth,
+            }))
+        };
+        assert!(parse(json!({"username": "user", "password": "pass"})).is_ok());
+        assert!(parse(json!({"bearer_token": "secret"})).is_ok());
+        assert!(
+            parse(json!({"username": "user", "password": "pass", "bearer_token": "secret"}))
+                .is_err()
+        );
+        assert!(parse(json!({"bearer_token": "secret", "password": "pass"})).is_err());
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"Bearer secret", b"Bearer secret"));
+        assert!(!constant_time_eq(b"Bearer secret", b"Bearer secreT"));
+        assert!(!constant_time_eq(b"Bearer secret", b"Bearer secret1"));
+        assert!(constant_time_eq(b"", b""));
+    }
+}
@@ -1,8 +1,12 @@
 use {
     crate::{
+        admin::ClientRegistry,
         config::Config,
-        grpc::GrpcService,
+        grpc::{BoundAddresses, GrpcService},
         metrics::{self, PrometheusService},
+        record::MessageRecorder,
+        source::MessagesSender,
+        trace::OtlpExporter,
     },
     agave_geyser_plugin_interface::geyser_plugin_interface::{
         GeyserPlugin, GeyserPluginError, ReplicaAccountInfoVersions, ReplicaBlockInfoVersions,
@@ -15,12 +19,9 @@ use {
             atomic::{AtomicBool, Ordering},
             Arc, Mutex,
         },
-        time::Duration,
-    },
-    tokio::{
-        runtime::{Builder, Runtime},
-        sync::{mpsc, Notify},
+        time::{Duration, Instant},
     },
+    tokio::{runtime::Runtime, sync::Notify},
     yellowstone_grpc_proto::plugin::message::{
         Message, MessageAccount, MessageBlockMeta, MessageEntry, MessageSlot, MessageTransaction,
     },
@@ -31,16 +32,20 @@ pub struct PluginInner {
     runtime: Runtime,
     snapshot_channel: Mutex<Option<crossbeam_channel::Sender<Box<Message>>>>,
     snapshot_channel_closed: AtomicBool,
-    grpc_channel: mpsc::UnboundedSender<Message>,
+    grpc_channel: MessagesSender,
+    recorder: Option<MessageRecorder>,
     grpc_shutdown: Arc<Notify>,
+    grpc_addresses: BoundAddresses,
     prometheus: PrometheusService,
+    otlp: Option<OtlpExporter>,
 }
 
 impl PluginInner {
     fn send_message(&self, message: Message) {
-        if self.grpc_channel.send(message).is_ok() {
-            metrics::message_queue_size_inc();
+        if let Some(recorder) = &self.recorder {
+            recorder.record(&message);
         }
+        let _ = self.grpc_channel.send(message);
     }
 }
 
@@ -57,6 +62,11 @@ impl Plugin {
         let inner = self.inner.as_ref().expect("initialized");
         f(inner)
     }
+
+    /// Local addresses of the gRPC and admin services, `None` before `on_load`
+    pub fn bound_addresses(&self) -> Option<BoundAddresses> {
+        self.inner.as_ref().map(|inner| inner.grpc_addresses)
+    }
 }
 
 impl GeyserPlugin for Plugin {
@@ -70,44 +80,55 @@ impl GeyserPlugin for Plugin {
         // Setup logger
         solana_logger::setup_with_default(&config.log.level);
 
-        // Create inner
-        let mut builder = Builder::new_multi_thread();
-        if let Some(worker_threads) = config.tokio.worker_threads {
-            builder.worker_threads(worker_threads);
-        }
-        if let Some(tokio_cpus) = config.tokio.affinity.clone() {
-            builder.on_thread_start(move || {
-                affinity::set_thread_affinity(&tokio_cpus).expect("failed to set affinity")
-            });
-        }
-        let runtime = builder
-            .thread_name_fn(crate::get_thread_name)
-            .enable_all()
-            .build()
+        // Create inner, all tasks of the plugin are spawned on the own runtime
+        let runtime = crate::create_runtime(&config.tokio, "plugin")
             .map_err(|error| GeyserPluginError::Custom(Box::new(error)))?;
 
-        let (snapshot_channel, grpc_channel, grpc_shutdown, prometheus) =
+        // Spans are exported by the task on the runtime of the plugin
+        let otlp = match &config.log.otlp {
+            Some(config) => {
+                let _guard = runtime.enter();
+                let otlp = OtlpExporter::init(config)
+                    .map_err(|error| GeyserPluginError::Custom(format!("{error:?}").into()))?;
+                Some(otlp)
+            }
+            None => None,
+        };
+
+        let (snapshot_channel, grpc_channel, grpc_shutdown, grpc_addresses, prometheus, recorder) =
             runtime.block_on(async move {
-                let (debug_client_tx, debug_client_rx) = mpsc::unbounded_channel();
-                let (snapshot_channel, grpc_channel, grpc_shutdown) = GrpcService::create(
-                    config.tokio,
-                    config.grpc,
-                    config.debug_clients_http.then_some(debug_client_tx),
-                    is_reload,
-                )
-                .await
-                .map_err(|error| GeyserPluginError::Custom(format!("{error:?}").into()))?;
-                let prometheus = PrometheusService::new(
-                    config.prometheus,
-                    config.debug_clients_http.then_some(debug_client_rx),
-                )
-                .await
-                .map_err(|error| GeyserPluginError::Custom(Box::new(error)))?;
+                let recorder = match &config.record_messages_path {
+                    Some(path) => Some(
+                        MessageRecorder::create(path, config.record_messages_max_size)
+                            .await
+                            .map_err(|error| {
+                                GeyserPluginError::Custom(format!("{error:?}").into())
+                            })?,
+                    ),
+                    None => None,
+                };
+                let debug_clients = config
+                    .debug_clients_http
+                    .then(|| Arc::new(ClientRegistry::new(true)));
+                let (snapshot_channel, grpc_channel, grpc_shutdown, grpc_addresses) =
+                    GrpcService::create(
+                        config.tokio,
+                        config.grpc,
+                        debug_clients.clone(),
+                        is_reload,
+                    )
+                    .await
+                    .map_err(|error| GeyserPluginError::Custom(format!("{error:?}").into()))?;
+                let prometheus = PrometheusService::new(config.prometheus, debug_clients)
+                    .await
+                    .map_err(|error| GeyserPluginError::Custom(Box::new(error)))?;
                 Ok::<_, GeyserPluginError>((
                     snapshot_channel,
                     grpc_channel,
                     grpc_shutdown,
+                    grpc_addresses,
                     prometheus,
+                    recorder,
                 ))
             })?;
 
@@ -116,8 +137,11 @@ impl GeyserPlugin for Plugin {
             snapshot_channel: Mutex::new(snapshot_channel),
             snapshot_channel_closed: AtomicBool::new(false),
             grpc_channel,
+            recorder,
             grpc_shutdown,
+            grpc_addresses,
             prometheus,
+            otlp,
         });
 
         Ok(())
@@ -128,7 +152,13 @@ impl GeyserPlugin for Plugin {
             inner.grpc_shutdown.notify_one();
             drop(inner.grpc_channel);
             inner.prometheus.shutdown();
+            if let Some(otlp) = inner.otlp {
+                otlp.shutdown();
+            }
+            // tasks are not orphaned: the runtime waits for the geyser loop and blocking tasks
+            let started_at = Instant::now();
             inner.runtime.shutdown_timeout(Duration::from_secs(30));
+            tracing::info!("plugin runtime stopped in {:?}", started_at.elapsed());
         }
     }
 
@@ -139,25 +169,16 @@ impl GeyserPlugin for Plugin {
         is_startup: bool,
     ) -> PluginResult<()> {
         self.with_inner(|inner| {
-            let account = match account {
-                ReplicaAccountInfoVersions::V0_0_1(_info) => {
-                    unreachable!("ReplicaAccountInfoVersions::V0_0_1 is not supported")
-                }
-                ReplicaAccountInfoVersions::V0_0_2(_info) => {
-                    unreachable!("ReplicaAccountInfoVersions::V0_0_2 is not supported")
-                }
-                ReplicaAccountInfoVersions::V0_0_3(info) => info,
-            };
-
             if is_startup {
                 if let Some(channel) = inner.snapshot_channel.lock().unwrap().as_ref() {
-                    let message =
-                        Message::Account(MessageAccount::from_geyser(account, slot, is_startup));
+                    let message = Message::Account(MessageAccount::from_geyser_versions(
+                        &account, slot, is_startup,
+                    ));
                     match channel.send(Box::new(message)) {
-                        Ok(()) => metrics::message_queue_size_inc(),
+                        Ok(()) => metrics::startup_queue_size_inc(),
                         Err(_) => {
                             if !inner.snapshot_channel_closed.swap(true, Ordering::Relaxed) {
-                                log::error!(
+                                tracing::error!(
                                     "failed to send message to startup queue: channel closed"
                                 )
                             }
@@ -165,8 +186,9 @@ impl GeyserPlugin for Plugin {
                     }
                 }
             } else {
-                let message =
-                    Message::Account(MessageAccount::from_geyser(account, slot, is_startup));
+                let message = Message::Account(MessageAccount::from_geyser_versions(
+                    &account, slot, is_startup,
+                ));
                 inner.send_message(message);
             }
 
@@ -201,14 +223,8 @@ impl GeyserPlugin for Plugin {
         slot: u64,
     ) -> PluginResult<()> {
         self.with_inner(|inner| {
-            let transaction = match transaction {
-                ReplicaTransactionInfoVersions::V0_0_1(_info) => {
-                    unreachable!("ReplicaAccountInfoVersions::V0_0_1 is not supported")
-                }
-                ReplicaTransactionInfoVersions::V0_0_2(info) => info,
-            };
-
-            let message = Message::Transaction(MessageTransaction::from_geyser(transaction, slot));
+            let message =
+                Message::Transaction(MessageTransaction::from_geyser_versions(&transaction, slot));
             inner.send_message(message);
 
             Ok(())
@@ -217,15 +233,7 @@ impl GeyserPlugin for Plugin {
 
     fn notify_entry(&self, entry: ReplicaEntryInfoVersions) -> PluginResult<()> {
         self.with_inner(|inner| {
-            #[allow(clippy::infallible_destructuring_match)]
-            let entry = match entry {
-                ReplicaEntryInfoVersions::V0_0_1(_entry) => {
-                    unreachable!("ReplicaEntryInfoVersions::V0_0_1 is not supported")
-                }
-                ReplicaEntryInfoVersions::V0_0_2(entry) => entry,
-            };
-
-            let message = Message::Entry(Arc::new(MessageEntry::from_geyser(entry)));
+            let message = Message::Entry(Arc::new(MessageEntry::from_geyser_versions(&entry)));
             inner.send_message(message);
 
             Ok(())
@@ -234,20 +242,8 @@ impl GeyserPlugin for Plugin {
 
     fn notify_block_metadata(&self, blockinfo: ReplicaBlockInfoVersions<'_>) -> PluginResult<()> {
         self.with_inner(|inner| {
-            let blockinfo = match blockinfo {
-                ReplicaBlockInfoVersions::V0_0_1(_info) => {
-                    unreachable!("ReplicaBlockInfoVersions::V0_0_1 is not supported")
-                }
-                ReplicaBlockInfoVersions::V0_0_2(_info) => {
-                    unreachable!("ReplicaBlockInfoVersions::V0_0_2 is not supported")
-                }
-                ReplicaBlockInfoVersions::V0_0_3(_info) => {
-                    unreachable!("ReplicaBlockInfoVersions::V0_0_3 is not supported")
-                }
-                ReplicaBlockInfoVersions::V0_0_4(info) => info,
-            };
-
-            let message = Message::BlockMeta(Arc::new(MessageBlockMeta::from_geyser(blockinfo)));
+            let message =
+                Message::BlockMeta(Arc::new(MessageBlockMeta::from_geyser_versions(&blockinfo)));
             inner.send_message(message);
 
             Ok(())
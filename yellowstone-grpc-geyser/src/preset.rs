@@ -0,0 +1,298 @@
+//! Filter presets: accounts and transactions filters defined in the config, clients select
+//! the preset by name and provide only values of its variables.
+
+use {
+    crate::config::{ConfigGrpcFilterPreset, ConfigGrpcFilterPresetVariable},
+    solana_sdk::pubkey::Pubkey,
+    std::collections::HashMap,
+    yellowstone_grpc_proto::prelude::{
+        SubscribeRequest, SubscribeRequestFilterAccounts, SubscribeRequestFilterTransactions,
+    },
+};
+
+#[derive(Debug, Default)]
+pub struct FilterPresets {
+    presets: HashMap<String, ConfigGrpcFilterPreset>,
+}
+
+impl FilterPresets {
+    pub const fn new(presets: HashMap<String, ConfigGrpcFilterPreset>) -> Self {
+        Self { presets }
+    }
+
+    /// Replace accounts and transactions filters of the request with filters of the preset,
+    /// request without `preset` is not changed. Error names the preset and the variable.
+    pub fn apply(&self, request: &mut SubscribeRequest) -> Result<(), String> {
+        let Some(name) = request.preset.as_deref() else {
+            if !request.preset_params.is_empty() {
+                return Err("`preset_params` are set without `preset`".to_owned());
+            }
+            return Ok(());
+        };
+        let preset = self
+            .presets
+            .get(name)
+            .ok_or_else(|| format!("unknown preset `{name}`"))?;
+        if !request.accounts.is_empty() || !request.transactions.is_empty() {
+            return Err(format!(
+                "preset `{name}`: `accounts` and `transactions` filters are defined by the preset"
+            ));
+        }
+
+        let mut params = request.preset_params.keys().collect::<Vec<_>>();
+        params.sort();
+        if let Some(param) = params
+            .into_iter()
+            .find(|param| !preset.variables.contains_key(param.as_str()))
+        {
+            return Err(format!("preset `{name}`: unknown variable `{param}`"));
+        }
+
+        let mut variables = preset.variables.iter().collect::<Vec<_>>();
+        variables.sort_by_key(|(variable, _kind)| *variable);
+        let mut values = HashMap::with_capacity(variables.len());
+        for (variable, kind) in variables {
+            let value = request
+                .preset_params
+                .get(variable)
+                .ok_or_else(|| format!("preset `{name}`: missing value of `${{{variable}}}`"))?;
+            let value = Self::parse(variable, *kind, value)
+                .map_err(|error| format!("preset `{name}`: {error}"))?;
+            values.insert(variable.as_str(), value);
+        }
+
+        let substitute = |list: &[String]| -> Vec<String> {
+            list.iter()
+                .flat_map(|value| {
+                    match ConfigGrpcFilterPreset::get_variable(value)
+                        .and_then(|variable| values.get(variable))
+                    {
+                        Some(values) => values.clone(),
+                        None => vec![value.clone()],
+                    }
+                })
+                .collect()
+        };
+        request.accounts = preset
+            .accounts
+            .iter()
+            .map(|(filter_name, filter)| {
+                let filter = SubscribeRequestFilterAccounts {
+                    account: substitute(&filter.account),
+                    owner: substitute(&filter.owner),
+                    nonempty_txn_signature: filter.nonempty_txn_signature,
+                    ..Default::default()
+                };
+                (filter_name.clone(), filter)
+            })
+            .collect();
+        request.transactions = preset
+            .transactions
+            .iter()
+            .map(|(filter_name, filter)| {
+                let filter = SubscribeRequestFilterTransactions {
+                    vote: filter.vote,
+                    failed: filter.failed,
+                    account_include: substitute(&filter.account_include),
+                    account_exclude: substitute(&filter.account_exclude),
+                    account_required: substitute(&filter.account_required),
+                    ..Default::default()
+                };
+                (filter_name.clone(), filter)
+            })
+            .collect();
+        Ok(())
+    }
+
+    /// Pubkeys of the variable as strings, validated by type and length
+    fn parse(
+        variable: &str,
+        kind: ConfigGrpcFilterPresetVariable,
+        value: &str,
+    ) -> Result<Vec<String>, String> {
+        let parse_pubkey = |value: &str| {
+            value
+                .parse::<Pubkey>()
+                .map(|pubkey| pubkey.to_string())
+                .map_err(|error| format!("invalid pubkey in `${{{variable}}}`: {value} ({error})"))
+        };
+        match kind {
+            ConfigGrpcFilterPresetVariable::Pubkey => {
+                parse_pubkey(value.trim()).map(|pubkey| vec![pubkey])
+            }
+            ConfigGrpcFilterPresetVariable::Pubkeys { max } => {
+                let pubkeys = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|value| !value.is_empty())
+                    .map(parse_pubkey)
+                    .collect::<Result<Vec<_>, _>>()?;
+                // empty list in place of the variable would widen the filter to all accounts
+                if pubkeys.is_empty() {
+                    return Err(format!("`${{{variable}}}` has no pubkeys"));
+                }
+                if pubkeys.len() > max {
+                    return Err(format!(
+                        "`${{{variable}}}` has {} pubkeys, max {max}",
+                        pubkeys.len()
+                    ));
+                }
+                Ok(pubkeys)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::config::{ConfigGrpcFilterPresetAccounts, ConfigGrpcFilterPresetTransactions},
+    };
+
+    fn create_presets() -> FilterPresets {
+        FilterPresets::new(HashMap::from([(
+            "program".to_owned(),
+            ConfigGrpcFilterPreset {
+                variables: HashMap::from([
+                    (
+                        "program_id".to_owned(),
+                        ConfigGrpcFilterPresetVariable::Pubkey,
+                    ),
+                    (
+                        "accounts".to_owned(),
+                        ConfigGrpcFilterPresetVariable::Pubkeys { max: 2 },
+                    ),
+                ]),
+                accounts: HashMap::from([(
+                    "vaults".to_owned(),
+                    ConfigGrpcFilterPresetAccounts {
+                        account: vec!["${accounts}".to_owned()],
+                        owner: vec!["${program_id}".to_owned()],
+                        nonempty_txn_signature: None,
+                    },
+                )]),
+                transactions: HashMap::from([(
+                    "program".to_owned(),
+                    ConfigGrpcFilterPresetTransactions {
+                        vote: Some(false),
+                        failed: Some(false),
+                        account_include: vec!["${program_id}".to_owned()],
+                        account_exclude: vec![],
+                        account_required: vec![],
+                    },
+                )]),
+            },
+        )]))
+    }
+
+    fn create_request(params: &[(&str, String)]) -> SubscribeRequest {
+        SubscribeRequest {
+            preset: Some("program".to_owned()),
+            preset_params: params
+                .iter()
+                .map(|(name, value)| ((*name).to_owned(), value.clone()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let presets = create_presets();
+        let program_id = Pubkey::new_unique();
+        let accounts = [Pubkey::new_unique(), Pubkey::new_unique()];
+
+        let mut request = create_request(&[
+            ("program_id", program_id.to_string()),
+            ("accounts", format!("{}, {}", accounts[0], accounts[1])),
+        ]);
+        presets.apply(&mut request).expect("valid params");
+        let filter = &request.accounts["vaults"];
+        assert_eq!(
+            filter.account,
+            accounts.map(|pubkey| pubkey.to_string()).to_vec()
+        );
+        assert_eq!(filter.owner, vec![program_id.to_string()]);
+        let filter = &request.transactions["program"];
+        assert_eq!(filter.account_include, vec![program_id.to_string()]);
+        assert_eq!((filter.vote, filter.failed), (Some(false), Some(false)));
+
+        // request without preset is not changed
+        let mut request = SubscribeRequest::default();
+        presets.apply(&mut request).expect("no preset");
+        assert_eq!(request, SubscribeRequest::default());
+    }
+
+    #[test]
+    fn test_apply_errors() {
+        let presets = create_presets();
+        let program_id = Pubkey::new_unique().to_string();
+        let accounts = Pubkey::new_unique().to_string();
+
+        for (mut request, error) in [
+            (
+                create_request(&[("program_id", program_id.clone())]),
+                "preset `program`: missing value of `${accounts}`",
+            ),
+            (
+                create_request(&[
+                    ("program_id", program_id.clone()),
+                    ("accounts", accounts.clone()),
+                    ("owner", program_id.clone()),
+                ]),
+                "preset `program`: unknown variable `owner`",
+            ),
+            (
+                create_request(&[
+                    ("program_id", "0x123".to_owned()),
+                    ("accounts", accounts.clone()),
+                ]),
+                "preset `program`: invalid pubkey in `${program_id}`: 0x123 (Invalid Base58 string)",
+            ),
+            (
+                create_request(&[
+                    ("program_id", program_id.clone()),
+                    ("accounts", [accounts.as_str(); 3].join(",")),
+                ]),
+                "preset `program`: `${accounts}` has 3 pubkeys, max 2",
+            ),
+            (
+                create_request(&[
+                    ("program_id", program_id.clone()),
+                    ("accounts", " , ".to_owned()),
+                ]),
+                "preset `program`: `${accounts}` has no pubkeys",
+            ),
+            (
+                SubscribeRequest {
+                    preset: Some("unknown".to_owned()),
+                    ..Default::default()
+                },
+                "unknown preset `unknown`",
+            ),
+            (
+                SubscribeRequest {
+                    preset_params: HashMap::from([("accounts".to_owned(), accounts.clone())]),
+                    ..Default::default()
+                },
+                "`preset_params` are set without `preset`",
+            ),
+            (
+                SubscribeRequest {
+                    transactions: HashMap::from([(
+                        "all".to_owned(),
+                        SubscribeRequestFilterTransactions::default(),
+                    )]),
+                    ..create_request(&[
+                        ("program_id", program_id.clone()),
+                        ("accounts", accounts.clone()),
+                    ])
+                },
+                "preset `program`: `accounts` and `transactions` filters are defined by the preset",
+            ),
+        ] {
+            assert_eq!(presets.apply(&mut request), Err(error.to_owned()));
+        }
+    }
+}
@@ -0,0 +1,278 @@
+//! Quotas of bytes and messages sent to a subscription.
+
+use {
+    crate::{
+        config::{ConfigGrpcQuota, ConfigGrpcQuotaLimits, ConfigGrpcQuotaWindow},
+        metrics,
+    },
+    prometheus::IntGauge,
+    prost_types::Timestamp,
+    std::{
+        collections::VecDeque,
+        time::{Duration, SystemTime},
+    },
+    tokio::time::Instant,
+    yellowstone_grpc_proto::prelude::SubscribeUpdateQuotaWarning,
+};
+
+#[derive(Debug, Clone, Copy)]
+struct QuotaBucket {
+    start: Instant,
+    bytes: u64,
+    messages: u64,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum QuotaCheck {
+    Ok,
+    /// Soft limit reached, sent once per window
+    Warning(SubscribeUpdateQuotaWarning),
+}
+
+/// Usage of the client, fixed window is one bucket of `window` length, rolling window is
+/// split into `ROLLING_BUCKETS` buckets
+#[derive(Debug)]
+pub struct ClientQuota {
+    limits: ConfigGrpcQuotaLimits,
+    window: Duration,
+    bucket_len: Duration,
+    buckets: VecDeque<QuotaBucket>,
+    used_bytes: u64,
+    used_messages: u64,
+    warned: bool,
+    gauges: Option<(IntGauge, IntGauge)>,
+}
+
+impl ClientQuota {
+    const ROLLING_BUCKETS: u32 = 60;
+
+    pub fn new(config: &ConfigGrpcQuota, x_token: Option<&str>) -> Self {
+        Self {
+            limits: config.get_limits(x_token),
+            window: config.window,
+            bucket_len: match config.window_kind {
+                ConfigGrpcQuotaWindow::Fixed => config.window,
+                ConfigGrpcQuotaWindow::Rolling => config.window / Self::ROLLING_BUCKETS,
+            },
+            buckets: VecDeque::new(),
+            used_bytes: 0,
+            used_messages: 0,
+            warned: false,
+            gauges: None,
+        }
+    }
+
+    /// Report usage with `quota_used` metric
+    pub fn with_metrics(mut self, endpoint: &str, client: usize) -> Self {
+        self.gauges = Some(metrics::quota_used_gauges(endpoint, client));
+        self
+    }
+
+    /// Returns `false` if the update would exceed the hard limit
+    pub fn check(&mut self, bytes: u64, now: Instant) -> bool {
+        self.expire(now);
+        Self::is_within(self.used_bytes + bytes, self.limits.hard_bytes)
+            && Self::is_within(self.used_messages + 1, self.limits.hard_messages)
+    }
+
+    /// Account sent update
+    pub fn add(&mut self, bytes: u64, now: Instant) -> QuotaCheck {
+        self.expire(now);
+        match self.buckets.back_mut() {
+            Some(bucket) if now.saturating_duration_since(bucket.start) < self.bucket_len => {
+                bucket.bytes += bytes;
+                bucket.messages += 1;
+            }
+            _ => self.buckets.push_back(QuotaBucket {
+                start: now,
+                bytes,
+                messages: 1,
+            }),
+        }
+        self.used_bytes += bytes;
+        self.used_messages += 1;
+        self.update_metrics();
+
+        if !self.warned && self.is_soft_reached() {
+            self.warned = true;
+            QuotaCheck::Warning(self.get_warning(now))
+        } else {
+            QuotaCheck::Ok
+        }
+    }
+
+    pub fn get_warning(&self, now: Instant) -> SubscribeUpdateQuotaWarning {
+        let resets_in = self
+            .buckets
+            .front()
+            .map(|bucket| (bucket.start + self.window).saturating_duration_since(now))
+            .unwrap_or(self.window);
+        SubscribeUpdateQuotaWarning {
+            used_bytes: self.used_bytes,
+            limit_bytes: self.limits.hard_bytes.unwrap_or_default(),
+            resets_at: Some(Timestamp::from(SystemTime::now() + resets_in)),
+            used_messages: self.used_messages,
+            limit_messages: self.limits.hard_messages.unwrap_or_default(),
+        }
+    }
+
+    fn expire(&mut self, now: Instant) {
+        let mut expired = false;
+        while let Some(bucket) = self.buckets.front() {
+            if now.saturating_duration_since(bucket.start) < self.window {
+                break;
+            }
+            self.used_bytes -= bucket.bytes;
+            self.used_messages -= bucket.messages;
+            self.buckets.pop_front();
+            expired = true;
+        }
+        if expired {
+            if !self.is_soft_reached() {
+                self.warned = false;
+            }
+            self.update_metrics();
+        }
+    }
+
+    fn is_soft_reached(&self) -> bool {
+        !Self::is_within(self.used_bytes + 1, self.limits.soft_bytes)
+            || !Self::is_within(self.used_messages + 1, self.limits.soft_messages)
+    }
+
+    fn is_within(value: u64, limit: Option<u64>) -> bool {
+        limit.is_none_or(|limit| value <= limit)
+    }
+
+    fn update_metrics(&self) {
+        if let Some((bytes, messages)) = &self.gauges {
+            bytes.set(self.used_bytes as i64);
+            messages.set(self.used_messages as i64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_quota(
+        window_kind: ConfigGrpcQuotaWindow,
+        limits: ConfigGrpcQuotaLimits,
+    ) -> ClientQuota {
+        ClientQuota::new(
+            &ConfigGrpcQuota {
+                window: Duration::from_secs(60),
+                window_kind,
+                limits,
+                tokens: Default::default(),
+            },
+            None,
+        )
+    }
+
+    #[test]
+    fn test_soft_warning_once() {
+        let mut quota = create_quota(
+            ConfigGrpcQuotaWindow::Fixed,
+            ConfigGrpcQuotaLimits {
+                soft_bytes: Some(250),
+                hard_bytes: Some(400),
+                ..Default::default()
+            },
+        );
+        let now = Instant::now();
+        assert_eq!(quota.add(100, now), QuotaCheck::Ok);
+        assert_eq!(quota.add(100, now), QuotaCheck::Ok);
+        let QuotaCheck::Warning(warning) = quota.add(100, now) else {
+            panic!("warning expected");
+        };
+        assert_eq!(warning.used_bytes, 300);
+        assert_eq!(warning.limit_bytes, 400);
+        assert_eq!(warning.used_messages, 3);
+        assert_eq!(warning.limit_messages, 0);
+        assert!(warning.resets_at.is_some());
+        assert_eq!(quota.add(10, now), QuotaCheck::Ok);
+    }
+
+    #[test]
+    fn test_hard_limit() {
+        let mut quota = create_quota(
+            ConfigGrpcQuotaWindow::Fixed,
+            ConfigGrpcQuotaLimits {
+                hard_messages: Some(2),
+                ..Default::default()
+            },
+        );
+        let now = Instant::now();
+        for _ in 0..2 {
+            assert!(quota.check(1, now));
+            assert_eq!(quota.add(1, now), QuotaCheck::Ok);
+        }
+        assert!(!quota.check(1, now));
+    }
+
+    #[test]
+    fn test_fixed_window_reset() {
+        let mut quota = create_quota(
+            ConfigGrpcQuotaWindow::Fixed,
+            ConfigGrpcQuotaLimits {
+                soft_bytes: Some(100),
+                hard_bytes: Some(150),
+                ..Default::default()
+            },
+        );
+        let start = Instant::now();
+        assert!(matches!(quota.add(100, start), QuotaCheck::Warning(_)));
+        assert!(!quota.check(100, start + Duration::from_secs(59)));
+
+        let next = start + Duration::from_secs(60);
+        assert!(quota.check(100, next));
+        assert!(matches!(quota.add(100, next), QuotaCheck::Warning(_)));
+    }
+
+    #[test]
+    fn test_rolling_window() {
+        let mut quota = create_quota(
+            ConfigGrpcQuotaWindow::Rolling,
+            ConfigGrpcQuotaLimits {
+                hard_bytes: Some(200),
+                ..Default::default()
+            },
+        );
+        let start = Instant::now();
+        quota.add(100, start);
+        quota.add(100, start + Duration::from_secs(30));
+        assert!(!quota.check(1, start + Duration::from_secs(59)));
+
+        // first bucket is out of the window, second one is still accounted
+        assert!(quota.check(100, start + Duration::from_secs(60)));
+        assert!(!quota.check(101, start + Duration::from_secs(60)));
+        assert_eq!(quota.used_bytes, 100);
+    }
+
+    #[test]
+    fn test_token_limits() {
+        let config = ConfigGrpcQuota {
+            window: Duration::from_secs(60),
+            window_kind: ConfigGrpcQuotaWindow::Fixed,
+            limits: ConfigGrpcQuotaLimits {
+                hard_bytes: Some(10),
+                ..Default::default()
+            },
+            tokens: [(
+                "vip".to_owned(),
+                ConfigGrpcQuotaLimits {
+                    hard_bytes: Some(1_000),
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+        };
+        let now = Instant::now();
+        assert!(!ClientQuota::new(&config, None).check(100, now));
+        assert!(!ClientQuota::new(&config, Some("unknown")).check(100, now));
+        assert!(ClientQuota::new(&config, Some("vip")).check(100, now));
+    }
+}
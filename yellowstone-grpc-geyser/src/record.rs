@@ -0,0 +1,384 @@
+//! Recording of plugin messages and reading them back for a deterministic replay.
+//!
+//! Log is a sequence of frames from [`yellowstone_grpc_proto::frame`] with `SubscribeUpdate`
+//! as payload, `created_at` of the update keeps the original time of the message.
+
+use {
+    crate::metrics,
+    anyhow::Context,
+    std::{io, path::Path, sync::Arc},
+    tokio::{
+        fs::File,
+        io::{AsyncRead, AsyncWrite, BufReader, BufWriter},
+        sync::mpsc,
+    },
+    tracing::{error, info, warn},
+    yellowstone_grpc_proto::{
+        frame::{UpdateFrameReader, UpdateFrameWriter},
+        plugin::{
+            filter::{
+                message::{FilteredUpdate, FilteredUpdateFilters, FilteredUpdateOneof},
+                FilterAccountsDataSlice,
+            },
+            message::Message,
+        },
+        prelude::SubscribeUpdate,
+    },
+};
+
+/// Convert message to the update for the log, `Block`, `BlockDiagnostic`, `Votes` and `SlotStats`
+/// are not recorded because they're created by `geyser_loop` from other messages
+pub fn message_to_update(message: &Message) -> Option<SubscribeUpdate> {
+    let (message, created_at) = match message {
+        Message::Slot(msg) => (FilteredUpdateOneof::slot(msg.clone()), msg.created_at),
+        Message::Account(msg) => (
+            FilteredUpdateOneof::account(msg, FilterAccountsDataSlice::default()),
+            msg.created_at,
+        ),
+        Message::Transaction(msg) => {
+            let mut update = FilteredUpdateOneof::transaction(msg);
+            if let FilteredUpdateOneof::Transaction(update) = &mut update {
+                update.resolved_accounts = true;
+            }
+            (update, msg.created_at)
+        }
+        Message::Entry(msg) => (FilteredUpdateOneof::entry(Arc::clone(msg)), msg.created_at),
+        Message::BlockMeta(msg) => (
+            FilteredUpdateOneof::block_meta(Arc::clone(msg)),
+            msg.created_at,
+        ),
+        Message::Block(_)
+        | Message::BlockDiagnostic(_)
+        | Message::Votes(_)
+        | Message::SlotStats(_)
+        | Message::Rollback(_) => return None,
+    };
+    Some(
+        FilteredUpdate::new(FilteredUpdateFilters::new(), message, created_at)
+            .as_subscribe_update(),
+    )
+}
+
+/// Convert update from the log back to the message
+pub fn message_from_update(update: SubscribeUpdate) -> anyhow::Result<Message> {
+    let created_at = update.created_at.context("created_at should be defined")?;
+    let oneof = update
+        .update_oneof
+        .context("update_oneof should be defined")?;
+    let mut message = Message::from_update_oneof(oneof, created_at).map_err(anyhow::Error::msg)?;
+    if let Message::Transaction(msg) = &mut message {
+        Arc::get_mut(&mut msg.transaction)
+            .expect("not shared")
+            .fill_account_keys()
+            .map_err(anyhow::Error::msg)?;
+    }
+    Ok(message)
+}
+
+#[derive(Debug)]
+pub struct MessageLogWriter<W> {
+    writer: UpdateFrameWriter<W>,
+    written: usize,
+}
+
+impl<W: AsyncWrite + Unpin> MessageLogWriter<W> {
+    pub const fn new(writer: W) -> Self {
+        Self {
+            writer: UpdateFrameWriter::new(writer),
+            written: 0,
+        }
+    }
+
+    /// Write message to the log, returns `false` if the message is not recordable
+    pub async fn write(&mut self, message: &Message) -> io::Result<bool> {
+        Ok(match message_to_update(message) {
+            Some(update) => {
+                self.written += self.writer.write(&update).await?;
+                true
+            }
+            None => false,
+        })
+    }
+
+    /// Total number of written bytes
+    pub const fn written(&self) -> usize {
+        self.written
+    }
+
+    pub async fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush().await
+    }
+}
+
+impl MessageLogWriter<BufWriter<File>> {
+    pub async fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        File::create(path)
+            .await
+            .map(|file| Self::new(BufWriter::new(file)))
+    }
+}
+
+#[derive(Debug)]
+pub struct MessageLogReader<R> {
+    reader: UpdateFrameReader<R>,
+}
+
+impl<R: AsyncRead + Unpin> MessageLogReader<R> {
+    pub const fn new(reader: R) -> Self {
+        Self {
+            reader: UpdateFrameReader::new(reader),
+        }
+    }
+
+    /// Read next message, `None` on the end of the log
+    pub async fn read(&mut self) -> anyhow::Result<Option<Message>> {
+        match self.reader.read::<SubscribeUpdate>().await? {
+            Some(update) => message_from_update(update).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl MessageLogReader<BufReader<File>> {
+    pub async fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        File::open(path)
+            .await
+            .map(|file| Self::new(BufReader::new(file)))
+    }
+}
+
+/// Messages waiting for the writer, new messages are dropped once the channel is full so a
+/// slow disk doesn't grow the validator memory
+const MESSAGE_RECORDER_CHANNEL_CAPACITY: usize = 100_000;
+
+/// Record messages sent by the plugin to the file until `max_size` is reached
+#[derive(Debug)]
+pub struct MessageRecorder {
+    messages_tx: mpsc::Sender<Message>,
+}
+
+impl MessageRecorder {
+    /// Create the log file and spawn the writer, should be called within the runtime
+    pub async fn create(path: impl AsRef<Path>, max_size: usize) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let writer = MessageLogWriter::create(path)
+            .await
+            .with_context(|| format!("failed to create {path:?}"))?;
+        info!("record messages to {path:?}, max size: {max_size} bytes");
+
+        let (messages_tx, messages_rx) = mpsc::channel(MESSAGE_RECORDER_CHANNEL_CAPACITY);
+        tokio::spawn(Self::write_loop(writer, messages_rx, max_size));
+        Ok(Self { messages_tx })
+    }
+
+    pub fn record(&self, message: &Message) {
+        if let Err(mpsc::error::TrySendError::Full(_)) = self.messages_tx.try_send(message.clone())
+        {
+            metrics::record_messages_dropped_inc();
+        }
+    }
+
+    async fn write_loop(
+        mut writer: MessageLogWriter<BufWriter<File>>,
+        mut messages_rx: mpsc::Receiver<Message>,
+        max_size: usize,
+    ) {
+        while let Some(message) = messages_rx.recv().await {
+            if let Err(error) = writer.write(&message).await {
+                error!("failed to record message: {error}");
+                break;
+            }
+            if writer.written() >= max_size {
+                warn!("record messages stopped, max size reached: {max_size} bytes");
+                break;
+            }
+            if messages_rx.is_empty() {
+                if let Err(error) = writer.flush().await {
+                    error!("failed to flush recorded messages: {error}");
+                    break;
+                }
+            }
+        }
+        if let Err(error) = writer.flush().await {
+            error!("failed to flush recorded messages: {error}");
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use {
+        super::{MessageLogReader, MessageLogWriter},
+        prost_types::Timestamp,
+        solana_sdk::{hash::Hash, pubkey::Pubkey, signature::Signature},
+        std::{collections::HashSet, sync::Arc},
+        yellowstone_grpc_proto::{
+            plugin::message::{
+                Message, MessageAccount, MessageAccountInfo, MessageBlockMeta, MessageEntry,
+                MessageSlot, MessageTransaction, MessageTransactionInfo, SlotStatus,
+            },
+            prelude::{SubscribeUpdateBlockMeta, Transaction, TransactionStatusMeta},
+        },
+    };
+
+    pub const FIXTURE_ACCOUNTS_DEDUP: &str =
+        concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/accounts-dedup.bin");
+    pub const FIXTURE_FORK: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/fork.bin");
+
+    const fn created_at(ms: i32) -> Timestamp {
+        Timestamp {
+            seconds: 1_700_000_000,
+            nanos: ms * 1_000_000,
+        }
+    }
+
+    const fn slot(slot: u64, parent: u64, status: SlotStatus, ms: i32) -> Message {
+        Message::Slot(MessageSlot {
+            slot,
+            parent: Some(parent),
+            status,
+            dead_error: None,
+            created_at: created_at(ms),
+        })
+    }
+
+    fn account(slot: u64, pubkey: u8, write_version: u64, ms: i32) -> Message {
+        Message::Account(MessageAccount {
+            account: Arc::new(MessageAccountInfo {
+                pubkey: Pubkey::new_from_array([pubkey; 32]),
+                lamports: write_version * 1_000,
+                owner: Pubkey::new_from_array([0xff; 32]),
+                executable: false,
+                rent_epoch: 0,
+                data: write_version.to_le_bytes().to_vec().into(),
+                write_version,
+                txn_signature: None,
+                base58: Default::default(),
+            }),
+            slot,
+            is_startup: false,
+            created_at: created_at(ms),
+            confirmed_at_slot: None,
+            finalized_at_slot: None,
+        })
+    }
+
+    /// Same account updated few times in one slot, only the last version should be confirmed
+    fn fixture_accounts_dedup() -> Vec<Message> {
+        vec![
+            slot(100, 99, SlotStatus::Processed, 0),
+            account(100, 1, 10, 1),
+            account(100, 2, 11, 2),
+            account(100, 1, 12, 3),
+            account(100, 1, 13, 4),
+            slot(100, 99, SlotStatus::Confirmed, 5),
+        ]
+    }
+
+    /// Slots 101 and 102 are built on 100, only 102 is confirmed
+    fn fixture_fork() -> Vec<Message> {
+        vec![
+            slot(100, 99, SlotStatus::Processed, 0),
+            account(100, 1, 10, 1),
+            slot(101, 100, SlotStatus::Processed, 2),
+            account(101, 1, 11, 3),
+            slot(102, 100, SlotStatus::Processed, 4),
+            account(102, 1, 12, 5),
+            slot(102, 100, SlotStatus::Confirmed, 6),
+        ]
+    }
+
+    async fn write_log(messages: &[Message]) -> Vec<u8> {
+        let mut writer = MessageLogWriter::new(Vec::new());
+        for message in messages {
+            assert!(writer.write(message).await.expect("write to vec"));
+        }
+        writer.writer.into_inner()
+    }
+
+    pub async fn read_log(path: &str) -> Vec<Message> {
+        let mut reader = MessageLogReader::open(path).await.expect("fixture exists");
+        let mut messages = vec![];
+        while let Some(message) = reader.read().await.expect("valid fixture") {
+            messages.push(message);
+        }
+        messages
+    }
+
+    #[tokio::test]
+    async fn test_log_roundtrip() {
+        let mut messages = fixture_accounts_dedup();
+        messages.push(Message::Transaction(MessageTransaction {
+            transaction: Arc::new(MessageTransactionInfo {
+                signature: Signature::from([3; 64]),
+                is_vote: false,
+                transaction: Transaction {
+                    signatures: vec![vec![3; 64]],
+                    message: Some(Default::default()),
+                },
+                meta: TransactionStatusMeta {
+                    loaded_writable_addresses: vec![vec![4; 32]],
+                    ..Default::default()
+                },
+                token_balances_none: false,
+                rewards_none: true,
+                index: 7,
+                account_keys: HashSet::from([Pubkey::new_from_array([4; 32])]),
+                encoded: Default::default(),
+            }),
+            slot: 100,
+            created_at: created_at(6),
+        }));
+        messages.push(Message::Entry(Arc::new(MessageEntry {
+            slot: 100,
+            index: 0,
+            num_hashes: 1,
+            hash: Hash::new_from_array([5; 32]),
+            executed_transaction_count: 1,
+            starting_transaction_index: 0,
+            created_at: created_at(7),
+        })));
+        messages.push(Message::BlockMeta(Arc::new(MessageBlockMeta {
+            block_meta: SubscribeUpdateBlockMeta {
+                slot: 100,
+                parent_slot: 99,
+                executed_transaction_count: 1,
+                entries_count: 1,
+                ..Default::default()
+            },
+            created_at: created_at(8),
+        })));
+
+        let log = write_log(&messages).await;
+        let mut reader = MessageLogReader::new(log.as_slice());
+        for expected in messages {
+            let message = reader.read().await.expect("valid log").expect("message");
+            assert_eq!(message, expected);
+        }
+        assert!(reader.read().await.expect("valid log").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fixtures_up_to_date() {
+        for (path, messages) in [
+            (FIXTURE_ACCOUNTS_DEDUP, fixture_accounts_dedup()),
+            (FIXTURE_FORK, fixture_fork()),
+        ] {
+            assert_eq!(read_log(path).await, messages, "{path} is outdated");
+        }
+    }
+
+    /// Regenerate fixtures with `cargo test -p yellowstone-grpc-geyser generate_fixtures -- --ignored`
+    #[tokio::test]
+    #[ignore]
+    async fn generate_fixtures() {
+        for (path, messages) in [
+            (FIXTURE_ACCOUNTS_DEDUP, fixture_accounts_dedup()),
+            (FIXTURE_FORK, fixture_fork()),
+        ] {
+            tokio::fs::write(path, write_log(&messages).await)
+                .await
+                .expect("failed to write fixture");
+        }
+    }
+}
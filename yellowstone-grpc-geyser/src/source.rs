@@ -0,0 +1,210 @@
+//! Sender of messages to the gRPC service, used by the plugin and by custom message sources.
+
+use {
+    crate::metrics,
+    solana_sdk::clock::Slot,
+    std::collections::BTreeMap,
+    tokio::sync::mpsc,
+    tracing::warn,
+    yellowstone_grpc_proto::plugin::message::{Message, SlotStatus},
+};
+
+/// Sender of messages to the geyser loop, returned by [`GrpcService::create`].
+///
+/// Messages are expected in the same order as the validator notifies the plugin:
+/// - every slot starts with `Slot` message with `Processed` status and `parent`, statuses of the
+///   slot follow in order `Processed`, `Confirmed`, `Finalized`. Duplicated and older statuses
+///   are ignored, missed `Confirmed` / `Finalized` statuses of parent slots are generated by the
+///   service
+/// - `Account`, `Transaction` and `Entry` messages of the slot are sent before `Confirmed` status
+///   of the slot, later messages are delivered only with `processed` and `finalized` commitment
+/// - `BlockMeta` is sent once per slot, before `Finalized` status of the slot. `Block` is
+///   assembled by the service once `executed_transaction_count` transactions and
+///   `entries_count` entries from `BlockMeta` are received, so `Block` should not be sent
+/// - messages of slots up to the first `Processed` status are treated as startup messages and
+///   may violate the order (validator sends statuses of the snapshot slots)
+///
+/// Violations are logged by the geyser loop and counted by `messages_order_violations_total`
+/// metric.
+///
+/// [`GrpcService::create`]: crate::grpc::GrpcService::create
+#[derive(Debug, Clone)]
+pub struct MessagesSender {
+    messages_tx: mpsc::UnboundedSender<Message>,
+}
+
+impl MessagesSender {
+    pub(crate) const fn new(messages_tx: mpsc::UnboundedSender<Message>) -> Self {
+        Self { messages_tx }
+    }
+
+    /// Send message to the geyser loop, returns error if the service is stopped
+    pub fn send(&self, message: Message) -> Result<(), mpsc::error::SendError<Message>> {
+        metrics::message_queue_size_inc();
+        self.messages_tx.send(message).inspect_err(|_error| {
+            metrics::message_queue_size_dec();
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct SlotState {
+    block_meta: bool,
+    finalized: bool,
+}
+
+/// Checker of the order described in [`MessagesSender`], owned by the geyser loop, so
+/// senders are not synchronized
+#[derive(Debug, Default)]
+pub(crate) struct MessagesOrderChecker {
+    first_processed: Option<Slot>,
+    slots: BTreeMap<Slot, SlotState>,
+}
+
+impl MessagesOrderChecker {
+    // slots behind the finalized slot are on dead forks
+    const KEEP_SLOTS: u64 = 32;
+
+    pub(crate) fn check(&mut self, message: &Message) {
+        if let Err(violation) = self.check_order(message) {
+            warn!(
+                "messages order violation at slot {}: {violation}",
+                message.get_slot()
+            );
+            metrics::messages_order_violations_inc(violation);
+        }
+    }
+
+    fn check_order(&mut self, message: &Message) -> Result<(), &'static str> {
+        let slot = message.get_slot();
+        if self.first_processed.is_none() {
+            if let Message::Slot(msg) = message {
+                if msg.status == SlotStatus::Processed {
+                    self.first_processed = Some(msg.slot);
+                }
+            }
+        }
+        if self.first_processed.is_none_or(|first| slot <= first) {
+            return Ok(());
+        }
+
+        let state = self.slots.entry(slot).or_default();
+        match message {
+            Message::Slot(msg) if msg.status == SlotStatus::Finalized => {
+                let block_meta = state.block_meta;
+                state.finalized = true;
+                self.slots = self.slots.split_off(&slot.saturating_sub(Self::KEEP_SLOTS));
+                if !block_meta {
+                    return Err("finalized_before_block_meta");
+                }
+            }
+            Message::Slot(_) => {}
+            // created by the service
+            Message::Block(_)
+            | Message::BlockDiagnostic(_)
+            | Message::Votes(_)
+            | Message::SlotStats(_)
+            | Message::Rollback(_) => return Err("service_message"),
+            Message::BlockMeta(_) => {
+                if state.finalized {
+                    return Err("block_meta_after_finalized");
+                }
+                if state.block_meta {
+                    return Err("duplicated_block_meta");
+                }
+                state.block_meta = true;
+            }
+            Message::Account(_) | Message::Transaction(_) | Message::Entry(_) => {
+                if state.finalized {
+                    return Err("message_after_finalized");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::MessagesOrderChecker,
+        prost_types::Timestamp,
+        solana_sdk::clock::Slot,
+        std::sync::Arc,
+        yellowstone_grpc_proto::{
+            plugin::message::{Message, MessageBlockMeta, MessageSlot, SlotStatus},
+            prelude::SubscribeUpdateBlockMeta,
+        },
+    };
+
+    fn slot(slot: Slot, status: SlotStatus) -> Message {
+        Message::Slot(MessageSlot::new(slot, slot.checked_sub(1), status))
+    }
+
+    fn block_meta(slot: Slot) -> Message {
+        Message::BlockMeta(Arc::new(MessageBlockMeta::from_update_oneof(
+            SubscribeUpdateBlockMeta {
+                slot,
+                ..Default::default()
+            },
+            Timestamp::default(),
+        )))
+    }
+
+    /// Violations of the last message are returned, previous messages are expected valid
+    fn check(messages: &[Message]) -> Result<(), &'static str> {
+        let mut checker = MessagesOrderChecker::default();
+        let (last, messages) = messages.split_last().expect("messages");
+        for message in messages {
+            assert_eq!(checker.check_order(message), Ok(()));
+        }
+        checker.check_order(last)
+    }
+
+    #[test]
+    fn test_valid_order() {
+        let result = check(&[
+            // startup
+            slot(90, SlotStatus::Finalized),
+            slot(100, SlotStatus::Processed),
+            slot(100, SlotStatus::Finalized),
+            // live
+            slot(101, SlotStatus::Processed),
+            block_meta(101),
+            slot(101, SlotStatus::Confirmed),
+            slot(101, SlotStatus::Finalized),
+        ]);
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn test_finalized_before_block_meta() {
+        let result = check(&[
+            slot(100, SlotStatus::Processed),
+            slot(101, SlotStatus::Processed),
+            slot(101, SlotStatus::Finalized),
+        ]);
+        assert_eq!(result, Err("finalized_before_block_meta"));
+    }
+
+    #[test]
+    fn test_duplicated_block_meta() {
+        let result = check(&[
+            slot(100, SlotStatus::Processed),
+            block_meta(101),
+            block_meta(101),
+        ]);
+        assert_eq!(result, Err("duplicated_block_meta"));
+    }
+
+    #[test]
+    fn test_message_after_finalized() {
+        let result = check(&[
+            slot(100, SlotStatus::Processed),
+            block_meta(101),
+            slot(101, SlotStatus::Finalized),
+            block_meta(101),
+        ]);
+        assert_eq!(result, Err("block_meta_after_finalized"));
+    }
+}
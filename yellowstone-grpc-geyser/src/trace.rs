@@ -0,0 +1,117 @@
+//! Tracing spans of the broadcast path and their export to OpenTelemetry collector.
+//!
+//! Without `log.otlp` in the config no subscriber is installed: spans are disabled and events
+//! are written only to the validator log, same as `log` macros.
+
+use {
+    crate::config::ConfigLogOtlp,
+    opentelemetry::{trace::TracerProvider as _, KeyValue},
+    opentelemetry_otlp::{SpanExporter, WithExportConfig},
+    opentelemetry_sdk::{
+        runtime,
+        trace::{Sampler, TracerProvider},
+        Resource,
+    },
+    std::sync::OnceLock,
+    tracing::{debug_span, warn, Span},
+    tracing_subscriber::{
+        layer::{Layered, SubscriberExt},
+        reload, EnvFilter, Layer, Registry,
+    },
+    yellowstone_grpc_proto::plugin::message::{CommitmentLevel, Message},
+};
+
+type FilteredRegistry = Layered<reload::Layer<EnvFilter, Registry>, Registry>;
+
+type OtlpLayer = Option<Box<dyn Layer<FilteredRegistry> + Send + Sync>>;
+
+/// Handles of the global subscriber, the global subscriber can be set only once per process,
+/// so the plugin swaps the filter and the exporter on every load
+struct OtlpReload {
+    filter: reload::Handle<EnvFilter, Registry>,
+    layer: reload::Handle<OtlpLayer, FilteredRegistry>,
+}
+
+impl OtlpReload {
+    /// Fails if the global subscriber is already set by someone else
+    fn get() -> anyhow::Result<&'static Self> {
+        static RELOAD: OnceLock<Option<OtlpReload>> = OnceLock::new();
+        RELOAD
+            .get_or_init(|| {
+                let (filter, filter_handle) = reload::Layer::new(EnvFilter::new("off"));
+                let (layer, layer_handle) = reload::Layer::new(None);
+                tracing::subscriber::set_global_default(
+                    tracing_subscriber::registry().with(filter).with(layer),
+                )
+                .ok()
+                .map(|()| Self {
+                    filter: filter_handle,
+                    layer: layer_handle,
+                })
+            })
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("global tracing subscriber is already set"))
+    }
+}
+
+/// Span of the message from the validator in `geyser_loop`, parent of broadcast batches
+pub fn message_span(message: &Message) -> Span {
+    debug_span!(
+        "geyser_message",
+        slot = message.get_slot(),
+        kind = message.get_type()
+    )
+}
+
+/// Span of the batch sent to clients of the commitment
+pub fn batch_span(commitment: CommitmentLevel, messages: usize, bytes: usize) -> Span {
+    debug_span!("broadcast_batch", commitment = ?commitment, messages, bytes)
+}
+
+/// OTLP exporter of the global subscriber, buffered spans are sent on shutdown and the
+/// exporter is removed from the subscriber, so the plugin can be reloaded
+#[derive(Debug)]
+pub struct OtlpExporter {
+    provider: TracerProvider,
+}
+
+impl OtlpExporter {
+    /// Should be called from the runtime of the plugin, spans are exported by a task spawned
+    /// on the current runtime
+    pub fn init(config: &ConfigLogOtlp) -> anyhow::Result<Self> {
+        let exporter = SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(config.endpoint.clone())
+            .build()?;
+        let provider = TracerProvider::builder()
+            .with_batch_exporter(exporter, runtime::Tokio)
+            .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                config.sampling_ratio,
+            ))))
+            .with_resource(Resource::new([KeyValue::new(
+                "service.name",
+                config.service_name.clone(),
+            )]))
+            .build();
+
+        let filter = EnvFilter::try_new(&config.filter)?;
+        let layer = tracing_opentelemetry::layer()
+            .with_tracer(provider.tracer("yellowstone-grpc-geyser"))
+            .boxed();
+        let reload = OtlpReload::get()?;
+        reload.layer.reload(Some(layer))?;
+        reload.filter.reload(filter)?;
+
+        Ok(Self { provider })
+    }
+
+    pub fn shutdown(self) {
+        if let Ok(reload) = OtlpReload::get() {
+            let _ = reload.filter.reload(EnvFilter::new("off"));
+            let _ = reload.layer.reload(None);
+        }
+        if let Err(error) = self.provider.shutdown() {
+            warn!("failed to shutdown OTLP exporter: {error}");
+        }
+    }
+}
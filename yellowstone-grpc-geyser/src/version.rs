@@ -6,6 +6,7 @@ pub struct Version {
     pub version: &'static str,
     pub proto: &'static str,
     pub solana: &'static str,
+    pub geyser_interface: &'static str,
     pub git: &'static str,
     pub rustc: &'static str,
     pub buildts: &'static str,
@@ -16,6 +17,7 @@ pub const VERSION: Version = Version {
     version: env!("CARGO_PKG_VERSION"),
     proto: env!("YELLOWSTONE_GRPC_PROTO_VERSION"),
     solana: env!("SOLANA_SDK_VERSION"),
+    geyser_interface: env!("GEYSER_INTERFACE_VERSION"),
     git: env!("GIT_VERSION"),
     rustc: env!("VERGEN_RUSTC_SEMVER"),
     buildts: env!("VERGEN_BUILD_TIMESTAMP"),
@@ -44,3 +46,30 @@ impl Default for GrpcVersionInfo {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::GrpcVersionInfo;
+
+    #[test]
+    fn test_version_json() {
+        let json = serde_json::to_string(&GrpcVersionInfo::default()).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        for field in [
+            "package",
+            "version",
+            "proto",
+            "solana",
+            "geyser_interface",
+            "git",
+            "rustc",
+            "buildts",
+        ] {
+            let value = value["version"][field].as_str();
+            assert!(
+                value.is_some_and(|value| !value.is_empty()),
+                "field {field} is missed or empty"
+            );
+        }
+    }
+}
@@ -0,0 +1,424 @@
+//! Harness for integration tests: gRPC service on a loopback port with injected messages and a
+//! real client connected over TCP.
+//!
+//! Every test binary includes this module with `mod common;`, so not every item is used by
+//! every binary.
+#![allow(dead_code)]
+
+use {
+    futures::{
+        channel::mpsc::SendError,
+        sink::{Sink, SinkExt},
+        stream::{BoxStream, StreamExt},
+    },
+    serde_json::{json, Value},
+    solana_sdk::{
+        account::Account,
+        clock::Slot,
+        hash::Hash,
+        pubkey::Pubkey,
+        signature::{Keypair, Signature},
+        system_program, system_transaction,
+        transaction::VersionedTransaction,
+    },
+    solana_transaction_status::TransactionStatusMeta,
+    std::{net::SocketAddr, pin::Pin, sync::Arc, time::Duration},
+    tokio::{sync::Notify, time::timeout},
+    tonic::{service::Interceptor, Status},
+    yellowstone_grpc_client::{GeyserGrpcBuilder, GeyserGrpcClient},
+    yellowstone_grpc_geyser::{
+        config::{ConfigGrpc, ConfigTokio},
+        grpc::{BoundAddresses, GrpcService},
+        source::MessagesSender,
+    },
+    yellowstone_grpc_proto::{
+        plugin::message::{
+            Message, MessageAccount, MessageAccountInfo, MessageBlockMeta, MessageEntry,
+            MessageSlot, MessageTransaction, MessageTransactionInfo, SlotStatus,
+        },
+        prelude::{
+            subscribe_update::UpdateOneof, SlotStatus as SlotStatusProto, SubscribeRequest,
+            SubscribeRequestPing, SubscribeUpdate, SubscribeUpdateStreamInfo,
+        },
+    },
+};
+
+/// Max time to wait for an update, the test fails after it
+pub const UPDATE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// gRPC service created with `GrpcService::create`, stopped on drop
+#[derive(Debug)]
+pub struct TestServer {
+    address: SocketAddr,
+    messages_tx: MessagesSender,
+    shutdown: Arc<Notify>,
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.shutdown.notify_one();
+    }
+}
+
+impl TestServer {
+    /// Start the service on a free loopback port with default config
+    pub async fn start() -> Self {
+        Self::start_with_config(json!({})).await
+    }
+
+    /// Start the service, `config` is a JSON object with `grpc` options, `address` is set by
+    /// the harness
+    pub async fn start_with_config(mut config: Value) -> Self {
+        config["address"] = json!("127.0.0.1:0");
+        let config: ConfigGrpc = serde_json::from_value(config).expect("valid config");
+        let (_snapshot_tx, messages_tx, shutdown, BoundAddresses { grpc: address, .. }) =
+            GrpcService::create(ConfigTokio::default(), config, None, false)
+                .await
+                .expect("service created");
+        Self {
+            address,
+            messages_tx,
+            shutdown,
+        }
+    }
+
+    pub const fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub fn endpoint(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    /// Send message to the geyser loop, as the plugin does
+    pub fn send(&self, message: Message) {
+        self.messages_tx
+            .send(message)
+            .expect("geyser loop is alive");
+    }
+
+    pub fn send_all(&self, messages: impl IntoIterator<Item = Message>) {
+        for message in messages {
+            self.send(message);
+        }
+    }
+
+    /// Stop accepting new connections
+    pub fn shutdown(&self) {
+        self.shutdown.notify_one();
+    }
+
+    /// Builder of the client for the service, options can be added before `connect`
+    pub fn client_builder(&self) -> GeyserGrpcBuilder {
+        GeyserGrpcClient::build_from_shared(self.endpoint())
+            .expect("valid endpoint")
+            .connect_timeout(Duration::from_secs(5))
+    }
+
+    pub async fn connect(&self) -> GeyserGrpcClient<impl Interceptor> {
+        self.client_builder().connect().await.expect("connected")
+    }
+}
+
+type RequestsSink<'a> = Pin<Box<dyn Sink<SubscribeRequest, Error = SendError> + Send + 'a>>;
+
+/// Subscription of the client with helpers to wait for updates
+pub struct TestSubscription<'a> {
+    requests: RequestsSink<'a>,
+    updates: BoxStream<'a, Result<SubscribeUpdate, Status>>,
+}
+
+impl<'a> TestSubscription<'a> {
+    pub async fn new<F: Interceptor + Send + 'a>(
+        client: &'a mut GeyserGrpcClient<F>,
+        request: SubscribeRequest,
+    ) -> Self {
+        let (requests, updates) = client
+            .subscribe_with_request(Some(request))
+            .await
+            .expect("subscribed");
+        let mut subscription = Self {
+            requests: Box::pin(requests),
+            updates: updates.boxed(),
+        };
+        subscription.wait_stream_info().await;
+        subscription
+    }
+
+    /// Replace filters of the subscription, returns once the new filter is applied
+    pub async fn update_filter(&mut self, request: SubscribeRequest) -> SubscribeUpdateStreamInfo {
+        self.requests.send(request).await.expect("stream is alive");
+        self.wait_stream_info().await
+    }
+
+    /// Stream info is the first update after the accepted filter, updates before it are skipped
+    async fn wait_stream_info(&mut self) -> SubscribeUpdateStreamInfo {
+        self.next_matching(|update| match update {
+            UpdateOneof::StreamInfo(msg) => Some(msg),
+            _ => None,
+        })
+        .await
+    }
+
+    /// Next item of the stream, `None` if the stream is closed
+    pub async fn next(&mut self) -> Option<Result<SubscribeUpdate, Status>> {
+        self.next_timeout(UPDATE_TIMEOUT).await
+    }
+
+    pub async fn next_timeout(
+        &mut self,
+        duration: Duration,
+    ) -> Option<Result<SubscribeUpdate, Status>> {
+        timeout(duration, self.updates.next())
+            .await
+            .expect("timeout")
+    }
+
+    /// Next update, ping from the service is skipped
+    pub async fn next_update(&mut self) -> UpdateOneof {
+        loop {
+            let update = self
+                .next()
+                .await
+                .expect("stream is alive")
+                .expect("valid update");
+            match update.update_oneof.expect("update is set") {
+                UpdateOneof::Ping(_) => {}
+                update => return update,
+            }
+        }
+    }
+
+    /// Skip updates until `f` returns `Some`
+    pub async fn next_matching<T>(&mut self, mut f: impl FnMut(UpdateOneof) -> Option<T>) -> T {
+        loop {
+            if let Some(value) = f(self.next_update().await) {
+                return value;
+            }
+        }
+    }
+
+    /// Updates received before the slot update with `status` of `slot`
+    pub async fn collect_until_slot(&mut self, slot: Slot, status: SlotStatus) -> Vec<UpdateOneof> {
+        let status = SlotStatusProto::from(status) as i32;
+        let mut updates = vec![];
+        loop {
+            match self.next_update().await {
+                UpdateOneof::Slot(msg) if msg.slot == slot && msg.status == status => {
+                    return updates
+                }
+                update => updates.push(update),
+            }
+        }
+    }
+
+    /// Send ping request, returns updates received before the pong
+    pub async fn ping(&mut self, id: i32) -> Vec<UpdateOneof> {
+        self.requests
+            .send(SubscribeRequest {
+                ping: Some(SubscribeRequestPing { id }),
+                ..Default::default()
+            })
+            .await
+            .expect("stream is alive");
+        let mut updates = vec![];
+        loop {
+            match self.next_update().await {
+                UpdateOneof::Pong(msg) if msg.id == id => return updates,
+                update => updates.push(update),
+            }
+        }
+    }
+
+    /// Error of the stream, updates before it are skipped
+    pub async fn error(&mut self) -> Status {
+        loop {
+            match self.next().await.expect("stream is alive") {
+                Ok(_update) => {}
+                Err(status) => return status,
+            }
+        }
+    }
+}
+
+/// Messages of the slot produced by [`TestChain`]
+#[derive(Debug, Clone)]
+pub struct TestBlock {
+    pub slot: Slot,
+    pub parent: Slot,
+    pub blockhash: Hash,
+    pub parent_blockhash: Hash,
+    pub transactions: Vec<MessageTransactionInfo>,
+    pub accounts: Vec<MessageAccountInfo>,
+}
+
+impl TestBlock {
+    pub fn status(&self, status: SlotStatus) -> Message {
+        Message::Slot(MessageSlot::new(self.slot, Some(self.parent), status))
+    }
+
+    pub fn entries_count(&self) -> usize {
+        self.transactions.len().max(1)
+    }
+
+    /// Accounts, transactions, entries (one per transaction) and block meta of the block
+    pub fn body(&self) -> Vec<Message> {
+        let mut messages = vec![];
+        for (transaction, account) in self.transactions.iter().zip(self.accounts.iter()) {
+            messages.push(Message::Account(MessageAccount::new(
+                account.clone(),
+                self.slot,
+            )));
+            messages.push(Message::Transaction(MessageTransaction::new(
+                transaction.clone(),
+                self.slot,
+            )));
+        }
+        let executed_per_entry = u64::from(!self.transactions.is_empty());
+        for index in 0..self.entries_count() {
+            messages.push(Message::Entry(Arc::new(MessageEntry::new(
+                self.slot,
+                index,
+                1,
+                Hash::new_unique(),
+                executed_per_entry,
+                index as u64 * executed_per_entry,
+            ))));
+        }
+        messages.push(Message::BlockMeta(Arc::new(
+            MessageBlockMeta::new(
+                self.slot,
+                self.parent,
+                self.blockhash,
+                self.parent_blockhash,
+                self.transactions.len() as u64,
+                self.entries_count() as u64,
+            )
+            .with_block_height(self.slot),
+        )));
+        messages
+    }
+
+    /// Processed status, body of the block and confirmed status
+    pub fn messages(&self) -> Vec<Message> {
+        let mut messages = vec![self.status(SlotStatus::Processed)];
+        messages.extend(self.body());
+        messages.push(self.status(SlotStatus::Confirmed));
+        messages
+    }
+}
+
+/// Producer of consecutive blocks, every transaction is a transfer to a new account
+#[derive(Debug)]
+pub struct TestChain {
+    slot: Slot,
+    parent: Slot,
+    blockhash: Hash,
+    payer: Keypair,
+    write_version: u64,
+}
+
+impl TestChain {
+    pub fn new(first_slot: Slot) -> Self {
+        Self {
+            slot: first_slot.max(1) - 1,
+            parent: first_slot.max(1) - 1,
+            blockhash: Hash::new_unique(),
+            payer: Keypair::new(),
+            write_version: 0,
+        }
+    }
+
+    /// Slot of the last produced block
+    pub const fn slot(&self) -> Slot {
+        self.parent
+    }
+
+    /// Next `count` slots are skipped by the leaders, parent of the next block is not changed
+    pub fn skip_slots(&mut self, count: u64) {
+        self.slot += count;
+    }
+
+    pub fn next_blocks(&mut self, count: usize, transactions: usize) -> Vec<TestBlock> {
+        (0..count).map(|_| self.next_block(transactions)).collect()
+    }
+
+    pub fn next_block(&mut self, transactions: usize) -> TestBlock {
+        let parent = self.parent;
+        let parent_blockhash = self.blockhash;
+        self.slot += 1;
+        self.parent = self.slot;
+        self.blockhash = Hash::new_unique();
+
+        let mut block = TestBlock {
+            slot: self.slot,
+            parent,
+            blockhash: self.blockhash,
+            parent_blockhash,
+            transactions: Vec::with_capacity(transactions),
+            accounts: Vec::with_capacity(transactions),
+        };
+        for index in 0..transactions {
+            let recipient = Pubkey::new_unique();
+            let transaction = VersionedTransaction::from(system_transaction::transfer(
+                &self.payer,
+                &recipient,
+                1,
+                parent_blockhash,
+            ));
+            let meta = TransactionStatusMeta {
+                status: Ok(()),
+                fee: 5_000,
+                pre_balances: vec![1_000_000, 0, 1],
+                post_balances: vec![994_999, 1, 1],
+                ..Default::default()
+            };
+            self.write_version += 1;
+            block.accounts.push(create_account(
+                recipient,
+                1,
+                0,
+                self.write_version,
+                Some(transaction.signatures[0]),
+            ));
+            block
+                .transactions
+                .push(MessageTransactionInfo::from_transaction(
+                    &transaction,
+                    &meta,
+                    false,
+                    index,
+                ));
+        }
+        block
+    }
+}
+
+/// Messages of confirmed blocks, every block is finalized once `finalized_lag` blocks are
+/// confirmed after it, as the validator does
+pub fn progression(blocks: &[TestBlock], finalized_lag: usize) -> Vec<Message> {
+    let mut messages = vec![];
+    for (index, block) in blocks.iter().enumerate() {
+        messages.extend(block.messages());
+        if let Some(finalized) = index.checked_sub(finalized_lag) {
+            messages.push(blocks[finalized].status(SlotStatus::Finalized));
+        }
+    }
+    messages
+}
+
+/// System account with `data_len` zero bytes of data
+pub fn create_account(
+    pubkey: Pubkey,
+    lamports: u64,
+    data_len: usize,
+    write_version: u64,
+    txn_signature: Option<Signature>,
+) -> MessageAccountInfo {
+    MessageAccountInfo::from_account(
+        pubkey,
+        &Account::new(lamports, data_len, &system_program::id()),
+        write_version,
+        txn_signature,
+    )
+}
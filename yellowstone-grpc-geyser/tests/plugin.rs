@@ -0,0 +1,76 @@
+mod common;
+
+use {
+    agave_geyser_plugin_interface::geyser_plugin_interface::{GeyserPlugin, SlotStatus},
+    common::TestSubscription,
+    serde_json::json,
+    std::{collections::HashMap, net::TcpStream, time::Duration},
+    yellowstone_grpc_client::GeyserGrpcClient,
+    yellowstone_grpc_geyser::plugin::Plugin,
+    yellowstone_grpc_proto::prelude::{
+        subscribe_update::UpdateOneof, SubscribeRequest, SubscribeRequestFilterSlots,
+    },
+};
+
+// The validator loads the plugin from its own thread, not from an async context, so the
+// plugin has to create and own the runtime of all its tasks
+#[test]
+fn test_plugin_on_load_from_sync_context() {
+    let config_path = std::env::temp_dir().join(format!(
+        "yellowstone-grpc-geyser-plugin-{}.json",
+        std::process::id()
+    ));
+    let config = json!({
+        "libpath": "libyellowstone_grpc_geyser.so",
+        "tokio": {
+            "worker_threads": 2,
+            "thread_name_prefix": "solGeyserTest"
+        },
+        "grpc": {
+            "address": "127.0.0.1:0"
+        }
+    });
+    std::fs::write(&config_path, config.to_string()).expect("config is written");
+
+    let mut plugin = Plugin::default();
+    plugin
+        .on_load(config_path.to_str().expect("valid path"), false)
+        .expect("plugin is loaded");
+    std::fs::remove_file(&config_path).expect("config is removed");
+    let address = plugin.bound_addresses().expect("plugin is loaded").grpc;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("client runtime");
+    runtime.block_on(async {
+        let mut client = GeyserGrpcClient::build_from_shared(format!("http://{address}"))
+            .expect("valid endpoint")
+            .connect_timeout(Duration::from_secs(5))
+            .connect()
+            .await
+            .expect("connected");
+        let request = SubscribeRequest {
+            slots: HashMap::from([("plugin".to_owned(), SubscribeRequestFilterSlots::default())]),
+            ..Default::default()
+        };
+        let mut subscription = TestSubscription::new(&mut client, request).await;
+
+        // geyser callbacks are called from validator threads
+        plugin
+            .update_slot_status(42, Some(41), &SlotStatus::Processed)
+            .expect("slot status is sent");
+        let slot = subscription
+            .next_matching(|update| match update {
+                UpdateOneof::Slot(msg) => Some(msg.slot),
+                _ => None,
+            })
+            .await;
+        assert_eq!(slot, 42);
+    });
+    drop(runtime);
+
+    // the server and all tasks are stopped with the runtime of the plugin
+    plugin.on_unload();
+    assert!(TcpStream::connect(address).is_err());
+}
@@ -0,0 +1,582 @@
+mod common;
+
+use {
+    common::{create_account, progression, TestChain, TestServer, TestSubscription},
+    futures::stream::StreamExt,
+    serde_json::json,
+    solana_sdk::pubkey::Pubkey,
+    std::{collections::HashMap, time::Duration},
+    tokio::time::{sleep, timeout},
+    tonic::{codec::CompressionEncoding, Code},
+    yellowstone_grpc_client::GeyserGrpcClientError,
+    yellowstone_grpc_proto::{
+        plugin::message::{Message, MessageAccount, SlotStatus},
+        prelude::{
+            subscribe_update::UpdateOneof, CommitmentLevel, SlotStatus as SlotStatusProto,
+            SubscribeRequest, SubscribeRequestFilterAccounts, SubscribeRequestFilterBlocks,
+            SubscribeRequestFilterBlocksMeta, SubscribeRequestFilterEntry,
+            SubscribeRequestFilterSlots, SubscribeRequestFilterTransactions,
+        },
+    },
+};
+
+const PING_INTERVAL: Duration = Duration::from_millis(100);
+
+fn filters<T>(filter: T) -> HashMap<String, T> {
+    HashMap::from([("test".to_owned(), filter)])
+}
+
+fn request_slots() -> SubscribeRequest {
+    SubscribeRequest {
+        slots: filters(SubscribeRequestFilterSlots::default()),
+        ..Default::default()
+    }
+}
+
+fn request_accounts(commitment: CommitmentLevel) -> SubscribeRequest {
+    SubscribeRequest {
+        accounts: filters(SubscribeRequestFilterAccounts::default()),
+        slots: filters(SubscribeRequestFilterSlots::default()),
+        commitment: Some(commitment as i32),
+        ..Default::default()
+    }
+}
+
+fn request_accounts_default() -> SubscribeRequest {
+    SubscribeRequest {
+        accounts: filters(SubscribeRequestFilterAccounts::default()),
+        ..Default::default()
+    }
+}
+
+fn get_account(update: UpdateOneof) -> Option<(Vec<u8>, u64, u64)> {
+    match update {
+        UpdateOneof::Account(msg) => msg
+            .account
+            .map(|account| (account.pubkey, account.lamports, account.write_version)),
+        _ => None,
+    }
+}
+
+fn get_accounts(updates: Vec<UpdateOneof>) -> Vec<(Vec<u8>, u64, u64)> {
+    updates.into_iter().filter_map(get_account).collect()
+}
+
+fn get_slot_statuses(updates: &[UpdateOneof]) -> Vec<(u64, i32)> {
+    updates
+        .iter()
+        .filter_map(|update| match update {
+            UpdateOneof::Slot(msg) => Some((msg.slot, msg.status)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_slot_statuses_progression() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+    let mut stream = TestSubscription::new(&mut client, request_slots()).await;
+
+    let mut chain = TestChain::new(100);
+    let mut blocks = chain.next_blocks(3, 1);
+    chain.skip_slots(2);
+    blocks.extend(chain.next_blocks(5, 1));
+    server.send_all(progression(&blocks, 4));
+
+    let last_finalized = &blocks[blocks.len() - 1 - 4];
+    let updates = stream
+        .collect_until_slot(last_finalized.slot, SlotStatus::Finalized)
+        .await;
+    let mut statuses = get_slot_statuses(&updates);
+    statuses.push((last_finalized.slot, SlotStatusProto::SlotFinalized as i32));
+    for block in &blocks[..blocks.len() - 4] {
+        let block_statuses = statuses
+            .iter()
+            .filter(|(slot, _status)| *slot == block.slot)
+            .map(|(_slot, status)| *status)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            block_statuses,
+            [
+                SlotStatusProto::SlotProcessed as i32,
+                SlotStatusProto::SlotConfirmed as i32,
+                SlotStatusProto::SlotFinalized as i32,
+            ],
+            "statuses of slot {}",
+            block.slot
+        );
+    }
+    // skipped slots never get statuses
+    assert!(statuses
+        .iter()
+        .all(|(slot, _status)| blocks.iter().any(|block| block.slot == *slot)));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_missed_statuses_of_parents() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+    let mut stream = TestSubscription::new(&mut client, request_slots()).await;
+
+    // validator does not always send confirmed status of every slot
+    let mut chain = TestChain::new(100);
+    let parent = chain.next_block(0);
+    chain.skip_slots(1);
+    let block = chain.next_block(0);
+    server.send(parent.status(SlotStatus::Processed));
+    server.send_all(parent.body());
+    server.send_all(block.messages());
+
+    let updates = stream
+        .collect_until_slot(block.slot, SlotStatus::Confirmed)
+        .await;
+    assert!(
+        get_slot_statuses(&updates).contains(&(parent.slot, SlotStatusProto::SlotConfirmed as i32))
+    );
+
+    server.send(block.status(SlotStatus::Finalized));
+    let updates = stream
+        .collect_until_slot(block.slot, SlotStatus::Finalized)
+        .await;
+    assert_eq!(
+        get_slot_statuses(&updates),
+        [(parent.slot, SlotStatusProto::SlotFinalized as i32)]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_commitment_levels() {
+    let server = TestServer::start().await;
+    let request = |commitment| SubscribeRequest {
+        accounts: filters(SubscribeRequestFilterAccounts::default()),
+        commitment: Some(commitment as i32),
+        ..Default::default()
+    };
+    let mut processed_client = server.connect().await;
+    let mut processed =
+        TestSubscription::new(&mut processed_client, request(CommitmentLevel::Processed)).await;
+    let mut confirmed_client = server.connect().await;
+    let mut confirmed =
+        TestSubscription::new(&mut confirmed_client, request(CommitmentLevel::Confirmed)).await;
+    let mut finalized_client = server.connect().await;
+    let mut finalized =
+        TestSubscription::new(&mut finalized_client, request(CommitmentLevel::Finalized)).await;
+
+    let block = TestChain::new(100).next_block(1);
+    let pubkey = block.accounts[0].pubkey.to_bytes().to_vec();
+    server.send(block.status(SlotStatus::Processed));
+    server.send_all(block.body());
+    assert_eq!(processed.next_matching(get_account).await.0, pubkey);
+
+    // messages of the slot are sent with the commitment status of the slot
+    assert_eq!(confirmed.ping(1).await, []);
+    assert_eq!(finalized.ping(1).await, []);
+    server.send(block.status(SlotStatus::Confirmed));
+    assert_eq!(confirmed.next_matching(get_account).await.0, pubkey);
+    assert_eq!(finalized.ping(2).await, []);
+    server.send(block.status(SlotStatus::Finalized));
+    assert_eq!(finalized.next_matching(get_account).await.0, pubkey);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_account_dedup_by_write_version() {
+    let server = TestServer::start().await;
+    let mut processed_client = server.connect().await;
+    let mut processed = TestSubscription::new(
+        &mut processed_client,
+        request_accounts(CommitmentLevel::Processed),
+    )
+    .await;
+    let mut confirmed_client = server.connect().await;
+    let mut confirmed = TestSubscription::new(
+        &mut confirmed_client,
+        request_accounts(CommitmentLevel::Confirmed),
+    )
+    .await;
+
+    let block = TestChain::new(100).next_block(0);
+    let pubkey = Pubkey::new_unique();
+    let account = |lamports, write_version| {
+        Message::Account(MessageAccount::new(
+            create_account(pubkey, lamports, 0, write_version, None),
+            block.slot,
+        ))
+    };
+    server.send(block.status(SlotStatus::Processed));
+    server.send_all([account(10, 1), account(20, 2), account(30, 3)]);
+    server.send_all(block.body());
+    server.send(block.status(SlotStatus::Confirmed));
+
+    // every write is sent with processed commitment, only the latest with confirmed
+    let pubkey = pubkey.to_bytes().to_vec();
+    let updates = processed
+        .collect_until_slot(block.slot, SlotStatus::Confirmed)
+        .await;
+    assert_eq!(
+        get_accounts(updates),
+        [
+            (pubkey.clone(), 10, 1),
+            (pubkey.clone(), 20, 2),
+            (pubkey.clone(), 30, 3)
+        ]
+    );
+    let updates = confirmed
+        .collect_until_slot(block.slot, SlotStatus::Confirmed)
+        .await;
+    assert_eq!(get_accounts(updates), [(pubkey, 30, 3)]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_transactions_account_include() {
+    let server = TestServer::start().await;
+    let mut chain = TestChain::new(100);
+    let block = chain.next_block(3);
+    let mut client = server.connect().await;
+    let mut stream = TestSubscription::new(
+        &mut client,
+        SubscribeRequest {
+            slots: filters(SubscribeRequestFilterSlots::default()),
+            transactions: filters(SubscribeRequestFilterTransactions {
+                account_include: vec![block.accounts[1].pubkey.to_string()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    server.send_all(block.messages());
+    let signatures = stream
+        .collect_until_slot(block.slot, SlotStatus::Confirmed)
+        .await
+        .into_iter()
+        .filter_map(|update| match update {
+            UpdateOneof::Transaction(msg) => msg.transaction.map(|tx| tx.signature),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        signatures,
+        [block.transactions[1].signature.as_ref().to_vec()]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_block_assembly() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+    let mut stream = TestSubscription::new(
+        &mut client,
+        SubscribeRequest {
+            blocks: filters(SubscribeRequestFilterBlocks {
+                include_transactions: Some(true),
+                include_accounts: Some(true),
+                include_entries: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    let mut chain = TestChain::new(100);
+    let blocks = chain.next_blocks(2, 3);
+    server.send_all(progression(&blocks, 1));
+    for block in &blocks {
+        let msg = stream
+            .next_matching(|update| match update {
+                UpdateOneof::Block(msg) => Some(msg),
+                _ => None,
+            })
+            .await;
+        assert_eq!(msg.slot, block.slot);
+        assert_eq!(msg.blockhash, block.blockhash.to_string());
+        assert_eq!(msg.parent_slot, block.parent);
+        assert_eq!(msg.parent_blockhash, block.parent_blockhash.to_string());
+        assert_eq!(msg.executed_transaction_count, 3);
+        assert_eq!(msg.entries_count, 3);
+        assert_eq!(msg.transactions.len(), 3);
+        assert_eq!(msg.accounts.len(), 3);
+        assert_eq!(msg.entries.len(), 3);
+        assert!(!msg.is_partial);
+        assert_eq!(msg.missing_transaction_count, 0);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_blocks_meta_and_entries() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+    let mut stream = TestSubscription::new(
+        &mut client,
+        SubscribeRequest {
+            slots: filters(SubscribeRequestFilterSlots::default()),
+            blocks_meta: filters(SubscribeRequestFilterBlocksMeta::default()),
+            entry: filters(SubscribeRequestFilterEntry::default()),
+            commitment: Some(CommitmentLevel::Confirmed as i32),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    let block = TestChain::new(100).next_block(2);
+    server.send_all(block.messages());
+    let updates = stream
+        .collect_until_slot(block.slot, SlotStatus::Confirmed)
+        .await;
+    let entries = updates
+        .iter()
+        .filter(|update| matches!(update, UpdateOneof::Entry(msg) if msg.slot == block.slot))
+        .count();
+    assert_eq!(entries, block.entries_count());
+    let blocks_meta = updates
+        .iter()
+        .filter_map(|update| match update {
+            UpdateOneof::BlockMeta(msg) => Some((msg.slot, msg.blockhash.clone())),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(blocks_meta, [(block.slot, block.blockhash.to_string())]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_filter_update_mid_stream() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+    let mut stream = TestSubscription::new(&mut client, request_slots()).await;
+
+    let mut chain = TestChain::new(100);
+    let block = chain.next_block(1);
+    server.send_all(block.messages());
+    let updates = stream
+        .collect_until_slot(block.slot, SlotStatus::Confirmed)
+        .await;
+    assert_eq!(get_accounts(updates), []);
+
+    let stream_info = stream
+        .update_filter(request_accounts(CommitmentLevel::Processed))
+        .await;
+    assert_eq!(stream_info.accounts, ["test"]);
+    assert_eq!(stream_info.slots, ["test"]);
+
+    let block = chain.next_block(1);
+    server.send_all(block.messages());
+    let updates = stream
+        .collect_until_slot(block.slot, SlotStatus::Confirmed)
+        .await;
+    let pubkey = block.accounts[0].pubkey.to_bytes().to_vec();
+    assert_eq!(
+        get_accounts(updates)
+            .into_iter()
+            .map(|(pubkey, _lamports, _write_version)| pubkey)
+            .collect::<Vec<_>>(),
+        [pubkey]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_invalid_filter_rejected() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+    let stream = client
+        .subscribe_once(SubscribeRequest {
+            accounts: filters(SubscribeRequestFilterAccounts {
+                account: vec!["invalid".to_owned()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .await
+        .expect("subscribed");
+    tokio::pin!(stream);
+
+    let status = timeout(common::UPDATE_TIMEOUT, stream.next())
+        .await
+        .expect("timeout")
+        .expect("stream is alive")
+        .expect_err("filter is rejected");
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_default_and_allowed_commitments() {
+    let server = TestServer::start_with_config(json!({
+        "filter_limits": {
+            "default_commitment": "confirmed",
+            "allowed_commitments": ["confirmed", "finalized"],
+        },
+    }))
+    .await;
+
+    // omitted commitment is replaced by the default
+    let mut client = server.connect().await;
+    let mut stream = TestSubscription::new(&mut client, request_slots()).await;
+    let info = stream.update_filter(request_accounts_default()).await;
+    assert_eq!(info.commitment, CommitmentLevel::Confirmed as i32);
+    let block = TestChain::new(100).next_block(1);
+    let pubkey = block.accounts[0].pubkey.to_bytes().to_vec();
+    server.send_all(block.messages());
+    assert_eq!(stream.next_matching(get_account).await.0, pubkey);
+
+    // not supported commitment is rejected on the stream and by unary methods
+    let mut client = server.connect().await;
+    let stream = client
+        .subscribe_once(request_accounts(CommitmentLevel::Processed))
+        .await
+        .expect("subscribed");
+    tokio::pin!(stream);
+    let status = timeout(common::UPDATE_TIMEOUT, stream.next())
+        .await
+        .expect("timeout")
+        .expect("stream is alive")
+        .expect_err("filter is rejected");
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert!(
+        status.message().contains("supported: confirmed, finalized"),
+        "{status:?}"
+    );
+
+    match client.get_slot(Some(CommitmentLevel::Processed)).await {
+        Err(GeyserGrpcClientError::TonicStatus(status)) => {
+            assert_eq!(status.code(), Code::InvalidArgument)
+        }
+        result => panic!("unexpected result: {result:?}"),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_compression() {
+    let server = TestServer::start().await;
+    let mut chain = TestChain::new(100);
+    for encoding in [CompressionEncoding::Gzip, CompressionEncoding::Zstd] {
+        let mut client = server
+            .client_builder()
+            .send_compressed(encoding)
+            .accept_compressed(encoding)
+            .connect()
+            .await
+            .expect("connected");
+        let mut stream =
+            TestSubscription::new(&mut client, request_accounts(CommitmentLevel::Processed)).await;
+
+        let block = chain.next_block(1);
+        server.send_all(block.messages());
+        let updates = stream
+            .collect_until_slot(block.slot, SlotStatus::Confirmed)
+            .await;
+        assert_eq!(get_accounts(updates).len(), 1, "{encoding:?}");
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_lagged_client_disconnect() {
+    let server = TestServer::start_with_config(json!({
+        "channel_capacity": 1,
+        "channel_capacity_processed": 100_000,
+    }))
+    .await;
+    let mut client = server.connect().await;
+    let mut stream =
+        TestSubscription::new(&mut client, request_accounts(CommitmentLevel::Processed)).await;
+
+    // updates are not read until accounts are over HTTP/2 windows and socket buffers
+    let block = TestChain::new(100).next_block(0);
+    server.send(block.status(SlotStatus::Processed));
+    for write_version in 0..256 {
+        server.send(Message::Account(MessageAccount::new(
+            create_account(Pubkey::new_unique(), 1, 256 * 1024, write_version, None),
+            block.slot,
+        )));
+    }
+
+    let status = stream.error().await;
+    assert_eq!(status.code(), Code::Internal);
+    assert!(status.message().starts_with("lagged"), "{status:?}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_ping_pong() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+    let pong = client.ping(3).await.expect("pong");
+    assert_eq!(pong.count, 3);
+    assert!(pong.server_timestamp > 0);
+
+    let mut stream = TestSubscription::new(&mut client, request_slots()).await;
+    for id in 1..=3 {
+        assert_eq!(stream.ping(id).await, []);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_server_ping() {
+    let server = TestServer::start_with_config(json!({ "ping_interval": "100ms" })).await;
+    let mut client = server.connect().await;
+    let mut stream = TestSubscription::new(&mut client, request_slots()).await;
+
+    // ping keeps the connection alive without updates
+    let mut seq = 0;
+    while seq < 2 {
+        let update = stream
+            .next_timeout(PING_INTERVAL * 2)
+            .await
+            .expect("stream is alive")
+            .expect("valid update");
+        if let Some(UpdateOneof::Ping(msg)) = update.update_oneof {
+            assert_eq!(msg.seq, seq + 1);
+            assert!(msg.server_timestamp > 0);
+            seq = msg.seq;
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_x_token() {
+    let server = TestServer::start_with_config(json!({ "x_token": "secret" })).await;
+
+    let mut client = server.connect().await;
+    match client.subscribe_once(request_slots()).await {
+        Err(GeyserGrpcClientError::TonicStatus(status)) => {
+            assert_eq!(status.code(), Code::Unauthenticated)
+        }
+        Err(error) => panic!("unexpected error: {error:?}"),
+        Ok(_stream) => panic!("subscribed without x_token"),
+    }
+
+    let mut client = server
+        .client_builder()
+        .x_token(Some("secret"))
+        .expect("valid x_token")
+        .connect()
+        .await
+        .expect("connected");
+    let mut stream = TestSubscription::new(&mut client, request_slots()).await;
+    let block = TestChain::new(100).next_block(0);
+    server.send_all(block.messages());
+    stream
+        .collect_until_slot(block.slot, SlotStatus::Confirmed)
+        .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_server_shutdown() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+    assert!(client.get_version().await.is_ok());
+
+    server.shutdown();
+    timeout(common::UPDATE_TIMEOUT, async {
+        loop {
+            let builder = server.client_builder().timeout(Duration::from_secs(1));
+            match builder.connect().await {
+                Ok(mut client) if client.get_version().await.is_ok() => {
+                    sleep(Duration::from_millis(50)).await;
+                }
+                _ => break,
+            }
+        }
+    })
+    .await
+    .expect("new requests are rejected");
+}
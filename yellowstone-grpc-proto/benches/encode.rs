@@ -1,17 +1,29 @@
 use {
-    criterion::{criterion_group, criterion_main, BenchmarkId, Criterion},
+    criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion},
     prost::Message as _,
     prost_types::Timestamp,
-    std::time::{Duration, SystemTime},
-    yellowstone_grpc_proto::plugin::{
-        filter::message::{
-            tests::{
-                create_accounts, create_message_filters, load_predefined_blocks,
-                load_predefined_transactions,
+    solana_sdk::pubkey::Pubkey,
+    std::{
+        sync::Arc,
+        time::{Duration, SystemTime},
+    },
+    yellowstone_grpc_proto::{
+        plugin::{
+            filter::{
+                message::{
+                    tests::{
+                        create_accounts, create_message_filters, load_predefined_blocks,
+                        load_predefined_transactions,
+                    },
+                    FilteredUpdate, FilteredUpdateOneof,
+                },
+                FilterAccountsDataSlice,
+            },
+            message::{
+                MessageAccount, MessageAccountInfo, MessageTransaction, MessageTransactionInfo,
             },
-            FilteredUpdate, FilteredUpdateOneof,
         },
-        message::MessageTransaction,
+        prost::bytes::Bytes,
     },
 };
 
@@ -42,7 +54,12 @@ fn bench_account(c: &mut Criterion) {
         .map(|(msg, data_slice)| FilteredUpdate {
             filters: filters.clone(),
             message: FilteredUpdateOneof::account(&msg, data_slice),
-            created_at: Timestamp::from(SystemTime::now()),
+            created_at: Some(Timestamp::from(SystemTime::now())),
+            warnings: vec![],
+            sequence: 0,
+            dropped_since_last: 0,
+            dropped_batches_since_last: 0,
+            encoded_bytes: None,
         })
         .collect::<Vec<_>>();
     bench!(&updates, "accounts");
@@ -56,7 +73,12 @@ fn bench_account(c: &mut Criterion) {
                 slot: 42,
                 created_at: Timestamp::from(SystemTime::now()),
             }),
-            created_at: Timestamp::from(SystemTime::now()),
+            created_at: Some(Timestamp::from(SystemTime::now())),
+            warnings: vec![],
+            sequence: 0,
+            dropped_since_last: 0,
+            dropped_batches_since_last: 0,
+            encoded_bytes: None,
         })
         .collect::<Vec<_>>();
     bench!(&updates, "transactions");
@@ -66,17 +88,139 @@ fn bench_account(c: &mut Criterion) {
         .map(|block| FilteredUpdate {
             filters: filters.clone(),
             message: FilteredUpdateOneof::block(Box::new(block)),
-            created_at: Timestamp::from(SystemTime::now()),
+            created_at: Some(Timestamp::from(SystemTime::now())),
+            warnings: vec![],
+            sequence: 0,
+            dropped_since_last: 0,
+            dropped_batches_since_last: 0,
+            encoded_bytes: None,
         })
         .collect::<Vec<_>>();
     bench!(&updates, "blocks");
 }
 
+fn bench_transactions_shared(c: &mut Criterion) {
+    // predefined transactions are votes, same batch is sent to several clients
+    const CLIENTS: usize = 5;
+    let filters = create_message_filters(&["my special filter"]);
+    let transactions = load_predefined_transactions();
+    let create_updates = |transactions: &[Arc<MessageTransactionInfo>]| {
+        transactions
+            .iter()
+            .map(|transaction| FilteredUpdate {
+                filters: filters.clone(),
+                message: FilteredUpdateOneof::transaction(&MessageTransaction {
+                    transaction: Arc::new(transaction.as_ref().clone()),
+                    slot: 42,
+                    created_at: Timestamp::from(SystemTime::now()),
+                }),
+                created_at: Some(Timestamp::from(SystemTime::now())),
+                warnings: vec![],
+                sequence: 0,
+                dropped_since_last: 0,
+                dropped_batches_since_last: 0,
+                encoded_bytes: None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    c.bench_function("transactions_clients/shared", |b| {
+        b.iter_batched(
+            || create_updates(&transactions),
+            |updates| {
+                for _ in 0..CLIENTS {
+                    for update in updates.iter() {
+                        update.encode_to_vec().len();
+                    }
+                }
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    c.bench_function("transactions_clients/per_client", |b| {
+        b.iter_batched(
+            || {
+                (0..CLIENTS)
+                    .map(|_| create_updates(&transactions))
+                    .collect::<Vec<_>>()
+            },
+            |clients| {
+                for updates in clients.iter() {
+                    for update in updates.iter() {
+                        update.encode_to_vec().len();
+                    }
+                }
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn bench_account_fanout(c: &mut Criterion) {
+    // 1MB account sent to 10 subscribers with contiguous data slices, `prost` conversion
+    // copies the data without `bytes-account-data` feature
+    const SUBSCRIBERS: usize = 10;
+    const SIZE: usize = 1024 * 1024;
+    let account = MessageAccount {
+        account: Arc::new(MessageAccountInfo {
+            pubkey: Pubkey::new_unique(),
+            lamports: 42,
+            owner: Pubkey::new_unique(),
+            executable: false,
+            rent_epoch: 0,
+            data: Bytes::from(vec![42; SIZE]),
+            write_version: 1,
+            txn_signature: None,
+            base58: Default::default(),
+        }),
+        slot: 42,
+        is_startup: false,
+        created_at: Timestamp::from(SystemTime::now()),
+        confirmed_at_slot: None,
+        finalized_at_slot: None,
+    };
+    let data_slice =
+        FilterAccountsDataSlice::new_unchecked(Arc::new(vec![0..SIZE / 2, SIZE / 2..SIZE]));
+    let filters = create_message_filters(&["my special filter"]);
+
+    c.bench_function("account_fanout_1mb/slice_view", |b| {
+        b.iter(|| {
+            for _ in 0..SUBSCRIBERS {
+                black_box(data_slice.get_slice(&account.account.data));
+            }
+        })
+    });
+    c.bench_function("account_fanout_1mb/slice_copy", |b| {
+        b.iter(|| {
+            for _ in 0..SUBSCRIBERS {
+                black_box(data_slice.get_slice(&account.account.data).to_vec());
+            }
+        })
+    });
+    c.bench_function("account_fanout_1mb/prost", |b| {
+        b.iter(|| {
+            for _ in 0..SUBSCRIBERS {
+                let update = FilteredUpdate {
+                    filters: filters.clone(),
+                    message: FilteredUpdateOneof::account(&account, data_slice.clone()),
+                    created_at: None,
+                    warnings: vec![],
+                    sequence: 0,
+                    dropped_since_last: 0,
+                    dropped_batches_since_last: 0,
+                    encoded_bytes: None,
+                };
+                black_box(update.as_subscribe_update());
+            }
+        })
+    });
+}
+
 criterion_group!(
     name = benches;
     config = Criterion::default()
         .warm_up_time(Duration::from_secs(3)) // default 3
         .measurement_time(Duration::from_secs(5)); // default 5
-    targets = bench_account
+    targets = bench_account, bench_transactions_shared, bench_account_fanout
 );
 criterion_main!(benches);
@@ -0,0 +1,192 @@
+use {
+    criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion},
+    prost_types::Timestamp,
+    solana_sdk::pubkey::Pubkey,
+    std::{
+        alloc::{GlobalAlloc, Layout, System},
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::{Duration, SystemTime},
+    },
+    yellowstone_grpc_proto::{
+        geyser::{SubscribeRequest, SubscribeRequestFilterAccounts},
+        plugin::{
+            filter::{name::FilterNames, Filter},
+            message::{Message, MessageAccount, MessageAccountInfo},
+        },
+    },
+};
+
+/// Counts allocated bytes to compare memory of filters with shared and separate Pubkey lists
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn create_message(pubkey: Pubkey) -> Message {
+    Message::Account(MessageAccount {
+        account: Arc::new(MessageAccountInfo {
+            pubkey,
+            lamports: 42,
+            owner: Pubkey::new_unique(),
+            executable: false,
+            rent_epoch: 0,
+            data: vec![0; 165].into(),
+            write_version: 1,
+            txn_signature: None,
+            base58: Default::default(),
+        }),
+        slot: 42,
+        is_startup: false,
+        created_at: Timestamp::from(SystemTime::now()),
+        confirmed_at_slot: None,
+        finalized_at_slot: None,
+    })
+}
+
+fn bench_accounts_match(c: &mut Criterion) {
+    for size in [100, 100_000] {
+        let keys = (0..size).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+        let config = SubscribeRequest {
+            accounts: HashMap::from([(
+                "accounts".to_owned(),
+                SubscribeRequestFilterAccounts {
+                    account: keys.iter().map(|key| key.to_string()).collect(),
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &config,
+            &Default::default(),
+            &mut FilterNames::new(64, 1024, Duration::from_secs(1)),
+        )
+        .expect("failed to create filter");
+
+        let messages = keys
+            .iter()
+            .step_by(size / 100)
+            .copied()
+            .chain((0..100).map(|_| Pubkey::new_unique()))
+            .map(create_message)
+            .collect::<Vec<_>>();
+
+        c.bench_with_input(
+            BenchmarkId::new("accounts_match", format!("filter/{size}")),
+            &messages,
+            |b, messages| {
+                b.iter(|| {
+                    for message in messages.iter() {
+                        filter.get_updates(message, None).len();
+                    }
+                })
+            },
+        );
+        c.bench_with_input(
+            BenchmarkId::new("accounts_match", format!("vec_scan/{size}")),
+            &messages,
+            |b, messages| {
+                b.iter(|| {
+                    for message in messages.iter() {
+                        if let Message::Account(msg) = message {
+                            black_box(keys.contains(&msg.account.pubkey));
+                        }
+                    }
+                })
+            },
+        );
+    }
+}
+
+fn bench_filters_shared(c: &mut Criterion) {
+    const CLIENTS: usize = 200;
+    const KEYS: usize = 50_000;
+
+    let keys = (0..KEYS).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+    let config = SubscribeRequest {
+        accounts: HashMap::from([(
+            "markets".to_owned(),
+            SubscribeRequestFilterAccounts {
+                account: keys.iter().map(|key| key.to_string()).collect(),
+                ..Default::default()
+            },
+        )]),
+        ..Default::default()
+    };
+    let messages = keys
+        .iter()
+        .step_by(KEYS / 100)
+        .copied()
+        .chain((0..100).map(|_| Pubkey::new_unique()))
+        .map(create_message)
+        .collect::<Vec<_>>();
+
+    // with shared `FilterNames` lists are interned, otherwise every client has own copy
+    let mut names = FilterNames::new(64, 1024, Duration::from_secs(1));
+    for (kind, shared) in [("shared", true), ("separate", false)] {
+        let allocated = ALLOCATED.load(Ordering::Relaxed);
+        let filters = (0..CLIENTS)
+            .map(|_| {
+                let mut names_separate = FilterNames::new(64, 1024, Duration::from_secs(1));
+                Filter::new(
+                    &config,
+                    &Default::default(),
+                    if shared {
+                        &mut names
+                    } else {
+                        &mut names_separate
+                    },
+                )
+                .expect("failed to create filter")
+            })
+            .collect::<Vec<_>>();
+        println!(
+            "{CLIENTS} filters with {KEYS} Pubkeys, {kind} lists: {} KiB allocated",
+            ALLOCATED.load(Ordering::Relaxed).saturating_sub(allocated) / 1024
+        );
+
+        c.bench_with_input(
+            BenchmarkId::new("filters_match", format!("{kind}/{CLIENTS}x{KEYS}")),
+            &messages,
+            |b, messages| {
+                b.iter(|| {
+                    for filter in filters.iter() {
+                        for message in messages.iter() {
+                            black_box(filter.get_updates(message, None).len());
+                        }
+                    }
+                })
+            },
+        );
+    }
+}
+
+criterion_group!(
+    name = benches;
+    config = Criterion::default()
+        .warm_up_time(Duration::from_secs(3)) // default 3
+        .measurement_time(Duration::from_secs(5)); // default 5
+    targets = bench_accounts_match, bench_filters_shared
+);
+criterion_main!(benches);
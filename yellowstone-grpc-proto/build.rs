@@ -1,23 +1,40 @@
 use {
-    std::{env, fs, path::Path},
+    anyhow::Context,
+    prost::Message,
+    prost_types::{
+        field_descriptor_proto::{Label, Type},
+        DescriptorProto, FileDescriptorSet,
+    },
+    std::{env, fs, path::Path, process::Command},
     tonic_build::manual::{Builder, Method, Service},
 };
 
 fn main() -> anyhow::Result<()> {
-    _ = std::env::var("PROTOC").expect("PROTOC not found");
+    let protoc = std::env::var("PROTOC").expect("PROTOC not found");
 
-    // build protos
-    tonic_build::configure().compile_protos(&["proto/geyser.proto"], &["proto"])?;
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR not found");
+    let bytes_account_data = env::var_os("CARGO_FEATURE_BYTES_ACCOUNT_DATA").is_some();
+    let serde_fields = serde_fields(Path::new(&out_dir), &protoc)?;
+
+    // build protos with file descriptor set for gRPC reflection
+    with_serde(
+        with_bytes(tonic_build::configure(), bytes_account_data),
+        &serde_fields,
+    )
+    .file_descriptor_set_path(Path::new(&out_dir).join("geyser_descriptor.bin"))
+    .compile_protos(&["proto/geyser.proto"], &["proto"])?;
 
     // build protos without tonic (wasm)
-    let out_dir = env::var("OUT_DIR").expect("OUT_DIR not found");
     let out_dir_path = Path::new(&out_dir).join("no-tonic");
     fs::create_dir_all(&out_dir_path).expect("failed to create out no-tonic directory");
-    tonic_build::configure()
-        .build_client(false)
-        .build_server(false)
-        .out_dir(out_dir_path)
-        .compile_protos(&["proto/geyser.proto"], &["proto"])?;
+    with_serde(
+        with_bytes(tonic_build::configure(), bytes_account_data),
+        &serde_fields,
+    )
+    .build_client(false)
+    .build_server(false)
+    .out_dir(out_dir_path)
+    .compile_protos(&["proto/geyser.proto"], &["proto"])?;
 
     // build with accepting our custom struct
     let geyser_service = Service::builder()
@@ -90,6 +107,42 @@ fn main() -> anyhow::Result<()> {
                 .codec_path("tonic::codec::ProstCodec")
                 .build(),
         )
+        .method(
+            Method::builder()
+                .name("get_slot_status")
+                .route_name("GetSlotStatus")
+                .input_type("crate::geyser::GetSlotStatusRequest")
+                .output_type("crate::geyser::GetSlotStatusResponse")
+                .codec_path("tonic::codec::ProstCodec")
+                .build(),
+        )
+        .method(
+            Method::builder()
+                .name("get_accounts_snapshot")
+                .route_name("GetAccountsSnapshot")
+                .input_type("crate::geyser::GetAccountsSnapshotRequest")
+                .output_type("crate::geyser::GetAccountsSnapshotResponse")
+                .codec_path("tonic::codec::ProstCodec")
+                .build(),
+        )
+        .method(
+            Method::builder()
+                .name("get_transaction_status")
+                .route_name("GetTransactionStatus")
+                .input_type("crate::geyser::GetTransactionStatusRequest")
+                .output_type("crate::geyser::GetTransactionStatusResponse")
+                .codec_path("tonic::codec::ProstCodec")
+                .build(),
+        )
+        .method(
+            Method::builder()
+                .name("get_slot_ancestry")
+                .route_name("GetSlotAncestry")
+                .input_type("crate::geyser::GetSlotAncestryRequest")
+                .output_type("crate::geyser::GetSlotAncestryResponse")
+                .codec_path("tonic::codec::ProstCodec")
+                .build(),
+        )
         .build();
     Builder::new()
         .build_client(false)
@@ -107,3 +160,89 @@ fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+// derive serde traits behind `serde` feature, bytes and timestamps require custom encoding
+// `bytes::Bytes` instead of `Vec<u8>` for account data, zero-copy from the plugin message
+fn with_bytes(builder: tonic_build::Builder, enabled: bool) -> tonic_build::Builder {
+    if enabled {
+        builder.bytes([".geyser.SubscribeUpdateAccountInfo.data"])
+    } else {
+        builder
+    }
+}
+
+fn with_serde(builder: tonic_build::Builder, fields: &[(&str, String)]) -> tonic_build::Builder {
+    let builder = builder.type_attribute(
+        ".",
+        r#"#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]"#,
+    );
+    fields.iter().fold(builder, |builder, (with, path)| {
+        builder.field_attribute(
+            path,
+            format!(
+                r#"#[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::{with}"))]"#
+            ),
+        )
+    })
+}
+
+// fields with custom serde encoding are collected from the descriptors, so a new bytes or
+// timestamp field can't be left with the derived encoding
+fn serde_fields(out_dir: &Path, protoc: &str) -> anyhow::Result<Vec<(&'static str, String)>> {
+    let path = out_dir.join("geyser_serde_descriptor.bin");
+    let status = Command::new(protoc)
+        .arg("--include_imports")
+        .arg("--proto_path=proto")
+        .arg(format!("--descriptor_set_out={}", path.display()))
+        .arg("proto/geyser.proto")
+        .status()
+        .context("failed to run protoc")?;
+    anyhow::ensure!(status.success(), "protoc failed: {status}");
+    let set = FileDescriptorSet::decode(fs::read(&path)?.as_slice())?;
+
+    let mut fields = vec![];
+    for file in set.file.iter() {
+        // well-known types are provided by `prost-types`
+        if file.package() != "google.protobuf" {
+            for message in file.message_type.iter() {
+                collect_serde_fields(&format!(".{}", file.package()), message, &mut fields);
+            }
+        }
+    }
+    Ok(fields)
+}
+
+fn collect_serde_fields(
+    prefix: &str,
+    message: &DescriptorProto,
+    fields: &mut Vec<(&'static str, String)>,
+) {
+    let path = format!("{prefix}.{}", message.name());
+    for field in message.field.iter() {
+        let with = match field.r#type() {
+            Type::Bytes if field.label() == Label::Repeated => "bytes_vec",
+            Type::Bytes if field.proto3_optional() => "bytes_opt",
+            Type::Bytes => "bytes",
+            Type::Message
+                if field.type_name() == ".google.protobuf.Timestamp"
+                    && field.label() != Label::Repeated =>
+            {
+                "timestamp_opt"
+            }
+            _ => continue,
+        };
+        // fields of `oneof` are variants of the enum named after it
+        let name = match field.oneof_index {
+            Some(index) if !field.proto3_optional() => format!(
+                "{path}.{}.{}",
+                message.oneof_decl[index as usize].name(),
+                field.name()
+            ),
+            _ => format!("{path}.{}", field.name()),
+        };
+        fields.push((with, name));
+    }
+    for nested in message.nested_type.iter() {
+        collect_serde_fields(&path, nested, fields);
+    }
+}
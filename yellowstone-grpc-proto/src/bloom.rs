@@ -0,0 +1,161 @@
+use crate::geyser::SubscribeRequestSeenSignaturesBloom;
+
+/// Bloom filter of transaction signatures, see `SubscribeRequestSeenSignaturesBloom`
+/// for the layout. Signatures are uniformly distributed, so bytes of the signature
+/// are used as hashes directly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignaturesBloom {
+    bits: Vec<u8>,
+    num_hashes: u32,
+}
+
+impl SignaturesBloom {
+    pub const MAX_HASHES: u32 = 32;
+
+    pub fn new(num_bytes: usize, num_hashes: u32) -> Self {
+        Self {
+            bits: vec![0; num_bytes.max(1)],
+            num_hashes: num_hashes.clamp(1, Self::MAX_HASHES),
+        }
+    }
+
+    /// Optimal size and number of hashes for expected amount of signatures and false positive rate
+    pub fn with_false_positive_rate(items: usize, false_positive_rate: f64) -> Self {
+        let ln2 = std::f64::consts::LN_2;
+        let items = items.max(1) as f64;
+        let rate = false_positive_rate.clamp(f64::MIN_POSITIVE, 0.5);
+        let num_bits = (-items * rate.ln() / (ln2 * ln2)).ceil().max(8.0);
+        let num_hashes = (num_bits / items * ln2)
+            .round()
+            .clamp(1.0, Self::MAX_HASHES as f64) as u32;
+        Self::new((num_bits as usize).div_ceil(8), num_hashes)
+    }
+
+    pub fn insert(&mut self, signature: &[u8]) {
+        let num_bits = self.num_bits();
+        for bit in Self::positions(signature, num_bits, self.num_hashes) {
+            self.bits[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+
+    pub fn contains(&self, signature: &[u8]) -> bool {
+        Self::positions(signature, self.num_bits(), self.num_hashes)
+            .all(|bit| self.bits[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+
+    pub fn num_bytes(&self) -> usize {
+        self.bits.len()
+    }
+
+    pub const fn num_hashes(&self) -> u32 {
+        self.num_hashes
+    }
+
+    fn num_bits(&self) -> u64 {
+        self.bits.len() as u64 * 8
+    }
+
+    fn positions(signature: &[u8], num_bits: u64, num_hashes: u32) -> impl Iterator<Item = usize> {
+        let h1 = Self::read_u64(signature, 0);
+        let h2 = Self::read_u64(signature, 8) | 1;
+        (0..num_hashes as u64)
+            .map(move |k| (h1.wrapping_add(k.wrapping_mul(h2)) % num_bits) as usize)
+    }
+
+    fn read_u64(signature: &[u8], offset: usize) -> u64 {
+        let mut bytes = [0; 8];
+        if let Some(slice) = signature.get(offset..offset + 8) {
+            bytes.copy_from_slice(slice);
+        }
+        u64::from_le_bytes(bytes)
+    }
+}
+
+impl TryFrom<SubscribeRequestSeenSignaturesBloom> for SignaturesBloom {
+    type Error = &'static str;
+
+    fn try_from(msg: SubscribeRequestSeenSignaturesBloom) -> Result<Self, Self::Error> {
+        if msg.bits.is_empty() {
+            return Err("bloom bits should not be empty");
+        }
+        if msg.num_hashes == 0 || msg.num_hashes > Self::MAX_HASHES {
+            return Err("bloom num_hashes should be from 1 to 32");
+        }
+        Ok(Self {
+            bits: msg.bits,
+            num_hashes: msg.num_hashes,
+        })
+    }
+}
+
+impl From<SignaturesBloom> for SubscribeRequestSeenSignaturesBloom {
+    fn from(bloom: SignaturesBloom) -> Self {
+        Self {
+            bits: bloom.bits,
+            num_hashes: bloom.num_hashes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::SignaturesBloom,
+        crate::geyser::SubscribeRequestSeenSignaturesBloom,
+        std::{collections::hash_map::DefaultHasher, hash::Hasher},
+    };
+
+    fn signature(seed: u64) -> [u8; 64] {
+        let mut signature = [0; 64];
+        for (index, chunk) in signature.chunks_mut(8).enumerate() {
+            let mut hasher = DefaultHasher::new();
+            hasher.write_u64(seed);
+            hasher.write_usize(index);
+            chunk.copy_from_slice(&hasher.finish().to_le_bytes());
+        }
+        signature
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut bloom = SignaturesBloom::with_false_positive_rate(1_000, 0.01);
+        for seed in 0..1_000 {
+            bloom.insert(&signature(seed));
+        }
+
+        let msg = SubscribeRequestSeenSignaturesBloom::from(bloom.clone());
+        let decoded = SignaturesBloom::try_from(msg).expect("valid bloom");
+        assert_eq!(decoded, bloom);
+        assert!((0..1_000).all(|seed| decoded.contains(&signature(seed))));
+
+        let false_positives = (1_000..11_000)
+            .filter(|seed| decoded.contains(&signature(*seed)))
+            .count();
+        assert!(false_positives < 200, "false positives: {false_positives}");
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(
+            SignaturesBloom::try_from(SubscribeRequestSeenSignaturesBloom {
+                bits: vec![],
+                num_hashes: 1,
+            })
+            .is_err()
+        );
+        assert!(
+            SignaturesBloom::try_from(SubscribeRequestSeenSignaturesBloom {
+                bits: vec![0; 8],
+                num_hashes: 0,
+            })
+            .is_err()
+        );
+        assert!(
+            SignaturesBloom::try_from(SubscribeRequestSeenSignaturesBloom {
+                bits: vec![0; 8],
+                num_hashes: 33,
+            })
+            .is_err()
+        );
+    }
+}
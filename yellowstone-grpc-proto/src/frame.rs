@@ -0,0 +1,247 @@
+//! Length-delimited framing of prost messages for archiving raw updates.
+//!
+//! Every frame is `[len: u32 LE][crc32: u32 LE][payload: len bytes]`, where
+//! payload is a prost-encoded message (usually `SubscribeUpdate`) and `crc32`
+//! is checksum of the payload.
+
+use {
+    prost::Message,
+    std::io,
+    tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+};
+
+pub const FRAME_HEADER_SIZE: usize = 8;
+pub const FRAME_MAX_SIZE_DEFAULT: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum UpdateFrameError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("unexpected end of stream inside of the frame")]
+    UnexpectedEof,
+    #[error("frame size {size} exceeds the limit {max}")]
+    TooLarge { size: usize, max: usize },
+    #[error("frame checksum mismatch: expected {expected:#010x}, actual {actual:#010x}")]
+    Checksum { expected: u32, actual: u32 },
+    #[error(transparent)]
+    Decode(#[from] prost::DecodeError),
+}
+
+#[derive(Debug)]
+pub struct UpdateFrameWriter<W> {
+    writer: W,
+    buffer: Vec<u8>,
+}
+
+impl<W: AsyncWrite + Unpin> UpdateFrameWriter<W> {
+    pub const fn new(writer: W) -> Self {
+        Self {
+            writer,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Write message as one frame, returns number of written bytes
+    pub async fn write<M: Message>(&mut self, message: &M) -> io::Result<usize> {
+        let size = message.encoded_len();
+        let size_u32 = u32::try_from(size).map_err(|_error| {
+            io::Error::new(io::ErrorKind::InvalidInput, "message is too large")
+        })?;
+
+        self.buffer.clear();
+        self.buffer.reserve(FRAME_HEADER_SIZE + size);
+        self.buffer.extend_from_slice(&size_u32.to_le_bytes());
+        self.buffer.extend_from_slice(&[0; 4]);
+        message
+            .encode(&mut self.buffer)
+            .expect("buffer has enough capacity");
+        let crc = crc32fast::hash(&self.buffer[FRAME_HEADER_SIZE..]);
+        self.buffer[4..FRAME_HEADER_SIZE].copy_from_slice(&crc.to_le_bytes());
+
+        self.writer.write_all(&self.buffer).await?;
+        Ok(self.buffer.len())
+    }
+
+    pub async fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush().await
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[derive(Debug)]
+pub struct UpdateFrameReader<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    max_size: usize,
+}
+
+impl<R: AsyncRead + Unpin> UpdateFrameReader<R> {
+    pub const fn new(reader: R) -> Self {
+        Self::with_max_size(reader, FRAME_MAX_SIZE_DEFAULT)
+    }
+
+    pub const fn with_max_size(reader: R, max_size: usize) -> Self {
+        Self {
+            reader,
+            buffer: Vec::new(),
+            max_size,
+        }
+    }
+
+    /// Read next frame, `None` on the end of the stream at the frame boundary
+    pub async fn read<M: Message + Default>(&mut self) -> Result<Option<M>, UpdateFrameError> {
+        let mut header = [0u8; FRAME_HEADER_SIZE];
+        match self.read_exact_or_eof(&mut header).await? {
+            0 => return Ok(None),
+            FRAME_HEADER_SIZE => {}
+            _ => return Err(UpdateFrameError::UnexpectedEof),
+        }
+
+        let size = u32::from_le_bytes(header[0..4].try_into().expect("valid size")) as usize;
+        if size > self.max_size {
+            return Err(UpdateFrameError::TooLarge {
+                size,
+                max: self.max_size,
+            });
+        }
+        let expected = u32::from_le_bytes(header[4..8].try_into().expect("valid size"));
+
+        self.buffer.resize(size, 0);
+        let mut buffer = std::mem::take(&mut self.buffer);
+        let read = self.read_exact_or_eof(&mut buffer).await;
+        self.buffer = buffer;
+        if read? != size {
+            return Err(UpdateFrameError::UnexpectedEof);
+        }
+
+        let actual = crc32fast::hash(&self.buffer);
+        if actual != expected {
+            return Err(UpdateFrameError::Checksum { expected, actual });
+        }
+
+        Ok(Some(M::decode(self.buffer.as_slice())?))
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    async fn read_exact_or_eof(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut offset = 0;
+        while offset < buf.len() {
+            match self.reader.read(&mut buf[offset..]).await? {
+                0 => break,
+                size => offset += size,
+            }
+        }
+        Ok(offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{UpdateFrameError, UpdateFrameReader, UpdateFrameWriter, FRAME_HEADER_SIZE},
+        crate::prelude::{
+            subscribe_update::UpdateOneof, SubscribeUpdate, SubscribeUpdatePong,
+            SubscribeUpdateSlot,
+        },
+    };
+
+    fn create_updates() -> Vec<SubscribeUpdate> {
+        (0..3)
+            .map(|slot| SubscribeUpdate {
+                filters: vec!["slots".to_owned()],
+                update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                    slot,
+                    parent: slot.checked_sub(1),
+                    status: 0,
+                    dead_error: None,
+                })),
+                created_at: None,
+                warnings: vec![],
+                sequence: 0,
+                dropped_since_last: 0,
+                dropped_batches_since_last: 0,
+            })
+            .chain(std::iter::once(SubscribeUpdate {
+                filters: vec![],
+                update_oneof: Some(UpdateOneof::Pong(SubscribeUpdatePong { id: 42 })),
+                created_at: None,
+                warnings: vec![],
+                sequence: 0,
+                dropped_since_last: 0,
+                dropped_batches_since_last: 0,
+            }))
+            .collect()
+    }
+
+    async fn write_updates(updates: &[SubscribeUpdate]) -> Vec<u8> {
+        let mut writer = UpdateFrameWriter::new(Vec::new());
+        for update in updates {
+            writer.write(update).await.expect("failed to write");
+        }
+        writer.flush().await.expect("failed to flush");
+        writer.into_inner()
+    }
+
+    #[tokio::test]
+    async fn test_roundtrip() {
+        let updates = create_updates();
+        let data = write_updates(&updates).await;
+
+        let mut reader = UpdateFrameReader::new(data.as_slice());
+        let mut decoded = vec![];
+        while let Some(update) = reader.read::<SubscribeUpdate>().await.unwrap() {
+            decoded.push(update);
+        }
+        assert_eq!(decoded, updates);
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_frame() {
+        let updates = create_updates();
+        let mut data = write_updates(&updates).await;
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+
+        let mut reader = UpdateFrameReader::new(data.as_slice());
+        for update in &updates[..updates.len() - 1] {
+            assert_eq!(
+                reader.read::<SubscribeUpdate>().await.unwrap().as_ref(),
+                Some(update)
+            );
+        }
+        assert!(matches!(
+            reader.read::<SubscribeUpdate>().await,
+            Err(UpdateFrameError::Checksum { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_truncated_frame() {
+        let updates = create_updates();
+        let data = write_updates(&updates[..1]).await;
+
+        for size in [FRAME_HEADER_SIZE - 1, data.len() - 1] {
+            let mut reader = UpdateFrameReader::new(&data[..size]);
+            assert!(matches!(
+                reader.read::<SubscribeUpdate>().await,
+                Err(UpdateFrameError::UnexpectedEof)
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_too_large_frame() {
+        let data = write_updates(&create_updates()[..1]).await;
+        let mut reader = UpdateFrameReader::with_max_size(data.as_slice(), 1);
+        assert!(matches!(
+            reader.read::<SubscribeUpdate>().await,
+            Err(UpdateFrameError::TooLarge { .. })
+        ));
+    }
+}
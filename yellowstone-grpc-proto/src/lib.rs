@@ -8,6 +8,10 @@ pub mod geyser {
     include!(concat!(env!("OUT_DIR"), "/geyser.rs"));
     #[cfg(not(feature = "tonic"))]
     include!(concat!(env!("OUT_DIR"), "/no-tonic/geyser.rs"));
+
+    /// Encoded file descriptor set of `geyser.proto` with all imports, can be used for gRPC reflection
+    pub const FILE_DESCRIPTOR_SET: &[u8] =
+        include_bytes!(concat!(env!("OUT_DIR"), "/geyser_descriptor.bin"));
 }
 
 pub mod solana {
@@ -37,9 +41,17 @@ pub mod prelude {
 pub use tonic;
 pub use {prost, prost_types};
 
+pub mod bloom;
+
+#[cfg(feature = "frame")]
+pub mod frame;
+
 #[cfg(feature = "plugin")]
 pub mod plugin;
 
+#[cfg(feature = "serde")]
+pub mod serde_utils;
+
 #[cfg(feature = "convert")]
 pub mod convert_to {
     use {
@@ -49,11 +61,11 @@ pub mod convert_to {
             instruction::CompiledInstruction,
             message::{
                 v0::{LoadedMessage, MessageAddressTableLookup},
-                LegacyMessage, MessageHeader, SanitizedMessage,
+                LegacyMessage, MessageHeader, SanitizedMessage, VersionedMessage,
             },
             pubkey::Pubkey,
             signature::Signature,
-            transaction::{SanitizedTransaction, TransactionError},
+            transaction::{SanitizedTransaction, TransactionError, VersionedTransaction},
             transaction_context::TransactionReturnData,
         },
         solana_transaction_status::{
@@ -94,6 +106,38 @@ pub mod convert_to {
         }
     }
 
+    pub fn create_transaction_versioned(tx: &VersionedTransaction) -> proto::Transaction {
+        proto::Transaction {
+            signatures: tx
+                .signatures
+                .iter()
+                .map(|signature| <Signature as AsRef<[u8]>>::as_ref(signature).into())
+                .collect(),
+            message: Some(create_message_versioned(&tx.message)),
+        }
+    }
+
+    pub fn create_message_versioned(message: &VersionedMessage) -> proto::Message {
+        match message {
+            VersionedMessage::Legacy(message) => proto::Message {
+                header: Some(create_header(&message.header)),
+                account_keys: create_pubkeys(&message.account_keys),
+                recent_blockhash: message.recent_blockhash.to_bytes().into(),
+                instructions: create_instructions(&message.instructions),
+                versioned: false,
+                address_table_lookups: vec![],
+            },
+            VersionedMessage::V0(message) => proto::Message {
+                header: Some(create_header(&message.header)),
+                account_keys: create_pubkeys(&message.account_keys),
+                recent_blockhash: message.recent_blockhash.to_bytes().into(),
+                instructions: create_instructions(&message.instructions),
+                versioned: true,
+                address_table_lookups: create_lookups(&message.address_table_lookups),
+            },
+        }
+    }
+
     pub const fn create_header(header: &MessageHeader) -> proto::MessageHeader {
         proto::MessageHeader {
             num_required_signatures: header.num_required_signatures as u32,
@@ -361,7 +405,14 @@ pub mod convert_from {
     pub fn create_tx_with_meta(
         tx: proto::SubscribeUpdateTransactionInfo,
     ) -> CreateResult<TransactionWithStatusMeta> {
-        let meta = tx.meta.ok_or("failed to get transaction meta")?;
+        let mut meta = create_tx_meta(tx.meta.ok_or("failed to get transaction meta")?)?;
+        if tx.token_balances_none {
+            meta.pre_token_balances = None;
+            meta.post_token_balances = None;
+        }
+        if tx.rewards_none {
+            meta.rewards = None;
+        }
         let tx = tx
             .transaction
             .ok_or("failed to get transaction transaction")?;
@@ -369,7 +420,7 @@ pub mod convert_from {
         Ok(TransactionWithStatusMeta::Complete(
             VersionedTransactionWithStatusMeta {
                 transaction: create_tx_versioned(tx)?,
-                meta: create_tx_meta(meta)?,
+                meta,
             },
         ))
     }
@@ -631,13 +682,19 @@ pub mod convert_from {
         Pubkey::try_from(pubkey).map_err(|_| "failed to parse Pubkey")
     }
 
+    /// `SubscribeUpdateAccountInfo.data` is `Vec<u8>` or `Bytes` with `bytes-account-data`
+    /// feature, the generic conversion is a no-op for the same type
+    pub fn convert_account_data<T: Into<U>, U>(data: T) -> U {
+        data.into()
+    }
+
     pub fn create_account(
         account: proto::SubscribeUpdateAccountInfo,
     ) -> CreateResult<(Pubkey, Account)> {
         let pubkey = create_pubkey(&account.pubkey)?;
         let account = Account {
             lamports: account.lamports,
-            data: account.data,
+            data: convert_account_data(account.data),
             owner: create_pubkey(&account.owner)?,
             executable: account.executable,
             rent_epoch: account.rent_epoch,
@@ -0,0 +1,125 @@
+//! `From` impls for every replica info version of the geyser interface, so code converting
+//! a specific version doesn't depend on the version used by the plugin. All impls go through
+//! the version-agnostic `from_geyser_versions` constructors.
+
+use {
+    crate::plugin::message::{
+        MessageAccountInfo, MessageBlockMeta, MessageEntry, MessageTransactionInfo,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::{
+        ReplicaAccountInfo, ReplicaAccountInfoV2, ReplicaAccountInfoV3, ReplicaAccountInfoVersions,
+        ReplicaBlockInfo, ReplicaBlockInfoV2, ReplicaBlockInfoV3, ReplicaBlockInfoV4,
+        ReplicaBlockInfoVersions, ReplicaEntryInfo, ReplicaEntryInfoV2, ReplicaEntryInfoVersions,
+        ReplicaTransactionInfo, ReplicaTransactionInfoV2, ReplicaTransactionInfoVersions,
+    },
+};
+
+macro_rules! impl_from_versions {
+    ($message:ty, $versions:ident, $($version:ident => $info:ident),+ $(,)?) => {
+        $(
+            impl<'a> From<&'a $info<'a>> for $message {
+                fn from(info: &'a $info<'a>) -> Self {
+                    Self::from_geyser_versions(&$versions::$version(info))
+                }
+            }
+        )+
+    };
+}
+
+impl_from_versions!(
+    MessageAccountInfo,
+    ReplicaAccountInfoVersions,
+    V0_0_1 => ReplicaAccountInfo,
+    V0_0_2 => ReplicaAccountInfoV2,
+    V0_0_3 => ReplicaAccountInfoV3,
+);
+
+impl_from_versions!(
+    MessageTransactionInfo,
+    ReplicaTransactionInfoVersions,
+    V0_0_1 => ReplicaTransactionInfo,
+    V0_0_2 => ReplicaTransactionInfoV2,
+);
+
+impl_from_versions!(
+    MessageEntry,
+    ReplicaEntryInfoVersions,
+    V0_0_1 => ReplicaEntryInfo,
+    V0_0_2 => ReplicaEntryInfoV2,
+);
+
+impl_from_versions!(
+    MessageBlockMeta,
+    ReplicaBlockInfoVersions,
+    V0_0_1 => ReplicaBlockInfo,
+    V0_0_2 => ReplicaBlockInfoV2,
+    V0_0_3 => ReplicaBlockInfoV3,
+    V0_0_4 => ReplicaBlockInfoV4,
+);
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{MessageAccountInfo, MessageBlockMeta},
+        agave_geyser_plugin_interface::geyser_plugin_interface::{
+            ReplicaAccountInfo, ReplicaAccountInfoV3, ReplicaBlockInfo, ReplicaBlockInfoV4,
+        },
+        solana_sdk::{hash::Hash, pubkey::Pubkey},
+        solana_transaction_status::RewardsAndNumPartitions,
+    };
+
+    #[test]
+    fn test_from_shared_fields() {
+        let pubkey = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let v1 = MessageAccountInfo::from(&ReplicaAccountInfo {
+            pubkey: pubkey.as_ref(),
+            lamports: 42,
+            owner: owner.as_ref(),
+            executable: false,
+            rent_epoch: 7,
+            data: &[1, 2, 3],
+            write_version: 3,
+        });
+        let v3 = MessageAccountInfo::from(&ReplicaAccountInfoV3 {
+            pubkey: pubkey.as_ref(),
+            lamports: 42,
+            owner: owner.as_ref(),
+            executable: false,
+            rent_epoch: 7,
+            data: &[1, 2, 3],
+            write_version: 3,
+            txn: None,
+        });
+        assert_eq!(v1, v3);
+
+        let blockhash = Hash::new_unique().to_string();
+        let v1 = MessageBlockMeta::from(&ReplicaBlockInfo {
+            slot: 10,
+            blockhash: &blockhash,
+            rewards: &[],
+            block_time: Some(1_700_000_000),
+            block_height: Some(9),
+        });
+        let v4 = MessageBlockMeta::from(&ReplicaBlockInfoV4 {
+            parent_slot: 9,
+            parent_blockhash: &Hash::new_unique().to_string(),
+            slot: 10,
+            blockhash: &blockhash,
+            rewards: &RewardsAndNumPartitions {
+                rewards: vec![],
+                num_partitions: None,
+            },
+            block_time: Some(1_700_000_000),
+            block_height: Some(9),
+            executed_transaction_count: 20,
+            entry_count: 6,
+        });
+        assert_eq!(
+            (v1.slot, &v1.blockhash, v1.block_time, v1.block_height),
+            (v4.slot, &v4.blockhash, v4.block_time, v4.block_height)
+        );
+        assert!(v1.executed_transaction_count_unknown);
+        assert_eq!(v4.executed_transaction_count, 20);
+    }
+}
@@ -1,48 +1,63 @@
 use {
     crate::{
+        bloom::SignaturesBloom,
         geyser::{
             subscribe_request_filter_accounts_filter::Filter as AccountsFilterDataOneof,
             subscribe_request_filter_accounts_filter_lamports::Cmp as AccountsFilterLamports,
             subscribe_request_filter_accounts_filter_memcmp::Data as AccountsFilterMemcmpOneof,
-            CommitmentLevel as CommitmentLevelProto, SubscribeRequest,
-            SubscribeRequestAccountsDataSlice, SubscribeRequestFilterAccounts,
-            SubscribeRequestFilterAccountsFilter, SubscribeRequestFilterAccountsFilterLamports,
-            SubscribeRequestFilterBlocks, SubscribeRequestFilterBlocksMeta,
-            SubscribeRequestFilterEntry, SubscribeRequestFilterSlots,
-            SubscribeRequestFilterTransactions,
+            AccountOrigin, CommitmentLevel as CommitmentLevelProto, OnLag,
+            SlotStatus as SlotStatusProto, SubscribeRequest, SubscribeRequestAccountsDataSlice,
+            SubscribeRequestFilterAccounts, SubscribeRequestFilterAccountsFilter,
+            SubscribeRequestFilterAccountsFilterLamports,
+            SubscribeRequestFilterAccountsFilterLamportsRange, SubscribeRequestFilterAccountsPda,
+            SubscribeRequestFilterAccountsPdaTemplate, SubscribeRequestFilterBlocks,
+            SubscribeRequestFilterBlocksMeta, SubscribeRequestFilterEntry,
+            SubscribeRequestFilterSlots, SubscribeRequestFilterStats,
+            SubscribeRequestFilterTransactions, SubscribeRequestFilterVotes,
+            SubscribeRequestSeenSignaturesBloom, SubscribeUpdateRollback,
+            SubscribeUpdateStreamInfo, SubscribeUpdateVotes,
         },
         plugin::{
             filter::{
                 limits::{
-                    FilterLimits, FilterLimitsAccounts, FilterLimitsBlocks, FilterLimitsBlocksMeta,
-                    FilterLimitsCheckError, FilterLimitsEntries, FilterLimitsSlots,
-                    FilterLimitsTransactions,
+                    FilterAccessKind, FilterCaller, FilterLimits, FilterLimitsAccounts,
+                    FilterLimitsBlocks, FilterLimitsBlocksMeta, FilterLimitsCheckError,
+                    FilterLimitsEntries, FilterLimitsOverLimitPolicy, FilterLimitsSeenSignatures,
+                    FilterLimitsSlots, FilterLimitsStats, FilterLimitsTransactions,
+                    FilterLimitsVotes,
                 },
                 message::{
                     FilteredUpdate, FilteredUpdateBlock, FilteredUpdateFilters,
                     FilteredUpdateOneof, FilteredUpdates,
                 },
                 name::{FilterName, FilterNameError, FilterNames},
+                pubkeys::{FilterPubkeys, FilterPubkeysIndex},
             },
             message::{
-                CommitmentLevel, Message, MessageAccount, MessageBlock, MessageBlockMeta,
-                MessageEntry, MessageSlot, MessageTransaction, SlotStatus,
+                CommitmentLevel, Message, MessageAccount, MessageAccountInfo, MessageBlock,
+                MessageBlockDiagnostic, MessageBlockMeta, MessageEntry, MessageRollback,
+                MessageSlot, MessageSlotStats, MessageTransaction, MessageTransactionInfo,
+                MessageVotes, SlotStatus, TransactionMetaSections,
             },
         },
     },
     base64::{engine::general_purpose::STANDARD as base64_engine, Engine},
-    bytes::buf::BufMut,
+    bytes::{buf::BufMut, Bytes, BytesMut},
     prost::encoding::{encode_key, encode_varint, WireType},
     solana_sdk::{
-        pubkey::{ParsePubkeyError, Pubkey},
+        pubkey::{ParsePubkeyError, Pubkey, MAX_SEEDS, MAX_SEED_LEN},
+        rent::Rent,
         signature::{ParseSignatureError, Signature},
     },
     spl_token_2022::{generic_token_account::GenericTokenAccount, state::Account as TokenAccount},
     std::{
-        collections::{HashMap, HashSet},
+        collections::{BTreeMap, BTreeSet, HashMap, HashSet},
         ops::Range,
         str::FromStr,
-        sync::Arc,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
     },
 };
 
@@ -55,6 +70,12 @@ pub enum FilterError {
 
     #[error("failed to create CommitmentLevel from {commitment}")]
     InvalidCommitment { commitment: i32 },
+    #[error("failed to create SlotStatus from {status}")]
+    InvalidSlotStatus { status: i32 },
+    #[error("failed to create OnLag from {on_lag}")]
+    InvalidOnLag { on_lag: i32 },
+    #[error("failed to create AccountOrigin from {origin}")]
+    InvalidAccountOrigin { origin: i32 },
     #[error(transparent)]
     InvalidPubkey(#[from] ParsePubkeyError),
     #[error(transparent)]
@@ -70,6 +91,27 @@ pub enum FilterError {
     CreateDataSliceOutOfOrder,
     #[error("failed to create filter: data slices overlapped")]
     CreateDataSliceOverlap,
+    #[error("invalid base58 prefix `{prefix}`")]
+    InvalidPrefix { prefix: String },
+    #[error("invalid seen signatures bloom: {0}")]
+    InvalidSeenSignaturesBloom(&'static str),
+    #[error("`finalized_with_proof` requires `finalized` commitment")]
+    FinalizedWithProofCommitment,
+}
+
+impl FilterError {
+    /// Error caused by access rules of the caller, not by invalid request
+    pub const fn is_permission_denied(&self) -> bool {
+        matches!(self, Self::LimitsCheck(error) if error.is_permission_denied())
+    }
+
+    /// Max amount of Pubkeys per subscription and suggested number of subscriptions
+    pub const fn shard_hint(&self) -> Option<(usize, usize)> {
+        match self {
+            Self::LimitsCheck(error) => error.shard_hint(),
+            _ => None,
+        }
+    }
 }
 
 pub type FilterResult<T> = Result<T, FilterError>;
@@ -107,9 +149,18 @@ pub struct Filter {
     entries: FilterEntries,
     blocks: FilterBlocks,
     blocks_meta: FilterBlocksMeta,
+    votes: FilterVotes,
+    stats: FilterStats,
     commitment: CommitmentLevel,
     accounts_data_slice: FilterAccountsDataSlice,
     ping: Option<i32>,
+    include_created_at: bool,
+    include_commitment_slot: bool,
+    include_resolved_accounts: bool,
+    include_ping_stats: bool,
+    startup_accounts: bool,
+    on_lag: OnLag,
+    seen_signatures: Option<FilterSeenSignatures>,
 }
 
 impl Default for Filter {
@@ -120,29 +171,97 @@ impl Default for Filter {
             transactions: FilterTransactions {
                 filter_type: FilterTransactionsType::Transaction,
                 filters: HashMap::new(),
+                ordered: HashSet::new(),
+                signatures: HashMap::new(),
+                signatures_required: HashSet::new(),
+                signatures_expire_slots: 0,
             },
             transactions_status: FilterTransactions {
                 filter_type: FilterTransactionsType::TransactionStatus,
                 filters: HashMap::new(),
+                ordered: HashSet::new(),
+                signatures: HashMap::new(),
+                signatures_required: HashSet::new(),
+                signatures_expire_slots: 0,
             },
             entries: FilterEntries::default(),
             blocks: FilterBlocks::default(),
             blocks_meta: FilterBlocksMeta::default(),
+            votes: FilterVotes::default(),
+            stats: FilterStats::default(),
             commitment: CommitmentLevel::Processed,
             accounts_data_slice: FilterAccountsDataSlice::default(),
             ping: None,
+            include_created_at: true,
+            include_commitment_slot: false,
+            include_resolved_accounts: false,
+            include_ping_stats: false,
+            startup_accounts: false,
+            on_lag: OnLag::Disconnect,
+            seen_signatures: None,
         }
     }
 }
 
-impl Filter {
-    pub fn new(
-        config: &SubscribeRequest,
-        limits: &FilterLimits,
-        names: &mut FilterNames,
-    ) -> FilterResult<Self> {
-        Ok(Self {
-            accounts: FilterAccounts::new(&config.accounts, &limits.accounts, names)?,
+/// Builder of [`Filter`], access rules of limits are resolved for the caller
+#[derive(Debug, Clone, Copy)]
+pub struct FilterBuilder<'a> {
+    limits: &'a FilterLimits,
+    caller: Option<&'a FilterCaller>,
+}
+
+impl<'a> FilterBuilder<'a> {
+    pub const fn new(limits: &'a FilterLimits) -> Self {
+        Self {
+            limits,
+            caller: None,
+        }
+    }
+
+    /// Caller matched by access rules, anonymous caller by default
+    pub const fn with_caller(self, caller: &'a FilterCaller) -> Self {
+        Self {
+            caller: Some(caller),
+            ..self
+        }
+    }
+
+    pub fn build(self, config: &SubscribeRequest, names: &mut FilterNames) -> FilterResult<Filter> {
+        let limits = self.limits;
+        let commitment = limits.check_commitment(Filter::decode_commitment(config.commitment)?)?;
+        if config.ping.is_none() {
+            limits.check_access(
+                self.caller.unwrap_or(&FilterCaller::default()),
+                commitment,
+                [
+                    (FilterAccessKind::Accounts, config.accounts.is_empty()),
+                    (FilterAccessKind::Slots, config.slots.is_empty()),
+                    (
+                        FilterAccessKind::Transactions,
+                        config.transactions.is_empty(),
+                    ),
+                    (
+                        FilterAccessKind::TransactionsStatus,
+                        config.transactions_status.is_empty(),
+                    ),
+                    (FilterAccessKind::Entries, config.entry.is_empty()),
+                    (FilterAccessKind::Blocks, config.blocks.is_empty()),
+                    (FilterAccessKind::BlocksMeta, config.blocks_meta.is_empty()),
+                    (FilterAccessKind::Votes, config.votes.is_empty()),
+                    (FilterAccessKind::Stats, config.stats.is_empty()),
+                ]
+                .into_iter()
+                .filter_map(|(kind, is_empty)| (!is_empty).then_some(kind)),
+            )?;
+        }
+
+        let accounts = FilterAccounts::new(&config.accounts, &limits.accounts, names)?;
+        if !accounts.finalized_with_proof.is_empty() && commitment != CommitmentLevel::Finalized {
+            return Err(FilterError::FinalizedWithProofCommitment);
+        }
+
+        Ok(Filter {
+            accounts,
             slots: FilterSlots::new(&config.slots, &limits.slots, names)?,
             transactions: FilterTransactions::new(
                 &config.transactions,
@@ -159,30 +278,53 @@ impl Filter {
             entries: FilterEntries::new(&config.entry, &limits.entries, names)?,
             blocks: FilterBlocks::new(&config.blocks, &limits.blocks, names)?,
             blocks_meta: FilterBlocksMeta::new(&config.blocks_meta, &limits.blocks_meta, names)?,
-            commitment: Self::decode_commitment(config.commitment)?,
+            votes: FilterVotes::new(&config.votes, &limits.votes, names)?,
+            stats: FilterStats::new(&config.stats, &limits.stats, names)?,
+            commitment,
             accounts_data_slice: FilterAccountsDataSlice::new(
                 &config.accounts_data_slice,
                 limits.accounts.data_slice_max,
             )?,
             ping: config.ping.as_ref().map(|msg| msg.id),
+            include_created_at: config.include_created_at.unwrap_or(true),
+            include_commitment_slot: config.include_commitment_slot.unwrap_or(false),
+            include_resolved_accounts: config.include_resolved_accounts.unwrap_or(false),
+            include_ping_stats: config.include_ping_stats.unwrap_or(false),
+            startup_accounts: config.startup_accounts.unwrap_or(false),
+            on_lag: config
+                .on_lag
+                .map(|on_lag| {
+                    OnLag::try_from(on_lag).map_err(|_error| FilterError::InvalidOnLag { on_lag })
+                })
+                .transpose()?
+                .unwrap_or(OnLag::Disconnect),
+            seen_signatures: FilterSeenSignatures::new(
+                config.seen_signatures_bloom.as_ref(),
+                &limits.seen_signatures,
+            )?,
         })
     }
+}
 
-    fn decode_commitment(commitment: Option<i32>) -> FilterResult<CommitmentLevel> {
-        let commitment = commitment.unwrap_or(CommitmentLevelProto::Processed as i32);
-        let commitment = CommitmentLevelProto::try_from(commitment)
-            .map(Into::into)
-            .map_err(|_error| FilterError::InvalidCommitment { commitment })?;
-        if !matches!(
-            commitment,
-            CommitmentLevel::Processed | CommitmentLevel::Confirmed | CommitmentLevel::Finalized
-        ) {
-            Err(FilterError::InvalidCommitment {
-                commitment: commitment as i32,
+impl Filter {
+    /// Filter of the anonymous caller, see [`FilterBuilder`] for access rules of the caller
+    pub fn new(
+        config: &SubscribeRequest,
+        limits: &FilterLimits,
+        names: &mut FilterNames,
+    ) -> FilterResult<Self> {
+        FilterBuilder::new(limits).build(config, names)
+    }
+
+    /// `None` if commitment is omitted, replaced by `default_commitment` of limits
+    fn decode_commitment(commitment: Option<i32>) -> FilterResult<Option<CommitmentLevel>> {
+        commitment
+            .map(|commitment| {
+                CommitmentLevelProto::try_from(commitment)
+                    .map(Into::into)
+                    .map_err(|_error| FilterError::InvalidCommitment { commitment })
             })
-        } else {
-            Ok(commitment)
-        }
+            .transpose()
     }
 
     fn decode_pubkeys<'a>(
@@ -203,7 +345,16 @@ impl Filter {
         Self::decode_pubkeys(pubkeys, limit).collect::<FilterResult<_>>()
     }
 
-    pub fn get_metrics(&self) -> [(&'static str, usize); 8] {
+    fn decode_pubkeys_interned(
+        pubkeys: &[String],
+        limit: &HashSet<Pubkey>,
+        names: &mut FilterNames,
+    ) -> FilterResult<Arc<FilterPubkeys>> {
+        let pubkeys = Self::decode_pubkeys(pubkeys, limit).collect::<FilterResult<_>>()?;
+        Ok(names.get_pubkeys(pubkeys))
+    }
+
+    pub fn get_metrics(&self) -> [(&'static str, usize); 10] {
         [
             ("accounts", self.accounts.filters.len()),
             ("slots", self.slots.filters.len()),
@@ -215,6 +366,8 @@ impl Filter {
             ("entries", self.entries.filters.len()),
             ("blocks", self.blocks.filters.len()),
             ("blocks_meta", self.blocks_meta.filters.len()),
+            ("votes", self.votes.filters.len()),
+            ("stats", self.stats.filters.len()),
             (
                 "all",
                 self.accounts.filters.len()
@@ -223,24 +376,73 @@ impl Filter {
                     + self.transactions_status.filters.len()
                     + self.entries.filters.len()
                     + self.blocks.filters.len()
-                    + self.blocks_meta.filters.len(),
+                    + self.blocks_meta.filters.len()
+                    + self.votes.filters.len()
+                    + self.stats.filters.len(),
             ),
         ]
     }
 
+    /// Commitment and names of accepted filters, slots and version are set by the server
+    pub fn get_stream_info(&self) -> SubscribeUpdateStreamInfo {
+        fn names<'a>(names: impl Iterator<Item = &'a FilterName>) -> Vec<String> {
+            let mut names = names.map(|name| name.to_string()).collect::<Vec<_>>();
+            names.sort_unstable();
+            names
+        }
+
+        SubscribeUpdateStreamInfo {
+            commitment: CommitmentLevelProto::from(self.commitment) as i32,
+            accounts: names(self.accounts.filters.iter().map(|(name, _state)| name)),
+            slots: names(self.slots.filters.keys()),
+            transactions: names(self.transactions.filters.keys()),
+            transactions_status: names(self.transactions_status.filters.keys()),
+            entries: names(self.entries.filters.iter()),
+            blocks: names(self.blocks.filters.keys()),
+            blocks_meta: names(self.blocks_meta.filters.iter()),
+            votes: names(self.votes.filters.keys()),
+            stats: names(self.stats.filters.iter()),
+            ..Default::default()
+        }
+    }
+
     pub const fn get_commitment_level(&self) -> CommitmentLevel {
         self.commitment
     }
 
+    pub const fn is_startup_accounts(&self) -> bool {
+        self.startup_accounts
+    }
+
+    /// Periodic pings are sent with counters of the stream
+    pub const fn is_ping_stats(&self) -> bool {
+        self.include_ping_stats
+    }
+
+    /// Full blocks are assembled by the service only for filters with blocks filters
+    pub fn has_blocks(&self) -> bool {
+        !self.blocks.filters.is_empty()
+    }
+
+    /// Returns `true` if messages should be skipped on lag instead of disconnect
+    pub const fn is_lag_skip(&self) -> bool {
+        matches!(self.on_lag, OnLag::Skip)
+    }
+
+    pub const fn get_seen_signatures(&self) -> Option<&FilterSeenSignatures> {
+        self.seen_signatures.as_ref()
+    }
+
     pub fn get_updates(
         &self,
         message: &Message,
         commitment: Option<CommitmentLevel>,
     ) -> FilteredUpdates {
-        match message {
-            Message::Account(message) => self
-                .accounts
-                .get_updates(message, &self.accounts_data_slice),
+        let updates = match message {
+            Message::Account(message) => {
+                self.accounts
+                    .get_updates(message, &self.accounts_data_slice, false)
+            }
             Message::Slot(message) => self.slots.get_updates(message, commitment),
             Message::Transaction(message) => {
                 let mut updates = self.transactions.get_updates(message);
@@ -250,24 +452,244 @@ impl Filter {
             Message::Entry(message) => self.entries.get_updates(message),
             Message::Block(message) => self.blocks.get_updates(message, &self.accounts_data_slice),
             Message::BlockMeta(message) => self.blocks_meta.get_updates(message),
+            Message::BlockDiagnostic(message) => self.blocks.get_diagnostic_updates(message),
+            Message::Votes(message) => self.votes.get_updates(message),
+            Message::SlotStats(message) => self.stats.get_updates(message),
+            Message::Rollback(message) => self.accounts.get_rollback_updates(message),
+        };
+        self.strip_updates(updates)
+    }
+
+    /// Returns `true` if any transactions filter has `ordered_per_slot`
+    pub fn is_transactions_ordered(&self) -> bool {
+        !self.transactions.ordered.is_empty() || !self.transactions_status.ordered.is_empty()
+    }
+
+    /// Names of transactions filters with `ordered_per_slot`
+    pub fn get_transactions_ordered(&self) -> FilteredUpdateFilters {
+        self.transactions
+            .ordered
+            .iter()
+            .chain(self.transactions_status.ordered.iter())
+            .cloned()
+            .collect()
+    }
+
+    /// Split filters of transaction update to filters without and with `ordered_per_slot`
+    pub fn split_transactions_ordered(
+        &self,
+        filters: &FilteredUpdateFilters,
+    ) -> (FilteredUpdateFilters, FilteredUpdateFilters) {
+        filters.iter().cloned().partition(|name| {
+            !self.transactions.ordered.contains(name)
+                && !self.transactions_status.ordered.contains(name)
+        })
+    }
+
+    /// Names of accounts filters matched by the account, can be used to check
+    /// a request without a server (account is matched as a write after startup)
+    ///
+    /// ```
+    /// use {
+    ///     solana_sdk::{account::Account, pubkey::Pubkey},
+    ///     std::time::Duration,
+    ///     yellowstone_grpc_proto::{
+    ///         plugin::{
+    ///             filter::{limits::FilterLimits, name::FilterNames, Filter},
+    ///             message::MessageAccountInfo,
+    ///         },
+    ///         prelude::{SubscribeRequest, SubscribeRequestFilterAccounts},
+    ///     },
+    /// };
+    ///
+    /// let owner = Pubkey::new_unique();
+    /// let request = SubscribeRequest {
+    ///     accounts: [(
+    ///         "owned".to_owned(),
+    ///         SubscribeRequestFilterAccounts {
+    ///             owner: vec![owner.to_string()],
+    ///             ..Default::default()
+    ///         },
+    ///     )]
+    ///     .into_iter()
+    ///     .collect(),
+    ///     ..Default::default()
+    /// };
+    /// let filter = Filter::new(
+    ///     &request,
+    ///     &FilterLimits::default(),
+    ///     &mut FilterNames::new(128, 4_096, Duration::from_secs(1)),
+    /// )
+    /// .unwrap();
+    ///
+    /// let account = Account::new(42, 0, &owner);
+    /// let account = MessageAccountInfo::from_account(Pubkey::new_unique(), &account, 0, None);
+    /// assert_eq!(filter.matches_account(&account), ["owned"]);
+    ///
+    /// let account = Account::new(42, 0, &Pubkey::new_unique());
+    /// let account = MessageAccountInfo::from_account(Pubkey::new_unique(), &account, 0, None);
+    /// assert!(filter.matches_account(&account).is_empty());
+    /// ```
+    pub fn matches_account(&self, account: &MessageAccountInfo) -> Vec<String> {
+        self.accounts
+            .get_filters(account, false)
+            .iter()
+            .map(|name| name.as_ref().to_owned())
+            .collect()
+    }
+
+    /// Names of transactions and transactions status filters matched by the transaction
+    pub fn matches_transaction(&self, transaction: &MessageTransactionInfo) -> Vec<String> {
+        self.transactions
+            .get_filters(transaction)
+            .iter()
+            .chain(self.transactions_status.get_filters(transaction).iter())
+            .map(|name| name.as_ref().to_owned())
+            .collect()
+    }
+
+    /// Returns `true` if any accounts filter has `coalesce_accounts_per_slot`
+    pub fn is_accounts_coalesce(&self) -> bool {
+        !self.accounts.coalesce.is_empty()
+    }
+
+    /// Owners of all accounts filters, `None` if any accounts filter matches accounts
+    /// without an `owner` constraint
+    pub fn get_accounts_owners(&self) -> Option<Vec<Pubkey>> {
+        self.accounts
+            .filters
+            .iter()
+            .all(|(name, _state)| self.accounts.owner_required.contains(name))
+            .then(|| self.accounts.owner.pubkeys().copied().collect())
+    }
+
+    /// Amount of `create_program_address` calls made by `pda` templates since the previous call
+    pub fn take_pda_derivations(&self) -> u64 {
+        self.accounts.pda_derivations.swap(0, Ordering::Relaxed)
+    }
+
+    /// Returns `true` if the signature is in `txn_signature` of any accounts filter
+    pub fn is_accounts_txn_signature(&self, signature: &Signature) -> bool {
+        self.accounts.txn_signature.contains_key(signature)
+    }
+
+    /// Remove the signature from `txn_signature` of all accounts filters, filters with
+    /// `txn_signature` don't match anything once all signatures are removed
+    pub fn remove_accounts_txn_signature(&mut self, signature: &Signature) {
+        self.accounts.txn_signature.remove(signature);
+    }
+
+    /// Returns `true` if the signature is in `signatures` of any transactions filter from `filters`
+    pub fn is_transactions_signature(
+        &self,
+        signature: &Signature,
+        filters: &FilteredUpdateFilters,
+    ) -> bool {
+        [&self.transactions, &self.transactions_status]
+            .into_iter()
+            .filter_map(|filter| filter.signatures.get(signature))
+            .any(|names| filters.iter().any(|name| names.contains(name)))
+    }
+
+    /// Remove the signature from `signatures` of all transactions filters, filters with
+    /// `signatures` don't match anything once all signatures are removed
+    pub fn remove_transactions_signature(&mut self, signature: &Signature) {
+        self.transactions.signatures.remove(signature);
+        self.transactions_status.signatures.remove(signature);
+    }
+
+    /// Remove all `signatures` of transactions filters
+    pub fn clear_transactions_signatures(&mut self) {
+        self.transactions.signatures.clear();
+        self.transactions_status.signatures.clear();
+    }
+
+    /// Amount of slots during which `signatures` of transactions filters are matched, `None`
+    /// if there are no signatures left
+    pub fn get_transactions_signatures_expire_slots(&self) -> Option<u64> {
+        [&self.transactions, &self.transactions_status]
+            .into_iter()
+            .filter(|filter| !filter.signatures.is_empty())
+            .map(|filter| filter.signatures_expire_slots)
+            .min()
+    }
+
+    /// Same as `get_updates` for account message, but skip filters with
+    /// `coalesce_accounts_per_slot` because newer write of the account is known
+    pub fn get_updates_superseded(&self, message: &MessageAccount) -> FilteredUpdates {
+        let updates = self
+            .accounts
+            .get_updates(message, &self.accounts_data_slice, true);
+        self.strip_updates(updates)
+    }
+
+    /// Remove optional fields not requested by the subscription, `resolved_accounts` of
+    /// transactions are added only on request
+    fn strip_updates(&self, mut updates: FilteredUpdates) -> FilteredUpdates {
+        if !self.include_created_at {
+            for update in updates.iter_mut() {
+                update.created_at = None;
+            }
         }
+        if !self.include_commitment_slot {
+            for update in updates.iter_mut() {
+                // finalized slot is the proof of `finalized_with_proof` filters
+                if update
+                    .filters
+                    .iter()
+                    .any(|name| self.accounts.finalized_with_proof.contains(name))
+                {
+                    continue;
+                }
+                if let FilteredUpdateOneof::Account(msg) = &mut update.message {
+                    msg.confirmed_at_slot = None;
+                    msg.finalized_at_slot = None;
+                }
+            }
+        }
+        if self.include_resolved_accounts {
+            for update in updates.iter_mut() {
+                if let FilteredUpdateOneof::Transaction(msg) = &mut update.message {
+                    msg.resolved_accounts = true;
+                }
+            }
+        }
+        updates
     }
 
     pub fn get_pong_msg(&self) -> Option<FilteredUpdate> {
         self.ping
             .map(|id| FilteredUpdate::new_empty(FilteredUpdateOneof::pong(id)))
     }
+
+    /// Warnings about accepted filter, sent with the stream info of the filter
+    pub fn get_warnings(&self) -> &[String] {
+        &self.accounts.warnings
+    }
 }
 
 #[derive(Debug, Default, Clone)]
 struct FilterAccounts {
     nonempty_txn_signature: Vec<(FilterName, Option<bool>)>,
     nonempty_txn_signature_required: HashSet<FilterName>,
-    account: HashMap<Pubkey, HashSet<FilterName>>,
+    txn_signature: HashMap<Signature, HashSet<FilterName>>,
+    txn_signature_required: HashSet<FilterName>,
+    origin: HashMap<AccountOrigin, HashSet<FilterName>>,
+    origin_required: HashSet<FilterName>,
+    account: FilterPubkeysIndex,
+    account_prefix: FilterAccountsPrefixes,
     account_required: HashSet<FilterName>,
-    owner: HashMap<Pubkey, HashSet<FilterName>>,
+    owner: FilterPubkeysIndex,
+    owner_prefix: FilterAccountsPrefixes,
     owner_required: HashSet<FilterName>,
+    account_exclude: HashMap<Pubkey, HashSet<FilterName>>,
+    owner_exclude: HashMap<Pubkey, HashSet<FilterName>>,
+    pda: HashMap<Pubkey, Vec<(FilterName, FilterAccountsPda)>>,
+    pda_derivations: Arc<AtomicU64>,
     filters: Vec<(FilterName, FilterAccountsState)>,
+    coalesce: HashSet<FilterName>,
+    finalized_with_proof: HashSet<FilterName>,
+    warnings: Vec<String>,
 }
 
 impl FilterAccounts {
@@ -279,6 +701,13 @@ impl FilterAccounts {
         FilterLimits::check_max(configs.len(), limits.max)?;
 
         let mut this = Self::default();
+        // exclude lists only narrow the filter, so reject lists are not applied
+        let exclude_reject = HashSet::new();
+        // `account` / `owner` lists of all filters are interned as one index
+        let mut account_lists = vec![];
+        let mut owner_lists = vec![];
+        // worst case, all `pda` of the subscription are of the same program
+        let mut pda_derivations_max = 0;
         for (name, filter) in configs {
             this.nonempty_txn_signature
                 .push((names.get(name)?, filter.nonempty_txn_signature));
@@ -288,71 +717,364 @@ impl FilterAccounts {
             }
 
             FilterLimits::check_any(
-                filter.account.is_empty() && filter.owner.is_empty(),
+                filter.account.is_empty()
+                    && filter.owner.is_empty()
+                    && filter.txn_signature.is_empty()
+                    && filter.account_prefix.is_empty()
+                    && filter.owner_prefix.is_empty()
+                    && filter.pda.is_empty(),
                 limits.any,
             )?;
-            FilterLimits::check_pubkey_max(filter.account.len(), limits.account_max)?;
-            FilterLimits::check_pubkey_max(filter.owner.len(), limits.owner_max)?;
+
+            FilterLimits::check_max(filter.txn_signature.len(), limits.txn_signature_max)?;
+            for signature in filter.txn_signature.iter() {
+                this.txn_signature
+                    .entry(signature.parse()?)
+                    .or_default()
+                    .insert(names.get(name)?);
+            }
+            if !filter.txn_signature.is_empty() {
+                this.txn_signature_required.insert(names.get(name)?);
+            }
+
+            for origin in filter.origins.iter() {
+                this.origin
+                    .entry(
+                        AccountOrigin::try_from(*origin).map_err(|_error| {
+                            FilterError::InvalidAccountOrigin { origin: *origin }
+                        })?,
+                    )
+                    .or_default()
+                    .insert(names.get(name)?);
+            }
+            if !filter.origins.is_empty() {
+                this.origin_required.insert(names.get(name)?);
+            }
+
+            let account = Self::clamp(
+                &mut this.warnings,
+                name,
+                "account",
+                "Pubkeys",
+                &filter.account,
+                limits.account_max,
+                limits.over_limit_policy,
+            )?;
+            let owner = Self::clamp(
+                &mut this.warnings,
+                name,
+                "owner",
+                "Pubkeys",
+                &filter.owner,
+                limits.owner_max,
+                limits.over_limit_policy,
+            )?;
 
             Self::set(
-                &mut this.account,
+                &mut account_lists,
                 &mut this.account_required,
                 name,
                 names,
-                Filter::decode_pubkeys(&filter.account, &limits.account_reject),
+                Filter::decode_pubkeys(account, &limits.account_reject),
             )?;
 
             Self::set(
-                &mut this.owner,
+                &mut owner_lists,
                 &mut this.owner_required,
                 name,
                 names,
-                Filter::decode_pubkeys(&filter.owner, &limits.owner_reject),
+                Filter::decode_pubkeys(owner, &limits.owner_reject),
+            )?;
+
+            // prefix matches are alternatives to `account` / `owner` lists of the same filter
+            if this.account_prefix.insert(
+                &mut this.warnings,
+                name,
+                names,
+                "account_prefix",
+                &filter.account_prefix,
+                &limits.account_reject,
+                limits,
+            )? {
+                this.account_required.insert(names.get(name)?);
+            }
+            if this.owner_prefix.insert(
+                &mut this.warnings,
+                name,
+                names,
+                "owner_prefix",
+                &filter.owner_prefix,
+                &limits.owner_reject,
+                limits,
+            )? {
+                this.owner_required.insert(names.get(name)?);
+            }
+            // and pda matches are alternative to `account` list
+            FilterLimits::check_max(filter.pda.len(), limits.pda_max)?;
+            for pda in filter.pda.iter() {
+                let (program_id, pda) = FilterAccountsPda::new(pda, limits)?;
+                pda_derivations_max += pda.get_derivations_max();
+                this.pda
+                    .entry(program_id)
+                    .or_default()
+                    .push((names.get(name)?, pda));
+            }
+            if !filter.pda.is_empty() {
+                this.account_required.insert(names.get(name)?);
+            }
+
+            // exclude lists are never clamped, dropped Pubkeys would broaden delivered updates
+            FilterLimits::check_pubkey_max(filter.account_exclude.len(), limits.account_max)?;
+            FilterLimits::check_pubkey_max(filter.owner_exclude.len(), limits.owner_max)?;
+
+            Self::insert(
+                &mut this.account_exclude,
+                name,
+                names,
+                Filter::decode_pubkeys(&filter.account_exclude, &exclude_reject),
+            )?;
+
+            Self::insert(
+                &mut this.owner_exclude,
+                name,
+                names,
+                Filter::decode_pubkeys(&filter.owner_exclude, &exclude_reject),
             )?;
 
-            this.filters
-                .push((names.get(name)?, FilterAccountsState::new(&filter.filters)?));
+            this.filters.push((
+                names.get(name)?,
+                FilterAccountsState::new(&filter.filters, filter.executable, limits)?,
+            ));
+
+            if filter.coalesce_accounts_per_slot.unwrap_or_default() {
+                this.coalesce.insert(names.get(name)?);
+            }
+            if filter.finalized_with_proof.unwrap_or_default() {
+                this.finalized_with_proof.insert(names.get(name)?);
+            }
         }
+        if pda_derivations_max > limits.pda_derivations_max {
+            return Err(FilterLimitsCheckError::MaxPdaDerivations {
+                max: limits.pda_derivations_max,
+                requested: pda_derivations_max,
+            }
+            .into());
+        }
+        this.account = names.get_pubkeys_index(account_lists);
+        this.owner = names.get_pubkeys_index(owner_lists);
         Ok(this)
     }
 
+    fn clamp<'a>(
+        warnings: &mut Vec<String>,
+        name: &str,
+        kind: &str,
+        unit: &str,
+        pubkeys: &'a [String],
+        max: usize,
+        policy: FilterLimitsOverLimitPolicy,
+    ) -> FilterResult<&'a [String]> {
+        let len = FilterLimits::check_pubkey_max_policy(pubkeys.len(), max, policy)?;
+        if len < pubkeys.len() {
+            warnings.push(format!(
+                "filter `{name}`: `{kind}` clamped to {len} {unit}, {} dropped",
+                pubkeys.len() - len
+            ));
+        }
+        Ok(&pubkeys[..len])
+    }
+
     fn set(
+        lists: &mut Vec<(FilterName, Vec<Pubkey>)>,
+        lists_required: &mut HashSet<FilterName>,
+        name: &str,
+        names: &mut FilterNames,
+        keys: impl Iterator<Item = FilterResult<Pubkey>>,
+    ) -> FilterResult<()> {
+        let list = keys.collect::<FilterResult<Vec<_>>>()?;
+        if !list.is_empty() {
+            lists.push((names.get(name)?, list));
+            lists_required.insert(names.get(name)?);
+        }
+        Ok(())
+    }
+
+    fn insert(
         map: &mut HashMap<Pubkey, HashSet<FilterName>>,
-        map_required: &mut HashSet<FilterName>,
         name: &str,
         names: &mut FilterNames,
         keys: impl Iterator<Item = FilterResult<Pubkey>>,
     ) -> FilterResult<bool> {
-        let mut required = false;
+        let mut inserted = false;
         for maybe_key in keys {
             if map.entry(maybe_key?).or_default().insert(names.get(name)?) {
-                required = true;
+                inserted = true;
             }
         }
+        Ok(inserted)
+    }
 
-        if required {
-            map_required.insert(names.get(name)?);
-        }
-        Ok(required)
+    fn get_filters(&self, account: &MessageAccountInfo, is_startup: bool) -> FilteredUpdateFilters {
+        let mut filter = FilterAccountsMatch::new(self);
+        filter.match_txn_signature(&account.txn_signature);
+        filter.match_origin(account.get_origin(is_startup));
+        filter.match_account(account);
+        filter.match_pda(&account.pubkey, &account.owner, &account.data);
+        filter.match_owner(account);
+        filter.match_exclude(&account.pubkey, &account.owner);
+        filter.match_data_lamports(&account.data, account.lamports, account.executable);
+        filter.get_filters()
     }
 
     fn get_updates(
         &self,
         message: &MessageAccount,
         accounts_data_slice: &FilterAccountsDataSlice,
+        superseded: bool,
     ) -> FilteredUpdates {
-        let mut filter = FilterAccountsMatch::new(self);
-        filter.match_txn_signature(&message.account.txn_signature);
-        filter.match_account(&message.account.pubkey);
-        filter.match_owner(&message.account.owner);
-        filter.match_data_lamports(&message.account.data, message.account.lamports);
-        let filters = filter.get_filters();
+        let mut filters = self.get_filters(&message.account, message.is_startup);
+        if superseded {
+            filters.retain(|name| !self.coalesce.contains(name));
+        }
+        // only writes flushed by the finalized status of the slot
+        if message.finalized_at_slot.is_none() {
+            filters.retain(|name| !self.finalized_with_proof.contains(name));
+        }
         filtered_updates_once_owned!(
             filters,
             FilteredUpdateOneof::account(message, accounts_data_slice.clone()),
             message.created_at
         )
     }
+
+    /// One update with all accounts of the rolled back slot matched by `finalized_with_proof`
+    /// filters, these accounts were sent by the same filters when the slot was finalized
+    fn get_rollback_updates(&self, message: &MessageRollback) -> FilteredUpdates {
+        let mut filters = FilteredUpdateFilters::new();
+        let mut pubkeys = vec![];
+        if !self.finalized_with_proof.is_empty() {
+            for account in message.accounts.iter() {
+                let mut matched = false;
+                for name in self.get_filters(&account.account, account.is_startup) {
+                    if self.finalized_with_proof.contains(&name) {
+                        matched = true;
+                        if !filters.contains(&name) {
+                            filters.push(name);
+                        }
+                    }
+                }
+                if matched {
+                    pubkeys.push(account.account.pubkey.to_bytes().to_vec());
+                }
+            }
+        }
+        filtered_updates_once_owned!(
+            filters,
+            FilteredUpdateOneof::rollback(SubscribeUpdateRollback {
+                slot: message.slot,
+                finalized_slot: message.finalized_slot,
+                pubkeys,
+            }),
+            message.created_at
+        )
+    }
+}
+
+/// Base58 string prefixes of Pubkeys, a Pubkey matches the prefix if its base58 string starts
+/// with it. That's not a byte prefix: leading zero bytes are encoded as `1` and the length of
+/// the string is from 32 to 44 characters, so Pubkeys with the same leading bytes can have
+/// different prefixes. Prefixes are sorted, matched ones are found with one lookup per
+/// distinct prefix length instead of a scan over all prefixes.
+#[derive(Debug, Default, Clone)]
+struct FilterAccountsPrefixes {
+    prefixes: BTreeMap<String, HashSet<FilterName>>,
+    lengths: BTreeSet<usize>,
+}
+
+impl FilterAccountsPrefixes {
+    const ALPHABET: &'static str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+    const PUBKEY_MAX_LEN: usize = 44;
+
+    /// Prefixes matching any rejected Pubkey are rejected, otherwise the reject list could be
+    /// bypassed with a prefix of the Pubkey
+    #[allow(clippy::too_many_arguments)]
+    fn insert(
+        &mut self,
+        warnings: &mut Vec<String>,
+        name: &str,
+        names: &mut FilterNames,
+        kind: &str,
+        prefixes: &[String],
+        reject: &HashSet<Pubkey>,
+        limits: &FilterLimitsAccounts,
+    ) -> FilterResult<bool> {
+        // prefixes are not sharded, so only clamped or rejected
+        let prefixes = if limits.over_limit_policy == FilterLimitsOverLimitPolicy::Clamp {
+            FilterAccounts::clamp(
+                warnings,
+                name,
+                kind,
+                "prefixes",
+                prefixes,
+                limits.prefix_max,
+                limits.over_limit_policy,
+            )?
+        } else {
+            FilterLimits::check_max(prefixes.len(), limits.prefix_max)?;
+            prefixes
+        };
+        let reject = if prefixes.is_empty() {
+            vec![]
+        } else {
+            reject
+                .iter()
+                .map(|pubkey| (pubkey, pubkey.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        for prefix in prefixes {
+            FilterLimits::check_prefix_min_len(prefix.len(), limits.prefix_min_len)?;
+            if prefix.len() > Self::PUBKEY_MAX_LEN
+                || !prefix.chars().all(|ch| Self::ALPHABET.contains(ch))
+            {
+                return Err(FilterError::InvalidPrefix {
+                    prefix: prefix.clone(),
+                });
+            }
+            if let Some((pubkey, _encoded)) = reject
+                .iter()
+                .find(|(_pubkey, encoded)| encoded.starts_with(prefix.as_str()))
+            {
+                return Err(FilterLimitsCheckError::PubkeyReject { pubkey: **pubkey }.into());
+            }
+
+            self.prefixes
+                .entry(prefix.clone())
+                .or_default()
+                .insert(names.get(name)?);
+            self.lengths.insert(prefix.len());
+        }
+        Ok(!prefixes.is_empty())
+    }
+
+    /// `pubkey` is base58 encoded only if there are prefixes
+    fn extend<'a, 'b>(&'a self, set: &mut HashSet<&'a str>, pubkey: impl FnOnce() -> &'b str) {
+        if self.prefixes.is_empty() {
+            return;
+        }
+
+        let pubkey = pubkey();
+        for len in self.lengths.iter() {
+            let Some(prefix) = pubkey.get(..*len) else {
+                break;
+            };
+            if let Some(names) = self.prefixes.get(prefix) {
+                for name in names {
+                    set.insert(name.as_ref());
+                }
+            }
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone)]
@@ -361,10 +1083,16 @@ struct FilterAccountsState {
     datasize: Option<usize>,
     token_account_state: bool,
     lamports: Vec<FilterAccountsLamports>,
+    lamports_range: Vec<FilterAccountsLamportsRange>,
+    executable: Option<bool>,
 }
 
 impl FilterAccountsState {
-    fn new(filters: &[SubscribeRequestFilterAccountsFilter]) -> FilterResult<Self> {
+    fn new(
+        filters: &[SubscribeRequestFilterAccountsFilter],
+        executable: Option<bool>,
+        limits: &FilterLimitsAccounts,
+    ) -> FilterResult<Self> {
         const MAX_FILTERS: usize = 4;
         const MAX_DATA_SIZE: usize = 128;
         const MAX_DATA_BASE58_SIZE: usize = 175;
@@ -374,7 +1102,10 @@ impl FilterAccountsState {
             return Err(FilterError::CreateAccountStateMaxFilters { max: MAX_FILTERS });
         }
 
-        let mut this = Self::default();
+        let mut this = Self {
+            executable,
+            ..Default::default()
+        };
         for filter in filters {
             match &filter.filter {
                 Some(AccountsFilterDataOneof::Memcmp(memcmp)) => {
@@ -432,6 +1163,10 @@ impl FilterAccountsState {
                     };
                     this.lamports.push(cmp.into());
                 }
+                Some(AccountsFilterDataOneof::LamportsRange(range)) => {
+                    this.lamports_range
+                        .push(FilterAccountsLamportsRange::new(range, limits)?);
+                }
                 None => {
                     return Err(FilterError::CreateAccountState("filter should be defined"));
                 }
@@ -445,9 +1180,14 @@ impl FilterAccountsState {
             && self.datasize.is_none()
             && !self.token_account_state
             && self.lamports.is_empty()
+            && self.lamports_range.is_empty()
+            && self.executable.is_none()
     }
 
-    fn is_match(&self, data: &[u8], lamports: u64) -> bool {
+    fn is_match(&self, data: &[u8], lamports: u64, executable: bool) -> bool {
+        if matches!(self.executable, Some(value) if value != executable) {
+            return false;
+        }
         if matches!(self.datasize, Some(datasize) if data.len() != datasize) {
             return false;
         }
@@ -457,6 +1197,13 @@ impl FilterAccountsState {
         if self.lamports.iter().any(|f| !f.is_match(lamports)) {
             return false;
         }
+        if self
+            .lamports_range
+            .iter()
+            .any(|f| !f.is_match(lamports, data.len()))
+        {
+            return false;
+        }
         for (offset, bytes) in self.memcmp.iter() {
             if data.len() < *offset + bytes.len() {
                 return false;
@@ -500,25 +1247,241 @@ impl FilterAccountsLamports {
     }
 }
 
-#[derive(Debug)]
-struct FilterAccountsMatch<'a> {
-    filter: &'a FilterAccounts,
-    nonempty_txn_signature: HashSet<&'a str>,
-    account: HashSet<&'a str>,
-    owner: HashSet<&'a str>,
-    data: HashSet<&'a str>,
+#[derive(Debug, Clone)]
+struct FilterAccountsLamportsRange {
+    min: u64,
+    max: u64,
+    below_rent_exempt: Option<Rent>,
 }
 
-impl<'a> FilterAccountsMatch<'a> {
-    fn new(filter: &'a FilterAccounts) -> Self {
-        Self {
-            filter,
-            nonempty_txn_signature: Default::default(),
-            account: Default::default(),
-            owner: Default::default(),
-            data: Default::default(),
+impl FilterAccountsLamportsRange {
+    fn new(
+        range: &SubscribeRequestFilterAccountsFilterLamportsRange,
+        limits: &FilterLimitsAccounts,
+    ) -> FilterResult<Self> {
+        let below_rent_exempt = range.below_rent_exempt.unwrap_or_default();
+        if range.min.is_none() && range.max.is_none() && !below_rent_exempt {
+            return Err(FilterError::CreateAccountState(
+                "min, max or below_rent_exempt for lamports_range should be defined",
+            ));
         }
-    }
+
+        let min = range.min.unwrap_or(u64::MIN);
+        let max = range.max.unwrap_or(u64::MAX);
+        if max < min {
+            return Err(FilterError::CreateAccountState(
+                "max for lamports_range should not be less than min",
+            ));
+        }
+
+        Ok(Self {
+            min,
+            max,
+            below_rent_exempt: below_rent_exempt.then(|| Rent {
+                lamports_per_byte_year: limits.rent_lamports_per_byte_year,
+                exemption_threshold: limits.rent_exemption_threshold,
+                ..Rent::default()
+            }),
+        })
+    }
+
+    fn is_match(&self, lamports: u64, data_len: usize) -> bool {
+        (self.min..=self.max).contains(&lamports)
+            && self
+                .below_rent_exempt
+                .as_ref()
+                .is_none_or(|rent| lamports < rent.minimum_balance(data_len))
+    }
+}
+
+/// Program derived address, the address can't be reversed to seeds so by default only the owner
+/// and the data prefix are checked. With templates the address is derived from seeds with one
+/// variable seed taken from the account data, amount of derivations per account update is
+/// limited by `pda_templates_max` and `pda_bump_search_max`.
+#[derive(Debug, Clone)]
+struct FilterAccountsPda {
+    seed_prefix: Vec<u8>,
+    data_prefix: Vec<u8>,
+    templates: Vec<FilterAccountsPdaTemplate>,
+    bump_search_max: usize,
+}
+
+impl FilterAccountsPda {
+    const MAX_DATA_PREFIX_SIZE: usize = 128;
+
+    fn new(
+        pda: &SubscribeRequestFilterAccountsPda,
+        limits: &FilterLimitsAccounts,
+    ) -> FilterResult<(Pubkey, Self)> {
+        let program_id = Pubkey::from_str(&pda.program_id)?;
+        FilterLimits::check_pubkey_reject(&program_id, &limits.owner_reject)?;
+        if pda.data_prefix.len() > Self::MAX_DATA_PREFIX_SIZE {
+            return Err(FilterError::CreateAccountState(
+                "data_prefix for pda too large",
+            ));
+        }
+        if pda.seed_prefix.len() > MAX_SEED_LEN {
+            return Err(FilterError::CreateAccountState("seed for pda too large"));
+        }
+
+        if !pda.templates.is_empty() && !limits.pda_templates {
+            return Err(FilterLimitsCheckError::AccessKind {
+                kind: "accounts.pda.templates",
+            }
+            .into());
+        }
+        FilterLimits::check_max(pda.templates.len(), limits.pda_templates_max)?;
+        let templates = pda
+            .templates
+            .iter()
+            .map(FilterAccountsPdaTemplate::new)
+            .collect::<FilterResult<_>>()?;
+
+        Ok((
+            program_id,
+            Self {
+                seed_prefix: pda.seed_prefix.clone(),
+                data_prefix: pda.data_prefix.clone(),
+                templates,
+                bump_search_max: limits.pda_bump_search_max.min(u8::MAX as usize + 1),
+            },
+        ))
+    }
+
+    /// Max amount of `create_program_address` calls per account update of the program
+    fn get_derivations_max(&self) -> usize {
+        self.templates
+            .iter()
+            .map(|template| match template.bump_offset {
+                Some(_) => 1,
+                None => self.bump_search_max,
+            })
+            .sum()
+    }
+
+    fn is_match(
+        &self,
+        pubkey: &Pubkey,
+        program_id: &Pubkey,
+        data: &[u8],
+        derivations: &AtomicU64,
+    ) -> bool {
+        if !data.starts_with(&self.data_prefix) {
+            return false;
+        }
+        if self.templates.is_empty() {
+            return true;
+        }
+
+        let mut count = 0;
+        let matched = self.templates.iter().any(|template| {
+            template.is_match(
+                pubkey,
+                program_id,
+                &self.seed_prefix,
+                data,
+                self.bump_search_max,
+                &mut count,
+            )
+        });
+        derivations.fetch_add(count, Ordering::Relaxed);
+        matched
+    }
+}
+
+#[derive(Debug, Clone)]
+struct FilterAccountsPdaTemplate {
+    data: Range<usize>,
+    seed_suffix: Vec<Vec<u8>>,
+    bump_offset: Option<usize>,
+}
+
+impl FilterAccountsPdaTemplate {
+    fn new(template: &SubscribeRequestFilterAccountsPdaTemplate) -> FilterResult<Self> {
+        // seed prefix, seed from the data, suffix and bump
+        if template.seed_suffix.len() + 3 > MAX_SEEDS {
+            return Err(FilterError::CreateAccountState("too many seeds for pda"));
+        }
+        if template.data_len as usize > MAX_SEED_LEN
+            || template
+                .seed_suffix
+                .iter()
+                .any(|seed| seed.len() > MAX_SEED_LEN)
+        {
+            return Err(FilterError::CreateAccountState("seed for pda too large"));
+        }
+        let start = template.data_offset as usize;
+        let end = start.checked_add(template.data_len as usize).ok_or(
+            FilterError::CreateAccountState("data_offset for pda too large"),
+        )?;
+
+        Ok(Self {
+            data: start..end,
+            seed_suffix: template.seed_suffix.clone(),
+            bump_offset: template.bump_offset.map(|offset| offset as usize),
+        })
+    }
+
+    fn is_match(
+        &self,
+        pubkey: &Pubkey,
+        program_id: &Pubkey,
+        seed_prefix: &[u8],
+        data: &[u8],
+        bump_search_max: usize,
+        count: &mut u64,
+    ) -> bool {
+        let Some(seed) = data.get(self.data.clone()) else {
+            return false;
+        };
+        let bumps = match self.bump_offset {
+            Some(offset) => match data.get(offset) {
+                Some(bump) => *bump..=*bump,
+                None => return false,
+            },
+            None if bump_search_max == 0 => return false,
+            None => (u8::MAX - (bump_search_max - 1) as u8)..=u8::MAX,
+        };
+
+        bumps.rev().any(|bump| {
+            *count += 1;
+            let bump = [bump];
+            let mut seeds = Vec::with_capacity(self.seed_suffix.len() + 3);
+            seeds.push(seed_prefix);
+            seeds.push(seed);
+            seeds.extend(self.seed_suffix.iter().map(Vec::as_slice));
+            seeds.push(&bump);
+            Pubkey::create_program_address(&seeds, program_id)
+                .is_ok_and(|address| address == *pubkey)
+        })
+    }
+}
+
+#[derive(Debug)]
+struct FilterAccountsMatch<'a> {
+    filter: &'a FilterAccounts,
+    nonempty_txn_signature: HashSet<&'a str>,
+    txn_signature: HashSet<&'a str>,
+    origin: HashSet<&'a str>,
+    account: HashSet<&'a str>,
+    owner: HashSet<&'a str>,
+    exclude: HashSet<&'a str>,
+    data: HashSet<&'a str>,
+}
+
+impl<'a> FilterAccountsMatch<'a> {
+    fn new(filter: &'a FilterAccounts) -> Self {
+        Self {
+            filter,
+            nonempty_txn_signature: Default::default(),
+            txn_signature: Default::default(),
+            origin: Default::default(),
+            account: Default::default(),
+            owner: Default::default(),
+            exclude: Default::default(),
+            data: Default::default(),
+        }
+    }
 
     fn extend(
         set: &mut HashSet<&'a str>,
@@ -540,19 +1503,66 @@ impl<'a> FilterAccountsMatch<'a> {
                 }
             }
         }
+
+        // startup accounts and some writes have no signature, never match
+        if let Some(names) = txn_signature
+            .as_ref()
+            .and_then(|signature| self.filter.txn_signature.get(signature))
+        {
+            for name in names {
+                self.txn_signature.insert(name.as_ref());
+            }
+        }
+    }
+
+    fn match_origin(&mut self, origin: AccountOrigin) {
+        if let Some(names) = self.filter.origin.get(&origin) {
+            for name in names {
+                self.origin.insert(name.as_ref());
+            }
+        }
+    }
+
+    fn extend_index(set: &mut HashSet<&'a str>, index: &'a FilterPubkeysIndex, key: &Pubkey) {
+        for name in index.get(key) {
+            set.insert(name.as_ref());
+        }
+    }
+
+    fn match_account(&mut self, account: &MessageAccountInfo) {
+        Self::extend_index(&mut self.account, &self.filter.account, &account.pubkey);
+        self.filter
+            .account_prefix
+            .extend(&mut self.account, || account.get_pubkey_base58());
+    }
+
+    fn match_pda(&mut self, pubkey: &Pubkey, owner: &Pubkey, data: &[u8]) {
+        if let Some(pdas) = self.filter.pda.get(owner) {
+            for (name, pda) in pdas {
+                if !self.account.contains(name.as_ref())
+                    && pda.is_match(pubkey, owner, data, &self.filter.pda_derivations)
+                {
+                    self.account.insert(name.as_ref());
+                }
+            }
+        }
     }
 
-    fn match_account(&mut self, pubkey: &Pubkey) {
-        Self::extend(&mut self.account, &self.filter.account, pubkey)
+    fn match_owner(&mut self, account: &MessageAccountInfo) {
+        Self::extend_index(&mut self.owner, &self.filter.owner, &account.owner);
+        self.filter
+            .owner_prefix
+            .extend(&mut self.owner, || account.get_owner_base58());
     }
 
-    fn match_owner(&mut self, pubkey: &Pubkey) {
-        Self::extend(&mut self.owner, &self.filter.owner, pubkey)
+    fn match_exclude(&mut self, pubkey: &Pubkey, owner: &Pubkey) {
+        Self::extend(&mut self.exclude, &self.filter.account_exclude, pubkey);
+        Self::extend(&mut self.exclude, &self.filter.owner_exclude, owner);
     }
 
-    fn match_data_lamports(&mut self, data: &[u8], lamports: u64) {
+    fn match_data_lamports(&mut self, data: &[u8], lamports: u64, executable: bool) {
         for (name, filter) in self.filter.filters.iter() {
-            if filter.is_match(data, lamports) {
+            if filter.is_match(data, lamports, executable) {
                 self.data.insert(name.as_ref());
             }
         }
@@ -572,6 +1582,12 @@ impl<'a> FilterAccountsMatch<'a> {
                 {
                     return None;
                 }
+                if af.txn_signature_required.contains(name) && !self.txn_signature.contains(name) {
+                    return None;
+                }
+                if af.origin_required.contains(name) && !self.origin.contains(name) {
+                    return None;
+                }
                 if af.account_required.contains(name) && !self.account.contains(name) {
                     return None;
                 }
@@ -581,6 +1597,10 @@ impl<'a> FilterAccountsMatch<'a> {
                 if !filter.is_empty() && !self.data.contains(name) {
                     return None;
                 }
+                // Exclude lists are applied after all positive matches
+                if self.exclude.contains(name) {
+                    return None;
+                }
 
                 Some(filter_name.clone())
             })
@@ -588,17 +1608,47 @@ impl<'a> FilterAccountsMatch<'a> {
     }
 }
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone)]
 struct FilterSlotsInner {
     filter_by_commitment: bool,
     interslot_updates: bool,
+    statuses: HashSet<SlotStatus>,
 }
 
 impl FilterSlotsInner {
-    fn new(filter: SubscribeRequestFilterSlots) -> Self {
-        Self {
+    fn new(filter: &SubscribeRequestFilterSlots) -> FilterResult<Self> {
+        Ok(Self {
             filter_by_commitment: filter.filter_by_commitment.unwrap_or_default(),
             interslot_updates: filter.interslot_updates.unwrap_or_default(),
+            statuses: filter
+                .statuses
+                .iter()
+                .map(|status| {
+                    SlotStatusProto::try_from(*status)
+                        .map(Into::into)
+                        .map_err(|_error| FilterError::InvalidSlotStatus { status: *status })
+                })
+                .collect::<FilterResult<_>>()?,
+        })
+    }
+
+    fn is_match(&self, message: &MessageSlot, commitment: Option<CommitmentLevel>) -> bool {
+        if self.filter_by_commitment
+            && !commitment
+                .map(|commitment| commitment == message.status)
+                .unwrap_or(false)
+        {
+            return false;
+        }
+
+        if self.statuses.is_empty() {
+            self.interslot_updates
+                || matches!(
+                    message.status,
+                    SlotStatus::Processed | SlotStatus::Confirmed | SlotStatus::Finalized
+                )
+        } else {
+            self.statuses.contains(&message.status)
         }
     }
 }
@@ -619,12 +1669,8 @@ impl FilterSlots {
         Ok(Self {
             filters: configs
                 .iter()
-                .map(|(name, filter)| {
-                    names
-                        .get(name)
-                        .map(|name| (name, FilterSlotsInner::new(*filter)))
-                })
-                .collect::<Result<_, _>>()?,
+                .map(|(name, filter)| Ok((names.get(name)?, FilterSlotsInner::new(filter)?)))
+                .collect::<FilterResult<_>>()?,
         })
     }
 
@@ -636,22 +1682,8 @@ impl FilterSlots {
         let filters = self
             .filters
             .iter()
-            .filter_map(|(name, inner)| {
-                if (!inner.filter_by_commitment
-                    || commitment
-                        .map(|commitment| commitment == message.status)
-                        .unwrap_or(false))
-                    && (inner.interslot_updates
-                        || matches!(
-                            message.status,
-                            SlotStatus::Processed | SlotStatus::Confirmed | SlotStatus::Finalized
-                        ))
-                {
-                    Some(name.clone())
-                } else {
-                    None
-                }
-            })
+            .filter(|(_name, inner)| inner.is_match(message, commitment))
+            .map(|(name, _inner)| name.clone())
             .collect::<FilteredUpdateFilters>();
         filtered_updates_once_owned!(
             filters,
@@ -672,15 +1704,41 @@ struct FilterTransactionsInner {
     vote: Option<bool>,
     failed: Option<bool>,
     signature: Option<Signature>,
-    account_include: HashSet<Pubkey>,
-    account_exclude: HashSet<Pubkey>,
-    account_required: HashSet<Pubkey>,
+    account_include: Arc<FilterPubkeys>,
+    account_exclude: Arc<FilterPubkeys>,
+    account_required: Arc<FilterPubkeys>,
+    include_loaded_addresses: bool,
+    meta_sections: TransactionMetaSections,
+    transfer: Option<FilterTransactionsTransfer>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FilterTransactionsTransfer {
+    lamports_min: u64,
+    include_inner: bool,
+    balance_fallback: bool,
+}
+
+impl FilterTransactionsTransfer {
+    fn is_match(&self, transaction: &MessageTransactionInfo) -> bool {
+        transaction
+            .get_max_transfer_lamports(self.include_inner)
+            .is_some_and(|lamports| lamports >= self.lamports_min)
+            || (self.balance_fallback
+                && transaction
+                    .get_max_balance_increase()
+                    .is_some_and(|lamports| lamports >= self.lamports_min))
+    }
 }
 
 #[derive(Debug, Clone)]
 struct FilterTransactions {
     filter_type: FilterTransactionsType,
     filters: HashMap<FilterName, FilterTransactionsInner>,
+    ordered: HashSet<FilterName>,
+    signatures: HashMap<Signature, HashSet<FilterName>>,
+    signatures_required: HashSet<FilterName>,
+    signatures_expire_slots: u64,
 }
 
 impl FilterTransactions {
@@ -693,15 +1751,31 @@ impl FilterTransactions {
         FilterLimits::check_max(configs.len(), limits.max)?;
 
         let mut filters = HashMap::new();
+        let mut ordered = HashSet::new();
+        let mut signatures = HashMap::<Signature, HashSet<FilterName>>::new();
+        let mut signatures_required = HashSet::new();
         for (name, filter) in configs {
             FilterLimits::check_any(
                 filter.vote.is_none()
                     && filter.failed.is_none()
                     && filter.account_include.is_empty()
                     && filter.account_exclude.is_empty()
-                    && filter.account_required.is_empty(),
+                    && filter.account_required.is_empty()
+                    && filter.transfer_lamports_min.is_none()
+                    && filter.signatures.is_empty(),
                 limits.any,
             )?;
+
+            FilterLimits::check_max(filter.signatures.len(), limits.signatures_max)?;
+            for signature in filter.signatures.iter() {
+                signatures
+                    .entry(signature.parse().map_err(FilterError::InvalidSignature)?)
+                    .or_default()
+                    .insert(names.get(name)?);
+            }
+            if !filter.signatures.is_empty() {
+                signatures_required.insert(names.get(name)?);
+            }
             FilterLimits::check_pubkey_max(
                 filter.account_include.len(),
                 limits.account_include_max,
@@ -727,87 +1801,155 @@ impl FilterTransactions {
                             signature_str.parse().map_err(FilterError::InvalidSignature)
                         })
                         .transpose()?,
-                    account_include: Filter::decode_pubkeys_into_set(
+                    account_include: Filter::decode_pubkeys_interned(
                         &filter.account_include,
                         &limits.account_include_reject,
+                        names,
                     )?,
-                    account_exclude: Filter::decode_pubkeys_into_set(
+                    account_exclude: Filter::decode_pubkeys_interned(
                         &filter.account_exclude,
                         &HashSet::new(),
+                        names,
                     )?,
-                    account_required: Filter::decode_pubkeys_into_set(
+                    account_required: Filter::decode_pubkeys_interned(
                         &filter.account_required,
                         &HashSet::new(),
+                        names,
                     )?,
+                    include_loaded_addresses: filter.include_loaded_addresses.unwrap_or(true),
+                    meta_sections: Self::decode_meta_sections(filter),
+                    transfer: filter.transfer_lamports_min.map(|lamports_min| {
+                        FilterTransactionsTransfer {
+                            lamports_min,
+                            include_inner: filter.transfer_include_inner.unwrap_or_default(),
+                            balance_fallback: filter.transfer_balance_fallback.unwrap_or_default(),
+                        }
+                    }),
                 },
             );
+
+            if filter.ordered_per_slot.unwrap_or_default() {
+                ordered.insert(names.get(name)?);
+            }
         }
         Ok(Self {
             filter_type,
             filters,
+            ordered,
+            signatures,
+            signatures_required,
+            signatures_expire_slots: limits.signatures_expire_slots,
         })
     }
 
-    pub fn get_updates(&self, message: &MessageTransaction) -> FilteredUpdates {
-        let filters = self
-            .filters
+    fn decode_meta_sections(
+        filter: &SubscribeRequestFilterTransactions,
+    ) -> TransactionMetaSections {
+        [
+            (filter.include_logs, TransactionMetaSections::LOGS),
+            (
+                filter.include_token_balances,
+                TransactionMetaSections::TOKEN_BALANCES,
+            ),
+            (
+                filter.include_inner_instructions,
+                TransactionMetaSections::INNER_INSTRUCTIONS,
+            ),
+            (
+                filter.include_return_data,
+                TransactionMetaSections::RETURN_DATA,
+            ),
+            (filter.include_rewards, TransactionMetaSections::REWARDS),
+        ]
+        .into_iter()
+        .fold(
+            TransactionMetaSections::ALL,
+            |sections, (include, section)| sections.with(section, include.unwrap_or(true)),
+        )
+    }
+
+    fn get_filters(&self, transaction: &MessageTransactionInfo) -> FilteredUpdateFilters {
+        let mut static_account_keys = None;
+        let signatures = self.signatures.get(&transaction.signature);
+        self.filters
             .iter()
             .filter_map(|(name, inner)| {
+                if self.signatures_required.contains(name)
+                    && !signatures.is_some_and(|names| names.contains(name))
+                {
+                    return None;
+                }
+
                 if let Some(is_vote) = inner.vote {
-                    if is_vote != message.transaction.is_vote {
+                    if is_vote != transaction.is_vote {
                         return None;
                     }
                 }
 
                 if let Some(is_failed) = inner.failed {
-                    if is_failed != message.transaction.meta.err.is_some() {
+                    if is_failed != transaction.meta.err.is_some() {
                         return None;
                     }
                 }
 
                 if let Some(signature) = &inner.signature {
-                    let tx_sig = message.transaction.transaction.signatures.first();
+                    let tx_sig = transaction.transaction.signatures.first();
                     if Some(signature.as_ref()) != tx_sig.map(|sig| sig.as_ref()) {
                         return None;
                     }
                 }
 
+                let account_keys = if inner.include_loaded_addresses {
+                    &transaction.account_keys
+                } else {
+                    static_account_keys.get_or_insert_with(|| transaction.static_account_keys())
+                };
+
                 if !inner.account_include.is_empty()
-                    && inner
-                        .account_include
-                        .intersection(&message.transaction.account_keys)
-                        .next()
-                        .is_none()
+                    && !inner.account_include.intersects(account_keys)
                 {
                     return None;
                 }
 
                 if !inner.account_exclude.is_empty()
-                    && inner
-                        .account_exclude
-                        .intersection(&message.transaction.account_keys)
-                        .next()
-                        .is_some()
+                    && inner.account_exclude.intersects(account_keys)
                 {
                     return None;
                 }
 
                 if !inner.account_required.is_empty()
-                    && !inner
-                        .account_required
-                        .is_subset(&message.transaction.account_keys)
+                    && !inner.account_required.is_subset(account_keys)
                 {
                     return None;
                 }
 
+                if let Some(transfer) = &inner.transfer {
+                    if !transfer.is_match(transaction) {
+                        return None;
+                    }
+                }
+
                 Some(name.clone())
             })
-            .collect::<FilteredUpdateFilters>();
+            .collect()
+    }
+
+    pub fn get_updates(&self, message: &MessageTransaction) -> FilteredUpdates {
+        let filters = self.get_filters(&message.transaction);
+        // sections requested by any of matched filters are sent
+        let meta_sections = filters
+            .iter()
+            .filter_map(|name| self.filters.get(name))
+            .fold(TransactionMetaSections::NONE, |sections, inner| {
+                sections.union(inner.meta_sections)
+            });
 
         filtered_updates_once_owned!(
             filters,
             match self.filter_type {
-                FilterTransactionsType::Transaction => FilteredUpdateOneof::transaction(message),
+                FilterTransactionsType::Transaction => {
+                    FilteredUpdateOneof::transaction_with_meta_sections(message, meta_sections)
+                }
                 FilterTransactionsType::TransactionStatus => {
                     FilteredUpdateOneof::transaction_status(message)
                 }
@@ -854,6 +1996,7 @@ struct FilterBlocksInner {
     include_transactions: Option<bool>,
     include_accounts: Option<bool>,
     include_entries: Option<bool>,
+    include_diagnostics: Option<bool>,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -899,6 +2042,7 @@ impl FilterBlocks {
                     include_transactions: filter.include_transactions,
                     include_accounts: filter.include_accounts,
                     include_entries: filter.include_entries,
+                    include_diagnostics: filter.include_diagnostics,
                 },
             );
         }
@@ -971,12 +2115,32 @@ impl FilterBlocks {
                     accounts_data_slice: accounts_data_slice.clone(),
                     accounts,
                     entries,
+                    is_partial: message.is_partial,
+                    missing_transaction_count: message.missing_transaction_count,
                 })),
                 message.created_at,
             ));
         }
         updates
     }
+
+    fn get_diagnostic_updates(&self, message: &Arc<MessageBlockDiagnostic>) -> FilteredUpdates {
+        let mut filters = FilteredUpdateFilters::new();
+        for (filter, inner) in self.filters.iter() {
+            if inner.include_diagnostics == Some(true) {
+                filters.push(filter.clone());
+            }
+        }
+        let mut updates = FilteredUpdates::new();
+        if !filters.is_empty() {
+            updates.push(FilteredUpdate::new(
+                filters,
+                FilteredUpdateOneof::block_diagnostic(Arc::clone(message)),
+                message.created_at,
+            ));
+        }
+        updates
+    }
 }
 
 #[derive(Debug, Default, Clone)]
@@ -1010,6 +2174,116 @@ impl FilterBlocksMeta {
     }
 }
 
+#[derive(Debug, Default, Clone)]
+struct FilterVotes {
+    filters: HashMap<FilterName, HashSet<Pubkey>>,
+}
+
+impl FilterVotes {
+    fn new(
+        configs: &HashMap<String, SubscribeRequestFilterVotes>,
+        limits: &FilterLimitsVotes,
+        names: &mut FilterNames,
+    ) -> FilterResult<Self> {
+        FilterLimits::check_max(configs.len(), limits.max)?;
+
+        let mut this = Self::default();
+        for (name, filter) in configs {
+            FilterLimits::check_pubkey_max(filter.vote_pubkey.len(), limits.vote_pubkey_max)?;
+
+            this.filters.insert(
+                names.get(name)?,
+                filter
+                    .vote_pubkey
+                    .iter()
+                    .map(|value| Pubkey::from_str(value))
+                    .collect::<Result<_, _>>()?,
+            );
+        }
+        Ok(this)
+    }
+
+    /// Filters without `vote_pubkey` share the message, other filters receive entries
+    /// of own vote accounts only
+    fn get_updates(&self, message: &Arc<MessageVotes>) -> FilteredUpdates {
+        let mut filters = FilteredUpdateFilters::new();
+        let mut updates = FilteredUpdates::new();
+        for (name, vote_pubkeys) in self.filters.iter() {
+            if vote_pubkeys.is_empty() {
+                filters.push(name.clone());
+                continue;
+            }
+
+            let entries = message
+                .entries
+                .iter()
+                .filter(|entry| {
+                    Pubkey::try_from(entry.vote_pubkey.as_slice())
+                        .is_ok_and(|pubkey| vote_pubkeys.contains(&pubkey))
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            if !entries.is_empty() {
+                let mut filters = FilteredUpdateFilters::new();
+                filters.push(name.clone());
+                let votes = SubscribeUpdateVotes {
+                    slot: message.slot,
+                    from_slot: message.from_slot,
+                    to_slot: message.to_slot,
+                    entries,
+                };
+                updates.push(FilteredUpdate::new(
+                    filters,
+                    FilteredUpdateOneof::votes(Arc::new(MessageVotes::from_update_oneof(
+                        votes,
+                        message.created_at,
+                    ))),
+                    message.created_at,
+                ));
+            }
+        }
+        if !filters.is_empty() {
+            updates.push(FilteredUpdate::new(
+                filters,
+                FilteredUpdateOneof::votes(Arc::clone(message)),
+                message.created_at,
+            ));
+        }
+        updates
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+struct FilterStats {
+    filters: Vec<FilterName>,
+}
+
+impl FilterStats {
+    fn new(
+        configs: &HashMap<String, SubscribeRequestFilterStats>,
+        limits: &FilterLimitsStats,
+        names: &mut FilterNames,
+    ) -> FilterResult<Self> {
+        FilterLimits::check_max(configs.len(), limits.max)?;
+
+        Ok(Self {
+            filters: configs
+                .iter()
+                .map(|(name, _filter)| names.get(name))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn get_updates(&self, message: &Arc<MessageSlotStats>) -> FilteredUpdates {
+        let filters = self.filters.as_slice();
+        filtered_updates_once_ref!(
+            filters,
+            FilteredUpdateOneof::slot_stats(Arc::clone(message)),
+            message.created_at
+        )
+    }
+}
+
 #[derive(Debug, Clone, Default, PartialEq)]
 pub struct FilterAccountsDataSlice(Arc<Vec<Range<usize>>>);
 
@@ -1055,18 +2329,33 @@ impl FilterAccountsDataSlice {
         Self(slices)
     }
 
-    pub fn get_slice(&self, source: &[u8]) -> Vec<u8> {
+    /// Contiguous slices are a view of `source` without copy
+    pub fn get_slice(&self, source: &Bytes) -> Bytes {
         if self.0.is_empty() {
-            source.to_vec()
-        } else {
-            let mut data = Vec::with_capacity(self.0.iter().map(|ds| ds.end - ds.start).sum());
-            for data_slice in self.0.iter() {
-                if source.len() >= data_slice.end {
+            return source.clone();
+        }
+
+        let mut slices = self
+            .0
+            .iter()
+            .filter(|data_slice| source.len() >= data_slice.end);
+        let mut contiguous = match slices.next() {
+            Some(data_slice) => data_slice.clone(),
+            None => return Bytes::new(),
+        };
+        for data_slice in slices.by_ref() {
+            if contiguous.end != data_slice.start {
+                let mut data = BytesMut::with_capacity(self.get_slice_len(source));
+                data.extend_from_slice(&source[contiguous]);
+                data.extend_from_slice(&source[data_slice.start..data_slice.end]);
+                for data_slice in slices {
                     data.extend_from_slice(&source[data_slice.start..data_slice.end]);
                 }
+                return data.freeze();
             }
-            data
+            contiguous.end = data_slice.end;
         }
+        source.slice(contiguous)
     }
 
     pub fn get_slice_len(&self, source: &[u8]) -> usize {
@@ -1102,34 +2391,99 @@ impl FilterAccountsDataSlice {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use {
-        super::Filter,
-        crate::{
-            convert_to,
+/// Signatures already seen by the client, used only during the first `slots` of the stream
+#[derive(Debug, Clone)]
+pub struct FilterSeenSignatures {
+    bloom: Arc<SignaturesBloom>,
+    slots: u64,
+}
+
+impl FilterSeenSignatures {
+    fn new(
+        config: Option<&SubscribeRequestSeenSignaturesBloom>,
+        limits: &FilterLimitsSeenSignatures,
+    ) -> FilterResult<Option<Self>> {
+        let Some(config) = config else {
+            return Ok(None);
+        };
+        if config.bits.len() > limits.max_bytes {
+            return Err(FilterLimitsCheckError::MaxSeenSignaturesBytes {
+                max: limits.max_bytes,
+            }
+            .into());
+        }
+        let bloom = SignaturesBloom::try_from(config.clone())
+            .map_err(FilterError::InvalidSeenSignaturesBloom)?;
+        Ok(Some(Self {
+            bloom: Arc::new(bloom),
+            slots: limits.slots,
+        }))
+    }
+
+    pub fn contains(&self, signature: &Signature) -> bool {
+        self.bloom.contains(signature.as_ref())
+    }
+
+    pub const fn slots(&self) -> u64 {
+        self.slots
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{Filter, FilterAccountsDataSlice, FilterError},
+        crate::{
+            bloom::SignaturesBloom,
+            convert_to,
             geyser::{
-                SubscribeRequest, SubscribeRequestFilterAccounts,
-                SubscribeRequestFilterTransactions,
+                subscribe_request_filter_accounts_filter::Filter as AccountsFilterDataOneof,
+                AccountOrigin, CommitmentLevel as CommitmentLevelProto, OnLag,
+                SlotStatus as SlotStatusProto, SubscribeRequest, SubscribeRequestFilterAccounts,
+                SubscribeRequestFilterAccountsFilter,
+                SubscribeRequestFilterAccountsFilterLamportsRange,
+                SubscribeRequestFilterAccountsPda, SubscribeRequestFilterAccountsPdaTemplate,
+                SubscribeRequestFilterBlocksMeta, SubscribeRequestFilterSlots,
+                SubscribeRequestFilterStats, SubscribeRequestFilterTransactions,
+                SubscribeRequestFilterVotes, SubscribeRequestPing,
+                SubscribeRequestSeenSignaturesBloom, SubscribeUpdateSlotStats,
+                SubscribeUpdateVotes, SubscribeUpdateVotesEntry,
             },
             plugin::{
                 filter::{
-                    limits::FilterLimits,
+                    limits::{
+                        FilterAccessKind, FilterCaller, FilterLimits, FilterLimitsAccessAllow,
+                        FilterLimitsAccessRule, FilterLimitsCheckError,
+                        FilterLimitsOverLimitPolicy,
+                    },
                     message::{FilteredUpdateFilters, FilteredUpdateOneof},
                     name::{FilterName, FilterNames},
                 },
-                message::{Message, MessageTransaction, MessageTransactionInfo},
+                message::{
+                    CommitmentLevel, Message, MessageAccount, MessageAccountInfo, MessageRollback,
+                    MessageSlot, MessageSlotStats, MessageTransaction, MessageTransactionInfo,
+                    MessageVotes, SlotStatus, TransactionMetaSections,
+                },
             },
         },
+        bytes::Bytes,
         prost_types::Timestamp,
         solana_sdk::{
+            account::Account,
             hash::Hash,
-            message::{v0::LoadedAddresses, Message as SolMessage, MessageHeader},
+            instruction::{AccountMeta, CompiledInstruction, Instruction},
+            message::{
+                v0::{self, LoadedAddresses, MessageAddressTableLookup},
+                Message as SolMessage, MessageHeader, VersionedMessage,
+            },
             pubkey::Pubkey,
+            rent::Rent,
+            signature::Signature,
             signer::{keypair::Keypair, Signer},
-            transaction::{SanitizedTransaction, Transaction},
+            system_instruction, system_program,
+            transaction::{SanitizedTransaction, Transaction, VersionedTransaction},
         },
-        solana_transaction_status::TransactionStatusMeta,
+        solana_transaction_status::{InnerInstruction, InnerInstructions, TransactionStatusMeta},
         std::{
             collections::HashMap,
             sync::Arc,
@@ -1141,6 +2495,27 @@ mod tests {
         FilterNames::new(64, 1024, Duration::from_secs(1))
     }
 
+    fn create_message_account(pubkey: Pubkey, owner: Pubkey, write_version: u64) -> MessageAccount {
+        MessageAccount {
+            account: Arc::new(MessageAccountInfo {
+                pubkey,
+                lamports: 42,
+                owner,
+                executable: false,
+                rent_epoch: 0,
+                data: Bytes::new(),
+                write_version,
+                txn_signature: None,
+                base58: Default::default(),
+            }),
+            slot: 100,
+            is_startup: false,
+            created_at: Timestamp::from(SystemTime::now()),
+            confirmed_at_slot: None,
+            finalized_at_slot: None,
+        }
+    }
+
     fn create_message_transaction(
         keypair: &Keypair,
         account_keys: Vec<Pubkey>,
@@ -1184,8 +2559,11 @@ mod tests {
                 is_vote: true,
                 transaction: convert_to::create_transaction(&sanitized_transaction),
                 meta,
+                token_balances_none: true,
+                rewards_none: true,
                 index: 1,
                 account_keys,
+                encoded: Default::default(),
             }),
             slot: 100,
             created_at: Timestamp::from(SystemTime::now()),
@@ -1207,6 +2585,17 @@ mod tests {
             accounts_data_slice: Vec::new(),
             ping: None,
             from_slot: None,
+            include_created_at: None,
+            include_commitment_slot: None,
+            include_resolved_accounts: None,
+            include_ping_stats: None,
+            preset: None,
+            preset_params: HashMap::new(),
+            seen_signatures_bloom: None,
+            votes: HashMap::new(),
+            stats: HashMap::new(),
+            startup_accounts: None,
+            on_lag: None,
         };
         let limit = FilterLimits::default();
         let filter = Filter::new(&config, &limit, &mut create_filter_names());
@@ -1224,6 +2613,16 @@ mod tests {
                 account: vec![],
                 owner: vec![],
                 filters: vec![],
+                coalesce_accounts_per_slot: None,
+                account_exclude: vec![],
+                owner_exclude: vec![],
+                txn_signature: vec![],
+                account_prefix: vec![],
+                owner_prefix: vec![],
+                origins: vec![],
+                executable: None,
+                pda: vec![],
+                finalized_with_proof: None,
             },
         );
 
@@ -1239,6 +2638,17 @@ mod tests {
             accounts_data_slice: Vec::new(),
             ping: None,
             from_slot: None,
+            include_created_at: None,
+            include_commitment_slot: None,
+            include_resolved_accounts: None,
+            include_ping_stats: None,
+            preset: None,
+            preset_params: HashMap::new(),
+            seen_signatures_bloom: None,
+            votes: HashMap::new(),
+            stats: HashMap::new(),
+            startup_accounts: None,
+            on_lag: None,
         };
         let mut limit = FilterLimits::default();
         limit.accounts.any = false;
@@ -1260,6 +2670,17 @@ mod tests {
                 account_include: vec![],
                 account_exclude: vec![],
                 account_required: vec![],
+                ordered_per_slot: None,
+                include_logs: None,
+                include_token_balances: None,
+                include_inner_instructions: None,
+                include_return_data: None,
+                include_rewards: None,
+                include_loaded_addresses: None,
+                transfer_lamports_min: None,
+                transfer_include_inner: None,
+                transfer_balance_fallback: None,
+                signatures: vec![],
             },
         );
 
@@ -1275,6 +2696,17 @@ mod tests {
             accounts_data_slice: Vec::new(),
             ping: None,
             from_slot: None,
+            include_created_at: None,
+            include_commitment_slot: None,
+            include_resolved_accounts: None,
+            include_ping_stats: None,
+            preset: None,
+            preset_params: HashMap::new(),
+            seen_signatures_bloom: None,
+            votes: HashMap::new(),
+            stats: HashMap::new(),
+            startup_accounts: None,
+            on_lag: None,
         };
         let mut limit = FilterLimits::default();
         limit.transactions.any = false;
@@ -1295,6 +2727,17 @@ mod tests {
                 account_include: vec![],
                 account_exclude: vec![],
                 account_required: vec![],
+                ordered_per_slot: None,
+                include_logs: None,
+                include_token_balances: None,
+                include_inner_instructions: None,
+                include_return_data: None,
+                include_rewards: None,
+                include_loaded_addresses: None,
+                transfer_lamports_min: None,
+                transfer_include_inner: None,
+                transfer_balance_fallback: None,
+                signatures: vec![],
             },
         );
 
@@ -1310,6 +2753,17 @@ mod tests {
             accounts_data_slice: Vec::new(),
             ping: None,
             from_slot: None,
+            include_created_at: None,
+            include_commitment_slot: None,
+            include_resolved_accounts: None,
+            include_ping_stats: None,
+            preset: None,
+            preset_params: HashMap::new(),
+            seen_signatures_bloom: None,
+            votes: HashMap::new(),
+            stats: HashMap::new(),
+            startup_accounts: None,
+            on_lag: None,
         };
         let mut limit = FilterLimits::default();
         limit.transactions.any = false;
@@ -1336,6 +2790,17 @@ mod tests {
                 account_include,
                 account_exclude: vec![],
                 account_required: vec![],
+                ordered_per_slot: None,
+                include_logs: None,
+                include_token_balances: None,
+                include_inner_instructions: None,
+                include_return_data: None,
+                include_rewards: None,
+                include_loaded_addresses: None,
+                transfer_lamports_min: None,
+                transfer_include_inner: None,
+                transfer_balance_fallback: None,
+                signatures: vec![],
             },
         );
 
@@ -1351,6 +2816,17 @@ mod tests {
             accounts_data_slice: Vec::new(),
             ping: None,
             from_slot: None,
+            include_created_at: None,
+            include_commitment_slot: None,
+            include_resolved_accounts: None,
+            include_ping_stats: None,
+            preset: None,
+            preset_params: HashMap::new(),
+            seen_signatures_bloom: None,
+            votes: HashMap::new(),
+            stats: HashMap::new(),
+            startup_accounts: None,
+            on_lag: None,
         };
         let limit = FilterLimits::default();
         let filter = Filter::new(&config, &limit, &mut create_filter_names()).unwrap();
@@ -1401,6 +2877,17 @@ mod tests {
                 account_include,
                 account_exclude: vec![],
                 account_required: vec![],
+                ordered_per_slot: None,
+                include_logs: None,
+                include_token_balances: None,
+                include_inner_instructions: None,
+                include_return_data: None,
+                include_rewards: None,
+                include_loaded_addresses: None,
+                transfer_lamports_min: None,
+                transfer_include_inner: None,
+                transfer_balance_fallback: None,
+                signatures: vec![],
             },
         );
 
@@ -1416,6 +2903,17 @@ mod tests {
             accounts_data_slice: Vec::new(),
             ping: None,
             from_slot: None,
+            include_created_at: None,
+            include_commitment_slot: None,
+            include_resolved_accounts: None,
+            include_ping_stats: None,
+            preset: None,
+            preset_params: HashMap::new(),
+            seen_signatures_bloom: None,
+            votes: HashMap::new(),
+            stats: HashMap::new(),
+            startup_accounts: None,
+            on_lag: None,
         };
         let limit = FilterLimits::default();
         let filter = Filter::new(&config, &limit, &mut create_filter_names()).unwrap();
@@ -1466,6 +2964,17 @@ mod tests {
                 account_include: vec![],
                 account_exclude,
                 account_required: vec![],
+                ordered_per_slot: None,
+                include_logs: None,
+                include_token_balances: None,
+                include_inner_instructions: None,
+                include_return_data: None,
+                include_rewards: None,
+                include_loaded_addresses: None,
+                transfer_lamports_min: None,
+                transfer_include_inner: None,
+                transfer_balance_fallback: None,
+                signatures: vec![],
             },
         );
 
@@ -1481,6 +2990,17 @@ mod tests {
             accounts_data_slice: Vec::new(),
             ping: None,
             from_slot: None,
+            include_created_at: None,
+            include_commitment_slot: None,
+            include_resolved_accounts: None,
+            include_ping_stats: None,
+            preset: None,
+            preset_params: HashMap::new(),
+            seen_signatures_bloom: None,
+            votes: HashMap::new(),
+            stats: HashMap::new(),
+            startup_accounts: None,
+            on_lag: None,
         };
         let limit = FilterLimits::default();
         let filter = Filter::new(&config, &limit, &mut create_filter_names()).unwrap();
@@ -1517,6 +3037,17 @@ mod tests {
                 account_include,
                 account_exclude: vec![],
                 account_required,
+                ordered_per_slot: None,
+                include_logs: None,
+                include_token_balances: None,
+                include_inner_instructions: None,
+                include_return_data: None,
+                include_rewards: None,
+                include_loaded_addresses: None,
+                transfer_lamports_min: None,
+                transfer_include_inner: None,
+                transfer_balance_fallback: None,
+                signatures: vec![],
             },
         );
 
@@ -1532,6 +3063,17 @@ mod tests {
             accounts_data_slice: Vec::new(),
             ping: None,
             from_slot: None,
+            include_created_at: None,
+            include_commitment_slot: None,
+            include_resolved_accounts: None,
+            include_ping_stats: None,
+            preset: None,
+            preset_params: HashMap::new(),
+            seen_signatures_bloom: None,
+            votes: HashMap::new(),
+            stats: HashMap::new(),
+            startup_accounts: None,
+            on_lag: None,
         };
         let limit = FilterLimits::default();
         let filter = Filter::new(&config, &limit, &mut create_filter_names()).unwrap();
@@ -1590,6 +3132,17 @@ mod tests {
                 account_include,
                 account_exclude: vec![],
                 account_required,
+                ordered_per_slot: None,
+                include_logs: None,
+                include_token_balances: None,
+                include_inner_instructions: None,
+                include_return_data: None,
+                include_rewards: None,
+                include_loaded_addresses: None,
+                transfer_lamports_min: None,
+                transfer_include_inner: None,
+                transfer_balance_fallback: None,
+                signatures: vec![],
             },
         );
 
@@ -1605,6 +3158,17 @@ mod tests {
             accounts_data_slice: Vec::new(),
             ping: None,
             from_slot: None,
+            include_created_at: None,
+            include_commitment_slot: None,
+            include_resolved_accounts: None,
+            include_ping_stats: None,
+            preset: None,
+            preset_params: HashMap::new(),
+            seen_signatures_bloom: None,
+            votes: HashMap::new(),
+            stats: HashMap::new(),
+            startup_accounts: None,
+            on_lag: None,
         };
         let limit = FilterLimits::default();
         let filter = Filter::new(&config, &limit, &mut create_filter_names()).unwrap();
@@ -1616,4 +3180,2277 @@ mod tests {
             assert!(message.filters.is_empty());
         }
     }
+
+    #[test]
+    fn test_accounts_coalesce_superseded() {
+        let owner = Pubkey::new_unique();
+        let mut accounts = HashMap::new();
+        for (name, coalesce_accounts_per_slot) in [("coalesce", Some(true)), ("all", None)] {
+            accounts.insert(
+                name.to_owned(),
+                SubscribeRequestFilterAccounts {
+                    owner: vec![owner.to_string()],
+                    coalesce_accounts_per_slot,
+                    ..Default::default()
+                },
+            );
+        }
+        let config = SubscribeRequest {
+            accounts,
+            ..Default::default()
+        };
+        let limit = FilterLimits::default();
+        let filter = Filter::new(&config, &limit, &mut create_filter_names()).unwrap();
+        assert!(filter.is_accounts_coalesce());
+
+        let message = create_message_account(Pubkey::new_unique(), owner, 1);
+        let updates = filter.get_updates(&Message::Account(message.clone()), None);
+        assert_eq!(updates.len(), 1);
+        let mut filters = updates[0].filters.clone();
+        filters.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+        assert_eq!(
+            filters,
+            FilteredUpdateFilters::from_vec(vec![
+                FilterName::new("all"),
+                FilterName::new("coalesce")
+            ])
+        );
+
+        let updates = filter.get_updates_superseded(&message);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(
+            updates[0].filters,
+            FilteredUpdateFilters::from_vec(vec![FilterName::new("all")])
+        );
+    }
+
+    fn get_account_filters(filter: &Filter, pubkey: Pubkey, owner: Pubkey) -> Vec<String> {
+        let message = Message::Account(create_message_account(pubkey, owner, 1));
+        let mut names = filter
+            .get_updates(&message, None)
+            .into_iter()
+            .flat_map(|update| {
+                update
+                    .filters
+                    .iter()
+                    .map(|name| name.as_ref().to_owned())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_accounts_exclude() {
+        let owner = Pubkey::new_unique();
+        let hot = Pubkey::new_unique();
+        let hot_owner = Pubkey::new_unique();
+        let config = SubscribeRequest {
+            accounts: [(
+                "owner".to_owned(),
+                SubscribeRequestFilterAccounts {
+                    account: vec![hot.to_string()],
+                    owner: vec![owner.to_string(), hot_owner.to_string()],
+                    account_exclude: vec![hot.to_string()],
+                    owner_exclude: vec![hot_owner.to_string()],
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &config,
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap();
+
+        assert!(get_account_filters(&filter, hot, owner).is_empty());
+        assert!(get_account_filters(&filter, hot, hot_owner).is_empty());
+        assert!(get_account_filters(&filter, Pubkey::new_unique(), hot_owner).is_empty());
+        let other = Pubkey::new_unique();
+        assert!(get_account_filters(&filter, other, owner).is_empty());
+
+        // account list is required, exclude lists don't make it optional
+        let config = SubscribeRequest {
+            accounts: [(
+                "owner".to_owned(),
+                SubscribeRequestFilterAccounts {
+                    owner: vec![owner.to_string()],
+                    account_exclude: vec![hot.to_string()],
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &config,
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap();
+        assert!(get_account_filters(&filter, hot, owner).is_empty());
+        assert_eq!(get_account_filters(&filter, other, owner), vec!["owner"]);
+    }
+
+    #[test]
+    fn test_accounts_exclude_multiple_filters() {
+        let owner = Pubkey::new_unique();
+        let hot = Pubkey::new_unique();
+        let mut accounts = HashMap::new();
+        accounts.insert(
+            "owner".to_owned(),
+            SubscribeRequestFilterAccounts {
+                owner: vec![owner.to_string()],
+                account_exclude: vec![hot.to_string()],
+                ..Default::default()
+            },
+        );
+        accounts.insert(
+            "owner_all".to_owned(),
+            SubscribeRequestFilterAccounts {
+                owner: vec![owner.to_string()],
+                ..Default::default()
+            },
+        );
+        accounts.insert(
+            "hot".to_owned(),
+            SubscribeRequestFilterAccounts {
+                account: vec![hot.to_string()],
+                owner_exclude: vec![owner.to_string()],
+                ..Default::default()
+            },
+        );
+        let config = SubscribeRequest {
+            accounts,
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &config,
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap();
+
+        assert_eq!(get_account_filters(&filter, hot, owner), vec!["owner_all"]);
+        assert_eq!(
+            get_account_filters(&filter, Pubkey::new_unique(), owner),
+            vec!["owner", "owner_all"]
+        );
+        assert_eq!(
+            get_account_filters(&filter, hot, Pubkey::new_unique()),
+            vec!["hot"]
+        );
+    }
+
+    #[test]
+    fn test_accounts_exclude_limits() {
+        let owner = Pubkey::new_unique();
+        let config = SubscribeRequest {
+            accounts: [(
+                "owner".to_owned(),
+                SubscribeRequestFilterAccounts {
+                    owner: vec![owner.to_string()],
+                    account_exclude: (0..3).map(|_| Pubkey::new_unique().to_string()).collect(),
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let mut limit = FilterLimits::default();
+        limit.accounts.account_max = 2;
+        assert!(Filter::new(&config, &limit, &mut create_filter_names()).is_err());
+
+        // clamped exclude list would deliver updates of dropped Pubkeys
+        limit.accounts.over_limit_policy = FilterLimitsOverLimitPolicy::Clamp;
+        assert!(Filter::new(&config, &limit, &mut create_filter_names()).is_err());
+    }
+
+    #[test]
+    fn test_accounts_prefix() {
+        let token = solana_sdk::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+        let mut accounts = HashMap::new();
+        accounts.insert(
+            "token".to_owned(),
+            SubscribeRequestFilterAccounts {
+                owner_prefix: vec!["Tokenkeg".to_owned()],
+                ..Default::default()
+            },
+        );
+        accounts.insert(
+            "token_short".to_owned(),
+            SubscribeRequestFilterAccounts {
+                owner_prefix: vec!["Toke".to_owned(), "1111".to_owned()],
+                ..Default::default()
+            },
+        );
+        accounts.insert(
+            "token_or_list".to_owned(),
+            SubscribeRequestFilterAccounts {
+                owner: vec![system_program::ID.to_string()],
+                owner_prefix: vec!["TokenkegQ".to_owned()],
+                ..Default::default()
+            },
+        );
+        let config = SubscribeRequest {
+            accounts,
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &config,
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap();
+
+        let account = |owner: &Pubkey| {
+            let account = Account::new(42, 0, owner);
+            let account = MessageAccountInfo::from_account(Pubkey::new_unique(), &account, 0, None);
+            let mut names = filter.matches_account(&account);
+            names.sort();
+            names
+        };
+
+        // all matched prefixes of different lengths are reported
+        assert_eq!(account(&token), ["token", "token_or_list", "token_short"]);
+        // prefix or Pubkey from the list of the same filter
+        assert_eq!(
+            account(&system_program::ID),
+            ["token_or_list", "token_short"]
+        );
+        assert!(account(&solana_sdk::vote::program::ID).is_empty());
+    }
+
+    #[test]
+    fn test_accounts_prefix_limits() {
+        let token = solana_sdk::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+        let create_filter = |owner_prefix: &[&str], limits: &FilterLimits| {
+            Filter::new(
+                &SubscribeRequest {
+                    accounts: [(
+                        "prefix".to_owned(),
+                        SubscribeRequestFilterAccounts {
+                            owner_prefix: owner_prefix.iter().map(|s| s.to_string()).collect(),
+                            ..Default::default()
+                        },
+                    )]
+                    .into_iter()
+                    .collect(),
+                    ..Default::default()
+                },
+                limits,
+                &mut create_filter_names(),
+            )
+        };
+
+        // prefix of the rejected Pubkey would bypass the reject list
+        let mut limits = FilterLimits::default();
+        limits.accounts.owner_reject.insert(token);
+        for prefix in ["Toke", "TokenkegQ"] {
+            assert!(matches!(
+                create_filter(&[prefix], &limits),
+                Err(FilterError::LimitsCheck(FilterLimitsCheckError::PubkeyReject { pubkey })) if pubkey == token
+            ));
+        }
+        assert!(create_filter(&["Tokz"], &limits).is_ok());
+
+        // prefixes over the limit are clamped with the policy
+        limits.accounts.prefix_max = 1;
+        assert!(create_filter(&["Tokz", "1111"], &limits).is_err());
+        limits.accounts.over_limit_policy = FilterLimitsOverLimitPolicy::Clamp;
+        let filter = create_filter(&["Tokz", "1111"], &limits).expect("clamped");
+        assert_eq!(
+            filter.get_warnings(),
+            ["filter `prefix`: `owner_prefix` clamped to 1 prefixes, 1 dropped".to_owned()]
+        );
+    }
+
+    #[test]
+    fn test_accounts_pda_data_prefix() {
+        let program_id = Pubkey::new_unique();
+        let discriminator = [1, 2, 3, 4, 5, 6, 7, 8];
+        let pda = |user: &Pubkey| {
+            let (pubkey, bump) =
+                Pubkey::find_program_address(&[b"position", user.as_ref()], &program_id);
+            let mut data = discriminator.to_vec();
+            data.extend_from_slice(user.as_ref());
+            data.push(bump);
+            (pubkey, data)
+        };
+        let config = SubscribeRequest {
+            accounts: [
+                (
+                    "pda".to_owned(),
+                    SubscribeRequestFilterAccounts {
+                        pda: vec![SubscribeRequestFilterAccountsPda {
+                            program_id: program_id.to_string(),
+                            data_prefix: discriminator.to_vec(),
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "pda_or_list".to_owned(),
+                    SubscribeRequestFilterAccounts {
+                        account: vec![system_program::ID.to_string()],
+                        pda: vec![SubscribeRequestFilterAccountsPda {
+                            program_id: program_id.to_string(),
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &config,
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap();
+
+        let account = |pubkey: Pubkey, owner: &Pubkey, data: Vec<u8>| {
+            let mut account = Account::new(42, 0, owner);
+            account.data = data;
+            let account = MessageAccountInfo::from_account(pubkey, &account, 0, None);
+            let mut names = filter.matches_account(&account);
+            names.sort();
+            names
+        };
+
+        let (pubkey, data) = pda(&Pubkey::new_unique());
+        assert_eq!(
+            account(pubkey, &program_id, data.clone()),
+            ["pda", "pda_or_list"]
+        );
+        // other owner or data prefix
+        assert!(account(pubkey, &Pubkey::new_unique(), data.clone()).is_empty());
+        let mut data_other = data.clone();
+        data_other[0] = 0;
+        assert_eq!(account(pubkey, &program_id, data_other), ["pda_or_list"]);
+        // Pubkey from the list of the same filter
+        assert_eq!(
+            account(system_program::ID, &Pubkey::new_unique(), data),
+            ["pda_or_list"]
+        );
+        // address is not verified without templates
+        assert_eq!(filter.take_pda_derivations(), 0);
+
+        // templates are not allowed by default
+        let config = SubscribeRequest {
+            accounts: [(
+                "pda".to_owned(),
+                SubscribeRequestFilterAccounts {
+                    pda: vec![SubscribeRequestFilterAccountsPda {
+                        program_id: program_id.to_string(),
+                        templates: vec![SubscribeRequestFilterAccountsPdaTemplate::default()],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let error = Filter::new(
+            &config,
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap_err();
+        assert!(error.is_permission_denied());
+    }
+
+    #[test]
+    fn test_accounts_pda_templates() {
+        let program_id = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let discriminator = [1, 2, 3, 4, 5, 6, 7, 8];
+        // data: discriminator, user, bump
+        let pda = |user: &Pubkey| {
+            let (pubkey, bump) = Pubkey::find_program_address(
+                &[b"position", user.as_ref(), mint.as_ref()],
+                &program_id,
+            );
+            let mut data = discriminator.to_vec();
+            data.extend_from_slice(user.as_ref());
+            data.push(bump);
+            (pubkey, data)
+        };
+        let create_filter = |bump_offset: Option<u64>, bump_search_max: usize| {
+            let config = SubscribeRequest {
+                accounts: [(
+                    "pda".to_owned(),
+                    SubscribeRequestFilterAccounts {
+                        pda: vec![SubscribeRequestFilterAccountsPda {
+                            program_id: program_id.to_string(),
+                            seed_prefix: b"position".to_vec(),
+                            data_prefix: discriminator.to_vec(),
+                            templates: vec![SubscribeRequestFilterAccountsPdaTemplate {
+                                data_offset: 8,
+                                data_len: 32,
+                                seed_suffix: vec![mint.to_bytes().to_vec()],
+                                bump_offset,
+                            }],
+                        }],
+                        ..Default::default()
+                    },
+                )]
+                .into_iter()
+                .collect(),
+                ..Default::default()
+            };
+            let mut limits = FilterLimits::default();
+            limits.accounts.pda_templates = true;
+            limits.accounts.pda_bump_search_max = bump_search_max;
+            Filter::new(&config, &limits, &mut create_filter_names()).unwrap()
+        };
+        let is_match = |filter: &Filter, pubkey: Pubkey, data: Vec<u8>| {
+            let mut account = Account::new(42, 0, &program_id);
+            account.data = data;
+            let account = MessageAccountInfo::from_account(pubkey, &account, 0, None);
+            !filter.matches_account(&account).is_empty()
+        };
+
+        // bump from the data, one derivation per account update
+        let filter = create_filter(Some(40), 8);
+        for _ in 0..32 {
+            let (pubkey, data) = pda(&Pubkey::new_unique());
+            assert!(is_match(&filter, pubkey, data.clone()));
+            assert_eq!(filter.take_pda_derivations(), 1);
+            // same data, but not derived from it
+            assert!(!is_match(&filter, Pubkey::new_unique(), data.clone()));
+            // seed from the data is changed
+            let (_pubkey, data_other) = pda(&Pubkey::new_unique());
+            assert!(!is_match(&filter, pubkey, data_other));
+            // data is too short for the seed
+            assert!(!is_match(&filter, pubkey, data[..20].to_vec()));
+            assert_eq!(filter.take_pda_derivations(), 2);
+        }
+        assert_eq!(filter.take_pda_derivations(), 0);
+
+        // bump search is limited
+        let filter = create_filter(None, 8);
+        let filter_no_search = create_filter(None, 0);
+        for _ in 0..32 {
+            let (pubkey, data) = pda(&Pubkey::new_unique());
+            let bump = data[40];
+            assert_eq!(is_match(&filter, pubkey, data.clone()), bump >= 248);
+            assert!(!is_match(&filter_no_search, pubkey, data.clone()));
+            assert!(filter.take_pda_derivations() <= 8);
+            assert!(!is_match(&filter, Pubkey::new_unique(), data));
+            assert_eq!(filter.take_pda_derivations(), 8);
+        }
+        assert_eq!(filter_no_search.take_pda_derivations(), 0);
+
+        // too long seed and too many seeds are rejected
+        for template in [
+            SubscribeRequestFilterAccountsPdaTemplate {
+                data_len: 33,
+                ..Default::default()
+            },
+            SubscribeRequestFilterAccountsPdaTemplate {
+                seed_suffix: vec![vec![0; 32]; 14],
+                ..Default::default()
+            },
+        ] {
+            let config = SubscribeRequest {
+                accounts: [(
+                    "pda".to_owned(),
+                    SubscribeRequestFilterAccounts {
+                        pda: vec![SubscribeRequestFilterAccountsPda {
+                            program_id: program_id.to_string(),
+                            templates: vec![template],
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    },
+                )]
+                .into_iter()
+                .collect(),
+                ..Default::default()
+            };
+            let mut limits = FilterLimits::default();
+            limits.accounts.pda_templates = true;
+            assert!(matches!(
+                Filter::new(&config, &limits, &mut create_filter_names()),
+                Err(FilterError::CreateAccountState(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_accounts_pda_derivations_max() {
+        let program_id = Pubkey::new_unique();
+        // every filter costs 4 templates * 8 bumps derivations per account update
+        let create_filter = |filters: usize| {
+            let config = SubscribeRequest {
+                accounts: (0..filters)
+                    .map(|index| {
+                        (
+                            format!("pda{index}"),
+                            SubscribeRequestFilterAccounts {
+                                pda: vec![SubscribeRequestFilterAccountsPda {
+                                    program_id: program_id.to_string(),
+                                    templates: vec![
+                                        SubscribeRequestFilterAccountsPdaTemplate {
+                                            data_len: 32,
+                                            ..Default::default()
+                                        };
+                                        4
+                                    ],
+                                    ..Default::default()
+                                }],
+                                ..Default::default()
+                            },
+                        )
+                    })
+                    .collect(),
+                ..Default::default()
+            };
+            let mut limits = FilterLimits::default();
+            limits.accounts.pda_templates = true;
+            Filter::new(&config, &limits, &mut create_filter_names())
+        };
+
+        assert!(create_filter(2).is_ok());
+        assert!(matches!(
+            create_filter(3),
+            Err(FilterError::LimitsCheck(
+                FilterLimitsCheckError::MaxPdaDerivations {
+                    max: 64,
+                    requested: 96
+                }
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_accounts_origins() {
+        let mut accounts = HashMap::new();
+        accounts.insert(
+            "transaction".to_owned(),
+            SubscribeRequestFilterAccounts {
+                origins: vec![AccountOrigin::Transaction as i32],
+                ..Default::default()
+            },
+        );
+        accounts.insert(
+            "not_transaction".to_owned(),
+            SubscribeRequestFilterAccounts {
+                origins: vec![AccountOrigin::Startup as i32, AccountOrigin::Unknown as i32],
+                ..Default::default()
+            },
+        );
+        accounts.insert("all".to_owned(), SubscribeRequestFilterAccounts::default());
+        let config = SubscribeRequest {
+            accounts,
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &config,
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap();
+
+        for (txn_signature, is_startup, expected) in [
+            (Some(Signature::new_unique()), false, ["all", "transaction"]),
+            (None, true, ["all", "not_transaction"]),
+            (None, false, ["all", "not_transaction"]),
+        ] {
+            let account = Account::new(42, 0, &Pubkey::new_unique());
+            let mut message = MessageAccount::new(
+                MessageAccountInfo::from_account(Pubkey::new_unique(), &account, 0, txn_signature),
+                100,
+            );
+            message.is_startup = is_startup;
+            let updates = filter.get_updates(&Message::Account(message), None);
+            assert_eq!(updates.len(), 1);
+            let mut names = updates[0]
+                .filters
+                .iter()
+                .map(|name| name.as_ref())
+                .collect::<Vec<_>>();
+            names.sort();
+            assert_eq!(names, expected);
+        }
+
+        // unknown origin is rejected
+        let config = SubscribeRequest {
+            accounts: [(
+                "invalid".to_owned(),
+                SubscribeRequestFilterAccounts {
+                    origins: vec![42],
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Filter::new(
+                &config,
+                &FilterLimits::default(),
+                &mut create_filter_names()
+            ),
+            Err(FilterError::InvalidAccountOrigin { origin: 42 })
+        ));
+    }
+
+    #[test]
+    fn test_accounts_lamports_range_executable() {
+        let range = |min, max, below_rent_exempt| SubscribeRequestFilterAccounts {
+            filters: vec![SubscribeRequestFilterAccountsFilter {
+                filter: Some(AccountsFilterDataOneof::LamportsRange(
+                    SubscribeRequestFilterAccountsFilterLamportsRange {
+                        min,
+                        max,
+                        below_rent_exempt,
+                    },
+                )),
+            }],
+            ..Default::default()
+        };
+        let config = SubscribeRequest {
+            accounts: [
+                ("eq".to_owned(), range(Some(100), Some(100), None)),
+                ("range".to_owned(), range(Some(100), Some(200), None)),
+                ("min".to_owned(), range(Some(150), None, None)),
+                ("rent".to_owned(), range(None, None, Some(true))),
+                (
+                    "executable".to_owned(),
+                    SubscribeRequestFilterAccounts {
+                        executable: Some(true),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "max_not_executable".to_owned(),
+                    SubscribeRequestFilterAccounts {
+                        executable: Some(false),
+                        ..range(None, Some(100), None)
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &config,
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap();
+
+        let account = |lamports: u64, space: usize, executable: bool| {
+            let mut account = Account::new(lamports, space, &Pubkey::new_unique());
+            account.executable = executable;
+            let account = MessageAccountInfo::from_account(Pubkey::new_unique(), &account, 0, None);
+            let mut names = filter.matches_account(&account);
+            names.sort();
+            names
+        };
+
+        // bounds are inclusive
+        assert_eq!(account(99, 0, false), ["max_not_executable", "rent"]);
+        assert_eq!(
+            account(100, 0, false),
+            ["eq", "max_not_executable", "range", "rent"]
+        );
+        assert_eq!(account(100, 0, true), ["eq", "executable", "range", "rent"]);
+        assert_eq!(account(200, 0, false), ["min", "range", "rent"]);
+        assert_eq!(account(201, 0, false), ["min", "rent"]);
+
+        // rent-exempt minimum depends on the data size
+        let minimum = Rent::default().minimum_balance(10);
+        assert_eq!(account(minimum - 1, 10, false), ["min", "rent"]);
+        assert_eq!(account(minimum, 10, false), ["min"]);
+        assert_eq!(account(minimum - 1, 0, false), ["min"]);
+
+        // empty range and max less than min are rejected
+        for (min, max, below_rent_exempt) in [
+            (None, None, None),
+            (None, None, Some(false)),
+            (Some(101), Some(100), None),
+        ] {
+            let config = SubscribeRequest {
+                accounts: [("range".to_owned(), range(min, max, below_rent_exempt))]
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            };
+            assert!(matches!(
+                Filter::new(
+                    &config,
+                    &FilterLimits::default(),
+                    &mut create_filter_names()
+                ),
+                Err(FilterError::CreateAccountState(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_accounts_prefix_base58_semantics() {
+        let create_filter = |prefix: &str| {
+            let config = SubscribeRequest {
+                accounts: [(
+                    "prefix".to_owned(),
+                    SubscribeRequestFilterAccounts {
+                        account_prefix: vec![prefix.to_owned()],
+                        ..Default::default()
+                    },
+                )]
+                .into_iter()
+                .collect(),
+                ..Default::default()
+            };
+            Filter::new(
+                &config,
+                &FilterLimits::default(),
+                &mut create_filter_names(),
+            )
+        };
+        let is_match = |filter: &Filter, pubkey: Pubkey| {
+            let account = Account::new(42, 0, &Pubkey::new_unique());
+            let account = MessageAccountInfo::from_account(pubkey, &account, 0, None);
+            !filter.matches_account(&account).is_empty()
+        };
+
+        // leading zero bytes are encoded as `1`, a byte prefix is not a string prefix
+        let filter = create_filter("1111").unwrap();
+        assert!(is_match(&filter, Pubkey::new_from_array([0; 32])));
+        let mut bytes = [0xff; 32];
+        bytes[..3].fill(0);
+        assert!(!is_match(&filter, Pubkey::new_from_array(bytes)));
+        bytes[3] = 0;
+        assert!(is_match(&filter, Pubkey::new_from_array(bytes)));
+
+        // Pubkeys with the same leading byte have different string prefixes
+        let mut bytes = [0; 32];
+        bytes[0] = 0x20;
+        let pubkey_a = Pubkey::new_from_array(bytes);
+        bytes[1..].fill(0xff);
+        let pubkey_b = Pubkey::new_from_array(bytes);
+        assert_eq!(&pubkey_a.to_string()[..4], "39v3");
+        assert_eq!(&pubkey_b.to_string()[..4], "3DpT");
+        let filter = create_filter("39v3").unwrap();
+        assert!(is_match(&filter, pubkey_a));
+        assert!(!is_match(&filter, pubkey_b));
+
+        // matching is the same as a string prefix of the base58 Pubkey
+        let filter = create_filter("4vJ9").unwrap();
+        for _ in 0..1_000 {
+            let pubkey = Pubkey::new_unique();
+            assert_eq!(
+                is_match(&filter, pubkey),
+                pubkey.to_string().starts_with("4vJ9")
+            );
+        }
+
+        // not base58 characters, too short and too long prefixes are rejected
+        for prefix in ["0abc", "Ol1I", "abcd_", "abc"] {
+            assert!(create_filter(prefix).is_err(), "{prefix}");
+        }
+        assert!(create_filter(&"1".repeat(45)).is_err());
+        assert!(create_filter(&"1".repeat(44)).is_ok());
+    }
+
+    #[test]
+    fn test_accounts_txn_signature() {
+        let signature = Signature::new_unique();
+        let owner = Pubkey::new_unique();
+        let mut accounts = HashMap::new();
+        accounts.insert(
+            "signature".to_owned(),
+            SubscribeRequestFilterAccounts {
+                txn_signature: vec![signature.to_string(), Signature::new_unique().to_string()],
+                ..Default::default()
+            },
+        );
+        accounts.insert(
+            "signature_owner".to_owned(),
+            SubscribeRequestFilterAccounts {
+                owner: vec![owner.to_string()],
+                txn_signature: vec![signature.to_string()],
+                ..Default::default()
+            },
+        );
+        let config = SubscribeRequest {
+            accounts,
+            ..Default::default()
+        };
+        let mut limit = FilterLimits::default();
+        limit.accounts.any = false;
+        let mut filter = Filter::new(&config, &limit, &mut create_filter_names()).unwrap();
+
+        let account = |owner: &Pubkey, txn_signature: Option<Signature>| {
+            let account = Account::new(42, 0, owner);
+            let account =
+                MessageAccountInfo::from_account(Pubkey::new_unique(), &account, 0, txn_signature);
+            let mut names = filter.matches_account(&account);
+            names.sort();
+            names
+        };
+
+        // all accounts written by the transaction, PDAs are not known in advance
+        assert_eq!(
+            account(&owner, Some(signature)),
+            ["signature", "signature_owner"]
+        );
+        assert_eq!(
+            account(&Pubkey::new_unique(), Some(signature)),
+            ["signature"]
+        );
+        assert_eq!(
+            account(&Pubkey::new_unique(), Some(signature)),
+            ["signature"]
+        );
+        assert!(account(&owner, Some(Signature::new_unique())).is_empty());
+        assert!(account(&owner, None).is_empty());
+
+        assert!(filter.is_accounts_txn_signature(&signature));
+        filter.remove_accounts_txn_signature(&signature);
+        assert!(!filter.is_accounts_txn_signature(&signature));
+        let account = Account::new(42, 0, &owner);
+        let account =
+            MessageAccountInfo::from_account(Pubkey::new_unique(), &account, 0, Some(signature));
+        assert!(filter.matches_account(&account).is_empty());
+
+        limit.accounts.txn_signature_max = 1;
+        assert!(Filter::new(&config, &limit, &mut create_filter_names()).is_err());
+    }
+
+    #[test]
+    fn test_include_created_at() {
+        let owner = Pubkey::new_unique();
+        let message = Message::Account(create_message_account(Pubkey::new_unique(), owner, 1));
+        for (include_created_at, expected) in
+            [(None, true), (Some(true), true), (Some(false), false)]
+        {
+            let mut accounts = HashMap::new();
+            accounts.insert(
+                "owner".to_owned(),
+                SubscribeRequestFilterAccounts {
+                    owner: vec![owner.to_string()],
+                    ..Default::default()
+                },
+            );
+            let config = SubscribeRequest {
+                accounts,
+                include_created_at,
+                ..Default::default()
+            };
+            let limit = FilterLimits::default();
+            let filter = Filter::new(&config, &limit, &mut create_filter_names()).unwrap();
+            let updates = filter.get_updates(&message, None);
+            assert_eq!(updates.len(), 1);
+            assert_eq!(updates[0].created_at.is_some(), expected);
+            assert_eq!(
+                updates[0].as_subscribe_update().created_at.is_some(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_include_commitment_slot() {
+        let owner = Pubkey::new_unique();
+        let mut message = create_message_account(Pubkey::new_unique(), owner, 1);
+        message.confirmed_at_slot = Some(100);
+        message.finalized_at_slot = Some(101);
+        let message = Message::Account(message);
+        for (include_commitment_slot, expected) in [
+            (None, (None, None)),
+            (Some(false), (None, None)),
+            (Some(true), (Some(100), Some(101))),
+        ] {
+            let config = SubscribeRequest {
+                accounts: HashMap::from([(
+                    "owner".to_owned(),
+                    SubscribeRequestFilterAccounts {
+                        owner: vec![owner.to_string()],
+                        ..Default::default()
+                    },
+                )]),
+                include_commitment_slot,
+                ..Default::default()
+            };
+            let filter = Filter::new(
+                &config,
+                &FilterLimits::default(),
+                &mut create_filter_names(),
+            )
+            .unwrap();
+            let updates = filter.get_updates(&message, Some(CommitmentLevel::Finalized));
+            assert_eq!(updates.len(), 1);
+            match &updates[0].message {
+                FilteredUpdateOneof::Account(msg) => {
+                    assert_eq!((msg.confirmed_at_slot, msg.finalized_at_slot), expected)
+                }
+                update => panic!("unexpected update: {update:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_accounts_finalized_with_proof() {
+        let owner = Pubkey::new_unique();
+        let create_config = |commitment: CommitmentLevelProto| SubscribeRequest {
+            accounts: HashMap::from([
+                (
+                    "proof".to_owned(),
+                    SubscribeRequestFilterAccounts {
+                        owner: vec![owner.to_string()],
+                        finalized_with_proof: Some(true),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "owner".to_owned(),
+                    SubscribeRequestFilterAccounts {
+                        owner: vec![owner.to_string()],
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            commitment: Some(commitment as i32),
+            ..Default::default()
+        };
+
+        let error = Filter::new(
+            &create_config(CommitmentLevelProto::Confirmed),
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap_err();
+        assert!(matches!(error, FilterError::FinalizedWithProofCommitment));
+
+        let filter = Filter::new(
+            &create_config(CommitmentLevelProto::Finalized),
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap();
+
+        // write which was not flushed by the finalized status (startup account)
+        let mut message = create_message_account(Pubkey::new_unique(), owner, 1);
+        let updates = filter.get_updates(
+            &Message::Account(message.clone()),
+            Some(CommitmentLevel::Finalized),
+        );
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].filters.as_slice(), [FilterName::new("owner")]);
+
+        // finalized slot is kept without `include_commitment_slot`
+        message.confirmed_at_slot = Some(100);
+        message.finalized_at_slot = Some(101);
+        let updates =
+            filter.get_updates(&Message::Account(message), Some(CommitmentLevel::Finalized));
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].filters.len(), 2);
+        assert!(updates[0].filters.contains(&FilterName::new("proof")));
+        match &updates[0].message {
+            FilteredUpdateOneof::Account(msg) => {
+                assert_eq!(
+                    (msg.confirmed_at_slot, msg.finalized_at_slot),
+                    (Some(100), Some(101))
+                )
+            }
+            update => panic!("unexpected update: {update:?}"),
+        }
+    }
+
+    #[test]
+    fn test_accounts_rollback() {
+        let owner = Pubkey::new_unique();
+        let config = SubscribeRequest {
+            accounts: HashMap::from([
+                (
+                    "proof".to_owned(),
+                    SubscribeRequestFilterAccounts {
+                        owner: vec![owner.to_string()],
+                        finalized_with_proof: Some(true),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "all".to_owned(),
+                    SubscribeRequestFilterAccounts {
+                        owner: vec![owner.to_string()],
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            commitment: Some(CommitmentLevelProto::Finalized as i32),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &config,
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap();
+
+        let matched = Pubkey::new_unique();
+        let message = MessageRollback::new(
+            100,
+            101,
+            vec![
+                create_message_account(matched, owner, 1),
+                create_message_account(Pubkey::new_unique(), Pubkey::new_unique(), 2),
+            ],
+        );
+        let updates = filter.get_updates(
+            &Message::Rollback(Arc::new(message)),
+            Some(CommitmentLevel::Finalized),
+        );
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].filters.as_slice(), [FilterName::new("proof")]);
+        match &updates[0].message {
+            FilteredUpdateOneof::Rollback(msg) => {
+                assert_eq!((msg.slot, msg.finalized_slot), (100, 101));
+                assert_eq!(msg.pubkeys, vec![matched.to_bytes().to_vec()]);
+            }
+            update => panic!("unexpected update: {update:?}"),
+        }
+
+        // nothing is sent without matched accounts
+        let message = MessageRollback::new(
+            100,
+            101,
+            vec![create_message_account(
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                1,
+            )],
+        );
+        let updates = filter.get_updates(
+            &Message::Rollback(Arc::new(message)),
+            Some(CommitmentLevel::Finalized),
+        );
+        assert!(updates.is_empty());
+    }
+
+    #[test]
+    fn test_include_resolved_accounts() {
+        let keypair = Keypair::new();
+        let message =
+            Message::Transaction(create_message_transaction(&keypair, vec![keypair.pubkey()]));
+        for (include_resolved_accounts, expected) in
+            [(None, false), (Some(false), false), (Some(true), true)]
+        {
+            let config = SubscribeRequest {
+                transactions: HashMap::from([(
+                    "all".to_owned(),
+                    SubscribeRequestFilterTransactions::default(),
+                )]),
+                include_resolved_accounts,
+                ..Default::default()
+            };
+            let filter = Filter::new(
+                &config,
+                &FilterLimits::default(),
+                &mut create_filter_names(),
+            )
+            .unwrap();
+            let updates = filter.get_updates(&message, None);
+            assert_eq!(updates.len(), 1);
+            match &updates[0].message {
+                FilteredUpdateOneof::Transaction(msg) => {
+                    assert_eq!(msg.resolved_accounts, expected)
+                }
+                update => panic!("unexpected update: {update:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_on_lag() {
+        for (on_lag, expected) in [
+            (None, Some(false)),
+            (Some(OnLag::Disconnect as i32), Some(false)),
+            (Some(OnLag::Skip as i32), Some(true)),
+            (Some(42), None),
+        ] {
+            let config = SubscribeRequest {
+                on_lag,
+                ..Default::default()
+            };
+            let filter = Filter::new(
+                &config,
+                &FilterLimits::default(),
+                &mut create_filter_names(),
+            );
+            assert_eq!(filter.ok().map(|filter| filter.is_lag_skip()), expected);
+        }
+    }
+
+    fn create_access_request(
+        commitment: CommitmentLevelProto,
+        accounts: bool,
+        blocks_meta: bool,
+    ) -> SubscribeRequest {
+        let mut config = SubscribeRequest {
+            commitment: Some(commitment as i32),
+            ..Default::default()
+        };
+        if accounts {
+            config.accounts.insert(
+                "accounts".to_owned(),
+                SubscribeRequestFilterAccounts::default(),
+            );
+        }
+        if blocks_meta {
+            config.blocks_meta.insert(
+                "blocks_meta".to_owned(),
+                SubscribeRequestFilterBlocksMeta::default(),
+            );
+        }
+        config
+    }
+
+    fn check_access(
+        limit: &FilterLimits,
+        caller: &FilterCaller,
+        config: &SubscribeRequest,
+    ) -> Result<(), String> {
+        FilterBuilder::new(limit)
+            .with_caller(caller)
+            .build(config, &mut create_filter_names())
+            .map(|_filter| ())
+            .map_err(|error| {
+                assert!(error.is_permission_denied(), "unexpected error: {error}");
+                error.to_string()
+            })
+    }
+
+    #[test]
+    fn test_access_default_allow() {
+        let mut limit = FilterLimits::default();
+        limit.access.rules.push(FilterLimitsAccessRule {
+            cidr: Some("10.0.0.0/8".parse().unwrap()),
+            allow: FilterLimitsAccessAllow {
+                commitments: None,
+                filters: Some([FilterAccessKind::BlocksMeta].into_iter().collect()),
+            },
+            ..Default::default()
+        });
+
+        let anonymous = FilterCaller::default();
+        let restricted = FilterCaller {
+            x_token: None,
+            remote_ip: Some("10.1.2.3".parse().unwrap()),
+        };
+        let other = FilterCaller {
+            x_token: None,
+            remote_ip: Some("192.168.0.1".parse().unwrap()),
+        };
+
+        let config = create_access_request(CommitmentLevelProto::Processed, true, true);
+        assert_eq!(check_access(&limit, &anonymous, &config), Ok(()));
+        assert_eq!(check_access(&limit, &other, &config), Ok(()));
+        assert_eq!(
+            check_access(&limit, &restricted, &config),
+            Err("Filter `accounts` is not allowed".to_owned())
+        );
+
+        let config = create_access_request(CommitmentLevelProto::Processed, false, true);
+        assert_eq!(check_access(&limit, &restricted, &config), Ok(()));
+    }
+
+    #[test]
+    fn test_access_default_deny() {
+        let mut limit = FilterLimits::default();
+        limit.access.default = FilterLimitsAccessAllow {
+            commitments: Some(Default::default()),
+            filters: Some(Default::default()),
+        };
+        limit.access.rules.push(FilterLimitsAccessRule {
+            x_token: Some("premium".to_owned()),
+            ..Default::default()
+        });
+        limit.access.rules.push(FilterLimitsAccessRule {
+            x_token: Some("basic".to_owned()),
+            allow: FilterLimitsAccessAllow {
+                commitments: Some(
+                    [CommitmentLevel::Confirmed, CommitmentLevel::Finalized]
+                        .into_iter()
+                        .collect(),
+                ),
+                filters: None,
+            },
+            ..Default::default()
+        });
+
+        let caller = |x_token: &str| FilterCaller {
+            x_token: Some(x_token.to_owned()),
+            remote_ip: None,
+        };
+
+        let config = create_access_request(CommitmentLevelProto::Processed, true, false);
+        assert_eq!(check_access(&limit, &caller("premium"), &config), Ok(()));
+        assert_eq!(
+            check_access(&limit, &caller("basic"), &config),
+            Err("Commitment `processed` is not allowed".to_owned())
+        );
+        assert!(check_access(&limit, &caller("unknown"), &config).is_err());
+        assert!(check_access(&limit, &FilterCaller::default(), &config).is_err());
+
+        let config = create_access_request(CommitmentLevelProto::Confirmed, true, true);
+        assert_eq!(check_access(&limit, &caller("basic"), &config), Ok(()));
+
+        // ping is always allowed
+        let config = SubscribeRequest {
+            ping: Some(SubscribeRequestPing { id: 1 }),
+            ..Default::default()
+        };
+        assert_eq!(
+            check_access(&limit, &FilterCaller::default(), &config),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_commitment_default_allowed() {
+        let mut limits = FilterLimits::default();
+        limits.default_commitment = CommitmentLevel::Confirmed;
+        limits.allowed_commitments = Some(
+            [CommitmentLevel::Confirmed, CommitmentLevel::Finalized]
+                .into_iter()
+                .collect(),
+        );
+        let create_filter = |commitment: Option<CommitmentLevelProto>| {
+            let config = SubscribeRequest {
+                commitment: commitment.map(|commitment| commitment as i32),
+                ..Default::default()
+            };
+            Filter::new(&config, &limits, &mut create_filter_names())
+        };
+
+        // omitted commitment is replaced by the default
+        let filter = create_filter(None).unwrap();
+        assert_eq!(filter.get_commitment_level(), CommitmentLevel::Confirmed);
+        let filter = create_filter(Some(CommitmentLevelProto::Finalized)).unwrap();
+        assert_eq!(filter.get_commitment_level(), CommitmentLevel::Finalized);
+
+        // not supported commitment is rejected for every caller, error names supported ones
+        let error = create_filter(Some(CommitmentLevelProto::Processed)).unwrap_err();
+        assert!(!error.is_permission_denied());
+        assert_eq!(
+            error.to_string(),
+            "Commitment `processed` is not supported, supported: confirmed, finalized"
+        );
+
+        // processed by default
+        let filter = Filter::new(
+            &SubscribeRequest::default(),
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap();
+        assert_eq!(filter.get_commitment_level(), CommitmentLevel::Processed);
+    }
+
+    #[test]
+    fn test_slots_statuses() {
+        let all = [
+            SlotStatus::Processed,
+            SlotStatus::Confirmed,
+            SlotStatus::Finalized,
+            SlotStatus::FirstShredReceived,
+            SlotStatus::Completed,
+            SlotStatus::CreatedBank,
+            SlotStatus::Dead,
+        ];
+        let cases = [
+            // default: only commitment statuses
+            (
+                None,
+                None,
+                vec![],
+                CommitmentLevel::Processed,
+                vec![
+                    SlotStatus::Processed,
+                    SlotStatus::Confirmed,
+                    SlotStatus::Finalized,
+                ],
+            ),
+            // interslot updates
+            (
+                None,
+                Some(true),
+                vec![],
+                CommitmentLevel::Processed,
+                all.to_vec(),
+            ),
+            // filter by commitment
+            (
+                Some(true),
+                None,
+                vec![],
+                CommitmentLevel::Confirmed,
+                vec![SlotStatus::Confirmed],
+            ),
+            // explicit statuses, regardless of the stream commitment
+            (
+                None,
+                None,
+                vec![
+                    SlotStatusProto::SlotConfirmed,
+                    SlotStatusProto::SlotFinalized,
+                ],
+                CommitmentLevel::Processed,
+                vec![SlotStatus::Confirmed, SlotStatus::Finalized],
+            ),
+            // explicit statuses include interslot statuses
+            (
+                None,
+                None,
+                vec![SlotStatusProto::SlotDead],
+                CommitmentLevel::Finalized,
+                vec![SlotStatus::Dead],
+            ),
+            // explicit statuses combined with filter by commitment
+            (
+                Some(true),
+                None,
+                vec![
+                    SlotStatusProto::SlotConfirmed,
+                    SlotStatusProto::SlotFinalized,
+                ],
+                CommitmentLevel::Finalized,
+                vec![SlotStatus::Finalized],
+            ),
+        ];
+
+        for (filter_by_commitment, interslot_updates, statuses, commitment, expected) in cases {
+            let config = SubscribeRequest {
+                slots: [(
+                    "slots".to_owned(),
+                    SubscribeRequestFilterSlots {
+                        filter_by_commitment,
+                        interslot_updates,
+                        statuses: statuses.into_iter().map(|status| status as i32).collect(),
+                    },
+                )]
+                .into_iter()
+                .collect(),
+                ..Default::default()
+            };
+            let limit = FilterLimits::default();
+            let filter = Filter::new(&config, &limit, &mut create_filter_names()).unwrap();
+
+            let received = all
+                .into_iter()
+                .filter(|status| {
+                    let message = Message::Slot(MessageSlot {
+                        slot: 42,
+                        parent: None,
+                        status: *status,
+                        dead_error: None,
+                        created_at: Timestamp::from(SystemTime::now()),
+                    });
+                    !filter.get_updates(&message, Some(commitment)).is_empty()
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(received, expected);
+        }
+
+        let config = SubscribeRequest {
+            slots: [(
+                "slots".to_owned(),
+                SubscribeRequestFilterSlots {
+                    statuses: vec![42],
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let limit = FilterLimits::default();
+        assert!(Filter::new(&config, &limit, &mut create_filter_names()).is_err());
+    }
+
+    #[test]
+    fn test_accounts_over_limit_policy() {
+        let keys = (0..5).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+        let config = SubscribeRequest {
+            accounts: [(
+                "accounts".to_owned(),
+                SubscribeRequestFilterAccounts {
+                    account: keys.iter().map(|key| key.to_string()).collect(),
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let create_filter = |policy| {
+            let mut limit = FilterLimits::default();
+            limit.accounts.account_max = 2;
+            limit.accounts.over_limit_policy = policy;
+            Filter::new(&config, &limit, &mut create_filter_names())
+        };
+
+        let error = create_filter(FilterLimitsOverLimitPolicy::Reject).unwrap_err();
+        assert_eq!(error.shard_hint(), None);
+
+        let error = create_filter(FilterLimitsOverLimitPolicy::ShardHint).unwrap_err();
+        assert_eq!(error.shard_hint(), Some((2, 3)));
+
+        let filter = create_filter(FilterLimitsOverLimitPolicy::Clamp).unwrap();
+        assert_eq!(
+            filter.get_warnings(),
+            ["filter `accounts`: `account` clamped to 2 Pubkeys, 3 dropped".to_owned()]
+        );
+        for (index, key) in keys.iter().enumerate() {
+            let message = Message::Account(create_message_account(*key, Pubkey::new_unique(), 0));
+            let updates = filter.get_updates(&message, None);
+            assert_eq!(updates.len(), usize::from(index < 2));
+        }
+
+        let config = SubscribeRequest::default();
+        let mut limit = FilterLimits::default();
+        limit.accounts.over_limit_policy = FilterLimitsOverLimitPolicy::Clamp;
+        let filter = Filter::new(&config, &limit, &mut create_filter_names()).unwrap();
+        assert!(filter.get_warnings().is_empty());
+    }
+
+    #[test]
+    fn test_accounts_owners() {
+        let owners = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let create_filter = |filters: &[(&str, &[Pubkey], &[Pubkey])]| {
+            let config = SubscribeRequest {
+                accounts: filters
+                    .iter()
+                    .map(|(name, account, owner)| {
+                        (
+                            (*name).to_owned(),
+                            SubscribeRequestFilterAccounts {
+                                account: account.iter().map(|key| key.to_string()).collect(),
+                                owner: owner.iter().map(|key| key.to_string()).collect(),
+                                ..Default::default()
+                            },
+                        )
+                    })
+                    .collect(),
+                ..Default::default()
+            };
+            Filter::new(
+                &config,
+                &FilterLimits::default(),
+                &mut create_filter_names(),
+            )
+            .unwrap()
+        };
+
+        let filter = create_filter(&[]);
+        assert_eq!(filter.get_accounts_owners(), Some(vec![]));
+
+        let filter = create_filter(&[("a", &[], &owners[..1]), ("b", &[], &owners[..1])]);
+        assert_eq!(filter.get_accounts_owners(), Some(vec![owners[0]]));
+
+        let filter = create_filter(&[("a", &[Pubkey::new_unique()], &owners)]);
+        let mut filter_owners = filter.get_accounts_owners().expect("owner-scoped");
+        filter_owners.sort();
+        let mut expected = owners.to_vec();
+        expected.sort();
+        assert_eq!(filter_owners, expected);
+
+        let filter = create_filter(&[("a", &[], &owners[..1]), ("b", &[], &[])]);
+        assert_eq!(filter.get_accounts_owners(), None);
+
+        let filter = create_filter(&[("a", &[Pubkey::new_unique()], &[])]);
+        assert_eq!(filter.get_accounts_owners(), None);
+    }
+
+    #[test]
+    fn test_matches_transaction() {
+        let keypair = Keypair::new();
+        let [key_a, key_b] = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let config = SubscribeRequest {
+            transactions: [(
+                "include_b".to_owned(),
+                SubscribeRequestFilterTransactions {
+                    account_include: vec![key_b.to_string()],
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            transactions_status: [(
+                "required_a".to_owned(),
+                SubscribeRequestFilterTransactions {
+                    account_required: vec![key_a.to_string()],
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &config,
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap();
+
+        let create_transaction = |loaded_writable: Vec<Pubkey>| {
+            let message = SolMessage {
+                header: MessageHeader {
+                    num_required_signatures: 1,
+                    ..MessageHeader::default()
+                },
+                account_keys: vec![keypair.pubkey(), key_a],
+                ..SolMessage::default()
+            };
+            let transaction = Transaction::new(&[&keypair], message, Hash::default());
+            let meta = TransactionStatusMeta {
+                loaded_addresses: LoadedAddresses {
+                    writable: loaded_writable,
+                    readonly: vec![],
+                },
+                ..Default::default()
+            };
+            MessageTransactionInfo::from_transaction(&transaction.into(), &meta, false, 0)
+        };
+
+        // `key_b` is loaded from lookup table
+        let transaction = create_transaction(vec![key_b]);
+        assert!(transaction.account_keys.contains(&key_b));
+        assert_eq!(
+            transaction.transaction.signatures,
+            [transaction.signature.as_ref().to_vec()]
+        );
+        assert_eq!(
+            filter.matches_transaction(&transaction),
+            ["include_b", "required_a"]
+        );
+        assert_eq!(
+            filter.matches_transaction(&create_transaction(vec![])),
+            ["required_a"]
+        );
+    }
+
+    #[test]
+    fn test_transactions_signatures() {
+        let keypair = Keypair::new();
+        let create_transaction = |keypair: &Keypair| {
+            let message = SolMessage {
+                header: MessageHeader {
+                    num_required_signatures: 1,
+                    ..MessageHeader::default()
+                },
+                account_keys: vec![keypair.pubkey()],
+                ..SolMessage::default()
+            };
+            let transaction = Transaction::new(&[keypair], message, Hash::default());
+            MessageTransactionInfo::from_transaction(
+                &transaction.into(),
+                &TransactionStatusMeta::default(),
+                false,
+                0,
+            )
+        };
+        let transaction = create_transaction(&keypair);
+        let other = create_transaction(&Keypair::new());
+
+        let config = SubscribeRequest {
+            transactions: [(
+                "signatures".to_owned(),
+                SubscribeRequestFilterTransactions {
+                    signatures: vec![
+                        transaction.signature.to_string(),
+                        Signature::new_unique().to_string(),
+                    ],
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            transactions_status: [(
+                "all".to_owned(),
+                SubscribeRequestFilterTransactions::default(),
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let mut limit = FilterLimits::default();
+        limit.transactions.any = false;
+        limit.transactions.signatures_expire_slots = 10;
+        let mut filter_names = create_filter_names();
+        let mut filter = Filter::new(&config, &limit, &mut filter_names).unwrap();
+        assert_eq!(filter.get_transactions_signatures_expire_slots(), Some(10));
+
+        assert_eq!(
+            filter.matches_transaction(&transaction),
+            ["signatures", "all"]
+        );
+        assert_eq!(filter.matches_transaction(&other), ["all"]);
+
+        // only filters with the signature are checked
+        let signatures =
+            FilteredUpdateFilters::from_iter([filter_names.get("signatures").unwrap()]);
+        let all = FilteredUpdateFilters::from_iter([filter_names.get("all").unwrap()]);
+        assert!(filter.is_transactions_signature(&transaction.signature, &signatures));
+        assert!(!filter.is_transactions_signature(&transaction.signature, &all));
+
+        // filter with removed signatures doesn't match anything
+        filter.remove_transactions_signature(&transaction.signature);
+        assert!(!filter.is_transactions_signature(&transaction.signature, &signatures));
+        assert_eq!(filter.matches_transaction(&transaction), ["all"]);
+        assert_eq!(filter.get_transactions_signatures_expire_slots(), Some(10));
+        filter.clear_transactions_signatures();
+        assert_eq!(filter.get_transactions_signatures_expire_slots(), None);
+
+        // new filter replaces the set
+        let filter = Filter::new(&config, &limit, &mut filter_names).unwrap();
+        assert!(filter.is_transactions_signature(&transaction.signature, &signatures));
+
+        limit.transactions.signatures_max = 1;
+        assert!(Filter::new(&config, &limit, &mut filter_names).is_err());
+        limit.transactions.signatures_max = 2;
+        let config = SubscribeRequest {
+            transactions: [(
+                "signatures".to_owned(),
+                SubscribeRequestFilterTransactions {
+                    signatures: vec!["invalid".to_owned()],
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Filter::new(&config, &limit, &mut filter_names),
+            Err(FilterError::InvalidSignature(_))
+        ));
+    }
+
+    #[test]
+    fn test_transactions_loaded_addresses() {
+        let keypair = Keypair::new();
+        let [key_static, key_loaded] = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let create_filter = |include_loaded_addresses: Option<bool>| {
+            let create = |account_include: &[Pubkey],
+                          account_exclude: &[Pubkey],
+                          account_required: &[Pubkey]| {
+                SubscribeRequestFilterTransactions {
+                    account_include: account_include.iter().map(|k| k.to_string()).collect(),
+                    account_exclude: account_exclude.iter().map(|k| k.to_string()).collect(),
+                    account_required: account_required.iter().map(|k| k.to_string()).collect(),
+                    include_loaded_addresses,
+                    ..Default::default()
+                }
+            };
+            let config = SubscribeRequest {
+                transactions: [
+                    ("include".to_owned(), create(&[key_loaded], &[], &[])),
+                    ("exclude".to_owned(), create(&[], &[key_loaded], &[])),
+                    (
+                        "required".to_owned(),
+                        create(&[], &[], &[key_static, key_loaded]),
+                    ),
+                ]
+                .into_iter()
+                .collect(),
+                ..Default::default()
+            };
+            Filter::new(
+                &config,
+                &FilterLimits::default(),
+                &mut create_filter_names(),
+            )
+            .unwrap()
+        };
+
+        // `key_loaded` is mentioned only through the lookup table
+        let create_transaction = |writable: bool| {
+            let message = v0::Message {
+                header: MessageHeader {
+                    num_required_signatures: 1,
+                    ..MessageHeader::default()
+                },
+                account_keys: vec![keypair.pubkey(), key_static],
+                recent_blockhash: Hash::default(),
+                instructions: vec![],
+                address_table_lookups: vec![MessageAddressTableLookup {
+                    account_key: Pubkey::new_unique(),
+                    writable_indexes: if writable { vec![0] } else { vec![] },
+                    readonly_indexes: if writable { vec![] } else { vec![0] },
+                }],
+            };
+            let transaction =
+                VersionedTransaction::try_new(VersionedMessage::V0(message), &[&keypair]).unwrap();
+            let meta = TransactionStatusMeta {
+                loaded_addresses: if writable {
+                    LoadedAddresses {
+                        writable: vec![key_loaded],
+                        readonly: vec![],
+                    }
+                } else {
+                    LoadedAddresses {
+                        writable: vec![],
+                        readonly: vec![key_loaded],
+                    }
+                },
+                ..Default::default()
+            };
+            MessageTransactionInfo::from_transaction(&transaction, &meta, false, 0)
+        };
+        let matches = |filter: &Filter, transaction: &MessageTransactionInfo| {
+            let mut names = filter.matches_transaction(transaction);
+            names.sort();
+            names
+        };
+
+        for writable in [true, false] {
+            let transaction = create_transaction(writable);
+            assert!(!transaction.static_account_keys().contains(&key_loaded));
+
+            for include_loaded_addresses in [None, Some(true)] {
+                let filter = create_filter(include_loaded_addresses);
+                assert_eq!(matches(&filter, &transaction), ["include", "required"]);
+            }
+
+            let filter = create_filter(Some(false));
+            assert_eq!(matches(&filter, &transaction), ["exclude"]);
+        }
+    }
+
+    #[test]
+    fn test_transactions_transfer_lamports_min() {
+        let keypair = Keypair::new();
+        let to = Pubkey::new_unique();
+        let lamports_min = 1_000;
+        let config = SubscribeRequest {
+            transactions: [
+                ("top", None, None),
+                ("inner", Some(true), None),
+                ("balance", None, Some(true)),
+            ]
+            .into_iter()
+            .map(
+                |(name, transfer_include_inner, transfer_balance_fallback)| {
+                    (
+                        name.to_owned(),
+                        SubscribeRequestFilterTransactions {
+                            transfer_lamports_min: Some(lamports_min),
+                            transfer_include_inner,
+                            transfer_balance_fallback,
+                            ..Default::default()
+                        },
+                    )
+                },
+            )
+            .collect(),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &config,
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap();
+        let matches = |transaction: &MessageTransactionInfo| {
+            let mut names = filter.matches_transaction(transaction);
+            names.sort();
+            names
+        };
+
+        // legacy message with `Transfer`
+        let create_legacy = |lamports: u64| {
+            let message = SolMessage::new(
+                &[system_instruction::transfer(
+                    &keypair.pubkey(),
+                    &to,
+                    lamports,
+                )],
+                Some(&keypair.pubkey()),
+            );
+            let transaction = Transaction::new(&[&keypair], message, Hash::default());
+            MessageTransactionInfo::from_transaction(
+                &transaction.into(),
+                &TransactionStatusMeta::default(),
+                false,
+                0,
+            )
+        };
+        assert!(matches(&create_legacy(lamports_min - 1)).is_empty());
+        for lamports in [lamports_min, lamports_min + 1] {
+            assert_eq!(
+                matches(&create_legacy(lamports)),
+                ["balance", "inner", "top"]
+            );
+        }
+
+        // v0 message with `TransferWithSeed`
+        let create_v0 = |lamports: u64| {
+            let base = Keypair::new();
+            let instruction = system_instruction::transfer_with_seed(
+                &keypair.pubkey(),
+                &base.pubkey(),
+                "seed".to_owned(),
+                &Pubkey::new_unique(),
+                &to,
+                lamports,
+            );
+            let message =
+                v0::Message::try_compile(&keypair.pubkey(), &[instruction], &[], Hash::default())
+                    .unwrap();
+            let transaction =
+                VersionedTransaction::try_new(VersionedMessage::V0(message), &[&keypair, &base])
+                    .unwrap();
+            MessageTransactionInfo::from_transaction(
+                &transaction,
+                &TransactionStatusMeta::default(),
+                false,
+                0,
+            )
+        };
+        assert!(matches(&create_v0(lamports_min - 1)).is_empty());
+        assert_eq!(
+            matches(&create_v0(lamports_min)),
+            ["balance", "inner", "top"]
+        );
+
+        // transfer by CPI from another program, balances changed by the transfer
+        let create_cpi = |lamports: u64| {
+            let instruction = Instruction::new_with_bytes(
+                Pubkey::new_unique(),
+                &[],
+                vec![
+                    AccountMeta::new(keypair.pubkey(), true),
+                    AccountMeta::new(to, false),
+                    AccountMeta::new_readonly(system_program::ID, false),
+                ],
+            );
+            let message = SolMessage::new(&[instruction], Some(&keypair.pubkey()));
+            let system_program_index = message
+                .account_keys
+                .iter()
+                .position(|pubkey| *pubkey == system_program::ID)
+                .unwrap();
+            let to_index = message
+                .account_keys
+                .iter()
+                .position(|pubkey| *pubkey == to)
+                .unwrap();
+            let transaction = Transaction::new(&[&keypair], message, Hash::default());
+            let mut post_balances = vec![0; transaction.message.account_keys.len()];
+            post_balances[to_index] = lamports;
+            let meta = TransactionStatusMeta {
+                pre_balances: vec![0; post_balances.len()],
+                post_balances,
+                inner_instructions: Some(vec![InnerInstructions {
+                    index: 0,
+                    instructions: vec![InnerInstruction {
+                        instruction: CompiledInstruction {
+                            program_id_index: system_program_index as u8,
+                            accounts: vec![0, to_index as u8],
+                            data: system_instruction::transfer(&keypair.pubkey(), &to, lamports)
+                                .data,
+                        },
+                        stack_height: Some(2),
+                    }],
+                }]),
+                ..Default::default()
+            };
+            MessageTransactionInfo::from_transaction(&transaction.into(), &meta, false, 0)
+        };
+        assert!(matches(&create_cpi(lamports_min - 1)).is_empty());
+        assert_eq!(matches(&create_cpi(lamports_min)), ["balance", "inner"]);
+    }
+
+    #[test]
+    fn test_transactions_meta_sections() {
+        let keypair = Keypair::new();
+        let create_filter = |filters: &[(&str, Option<bool>, Option<bool>)]| {
+            let config = SubscribeRequest {
+                transactions: filters
+                    .iter()
+                    .map(|(name, include_logs, include_rewards)| {
+                        (
+                            (*name).to_owned(),
+                            SubscribeRequestFilterTransactions {
+                                include_logs: *include_logs,
+                                include_rewards: *include_rewards,
+                                ..Default::default()
+                            },
+                        )
+                    })
+                    .collect(),
+                ..Default::default()
+            };
+            Filter::new(
+                &config,
+                &FilterLimits::default(),
+                &mut create_filter_names(),
+            )
+            .unwrap()
+        };
+        let message =
+            Message::Transaction(create_message_transaction(&keypair, vec![keypair.pubkey()]));
+        let get_meta_sections = |filter: &Filter| {
+            let updates = filter.get_updates(&message, None);
+            assert_eq!(updates.len(), 1);
+            match &updates[0].message {
+                FilteredUpdateOneof::Transaction(msg) => msg.meta_sections,
+                _ => panic!("expected transaction update"),
+            }
+        };
+
+        let filter = create_filter(&[("a", None, None)]);
+        assert_eq!(get_meta_sections(&filter), TransactionMetaSections::ALL);
+
+        let filter = create_filter(&[("a", Some(false), Some(true))]);
+        assert_eq!(
+            get_meta_sections(&filter),
+            TransactionMetaSections::ALL.with(TransactionMetaSections::LOGS, false)
+        );
+
+        // sections requested by any filter are included
+        let filter = create_filter(&[("a", Some(false), None), ("b", None, Some(false))]);
+        assert_eq!(get_meta_sections(&filter), TransactionMetaSections::ALL);
+
+        let filter = create_filter(&[("a", Some(false), Some(false)), ("b", Some(false), None)]);
+        assert_eq!(
+            get_meta_sections(&filter),
+            TransactionMetaSections::ALL.with(TransactionMetaSections::LOGS, false)
+        );
+    }
+
+    #[test]
+    fn test_transactions_ordered() {
+        let create_filter = |ordered: &[(&str, Option<bool>)], status: &[(&str, Option<bool>)]| {
+            let create_filters = |filters: &[(&str, Option<bool>)]| {
+                filters
+                    .iter()
+                    .map(|(name, ordered_per_slot)| {
+                        (
+                            (*name).to_owned(),
+                            SubscribeRequestFilterTransactions {
+                                ordered_per_slot: *ordered_per_slot,
+                                ..Default::default()
+                            },
+                        )
+                    })
+                    .collect()
+            };
+            let config = SubscribeRequest {
+                transactions: create_filters(ordered),
+                transactions_status: create_filters(status),
+                ..Default::default()
+            };
+            Filter::new(
+                &config,
+                &FilterLimits::default(),
+                &mut create_filter_names(),
+            )
+            .unwrap()
+        };
+
+        let filter = create_filter(&[("a", None), ("b", Some(false))], &[]);
+        assert!(!filter.is_transactions_ordered());
+        assert!(filter.get_transactions_ordered().is_empty());
+
+        let filter = create_filter(&[("a", None)], &[("b", Some(true))]);
+        assert!(filter.is_transactions_ordered());
+        assert_eq!(
+            filter.get_transactions_ordered(),
+            FilteredUpdateFilters::from_vec(vec![FilterName::new("b")])
+        );
+        let (unordered, ordered) =
+            filter.split_transactions_ordered(&FilteredUpdateFilters::from_vec(vec![
+                FilterName::new("a"),
+                FilterName::new("b"),
+            ]));
+        assert_eq!(
+            unordered,
+            FilteredUpdateFilters::from_vec(vec![FilterName::new("a")])
+        );
+        assert_eq!(
+            ordered,
+            FilteredUpdateFilters::from_vec(vec![FilterName::new("b")])
+        );
+    }
+
+    #[test]
+    fn test_accounts_data_slice_bytes() {
+        let source = Bytes::from((0..100u8).collect::<Vec<_>>());
+        let create = |slices: Vec<std::ops::Range<usize>>| {
+            FilterAccountsDataSlice::new_unchecked(Arc::new(slices))
+        };
+
+        // whole data without copy
+        let data = create(vec![]).get_slice(&source);
+        assert_eq!(data.as_ptr(), source.as_ptr());
+        assert_eq!(data, source);
+
+        // contiguous slices are a view
+        let data = create(vec![10..20, 20..30]).get_slice(&source);
+        assert_eq!(data.as_ptr(), source[10..].as_ptr());
+        assert_eq!(data, source[10..30]);
+
+        // slices out of data are skipped
+        let data = create(vec![10..20, 90..120]).get_slice(&source);
+        assert_eq!(data.as_ptr(), source[10..].as_ptr());
+        assert_eq!(data, source[10..20]);
+        assert!(create(vec![100..110, 110..120])
+            .get_slice(&source)
+            .is_empty());
+
+        // gaps require a copy
+        let data = create(vec![0..2, 5..7, 7..8]).get_slice(&source);
+        assert_eq!(data, Bytes::from_static(&[0, 1, 5, 6, 7]));
+    }
+
+    #[test]
+    fn test_seen_signatures_bloom() {
+        let signature = Signature::new_unique();
+        let mut bloom = SignaturesBloom::with_false_positive_rate(100, 0.01);
+        bloom.insert(signature.as_ref());
+        let config = SubscribeRequest {
+            seen_signatures_bloom: Some(bloom.into()),
+            ..Default::default()
+        };
+
+        let filter = Filter::new(
+            &config,
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap();
+        let seen = filter.get_seen_signatures().unwrap();
+        assert!(seen.contains(&signature));
+        assert_eq!(seen.slots(), FilterLimits::default().seen_signatures.slots);
+
+        let mut limits = FilterLimits::default();
+        limits.seen_signatures.max_bytes = 8;
+        assert!(matches!(
+            Filter::new(&config, &limits, &mut create_filter_names()),
+            Err(FilterError::LimitsCheck(
+                FilterLimitsCheckError::MaxSeenSignaturesBytes { max: 8 }
+            ))
+        ));
+
+        let config = SubscribeRequest {
+            seen_signatures_bloom: Some(SubscribeRequestSeenSignaturesBloom {
+                bits: vec![0; 8],
+                num_hashes: 0,
+            }),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Filter::new(
+                &config,
+                &FilterLimits::default(),
+                &mut create_filter_names()
+            ),
+            Err(FilterError::InvalidSeenSignaturesBloom(_))
+        ));
+    }
+
+    #[test]
+    fn test_votes() {
+        let vote_a = Pubkey::new_unique();
+        let vote_b = Pubkey::new_unique();
+        let config = SubscribeRequest {
+            votes: [
+                ("all".to_owned(), SubscribeRequestFilterVotes::default()),
+                (
+                    "a".to_owned(),
+                    SubscribeRequestFilterVotes {
+                        vote_pubkey: vec![vote_a.to_string()],
+                    },
+                ),
+                (
+                    "unknown".to_owned(),
+                    SubscribeRequestFilterVotes {
+                        vote_pubkey: vec![Pubkey::new_unique().to_string()],
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &config,
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap();
+        assert_eq!(filter.get_stream_info().votes, ["a", "all", "unknown"]);
+
+        let entry = |vote_pubkey: Pubkey| SubscribeUpdateVotesEntry {
+            vote_pubkey: vote_pubkey.to_bytes().to_vec(),
+            voted_on_slot: 99,
+            latency_slots: 1,
+            count: 1,
+        };
+        let message = Message::Votes(Arc::new(MessageVotes::new(SubscribeUpdateVotes {
+            slot: 100,
+            from_slot: 100,
+            to_slot: 100,
+            entries: vec![entry(vote_a), entry(vote_b)],
+        })));
+        let mut updates = filter
+            .get_updates(&message, None)
+            .into_iter()
+            .map(|update| match update.message {
+                FilteredUpdateOneof::Votes(msg) => (update.filters, msg.entries.clone()),
+                message => panic!("unexpected update: {message:?}"),
+            })
+            .collect::<Vec<_>>();
+        updates.sort_by_key(|(filters, _entries)| filters.len());
+        assert_eq!(
+            updates,
+            [
+                (
+                    FilteredUpdateFilters::from_vec(vec![FilterName::new("a")]),
+                    vec![entry(vote_a)]
+                ),
+                (
+                    FilteredUpdateFilters::from_vec(vec![FilterName::new("all")]),
+                    vec![entry(vote_a), entry(vote_b)]
+                ),
+            ]
+        );
+
+        let mut limits = FilterLimits::default();
+        limits.votes.vote_pubkey_max = 0;
+        assert!(matches!(
+            Filter::new(&config, &limits, &mut create_filter_names()),
+            Err(FilterError::LimitsCheck(
+                FilterLimitsCheckError::MaxPubkey { max: 0 }
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_stats() {
+        let config = SubscribeRequest {
+            stats: [("stats".to_owned(), SubscribeRequestFilterStats::default())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &config,
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap();
+        assert_eq!(filter.get_stream_info().stats, ["stats"]);
+        assert_eq!(filter.get_metrics()[8], ("stats", 1));
+
+        let message =
+            Message::SlotStats(Arc::new(MessageSlotStats::new(SubscribeUpdateSlotStats {
+                slot: 100,
+                account_updates: 3,
+                unique_accounts: 2,
+                data_bytes: 128,
+                top_owners: vec![],
+            })));
+        let updates = filter.get_updates(&message, None);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(
+            updates[0].filters,
+            FilteredUpdateFilters::from_vec(vec![FilterName::new("stats")])
+        );
+        assert!(matches!(
+            &updates[0].message,
+            FilteredUpdateOneof::SlotStats(msg) if msg.slot == 100 && msg.unique_accounts == 2
+        ));
+
+        let mut limits = FilterLimits::default();
+        limits.stats.max = 0;
+        assert!(matches!(
+            Filter::new(&config, &limits, &mut create_filter_names()),
+            Err(FilterError::LimitsCheck(FilterLimitsCheckError::Max {
+                max: 0
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_interned_pubkeys() {
+        let keys = (0..64).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+        let create_config = |keys: &[Pubkey]| {
+            let keys = keys.iter().map(|key| key.to_string()).collect::<Vec<_>>();
+            SubscribeRequest {
+                accounts: [(
+                    "accounts".to_owned(),
+                    SubscribeRequestFilterAccounts {
+                        account: keys.clone(),
+                        ..Default::default()
+                    },
+                )]
+                .into_iter()
+                .collect(),
+                transactions: [(
+                    "transactions".to_owned(),
+                    SubscribeRequestFilterTransactions {
+                        account_include: keys,
+                        ..Default::default()
+                    },
+                )]
+                .into_iter()
+                .collect(),
+                ..Default::default()
+            }
+        };
+        let mut names = create_filter_names();
+        let mut create_filter = |config: &SubscribeRequest| {
+            Filter::new(config, &FilterLimits::default(), &mut names).unwrap()
+        };
+
+        let filter1 = create_filter(&create_config(&keys));
+        let mut reversed = keys.clone();
+        reversed.reverse();
+        let filter2 = create_filter(&create_config(&reversed));
+        let filter3 = create_filter(&create_config(&keys[1..]));
+
+        // same index of accounts filters and same list of transactions filter in any order
+        let index = &filter1.accounts.account;
+        assert!(index.is_shared(&filter2.accounts.account));
+        assert!(!index.is_shared(&filter3.accounts.account));
+        let get_list = |filter: &Filter| {
+            Arc::clone(
+                &filter.transactions.filters[&FilterName::new("transactions")].account_include,
+            )
+        };
+        assert!(Arc::ptr_eq(&get_list(&filter1), &get_list(&filter2)));
+        assert!(!Arc::ptr_eq(&get_list(&filter1), &get_list(&filter3)));
+        assert_eq!(names.take_pubkeys_stats(), (2, 4));
+
+        // matched by the shared list
+        for (filter, expected) in [(&filter1, true), (&filter3, false)] {
+            let message =
+                Message::Account(create_message_account(keys[0], Pubkey::new_unique(), 0));
+            assert_eq!(!filter.get_updates(&message, None).is_empty(), expected);
+        }
+    }
 }
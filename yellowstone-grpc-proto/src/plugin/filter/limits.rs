@@ -1,7 +1,8 @@
 use {
+    crate::plugin::message::CommitmentLevel,
     serde::{de, Deserialize, Deserializer},
-    solana_sdk::pubkey::Pubkey,
-    std::collections::HashSet,
+    solana_sdk::{clock::MAX_PROCESSING_AGE, pubkey::Pubkey, rent::Rent},
+    std::{collections::HashSet, net::IpAddr, str::FromStr},
 };
 
 #[derive(Debug, thiserror::Error)]
@@ -12,8 +13,48 @@ pub enum FilterLimitsCheckError {
     Any,
     #[error("Max amount of Pubkeys reached, only {max} allowed")]
     MaxPubkey { max: usize },
+    #[error("Max amount of Pubkeys reached, only {max} allowed per subscription, split {requested} Pubkeys over {connections} subscriptions")]
+    MaxPubkeyShardHint {
+        max: usize,
+        requested: usize,
+        connections: usize,
+    },
+    #[error("Seen signatures bloom is too big, only {max} bytes allowed")]
+    MaxSeenSignaturesBytes { max: usize },
+    #[error("Max amount of pda derivations per account update reached, only {max} allowed, requested {requested}")]
+    MaxPdaDerivations { max: usize, requested: usize },
+    #[error("Prefix is too short, at least {min} characters required")]
+    PrefixMinLen { min: usize },
     #[error("Pubkey {pubkey} in filters is not allowed")]
     PubkeyReject { pubkey: Pubkey },
+    #[error("Filter `{kind}` is not allowed")]
+    AccessKind { kind: &'static str },
+    #[error("Commitment `{commitment}` is not allowed")]
+    AccessCommitment { commitment: &'static str },
+    #[error("Commitment `{commitment}` is not supported, supported: {allowed}")]
+    Commitment {
+        commitment: &'static str,
+        allowed: String,
+    },
+}
+
+impl FilterLimitsCheckError {
+    pub const fn is_permission_denied(&self) -> bool {
+        matches!(
+            self,
+            Self::AccessKind { .. } | Self::AccessCommitment { .. }
+        )
+    }
+
+    /// Max amount of Pubkeys per subscription and suggested number of subscriptions
+    pub const fn shard_hint(&self) -> Option<(usize, usize)> {
+        match self {
+            Self::MaxPubkeyShardHint {
+                max, connections, ..
+            } => Some((*max, *connections)),
+            _ => None,
+        }
+    }
 }
 
 pub type FilterLimitsCheckResult = Result<(), FilterLimitsCheckError>;
@@ -21,6 +62,12 @@ pub type FilterLimitsCheckResult = Result<(), FilterLimitsCheckError>;
 #[derive(Debug, Default, Clone, Deserialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct FilterLimits {
+    /// Commitment of requests without `commitment`
+    #[serde(deserialize_with = "deserialize_commitment")]
+    pub default_commitment: CommitmentLevel,
+    /// Commitments supported by the deployment for all callers, `None` allows everything
+    #[serde(deserialize_with = "deserialize_commitment_set_maybe")]
+    pub allowed_commitments: Option<HashSet<CommitmentLevel>>,
     pub accounts: FilterLimitsAccounts,
     pub slots: FilterLimitsSlots,
     pub transactions: FilterLimitsTransactions,
@@ -28,6 +75,10 @@ pub struct FilterLimits {
     pub blocks: FilterLimitsBlocks,
     pub blocks_meta: FilterLimitsBlocksMeta,
     pub entries: FilterLimitsEntries,
+    pub votes: FilterLimitsVotes,
+    pub stats: FilterLimitsStats,
+    pub seen_signatures: FilterLimitsSeenSignatures,
+    pub access: FilterLimitsAccess,
 }
 
 impl FilterLimits {
@@ -55,6 +106,36 @@ impl FilterLimits {
         }
     }
 
+    /// Check amount of Pubkeys according to the policy, returns amount of Pubkeys to keep
+    pub const fn check_pubkey_max_policy(
+        len: usize,
+        max: usize,
+        policy: FilterLimitsOverLimitPolicy,
+    ) -> Result<usize, FilterLimitsCheckError> {
+        if len <= max {
+            return Ok(len);
+        }
+        match policy {
+            FilterLimitsOverLimitPolicy::Reject => Err(FilterLimitsCheckError::MaxPubkey { max }),
+            FilterLimitsOverLimitPolicy::Clamp => Ok(max),
+            FilterLimitsOverLimitPolicy::ShardHint => {
+                Err(FilterLimitsCheckError::MaxPubkeyShardHint {
+                    max,
+                    requested: len,
+                    connections: if max == 0 { 0 } else { len.div_ceil(max) },
+                })
+            }
+        }
+    }
+
+    pub const fn check_prefix_min_len(len: usize, min: usize) -> FilterLimitsCheckResult {
+        if len >= min {
+            Ok(())
+        } else {
+            Err(FilterLimitsCheckError::PrefixMinLen { min })
+        }
+    }
+
     pub fn check_pubkey_reject(pubkey: &Pubkey, set: &HashSet<Pubkey>) -> FilterLimitsCheckResult {
         if !set.contains(pubkey) {
             Ok(())
@@ -62,6 +143,233 @@ impl FilterLimits {
             Err(FilterLimitsCheckError::PubkeyReject { pubkey: *pubkey })
         }
     }
+
+    pub fn is_commitment_allowed(&self, commitment: CommitmentLevel) -> bool {
+        self.allowed_commitments
+            .as_ref()
+            .is_none_or(|commitments| commitments.contains(&commitment))
+    }
+
+    /// Commitment of the request or `default_commitment`, rejected if not in `allowed_commitments`
+    pub fn check_commitment(
+        &self,
+        commitment: Option<CommitmentLevel>,
+    ) -> Result<CommitmentLevel, FilterLimitsCheckError> {
+        let commitment = commitment.unwrap_or(self.default_commitment);
+        if self.is_commitment_allowed(commitment) {
+            Ok(commitment)
+        } else {
+            Err(FilterLimitsCheckError::Commitment {
+                commitment: commitment.as_str(),
+                allowed: [
+                    CommitmentLevel::Processed,
+                    CommitmentLevel::Confirmed,
+                    CommitmentLevel::Finalized,
+                ]
+                .into_iter()
+                .filter(|commitment| self.is_commitment_allowed(*commitment))
+                .map(|commitment| commitment.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            })
+        }
+    }
+
+    pub fn check_access(
+        &self,
+        caller: &FilterCaller,
+        commitment: CommitmentLevel,
+        kinds: impl IntoIterator<Item = FilterAccessKind>,
+    ) -> FilterLimitsCheckResult {
+        let allow = self.access.resolve(caller);
+        if let Some(commitments) = &allow.commitments {
+            if !commitments.contains(&commitment) {
+                return Err(FilterLimitsCheckError::AccessCommitment {
+                    commitment: commitment.as_str(),
+                });
+            }
+        }
+        if let Some(filters) = &allow.filters {
+            for kind in kinds {
+                if !filters.contains(&kind) {
+                    return Err(FilterLimitsCheckError::AccessKind {
+                        kind: kind.as_str(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Identity of the subscriber, used to resolve access rules
+#[derive(Debug, Default, Clone)]
+pub struct FilterCaller {
+    pub x_token: Option<String>,
+    pub remote_ip: Option<IpAddr>,
+}
+
+impl FilterCaller {
+    /// Time of the comparison doesn't depend on the position of the first different byte
+    fn is_x_token(&self, x_token: &str) -> bool {
+        self.x_token.as_ref().is_some_and(|caller| {
+            caller.len() == x_token.len()
+                && std::hint::black_box(
+                    caller
+                        .bytes()
+                        .zip(x_token.bytes())
+                        .fold(0, |acc, (a, b)| acc | (a ^ b)),
+                ) == 0
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterAccessKind {
+    Accounts,
+    Slots,
+    Transactions,
+    TransactionsStatus,
+    Entries,
+    Blocks,
+    BlocksMeta,
+    Votes,
+    Stats,
+}
+
+impl FilterAccessKind {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Accounts => "accounts",
+            Self::Slots => "slots",
+            Self::Transactions => "transactions",
+            Self::TransactionsStatus => "transactions_status",
+            Self::Entries => "entries",
+            Self::Blocks => "blocks",
+            Self::BlocksMeta => "blocks_meta",
+            Self::Votes => "votes",
+            Self::Stats => "stats",
+        }
+    }
+}
+
+/// Per-principal access rules, first matched rule is used,
+/// `default` is applied if no rule matched
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterLimitsAccess {
+    pub default: FilterLimitsAccessAllow,
+    pub rules: Vec<FilterLimitsAccessRule>,
+}
+
+impl FilterLimitsAccess {
+    pub fn resolve(&self, caller: &FilterCaller) -> &FilterLimitsAccessAllow {
+        self.rules
+            .iter()
+            .find(|rule| rule.is_match(caller))
+            .map(|rule| &rule.allow)
+            .unwrap_or(&self.default)
+    }
+}
+
+/// Allowed commitment levels and filter kinds, `None` allows everything
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterLimitsAccessAllow {
+    #[serde(deserialize_with = "deserialize_commitment_set_maybe")]
+    pub commitments: Option<HashSet<CommitmentLevel>>,
+    pub filters: Option<HashSet<FilterAccessKind>>,
+}
+
+/// Rule matched by `x_token` and/or `cidr`, both should match if specified
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterLimitsAccessRule {
+    pub x_token: Option<String>,
+    pub cidr: Option<FilterLimitsAccessCidr>,
+    pub allow: FilterLimitsAccessAllow,
+}
+
+impl FilterLimitsAccessRule {
+    pub fn is_match(&self, caller: &FilterCaller) -> bool {
+        if self.x_token.is_none() && self.cidr.is_none() {
+            return false;
+        }
+        if let Some(x_token) = &self.x_token {
+            if !caller.is_x_token(x_token) {
+                return false;
+            }
+        }
+        if let Some(cidr) = &self.cidr {
+            if !caller.remote_ip.is_some_and(|ip| cidr.contains(ip)) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilterLimitsAccessCidr {
+    pub addr: IpAddr,
+    pub prefix_len: u8,
+}
+
+impl FilterLimitsAccessCidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(addr), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(addr) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(addr), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(addr) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for FilterLimitsAccessCidr {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = match value.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (value, None),
+        };
+        let addr = addr
+            .parse::<IpAddr>()
+            .map_err(|error| format!("Invalid cidr: {value} ({error})"))?
+            .to_canonical();
+        let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix_len| *prefix_len <= max_prefix_len)
+                .ok_or_else(|| format!("Invalid cidr prefix length: {value}"))?,
+            None => max_prefix_len,
+        };
+        Ok(Self { addr, prefix_len })
+    }
+}
+
+impl<'de> Deserialize<'de> for FilterLimitsAccessCidr {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        <&str>::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -76,6 +384,28 @@ pub struct FilterLimitsAccounts {
     #[serde(deserialize_with = "deserialize_pubkey_set")]
     pub owner_reject: HashSet<Pubkey>,
     pub data_slice_max: usize,
+    pub txn_signature_max: usize,
+    /// Max amount of `account_prefix` / `owner_prefix` per filter
+    pub prefix_max: usize,
+    /// Min length of `account_prefix` / `owner_prefix`, short prefix matches too many accounts
+    pub prefix_min_len: usize,
+    pub over_limit_policy: FilterLimitsOverLimitPolicy,
+    /// Rent parameters for `below_rent_exempt` of `lamports_range`, default rent of the cluster
+    /// is compiled in, would be outdated if the cluster rent is changed
+    pub rent_lamports_per_byte_year: u64,
+    pub rent_exemption_threshold: f64,
+    /// Max amount of `pda` per filter
+    pub pda_max: usize,
+    /// Allow `templates` of `pda`, every template costs up to `pda_bump_search_max`
+    /// `create_program_address` calls per account update of the program
+    pub pda_templates: bool,
+    /// Max amount of `templates` per `pda`
+    pub pda_templates_max: usize,
+    /// Max amount of bumps tried if `bump_offset` of the template is not set
+    pub pda_bump_search_max: usize,
+    /// Max amount of `create_program_address` calls per account update over all `pda` of the
+    /// subscription: one per template with `bump_offset`, `pda_bump_search_max` otherwise
+    pub pda_derivations_max: usize,
 }
 
 impl Default for FilterLimitsAccounts {
@@ -88,10 +418,35 @@ impl Default for FilterLimitsAccounts {
             owner_max: usize::MAX,
             owner_reject: HashSet::new(),
             data_slice_max: usize::MAX,
+            txn_signature_max: usize::MAX,
+            prefix_max: usize::MAX,
+            prefix_min_len: 4,
+            over_limit_policy: FilterLimitsOverLimitPolicy::default(),
+            rent_lamports_per_byte_year: Rent::default().lamports_per_byte_year,
+            rent_exemption_threshold: Rent::default().exemption_threshold,
+            pda_max: 16,
+            pda_templates: false,
+            pda_templates_max: 4,
+            pda_bump_search_max: 8,
+            pda_derivations_max: 64,
         }
     }
 }
 
+/// What to do when filter has more Pubkeys than `account_max` / `owner_max`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterLimitsOverLimitPolicy {
+    /// Reject subscription
+    #[default]
+    Reject,
+    /// Accept first `max` Pubkeys, amount of dropped Pubkeys reported in `warnings`, exclude
+    /// lists are rejected because clamping them would broaden delivered updates
+    Clamp,
+    /// Reject subscription with max amount of Pubkeys per subscription and suggested number of subscriptions
+    ShardHint,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct FilterLimitsSlots {
@@ -119,6 +474,12 @@ pub struct FilterLimitsTransactions {
     pub account_exclude_max: usize,
     #[serde(deserialize_with = "deserialize_usize_str")]
     pub account_required_max: usize,
+    /// Max amount of `signatures` per filter
+    #[serde(deserialize_with = "deserialize_usize_str")]
+    pub signatures_max: usize,
+    /// Amount of slots from the first slot after the filter is accepted during which
+    /// `signatures` are matched
+    pub signatures_expire_slots: u64,
 }
 
 impl Default for FilterLimitsTransactions {
@@ -130,6 +491,8 @@ impl Default for FilterLimitsTransactions {
             account_include_reject: HashSet::new(),
             account_exclude_max: usize::MAX,
             account_required_max: usize::MAX,
+            signatures_max: usize::MAX,
+            signatures_expire_slots: MAX_PROCESSING_AGE as u64,
         }
     }
 }
@@ -189,6 +552,56 @@ impl Default for FilterLimitsEntries {
     }
 }
 
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterLimitsVotes {
+    #[serde(deserialize_with = "deserialize_usize_str")]
+    pub max: usize,
+    #[serde(deserialize_with = "deserialize_usize_str")]
+    pub vote_pubkey_max: usize,
+}
+
+impl Default for FilterLimitsVotes {
+    fn default() -> Self {
+        Self {
+            max: usize::MAX,
+            vote_pubkey_max: usize::MAX,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterLimitsStats {
+    #[serde(deserialize_with = "deserialize_usize_str")]
+    pub max: usize,
+}
+
+impl Default for FilterLimitsStats {
+    fn default() -> Self {
+        Self { max: usize::MAX }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterLimitsSeenSignatures {
+    /// Max size of `seen_signatures_bloom` in bytes, `0` disables the bloom
+    #[serde(deserialize_with = "deserialize_usize_str")]
+    pub max_bytes: usize,
+    /// Amount of slots from the first transaction of the stream during which the bloom is used
+    pub slots: u64,
+}
+
+impl Default for FilterLimitsSeenSignatures {
+    fn default() -> Self {
+        Self {
+            max_bytes: 1024 * 1024,
+            slots: 10,
+        }
+    }
+}
+
 fn deserialize_usize_str<'de, D>(deserializer: D) -> Result<usize, D::Error>
 where
     D: Deserializer<'de>,
@@ -222,3 +635,35 @@ where
         })
         .collect::<Result<_, _>>()
 }
+
+fn deserialize_commitment<'de, D>(deserializer: D) -> Result<CommitmentLevel, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    parse_commitment(<&str>::deserialize(deserializer)?)
+}
+
+fn deserialize_commitment_set_maybe<'de, D>(
+    deserializer: D,
+) -> Result<Option<HashSet<CommitmentLevel>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<Vec<&str>>::deserialize(deserializer)?
+        .map(|values| {
+            values
+                .into_iter()
+                .map(parse_commitment)
+                .collect::<Result<_, _>>()
+        })
+        .transpose()
+}
+
+fn parse_commitment<E: de::Error>(value: &str) -> Result<CommitmentLevel, E> {
+    match value {
+        "processed" => Ok(CommitmentLevel::Processed),
+        "confirmed" => Ok(CommitmentLevel::Confirmed),
+        "finalized" => Ok(CommitmentLevel::Finalized),
+        value => Err(de::Error::custom(format!("Invalid commitment: {value}"))),
+    }
+}
@@ -1,17 +1,24 @@
 use {
     crate::{
+        convert_from::convert_account_data,
         geyser::{
             subscribe_update::UpdateOneof, SlotStatus as SlotStatusProto, SubscribeUpdate,
             SubscribeUpdateAccount, SubscribeUpdateAccountInfo, SubscribeUpdateBlock,
-            SubscribeUpdateEntry, SubscribeUpdatePing, SubscribeUpdatePong, SubscribeUpdateSlot,
-            SubscribeUpdateTransaction, SubscribeUpdateTransactionInfo,
+            SubscribeUpdateEndOfStartup, SubscribeUpdateEntry, SubscribeUpdateLost,
+            SubscribeUpdateMaintenance, SubscribeUpdatePing, SubscribeUpdatePong,
+            SubscribeUpdateQuotaWarning, SubscribeUpdateReplayDone, SubscribeUpdateRollback,
+            SubscribeUpdateSlot, SubscribeUpdateStreamInfo, SubscribeUpdateTransaction,
+            SubscribeUpdateTransactionInfo, SubscribeUpdateTransactionSlotDone,
             SubscribeUpdateTransactionStatus,
         },
         plugin::{
             filter::{name::FilterName, FilterAccountsDataSlice},
             message::{
-                MessageAccount, MessageAccountInfo, MessageBlock, MessageBlockMeta, MessageEntry,
-                MessageSlot, MessageTransaction, MessageTransactionInfo,
+                MessageAccount, MessageAccountInfo, MessageBlock, MessageBlockDiagnostic,
+                MessageBlockMeta, MessageEntry, MessageSlot, MessageSlotStats, MessageTransaction,
+                MessageTransactionInfo, MessageTransactionInfoEncoded,
+                MessageTransactionInfoEncodedCache, MessageVotes, TransactionMetaSections,
+                ENTRY_BYTES, SLOT_BYTES, UPDATE_OVERHEAD_BYTES,
             },
         },
         solana::storage::confirmed_block,
@@ -30,7 +37,10 @@ use {
     std::{
         collections::HashSet,
         ops::{Deref, DerefMut},
-        sync::Arc,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
         time::SystemTime,
     },
 };
@@ -41,7 +51,7 @@ pub const fn prost_field_encoded_len(tag: u32, len: usize) -> usize {
 }
 
 #[inline]
-fn prost_bytes_encode_raw(tag: u32, value: &[u8], buf: &mut impl BufMut) {
+pub fn prost_bytes_encode_raw(tag: u32, value: &[u8], buf: &mut impl BufMut) {
     encode_key(tag, WireType::LengthDelimited, buf);
     encode_varint(value.len() as u64, buf);
     buf.put(value);
@@ -65,28 +75,93 @@ macro_rules! prost_repeated_encoded_len_map {
 
 pub type FilteredUpdates = SmallVec<[FilteredUpdate; 2]>;
 
+/// Size of encoded updates, counted while updates are encoded for the client
+#[derive(Debug, Default, Clone)]
+pub struct EncodedBytesCounter(Arc<AtomicU64>);
+
+impl PartialEq for EncodedBytesCounter {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl EncodedBytesCounter {
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn add(&self, bytes: usize) {
+        self.0.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct FilteredUpdate {
     pub filters: FilteredUpdateFilters,
     pub message: FilteredUpdateOneof,
-    pub created_at: Timestamp,
+    pub created_at: Option<Timestamp>,
+    pub warnings: Vec<String>,
+    pub sequence: u64,
+    pub dropped_since_last: u64,
+    pub dropped_batches_since_last: u64,
+    /// Not encoded, incremented on every encoding of the update
+    pub encoded_bytes: Option<EncodedBytesCounter>,
 }
 
 impl prost::Message for FilteredUpdate {
     fn encode_raw(&self, buf: &mut impl BufMut) {
+        let remaining = buf.remaining_mut();
         for name in self.filters.iter().map(|filter| filter.as_ref()) {
             encode_key(1u32, WireType::LengthDelimited, buf);
             encode_varint(name.len() as u64, buf);
             buf.put_slice(name.as_bytes());
         }
         self.message.encode_raw(buf);
-        message::encode(11u32, &self.created_at, buf);
+        if let Some(created_at) = &self.created_at {
+            message::encode(11u32, created_at, buf);
+        }
+        for warning in self.warnings.iter() {
+            encode_key(13u32, WireType::LengthDelimited, buf);
+            encode_varint(warning.len() as u64, buf);
+            buf.put_slice(warning.as_bytes());
+        }
+        if self.sequence != 0 {
+            ::prost::encoding::uint64::encode(21u32, &self.sequence, buf);
+        }
+        if self.dropped_since_last != 0 {
+            ::prost::encoding::uint64::encode(22u32, &self.dropped_since_last, buf);
+        }
+        if self.dropped_batches_since_last != 0 {
+            ::prost::encoding::uint64::encode(26u32, &self.dropped_batches_since_last, buf);
+        }
+        if let Some(counter) = &self.encoded_bytes {
+            counter.add(remaining - buf.remaining_mut());
+        }
     }
 
     fn encoded_len(&self) -> usize {
         prost_repeated_encoded_len_map!(1u32, self.filters, |filter| filter.as_ref().len())
             + self.message.encoded_len()
-            + message::encoded_len(11u32, &self.created_at)
+            + self
+                .created_at
+                .as_ref()
+                .map_or(0, |created_at| message::encoded_len(11u32, created_at))
+            + prost_repeated_encoded_len_map!(13u32, self.warnings, |warning| warning.len())
+            + if self.sequence != 0 {
+                ::prost::encoding::uint64::encoded_len(21u32, &self.sequence)
+            } else {
+                0
+            }
+            + if self.dropped_since_last != 0 {
+                ::prost::encoding::uint64::encoded_len(22u32, &self.dropped_since_last)
+            } else {
+                0
+            }
+            + if self.dropped_batches_since_last != 0 {
+                ::prost::encoding::uint64::encoded_len(26u32, &self.dropped_batches_since_last)
+            } else {
+                0
+            }
     }
 
     fn merge_field(
@@ -113,7 +188,12 @@ impl FilteredUpdate {
         Self {
             filters,
             message,
-            created_at,
+            created_at: Some(created_at),
+            warnings: Vec::new(),
+            sequence: 0,
+            dropped_since_last: 0,
+            dropped_batches_since_last: 0,
+            encoded_bytes: None,
         }
     }
 
@@ -125,8 +205,67 @@ impl FilteredUpdate {
         )
     }
 
+    /// Ping message with warnings for the client
+    pub fn new_warnings(warnings: Vec<String>) -> Self {
+        let mut msg = Self::new_empty(FilteredUpdateOneof::Ping(SubscribeUpdatePing::default()));
+        msg.warnings = warnings;
+        msg
+    }
+
+    /// Approximate size of the encoded update in bytes, estimated like
+    /// [`Message::get_size_estimate`](crate::plugin::message::Message::get_size_estimate) with
+    /// data slices of accounts applied. Transactions are counted with all meta sections, updates
+    /// without a source message are counted exactly.
+    pub fn get_size_estimate(&self) -> usize {
+        prost_repeated_encoded_len_map!(1u32, self.filters, |filter| filter.as_ref().len())
+            + prost_repeated_encoded_len_map!(13u32, self.warnings, |warning| warning.len())
+            + match &self.message {
+                FilteredUpdateOneof::Account(msg) => {
+                    UPDATE_OVERHEAD_BYTES
+                        + MessageAccountInfo::get_size_estimate_data_len(
+                            msg.data_slice.get_slice_len(&msg.account.data),
+                        )
+                }
+                FilteredUpdateOneof::Slot(_) => UPDATE_OVERHEAD_BYTES + SLOT_BYTES,
+                FilteredUpdateOneof::Transaction(msg) => {
+                    UPDATE_OVERHEAD_BYTES + msg.transaction.get_size_estimate()
+                }
+                FilteredUpdateOneof::Entry(_) => UPDATE_OVERHEAD_BYTES + ENTRY_BYTES,
+                FilteredUpdateOneof::BlockMeta(msg) => {
+                    UPDATE_OVERHEAD_BYTES + msg.block_meta.encoded_len()
+                }
+                FilteredUpdateOneof::Block(msg) => {
+                    UPDATE_OVERHEAD_BYTES
+                        + msg.meta.block_meta.encoded_len()
+                        + msg
+                            .transactions
+                            .iter()
+                            .map(|tx| tx.get_size_estimate())
+                            .sum::<usize>()
+                        + msg
+                            .accounts
+                            .iter()
+                            .map(|account| {
+                                MessageAccountInfo::get_size_estimate_data_len(
+                                    msg.accounts_data_slice.get_slice_len(&account.data),
+                                )
+                            })
+                            .sum::<usize>()
+                        + msg.entries.len() * ENTRY_BYTES
+                }
+                oneof => {
+                    oneof.encoded_len()
+                        + self
+                            .created_at
+                            .as_ref()
+                            .map_or(0, |created_at| message::encoded_len(11u32, created_at))
+                }
+            }
+    }
+
     fn as_subscribe_update_account(
         message: &MessageAccountInfo,
+        is_startup: bool,
         data_slice: &FilterAccountsDataSlice,
     ) -> SubscribeUpdateAccountInfo {
         SubscribeUpdateAccountInfo {
@@ -135,22 +274,34 @@ impl FilteredUpdate {
             owner: message.owner.as_ref().into(),
             executable: message.executable,
             rent_epoch: message.rent_epoch,
-            data: data_slice.get_slice(&message.data),
+            data: convert_account_data(data_slice.get_slice(&message.data)),
             write_version: message.write_version,
             txn_signature: message.txn_signature.map(|s| s.as_ref().into()),
+            origin: Some(message.get_origin(is_startup) as i32),
         }
     }
 
     fn as_subscribe_update_transaction(
         message: &MessageTransactionInfo,
+        meta_sections: TransactionMetaSections,
+        resolved_accounts: bool,
     ) -> SubscribeUpdateTransactionInfo {
-        SubscribeUpdateTransactionInfo {
+        let mut info = SubscribeUpdateTransactionInfo {
             signature: message.signature.as_ref().into(),
             is_vote: message.is_vote,
             transaction: Some(message.transaction.clone()),
             meta: Some(message.meta.clone()),
             index: message.index as u64,
-        }
+            resolved_accounts: if resolved_accounts {
+                message.get_resolved_accounts()
+            } else {
+                vec![]
+            },
+            token_balances_none: message.token_balances_none,
+            rewards_none: message.rewards_none,
+        };
+        meta_sections.strip(&mut info);
+        info
     }
 
     fn as_subscribe_update_entry(message: &MessageEntry) -> SubscribeUpdateEntry {
@@ -169,10 +320,13 @@ impl FilteredUpdate {
             FilteredUpdateOneof::Account(msg) => UpdateOneof::Account(SubscribeUpdateAccount {
                 account: Some(Self::as_subscribe_update_account(
                     msg.account.as_ref(),
+                    msg.is_startup,
                     &msg.data_slice,
                 )),
                 slot: msg.slot,
                 is_startup: msg.is_startup,
+                confirmed_at_slot: msg.confirmed_at_slot,
+                finalized_at_slot: msg.finalized_at_slot,
             }),
             FilteredUpdateOneof::Slot(msg) => UpdateOneof::Slot(SubscribeUpdateSlot {
                 slot: msg.slot,
@@ -184,6 +338,8 @@ impl FilteredUpdate {
                 UpdateOneof::Transaction(SubscribeUpdateTransaction {
                     transaction: Some(Self::as_subscribe_update_transaction(
                         msg.transaction.as_ref(),
+                        msg.meta_sections,
+                        msg.resolved_accounts,
                     )),
                     slot: msg.slot,
                 })
@@ -209,14 +365,24 @@ impl FilteredUpdate {
                 transactions: msg
                     .transactions
                     .iter()
-                    .map(|tx| Self::as_subscribe_update_transaction(tx.as_ref()))
+                    .map(|tx| {
+                        Self::as_subscribe_update_transaction(
+                            tx.as_ref(),
+                            TransactionMetaSections::ALL,
+                            false,
+                        )
+                    })
                     .collect(),
                 updated_account_count: msg.updated_account_count,
                 accounts: msg
                     .accounts
                     .iter()
                     .map(|acc| {
-                        Self::as_subscribe_update_account(acc.as_ref(), &msg.accounts_data_slice)
+                        Self::as_subscribe_update_account(
+                            acc.as_ref(),
+                            false,
+                            &msg.accounts_data_slice,
+                        )
                     })
                     .collect(),
                 entries_count: msg.meta.entries_count,
@@ -225,13 +391,33 @@ impl FilteredUpdate {
                     .iter()
                     .map(|entry| Self::as_subscribe_update_entry(entry.as_ref()))
                     .collect(),
+                is_partial: msg.is_partial,
+                missing_transaction_count: msg.missing_transaction_count,
+                parent_slot_unknown: msg.meta.parent_slot_unknown,
+                executed_transaction_count_unknown: msg.meta.executed_transaction_count_unknown,
+                entries_count_unknown: msg.meta.entries_count_unknown,
             }),
-            FilteredUpdateOneof::Ping => UpdateOneof::Ping(SubscribeUpdatePing {}),
+            FilteredUpdateOneof::Ping(msg) => UpdateOneof::Ping(*msg),
             FilteredUpdateOneof::Pong(msg) => UpdateOneof::Pong(*msg),
             FilteredUpdateOneof::BlockMeta(msg) => UpdateOneof::BlockMeta(msg.block_meta.clone()),
             FilteredUpdateOneof::Entry(msg) => {
                 UpdateOneof::Entry(Self::as_subscribe_update_entry(&msg.0))
             }
+            FilteredUpdateOneof::EndOfStartup => {
+                UpdateOneof::EndOfStartup(SubscribeUpdateEndOfStartup {})
+            }
+            FilteredUpdateOneof::Lost(msg) => UpdateOneof::Lost(*msg),
+            FilteredUpdateOneof::TransactionSlotDone(msg) => UpdateOneof::TransactionSlotDone(*msg),
+            FilteredUpdateOneof::ReplayDone(msg) => UpdateOneof::ReplayDone(*msg),
+            FilteredUpdateOneof::BlockDiagnostic(msg) => {
+                UpdateOneof::BlockDiagnostic(msg.diagnostic.clone())
+            }
+            FilteredUpdateOneof::QuotaWarning(msg) => UpdateOneof::QuotaWarning(*msg),
+            FilteredUpdateOneof::StreamInfo(msg) => UpdateOneof::StreamInfo(msg.as_ref().clone()),
+            FilteredUpdateOneof::Votes(msg) => UpdateOneof::Votes(msg.votes.clone()),
+            FilteredUpdateOneof::SlotStats(msg) => UpdateOneof::SlotStats(msg.stats.clone()),
+            FilteredUpdateOneof::Maintenance(msg) => UpdateOneof::Maintenance(msg.as_ref().clone()),
+            FilteredUpdateOneof::Rollback(msg) => UpdateOneof::Rollback(msg.as_ref().clone()),
         };
 
         SubscribeUpdate {
@@ -241,7 +427,11 @@ impl FilteredUpdate {
                 .map(|name| name.as_ref().to_string())
                 .collect(),
             update_oneof: Some(message),
-            created_at: Some(self.created_at),
+            created_at: self.created_at,
+            warnings: self.warnings.clone(),
+            sequence: self.sequence,
+            dropped_since_last: self.dropped_since_last,
+            dropped_batches_since_last: self.dropped_batches_since_last,
         }
     }
 
@@ -256,6 +446,8 @@ impl FilteredUpdate {
                     slot: account.slot,
                     is_startup: account.is_startup,
                     data_slice: FilterAccountsDataSlice::default(),
+                    confirmed_at_slot: account.confirmed_at_slot,
+                    finalized_at_slot: account.finalized_at_slot,
                 })
             }
             UpdateOneof::Slot(msg) => {
@@ -263,10 +455,16 @@ impl FilteredUpdate {
                 FilteredUpdateOneof::Slot(FilteredUpdateSlot(slot))
             }
             UpdateOneof::Transaction(msg) => {
+                let resolved_accounts = msg
+                    .transaction
+                    .as_ref()
+                    .is_some_and(|tx| !tx.resolved_accounts.is_empty());
                 let tx = MessageTransaction::from_update_oneof(msg, created_at)?;
                 FilteredUpdateOneof::Transaction(FilteredUpdateTransaction {
+                    resolved_accounts,
                     transaction: tx.transaction,
                     slot: tx.slot,
+                    meta_sections: TransactionMetaSections::ALL,
                 })
             }
             UpdateOneof::TransactionStatus(msg) => {
@@ -280,8 +478,11 @@ impl FilteredUpdate {
                             err: msg.err,
                             ..confirmed_block::TransactionStatusMeta::default()
                         },
+                        token_balances_none: false,
+                        rewards_none: false,
                         index: msg.index as usize,
                         account_keys: HashSet::new(),
+                        encoded: MessageTransactionInfoEncoded::default(),
                     }),
                     slot: msg.slot,
                 })
@@ -295,9 +496,11 @@ impl FilteredUpdate {
                     accounts: block.accounts,
                     accounts_data_slice: FilterAccountsDataSlice::default(),
                     entries: block.entries,
+                    is_partial: block.is_partial,
+                    missing_transaction_count: block.missing_transaction_count,
                 }))
             }
-            UpdateOneof::Ping(_) => FilteredUpdateOneof::Ping,
+            UpdateOneof::Ping(msg) => FilteredUpdateOneof::Ping(msg),
             UpdateOneof::Pong(msg) => FilteredUpdateOneof::Pong(msg),
             UpdateOneof::BlockMeta(msg) => {
                 let block_meta = MessageBlockMeta::from_update_oneof(msg, created_at);
@@ -307,12 +510,34 @@ impl FilteredUpdate {
                 let entry = MessageEntry::from_update_oneof(&msg, created_at)?;
                 FilteredUpdateOneof::Entry(FilteredUpdateEntry(Arc::new(entry)))
             }
+            UpdateOneof::EndOfStartup(_) => FilteredUpdateOneof::EndOfStartup,
+            UpdateOneof::Lost(msg) => FilteredUpdateOneof::Lost(msg),
+            UpdateOneof::TransactionSlotDone(msg) => FilteredUpdateOneof::TransactionSlotDone(msg),
+            UpdateOneof::ReplayDone(msg) => FilteredUpdateOneof::ReplayDone(msg),
+            UpdateOneof::BlockDiagnostic(msg) => FilteredUpdateOneof::BlockDiagnostic(Arc::new(
+                MessageBlockDiagnostic::from_update_oneof(msg, created_at),
+            )),
+            UpdateOneof::QuotaWarning(msg) => FilteredUpdateOneof::QuotaWarning(msg),
+            UpdateOneof::StreamInfo(msg) => FilteredUpdateOneof::StreamInfo(Box::new(msg)),
+            UpdateOneof::Votes(msg) => FilteredUpdateOneof::Votes(Arc::new(
+                MessageVotes::from_update_oneof(msg, created_at),
+            )),
+            UpdateOneof::SlotStats(msg) => FilteredUpdateOneof::SlotStats(Arc::new(
+                MessageSlotStats::from_update_oneof(msg, created_at),
+            )),
+            UpdateOneof::Maintenance(msg) => FilteredUpdateOneof::Maintenance(Arc::new(msg)),
+            UpdateOneof::Rollback(msg) => FilteredUpdateOneof::Rollback(Box::new(msg)),
         };
 
         Ok(Self {
             filters: update.filters.into_iter().map(FilterName::new).collect(),
             message,
-            created_at,
+            created_at: Some(created_at),
+            warnings: update.warnings,
+            sequence: update.sequence,
+            dropped_since_last: update.dropped_since_last,
+            dropped_batches_since_last: update.dropped_batches_since_last,
+            encoded_bytes: None,
         })
     }
 }
@@ -321,15 +546,26 @@ pub type FilteredUpdateFilters = SmallVec<[FilterName; 4]>;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum FilteredUpdateOneof {
-    Account(FilteredUpdateAccount),                     // 2
-    Slot(FilteredUpdateSlot),                           // 3
-    Transaction(FilteredUpdateTransaction),             // 4
-    TransactionStatus(FilteredUpdateTransactionStatus), // 10
-    Block(Box<FilteredUpdateBlock>),                    // 5
-    Ping,                                               // 6
-    Pong(SubscribeUpdatePong),                          // 9
-    BlockMeta(Arc<MessageBlockMeta>),                   // 7
-    Entry(FilteredUpdateEntry),                         // 8
+    Account(FilteredUpdateAccount),                          // 2
+    Slot(FilteredUpdateSlot),                                // 3
+    Transaction(FilteredUpdateTransaction),                  // 4
+    TransactionStatus(FilteredUpdateTransactionStatus),      // 10
+    Block(Box<FilteredUpdateBlock>),                         // 5
+    Ping(SubscribeUpdatePing),                               // 6
+    Pong(SubscribeUpdatePong),                               // 9
+    BlockMeta(Arc<MessageBlockMeta>),                        // 7
+    Entry(FilteredUpdateEntry),                              // 8
+    EndOfStartup,                                            // 12
+    Lost(SubscribeUpdateLost),                               // 14
+    TransactionSlotDone(SubscribeUpdateTransactionSlotDone), // 15
+    ReplayDone(SubscribeUpdateReplayDone),                   // 16
+    BlockDiagnostic(Arc<MessageBlockDiagnostic>),            // 17
+    QuotaWarning(SubscribeUpdateQuotaWarning),               // 18
+    StreamInfo(Box<SubscribeUpdateStreamInfo>),              // 19
+    Votes(Arc<MessageVotes>),                                // 20
+    SlotStats(Arc<MessageSlotStats>),                        // 23
+    Maintenance(Arc<SubscribeUpdateMaintenance>),            // 24
+    Rollback(Box<SubscribeUpdateRollback>),                  // 25
 }
 
 impl FilteredUpdateOneof {
@@ -339,6 +575,8 @@ impl FilteredUpdateOneof {
             account: Arc::clone(&message.account),
             is_startup: message.is_startup,
             data_slice,
+            confirmed_at_slot: message.confirmed_at_slot,
+            finalized_at_slot: message.finalized_at_slot,
         })
     }
 
@@ -347,9 +585,18 @@ impl FilteredUpdateOneof {
     }
 
     pub fn transaction(message: &MessageTransaction) -> Self {
+        Self::transaction_with_meta_sections(message, TransactionMetaSections::ALL)
+    }
+
+    pub fn transaction_with_meta_sections(
+        message: &MessageTransaction,
+        meta_sections: TransactionMetaSections,
+    ) -> Self {
         Self::Transaction(FilteredUpdateTransaction {
             transaction: Arc::clone(&message.transaction),
             slot: message.slot,
+            meta_sections,
+            resolved_accounts: false,
         })
     }
 
@@ -364,8 +611,12 @@ impl FilteredUpdateOneof {
         Self::Block(message)
     }
 
-    pub const fn ping() -> Self {
-        Self::Ping
+    pub const fn ping(seq: u64, server_timestamp: u64) -> Self {
+        Self::Ping(SubscribeUpdatePing {
+            seq,
+            server_timestamp,
+            stats: None,
+        })
     }
 
     pub const fn pong(id: i32) -> Self {
@@ -379,6 +630,77 @@ impl FilteredUpdateOneof {
     pub const fn entry(message: Arc<MessageEntry>) -> Self {
         Self::Entry(FilteredUpdateEntry(message))
     }
+
+    pub const fn end_of_startup() -> Self {
+        Self::EndOfStartup
+    }
+
+    pub const fn lost(message: SubscribeUpdateLost) -> Self {
+        Self::Lost(message)
+    }
+
+    pub const fn transaction_slot_done(message: SubscribeUpdateTransactionSlotDone) -> Self {
+        Self::TransactionSlotDone(message)
+    }
+
+    pub const fn replay_done(message: SubscribeUpdateReplayDone) -> Self {
+        Self::ReplayDone(message)
+    }
+
+    pub const fn block_diagnostic(message: Arc<MessageBlockDiagnostic>) -> Self {
+        Self::BlockDiagnostic(message)
+    }
+
+    pub const fn quota_warning(message: SubscribeUpdateQuotaWarning) -> Self {
+        Self::QuotaWarning(message)
+    }
+
+    pub fn stream_info(message: SubscribeUpdateStreamInfo) -> Self {
+        Self::StreamInfo(Box::new(message))
+    }
+
+    pub const fn votes(message: Arc<MessageVotes>) -> Self {
+        Self::Votes(message)
+    }
+
+    pub const fn slot_stats(message: Arc<MessageSlotStats>) -> Self {
+        Self::SlotStats(message)
+    }
+
+    pub const fn maintenance(message: Arc<SubscribeUpdateMaintenance>) -> Self {
+        Self::Maintenance(message)
+    }
+
+    pub fn rollback(message: SubscribeUpdateRollback) -> Self {
+        Self::Rollback(Box::new(message))
+    }
+
+    /// Slot of account, transaction, entry, block and aggregated updates, `None` for slot
+    /// statuses and updates which control the stream
+    pub fn get_data_slot(&self) -> Option<u64> {
+        match self {
+            Self::Account(msg) => Some(msg.slot),
+            Self::Transaction(msg) => Some(msg.slot),
+            Self::TransactionStatus(msg) => Some(msg.slot),
+            Self::Block(msg) => Some(msg.meta.slot),
+            Self::BlockMeta(msg) => Some(msg.slot),
+            Self::Entry(msg) => Some(msg.0.slot),
+            Self::BlockDiagnostic(msg) => Some(msg.slot),
+            Self::Votes(msg) => Some(msg.slot),
+            Self::SlotStats(msg) => Some(msg.slot),
+            Self::Slot(_)
+            | Self::Ping(_)
+            | Self::Pong(_)
+            | Self::EndOfStartup
+            | Self::Lost(_)
+            | Self::TransactionSlotDone(_)
+            | Self::ReplayDone(_)
+            | Self::QuotaWarning(_)
+            | Self::StreamInfo(_)
+            | Self::Maintenance(_)
+            | Self::Rollback(_) => None,
+        }
+    }
 }
 
 impl prost::Message for FilteredUpdateOneof {
@@ -389,13 +711,24 @@ impl prost::Message for FilteredUpdateOneof {
             Self::Transaction(msg) => message::encode(4u32, msg, buf),
             Self::TransactionStatus(msg) => message::encode(10u32, msg, buf),
             Self::Block(msg) => message::encode(5u32, msg, buf),
-            Self::Ping => {
-                encode_key(6u32, WireType::LengthDelimited, buf);
-                encode_varint(0, buf);
-            }
+            Self::Ping(msg) => message::encode(6u32, msg, buf),
             Self::Pong(msg) => message::encode(9u32, msg, buf),
             Self::BlockMeta(msg) => message::encode(7u32, &msg.block_meta, buf),
             Self::Entry(msg) => message::encode(8u32, msg, buf),
+            Self::EndOfStartup => {
+                encode_key(12u32, WireType::LengthDelimited, buf);
+                encode_varint(0, buf);
+            }
+            Self::Lost(msg) => message::encode(14u32, msg, buf),
+            Self::TransactionSlotDone(msg) => message::encode(15u32, msg, buf),
+            Self::ReplayDone(msg) => message::encode(16u32, msg, buf),
+            Self::BlockDiagnostic(msg) => message::encode(17u32, &msg.diagnostic, buf),
+            Self::QuotaWarning(msg) => message::encode(18u32, msg, buf),
+            Self::StreamInfo(msg) => message::encode(19u32, msg.as_ref(), buf),
+            Self::Votes(msg) => message::encode(20u32, &msg.votes, buf),
+            Self::SlotStats(msg) => message::encode(23u32, &msg.stats, buf),
+            Self::Maintenance(msg) => message::encode(24u32, msg.as_ref(), buf),
+            Self::Rollback(msg) => message::encode(25u32, msg.as_ref(), buf),
         }
     }
 
@@ -406,10 +739,21 @@ impl prost::Message for FilteredUpdateOneof {
             Self::Transaction(msg) => message::encoded_len(4u32, msg),
             Self::TransactionStatus(msg) => message::encoded_len(10u32, msg),
             Self::Block(msg) => message::encoded_len(5u32, msg),
-            Self::Ping => key_len(6u32) + encoded_len_varint(0),
+            Self::Ping(msg) => message::encoded_len(6u32, msg),
             Self::Pong(msg) => message::encoded_len(9u32, msg),
             Self::BlockMeta(msg) => message::encoded_len(7u32, &msg.block_meta),
             Self::Entry(msg) => message::encoded_len(8u32, msg),
+            Self::EndOfStartup => key_len(12u32) + encoded_len_varint(0),
+            Self::Lost(msg) => message::encoded_len(14u32, msg),
+            Self::TransactionSlotDone(msg) => message::encoded_len(15u32, msg),
+            Self::ReplayDone(msg) => message::encoded_len(16u32, msg),
+            Self::BlockDiagnostic(msg) => message::encoded_len(17u32, &msg.diagnostic),
+            Self::QuotaWarning(msg) => message::encoded_len(18u32, msg),
+            Self::StreamInfo(msg) => message::encoded_len(19u32, msg.as_ref()),
+            Self::Votes(msg) => message::encoded_len(20u32, &msg.votes),
+            Self::SlotStats(msg) => message::encoded_len(23u32, &msg.stats),
+            Self::Maintenance(msg) => message::encoded_len(24u32, msg.as_ref()),
+            Self::Rollback(msg) => message::encoded_len(25u32, msg.as_ref()),
         }
     }
 
@@ -434,23 +778,31 @@ pub struct FilteredUpdateAccount {
     pub slot: u64,
     pub is_startup: bool,
     pub data_slice: FilterAccountsDataSlice,
+    pub confirmed_at_slot: Option<u64>,
+    pub finalized_at_slot: Option<u64>,
 }
 
 impl prost::Message for FilteredUpdateAccount {
     fn encode_raw(&self, buf: &mut impl BufMut) {
-        Self::account_encode_raw(1u32, &self.account, &self.data_slice, buf);
+        Self::account_encode_raw(1u32, &self.account, self.is_startup, &self.data_slice, buf);
         if self.slot != 0u64 {
             ::prost::encoding::uint64::encode(2u32, &self.slot, buf);
         }
         if self.is_startup {
             ::prost::encoding::bool::encode(3u32, &self.is_startup, buf);
         }
+        if let Some(slot) = &self.confirmed_at_slot {
+            ::prost::encoding::uint64::encode(4u32, slot, buf);
+        }
+        if let Some(slot) = &self.finalized_at_slot {
+            ::prost::encoding::uint64::encode(5u32, slot, buf);
+        }
     }
 
     fn encoded_len(&self) -> usize {
         prost_field_encoded_len(
             1u32,
-            Self::account_encoded_len(&self.account, &self.data_slice),
+            Self::account_encoded_len(&self.account, self.is_startup, &self.data_slice),
         ) + if self.slot != 0u64 {
             ::prost::encoding::uint64::encoded_len(2u32, &self.slot)
         } else {
@@ -459,7 +811,14 @@ impl prost::Message for FilteredUpdateAccount {
             ::prost::encoding::bool::encoded_len(3u32, &self.is_startup)
         } else {
             0
-        }
+        } + self
+            .confirmed_at_slot
+            .as_ref()
+            .map_or(0, |slot| ::prost::encoding::uint64::encoded_len(4u32, slot))
+            + self
+                .finalized_at_slot
+                .as_ref()
+                .map_or(0, |slot| ::prost::encoding::uint64::encoded_len(5u32, slot))
     }
 
     fn merge_field(
@@ -481,11 +840,15 @@ impl FilteredUpdateAccount {
     fn account_encode_raw(
         tag: u32,
         account: &MessageAccountInfo,
+        is_startup: bool,
         data_slice: &FilterAccountsDataSlice,
         buf: &mut impl BufMut,
     ) {
         encode_key(tag, WireType::LengthDelimited, buf);
-        encode_varint(Self::account_encoded_len(account, data_slice) as u64, buf);
+        encode_varint(
+            Self::account_encoded_len(account, is_startup, data_slice) as u64,
+            buf,
+        );
 
         prost_bytes_encode_raw(1u32, account.pubkey.as_ref(), buf);
         if account.lamports != 0u64 {
@@ -505,10 +868,12 @@ impl FilteredUpdateAccount {
         if let Some(value) = &account.txn_signature {
             prost_bytes_encode_raw(8u32, value.as_ref(), buf);
         }
+        ::prost::encoding::int32::encode(9u32, &(account.get_origin(is_startup) as i32), buf);
     }
 
     fn account_encoded_len(
         account: &MessageAccountInfo,
+        is_startup: bool,
         data_slice: &FilterAccountsDataSlice,
     ) -> usize {
         let data_len = data_slice.get_slice_len(&account.data);
@@ -543,6 +908,7 @@ impl FilteredUpdateAccount {
             + account
                 .txn_signature
                 .map_or(0, |sig| prost_bytes_encoded_len(8u32, sig.as_ref()))
+            + ::prost::encoding::int32::encoded_len(9u32, &(account.get_origin(is_startup) as i32))
     }
 }
 
@@ -619,23 +985,37 @@ impl prost::Message for FilteredUpdateSlot {
 pub struct FilteredUpdateTransaction {
     pub transaction: Arc<MessageTransactionInfo>,
     pub slot: u64,
+    pub meta_sections: TransactionMetaSections,
+    pub resolved_accounts: bool,
 }
 
 impl prost::Message for FilteredUpdateTransaction {
     fn encode_raw(&self, buf: &mut impl BufMut) {
-        Self::tx_encode_raw(1u32, &self.transaction, buf);
+        Self::tx_encode_raw(
+            1u32,
+            &self.transaction,
+            self.meta_sections,
+            self.resolved_accounts,
+            buf,
+        );
         if self.slot != 0u64 {
             ::prost::encoding::uint64::encode(2u32, &self.slot, buf);
         }
     }
 
     fn encoded_len(&self) -> usize {
-        prost_field_encoded_len(1u32, Self::tx_encoded_len(&self.transaction))
-            + if self.slot != 0u64 {
-                ::prost::encoding::uint64::encoded_len(2u32, &self.slot)
-            } else {
-                0
-            }
+        prost_field_encoded_len(
+            1u32,
+            Self::tx_encoded_len(
+                &self.transaction,
+                self.meta_sections,
+                self.resolved_accounts,
+            ),
+        ) + if self.slot != 0u64 {
+            ::prost::encoding::uint64::encoded_len(2u32, &self.slot)
+        } else {
+            0
+        }
     }
 
     fn merge_field(
@@ -654,40 +1034,58 @@ impl prost::Message for FilteredUpdateTransaction {
 }
 
 impl FilteredUpdateTransaction {
-    fn tx_encode_raw(tag: u32, tx: &MessageTransactionInfo, buf: &mut impl BufMut) {
+    fn tx_encode_raw(
+        tag: u32,
+        tx: &MessageTransactionInfo,
+        meta_sections: TransactionMetaSections,
+        resolved_accounts: bool,
+        buf: &mut impl BufMut,
+    ) {
+        let cache = tx.encoded.get();
+        let parts = cache.get_parts(tx);
+        let resolved = if resolved_accounts {
+            Self::tx_encoded_resolved_accounts(&cache, tx)
+        } else {
+            &[]
+        };
         encode_key(tag, WireType::LengthDelimited, buf);
-        encode_varint(Self::tx_encoded_len(tx) as u64, buf);
-
-        let index = tx.index as u64;
-
-        prost_bytes_encode_raw(1u32, tx.signature.as_ref(), buf);
-        if tx.is_vote {
-            ::prost::encoding::bool::encode(2u32, &tx.is_vote, buf);
-        }
-        message::encode(3u32, &tx.transaction, buf);
-        message::encode(4u32, &tx.meta, buf);
-        if index != 0u64 {
-            ::prost::encoding::uint64::encode(5u32, &index, buf);
-        }
+        encode_varint(
+            (parts.encoded_len(meta_sections) + resolved.len()) as u64,
+            buf,
+        );
+        parts.encode(meta_sections, buf);
+        buf.put_slice(resolved);
     }
 
-    fn tx_encoded_len(tx: &MessageTransactionInfo) -> usize {
-        let index = tx.index as u64;
-
-        prost_bytes_encoded_len(1u32, tx.signature.as_ref())
-            + if tx.is_vote {
-                ::prost::encoding::bool::encoded_len(2u32, &tx.is_vote)
-            } else {
-                0
-            }
-            + message::encoded_len(3u32, &tx.transaction)
-            + message::encoded_len(4u32, &tx.meta)
-            + if index != 0u64 {
-                ::prost::encoding::uint64::encoded_len(5u32, &index)
+    /// Transaction is encoded once by parts and shared by all clients
+    fn tx_encoded_len(
+        tx: &MessageTransactionInfo,
+        meta_sections: TransactionMetaSections,
+        resolved_accounts: bool,
+    ) -> usize {
+        let cache = tx.encoded.get();
+        cache.get_parts(tx).encoded_len(meta_sections)
+            + if resolved_accounts {
+                Self::tx_encoded_resolved_accounts(&cache, tx).len()
             } else {
                 0
             }
     }
+
+    /// Repeated `resolved_accounts` field, appended to the encoded transaction, so the
+    /// transaction itself is shared with subscriptions which do not request it. Accounts
+    /// are resolved only once the first subscription requests them.
+    fn tx_encoded_resolved_accounts<'a>(
+        cache: &'a MessageTransactionInfoEncodedCache,
+        tx: &MessageTransactionInfo,
+    ) -> &'a [u8] {
+        cache.get_or_init_resolved_accounts(|| {
+            let accounts = tx.get_resolved_accounts();
+            let mut buf = Vec::with_capacity(message::encoded_len_repeated(6u32, &accounts));
+            message::encode_repeated(6u32, &accounts, &mut buf);
+            buf
+        })
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -763,6 +1161,8 @@ pub struct FilteredUpdateBlock {
     pub accounts: Vec<Arc<MessageAccountInfo>>,
     pub accounts_data_slice: FilterAccountsDataSlice,
     pub entries: Vec<Arc<MessageEntry>>,
+    pub is_partial: bool,
+    pub missing_transaction_count: u64,
 }
 
 impl prost::Message for FilteredUpdateBlock {
@@ -783,7 +1183,13 @@ impl prost::Message for FilteredUpdateBlock {
             message::encode(5u32, msg, buf);
         }
         for tx in &self.transactions {
-            FilteredUpdateTransaction::tx_encode_raw(6u32, tx.as_ref(), buf);
+            FilteredUpdateTransaction::tx_encode_raw(
+                6u32,
+                tx.as_ref(),
+                TransactionMetaSections::ALL,
+                false,
+                buf,
+            );
         }
         if self.meta.parent_slot != 0u64 {
             ::prost::encoding::uint64::encode(7u32, &self.meta.parent_slot, buf);
@@ -801,6 +1207,7 @@ impl prost::Message for FilteredUpdateBlock {
             FilteredUpdateAccount::account_encode_raw(
                 11u32,
                 account.as_ref(),
+                false,
                 &self.accounts_data_slice,
                 buf,
             );
@@ -816,6 +1223,25 @@ impl prost::Message for FilteredUpdateBlock {
             );
             FilteredUpdateEntry::entry_encode_raw(entry, buf);
         }
+        if self.is_partial {
+            ::prost::encoding::bool::encode(14u32, &self.is_partial, buf);
+        }
+        if self.missing_transaction_count != 0u64 {
+            ::prost::encoding::uint64::encode(15u32, &self.missing_transaction_count, buf);
+        }
+        if self.meta.parent_slot_unknown {
+            ::prost::encoding::bool::encode(16u32, &self.meta.parent_slot_unknown, buf);
+        }
+        if self.meta.executed_transaction_count_unknown {
+            ::prost::encoding::bool::encode(
+                17u32,
+                &self.meta.executed_transaction_count_unknown,
+                buf,
+            );
+        }
+        if self.meta.entries_count_unknown {
+            ::prost::encoding::bool::encode(18u32, &self.meta.entries_count_unknown, buf);
+        }
     }
 
     fn encoded_len(&self) -> usize {
@@ -843,7 +1269,11 @@ impl prost::Message for FilteredUpdateBlock {
                 .as_ref()
                 .map_or(0, |msg| message::encoded_len(5u32, msg))
             + prost_repeated_encoded_len_map!(6u32, self.transactions, |tx| {
-                FilteredUpdateTransaction::tx_encoded_len(tx.as_ref())
+                FilteredUpdateTransaction::tx_encoded_len(
+                    tx.as_ref(),
+                    TransactionMetaSections::ALL,
+                    false,
+                )
             })
             + if self.meta.parent_slot != 0u64 {
                 ::prost::encoding::uint64::encoded_len(7u32, &self.meta.parent_slot)
@@ -868,6 +1298,7 @@ impl prost::Message for FilteredUpdateBlock {
             + prost_repeated_encoded_len_map!(11u32, self.accounts, |account| {
                 FilteredUpdateAccount::account_encoded_len(
                     account.as_ref(),
+                    false,
                     &self.accounts_data_slice,
                 )
             })
@@ -879,6 +1310,34 @@ impl prost::Message for FilteredUpdateBlock {
             + prost_repeated_encoded_len_map!(13u32, self.entries, |entry| {
                 FilteredUpdateEntry::entry_encoded_len(entry)
             })
+            + if self.is_partial {
+                ::prost::encoding::bool::encoded_len(14u32, &self.is_partial)
+            } else {
+                0
+            }
+            + if self.missing_transaction_count != 0u64 {
+                ::prost::encoding::uint64::encoded_len(15u32, &self.missing_transaction_count)
+            } else {
+                0
+            }
+            + if self.meta.parent_slot_unknown {
+                ::prost::encoding::bool::encoded_len(16u32, &self.meta.parent_slot_unknown)
+            } else {
+                0
+            }
+            + if self.meta.executed_transaction_count_unknown {
+                ::prost::encoding::bool::encoded_len(
+                    17u32,
+                    &self.meta.executed_transaction_count_unknown,
+                )
+            } else {
+                0
+            }
+            + if self.meta.entries_count_unknown {
+                ::prost::encoding::bool::encoded_len(18u32, &self.meta.entries_count_unknown)
+            } else {
+                0
+            }
     }
 
     fn merge_field(
@@ -979,17 +1438,29 @@ pub mod tests {
     #![cfg_attr(feature = "plugin-bench", allow(dead_code))]
     #![cfg_attr(feature = "plugin-bench", allow(unused_imports))]
     use {
-        super::{FilteredUpdate, FilteredUpdateBlock, FilteredUpdateFilters, FilteredUpdateOneof},
+        super::{
+            EncodedBytesCounter, FilteredUpdate, FilteredUpdateBlock, FilteredUpdateFilters,
+            FilteredUpdateOneof,
+        },
         crate::{
             convert_to,
-            geyser::{SubscribeUpdate, SubscribeUpdateBlockMeta},
+            geyser::{
+                subscribe_update::UpdateOneof, CommitmentLevel as CommitmentLevelProto,
+                CommitmentSlot, SubscribeUpdate, SubscribeUpdateBlockMeta, SubscribeUpdateLost,
+                SubscribeUpdateMaintenance, SubscribeUpdateQuotaWarning, SubscribeUpdateReplayDone,
+                SubscribeUpdateRollback, SubscribeUpdateSlotStats, SubscribeUpdateSlotStatsOwner,
+                SubscribeUpdateStreamInfo, SubscribeUpdateTransactionSlotDone,
+                SubscribeUpdateVotes, SubscribeUpdateVotesEntry,
+            },
             plugin::{
                 filter::{name::FilterName, FilterAccountsDataSlice},
                 message::{
-                    MessageAccount, MessageAccountInfo, MessageBlockMeta, MessageEntry,
-                    MessageSlot, MessageTransaction, MessageTransactionInfo, SlotStatus,
+                    Message, MessageAccount, MessageAccountInfo, MessageBlock, MessageBlockMeta,
+                    MessageEntry, MessageSlot, MessageSlotStats, MessageTransaction,
+                    MessageTransactionInfo, MessageVotes, SlotStatus, TransactionMetaSections,
                 },
             },
+            solana::storage::confirmed_block,
         },
         prost::Message as _,
         prost_011::Message as _,
@@ -1057,9 +1528,10 @@ pub mod tests {
                                     owner,
                                     executable,
                                     rent_epoch,
-                                    data: data.clone(),
+                                    data: data.clone().into(),
                                     write_version,
                                     txn_signature,
+                                    base58: Default::default(),
                                 }));
                             }
                         }
@@ -1075,14 +1547,18 @@ pub mod tests {
         for account in create_accounts_raw() {
             for slot in [0, 42] {
                 for is_startup in [true, false] {
-                    for data_slice in create_account_data_slice() {
-                        let msg = MessageAccount {
-                            account: Arc::clone(&account),
-                            slot,
-                            is_startup,
-                            created_at: Timestamp::from(SystemTime::now()),
-                        };
-                        vec.push((msg, data_slice));
+                    for commitment_slot in [None, Some(0), Some(43)] {
+                        for data_slice in create_account_data_slice() {
+                            let msg = MessageAccount {
+                                account: Arc::clone(&account),
+                                slot,
+                                is_startup,
+                                created_at: Timestamp::from(SystemTime::now()),
+                                confirmed_at_slot: commitment_slot,
+                                finalized_at_slot: commitment_slot.map(|slot| slot + 1),
+                            };
+                            vec.push((msg, data_slice));
+                        }
                     }
                 }
             }
@@ -1173,8 +1649,12 @@ pub mod tests {
                             is_vote: true,
                             transaction: convert_to::create_transaction(&transaction),
                             meta: convert_to::create_transaction_meta(&tx.meta),
+                            token_balances_none: tx.meta.pre_token_balances.is_none()
+                                && tx.meta.post_token_balances.is_none(),
+                            rewards_none: tx.meta.rewards.is_none(),
                             index,
                             account_keys: HashSet::new(),
+                            encoded: Default::default(),
                         }
                     })
                     .map(Arc::new)
@@ -1197,6 +1677,9 @@ pub mod tests {
                         block_height: block.block_height.map(convert_to::create_block_height),
                         executed_transaction_count: transactions.len() as u64,
                         entries_count: entries.len() as u64,
+                        parent_slot_unknown: false,
+                        executed_transaction_count_unknown: false,
+                        entries_count_unknown: false,
                     },
                     created_at: Timestamp::from(SystemTime::now()),
                 };
@@ -1219,14 +1702,28 @@ pub mod tests {
                                 accounts: accounts.clone(),
                                 accounts_data_slice: data_slice.clone(),
                                 entries: entries.clone(),
+                                is_partial: false,
+                                missing_transaction_count: 0,
                             },
                             FilteredUpdateBlock {
                                 meta: Arc::clone(&block_meta2),
                                 transactions: transactions.clone(),
                                 updated_account_count: accounts.len() as u64,
                                 accounts: accounts.clone(),
+                                accounts_data_slice: data_slice.clone(),
+                                entries: entries.clone(),
+                                is_partial: false,
+                                missing_transaction_count: 0,
+                            },
+                            FilteredUpdateBlock {
+                                meta: Arc::clone(&block_meta1),
+                                transactions: transactions.iter().skip(1).cloned().collect(),
+                                updated_account_count: accounts.len() as u64,
+                                accounts: accounts.clone(),
                                 accounts_data_slice: data_slice,
                                 entries: entries.clone(),
+                                is_partial: true,
+                                missing_transaction_count: 1,
                             },
                         ]
                     })
@@ -1235,10 +1732,15 @@ pub mod tests {
     }
 
     fn encode_decode_cmp(filters: &[&str], message: FilteredUpdateOneof) {
-        let msg = FilteredUpdate {
+        let mut msg = FilteredUpdate {
             filters: create_message_filters(filters),
             message,
-            created_at: Timestamp::from(SystemTime::now()),
+            created_at: Some(Timestamp::from(SystemTime::now())),
+            warnings: vec![],
+            sequence: 0,
+            dropped_since_last: 0,
+            dropped_batches_since_last: 0,
+            encoded_bytes: None,
         };
         let update = msg.as_subscribe_update();
         assert_eq!(msg.encoded_len(), update.encoded_len());
@@ -1251,6 +1753,18 @@ pub mod tests {
                 .map(|msg| msg.as_subscribe_update()),
             Ok(update)
         );
+
+        msg.created_at = None;
+        msg.warnings = vec!["warning".to_owned()];
+        msg.sequence = u64::MAX;
+        msg.dropped_since_last = 42;
+        msg.dropped_batches_since_last = 3;
+        let update = msg.as_subscribe_update();
+        assert_eq!(msg.encoded_len(), update.encoded_len());
+        assert_eq!(
+            SubscribeUpdate::decode(msg.encode_to_vec().as_slice()).expect("failed to decode"),
+            update
+        );
     }
 
     #[test]
@@ -1311,6 +1825,220 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn test_message_transaction_meta_sections() {
+        let mut compared = 0;
+        for transaction in load_predefined_transactions() {
+            let msg = MessageTransaction {
+                transaction: Arc::clone(&transaction),
+                slot: 42,
+                created_at: Timestamp::from(SystemTime::now()),
+            };
+            for meta_sections in [
+                TransactionMetaSections::LOGS,
+                TransactionMetaSections::TOKEN_BALANCES,
+                TransactionMetaSections::INNER_INSTRUCTIONS,
+                TransactionMetaSections::RETURN_DATA,
+                TransactionMetaSections::REWARDS,
+            ] {
+                let excluded = TransactionMetaSections::ALL.with(meta_sections, false);
+                encode_decode_cmp(
+                    &["123"],
+                    FilteredUpdateOneof::transaction_with_meta_sections(&msg, excluded),
+                );
+            }
+
+            let full =
+                FilteredUpdate::new_empty(FilteredUpdateOneof::transaction(&msg)).encode_to_vec();
+            let stripped =
+                FilteredUpdate::new_empty(FilteredUpdateOneof::transaction_with_meta_sections(
+                    &msg,
+                    TransactionMetaSections::NONE,
+                ))
+                .encode_to_vec();
+            // `*_none` flags are encoded instead of empty sections
+            let meta = &transaction.meta;
+            if !meta.log_messages.is_empty() || !meta.inner_instructions.is_empty() {
+                assert!(stripped.len() < full.len());
+                compared += 1;
+            }
+            let cache = transaction.encoded.get();
+            assert!(cache.is_initialized());
+
+            // encodings are created again after clear
+            transaction.encoded.clear();
+            assert!(transaction.encoded.is_empty());
+            let encoded =
+                FilteredUpdate::new_empty(FilteredUpdateOneof::transaction(&msg)).encode_to_vec();
+            assert_eq!(encoded, full);
+            assert!(!Arc::ptr_eq(&cache, &transaction.encoded.get()));
+
+            let SubscribeUpdate {
+                update_oneof: Some(UpdateOneof::Transaction(update)),
+                ..
+            } = SubscribeUpdate::decode(stripped.as_slice()).expect("failed to decode")
+            else {
+                panic!("expected transaction update");
+            };
+            let info = update.transaction.expect("transaction");
+            assert!(info.token_balances_none && info.rewards_none);
+            let meta = info.meta.expect("meta");
+            assert!(meta.log_messages.is_empty() && meta.log_messages_none);
+            assert!(meta.inner_instructions.is_empty() && meta.inner_instructions_none);
+            assert!(meta.return_data.is_none() && meta.return_data_none);
+            assert!(meta.pre_token_balances.is_empty() && meta.post_token_balances.is_empty());
+            assert!(meta.rewards.is_empty());
+        }
+        assert!(compared > 0);
+    }
+
+    #[test]
+    fn test_message_transaction_meta_fields() {
+        // no `..Default::default()`: new field of `TransactionStatusMeta` fails the build here
+        // until it is encoded by `MessageTransactionInfoEncodedParts`
+        let meta = confirmed_block::TransactionStatusMeta {
+            err: Some(confirmed_block::TransactionError { err: vec![1] }),
+            fee: 2,
+            pre_balances: vec![3],
+            post_balances: vec![4],
+            inner_instructions: vec![confirmed_block::InnerInstructions {
+                index: 5,
+                instructions: vec![Default::default()],
+            }],
+            inner_instructions_none: false,
+            log_messages: vec!["6".to_owned()],
+            log_messages_none: false,
+            pre_token_balances: vec![confirmed_block::TokenBalance {
+                account_index: 7,
+                ..Default::default()
+            }],
+            post_token_balances: vec![confirmed_block::TokenBalance {
+                account_index: 8,
+                ..Default::default()
+            }],
+            rewards: vec![confirmed_block::Reward {
+                lamports: 9,
+                ..Default::default()
+            }],
+            loaded_writable_addresses: vec![vec![10; 32]],
+            loaded_readonly_addresses: vec![vec![11; 32]],
+            return_data: Some(confirmed_block::ReturnData {
+                program_id: vec![12; 32],
+                data: vec![13],
+            }),
+            return_data_none: false,
+            compute_units_consumed: Some(14),
+        };
+        for (token_balances_none, rewards_none) in [(false, false), (true, true)] {
+            let msg = MessageTransaction {
+                transaction: Arc::new(MessageTransactionInfo {
+                    signature: Signature::from([15; 64]),
+                    is_vote: true,
+                    transaction: Default::default(),
+                    meta: meta.clone(),
+                    token_balances_none,
+                    rewards_none,
+                    index: 16,
+                    account_keys: HashSet::new(),
+                    encoded: Default::default(),
+                }),
+                slot: 42,
+                created_at: Timestamp::from(SystemTime::now()),
+            };
+            encode_decode_cmp(&["123"], FilteredUpdateOneof::transaction(&msg));
+            for meta_sections in [
+                TransactionMetaSections::NONE,
+                TransactionMetaSections::LOGS,
+                TransactionMetaSections::TOKEN_BALANCES,
+                TransactionMetaSections::REWARDS,
+            ] {
+                encode_decode_cmp(
+                    &["123"],
+                    FilteredUpdateOneof::transaction_with_meta_sections(&msg, meta_sections),
+                );
+            }
+
+            // every field is sent with all sections
+            let update = FilteredUpdate::new_empty(FilteredUpdateOneof::transaction(&msg));
+            let Some(UpdateOneof::Transaction(update)) =
+                SubscribeUpdate::decode(update.encode_to_vec().as_slice())
+                    .expect("failed to decode")
+                    .update_oneof
+            else {
+                panic!("expected transaction update");
+            };
+            let info = update.transaction.expect("transaction");
+            assert_eq!(info.meta.as_ref(), Some(&meta));
+            assert_eq!(
+                (info.token_balances_none, info.rewards_none),
+                (token_balances_none, rewards_none)
+            );
+        }
+    }
+
+    #[test]
+    fn test_message_transaction_resolved_accounts() {
+        for transaction in load_predefined_transactions() {
+            assert!(!transaction.get_resolved_accounts().is_empty());
+            let msg = MessageTransaction {
+                transaction,
+                slot: 42,
+                created_at: Timestamp::from(SystemTime::now()),
+            };
+            for resolved_accounts in [false, true] {
+                let mut message = FilteredUpdateOneof::transaction(&msg);
+                if let FilteredUpdateOneof::Transaction(msg) = &mut message {
+                    msg.resolved_accounts = resolved_accounts;
+                }
+                encode_decode_cmp(&["123"], message);
+            }
+        }
+    }
+
+    #[test]
+    fn test_message_transaction_encoded_once() {
+        let transaction = load_predefined_transactions()
+            .into_iter()
+            .next()
+            .expect("predefined transaction");
+        assert!(transaction.encoded.is_empty());
+        let msg = MessageTransaction {
+            transaction: Arc::clone(&transaction),
+            slot: 42,
+            created_at: Timestamp::from(SystemTime::now()),
+        };
+        let update = FilteredUpdate::new_empty(FilteredUpdateOneof::transaction(&msg));
+        let encoded = update.encode_to_vec();
+        assert!(transaction.encoded.get().is_initialized());
+        assert_eq!(update.encode_to_vec(), encoded);
+
+        // cache is not copied, changed fields are encoded
+        let mut transaction = (*transaction).clone();
+        assert!(transaction.encoded.is_empty());
+        transaction.index += 1;
+        let msg = MessageTransaction {
+            transaction: Arc::new(transaction),
+            slot: 42,
+            created_at: msg.created_at,
+        };
+        let update = FilteredUpdate {
+            created_at: update.created_at,
+            ..FilteredUpdate::new_empty(FilteredUpdateOneof::transaction(&msg))
+        };
+        assert_ne!(update.encode_to_vec(), encoded);
+    }
+
+    #[test]
+    fn test_message_encoded_bytes() {
+        let counter = EncodedBytesCounter::default();
+        let mut update = FilteredUpdate::new_empty(FilteredUpdateOneof::pong(42));
+        update.encoded_bytes = Some(counter.clone());
+        let first = update.encode_to_vec();
+        let second = update.encode_to_vec();
+        assert_eq!(first, second);
+        assert_eq!(counter.get(), (first.len() + second.len()) as u64);
+    }
+
     #[test]
     fn test_message_block() {
         for block in load_predefined_blocks() {
@@ -1320,7 +2048,200 @@ pub mod tests {
 
     #[test]
     fn test_message_ping() {
-        encode_decode_cmp(&["123"], FilteredUpdateOneof::Ping)
+        encode_decode_cmp(&["123"], FilteredUpdateOneof::ping(0, 0));
+        encode_decode_cmp(&["123"], FilteredUpdateOneof::ping(42, 1_700_000_000_000));
+    }
+
+    #[test]
+    fn test_message_end_of_startup() {
+        encode_decode_cmp(&[], FilteredUpdateOneof::EndOfStartup)
+    }
+
+    #[test]
+    fn test_message_lost() {
+        encode_decode_cmp(
+            &[],
+            FilteredUpdateOneof::lost(SubscribeUpdateLost::default()),
+        );
+        encode_decode_cmp(
+            &[],
+            FilteredUpdateOneof::lost(SubscribeUpdateLost {
+                skipped_batches: 3,
+                skipped_updates: 42,
+                from_slot: 100,
+                to_slot: 105,
+            }),
+        );
+    }
+
+    #[test]
+    fn test_message_replay_done() {
+        encode_decode_cmp(
+            &[],
+            FilteredUpdateOneof::replay_done(SubscribeUpdateReplayDone {
+                from_slot: 100,
+                to_slot: 105,
+                replayed_updates: 42,
+            }),
+        );
+    }
+
+    #[test]
+    fn test_message_quota_warning() {
+        encode_decode_cmp(
+            &[],
+            FilteredUpdateOneof::quota_warning(SubscribeUpdateQuotaWarning {
+                used_bytes: 42_000,
+                limit_bytes: 50_000,
+                resets_at: Some(Timestamp::from(SystemTime::now())),
+                used_messages: 42,
+                limit_messages: 0,
+            }),
+        );
+    }
+
+    #[test]
+    fn test_message_maintenance() {
+        for (expected_at_slot, cancelled) in [(None, false), (Some(42), false), (Some(42), true)] {
+            encode_decode_cmp(
+                &[],
+                FilteredUpdateOneof::maintenance(Arc::new(SubscribeUpdateMaintenance {
+                    reason: "validator restart".to_owned(),
+                    expected_at_slot,
+                    drain_deadline_secs: 60,
+                    announced_at: 1_700_000_000_000,
+                    cancelled,
+                })),
+            );
+        }
+    }
+
+    #[test]
+    fn test_message_rollback() {
+        encode_decode_cmp(
+            &["finalized"],
+            FilteredUpdateOneof::rollback(SubscribeUpdateRollback {
+                slot: 41,
+                finalized_slot: 42,
+                pubkeys: (0..3)
+                    .map(|_| Pubkey::new_unique().to_bytes().to_vec())
+                    .collect(),
+            }),
+        );
+    }
+
+    #[test]
+    fn test_message_stream_info() {
+        encode_decode_cmp(
+            &[],
+            FilteredUpdateOneof::stream_info(SubscribeUpdateStreamInfo {
+                processed: Some(CommitmentSlot {
+                    slot: 42,
+                    updated_at: Some(Timestamp::from(SystemTime::now())),
+                }),
+                confirmed: None,
+                finalized: None,
+                version: "1.0.0".to_owned(),
+                commitment: CommitmentLevelProto::Confirmed as i32,
+                accounts: vec!["accounts".to_owned()],
+                slots: vec!["slots".to_owned()],
+                ..Default::default()
+            }),
+        );
+    }
+
+    #[test]
+    fn test_message_votes() {
+        encode_decode_cmp(
+            &["votes"],
+            FilteredUpdateOneof::votes(Arc::new(MessageVotes::new(SubscribeUpdateVotes {
+                slot: 100,
+                from_slot: 100,
+                to_slot: 100,
+                entries: vec![],
+            }))),
+        );
+        encode_decode_cmp(
+            &["votes"],
+            FilteredUpdateOneof::votes(Arc::new(MessageVotes::new(SubscribeUpdateVotes {
+                slot: 105,
+                from_slot: 100,
+                to_slot: 103,
+                entries: vec![
+                    SubscribeUpdateVotesEntry {
+                        vote_pubkey: Pubkey::new_unique().to_bytes().to_vec(),
+                        voted_on_slot: 102,
+                        latency_slots: 1,
+                        count: 3,
+                    },
+                    SubscribeUpdateVotesEntry {
+                        vote_pubkey: Pubkey::new_unique().to_bytes().to_vec(),
+                        voted_on_slot: 98,
+                        latency_slots: 4,
+                        count: 1,
+                    },
+                ],
+            }))),
+        );
+    }
+
+    #[test]
+    fn test_message_slot_stats() {
+        encode_decode_cmp(
+            &["stats"],
+            FilteredUpdateOneof::slot_stats(Arc::new(MessageSlotStats::new(
+                SubscribeUpdateSlotStats {
+                    slot: 100,
+                    account_updates: 0,
+                    unique_accounts: 0,
+                    data_bytes: 0,
+                    top_owners: vec![],
+                },
+            ))),
+        );
+        encode_decode_cmp(
+            &["stats"],
+            FilteredUpdateOneof::slot_stats(Arc::new(MessageSlotStats::new(
+                SubscribeUpdateSlotStats {
+                    slot: 101,
+                    account_updates: 12,
+                    unique_accounts: 7,
+                    data_bytes: 4096,
+                    top_owners: vec![
+                        SubscribeUpdateSlotStatsOwner {
+                            owner: Pubkey::new_unique().to_bytes().to_vec(),
+                            account_updates: 9,
+                            data_bytes: 3000,
+                        },
+                        SubscribeUpdateSlotStatsOwner {
+                            owner: Pubkey::new_unique().to_bytes().to_vec(),
+                            account_updates: 3,
+                            data_bytes: 1096,
+                        },
+                    ],
+                },
+            ))),
+        );
+    }
+
+    #[test]
+    fn test_message_transaction_slot_done() {
+        encode_decode_cmp(
+            &["ordered"],
+            FilteredUpdateOneof::transaction_slot_done(SubscribeUpdateTransactionSlotDone {
+                slot: 100,
+                sent_count: 42,
+                best_effort: false,
+            }),
+        );
+        encode_decode_cmp(
+            &["ordered"],
+            FilteredUpdateOneof::transaction_slot_done(SubscribeUpdateTransactionSlotDone {
+                slot: 101,
+                sent_count: 0,
+                best_effort: true,
+            }),
+        );
     }
 
     #[test]
@@ -1342,4 +2263,105 @@ pub mod tests {
             encode_decode_cmp(&["123"], FilteredUpdateOneof::entry(entry));
         }
     }
+
+    fn assert_size_estimate(message: Message, update: FilteredUpdateOneof) {
+        let update = FilteredUpdate::new_empty(update);
+        let encoded = update.encoded_len();
+        for estimate in [message.get_size_estimate(), update.get_size_estimate()] {
+            assert!(
+                estimate.abs_diff(encoded) <= 96 + encoded / 20,
+                "estimate {estimate} bytes, encoded {encoded} bytes"
+            );
+        }
+    }
+
+    #[test]
+    fn test_message_size_estimate() {
+        for (msg, data_slice) in create_accounts() {
+            // estimate is for the whole account data
+            if data_slice.as_ref().is_empty() {
+                let update = FilteredUpdateOneof::account(&msg, data_slice);
+                assert_size_estimate(Message::Account(msg), update);
+            }
+        }
+
+        for transaction in load_predefined_transactions() {
+            let msg = MessageTransaction {
+                transaction,
+                slot: 42,
+                created_at: Timestamp::from(SystemTime::now()),
+            };
+            let update = FilteredUpdateOneof::transaction(&msg);
+            assert_size_estimate(Message::Transaction(msg), update);
+        }
+
+        for entry in create_entries() {
+            let update = FilteredUpdateOneof::entry(Arc::clone(&entry));
+            assert_size_estimate(Message::Entry(entry), update);
+        }
+
+        for block_meta in load_predefined_blockmeta() {
+            let update = FilteredUpdateOneof::block_meta(Arc::clone(&block_meta));
+            assert_size_estimate(Message::BlockMeta(block_meta), update);
+        }
+
+        for block in load_predefined_blocks() {
+            if block.accounts_data_slice.as_ref().is_empty() {
+                let msg = MessageBlock {
+                    meta: Arc::clone(&block.meta),
+                    transactions: block.transactions.clone(),
+                    updated_account_count: block.updated_account_count,
+                    accounts: block.accounts.clone(),
+                    entries: block.entries.clone(),
+                    is_partial: block.is_partial,
+                    missing_transaction_count: block.missing_transaction_count,
+                    created_at: Timestamp::from(SystemTime::now()),
+                };
+                let update = FilteredUpdateOneof::block(Box::new(block));
+                assert_size_estimate(Message::Block(Arc::new(msg)), update);
+            }
+        }
+
+        let msg = MessageSlot {
+            slot: 299888121,
+            parent: Some(299888120),
+            status: SlotStatus::Confirmed,
+            dead_error: None,
+            created_at: Timestamp::from(SystemTime::now()),
+        };
+        assert_size_estimate(Message::Slot(msg.clone()), FilteredUpdateOneof::slot(msg));
+    }
+
+    #[test]
+    fn test_filtered_update_size_estimate() {
+        let mut updates = create_accounts()
+            .into_iter()
+            .map(|(msg, data_slice)| FilteredUpdateOneof::account(&msg, data_slice))
+            .collect::<Vec<_>>();
+        updates.extend(
+            load_predefined_blocks()
+                .into_iter()
+                .map(|block| FilteredUpdateOneof::block(Box::new(block))),
+        );
+        updates.push(FilteredUpdateOneof::Lost(SubscribeUpdateLost {
+            from_slot: 42,
+            to_slot: 45,
+            skipped_updates: 3,
+            ..Default::default()
+        }));
+        for update in updates {
+            let mut update = FilteredUpdate::new(
+                create_message_filters(&["123", "456"]),
+                update,
+                Timestamp::from(SystemTime::now()),
+            );
+            update.warnings = vec!["warning".to_owned()];
+            let estimate = update.get_size_estimate();
+            let encoded = update.encoded_len();
+            assert!(
+                estimate.abs_diff(encoded) <= 96 + encoded / 20,
+                "estimate {estimate} bytes, encoded {encoded} bytes"
+            );
+        }
+    }
 }
@@ -3,5 +3,8 @@ mod filter;
 pub mod limits;
 pub mod message;
 pub mod name;
+pub mod pubkeys;
 
-pub use filter::{Filter, FilterAccountsDataSlice, FilterError, FilterResult};
+pub use filter::{
+    Filter, FilterAccountsDataSlice, FilterBuilder, FilterError, FilterResult, FilterSeenSignatures,
+};
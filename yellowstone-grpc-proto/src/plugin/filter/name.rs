@@ -1,9 +1,13 @@
-use std::{
-    borrow::Borrow,
-    collections::HashSet,
-    ops::Deref,
-    sync::Arc,
-    time::{Duration, Instant},
+use {
+    crate::plugin::filter::pubkeys::{FilterPubkeys, FilterPubkeysCache, FilterPubkeysIndex},
+    solana_sdk::pubkey::Pubkey,
+    std::{
+        borrow::Borrow,
+        collections::HashSet,
+        ops::Deref,
+        sync::Arc,
+        time::{Duration, Instant},
+    },
 };
 
 #[derive(Debug, thiserror::Error)]
@@ -49,6 +53,7 @@ impl FilterName {
     }
 }
 
+/// Filter names and Pubkey lists interned between filters of all clients
 #[derive(Debug)]
 pub struct FilterNames {
     name_size_limit: usize,
@@ -56,6 +61,7 @@ pub struct FilterNames {
     names_size_limit: usize,
     cleanup_ts: Instant,
     cleanup_interval: Duration,
+    pubkeys: FilterPubkeysCache,
 }
 
 impl FilterNames {
@@ -70,6 +76,7 @@ impl FilterNames {
             names_size_limit,
             cleanup_ts: Instant::now(),
             cleanup_interval,
+            pubkeys: FilterPubkeysCache::new(cleanup_interval),
         }
     }
 
@@ -80,6 +87,23 @@ impl FilterNames {
             self.names.retain(|name| !name.is_uniq());
             self.cleanup_ts = Instant::now();
         }
+        self.pubkeys.try_clean();
+    }
+
+    pub fn get_pubkeys(&mut self, pubkeys: Vec<Pubkey>) -> Arc<FilterPubkeys> {
+        self.pubkeys.get(pubkeys)
+    }
+
+    pub fn get_pubkeys_index(
+        &mut self,
+        lists: Vec<(FilterName, Vec<Pubkey>)>,
+    ) -> FilterPubkeysIndex {
+        self.pubkeys.get_index(lists)
+    }
+
+    /// Hits and misses of interned Pubkey lists and indexes since the previous call
+    pub fn take_pubkeys_stats(&mut self) -> (u64, u64) {
+        self.pubkeys.take_stats()
     }
 
     pub fn get(&mut self, name: &str) -> FilterNameResult<FilterName> {
@@ -0,0 +1,394 @@
+use {
+    crate::plugin::filter::name::FilterName,
+    solana_sdk::pubkey::Pubkey,
+    std::{
+        collections::{hash_map::RandomState, HashMap, HashSet},
+        hash::{BuildHasher, Hash, Hasher},
+        ops::Deref,
+        sync::{Arc, Weak},
+        time::{Duration, Instant},
+    },
+};
+
+/// Set of unique Pubkeys of one filter
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FilterPubkeys(HashSet<Pubkey>);
+
+impl Deref for FilterPubkeys {
+    type Target = HashSet<Pubkey>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FilterPubkeys {
+    pub fn new(pubkeys: Vec<Pubkey>) -> Self {
+        Self(pubkeys.into_iter().collect())
+    }
+
+    /// Returns `true` if any of `keys` is in the set, the smaller side is iterated
+    pub fn intersects(&self, keys: &HashSet<Pubkey>) -> bool {
+        if self.0.len() <= keys.len() {
+            self.0.iter().any(|pubkey| keys.contains(pubkey))
+        } else {
+            keys.iter().any(|pubkey| self.0.contains(pubkey))
+        }
+    }
+
+    /// Lists with duplicated Pubkeys are never equal to the set
+    fn is_equal(&self, pubkeys: &[Pubkey]) -> bool {
+        self.0.len() == pubkeys.len() && pubkeys.iter().all(|pubkey| self.0.contains(pubkey))
+    }
+}
+
+/// Pubkeys of all lists of one kind of filters with positions of lists which contain them,
+/// shared by subscriptions with the same lists regardless of names of filters
+#[derive(Debug, Default)]
+struct FilterPubkeysPositions {
+    positions: HashMap<Pubkey, Box<[u32]>>,
+    sizes: Box<[usize]>,
+}
+
+impl FilterPubkeysPositions {
+    fn new<'a>(lists: impl Iterator<Item = &'a [Pubkey]>) -> Self {
+        let mut positions = HashMap::<Pubkey, Vec<u32>>::new();
+        let mut sizes = vec![];
+        for (position, pubkeys) in lists.enumerate() {
+            let position = position as u32;
+            let mut size = 0;
+            for pubkey in pubkeys {
+                let list = positions.entry(*pubkey).or_default();
+                if list.last() != Some(&position) {
+                    list.push(position);
+                    size += 1;
+                }
+            }
+            sizes.push(size);
+        }
+        Self {
+            positions: positions
+                .into_iter()
+                .map(|(pubkey, positions)| (pubkey, positions.into_boxed_slice()))
+                .collect(),
+            sizes: sizes.into_boxed_slice(),
+        }
+    }
+
+    /// Lists with duplicated Pubkeys are never equal to the index
+    fn is_equal<'a>(&self, lists: impl ExactSizeIterator<Item = &'a [Pubkey]>) -> bool {
+        self.sizes.len() == lists.len()
+            && lists
+                .zip(self.sizes.iter())
+                .enumerate()
+                .all(|(position, (pubkeys, size))| {
+                    *size == pubkeys.len()
+                        && pubkeys.iter().all(|pubkey| {
+                            self.positions
+                                .get(pubkey)
+                                .is_some_and(|positions| positions.contains(&(position as u32)))
+                        })
+                })
+    }
+}
+
+/// Pubkeys of all filters of one kind of the subscription with names of filters which
+/// contain them, one lookup matches all filters. Pubkeys with positions of lists are interned,
+/// names of filters are kept per subscription.
+#[derive(Debug, Default, Clone)]
+pub struct FilterPubkeysIndex {
+    positions: Arc<FilterPubkeysPositions>,
+    names: Box<[FilterName]>,
+}
+
+impl FilterPubkeysIndex {
+    pub fn is_empty(&self) -> bool {
+        self.positions.positions.is_empty()
+    }
+
+    pub fn get(&self, pubkey: &Pubkey) -> impl Iterator<Item = &FilterName> {
+        self.positions
+            .positions
+            .get(pubkey)
+            .into_iter()
+            .flat_map(|positions| positions.iter())
+            .map(|position| &self.names[*position as usize])
+    }
+
+    pub fn pubkeys(&self) -> impl Iterator<Item = &Pubkey> {
+        self.positions.positions.keys()
+    }
+
+    /// Returns `true` if Pubkeys are shared with the other index
+    pub fn is_shared(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.positions, &other.positions)
+    }
+}
+
+/// Weak references to interned values grouped by hash
+#[derive(Debug)]
+struct Interned<T>(HashMap<u64, Vec<Weak<T>>>);
+
+impl<T> Default for Interned<T> {
+    fn default() -> Self {
+        Self(HashMap::new())
+    }
+}
+
+impl<T> Interned<T> {
+    /// Returns the shared value with `hash` which is equal to the requested one
+    fn find(&self, hash: u64, is_equal: impl Fn(&T) -> bool) -> Option<Arc<T>> {
+        self.0
+            .get(&hash)?
+            .iter()
+            .filter_map(Weak::upgrade)
+            .find(|shared| is_equal(shared))
+    }
+
+    fn insert(&mut self, hash: u64, value: T) -> Arc<T> {
+        let values = self.0.entry(hash).or_default();
+        values.retain(|shared| shared.strong_count() > 0);
+        let value = Arc::new(value);
+        values.push(Arc::downgrade(&value));
+        value
+    }
+
+    fn retain_alive(&mut self) {
+        self.0.retain(|_hash, values| {
+            values.retain(|value| value.strong_count() > 0);
+            !values.is_empty()
+        });
+    }
+
+    fn len(&self) -> usize {
+        self.0
+            .values()
+            .flatten()
+            .filter(|value| value.strong_count() > 0)
+            .count()
+    }
+}
+
+/// Interned Pubkey sets of filters and Pubkey indexes of subscriptions, clients with the same
+/// lists (in any order) share one allocation. Values are held with weak references, so
+/// memory is released with the last filter using it.
+#[derive(Debug)]
+pub struct FilterPubkeysCache {
+    lists: Interned<FilterPubkeys>,
+    indexes: Interned<FilterPubkeysPositions>,
+    hasher: RandomState,
+    hits: u64,
+    misses: u64,
+    cleanup_ts: Instant,
+    cleanup_interval: Duration,
+}
+
+impl FilterPubkeysCache {
+    pub fn new(cleanup_interval: Duration) -> Self {
+        Self {
+            lists: Interned::default(),
+            indexes: Interned::default(),
+            hasher: RandomState::new(),
+            hits: 0,
+            misses: 0,
+            cleanup_ts: Instant::now(),
+            cleanup_interval,
+        }
+    }
+
+    /// Remove released values
+    pub fn try_clean(&mut self) {
+        if self.cleanup_ts.elapsed() > self.cleanup_interval {
+            self.lists.retain_alive();
+            self.indexes.retain_alive();
+            self.cleanup_ts = Instant::now();
+        }
+    }
+
+    /// Hash doesn't depend on the order of Pubkeys and is computed without allocations, keys
+    /// of the hasher are random, so lists with colliding hashes can't be crafted
+    fn get_hash(&self, pubkeys: &[Pubkey]) -> u64 {
+        let sum = pubkeys.iter().fold(0u64, |sum, pubkey| {
+            sum.wrapping_add(self.hasher.hash_one(pubkey))
+        });
+        self.hasher.hash_one((sum, pubkeys.len()))
+    }
+
+    /// Empty sets are not interned
+    pub fn get(&mut self, pubkeys: Vec<Pubkey>) -> Arc<FilterPubkeys> {
+        if pubkeys.is_empty() {
+            return Arc::default();
+        }
+
+        let hash = self.get_hash(&pubkeys);
+        let found = self.lists.find(hash, |list| list.is_equal(&pubkeys));
+        self.add_stats(found.is_some());
+        found.unwrap_or_else(|| self.lists.insert(hash, FilterPubkeys::new(pubkeys)))
+    }
+
+    /// Empty indexes are not interned. Lists are ordered by their hashes, so positions don't
+    /// depend on the order of filters and only Pubkeys are compared.
+    pub fn get_index(&mut self, lists: Vec<(FilterName, Vec<Pubkey>)>) -> FilterPubkeysIndex {
+        if lists.iter().all(|(_name, pubkeys)| pubkeys.is_empty()) {
+            return FilterPubkeysIndex::default();
+        }
+
+        let mut order = lists
+            .iter()
+            .enumerate()
+            .map(|(index, (_name, pubkeys))| (self.get_hash(pubkeys), index))
+            .collect::<Vec<_>>();
+        order.sort_unstable();
+        let mut hasher = self.hasher.build_hasher();
+        for (hash, _index) in order.iter() {
+            hash.hash(&mut hasher);
+        }
+        let hash = hasher.finish();
+
+        let ordered = || {
+            order
+                .iter()
+                .map(|(_hash, index)| lists[*index].1.as_slice())
+        };
+        let found = self
+            .indexes
+            .find(hash, |positions| positions.is_equal(ordered()));
+        self.add_stats(found.is_some());
+        let positions = found.unwrap_or_else(|| {
+            self.indexes
+                .insert(hash, FilterPubkeysPositions::new(ordered()))
+        });
+        FilterPubkeysIndex {
+            positions,
+            names: order
+                .iter()
+                .map(|(_hash, index)| lists[*index].0.clone())
+                .collect(),
+        }
+    }
+
+    fn add_stats(&mut self, found: bool) {
+        if found {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+    }
+
+    /// Cache hits and misses since the previous call
+    pub fn take_stats(&mut self) -> (u64, u64) {
+        (
+            std::mem::take(&mut self.hits),
+            std::mem::take(&mut self.misses),
+        )
+    }
+
+    /// Number of sets and indexes in use
+    pub fn len(&self) -> usize {
+        self.lists.len() + self.indexes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{FilterPubkeys, FilterPubkeysCache, FilterPubkeysIndex},
+        crate::plugin::filter::name::FilterName,
+        solana_sdk::pubkey::Pubkey,
+        std::{collections::HashSet, sync::Arc, time::Duration},
+    };
+
+    #[test]
+    fn test_pubkeys_match() {
+        let keys = (0..8).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+        let pubkeys = FilterPubkeys::new(vec![keys[2], keys[0], keys[1], keys[0]]);
+        assert_eq!(pubkeys.len(), 3);
+        assert!(keys[..3].iter().all(|key| pubkeys.contains(key)));
+        assert!(!pubkeys.contains(&keys[3]));
+
+        let small = HashSet::from([keys[1], keys[7]]);
+        let large = keys[3..].iter().copied().collect::<HashSet<_>>();
+        assert!(pubkeys.intersects(&small));
+        assert!(!pubkeys.intersects(&large));
+    }
+
+    #[test]
+    fn test_cache_shared_and_released() {
+        let mut cache = FilterPubkeysCache::new(Duration::ZERO);
+        let keys = (0..100).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+        let mut reversed = keys.clone();
+        reversed.reverse();
+
+        let list1 = cache.get(keys.clone());
+        let list2 = cache.get(reversed);
+        assert!(Arc::ptr_eq(&list1, &list2));
+        let list3 = cache.get(keys[1..].to_vec());
+        assert!(!Arc::ptr_eq(&list1, &list3));
+        assert_eq!(cache.take_stats(), (1, 2));
+        assert_eq!(cache.take_stats(), (0, 0));
+        assert!(cache.get(vec![]).is_empty());
+        assert_eq!(cache.take_stats(), (0, 0));
+        assert_eq!(cache.len(), 2);
+
+        drop((list1, list2));
+        assert_eq!(cache.len(), 1);
+        cache.try_clean();
+        assert_eq!(cache.lists.0.len(), 1);
+
+        // released list is created again
+        let list1 = cache.get(keys);
+        assert_eq!(list1.len(), 100);
+        assert_eq!(cache.take_stats(), (0, 1));
+    }
+
+    #[test]
+    fn test_index_shared() {
+        let mut cache = FilterPubkeysCache::new(Duration::ZERO);
+        let keys = (0..4).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+        let [a, b, c] = ["a", "b", "c"].map(FilterName::new);
+        let get = |index: &FilterPubkeysIndex, key: &Pubkey| {
+            let mut names = index.get(key).cloned().collect::<Vec<_>>();
+            names.sort_unstable_by(|a, b| a.as_ref().cmp(b.as_ref()));
+            names
+        };
+
+        let index1 = cache.get_index(vec![
+            (a.clone(), keys[..3].to_vec()),
+            (b.clone(), keys[2..].to_vec()),
+        ]);
+        assert_eq!(get(&index1, &keys[0]), [a.clone()]);
+        assert_eq!(get(&index1, &keys[2]), [a.clone(), b.clone()]);
+        assert_eq!(get(&index1, &keys[3]), [b.clone()]);
+        assert!(get(&index1, &Pubkey::new_unique()).is_empty());
+
+        // same filters in another order
+        let index2 = cache.get_index(vec![
+            (b.clone(), keys[2..].iter().rev().copied().collect()),
+            (a.clone(), keys[..3].to_vec()),
+        ]);
+        assert!(index1.is_shared(&index2));
+        // same Pubkeys with other names, names are kept per subscription
+        let index3 = cache.get_index(vec![
+            (c.clone(), keys[..3].to_vec()),
+            (a.clone(), keys[2..].to_vec()),
+        ]);
+        assert!(index1.is_shared(&index3));
+        assert_eq!(get(&index3, &keys[0]), [c.clone()]);
+        assert_eq!(get(&index3, &keys[2]), [a.clone(), c.clone()]);
+        assert_eq!(get(&index3, &keys[3]), [a.clone()]);
+        // same Pubkeys in other lists
+        let index4 = cache.get_index(vec![(a, keys[..3].to_vec()), (b, keys[..3].to_vec())]);
+        assert!(!index1.is_shared(&index4));
+        // duplicated Pubkeys are not matched with the set
+        let index5 = cache.get_index(vec![(c, vec![keys[0], keys[1], keys[2], keys[0]])]);
+        assert!(!index1.is_shared(&index5));
+        assert_eq!(index5.pubkeys().count(), 3);
+        assert_eq!(cache.take_stats(), (2, 3));
+        assert!(cache.get_index(vec![]).is_empty());
+        assert_eq!(cache.len(), 3);
+    }
+}
@@ -1,37 +1,73 @@
 use {
     crate::{
+        convert_from::{self, convert_account_data},
         convert_to,
         geyser::{
-            subscribe_update::UpdateOneof, CommitmentLevel as CommitmentLevelProto,
+            subscribe_update::UpdateOneof, AccountOrigin, CommitmentLevel as CommitmentLevelProto,
             SlotStatus as SlotStatusProto, SubscribeUpdateAccount, SubscribeUpdateAccountInfo,
-            SubscribeUpdateBlock, SubscribeUpdateBlockMeta, SubscribeUpdateEntry,
-            SubscribeUpdateSlot, SubscribeUpdateTransaction, SubscribeUpdateTransactionInfo,
+            SubscribeUpdateBlock, SubscribeUpdateBlockDiagnostic, SubscribeUpdateBlockMeta,
+            SubscribeUpdateEntry, SubscribeUpdateSlot, SubscribeUpdateSlotStats,
+            SubscribeUpdateTransaction, SubscribeUpdateTransactionAccount,
+            SubscribeUpdateTransactionInfo, SubscribeUpdateVotes,
         },
+        plugin::filter::message::{prost_bytes_encode_raw, prost_field_encoded_len},
         solana::storage::confirmed_block,
     },
     agave_geyser_plugin_interface::geyser_plugin_interface::{
-        ReplicaAccountInfoV3, ReplicaBlockInfoV4, ReplicaEntryInfoV2, ReplicaTransactionInfoV2,
-        SlotStatus as GeyserSlotStatus,
+        ReplicaAccountInfoV3, ReplicaAccountInfoVersions, ReplicaBlockInfoV4,
+        ReplicaBlockInfoVersions, ReplicaEntryInfoV2, ReplicaEntryInfoVersions,
+        ReplicaTransactionInfoV2, ReplicaTransactionInfoVersions, SlotStatus as GeyserSlotStatus,
+    },
+    bytes::{BufMut, Bytes},
+    prost::{
+        encoding::{self, encoded_len_varint, WireType},
+        Message as _,
     },
     prost_types::Timestamp,
     solana_sdk::{
-        clock::Slot,
+        account::ReadableAccount,
+        clock::{Slot, UnixTimestamp},
         hash::{Hash, HASH_BYTES},
+        message::{
+            v0::{LoadedAddresses, LoadedMessage},
+            LegacyMessage, SanitizedMessage, VersionedMessage,
+        },
         pubkey::Pubkey,
+        reserved_account_keys::ReservedAccountKeys,
         signature::Signature,
+        system_program,
+        transaction::{SanitizedTransaction, VersionedTransaction},
+        vote::{self, instruction::VoteInstruction},
     },
+    solana_transaction_status::{Reward, TransactionStatusMeta},
     std::{
         collections::HashSet,
         ops::{Deref, DerefMut},
-        sync::Arc,
+        sync::{Arc, OnceLock, PoisonError, RwLock},
         time::SystemTime,
     },
 };
 
 type FromUpdateOneofResult<T> = Result<T, &'static str>;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+// Fixed parts of the estimated encoded size, see [`Message::get_size_estimate`]
+// oneof key and length prefix, slot and `created_at` of the update
+pub(crate) const UPDATE_OVERHEAD_BYTES: usize = 24;
+// pubkey, owner, txn_signature, integer fields and length prefixes of the account
+const ACCOUNT_INFO_OVERHEAD_BYTES: usize = 136;
+// signature, index and length prefixes of the transaction and meta
+const TRANSACTION_INFO_OVERHEAD_BYTES: usize = 80;
+// hash and integer fields of the entry
+pub(crate) const ENTRY_BYTES: usize = 48;
+// parent, status and dead error of the slot status
+pub(crate) const SLOT_BYTES: usize = 8;
+// slots of the rollback, every pubkey with key and length prefix
+const ROLLBACK_BYTES: usize = 16;
+const ROLLBACK_PUBKEY_BYTES: usize = 34;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum CommitmentLevel {
+    #[default]
     Processed,
     Confirmed,
     Finalized,
@@ -67,7 +103,7 @@ impl CommitmentLevel {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum SlotStatus {
     Processed,
     Confirmed,
@@ -169,6 +205,16 @@ impl MessageSlot {
         }
     }
 
+    pub fn new(slot: Slot, parent: Option<Slot>, status: SlotStatus) -> Self {
+        Self {
+            slot,
+            parent,
+            status,
+            dead_error: None,
+            created_at: Timestamp::from(SystemTime::now()),
+        }
+    }
+
     pub fn from_update_oneof(
         msg: &SubscribeUpdateSlot,
         created_at: Timestamp,
@@ -185,6 +231,21 @@ impl MessageSlot {
     }
 }
 
+/// Base58 strings of `pubkey` and `owner` of the account, encoded on the first use by prefix
+/// filters and shared by all subscriptions
+#[derive(Debug, Default, Clone)]
+pub struct MessageAccountInfoBase58 {
+    pubkey: OnceLock<String>,
+    owner: OnceLock<String>,
+}
+
+impl PartialEq for MessageAccountInfoBase58 {
+    // derived from `pubkey` and `owner`, which are compared instead
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct MessageAccountInfo {
     pub pubkey: Pubkey,
@@ -192,22 +253,73 @@ pub struct MessageAccountInfo {
     pub owner: Pubkey,
     pub executable: bool,
     pub rent_epoch: u64,
-    pub data: Vec<u8>,
+    pub data: Bytes,
     pub write_version: u64,
     pub txn_signature: Option<Signature>,
+    pub base58: MessageAccountInfoBase58,
 }
 
 impl MessageAccountInfo {
     pub fn from_geyser(info: &ReplicaAccountInfoV3<'_>) -> Self {
+        Self::from_geyser_versions(&ReplicaAccountInfoVersions::V0_0_3(info))
+    }
+
+    /// Fields missed in the older interface versions are set to `None`
+    pub fn from_geyser_versions(info: &ReplicaAccountInfoVersions<'_>) -> Self {
+        macro_rules! from_info {
+            ($info:ident, $txn_signature:expr) => {
+                Self {
+                    pubkey: Pubkey::try_from($info.pubkey).expect("valid Pubkey"),
+                    lamports: $info.lamports,
+                    owner: Pubkey::try_from($info.owner).expect("valid Pubkey"),
+                    executable: $info.executable,
+                    rent_epoch: $info.rent_epoch,
+                    data: Bytes::copy_from_slice($info.data),
+                    write_version: $info.write_version,
+                    txn_signature: $txn_signature,
+                    base58: MessageAccountInfoBase58::default(),
+                }
+            };
+        }
+
+        match info {
+            ReplicaAccountInfoVersions::V0_0_1(info) => from_info!(info, None),
+            ReplicaAccountInfoVersions::V0_0_2(info) => {
+                from_info!(info, info.txn_signature.copied())
+            }
+            ReplicaAccountInfoVersions::V0_0_3(info) => {
+                from_info!(info, info.txn.map(|txn| *txn.signature()))
+            }
+        }
+    }
+
+    pub fn from_account(
+        pubkey: Pubkey,
+        account: &impl ReadableAccount,
+        write_version: u64,
+        txn_signature: Option<Signature>,
+    ) -> Self {
         Self {
-            pubkey: Pubkey::try_from(info.pubkey).expect("valid Pubkey"),
-            lamports: info.lamports,
-            owner: Pubkey::try_from(info.owner).expect("valid Pubkey"),
-            executable: info.executable,
-            rent_epoch: info.rent_epoch,
-            data: info.data.into(),
-            write_version: info.write_version,
-            txn_signature: info.txn.map(|txn| *txn.signature()),
+            pubkey,
+            lamports: account.lamports(),
+            owner: *account.owner(),
+            executable: account.executable(),
+            rent_epoch: account.rent_epoch(),
+            data: Bytes::copy_from_slice(account.data()),
+            write_version,
+            txn_signature,
+            base58: MessageAccountInfoBase58::default(),
+        }
+    }
+
+    /// Writes without signature are not distinguished: rent collection, sysvars, etc
+    pub const fn get_origin(&self, is_startup: bool) -> AccountOrigin {
+        if is_startup {
+            AccountOrigin::Startup
+        } else if self.txn_signature.is_some() {
+            AccountOrigin::Transaction
+        } else {
+            AccountOrigin::Unknown
         }
     }
 
@@ -218,7 +330,7 @@ impl MessageAccountInfo {
             owner: Pubkey::try_from(msg.owner.as_slice()).map_err(|_| "invalid owner length")?,
             executable: msg.executable,
             rent_epoch: msg.rent_epoch,
-            data: msg.data,
+            data: convert_account_data(msg.data),
             write_version: msg.write_version,
             txn_signature: msg
                 .txn_signature
@@ -226,8 +338,27 @@ impl MessageAccountInfo {
                     Signature::try_from(sig.as_slice()).map_err(|_| "invalid signature length")
                 })
                 .transpose()?,
+            base58: MessageAccountInfoBase58::default(),
         })
     }
+
+    pub fn get_pubkey_base58(&self) -> &str {
+        self.base58.pubkey.get_or_init(|| self.pubkey.to_string())
+    }
+
+    pub fn get_owner_base58(&self) -> &str {
+        self.base58.owner.get_or_init(|| self.owner.to_string())
+    }
+
+    /// Approximate encoded size in bytes, data slices of filters are not applied
+    pub fn get_size_estimate(&self) -> usize {
+        Self::get_size_estimate_data_len(self.data.len())
+    }
+
+    /// Approximate encoded size in bytes with `data_len` bytes of the data
+    pub const fn get_size_estimate_data_len(data_len: usize) -> usize {
+        ACCOUNT_INFO_OVERHEAD_BYTES + encoded_len_varint(data_len as u64) + data_len
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -236,15 +367,44 @@ pub struct MessageAccount {
     pub slot: Slot,
     pub is_startup: bool,
     pub created_at: Timestamp,
+    /// Highest processed slot when the message was flushed with confirmed commitment
+    pub confirmed_at_slot: Option<Slot>,
+    /// Highest processed slot when the message was flushed with finalized commitment
+    pub finalized_at_slot: Option<Slot>,
 }
 
 impl MessageAccount {
     pub fn from_geyser(info: &ReplicaAccountInfoV3<'_>, slot: Slot, is_startup: bool) -> Self {
+        Self::from_geyser_versions(&ReplicaAccountInfoVersions::V0_0_3(info), slot, is_startup)
+    }
+
+    pub fn from_geyser_versions(
+        info: &ReplicaAccountInfoVersions<'_>,
+        slot: Slot,
+        is_startup: bool,
+    ) -> Self {
         Self {
-            account: Arc::new(MessageAccountInfo::from_geyser(info)),
+            account: Arc::new(MessageAccountInfo::from_geyser_versions(info)),
             slot,
             is_startup,
             created_at: Timestamp::from(SystemTime::now()),
+            confirmed_at_slot: None,
+            finalized_at_slot: None,
+        }
+    }
+
+    pub fn get_origin(&self) -> AccountOrigin {
+        self.account.get_origin(self.is_startup)
+    }
+
+    pub fn new(account: MessageAccountInfo, slot: Slot) -> Self {
+        Self {
+            account: Arc::new(account),
+            slot,
+            is_startup: false,
+            created_at: Timestamp::from(SystemTime::now()),
+            confirmed_at_slot: None,
+            finalized_at_slot: None,
         }
     }
 
@@ -259,37 +419,446 @@ impl MessageAccount {
             slot: msg.slot,
             is_startup: msg.is_startup,
             created_at,
+            confirmed_at_slot: msg.confirmed_at_slot,
+            finalized_at_slot: msg.finalized_at_slot,
         })
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct MessageTransactionInfo {
     pub signature: Signature,
     pub is_vote: bool,
     pub transaction: confirmed_block::Transaction,
     pub meta: confirmed_block::TransactionStatusMeta,
+    // not recorded by the node, `meta` has no `*_none` flags for them
+    pub token_balances_none: bool,
+    pub rewards_none: bool,
     pub index: usize,
     pub account_keys: HashSet<Pubkey>,
+    pub encoded: MessageTransactionInfoEncoded,
+}
+
+// `encoded` is derived from other fields and is not compared
+impl PartialEq for MessageTransactionInfo {
+    fn eq(&self, other: &Self) -> bool {
+        self.signature == other.signature
+            && self.is_vote == other.is_vote
+            && self.transaction == other.transaction
+            && self.meta == other.meta
+            && self.token_balances_none == other.token_balances_none
+            && self.rewards_none == other.rewards_none
+            && self.index == other.index
+            && self.account_keys == other.account_keys
+    }
+}
+
+/// Sections of `TransactionStatusMeta` which can be excluded by transactions filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TransactionMetaSections(u8);
+
+impl Default for TransactionMetaSections {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl TransactionMetaSections {
+    pub const NONE: Self = Self(0);
+    pub const LOGS: Self = Self(1 << 0);
+    pub const TOKEN_BALANCES: Self = Self(1 << 1);
+    pub const INNER_INSTRUCTIONS: Self = Self(1 << 2);
+    pub const RETURN_DATA: Self = Self(1 << 3);
+    pub const REWARDS: Self = Self(1 << 4);
+    pub const ALL: Self = Self((1 << 5) - 1);
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub const fn with(self, other: Self, enabled: bool) -> Self {
+        if enabled {
+            self.union(other)
+        } else {
+            Self(self.0 & !other.0)
+        }
+    }
+
+    /// Every section with the tag of its `*_none` flag
+    pub const SECTIONS: [(Self, TransactionMetaNoneTag); 5] = [
+        (Self::LOGS, TransactionMetaNoneTag::Meta(11)),
+        (Self::TOKEN_BALANCES, TransactionMetaNoneTag::Info(7)),
+        (Self::INNER_INSTRUCTIONS, TransactionMetaNoneTag::Meta(10)),
+        (Self::RETURN_DATA, TransactionMetaNoneTag::Meta(15)),
+        (Self::REWARDS, TransactionMetaNoneTag::Info(8)),
+    ];
+
+    /// `*_none` flag of the section
+    fn is_none(tx: &MessageTransactionInfo, section: Self) -> bool {
+        match section {
+            Self::LOGS => tx.meta.log_messages_none,
+            Self::TOKEN_BALANCES => tx.token_balances_none,
+            Self::INNER_INSTRUCTIONS => tx.meta.inner_instructions_none,
+            Self::RETURN_DATA => tx.meta.return_data_none,
+            Self::REWARDS => tx.rewards_none,
+            _ => false,
+        }
+    }
+
+    /// Remove excluded sections, `*_none` flags are set so clients can tell excluded
+    /// sections from empty ones
+    pub fn strip(self, info: &mut SubscribeUpdateTransactionInfo) {
+        if !self.contains(Self::TOKEN_BALANCES) {
+            info.token_balances_none = true;
+        }
+        if !self.contains(Self::REWARDS) {
+            info.rewards_none = true;
+        }
+        let Some(meta) = info.meta.as_mut() else {
+            return;
+        };
+        if !self.contains(Self::LOGS) {
+            meta.log_messages = vec![];
+            meta.log_messages_none = true;
+        }
+        if !self.contains(Self::TOKEN_BALANCES) {
+            meta.pre_token_balances = vec![];
+            meta.post_token_balances = vec![];
+        }
+        if !self.contains(Self::INNER_INSTRUCTIONS) {
+            meta.inner_instructions = vec![];
+            meta.inner_instructions_none = true;
+        }
+        if !self.contains(Self::RETURN_DATA) {
+            meta.return_data = None;
+            meta.return_data_none = true;
+        }
+        if !self.contains(Self::REWARDS) {
+            meta.rewards = vec![];
+        }
+    }
+}
+
+/// Field of the `*_none` flag of the meta section
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionMetaNoneTag {
+    /// Field of `TransactionStatusMeta`
+    Meta(u32),
+    /// Field of `SubscribeUpdateTransactionInfo`, for sections without the flag in upstream
+    /// `TransactionStatusMeta`
+    Info(u32),
+}
+
+/// Encoded `SubscribeUpdateTransactionInfo`, created on the first send and shared by all
+/// clients which receive the same transaction with the same meta sections. Not copied on
+/// clone, so fields of the cloned transaction can be changed. Dropped with [`Self::clear`]
+/// once the slot is sent to all commitments, while the transaction is kept for replay.
+#[derive(Debug, Default)]
+pub struct MessageTransactionInfoEncoded {
+    cache: RwLock<Option<Arc<MessageTransactionInfoEncodedCache>>>,
+    // computed once, estimate of every message is used several times on the way to clients
+    size_estimate: OnceLock<usize>,
+}
+
+impl Clone for MessageTransactionInfoEncoded {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl MessageTransactionInfoEncoded {
+    /// Encodings are created again after [`Self::clear`]
+    pub fn get(&self) -> Arc<MessageTransactionInfoEncodedCache> {
+        if let Some(cache) = self
+            .cache
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+        {
+            return Arc::clone(cache);
+        }
+        Arc::clone(
+            self.cache
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get_or_insert_with(Arc::default),
+        )
+    }
+
+    /// Drop encodings, clients which hold them finish encoding with their copy
+    pub fn clear(&self) {
+        self.cache
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_none()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MessageTransactionInfoEncodedCache {
+    parts: OnceLock<MessageTransactionInfoEncodedParts>,
+    // `resolved_accounts` field, appended to the transaction if requested by subscription
+    resolved_accounts: OnceLock<Vec<u8>>,
+}
+
+impl MessageTransactionInfoEncodedCache {
+    pub fn get_parts(&self, tx: &MessageTransactionInfo) -> &MessageTransactionInfoEncodedParts {
+        self.parts
+            .get_or_init(|| MessageTransactionInfoEncodedParts::new(tx))
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.parts.get().is_some()
+    }
+
+    pub fn get_or_init_resolved_accounts(&self, f: impl FnOnce() -> Vec<u8>) -> &[u8] {
+        self.resolved_accounts.get_or_init(f)
+    }
+}
+
+/// `SubscribeUpdateTransactionInfo` encoded by parts: every meta section is encoded once
+/// and the parts are concatenated for the meta sections of the subscription
+#[derive(Debug)]
+pub struct MessageTransactionInfoEncodedParts {
+    // fields of `SubscribeUpdateTransactionInfo` except `meta`
+    info: Vec<u8>,
+    // fields of `TransactionStatusMeta` not included into sections
+    meta: Vec<u8>,
+    // fields of every section and its `*_none` flag, in order of
+    // `TransactionMetaSections::SECTIONS`
+    sections: [(Vec<u8>, bool); TransactionMetaSections::SECTIONS.len()],
+}
+
+impl MessageTransactionInfoEncodedParts {
+    fn new(tx: &MessageTransactionInfo) -> Self {
+        let index = tx.index as u64;
+        let mut info = vec![];
+        prost_bytes_encode_raw(1u32, tx.signature.as_ref(), &mut info);
+        if tx.is_vote {
+            encoding::bool::encode(2u32, &tx.is_vote, &mut info);
+        }
+        encoding::message::encode(3u32, &tx.transaction, &mut info);
+        if index != 0u64 {
+            encoding::uint64::encode(5u32, &index, &mut info);
+        }
+
+        let meta = &tx.meta;
+        let mut common = vec![];
+        if let Some(err) = &meta.err {
+            encoding::message::encode(1u32, err, &mut common);
+        }
+        if meta.fee != 0u64 {
+            encoding::uint64::encode(2u32, &meta.fee, &mut common);
+        }
+        encoding::uint64::encode_packed(3u32, &meta.pre_balances, &mut common);
+        encoding::uint64::encode_packed(4u32, &meta.post_balances, &mut common);
+        encoding::bytes::encode_repeated(12u32, &meta.loaded_writable_addresses, &mut common);
+        encoding::bytes::encode_repeated(13u32, &meta.loaded_readonly_addresses, &mut common);
+        if let Some(compute_units_consumed) = &meta.compute_units_consumed {
+            encoding::uint64::encode(16u32, compute_units_consumed, &mut common);
+        }
+
+        Self {
+            info,
+            meta: common,
+            sections: TransactionMetaSections::SECTIONS.map(|(section, _none_tag)| {
+                let mut buf = vec![];
+                match section {
+                    TransactionMetaSections::LOGS => {
+                        encoding::string::encode_repeated(6u32, &meta.log_messages, &mut buf);
+                    }
+                    TransactionMetaSections::TOKEN_BALANCES => {
+                        encoding::message::encode_repeated(
+                            7u32,
+                            &meta.pre_token_balances,
+                            &mut buf,
+                        );
+                        encoding::message::encode_repeated(
+                            8u32,
+                            &meta.post_token_balances,
+                            &mut buf,
+                        );
+                    }
+                    TransactionMetaSections::INNER_INSTRUCTIONS => {
+                        encoding::message::encode_repeated(
+                            5u32,
+                            &meta.inner_instructions,
+                            &mut buf,
+                        );
+                    }
+                    TransactionMetaSections::RETURN_DATA => {
+                        if let Some(return_data) = &meta.return_data {
+                            encoding::message::encode(14u32, return_data, &mut buf);
+                        }
+                    }
+                    TransactionMetaSections::REWARDS => {
+                        encoding::message::encode_repeated(9u32, &meta.rewards, &mut buf);
+                    }
+                    _ => unreachable!("unknown meta section"),
+                }
+                (buf, TransactionMetaSections::is_none(tx, section))
+            }),
+        }
+    }
+
+    /// Length of encoded `SubscribeUpdateTransactionInfo` without the field key and length
+    pub fn encoded_len(&self, sections: TransactionMetaSections) -> usize {
+        self.info.len()
+            + prost_field_encoded_len(4u32, self.meta_encoded_len(sections))
+            + self
+                .get_none_tags(sections, false)
+                .map(|tag| encoding::bool::encoded_len(tag, &true))
+                .sum::<usize>()
+    }
+
+    pub fn encode(&self, sections: TransactionMetaSections, buf: &mut impl BufMut) {
+        buf.put_slice(&self.info);
+        encoding::encode_key(4u32, WireType::LengthDelimited, buf);
+        encoding::encode_varint(self.meta_encoded_len(sections) as u64, buf);
+        buf.put_slice(&self.meta);
+        for ((section, _none_tag), (encoded, _none)) in TransactionMetaSections::SECTIONS
+            .iter()
+            .zip(self.sections.iter())
+        {
+            if sections.contains(*section) {
+                buf.put_slice(encoded);
+            }
+        }
+        for tag in self.get_none_tags(sections, true) {
+            encoding::bool::encode(tag, &true, buf);
+        }
+        for tag in self.get_none_tags(sections, false) {
+            encoding::bool::encode(tag, &true, buf);
+        }
+    }
+
+    fn meta_encoded_len(&self, sections: TransactionMetaSections) -> usize {
+        TransactionMetaSections::SECTIONS
+            .iter()
+            .zip(self.sections.iter())
+            .filter(|((section, _none_tag), _encoded)| sections.contains(*section))
+            .map(|(_section, (encoded, _none))| encoded.len())
+            .chain(
+                self.get_none_tags(sections, true)
+                    .map(|tag| encoding::bool::encoded_len(tag, &true)),
+            )
+            .sum::<usize>()
+            + self.meta.len()
+    }
+
+    /// Tags of `*_none` flags which are set: sections not recorded by the node or excluded,
+    /// flags of `TransactionStatusMeta` or `SubscribeUpdateTransactionInfo`
+    fn get_none_tags(
+        &self,
+        sections: TransactionMetaSections,
+        meta: bool,
+    ) -> impl Iterator<Item = u32> + '_ {
+        TransactionMetaSections::SECTIONS
+            .iter()
+            .zip(self.sections.iter())
+            .filter(move |((section, _none_tag), (_encoded, none))| {
+                *none || !sections.contains(*section)
+            })
+            .filter_map(move |((_section, none_tag), _encoded)| match *none_tag {
+                TransactionMetaNoneTag::Meta(tag) if meta => Some(tag),
+                TransactionMetaNoneTag::Info(tag) if !meta => Some(tag),
+                _ => None,
+            })
+    }
+}
+
+/// Reserved accounts are demoted to readonly, keys of all features are used
+fn reserved_account_keys() -> &'static HashSet<Pubkey> {
+    static KEYS: OnceLock<HashSet<Pubkey>> = OnceLock::new();
+    KEYS.get_or_init(|| ReservedAccountKeys::new_all_activated().active)
 }
 
 impl MessageTransactionInfo {
     pub fn from_geyser(info: &ReplicaTransactionInfoV2<'_>) -> Self {
-        let account_keys = info
-            .transaction
-            .message()
-            .account_keys()
+        Self::from_geyser_versions(&ReplicaTransactionInfoVersions::V0_0_2(info))
+    }
+
+    /// Index is set to `0` for `ReplicaTransactionInfoVersions::V0_0_1`
+    pub fn from_geyser_versions(info: &ReplicaTransactionInfoVersions<'_>) -> Self {
+        match info {
+            ReplicaTransactionInfoVersions::V0_0_1(info) => Self::from_sanitized(
+                info.signature,
+                info.is_vote,
+                info.transaction,
+                info.transaction_status_meta,
+                0,
+            ),
+            ReplicaTransactionInfoVersions::V0_0_2(info) => Self::from_sanitized(
+                info.signature,
+                info.is_vote,
+                info.transaction,
+                info.transaction_status_meta,
+                info.index,
+            ),
+        }
+    }
+
+    fn from_sanitized(
+        signature: &Signature,
+        is_vote: bool,
+        transaction: &SanitizedTransaction,
+        meta: &TransactionStatusMeta,
+        index: usize,
+    ) -> Self {
+        let message = transaction.message();
+        let account_keys = message.account_keys().iter().copied().collect();
+
+        Self {
+            signature: *signature,
+            is_vote,
+            transaction: convert_to::create_transaction(transaction),
+            meta: convert_to::create_transaction_meta(meta),
+            token_balances_none: meta.pre_token_balances.is_none()
+                && meta.post_token_balances.is_none(),
+            rewards_none: meta.rewards.is_none(),
+            index,
+            account_keys,
+            encoded: MessageTransactionInfoEncoded::default(),
+        }
+    }
+
+    pub fn from_transaction(
+        transaction: &VersionedTransaction,
+        meta: &TransactionStatusMeta,
+        is_vote: bool,
+        index: usize,
+    ) -> Self {
+        let account_keys = transaction
+            .message
+            .static_account_keys()
             .iter()
+            .chain(meta.loaded_addresses.writable.iter())
+            .chain(meta.loaded_addresses.readonly.iter())
             .copied()
             .collect();
 
         Self {
-            signature: *info.signature,
-            is_vote: info.is_vote,
-            transaction: convert_to::create_transaction(info.transaction),
-            meta: convert_to::create_transaction_meta(info.transaction_status_meta),
-            index: info.index,
+            signature: transaction.signatures.first().copied().unwrap_or_default(),
+            is_vote,
+            transaction: convert_to::create_transaction_versioned(transaction),
+            meta: convert_to::create_transaction_meta(meta),
+            token_balances_none: meta.pre_token_balances.is_none()
+                && meta.post_token_balances.is_none(),
+            rewards_none: meta.rewards.is_none(),
+            index,
             account_keys,
+            encoded: MessageTransactionInfoEncoded::default(),
         }
     }
 
@@ -302,8 +871,11 @@ impl MessageTransactionInfo {
                 .transaction
                 .ok_or("transaction message should be defined")?,
             meta: msg.meta.ok_or("meta message should be defined")?,
+            token_balances_none: msg.token_balances_none,
+            rewards_none: msg.rewards_none,
             index: msg.index as usize,
             account_keys: HashSet::new(),
+            encoded: MessageTransactionInfoEncoded::default(),
         })
     }
 
@@ -337,6 +909,171 @@ impl MessageTransactionInfo {
         self.account_keys = account_keys;
         Ok(())
     }
+
+    /// Accounts at the on-chain account index: static keys, then loaded writable and loaded
+    /// readonly addresses, duplicates are kept. Computed from the encoded message, so the
+    /// result does not depend on the source of the transaction: writable flags follow
+    /// `SanitizedMessage::is_writable` with all reserved account keys active.
+    pub fn get_resolved_accounts(&self) -> Vec<SubscribeUpdateTransactionAccount> {
+        let Some(message) = self
+            .transaction
+            .message
+            .clone()
+            .and_then(|message| convert_from::create_message(message).ok())
+        else {
+            return vec![];
+        };
+        let message = match message {
+            VersionedMessage::Legacy(message) => {
+                SanitizedMessage::Legacy(LegacyMessage::new(message, reserved_account_keys()))
+            }
+            VersionedMessage::V0(message) => {
+                let (Ok(writable), Ok(readonly)) = (
+                    convert_from::create_pubkey_vec(self.meta.loaded_writable_addresses.clone()),
+                    convert_from::create_pubkey_vec(self.meta.loaded_readonly_addresses.clone()),
+                ) else {
+                    return vec![];
+                };
+                SanitizedMessage::V0(LoadedMessage::new(
+                    message,
+                    LoadedAddresses { writable, readonly },
+                    reserved_account_keys(),
+                ))
+            }
+        };
+
+        message
+            .account_keys()
+            .iter()
+            .enumerate()
+            .map(|(index, pubkey)| SubscribeUpdateTransactionAccount {
+                pubkey: pubkey.as_ref().into(),
+                is_signer: message.is_signer(index),
+                is_writable: message.is_writable(index),
+            })
+            .collect()
+    }
+
+    /// Max lamports of system program `Transfer` / `TransferWithSeed` instructions, inner
+    /// instructions from the meta are checked with `include_inner`
+    pub fn get_max_transfer_lamports(&self, include_inner: bool) -> Option<u64> {
+        if !self.account_keys.contains(&system_program::ID) {
+            return None;
+        }
+
+        // indexes of loaded addresses follow static keys
+        let message = self.transaction.message.as_ref()?;
+        let is_system_program = |index: u32| {
+            message
+                .account_keys
+                .iter()
+                .chain(self.meta.loaded_writable_addresses.iter())
+                .chain(self.meta.loaded_readonly_addresses.iter())
+                .nth(index as usize)
+                .is_some_and(|pubkey| pubkey.as_slice() == system_program::ID.as_ref())
+        };
+
+        let instructions = message
+            .instructions
+            .iter()
+            .map(|ix| (ix.program_id_index, ix.data.as_slice()));
+        let inner_instructions = self
+            .meta
+            .inner_instructions
+            .iter()
+            .filter(|_| include_inner)
+            .flat_map(|ixs| ixs.instructions.iter())
+            .map(|ix| (ix.program_id_index, ix.data.as_slice()));
+        instructions
+            .chain(inner_instructions)
+            .filter(|(program_id_index, _data)| is_system_program(*program_id_index))
+            .filter_map(|(_program_id_index, data)| Self::decode_transfer_lamports(data))
+            .max()
+    }
+
+    /// Lamports of bincode encoded `SystemInstruction::Transfer` and `TransferWithSeed`,
+    /// in both cases lamports follow `u32` discriminant
+    fn decode_transfer_lamports(data: &[u8]) -> Option<u64> {
+        const TRANSFER: u32 = 2;
+        const TRANSFER_WITH_SEED: u32 = 11;
+
+        let discriminant = u32::from_le_bytes(data.get(0..4)?.try_into().ok()?);
+        if matches!(discriminant, TRANSFER | TRANSFER_WITH_SEED) {
+            Some(u64::from_le_bytes(data.get(4..12)?.try_into().ok()?))
+        } else {
+            None
+        }
+    }
+
+    /// Vote account and the last voted slot of the last simple vote instruction (`Vote`,
+    /// `UpdateVoteState`, `TowerSync` and their variants), `None` for failed transactions
+    pub fn get_vote(&self) -> Option<(Pubkey, Slot)> {
+        if !self.is_vote || self.meta.err.is_some() {
+            return None;
+        }
+
+        // indexes of loaded addresses follow static keys
+        let message = self.transaction.message.as_ref()?;
+        let get_pubkey = |index: usize| {
+            message
+                .account_keys
+                .iter()
+                .chain(self.meta.loaded_writable_addresses.iter())
+                .chain(self.meta.loaded_readonly_addresses.iter())
+                .nth(index)
+                .and_then(|pubkey| Pubkey::try_from(pubkey.as_slice()).ok())
+        };
+
+        message
+            .instructions
+            .iter()
+            .rev()
+            .filter(|ix| get_pubkey(ix.program_id_index as usize) == Some(vote::program::ID))
+            .find_map(|ix| {
+                let instruction = bincode::deserialize::<VoteInstruction>(&ix.data).ok()?;
+                if !instruction.is_simple_vote() {
+                    return None;
+                }
+                let vote_pubkey = get_pubkey(*ix.accounts.first()? as usize)?;
+                Some((vote_pubkey, instruction.last_voted_slot()?))
+            })
+    }
+
+    /// Max increase of account balance from pre and post balances of the meta
+    pub fn get_max_balance_increase(&self) -> Option<u64> {
+        self.meta
+            .pre_balances
+            .iter()
+            .zip(self.meta.post_balances.iter())
+            .filter_map(|(pre, post)| post.checked_sub(*pre))
+            .max()
+    }
+
+    /// Account keys without addresses loaded from lookup tables
+    pub fn static_account_keys(&self) -> HashSet<Pubkey> {
+        let mut account_keys = self.account_keys.clone();
+        for pubkey in self
+            .meta
+            .loaded_writable_addresses
+            .iter()
+            .chain(self.meta.loaded_readonly_addresses.iter())
+        {
+            if let Ok(pubkey) = Pubkey::try_from(pubkey.as_slice()) {
+                account_keys.remove(&pubkey);
+            }
+        }
+        account_keys
+    }
+
+    /// Approximate encoded size in bytes with all meta sections, resolved accounts are not
+    /// counted. Computed on the first call and cached with the encoded transaction.
+    pub fn get_size_estimate(&self) -> usize {
+        *self.encoded.size_estimate.get_or_init(|| {
+            TRANSACTION_INFO_OVERHEAD_BYTES
+                + self.transaction.encoded_len()
+                + self.meta.encoded_len()
+        })
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -348,8 +1085,20 @@ pub struct MessageTransaction {
 
 impl MessageTransaction {
     pub fn from_geyser(info: &ReplicaTransactionInfoV2<'_>, slot: Slot) -> Self {
+        Self::from_geyser_versions(&ReplicaTransactionInfoVersions::V0_0_2(info), slot)
+    }
+
+    pub fn from_geyser_versions(info: &ReplicaTransactionInfoVersions<'_>, slot: Slot) -> Self {
         Self {
-            transaction: Arc::new(MessageTransactionInfo::from_geyser(info)),
+            transaction: Arc::new(MessageTransactionInfo::from_geyser_versions(info)),
+            slot,
+            created_at: Timestamp::from(SystemTime::now()),
+        }
+    }
+
+    pub fn new(transaction: MessageTransactionInfo, slot: Slot) -> Self {
+        Self {
+            transaction: Arc::new(transaction),
             slot,
             created_at: Timestamp::from(SystemTime::now()),
         }
@@ -383,16 +1132,50 @@ pub struct MessageEntry {
 
 impl MessageEntry {
     pub fn from_geyser(info: &ReplicaEntryInfoV2) -> Self {
+        Self::from_geyser_versions(&ReplicaEntryInfoVersions::V0_0_2(info))
+    }
+
+    /// Starting transaction index is set to `0` for `ReplicaEntryInfoVersions::V0_0_1`
+    pub fn from_geyser_versions(info: &ReplicaEntryInfoVersions<'_>) -> Self {
+        macro_rules! from_info {
+            ($info:ident, $starting_transaction_index:expr) => {
+                Self::new(
+                    $info.slot,
+                    $info.index,
+                    $info.num_hashes,
+                    Hash::new_from_array(<[u8; HASH_BYTES]>::try_from($info.hash).unwrap()),
+                    $info.executed_transaction_count,
+                    $starting_transaction_index,
+                )
+            };
+        }
+
+        match info {
+            ReplicaEntryInfoVersions::V0_0_1(info) => from_info!(info, 0),
+            ReplicaEntryInfoVersions::V0_0_2(info) => from_info!(
+                info,
+                info.starting_transaction_index
+                    .try_into()
+                    .expect("failed convert usize to u64")
+            ),
+        }
+    }
+
+    pub fn new(
+        slot: Slot,
+        index: usize,
+        num_hashes: u64,
+        hash: Hash,
+        executed_transaction_count: u64,
+        starting_transaction_index: u64,
+    ) -> Self {
         Self {
-            slot: info.slot,
-            index: info.index,
-            num_hashes: info.num_hashes,
-            hash: Hash::new_from_array(<[u8; HASH_BYTES]>::try_from(info.hash).unwrap()),
-            executed_transaction_count: info.executed_transaction_count,
-            starting_transaction_index: info
-                .starting_transaction_index
-                .try_into()
-                .expect("failed convert usize to u64"),
+            slot,
+            index,
+            num_hashes,
+            hash,
+            executed_transaction_count,
+            starting_transaction_index,
             created_at: Timestamp::from(SystemTime::now()),
         }
     }
@@ -438,25 +1221,122 @@ impl DerefMut for MessageBlockMeta {
 
 impl MessageBlockMeta {
     pub fn from_geyser(info: &ReplicaBlockInfoV4<'_>) -> Self {
+        Self::from_geyser_versions(&ReplicaBlockInfoVersions::V0_0_4(info))
+    }
+
+    /// Fields missed in the older interface versions are set to default values and marked
+    /// with `*_unknown` flags
+    pub fn from_geyser_versions(info: &ReplicaBlockInfoVersions<'_>) -> Self {
+        macro_rules! from_info {
+            (
+                $info:ident,
+                $parent_slot:expr,
+                $parent_blockhash:expr,
+                $rewards:expr,
+                $num_partitions:expr,
+                $executed_transaction_count:expr,
+                $entries_count:expr
+            ) => {{
+                let parent_slot: Option<Slot> = $parent_slot;
+                let executed_transaction_count: Option<u64> = $executed_transaction_count;
+                let entries_count: Option<u64> = $entries_count;
+                Self {
+                    block_meta: SubscribeUpdateBlockMeta {
+                        parent_slot: parent_slot.unwrap_or_default(),
+                        slot: $info.slot,
+                        parent_blockhash: $parent_blockhash,
+                        blockhash: $info.blockhash.to_string(),
+                        rewards: Some(convert_to::create_rewards_obj($rewards, $num_partitions)),
+                        block_time: $info.block_time.map(convert_to::create_timestamp),
+                        block_height: $info.block_height.map(convert_to::create_block_height),
+                        executed_transaction_count: executed_transaction_count.unwrap_or_default(),
+                        entries_count: entries_count.unwrap_or_default(),
+                        parent_slot_unknown: parent_slot.is_none(),
+                        executed_transaction_count_unknown: executed_transaction_count.is_none(),
+                        entries_count_unknown: entries_count.is_none(),
+                    },
+                    created_at: Timestamp::from(SystemTime::now()),
+                }
+            }};
+        }
+
+        match info {
+            ReplicaBlockInfoVersions::V0_0_1(info) => {
+                from_info!(info, None, String::new(), info.rewards, None, None, None)
+            }
+            ReplicaBlockInfoVersions::V0_0_2(info) => from_info!(
+                info,
+                Some(info.parent_slot),
+                info.parent_blockhash.to_string(),
+                info.rewards,
+                None,
+                Some(info.executed_transaction_count),
+                None
+            ),
+            ReplicaBlockInfoVersions::V0_0_3(info) => from_info!(
+                info,
+                Some(info.parent_slot),
+                info.parent_blockhash.to_string(),
+                info.rewards,
+                None,
+                Some(info.executed_transaction_count),
+                Some(info.entry_count)
+            ),
+            ReplicaBlockInfoVersions::V0_0_4(info) => from_info!(
+                info,
+                Some(info.parent_slot),
+                info.parent_blockhash.to_string(),
+                &info.rewards.rewards,
+                info.rewards.num_partitions,
+                Some(info.executed_transaction_count),
+                Some(info.entry_count)
+            ),
+        }
+    }
+
+    /// Block meta without rewards, block time and block height, see `with_*` methods
+    pub fn new(
+        slot: Slot,
+        parent_slot: Slot,
+        blockhash: Hash,
+        parent_blockhash: Hash,
+        executed_transaction_count: u64,
+        entries_count: u64,
+    ) -> Self {
         Self {
             block_meta: SubscribeUpdateBlockMeta {
-                parent_slot: info.parent_slot,
-                slot: info.slot,
-                parent_blockhash: info.parent_blockhash.to_string(),
-                blockhash: info.blockhash.to_string(),
-                rewards: Some(convert_to::create_rewards_obj(
-                    &info.rewards.rewards,
-                    info.rewards.num_partitions,
-                )),
-                block_time: info.block_time.map(convert_to::create_timestamp),
-                block_height: info.block_height.map(convert_to::create_block_height),
-                executed_transaction_count: info.executed_transaction_count,
-                entries_count: info.entry_count,
+                parent_slot,
+                slot,
+                parent_blockhash: parent_blockhash.to_string(),
+                blockhash: blockhash.to_string(),
+                rewards: Some(convert_to::create_rewards_obj(&[], None)),
+                block_time: None,
+                block_height: None,
+                executed_transaction_count,
+                entries_count,
+                parent_slot_unknown: false,
+                executed_transaction_count_unknown: false,
+                entries_count_unknown: false,
             },
             created_at: Timestamp::from(SystemTime::now()),
         }
     }
 
+    pub fn with_rewards(mut self, rewards: &[Reward], num_partitions: Option<u64>) -> Self {
+        self.block_meta.rewards = Some(convert_to::create_rewards_obj(rewards, num_partitions));
+        self
+    }
+
+    pub const fn with_block_time(mut self, block_time: UnixTimestamp) -> Self {
+        self.block_meta.block_time = Some(convert_to::create_timestamp(block_time));
+        self
+    }
+
+    pub const fn with_block_height(mut self, block_height: u64) -> Self {
+        self.block_meta.block_height = Some(convert_to::create_block_height(block_height));
+        self
+    }
+
     pub const fn from_update_oneof(
         block_meta: SubscribeUpdateBlockMeta,
         created_at: Timestamp,
@@ -468,6 +1348,114 @@ impl MessageBlockMeta {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageBlockDiagnostic {
+    pub diagnostic: SubscribeUpdateBlockDiagnostic,
+    pub created_at: Timestamp,
+}
+
+impl Deref for MessageBlockDiagnostic {
+    type Target = SubscribeUpdateBlockDiagnostic;
+
+    fn deref(&self) -> &Self::Target {
+        &self.diagnostic
+    }
+}
+
+impl MessageBlockDiagnostic {
+    pub fn new(diagnostic: SubscribeUpdateBlockDiagnostic) -> Self {
+        Self {
+            diagnostic,
+            created_at: Timestamp::from(SystemTime::now()),
+        }
+    }
+
+    pub const fn from_update_oneof(
+        diagnostic: SubscribeUpdateBlockDiagnostic,
+        created_at: Timestamp,
+    ) -> Self {
+        Self {
+            diagnostic,
+            created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageVotes {
+    pub votes: SubscribeUpdateVotes,
+    pub created_at: Timestamp,
+}
+
+impl Deref for MessageVotes {
+    type Target = SubscribeUpdateVotes;
+
+    fn deref(&self) -> &Self::Target {
+        &self.votes
+    }
+}
+
+impl MessageVotes {
+    pub fn new(votes: SubscribeUpdateVotes) -> Self {
+        Self {
+            votes,
+            created_at: Timestamp::from(SystemTime::now()),
+        }
+    }
+
+    pub const fn from_update_oneof(votes: SubscribeUpdateVotes, created_at: Timestamp) -> Self {
+        Self { votes, created_at }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageSlotStats {
+    pub stats: SubscribeUpdateSlotStats,
+    pub created_at: Timestamp,
+}
+
+impl Deref for MessageSlotStats {
+    type Target = SubscribeUpdateSlotStats;
+
+    fn deref(&self) -> &Self::Target {
+        &self.stats
+    }
+}
+
+impl MessageSlotStats {
+    pub fn new(stats: SubscribeUpdateSlotStats) -> Self {
+        Self {
+            stats,
+            created_at: Timestamp::from(SystemTime::now()),
+        }
+    }
+
+    pub const fn from_update_oneof(stats: SubscribeUpdateSlotStats, created_at: Timestamp) -> Self {
+        Self { stats, created_at }
+    }
+}
+
+/// Account writes of the slot sent with `finalized` commitment, the slot is not an ancestor
+/// of the finalized `finalized_slot`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageRollback {
+    pub slot: Slot,
+    pub finalized_slot: Slot,
+    pub accounts: Vec<MessageAccount>,
+    pub created_at: Timestamp,
+}
+
+impl MessageRollback {
+    pub fn new(slot: Slot, finalized_slot: Slot, accounts: Vec<MessageAccount>) -> Self {
+        Self {
+            slot,
+            finalized_slot,
+            accounts,
+            created_at: Timestamp::from(SystemTime::now()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct MessageBlock {
     pub meta: Arc<MessageBlockMeta>,
@@ -475,6 +1463,8 @@ pub struct MessageBlock {
     pub updated_account_count: u64,
     pub accounts: Vec<Arc<MessageAccountInfo>>,
     pub entries: Vec<Arc<MessageEntry>>,
+    pub is_partial: bool,
+    pub missing_transaction_count: u64,
     pub created_at: Timestamp,
 }
 
@@ -491,6 +1481,8 @@ impl MessageBlock {
             updated_account_count: accounts.len() as u64,
             accounts,
             entries,
+            is_partial: false,
+            missing_transaction_count: 0,
             created_at: Timestamp::from(SystemTime::now()),
         }
     }
@@ -511,6 +1503,9 @@ impl MessageBlock {
                     parent_blockhash: msg.parent_blockhash,
                     executed_transaction_count: msg.executed_transaction_count,
                     entries_count: msg.entries_count,
+                    parent_slot_unknown: msg.parent_slot_unknown,
+                    executed_transaction_count_unknown: msg.executed_transaction_count_unknown,
+                    entries_count_unknown: msg.entries_count_unknown,
                 },
                 created_at,
             }),
@@ -530,6 +1525,8 @@ impl MessageBlock {
                 .iter()
                 .map(|entry| MessageEntry::from_update_oneof(entry, created_at).map(Arc::new))
                 .collect::<Result<Vec<_>, _>>()?,
+            is_partial: msg.is_partial,
+            missing_transaction_count: msg.missing_transaction_count,
             created_at,
         })
     }
@@ -543,6 +1540,10 @@ pub enum Message {
     Entry(Arc<MessageEntry>),
     BlockMeta(Arc<MessageBlockMeta>),
     Block(Arc<MessageBlock>),
+    BlockDiagnostic(Arc<MessageBlockDiagnostic>),
+    Votes(Arc<MessageVotes>),
+    SlotStats(Arc<MessageSlotStats>),
+    Rollback(Arc<MessageRollback>),
 }
 
 impl Message {
@@ -554,6 +1555,87 @@ impl Message {
             Self::Entry(msg) => msg.slot,
             Self::BlockMeta(msg) => msg.slot,
             Self::Block(msg) => msg.meta.slot,
+            Self::BlockDiagnostic(msg) => msg.slot,
+            Self::Votes(msg) => msg.slot,
+            Self::SlotStats(msg) => msg.slot,
+            Self::Rollback(msg) => msg.finalized_slot,
+        }
+    }
+
+    /// Name of the variant, used as `kind` field of tracing spans
+    pub const fn get_type(&self) -> &'static str {
+        match self {
+            Self::Slot(_) => "slot",
+            Self::Account(_) => "account",
+            Self::Transaction(_) => "transaction",
+            Self::Entry(_) => "entry",
+            Self::BlockMeta(_) => "block_meta",
+            Self::Block(_) => "block",
+            Self::BlockDiagnostic(_) => "block_diagnostic",
+            Self::Votes(_) => "votes",
+            Self::SlotStats(_) => "slot_stats",
+            Self::Rollback(_) => "rollback",
+        }
+    }
+
+    pub fn get_created_at(&self) -> Timestamp {
+        match self {
+            Self::Slot(msg) => msg.created_at,
+            Self::Account(msg) => msg.created_at,
+            Self::Transaction(msg) => msg.created_at,
+            Self::Entry(msg) => msg.created_at,
+            Self::BlockMeta(msg) => msg.created_at,
+            Self::Block(msg) => msg.created_at,
+            Self::BlockDiagnostic(msg) => msg.created_at,
+            Self::Votes(msg) => msg.created_at,
+            Self::SlotStats(msg) => msg.created_at,
+            Self::Rollback(msg) => msg.created_at,
+        }
+    }
+
+    /// Approximate size of the encoded update in bytes, computed without encoding: account
+    /// data and transaction sections are counted exactly, other fields as fixed overhead.
+    /// Used for all byte limits of buffered messages, so large account writes and
+    /// transactions with large meta are weighted accordingly.
+    pub fn get_size_estimate(&self) -> usize {
+        UPDATE_OVERHEAD_BYTES
+            + match self {
+                Self::Slot(_) => SLOT_BYTES,
+                Self::Account(msg) => msg.account.get_size_estimate(),
+                Self::Transaction(msg) => msg.transaction.get_size_estimate(),
+                Self::Entry(_) => ENTRY_BYTES,
+                Self::BlockMeta(msg) => msg.block_meta.encoded_len(),
+                Self::Block(msg) => {
+                    msg.meta.block_meta.encoded_len()
+                        + msg
+                            .transactions
+                            .iter()
+                            .map(|tx| tx.get_size_estimate())
+                            .sum::<usize>()
+                        + msg
+                            .accounts
+                            .iter()
+                            .map(|account| account.get_size_estimate())
+                            .sum::<usize>()
+                        + msg.entries.len() * ENTRY_BYTES
+                }
+                Self::BlockDiagnostic(msg) => msg.diagnostic.encoded_len(),
+                Self::Votes(msg) => msg.votes.encoded_len(),
+                Self::SlotStats(msg) => msg.stats.encoded_len(),
+                Self::Rollback(msg) => ROLLBACK_BYTES + msg.accounts.len() * ROLLBACK_PUBKEY_BYTES,
+            }
+    }
+
+    /// Drop shared encodings of transactions, see [`MessageTransactionInfoEncoded::clear`]
+    pub fn clear_encoded(&self) {
+        match self {
+            Self::Transaction(msg) => msg.transaction.encoded.clear(),
+            Self::Block(msg) => {
+                for tx in msg.transactions.iter() {
+                    tx.encoded.clear();
+                }
+            }
+            _ => {}
         }
     }
 
@@ -577,12 +1659,564 @@ impl Message {
             }
             UpdateOneof::Ping(_) => return Err("Ping message is not supported"),
             UpdateOneof::Pong(_) => return Err("Pong message is not supported"),
+            UpdateOneof::EndOfStartup(_) => return Err("EndOfStartup message is not supported"),
+            UpdateOneof::Lost(_) => return Err("Lost message is not supported"),
+            UpdateOneof::ReplayDone(_) => return Err("ReplayDone message is not supported"),
+            UpdateOneof::QuotaWarning(_) => return Err("QuotaWarning message is not supported"),
+            UpdateOneof::StreamInfo(_) => return Err("StreamInfo message is not supported"),
+            UpdateOneof::Maintenance(_) => return Err("Maintenance message is not supported"),
+            UpdateOneof::Rollback(_) => return Err("Rollback message is not supported"),
+            UpdateOneof::TransactionSlotDone(_) => {
+                return Err("TransactionSlotDone message is not supported")
+            }
             UpdateOneof::BlockMeta(msg) => Self::BlockMeta(Arc::new(
                 MessageBlockMeta::from_update_oneof(msg, created_at),
             )),
             UpdateOneof::Entry(msg) => {
                 Self::Entry(Arc::new(MessageEntry::from_update_oneof(&msg, created_at)?))
             }
+            UpdateOneof::BlockDiagnostic(msg) => Self::BlockDiagnostic(Arc::new(
+                MessageBlockDiagnostic::from_update_oneof(msg, created_at),
+            )),
+            UpdateOneof::Votes(msg) => {
+                Self::Votes(Arc::new(MessageVotes::from_update_oneof(msg, created_at)))
+            }
+            UpdateOneof::SlotStats(msg) => Self::SlotStats(Arc::new(
+                MessageSlotStats::from_update_oneof(msg, created_at),
+            )),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{
+            MessageAccount, MessageAccountInfo, MessageBlockMeta, MessageEntry,
+            MessageTransactionInfo,
+        },
+        crate::geyser::{AccountOrigin, SubscribeUpdateTransactionInfo},
+        agave_geyser_plugin_interface::geyser_plugin_interface::{
+            ReplicaAccountInfo, ReplicaAccountInfoV2, ReplicaAccountInfoV3,
+            ReplicaAccountInfoVersions, ReplicaBlockInfo, ReplicaBlockInfoV2, ReplicaBlockInfoV3,
+            ReplicaBlockInfoV4, ReplicaBlockInfoVersions, ReplicaEntryInfo, ReplicaEntryInfoV2,
+            ReplicaEntryInfoVersions, ReplicaTransactionInfo, ReplicaTransactionInfoV2,
+            ReplicaTransactionInfoVersions,
+        },
+        solana_sdk::{
+            bpf_loader_upgradeable,
+            hash::Hash,
+            instruction::{AccountMeta, CompiledInstruction, Instruction},
+            message::{
+                v0::{self, LoadedAddresses, MessageAddressTableLookup},
+                Message as SolMessage, MessageHeader, SimpleAddressLoader, VersionedMessage,
+            },
+            pubkey::Pubkey,
+            signer::{keypair::Keypair, Signer},
+            sysvar,
+            transaction::{
+                MessageHash, SanitizedTransaction, Transaction, TransactionError,
+                VersionedTransaction,
+            },
+            vote::{instruction as vote_instruction, state::Vote},
+        },
+        solana_transaction_status::{
+            Reward, RewardType, RewardsAndNumPartitions, TransactionStatusMeta,
+        },
+        std::collections::HashSet,
+    };
+
+    fn create_transaction() -> SanitizedTransaction {
+        let keypair = Keypair::new();
+        let message = SolMessage::new(&[], Some(&keypair.pubkey()));
+        SanitizedTransaction::from_transaction_for_tests(Transaction::new(
+            &[&keypair],
+            message,
+            Hash::new_unique(),
+        ))
+    }
+
+    #[test]
+    fn test_account_versions() {
+        let pubkey = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let data = vec![1, 2, 3];
+        let transaction = create_transaction();
+
+        let v1 = MessageAccountInfo::from_geyser_versions(&ReplicaAccountInfoVersions::V0_0_1(
+            &ReplicaAccountInfo {
+                pubkey: pubkey.as_ref(),
+                lamports: 42,
+                owner: owner.as_ref(),
+                executable: false,
+                rent_epoch: 7,
+                data: &data,
+                write_version: 3,
+            },
+        ));
+        let v2 = MessageAccountInfo::from_geyser_versions(&ReplicaAccountInfoVersions::V0_0_2(
+            &ReplicaAccountInfoV2 {
+                pubkey: pubkey.as_ref(),
+                lamports: 42,
+                owner: owner.as_ref(),
+                executable: false,
+                rent_epoch: 7,
+                data: &data,
+                write_version: 3,
+                txn_signature: Some(transaction.signature()),
+            },
+        ));
+        let v3 = MessageAccountInfo::from_geyser(&ReplicaAccountInfoV3 {
+            pubkey: pubkey.as_ref(),
+            lamports: 42,
+            owner: owner.as_ref(),
+            executable: false,
+            rent_epoch: 7,
+            data: &data,
+            write_version: 3,
+            txn: Some(&transaction),
+        });
+
+        assert_eq!(v1.txn_signature, None);
+        assert_eq!(v2, v3);
+        assert_eq!(
+            v1,
+            MessageAccountInfo {
+                txn_signature: None,
+                ..v3
+            }
+        );
+    }
+
+    #[test]
+    fn test_account_origin() {
+        let pubkey = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let transaction = create_transaction();
+        let create_info = |txn_signature| ReplicaAccountInfoV2 {
+            pubkey: pubkey.as_ref(),
+            lamports: 42,
+            owner: owner.as_ref(),
+            executable: false,
+            rent_epoch: 7,
+            data: &[],
+            write_version: 3,
+            txn_signature,
+        };
+
+        for (txn_signature, is_startup, origin) in [
+            (
+                Some(transaction.signature()),
+                false,
+                AccountOrigin::Transaction,
+            ),
+            (None, true, AccountOrigin::Startup),
+            (None, false, AccountOrigin::Unknown),
+        ] {
+            let message = MessageAccount::from_geyser_versions(
+                &ReplicaAccountInfoVersions::V0_0_2(&create_info(txn_signature)),
+                100,
+                is_startup,
+            );
+            assert_eq!(message.get_origin(), origin);
+        }
+    }
+
+    #[test]
+    fn test_transaction_versions() {
+        let transaction = create_transaction();
+        let meta = TransactionStatusMeta::default();
+
+        let v1 = MessageTransactionInfo::from_geyser_versions(
+            &ReplicaTransactionInfoVersions::V0_0_1(&ReplicaTransactionInfo {
+                signature: transaction.signature(),
+                is_vote: false,
+                transaction: &transaction,
+                transaction_status_meta: &meta,
+            }),
+        );
+        let v2 = MessageTransactionInfo::from_geyser(&ReplicaTransactionInfoV2 {
+            signature: transaction.signature(),
+            is_vote: false,
+            transaction: &transaction,
+            transaction_status_meta: &meta,
+            index: 5,
+        });
+
+        assert_eq!(v1.index, 0);
+        assert_eq!(v2.index, 5);
+        assert_eq!(v1.signature, v2.signature);
+        assert_eq!(v1.transaction, v2.transaction);
+        assert_eq!(v1.meta, v2.meta);
+        assert_eq!(v1.account_keys, v2.account_keys);
+    }
+
+    #[test]
+    fn test_transaction_size_estimate_cached() {
+        let transaction = create_transaction();
+        let meta = TransactionStatusMeta::default();
+        let info = MessageTransactionInfo::from_geyser(&ReplicaTransactionInfoV2 {
+            signature: transaction.signature(),
+            is_vote: false,
+            transaction: &transaction,
+            transaction_status_meta: &meta,
+            index: 0,
+        });
+        let estimate = info.get_size_estimate();
+        assert_eq!(info.encoded.size_estimate.get(), Some(&estimate));
+
+        // cache is not copied, changed fields are counted
+        let mut cloned = info.clone();
+        cloned.meta.log_messages.push("log".to_owned());
+        assert!(cloned.get_size_estimate() > estimate);
+        assert_eq!(info.get_size_estimate(), estimate);
+    }
+
+    fn get_resolved_accounts(info: &MessageTransactionInfo) -> Vec<(Pubkey, bool, bool)> {
+        info.get_resolved_accounts()
+            .iter()
+            .map(|account| {
+                (
+                    Pubkey::try_from(account.pubkey.as_slice()).expect("valid pubkey"),
+                    account.is_signer,
+                    account.is_writable,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_transaction_resolved_accounts_legacy() {
+        let payer = Keypair::new();
+        let writable = Pubkey::new_unique();
+        let readonly = Pubkey::new_unique();
+        let program = Pubkey::new_unique();
+        let instruction = Instruction::new_with_bytes(
+            program,
+            &[],
+            vec![
+                AccountMeta::new(writable, false),
+                AccountMeta::new_readonly(readonly, false),
+                // invoked program is demoted to readonly
+                AccountMeta::new(program, false),
+                // reserved account is demoted to readonly
+                AccountMeta::new(sysvar::clock::id(), false),
+            ],
+        );
+        let transaction = Transaction::new(
+            &[&payer],
+            SolMessage::new(&[instruction], Some(&payer.pubkey())),
+            Hash::new_unique(),
+        );
+        let sanitized = SanitizedTransaction::from_transaction_for_tests(transaction.clone());
+        let meta = TransactionStatusMeta::default();
+
+        let info = MessageTransactionInfo::from_geyser(&ReplicaTransactionInfoV2 {
+            signature: sanitized.signature(),
+            is_vote: false,
+            transaction: &sanitized,
+            transaction_status_meta: &meta,
+            index: 0,
+        });
+        let resolved = get_resolved_accounts(&info);
+        assert_eq!(
+            resolved
+                .iter()
+                .map(|(pubkey, _, _)| *pubkey)
+                .collect::<Vec<_>>(),
+            transaction.message.account_keys
+        );
+        for &(pubkey, is_signer, is_writable) in resolved.iter() {
+            assert_eq!(is_signer, pubkey == payer.pubkey());
+            assert_eq!(is_writable, pubkey == payer.pubkey() || pubkey == writable);
+        }
+
+        let info = MessageTransactionInfo::from_transaction(
+            &VersionedTransaction::from(transaction),
+            &meta,
+            false,
+            0,
+        );
+        assert_eq!(get_resolved_accounts(&info), resolved);
+    }
+
+    #[test]
+    fn test_transaction_resolved_accounts_upgradeable_loader() {
+        let payer = Keypair::new();
+        let program = Pubkey::new_unique();
+        let instruction = Instruction::new_with_bytes(
+            program,
+            &[],
+            vec![
+                AccountMeta::new(program, false),
+                AccountMeta::new_readonly(bpf_loader_upgradeable::id(), false),
+            ],
+        );
+        let transaction = Transaction::new(
+            &[&payer],
+            SolMessage::new(&[instruction], Some(&payer.pubkey())),
+            Hash::new_unique(),
+        );
+        let meta = TransactionStatusMeta::default();
+
+        // invoked program is not demoted if the upgradeable loader is present
+        let info = MessageTransactionInfo::from_transaction(
+            &VersionedTransaction::from(transaction),
+            &meta,
+            false,
+            0,
+        );
+        let resolved = get_resolved_accounts(&info);
+        assert!(resolved.contains(&(program, false, true)));
+        assert!(resolved.contains(&(bpf_loader_upgradeable::id(), false, false)));
+
+        let update = SubscribeUpdateTransactionInfo {
+            signature: info.signature.as_ref().into(),
+            transaction: Some(info.transaction.clone()),
+            meta: Some(info.meta.clone()),
+            ..Default::default()
+        };
+        let info = MessageTransactionInfo::from_update_oneof(update).expect("valid update");
+        assert_eq!(get_resolved_accounts(&info), resolved);
+    }
+
+    #[test]
+    fn test_transaction_resolved_accounts_v0() {
+        let payer = Keypair::new();
+        let program = Pubkey::new_unique();
+        let loaded = Pubkey::new_unique();
+        let message = v0::Message {
+            header: MessageHeader {
+                num_required_signatures: 1,
+                num_readonly_signed_accounts: 0,
+                num_readonly_unsigned_accounts: 1,
+            },
+            account_keys: vec![payer.pubkey(), program],
+            recent_blockhash: Hash::new_unique(),
+            instructions: vec![CompiledInstruction {
+                program_id_index: 1,
+                accounts: vec![0, 2, 3, 4],
+                data: vec![],
+            }],
+            address_table_lookups: vec![MessageAddressTableLookup {
+                account_key: Pubkey::new_unique(),
+                writable_indexes: vec![0, 1],
+                readonly_indexes: vec![2],
+            }],
+        };
+        // lookup table contains keys which are static keys of the message too
+        let loaded_addresses = LoadedAddresses {
+            writable: vec![loaded, payer.pubkey()],
+            readonly: vec![program],
+        };
+        let transaction = VersionedTransaction::try_new(VersionedMessage::V0(message), &[&payer])
+            .expect("failed to sign");
+        let sanitized = SanitizedTransaction::try_create(
+            transaction.clone(),
+            MessageHash::Compute,
+            None,
+            SimpleAddressLoader::Enabled(loaded_addresses.clone()),
+            &HashSet::new(),
+        )
+        .expect("failed to sanitize");
+        let meta = TransactionStatusMeta {
+            loaded_addresses,
+            ..TransactionStatusMeta::default()
+        };
+
+        // duplicates are kept, flags follow the account index
+        let expected = vec![
+            (payer.pubkey(), true, true),
+            (program, false, false),
+            (loaded, false, true),
+            (payer.pubkey(), false, true),
+            (program, false, false),
+        ];
+        let info = MessageTransactionInfo::from_geyser(&ReplicaTransactionInfoV2 {
+            signature: sanitized.signature(),
+            is_vote: false,
+            transaction: &sanitized,
+            transaction_status_meta: &meta,
+            index: 0,
+        });
+        assert_eq!(get_resolved_accounts(&info), expected);
+        assert_eq!(info.account_keys.len(), 3);
+
+        let info = MessageTransactionInfo::from_transaction(&transaction, &meta, false, 0);
+        assert_eq!(get_resolved_accounts(&info), expected);
+        assert_eq!(info.account_keys.len(), 3);
+    }
+
+    #[test]
+    fn test_transaction_vote() {
+        let vote_pubkey = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let instruction = vote_instruction::vote(
+            &vote_pubkey,
+            &authority,
+            Vote::new(vec![40, 41, 42], Hash::new_unique()),
+        );
+        let transaction = VersionedTransaction::from(Transaction::new_with_payer(
+            &[instruction],
+            Some(&authority),
+        ));
+        let meta = TransactionStatusMeta::default();
+
+        let info = MessageTransactionInfo::from_transaction(&transaction, &meta, true, 0);
+        assert_eq!(info.get_vote(), Some((vote_pubkey, 42)));
+
+        let info = MessageTransactionInfo::from_transaction(&transaction, &meta, false, 0);
+        assert_eq!(info.get_vote(), None);
+
+        let meta = TransactionStatusMeta {
+            status: Err(TransactionError::AccountInUse),
+            ..TransactionStatusMeta::default()
+        };
+        let info = MessageTransactionInfo::from_transaction(&transaction, &meta, true, 0);
+        assert_eq!(info.get_vote(), None);
+    }
+
+    #[test]
+    fn test_entry_versions() {
+        let hash = Hash::new_unique();
+
+        let v1 = MessageEntry::from_geyser_versions(&ReplicaEntryInfoVersions::V0_0_1(
+            &ReplicaEntryInfo {
+                slot: 10,
+                index: 2,
+                num_hashes: 12,
+                hash: hash.as_ref(),
+                executed_transaction_count: 4,
+            },
+        ));
+        let v2 = MessageEntry::from_geyser(&ReplicaEntryInfoV2 {
+            slot: 10,
+            index: 2,
+            num_hashes: 12,
+            hash: hash.as_ref(),
+            executed_transaction_count: 4,
+            starting_transaction_index: 8,
+        });
+
+        assert_eq!(v1.starting_transaction_index, 0);
+        assert_eq!(v2.starting_transaction_index, 8);
+        assert_eq!(
+            v1,
+            MessageEntry {
+                starting_transaction_index: 0,
+                created_at: v1.created_at,
+                ..v2
+            }
+        );
+    }
+
+    #[test]
+    fn test_block_meta_versions() {
+        let blockhash = Hash::new_unique().to_string();
+        let parent_blockhash = Hash::new_unique().to_string();
+        let rewards = vec![Reward {
+            pubkey: Pubkey::new_unique().to_string(),
+            lamports: 100,
+            post_balance: 1_000,
+            reward_type: Some(RewardType::Fee),
+            commission: None,
+        }];
+        let rewards_v4 = RewardsAndNumPartitions {
+            rewards: rewards.clone(),
+            num_partitions: Some(3),
+        };
+
+        let v1 = MessageBlockMeta::from_geyser_versions(&ReplicaBlockInfoVersions::V0_0_1(
+            &ReplicaBlockInfo {
+                slot: 10,
+                blockhash: &blockhash,
+                rewards: &rewards,
+                block_time: Some(1_700_000_000),
+                block_height: Some(9),
+            },
+        ));
+        let v2 = MessageBlockMeta::from_geyser_versions(&ReplicaBlockInfoVersions::V0_0_2(
+            &ReplicaBlockInfoV2 {
+                parent_slot: 9,
+                parent_blockhash: &parent_blockhash,
+                slot: 10,
+                blockhash: &blockhash,
+                rewards: &rewards,
+                block_time: Some(1_700_000_000),
+                block_height: Some(9),
+                executed_transaction_count: 20,
+            },
+        ));
+        let v3 = MessageBlockMeta::from_geyser_versions(&ReplicaBlockInfoVersions::V0_0_3(
+            &ReplicaBlockInfoV3 {
+                parent_slot: 9,
+                parent_blockhash: &parent_blockhash,
+                slot: 10,
+                blockhash: &blockhash,
+                rewards: &rewards,
+                block_time: Some(1_700_000_000),
+                block_height: Some(9),
+                executed_transaction_count: 20,
+                entry_count: 6,
+            },
+        ));
+        let v4 = MessageBlockMeta::from_geyser(&ReplicaBlockInfoV4 {
+            parent_slot: 9,
+            parent_blockhash: &parent_blockhash,
+            slot: 10,
+            blockhash: &blockhash,
+            rewards: &rewards_v4,
+            block_time: Some(1_700_000_000),
+            block_height: Some(9),
+            executed_transaction_count: 20,
+            entry_count: 6,
+        });
+
+        for meta in [&v1, &v2, &v3] {
+            assert_eq!(meta.slot, v4.slot);
+            assert_eq!(meta.blockhash, v4.blockhash);
+            assert_eq!(meta.block_time, v4.block_time);
+            assert_eq!(meta.block_height, v4.block_height);
+            assert_eq!(
+                meta.rewards.as_ref().map(|rewards| &rewards.rewards),
+                v4.rewards.as_ref().map(|rewards| &rewards.rewards)
+            );
+            assert_eq!(
+                meta.rewards
+                    .as_ref()
+                    .and_then(|rewards| rewards.num_partitions),
+                None
+            );
+        }
+        for meta in [&v2, &v3] {
+            assert_eq!(meta.parent_slot, v4.parent_slot);
+            assert_eq!(meta.parent_blockhash, v4.parent_blockhash);
+            assert_eq!(
+                meta.executed_transaction_count,
+                v4.executed_transaction_count
+            );
+        }
+        // missed fields are zero and marked as unknown
+        assert_eq!(v1.parent_slot, 0);
+        assert_eq!(v1.parent_blockhash, "");
+        assert_eq!(v1.executed_transaction_count, 0);
+        assert_eq!(v1.entries_count, 0);
+        assert!(v1.parent_slot_unknown);
+        assert!(v1.executed_transaction_count_unknown);
+        assert!(v1.entries_count_unknown);
+        assert_eq!(v2.entries_count, 0);
+        assert!(!v2.parent_slot_unknown);
+        assert!(!v2.executed_transaction_count_unknown);
+        assert!(v2.entries_count_unknown);
+        assert_eq!(v3.entries_count, 6);
+        assert!(!v3.entries_count_unknown);
+        assert_eq!(v3.entries_count, v4.entries_count);
+        assert_eq!(
+            v4.rewards
+                .as_ref()
+                .and_then(|rewards| rewards.num_partitions)
+                .map(|num_partitions| num_partitions.num_partitions),
+            Some(3)
+        );
+    }
+}
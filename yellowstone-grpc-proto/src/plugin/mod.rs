@@ -1,3 +1,5 @@
+#[cfg(feature = "plugin-compat")]
+pub mod compat;
 pub mod filter;
 pub mod message;
 
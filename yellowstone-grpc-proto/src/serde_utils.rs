@@ -0,0 +1,297 @@
+//! Custom serde encodings for generated proto types: bytes are encoded as base64
+//! strings and timestamps as RFC 3339 strings in human-readable formats (JSON),
+//! raw bytes and `(seconds, nanos)` are used otherwise (bincode).
+
+use {
+    base64::{engine::general_purpose::STANDARD as base64_engine, Engine},
+    prost_types::Timestamp,
+    serde::{de, Deserialize, Deserializer, Serialize, Serializer},
+    std::borrow::Cow,
+};
+
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
+struct Bytes<'a>(#[serde(with = "bytes", borrow)] Cow<'a, [u8]>);
+
+pub mod bytes {
+    use super::*;
+
+    pub fn serialize<S, T>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: AsRef<[u8]>,
+    {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&base64_engine.encode(value))
+        } else {
+            serializer.serialize_bytes(value.as_ref())
+        }
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: From<Vec<u8>>,
+    {
+        if deserializer.is_human_readable() {
+            let value = Cow::<str>::deserialize(deserializer)?;
+            base64_engine
+                .decode(value.as_bytes())
+                .map(Into::into)
+                .map_err(de::Error::custom)
+        } else {
+            Vec::<u8>::deserialize(deserializer).map(Into::into)
+        }
+    }
+}
+
+pub mod bytes_opt {
+    use super::*;
+
+    pub fn serialize<S>(value: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        value
+            .as_deref()
+            .map(|value| Bytes(Cow::Borrowed(value)))
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Option::<Bytes>::deserialize(deserializer).map(|value| value.map(|value| value.0.into()))
+    }
+}
+
+pub mod bytes_vec {
+    use super::*;
+
+    pub fn serialize<S>(value: &[Vec<u8>], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(value.iter().map(|value| Bytes(Cow::Borrowed(value))))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<Vec<u8>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Vec::<Bytes>::deserialize(deserializer)
+            .map(|values| values.into_iter().map(|value| value.0.into()).collect())
+    }
+}
+
+pub mod timestamp_opt {
+    use super::*;
+
+    pub fn serialize<S>(value: &Option<Timestamp>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match value {
+            Some(value) if serializer.is_human_readable() => {
+                serializer.serialize_some(&value.to_string())
+            }
+            Some(value) => serializer.serialize_some(&(value.seconds, value.nanos)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Timestamp>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            Option::<Cow<str>>::deserialize(deserializer)?
+                .map(|value| value.parse().map_err(de::Error::custom))
+                .transpose()
+        } else {
+            Ok(Option::<(i64, i32)>::deserialize(deserializer)?
+                .map(|(seconds, nanos)| Timestamp { seconds, nanos }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::prelude::{
+            subscribe_update::UpdateOneof, AccountOrigin, CompiledInstruction, Message,
+            MessageHeader, SubscribeUpdate, SubscribeUpdateAccount, SubscribeUpdateAccountInfo,
+            SubscribeUpdateRollback, SubscribeUpdateSlotStats, SubscribeUpdateSlotStatsOwner,
+            SubscribeUpdateTransaction, SubscribeUpdateTransactionInfo, SubscribeUpdateVotes,
+            SubscribeUpdateVotesEntry, Transaction,
+        },
+        prost_types::Timestamp,
+        std::time::SystemTime,
+    };
+
+    fn create_updates() -> Vec<SubscribeUpdate> {
+        vec![
+            SubscribeUpdate {
+                filters: vec!["accounts".to_owned()],
+                update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                    account: Some(SubscribeUpdateAccountInfo {
+                        pubkey: vec![1; 32],
+                        lamports: 42,
+                        owner: vec![2; 32],
+                        executable: false,
+                        rent_epoch: u64::MAX,
+                        data: (&[0, 1, 2, 3][..]).into(),
+                        write_version: 1,
+                        txn_signature: Some(vec![3; 64]),
+                        origin: Some(AccountOrigin::Transaction as i32),
+                    }),
+                    slot: 100,
+                    is_startup: false,
+                    confirmed_at_slot: Some(100),
+                    finalized_at_slot: None,
+                })),
+                created_at: Some(Timestamp::from(SystemTime::now())),
+                warnings: vec![],
+                sequence: 0,
+                dropped_since_last: 0,
+                dropped_batches_since_last: 0,
+            },
+            SubscribeUpdate {
+                filters: vec!["transactions".to_owned()],
+                update_oneof: Some(UpdateOneof::Transaction(SubscribeUpdateTransaction {
+                    transaction: Some(SubscribeUpdateTransactionInfo {
+                        signature: vec![4; 64],
+                        is_vote: false,
+                        transaction: Some(Transaction {
+                            signatures: vec![vec![4; 64]],
+                            message: Some(Message {
+                                header: Some(MessageHeader::default()),
+                                account_keys: vec![vec![5; 32], vec![6; 32]],
+                                recent_blockhash: vec![7; 32],
+                                instructions: vec![CompiledInstruction {
+                                    program_id_index: 1,
+                                    accounts: vec![0],
+                                    data: vec![],
+                                }],
+                                versioned: false,
+                                address_table_lookups: vec![],
+                            }),
+                        }),
+                        meta: None,
+                        index: 0,
+                        resolved_accounts: vec![],
+                        token_balances_none: false,
+                        rewards_none: false,
+                    }),
+                    slot: 100,
+                })),
+                created_at: None,
+                warnings: vec![],
+                sequence: 0,
+                dropped_since_last: 0,
+                dropped_batches_since_last: 0,
+            },
+            SubscribeUpdate {
+                filters: vec!["votes".to_owned()],
+                update_oneof: Some(UpdateOneof::Votes(SubscribeUpdateVotes {
+                    slot: 100,
+                    from_slot: 90,
+                    to_slot: 99,
+                    entries: vec![SubscribeUpdateVotesEntry {
+                        vote_pubkey: vec![8; 32],
+                        voted_on_slot: 98,
+                        latency_slots: 1,
+                        count: 10,
+                    }],
+                })),
+                created_at: None,
+                warnings: vec![],
+                sequence: 0,
+                dropped_since_last: 0,
+                dropped_batches_since_last: 0,
+            },
+            SubscribeUpdate {
+                filters: vec!["stats".to_owned()],
+                update_oneof: Some(UpdateOneof::SlotStats(SubscribeUpdateSlotStats {
+                    slot: 100,
+                    account_updates: 5,
+                    unique_accounts: 3,
+                    data_bytes: 1024,
+                    top_owners: vec![SubscribeUpdateSlotStatsOwner {
+                        owner: vec![9; 32],
+                        account_updates: 5,
+                        data_bytes: 1024,
+                    }],
+                })),
+                created_at: None,
+                warnings: vec![],
+                sequence: 0,
+                dropped_since_last: 0,
+                dropped_batches_since_last: 0,
+            },
+            SubscribeUpdate {
+                filters: vec!["rollback".to_owned()],
+                update_oneof: Some(UpdateOneof::Rollback(SubscribeUpdateRollback {
+                    slot: 100,
+                    finalized_slot: 98,
+                    pubkeys: vec![vec![10; 32]],
+                })),
+                created_at: None,
+                warnings: vec![],
+                sequence: 0,
+                dropped_since_last: 0,
+                dropped_batches_since_last: 0,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        for update in create_updates() {
+            let json = serde_json::to_value(&update).expect("failed to serialize");
+            if let Some(UpdateOneof::Account(msg)) = &update.update_oneof {
+                assert_eq!(
+                    json["update_oneof"]["Account"]["account"]["data"],
+                    serde_json::json!("AAECAw==")
+                );
+                assert_eq!(
+                    json["created_at"],
+                    serde_json::json!(update.created_at.unwrap().to_string())
+                );
+                assert_eq!(msg.account.as_ref().unwrap().data, vec![0, 1, 2, 3]);
+            }
+            if let Some(UpdateOneof::Votes(_)) = &update.update_oneof {
+                assert_eq!(
+                    json["update_oneof"]["Votes"]["entries"][0]["vote_pubkey"],
+                    serde_json::json!("CAgICAgICAgICAgICAgICAgICAgICAgICAgICAgICAg=")
+                );
+            }
+            if let Some(UpdateOneof::SlotStats(_)) = &update.update_oneof {
+                assert_eq!(
+                    json["update_oneof"]["SlotStats"]["top_owners"][0]["owner"],
+                    serde_json::json!("CQkJCQkJCQkJCQkJCQkJCQkJCQkJCQkJCQkJCQkJCQk=")
+                );
+            }
+            if let Some(UpdateOneof::Rollback(_)) = &update.update_oneof {
+                assert_eq!(
+                    json["update_oneof"]["Rollback"]["pubkeys"],
+                    serde_json::json!(["CgoKCgoKCgoKCgoKCgoKCgoKCgoKCgoKCgoKCgoKCgo="])
+                );
+            }
+            let decoded: SubscribeUpdate =
+                serde_json::from_value(json).expect("failed to deserialize");
+            assert_eq!(decoded, update);
+        }
+    }
+
+    #[test]
+    fn test_bincode_roundtrip() {
+        for update in create_updates() {
+            let encoded = bincode::serialize(&update).expect("failed to serialize");
+            let decoded: SubscribeUpdate =
+                bincode::deserialize(&encoded).expect("failed to deserialize");
+            assert_eq!(decoded, update);
+        }
+    }
+}